@@ -0,0 +1,322 @@
+//! Record counts of a completed IoT epoch, read from the outputs of each
+//! oracle along the way.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    compaction,
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_valid_poc::IotPoc,
+    iot_witness_report::IotWitnessIngestReport,
+    reward_manifest::RewardManifest,
+    traits::{MsgDecode, TimestampEncode},
+    FileInfo, FileStore, FileType,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::{
+    iot_reward_share::Reward as IotReward, IotRewardShare, VerificationStatus,
+};
+use prost::Message;
+use serde::Serialize;
+use std::{collections::HashSet, ops::Range, str::FromStr};
+
+/// Margin around the epoch ingest files are read from, for reports received
+/// at its edges
+const INGEST_FILE_MARGIN_MINUTES: i64 = 30;
+
+/// How long after the end of its epoch a reward manifest is looked for
+const MANIFEST_LOOKAHEAD_HOURS: i64 = 48;
+
+/// Number of files read concurrently
+const FILE_WORKERS: usize = 5;
+
+#[derive(Debug, Default, Serialize)]
+pub struct EpochCounts {
+    pub beacons_ingested: u64,
+    pub witnesses_ingested: u64,
+    pub valid_beacons: u64,
+    pub invalid_beacons: u64,
+    pub verified_witnesses: u64,
+    pub invalid_witnesses: u64,
+    pub reward_files: u64,
+    pub missing_reward_files: u64,
+    pub reward_shares: u64,
+    /// Reward shares whose period isn't the epoch of the manifest
+    pub misdated_reward_shares: u64,
+    /// Gateways rewarded a beacon amount without a valid beacon in the epoch
+    pub unbacked_beacon_rewards: u64,
+    /// Gateways rewarded a witness amount without a valid witness in the
+    /// epoch
+    pub unbacked_witness_rewards: u64,
+    pub gateways_rewarded: u64,
+    pub beacon_rewards: u64,
+    pub witness_rewards: u64,
+    pub dc_transfer_rewards: u64,
+    pub operational_rewards: u64,
+}
+
+impl EpochCounts {
+    pub fn total_rewards(&self) -> u64 {
+        self.beacon_rewards
+            + self.witness_rewards
+            + self.dc_transfer_rewards
+            + self.operational_rewards
+    }
+}
+
+/// The reward manifest of the epoch ending at `epoch_end`, or of the latest
+/// rewarded epoch without one
+pub async fn find_manifest(
+    verifier: &FileStore,
+    epoch_end: Option<DateTime<Utc>>,
+) -> Result<RewardManifest> {
+    let infos = match epoch_end {
+        Some(epoch_end) => {
+            verifier
+                .list_all(
+                    FileType::RewardManifest,
+                    epoch_end - Duration::seconds(1),
+                    epoch_end + Duration::hours(MANIFEST_LOOKAHEAD_HOURS),
+                )
+                .await?
+        }
+        None => verifier
+            .list_all(FileType::RewardManifest, None, None)
+            .await?
+            .into_iter()
+            .last()
+            .into_iter()
+            .collect(),
+    };
+    let mut latest: Option<RewardManifest> = None;
+    for info in infos {
+        let mut manifests = verifier.stream_file(info).await?;
+        while let Some(msg) = manifests.try_next().await? {
+            let manifest = RewardManifest::decode(msg)?;
+            match epoch_end {
+                Some(epoch_end) if manifest.end_timestamp == epoch_end => return Ok(manifest),
+                Some(_) => (),
+                None => latest = Some(manifest),
+            }
+        }
+    }
+    latest.ok_or_else(|| match epoch_end {
+        Some(epoch_end) => anyhow!("no reward manifest for the epoch ending at {epoch_end}"),
+        None => anyhow!("no reward manifest found"),
+    })
+}
+
+/// Count the reports and rewards of the epoch rewarded by `manifest`, from the
+/// ingest bucket, the verified reports and the reward shares it lists.
+/// Reports are attributed to the epoch by their received timestamp, so files
+/// of neighbouring epochs are read too and their reports counted once.
+/// Compacted hours are read from their compacted object
+pub async fn count(
+    ingest: &FileStore,
+    verifier: &FileStore,
+    manifest: &RewardManifest,
+    max_verification_delay: Duration,
+) -> Result<EpochCounts> {
+    let epoch = manifest.start_timestamp..manifest.end_timestamp;
+    let mut counts = EpochCounts::default();
+    count_ingest(ingest, &epoch, &mut counts).await?;
+    let participants =
+        count_verified(verifier, &epoch, max_verification_delay, &mut counts).await?;
+    count_rewards(verifier, manifest, &participants, &mut counts).await?;
+    Ok(counts)
+}
+
+async fn count_ingest(
+    ingest: &FileStore,
+    epoch: &Range<DateTime<Utc>>,
+    counts: &mut EpochCounts,
+) -> Result<()> {
+    let after = epoch.start - Duration::minutes(INGEST_FILE_MARGIN_MINUTES);
+    let before = epoch.end + Duration::minutes(INGEST_FILE_MARGIN_MINUTES);
+
+    let mut beacons = ingest.source_unordered(
+        FILE_WORKERS,
+        compaction::stream_compacted(
+            ingest,
+            FileType::IotBeaconIngestReport,
+            Some(after),
+            Some(before),
+        ),
+    );
+    while let Some(msg) = beacons.try_next().await? {
+        let beacon = IotBeaconIngestReport::decode(msg)?;
+        if epoch.contains(&beacon.received_timestamp) {
+            counts.beacons_ingested += 1;
+        }
+    }
+
+    let mut witnesses = ingest.source_unordered(
+        FILE_WORKERS,
+        compaction::stream_compacted(
+            ingest,
+            FileType::IotWitnessIngestReport,
+            Some(after),
+            Some(before),
+        ),
+    );
+    while let Some(msg) = witnesses.try_next().await? {
+        let witness = IotWitnessIngestReport::decode(msg)?;
+        if epoch.contains(&witness.received_timestamp) {
+            counts.witnesses_ingested += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Gateways with a valid beacon, and with a valid selected witness, in the
+/// epoch
+#[derive(Default)]
+struct Participants {
+    beaconers: HashSet<PublicKeyBinary>,
+    witnesses: HashSet<PublicKeyBinary>,
+}
+
+async fn count_verified(
+    verifier: &FileStore,
+    epoch: &Range<DateTime<Utc>>,
+    max_verification_delay: Duration,
+    counts: &mut EpochCounts,
+) -> Result<Participants> {
+    let after = epoch.start;
+    let before = epoch.end + max_verification_delay;
+    let mut participants = Participants::default();
+
+    let mut pocs = verifier.source_unordered(
+        FILE_WORKERS,
+        compaction::stream_compacted(verifier, FileType::IotPoc, Some(after), Some(before)),
+    );
+    while let Some(msg) = pocs.try_next().await? {
+        let poc = IotPoc::decode(msg)?;
+        if epoch.contains(&poc.beacon_report.received_timestamp) {
+            counts.valid_beacons += 1;
+            participants
+                .beaconers
+                .insert(poc.beacon_report.report.pub_key);
+        }
+        for witness in &poc.unselected_witnesses {
+            if epoch.contains(&witness.received_timestamp) {
+                counts.verified_witnesses += 1;
+            }
+        }
+        for witness in poc.selected_witnesses {
+            if epoch.contains(&witness.received_timestamp) {
+                counts.verified_witnesses += 1;
+                if witness.status == VerificationStatus::Valid {
+                    participants.witnesses.insert(witness.report.pub_key);
+                }
+            }
+        }
+    }
+
+    let mut invalid_beacons = verifier.source_unordered(
+        FILE_WORKERS,
+        compaction::stream_compacted(
+            verifier,
+            FileType::IotInvalidBeaconReport,
+            Some(after),
+            Some(before),
+        ),
+    );
+    while let Some(msg) = invalid_beacons.try_next().await? {
+        let beacon = IotInvalidBeaconReport::decode(msg)?;
+        if epoch.contains(&beacon.received_timestamp) {
+            counts.invalid_beacons += 1;
+        }
+    }
+
+    let mut invalid_witnesses = verifier.source_unordered(
+        FILE_WORKERS,
+        compaction::stream_compacted(
+            verifier,
+            FileType::IotInvalidWitnessReport,
+            Some(after),
+            Some(before),
+        ),
+    );
+    while let Some(msg) = invalid_witnesses.try_next().await? {
+        let witness = IotInvalidWitnessReport::decode(msg)?;
+        if epoch.contains(&witness.received_timestamp) {
+            counts.invalid_witnesses += 1;
+        }
+    }
+    Ok(participants)
+}
+
+async fn count_rewards(
+    verifier: &FileStore,
+    manifest: &RewardManifest,
+    participants: &Participants,
+    counts: &mut EpochCounts,
+) -> Result<()> {
+    let start_period = manifest.start_timestamp.encode_timestamp();
+    let end_period = manifest.end_timestamp.encode_timestamp();
+
+    let mut reward_files = vec![];
+    for file_name in &manifest.written_files {
+        counts.reward_files += 1;
+        match FileInfo::from_str(file_name) {
+            Ok(info) => reward_files.push(info),
+            Err(err) => {
+                tracing::error!(%file_name, ?err, "unreadable reward file name");
+                counts.missing_reward_files += 1;
+            }
+        }
+    }
+    let listed: HashSet<String> = verifier
+        .list_all(
+            FileType::IotRewardShare,
+            manifest.start_timestamp,
+            manifest.end_timestamp + Duration::hours(MANIFEST_LOOKAHEAD_HOURS),
+        )
+        .await?
+        .into_iter()
+        .map(|info| info.key)
+        .collect();
+    reward_files.retain(|info| {
+        let exists = listed.contains(&info.key);
+        if !exists {
+            tracing::error!(key = %info.key, "reward file of the manifest not found");
+            counts.missing_reward_files += 1;
+        }
+        exists
+    });
+
+    let reward_files = stream::iter(reward_files.into_iter().map(Ok)).boxed();
+    let mut reward_shares = verifier.source_unordered(FILE_WORKERS, reward_files);
+    while let Some(msg) = reward_shares.try_next().await? {
+        let share = IotRewardShare::decode(msg)?;
+        counts.reward_shares += 1;
+        if share.start_period != start_period || share.end_period != end_period {
+            counts.misdated_reward_shares += 1;
+        }
+        match share.reward {
+            Some(IotReward::GatewayReward(reward)) => {
+                let hotspot_key = PublicKeyBinary::from(reward.hotspot_key);
+                if reward.beacon_amount > 0 && !participants.beaconers.contains(&hotspot_key) {
+                    tracing::warn!(%hotspot_key, "beacon reward without a valid beacon");
+                    counts.unbacked_beacon_rewards += 1;
+                }
+                if reward.witness_amount > 0 && !participants.witnesses.contains(&hotspot_key) {
+                    tracing::warn!(%hotspot_key, "witness reward without a valid witness");
+                    counts.unbacked_witness_rewards += 1;
+                }
+                counts.gateways_rewarded += 1;
+                counts.beacon_rewards += reward.beacon_amount;
+                counts.witness_rewards += reward.witness_amount;
+                counts.dc_transfer_rewards += reward.dc_transfer_amount;
+            }
+            Some(IotReward::OperationalReward(reward)) => {
+                counts.operational_rewards += reward.amount;
+            }
+            _ => return Err(anyhow!("invalid iot reward share")),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod epoch;
+pub mod report;
+pub mod settings;
+
+pub use settings::Settings;
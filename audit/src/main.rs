@@ -0,0 +1,129 @@
+use anyhow::{bail, Result};
+use audit::{epoch, report::AuditReport, Settings};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use clap::Parser;
+use file_store::FileStore;
+use poc_metrics::preflight::Preflight;
+use std::path::PathBuf;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+#[derive(Debug, clap::Parser)]
+#[clap(version = env!("CARGO_PKG_VERSION"))]
+#[clap(about = "Helium Oracle Audit")]
+pub struct Cli {
+    /// Optional configuration file to use. If present the toml file at the
+    /// given path will be loaded. Environemnt variables can override the
+    /// settins in the given file.
+    #[clap(short = 'c')]
+    config: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        let settings = Settings::new(self.config)?;
+        self.cmd.run(settings).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    Iot(IotAudit),
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result<()> {
+        match self {
+            Self::Iot(cmd) => cmd.run(&settings).await,
+        }
+    }
+}
+
+/// Audit a completed IoT epoch, writing the signed report to the output
+/// bucket. Exits with an error when any check fails.
+#[derive(Debug, clap::Args)]
+pub struct IotAudit {
+    /// End of the epoch to audit, in UTC. Defaults to the latest rewarded
+    /// epoch
+    #[clap(long)]
+    epoch_end: Option<NaiveDateTime>,
+}
+
+impl IotAudit {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        // Check all external dependencies before reading anything
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight.check_result("keypair", settings.signing_keypair());
+        preflight
+            .check("ingest bucket", settings.ingest.check_read())
+            .await;
+        preflight
+            .check("verifier bucket", settings.verifier.check_read())
+            .await;
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        preflight.finish()?;
+
+        let keypair = settings.signing_keypair()?;
+        let ingest = FileStore::from_settings(&settings.ingest).await?;
+        let verifier = FileStore::from_settings(&settings.verifier).await?;
+        let output = FileStore::from_settings(&settings.output).await?;
+
+        let epoch_end = self
+            .epoch_end
+            .map(|epoch_end| Utc.from_utc_datetime(&epoch_end));
+        let manifest = epoch::find_manifest(&verifier, epoch_end).await?;
+        tracing::info!(
+            start = %manifest.start_timestamp,
+            end = %manifest.end_timestamp,
+            "auditing iot epoch"
+        );
+        let counts = epoch::count(
+            &ingest,
+            &verifier,
+            &manifest,
+            settings.max_verification_delay(),
+        )
+        .await?;
+        let report = AuditReport::new(
+            manifest.start_timestamp,
+            manifest.end_timestamp,
+            counts,
+            settings.max_discrepancy,
+        );
+
+        for check in report.failed_checks() {
+            tracing::error!(
+                check = check.name,
+                expected = check.expected,
+                actual = check.actual,
+                tolerance = check.tolerance,
+                explanation = check.explanation,
+                "audit check failed"
+            );
+        }
+        let passed = report.passed;
+        let failed = report.failed_checks().count();
+        let key = report.sign(&keypair)?.write(&output).await?;
+        tracing::info!(%key, passed, "audit report written");
+
+        if !passed {
+            bail!("{failed} audit checks failed, see {key}");
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    cli.run().await
+}
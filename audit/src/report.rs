@@ -0,0 +1,216 @@
+//! The audit report of an epoch: the checks cross referencing its counts,
+//! signed by the auditor and written to the output bucket as json.
+
+use crate::epoch::EpochCounts;
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use file_store::FileStore;
+use helium_crypto::{Keypair, Sign};
+use iot_verifier::reward_share::{REWARDED_SHARE, REWARDS_PER_DAY};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub expected: u64,
+    pub actual: u64,
+    /// Largest difference between `expected` and `actual` that passes
+    pub tolerance: u64,
+    pub passed: bool,
+    pub explanation: &'static str,
+}
+
+impl Check {
+    fn new(
+        name: &'static str,
+        expected: u64,
+        actual: u64,
+        tolerance: u64,
+        explanation: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            expected,
+            actual,
+            tolerance,
+            passed: expected.abs_diff(actual) <= tolerance,
+            explanation,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub epoch_start: DateTime<Utc>,
+    pub epoch_end: DateTime<Utc>,
+    pub counts: EpochCounts,
+    pub checks: Vec<Check>,
+    pub passed: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditReport {
+    /// Cross check the counts of the epoch. Checks where some drift is
+    /// expected allow `max_discrepancy` of their expected value, the others
+    /// none.
+    pub fn new(
+        epoch_start: DateTime<Utc>,
+        epoch_end: DateTime<Utc>,
+        counts: EpochCounts,
+        max_discrepancy: f64,
+    ) -> Self {
+        let ratio = |expected: u64| (expected as f64 * max_discrepancy) as u64;
+        let verified_beacons = counts.valid_beacons + counts.invalid_beacons;
+        let verified_witnesses = counts.verified_witnesses + counts.invalid_witnesses;
+        let expected_rewards = scheduled_rewards(epoch_end - epoch_start);
+        let checks = vec![
+            Check::new(
+                "beacons_verified",
+                counts.beacons_ingested,
+                verified_beacons,
+                ratio(counts.beacons_ingested),
+                "every ingested beacon is verified as valid or invalid",
+            ),
+            Check::new(
+                "witnesses_verified",
+                counts.witnesses_ingested,
+                verified_witnesses,
+                ratio(counts.witnesses_ingested),
+                "every ingested witness is verified as valid or invalid",
+            ),
+            Check::new(
+                "reward_files_present",
+                counts.reward_files,
+                counts.reward_files - counts.missing_reward_files,
+                0,
+                "every reward file of the manifest exists",
+            ),
+            Check::new(
+                "reward_periods",
+                counts.reward_shares,
+                counts.reward_shares - counts.misdated_reward_shares,
+                0,
+                "every reward share is for the epoch of the manifest",
+            ),
+            Check::new(
+                "poc_rewards_backed",
+                counts.gateways_rewarded,
+                counts.gateways_rewarded
+                    - counts
+                        .unbacked_beacon_rewards
+                        .max(counts.unbacked_witness_rewards),
+                ratio(counts.gateways_rewarded),
+                "gateways rewarded for beacons and witnesses beaconed or witnessed validly",
+            ),
+            Check::new(
+                "rewards_scheduled",
+                expected_rewards,
+                counts.total_rewards(),
+                ratio(expected_rewards),
+                "the rewards of the manifest total the scheduled emissions",
+            ),
+        ];
+        Self {
+            epoch_start,
+            epoch_end,
+            counts,
+            passed: checks.iter().all(|check| check.passed),
+            checks,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn failed_checks(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+
+    /// Key of the report in the output bucket
+    pub fn key(&self) -> String {
+        format!("audit/iot.{}.json", self.epoch_end.timestamp_millis())
+    }
+
+    pub fn sign(self, keypair: &Keypair) -> Result<SignedAuditReport> {
+        let signature = keypair.sign(&serde_json::to_vec(&self)?)?;
+        Ok(SignedAuditReport {
+            signer: keypair.public_key().to_string(),
+            signature: base64::engine::general_purpose::STANDARD.encode(signature),
+            report: self,
+        })
+    }
+}
+
+/// An audit report with the signature of the auditor over its json encoding
+#[derive(Debug, Serialize)]
+pub struct SignedAuditReport {
+    pub report: AuditReport,
+    pub signer: String,
+    pub signature: String,
+}
+
+impl SignedAuditReport {
+    pub async fn write(&self, output: &FileStore) -> Result<String> {
+        let key = self.report.key();
+        output.put_bytes(&key, serde_json::to_vec(self)?).await?;
+        Ok(key)
+    }
+}
+
+/// The emissions the verifier schedules for `duration`
+fn scheduled_rewards(duration: Duration) -> u64 {
+    let days =
+        Decimal::from(duration.num_seconds()) / Decimal::from(Duration::hours(24).num_seconds());
+    (*REWARDS_PER_DAY * *REWARDED_SHARE * days)
+        .to_u64()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The report of a day where 995 of 1000 beacons ingested and
+    /// `verified_witnesses` of 1000 witnesses were verified, with a 1%
+    /// tolerance
+    fn report(verified_witnesses: u64) -> AuditReport {
+        let epoch_end = Utc.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap();
+        let counts = EpochCounts {
+            beacons_ingested: 1000,
+            valid_beacons: 900,
+            invalid_beacons: 95,
+            witnesses_ingested: 1000,
+            verified_witnesses,
+            invalid_witnesses: 50,
+            operational_rewards: scheduled_rewards(Duration::hours(24)),
+            ..Default::default()
+        };
+        AuditReport::new(epoch_end - Duration::hours(24), epoch_end, counts, 0.01)
+    }
+
+    #[test]
+    fn drift_within_tolerance_passes() {
+        let report = report(945);
+        assert_eq!(0, report.failed_checks().count());
+        assert!(report.passed);
+    }
+
+    #[test]
+    fn drift_beyond_tolerance_fails() {
+        let report = report(900);
+        let failed: Vec<_> = report.failed_checks().map(|check| check.name).collect();
+        assert_eq!(failed, vec!["witnesses_verified"]);
+        assert!(!report.passed);
+    }
+
+    #[test]
+    fn schedules_the_rewards_of_the_verifier() {
+        // 87% of 178_082_191_780_821.917... bones a day
+        assert_eq!(154_931_506_849_315, scheduled_rewards(Duration::hours(24)));
+        assert_eq!(
+            scheduled_rewards(Duration::hours(24)) / 24,
+            scheduled_rewards(Duration::hours(1))
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::Duration;
+use helium_crypto::Keypair;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// RUST_LOG compatible settings string. Default to "audit=info"
+    #[serde(default = "default_log")]
+    pub log: String,
+    /// File from which to load the keypair signing audit reports
+    pub keypair: String,
+    /// Hours after the end of an epoch reports received within it may still
+    /// be verified, and its reward manifest written. (Default is 6)
+    #[serde(default = "default_max_verification_delay_hours")]
+    pub max_verification_delay_hours: i64,
+    /// Largest share of records of a check allowed to be unaccounted for
+    /// before it fails, for the checks where some drift is expected, such as
+    /// duplicate reports dropped by the verifier. (Default is 0.01)
+    #[serde(default = "default_max_discrepancy")]
+    pub max_discrepancy: f64,
+    /// Ingest bucket holding the beacon and witness reports received
+    pub ingest: file_store::Settings,
+    /// IoT verifier output bucket holding the verified reports, reward shares
+    /// and reward manifests
+    pub verifier: file_store::Settings,
+    /// Bucket the signed audit reports are written to
+    pub output: file_store::Settings,
+}
+
+pub fn default_log() -> String {
+    "audit=info".to_string()
+}
+
+fn default_max_verification_delay_hours() -> i64 {
+    6
+}
+
+fn default_max_discrepancy() -> f64 {
+    0.01
+}
+
+impl Settings {
+    /// Load Settings from a given path. Settings are loaded from a given
+    /// optional path and can be overriden with environment variables.
+    ///
+    /// Environemnt overrides have the same name as the entries in the settings
+    /// file in uppercase and prefixed with "AUDIT_". For example
+    /// "AUDIT_KEYPAIR" will override the keypair file.
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "AUDIT")
+    }
+
+    pub fn max_verification_delay(&self) -> Duration {
+        Duration::hours(self.max_verification_delay_hours)
+    }
+
+    pub fn signing_keypair(&self) -> anyhow::Result<Keypair> {
+        Ok(settings_loader::keypair(&self.keypair)?)
+    }
+}
@@ -0,0 +1,425 @@
+//! Caching of the responses of an upstream config service, shared by the
+//! read-only proxies of the iot and mobile config `gateway` apis.
+
+use file_store::traits::MsgVerify;
+use futures::{Future, StreamExt};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use retainer::Cache;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Code, Response, Status};
+
+const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60 * 5);
+
+/// Counts a lookup of an rpc by its result: "hit", "miss" or "stale"
+pub type LookupCounter = fn(&'static str, &'static str);
+
+#[derive(Clone)]
+struct Cached<T> {
+    response: T,
+    fetched_at: Instant,
+}
+
+/// Upstream responses by request key, kept for `max_stale` and served fresh
+/// for `ttl`. Responses are cached as signed by the upstream, once that
+/// signature verifies, and served unchanged so clients keep verifying them
+/// against the upstream key
+#[derive(Clone)]
+pub struct ResponseCache<K, T> {
+    cache: Arc<Cache<K, Cached<T>>>,
+    upstream_pubkey: Arc<PublicKey>,
+    ttl: Duration,
+    max_stale: Duration,
+    count_lookup: LookupCounter,
+}
+
+impl<K, T> ResponseCache<K, T>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+    T: MsgVerify + Clone + Send + Sync + 'static,
+{
+    pub fn new(
+        upstream_pubkey: Arc<PublicKey>,
+        ttl: Duration,
+        max_stale: Duration,
+        count_lookup: LookupCounter,
+    ) -> Self {
+        let cache = Arc::new(Cache::new());
+        let cache_clone = cache.clone();
+        tokio::spawn(async move { cache_clone.monitor(4, 0.25, CACHE_EVICTION_FREQUENCY).await });
+        Self {
+            cache,
+            upstream_pubkey,
+            ttl,
+            max_stale: max_stale.max(ttl),
+            count_lookup,
+        }
+    }
+
+    /// The cached response for `key` when fresh and the requester is
+    /// `authorized` to it, else the response of `fetch` from the upstream.
+    /// Authorized requesters are served a stale response when the upstream
+    /// fails.
+    pub async fn get_or_fetch(
+        &self,
+        rpc: &'static str,
+        key: K,
+        authorized: bool,
+        fetch: impl Future<Output = Result<Response<T>, Status>>,
+    ) -> Result<T, Status> {
+        let cached = self
+            .cache
+            .get(&key)
+            .await
+            .map(|entry| entry.value().clone());
+        if let Some(cached) = &cached {
+            if authorized && cached.fetched_at.elapsed() < self.ttl {
+                (self.count_lookup)(rpc, "hit");
+                return Ok(cached.response.clone());
+            }
+        }
+
+        match fetch.await {
+            Ok(response) => {
+                let response = response.into_inner();
+                response.verify(&self.upstream_pubkey).map_err(|_| {
+                    tracing::error!(rpc, "invalid upstream response signature");
+                    Status::internal("invalid upstream response signature")
+                })?;
+                (self.count_lookup)(rpc, "miss");
+                let fetched = Cached {
+                    response: response.clone(),
+                    fetched_at: Instant::now(),
+                };
+                self.cache.insert(key, fetched, self.max_stale).await;
+                Ok(response)
+            }
+            Err(status) if authorized && is_upstream_failure(&status) => match cached {
+                Some(cached) => {
+                    tracing::warn!(rpc, ?status, "upstream failed, serving stale response");
+                    (self.count_lookup)(rpc, "stale");
+                    Ok(cached.response)
+                }
+                None => Err(status),
+            },
+            Err(status) => Err(status),
+        }
+    }
+}
+
+/// Failures of the upstream itself rather than rejections of the request
+pub fn is_upstream_failure(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::Cancelled
+            | Code::Unknown
+            | Code::Internal
+    )
+}
+
+/// Signers of requests the upstream accepted, remembered for `ttl`. Requests
+/// which must be signed by a key the upstream authorizes can't be checked by a
+/// proxy without the database, so only these signers are served cached
+/// responses and the requests of any other signer are forwarded
+#[derive(Clone)]
+pub struct AcceptedSigners {
+    cache: Arc<Cache<PublicKeyBinary, ()>>,
+    ttl: Duration,
+}
+
+impl AcceptedSigners {
+    pub fn new(ttl: Duration) -> Self {
+        let cache = Arc::new(Cache::new());
+        let cache_clone = cache.clone();
+        tokio::spawn(async move { cache_clone.monitor(4, 0.25, CACHE_EVICTION_FREQUENCY).await });
+        Self { cache, ttl }
+    }
+
+    pub async fn contains(&self, signer: &PublicKeyBinary) -> bool {
+        self.cache.get(signer).await.is_some()
+    }
+
+    pub async fn insert(&self, signer: PublicKeyBinary) {
+        self.cache.insert(signer, (), self.ttl).await;
+    }
+}
+
+/// The key `signer` of a request, once it verifies the request signature
+pub fn verify_request_signer<R>(signer: &[u8], request: &R) -> Result<PublicKeyBinary, Status>
+where
+    R: MsgVerify,
+{
+    let signer = PublicKey::try_from(signer)
+        .map_err(|_| Status::invalid_argument(format!("invalid public key: {signer:?}")))?;
+    request
+        .verify(&signer)
+        .map_err(|_| Status::permission_denied("invalid request signature"))?;
+    Ok(signer.into())
+}
+
+/// Pass an upstream stream through uncached
+pub fn forward_stream<T>(
+    mut upstream: tonic::Streaming<T>,
+    buffer: usize,
+) -> ReceiverStream<Result<T, Status>>
+where
+    T: Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(buffer);
+    tokio::spawn(async move {
+        while let Some(response) = upstream.next().await {
+            if tx.send(response).await.is_err() {
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_crypto::{KeyTag, KeyType, Keypair, Network, Sign};
+    use helium_proto::{
+        services::iot_config::{GatewayLocationReqV1, GatewayLocationResV1},
+        Message,
+    };
+    use rand::rngs::OsRng;
+
+    fn keypair() -> Keypair {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed25519,
+        };
+        Keypair::generate(key_tag, &mut OsRng)
+    }
+
+    fn location(location: &str, upstream: &Keypair) -> GatewayLocationResV1 {
+        let mut response = GatewayLocationResV1 {
+            location: location.to_string(),
+            timestamp: 0,
+            signer: upstream.public_key().into(),
+            signature: vec![],
+        };
+        response.signature = upstream.sign(&response.encode_to_vec()).unwrap();
+        response
+    }
+
+    fn cache(
+        upstream: &Keypair,
+        ttl: Duration,
+        max_stale: Duration,
+    ) -> ResponseCache<u8, GatewayLocationResV1> {
+        ResponseCache::new(
+            Arc::new(upstream.public_key().clone()),
+            ttl,
+            max_stale,
+            |_, _| {},
+        )
+    }
+
+    async fn get(
+        cache: &ResponseCache<u8, GatewayLocationResV1>,
+        authorized: bool,
+        upstream: Result<GatewayLocationResV1, Status>,
+    ) -> Result<String, Status> {
+        cache
+            .get_or_fetch("location", 0, authorized, async move {
+                upstream.map(Response::new)
+            })
+            .await
+            .map(|response| response.location)
+    }
+
+    #[tokio::test]
+    async fn fresh_responses_are_served_from_the_cache() {
+        let upstream = keypair();
+        let cache = cache(&upstream, Duration::from_secs(60), Duration::from_secs(60));
+
+        assert_eq!(
+            "a",
+            get(&cache, true, Ok(location("a", &upstream)))
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            "a",
+            get(&cache, true, Ok(location("b", &upstream)))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn responses_are_revalidated_after_the_ttl() {
+        let upstream = keypair();
+        let cache = cache(
+            &upstream,
+            Duration::from_millis(20),
+            Duration::from_secs(60),
+        );
+
+        assert_eq!(
+            "a",
+            get(&cache, true, Ok(location("a", &upstream)))
+                .await
+                .unwrap()
+        );
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(
+            "b",
+            get(&cache, true, Ok(location("b", &upstream)))
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            "b",
+            get(&cache, true, Ok(location("c", &upstream)))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_responses_are_served_while_the_upstream_fails() {
+        let upstream = keypair();
+        let cache = cache(
+            &upstream,
+            Duration::from_millis(20),
+            Duration::from_secs(60),
+        );
+        get(&cache, true, Ok(location("a", &upstream)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        let unavailable = || Err(Status::unavailable("down"));
+        assert_eq!("a", get(&cache, true, unavailable()).await.unwrap());
+        // only to requesters the upstream authorized
+        assert_eq!(
+            Code::Unavailable,
+            get(&cache, false, unavailable()).await.unwrap_err().code()
+        );
+        // and not in place of rejections of the request
+        assert_eq!(
+            Code::PermissionDenied,
+            get(&cache, true, Err(Status::permission_denied("no")))
+                .await
+                .unwrap_err()
+                .code()
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_responses_expire() {
+        let upstream = keypair();
+        let cache = cache(
+            &upstream,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        );
+        get(&cache, true, Ok(location("a", &upstream)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        assert_eq!(
+            Code::Unavailable,
+            get(&cache, true, Err(Status::unavailable("down")))
+                .await
+                .unwrap_err()
+                .code()
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_requesters_are_forwarded() {
+        let upstream = keypair();
+        let cache = cache(&upstream, Duration::from_secs(60), Duration::from_secs(60));
+        get(&cache, true, Ok(location("a", &upstream)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            "b",
+            get(&cache, false, Ok(location("b", &upstream)))
+                .await
+                .unwrap()
+        );
+        // and refresh the cached response
+        assert_eq!(
+            "b",
+            get(&cache, true, Ok(location("c", &upstream)))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn responses_not_signed_by_the_upstream_are_rejected() {
+        let upstream = keypair();
+        let cache = cache(&upstream, Duration::from_secs(60), Duration::from_secs(60));
+
+        assert_eq!(
+            Code::Internal,
+            get(&cache, true, Ok(location("a", &keypair())))
+                .await
+                .unwrap_err()
+                .code()
+        );
+        assert_eq!(
+            Code::Unavailable,
+            get(&cache, true, Err(Status::unavailable("down")))
+                .await
+                .unwrap_err()
+                .code()
+        );
+    }
+
+    #[tokio::test]
+    async fn accepted_signers_are_remembered_for_the_ttl() {
+        let signer: PublicKeyBinary = keypair().public_key().clone().into();
+        let signers = AcceptedSigners::new(Duration::from_millis(20));
+        assert!(!signers.contains(&signer).await);
+
+        signers.insert(signer.clone()).await;
+        assert!(signers.contains(&signer).await);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(!signers.contains(&signer).await);
+    }
+
+    #[test]
+    fn request_signatures_are_verified() {
+        let signer = keypair();
+        let mut request = GatewayLocationReqV1 {
+            gateway: vec![1],
+            signer: signer.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = signer.sign(&request.encode_to_vec()).unwrap();
+        assert_eq!(
+            PublicKeyBinary::from(signer.public_key().clone()),
+            verify_request_signer(&request.signer, &request).unwrap()
+        );
+
+        let forged = GatewayLocationReqV1 {
+            gateway: vec![2],
+            ..request.clone()
+        };
+        assert_eq!(
+            Code::PermissionDenied,
+            verify_request_signer(&forged.signer, &forged)
+                .unwrap_err()
+                .code()
+        );
+        assert_eq!(
+            Code::InvalidArgument,
+            verify_request_signer(&[0], &request).unwrap_err().code()
+        );
+    }
+}
@@ -0,0 +1,769 @@
+//! Dual writes to a shadow database, for moving a service to a new database
+//! cluster with minimal downtime.
+
+use crate::{Error, Result};
+use futures::stream::TryStreamExt;
+use sqlx::{types::chrono, Executor, Pool, Postgres};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+pub const DEFAULT_BATCH_SIZE: u32 = 1000;
+/// How often the mirror replays the captured writes
+pub const MIRROR_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the mirror compares the row counts of both databases
+pub const DIVERGENCE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const PENDING_GAUGE: &str = "db_store_dual_write_pending";
+const LAG_GAUGE: &str = "db_store_dual_write_lag_seconds";
+const DIVERGENCE_GAUGE: &str = "db_store_dual_write_divergence";
+const FAILURE_COUNTER: &str = "db_store_dual_write_failures";
+
+const INSTALL: &str = r#"
+    create table if not exists dual_write_log (
+        id bigserial primary key,
+        table_name text not null,
+        op text not null,
+        row jsonb not null,
+        old_row jsonb,
+        logged_at timestamptz not null default now()
+    );
+
+    create table if not exists dual_write_state (
+        id boolean primary key default true check (id),
+        installed_at timestamptz not null default now(),
+        backfilled_at timestamptz,
+        cutover_at timestamptz
+    );
+
+    insert into dual_write_state (id) values (true) on conflict do nothing;
+
+    create or replace function dual_write_capture() returns trigger as $$
+    begin
+        if tg_op = 'DELETE' then
+            insert into dual_write_log (table_name, op, row)
+            values (tg_table_name, 'delete', to_jsonb(old));
+            return old;
+        elsif tg_op = 'UPDATE' then
+            insert into dual_write_log (table_name, op, row, old_row)
+            values (tg_table_name, 'update', to_jsonb(new), to_jsonb(old));
+        else
+            insert into dual_write_log (table_name, op, row)
+            values (tg_table_name, 'insert', to_jsonb(new));
+        end if;
+        return new;
+    end
+    $$ language plpgsql;
+"#;
+
+/// A mirrored table
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    /// Primary key columns
+    pub key: Vec<String>,
+    pub columns: Vec<String>,
+}
+
+impl Table {
+    fn quoted(&self) -> String {
+        quote(&self.name)
+    }
+
+    fn key_list(&self) -> String {
+        self.key
+            .iter()
+            .map(|column| quote(column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Upsert of the rows of the jsonb array `$1`
+    fn upsert_sql(&self) -> String {
+        let updates: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|column| !self.key.contains(column))
+            .map(|column| format!("{0} = excluded.{0}", quote(column)))
+            .collect();
+        let on_conflict = if updates.is_empty() {
+            "do nothing".to_string()
+        } else {
+            format!("do update set {}", updates.join(", "))
+        };
+        format!(
+            "insert into {table} select * from jsonb_populate_recordset(null::{table}, $1::jsonb) \
+             on conflict ({key}) {on_conflict}",
+            table = self.quoted(),
+            key = self.key_list(),
+        )
+    }
+
+    /// Delete of the row with the key of the jsonb row `$1`, unless the jsonb
+    /// row `$2` has the same key
+    fn delete_sql(&self) -> String {
+        format!(
+            "delete from {table} where ({key}) = \
+             (select {key} from jsonb_populate_record(null::{table}, $1::jsonb)) \
+             and ({key}) is distinct from \
+             (select {key} from jsonb_populate_record(null::{table}, $2::jsonb))",
+            table = self.quoted(),
+            key = self.key_list(),
+        )
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// The tables of the current schema to mirror, all but the bookkeeping of
+/// dual writes and migrations
+pub async fn tables(exec: impl sqlx::PgExecutor<'_> + Copy) -> Result<Vec<Table>> {
+    let names = sqlx::query_scalar::<_, String>(
+        r#"
+        select c.relname from pg_class c
+        join pg_namespace n on n.oid = c.relnamespace
+        where n.nspname = current_schema()
+            and c.relkind = 'r'
+            and c.relname not like 'dual\_write\_%'
+            and c.relname <> '_sqlx_migrations'
+        order by c.relname
+        "#,
+    )
+    .fetch_all(exec)
+    .await?;
+
+    let mut tables = Vec::with_capacity(names.len());
+    for name in names {
+        let columns = sqlx::query_as::<_, (String, bool)>(
+            r#"
+            select a.attname, coalesce(a.attnum = any(i.indkey), false)
+            from pg_attribute a
+            left join pg_index i on i.indrelid = a.attrelid and i.indisprimary
+            where a.attrelid = $1::regclass and a.attnum > 0 and not a.attisdropped
+            order by a.attnum
+            "#,
+        )
+        .bind(quote(&name))
+        .fetch_all(exec)
+        .await?;
+        let key: Vec<String> = columns
+            .iter()
+            .filter(|(_, in_key)| *in_key)
+            .map(|(column, _)| column.clone())
+            .collect();
+        if key.is_empty() {
+            return Err(Error::DualWrite(format!("{name} has no primary key")));
+        }
+        tables.push(Table {
+            name,
+            key,
+            columns: columns.into_iter().map(|(column, _)| column).collect(),
+        });
+    }
+    Ok(tables)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct State {
+    pub installed_at: chrono::DateTime<chrono::Utc>,
+    pub backfilled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub cutover_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl State {
+    /// Whether the mirror replays captured writes
+    pub fn is_mirroring(&self) -> bool {
+        self.backfilled_at.is_some() && self.cutover_at.is_none()
+    }
+}
+
+/// The state of the dual writes of the primary, `None` if never installed
+pub async fn state(exec: impl sqlx::PgExecutor<'_> + Copy) -> Result<Option<State>> {
+    let installed =
+        sqlx::query_scalar::<_, bool>("select to_regclass('dual_write_state') is not null")
+            .fetch_one(exec)
+            .await?;
+    if !installed {
+        return Ok(None);
+    }
+    let state = sqlx::query_as::<_, State>(
+        "select installed_at, backfilled_at, cutover_at from dual_write_state",
+    )
+    .fetch_optional(exec)
+    .await?;
+    Ok(state)
+}
+
+/// Start capturing the writes to every table of the primary, from scratch
+/// if installed before, so a migration can be rehearsed again. Triggers
+/// capture every write as the full row it left, so replaying one twice is
+/// harmless; truncates are not captured and only tables with a primary key
+/// can be mirrored. Returns the tables captured
+pub async fn install(pool: &Pool<Postgres>) -> Result<Vec<Table>> {
+    let mut txn = pool.begin().await?;
+    // several statements, run without preparing them
+    (&mut txn).execute(INSTALL).await?;
+    sqlx::query(
+        r#"
+        update dual_write_state set
+        installed_at = now(),
+        backfilled_at = null,
+        cutover_at = null
+        "#,
+    )
+    .execute(&mut txn)
+    .await?;
+    sqlx::query("delete from dual_write_log")
+        .execute(&mut txn)
+        .await?;
+    txn.commit().await?;
+    refresh_triggers(pool).await
+}
+
+/// Capture the writes to tables created since the dual writes were
+/// installed, i.e. by service migrations. Does nothing unless dual writes are
+/// installed and not cut over yet
+pub async fn refresh_triggers(pool: &Pool<Postgres>) -> Result<Vec<Table>> {
+    match state(pool).await? {
+        Some(state) if state.cutover_at.is_none() => (),
+        _ => return Ok(vec![]),
+    }
+    let tables = tables(pool).await?;
+    let mut txn = pool.begin().await?;
+    for table in &tables {
+        let table = table.quoted();
+        sqlx::query(&format!(
+            "drop trigger if exists dual_write_capture on {table}"
+        ))
+        .execute(&mut txn)
+        .await?;
+        sqlx::query(&format!(
+            "create trigger dual_write_capture after insert or update or delete on {table} \
+             for each row execute function dual_write_capture()"
+        ))
+        .execute(&mut txn)
+        .await?;
+    }
+    txn.commit().await?;
+    Ok(tables)
+}
+
+/// Copy the rows of every table of the primary to the shadow, overwriting
+/// the rows already there, and let the mirror replay the writes captured
+/// since the install. Can be interrupted and rerun. Returns the number of
+/// rows copied
+pub async fn backfill(
+    primary: &Pool<Postgres>,
+    shadow: &Pool<Postgres>,
+    batch_size: u32,
+    shutdown: &triggered::Listener,
+) -> Result<u64> {
+    match state(primary).await? {
+        Some(state) if state.cutover_at.is_none() => (),
+        Some(_) => return Err(Error::DualWrite("already cut over".to_string())),
+        None => return Err(Error::DualWrite("not installed".to_string())),
+    }
+    let mut copied = 0;
+    for table in tables(primary).await? {
+        let upsert = table.upsert_sql();
+        let select = format!("select to_jsonb(t)::text from {} t", table.quoted());
+        let mut batches = sqlx::query_scalar::<_, String>(&select)
+            .fetch(primary)
+            .try_chunks(batch_size as usize);
+        while let Some(rows) = batches.try_next().await.map_err(|err| err.1)? {
+            if shutdown.is_triggered() {
+                tracing::info!(table = table.name, "dual write backfill interrupted");
+                return Ok(copied);
+            }
+            sqlx::query(&upsert)
+                .bind(format!("[{}]", rows.join(",")))
+                .execute(shadow)
+                .await?;
+            copied += rows.len() as u64;
+        }
+        tracing::info!(table = table.name, copied, "dual write backfill progress");
+    }
+    sqlx::query("update dual_write_state set backfilled_at = now()")
+        .execute(primary)
+        .await?;
+    Ok(copied)
+}
+
+#[derive(sqlx::FromRow)]
+struct LogEntry {
+    id: i64,
+    table_name: String,
+    op: String,
+    row: String,
+    old_row: Option<String>,
+}
+
+/// Replay a batch of the captured writes on the shadow, oldest first, and
+/// forget them. Returns the number of writes replayed.
+///
+/// Entries are forgotten by id: an entry may become visible after later ones
+/// while its transaction commits, but writes to the same row are captured in
+/// the order they commit.
+pub async fn replay(
+    primary: &Pool<Postgres>,
+    shadow: &Pool<Postgres>,
+    tables: &[Table],
+    batch_size: u32,
+) -> Result<u64> {
+    let entries = sqlx::query_as::<_, LogEntry>(
+        r#"
+        select id, table_name, op, row::text as row, old_row::text as old_row
+        from dual_write_log
+        order by id
+        limit $1
+        "#,
+    )
+    .bind(batch_size as i64)
+    .fetch_all(primary)
+    .await?;
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    let mut txn = shadow.begin().await?;
+    for entry in &entries {
+        let table = tables
+            .iter()
+            .find(|table| table.name == entry.table_name)
+            .ok_or_else(|| Error::DualWrite(format!("unknown table {}", entry.table_name)))?;
+        match (entry.op.as_str(), &entry.old_row) {
+            ("delete", _) => {
+                sqlx::query(&table.delete_sql())
+                    .bind(&entry.row)
+                    .bind(None::<String>)
+                    .execute(&mut txn)
+                    .await?;
+            }
+            (op, old_row) => {
+                if let (Some(old_row), "update") = (old_row, op) {
+                    // the key of the row may have changed
+                    sqlx::query(&table.delete_sql())
+                        .bind(old_row)
+                        .bind(&entry.row)
+                        .execute(&mut txn)
+                        .await?;
+                }
+                sqlx::query(&table.upsert_sql())
+                    .bind(format!("[{}]", entry.row))
+                    .execute(&mut txn)
+                    .await?;
+            }
+        }
+    }
+    txn.commit().await?;
+
+    let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+    sqlx::query("delete from dual_write_log where id = any($1)")
+        .bind(&ids)
+        .execute(primary)
+        .await?;
+    Ok(ids.len() as u64)
+}
+
+/// Writes captured and not replayed yet, and the age of the oldest in seconds
+pub async fn pending(exec: impl sqlx::PgExecutor<'_>) -> Result<(i64, f64)> {
+    let (count, lag) = sqlx::query_as::<_, (i64, Option<f64>)>(
+        r#"
+        select count(*), extract(epoch from now() - min(logged_at))::float8
+        from dual_write_log
+        "#,
+    )
+    .fetch_one(exec)
+    .await?;
+    Ok((count, lag.unwrap_or_default()))
+}
+
+/// Row counts of every table in the primary and the shadow
+pub async fn row_counts(
+    primary: &Pool<Postgres>,
+    shadow: &Pool<Postgres>,
+    tables: &[Table],
+) -> Result<Vec<(String, i64, i64)>> {
+    let mut counts = Vec::with_capacity(tables.len());
+    for table in tables {
+        let query = format!("select count(*) from {}", table.quoted());
+        let primary_count = sqlx::query_scalar::<_, i64>(&query)
+            .fetch_one(primary)
+            .await?;
+        let shadow_count = sqlx::query_scalar::<_, i64>(&query)
+            .fetch_one(shadow)
+            .await?;
+        counts.push((table.name.clone(), primary_count, shadow_count));
+    }
+    Ok(counts)
+}
+
+/// Replays the writes captured on the primary on the shadow while the
+/// service runs
+pub struct Mirror {
+    primary: Pool<Postgres>,
+    shadow: Pool<Postgres>,
+    batch_size: u32,
+}
+
+impl Mirror {
+    pub fn new(primary: Pool<Postgres>, shadow: Pool<Postgres>) -> Self {
+        Self {
+            primary,
+            shadow,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub async fn run(self, shutdown: &triggered::Listener) -> Result {
+        tracing::info!("starting dual write mirror");
+        let mut mirror_timer = time::interval(MIRROR_INTERVAL);
+        let mut divergence_timer = time::interval_at(
+            Instant::now() + DIVERGENCE_CHECK_INTERVAL,
+            DIVERGENCE_CHECK_INTERVAL,
+        );
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = mirror_timer.tick() => {
+                    if let Err(err) = self.handle_mirror_tick(shutdown).await {
+                        metrics::increment_counter!(FAILURE_COUNTER);
+                        tracing::error!(?err, "dual write replay failed");
+                    }
+                }
+                _ = divergence_timer.tick() => {
+                    if let Err(err) = self.handle_divergence_tick().await {
+                        tracing::error!(?err, "dual write divergence check failed");
+                    }
+                }
+            }
+        }
+        tracing::info!("stopping dual write mirror");
+        Ok(())
+    }
+
+    async fn handle_mirror_tick(&self, shutdown: &triggered::Listener) -> Result {
+        if !matches!(state(&self.primary).await?, Some(state) if state.is_mirroring()) {
+            return Ok(());
+        }
+        let tables = tables(&self.primary).await?;
+        // replay full batches until caught up
+        while !shutdown.is_triggered()
+            && replay(&self.primary, &self.shadow, &tables, self.batch_size).await?
+                == self.batch_size as u64
+        {}
+        let (pending, lag) = pending(&self.primary).await?;
+        metrics::gauge!(PENDING_GAUGE, pending as f64);
+        metrics::gauge!(LAG_GAUGE, lag);
+        Ok(())
+    }
+
+    async fn handle_divergence_tick(&self) -> Result {
+        if !matches!(state(&self.primary).await?, Some(state) if state.is_mirroring()) {
+            return Ok(());
+        }
+        let tables = tables(&self.primary).await?;
+        for (table, primary, shadow) in row_counts(&self.primary, &self.shadow, &tables).await? {
+            if primary != shadow {
+                tracing::warn!(table, primary, shadow, "dual write row counts differ");
+            }
+            metrics::gauge!(
+                DIVERGENCE_GAUGE,
+                (primary - shadow).abs() as f64,
+                "table" => table
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Cut the service over to the shadow: replay the remaining captured writes,
+/// check the row counts match unless `force`d, move the sequences of the
+/// shadow up to those of the primary and stop capturing. Writers must be
+/// stopped first, their writes to the primary are not mirrored anymore.
+pub async fn cutover(
+    primary: &Pool<Postgres>,
+    shadow: &Pool<Postgres>,
+    batch_size: u32,
+    force: bool,
+) -> Result {
+    match state(primary).await? {
+        Some(state) if state.is_mirroring() => (),
+        Some(state) if state.cutover_at.is_some() => {
+            return Err(Error::DualWrite("already cut over".to_string()))
+        }
+        Some(_) => return Err(Error::DualWrite("not backfilled".to_string())),
+        None => return Err(Error::DualWrite("not installed".to_string())),
+    }
+
+    let tables = tables(primary).await?;
+    while replay(primary, shadow, &tables, batch_size).await? > 0 {}
+
+    let diverged: Vec<String> = row_counts(primary, shadow, &tables)
+        .await?
+        .into_iter()
+        .filter(|(_, primary, shadow)| primary != shadow)
+        .map(|(table, primary, shadow)| format!("{table} ({primary} rows, {shadow} in shadow)"))
+        .collect();
+    if !diverged.is_empty() && !force {
+        return Err(Error::DualWrite(format!(
+            "row counts differ: {}",
+            diverged.join(", ")
+        )));
+    }
+
+    let sequences = sqlx::query_as::<_, (String, i64)>(
+        r#"
+        select sequencename::text, last_value from pg_sequences
+        where schemaname = current_schema()
+            and last_value is not null
+            and sequencename::text not like 'dual\_write\_%'
+        "#,
+    )
+    .fetch_all(primary)
+    .await?;
+    for (sequence, last_value) in sequences {
+        sqlx::query("select setval($1::regclass, $2, true)")
+            .bind(quote(&sequence))
+            .bind(last_value)
+            .execute(shadow)
+            .await?;
+    }
+
+    let mut txn = primary.begin().await?;
+    for table in &tables {
+        sqlx::query(&format!(
+            "drop trigger if exists dual_write_capture on {}",
+            table.quoted()
+        ))
+        .execute(&mut txn)
+        .await?;
+    }
+    sqlx::query("update dual_write_state set cutover_at = now()")
+        .execute(&mut txn)
+        .await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Command line driver of a migration to the shadow database, run in the
+/// order of its commands and rehearsed until it goes smoothly. The shadow
+/// must have the schema of the primary, i.e. the service migrations applied.
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// Start capturing the writes to every table of the primary. Starts over
+    /// when run again, the shadow needs to be backfilled again
+    Install,
+    /// Copy the existing rows of the primary to the shadow. Can be
+    /// interrupted and rerun
+    Backfill {
+        #[clap(long, default_value_t = DEFAULT_BATCH_SIZE)]
+        batch_size: u32,
+    },
+    /// Show the writes waiting to be mirrored and the row counts of both
+    /// databases
+    Status,
+    /// Replay the remaining writes on the shadow and stop capturing. Stop
+    /// every writer to the primary first
+    Cutover {
+        #[clap(long, default_value_t = DEFAULT_BATCH_SIZE)]
+        batch_size: u32,
+        /// Cut over even if row counts differ
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, primary: &Pool<Postgres>, shadow: &Pool<Postgres>) -> Result {
+        match self {
+            Self::Install => {
+                for table in install(primary).await? {
+                    println!("{}: capturing writes", table.name);
+                }
+                Ok(())
+            }
+            Self::Backfill { batch_size } => {
+                let (shutdown_trigger, shutdown) = triggered::trigger();
+                tokio::spawn(async move {
+                    let _ = tokio::signal::ctrl_c().await;
+                    shutdown_trigger.trigger()
+                });
+                let copied = backfill(primary, shadow, *batch_size, &shutdown).await?;
+                println!("{copied} rows copied");
+                Ok(())
+            }
+            Self::Status => {
+                let Some(state) = state(primary).await? else {
+                    println!("not installed");
+                    return Ok(());
+                };
+                println!(
+                    "installed {}, backfilled {}, cut over {}",
+                    state.installed_at,
+                    state
+                        .backfilled_at
+                        .map_or("never".to_string(), |at| at.to_string()),
+                    state
+                        .cutover_at
+                        .map_or("never".to_string(), |at| at.to_string()),
+                );
+                let (pending, lag) = pending(primary).await?;
+                println!("{pending} writes pending, oldest {lag:.0}s ago");
+                let tables = tables(primary).await?;
+                for (table, primary, shadow) in row_counts(primary, shadow, &tables).await? {
+                    println!("{table}: {primary} rows, {shadow} in shadow");
+                }
+                Ok(())
+            }
+            Self::Cutover { batch_size, force } => {
+                cutover(primary, shadow, *batch_size, *force).await?;
+                println!("cut over, point the service at the shadow database");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+    use std::str::FromStr;
+
+    const SCHEMA: &str = r#"
+        create table gateways (
+            id bigserial primary key,
+            address text not null unique,
+            gain int not null
+        );
+    "#;
+
+    /// A shadow database next to the test database of `primary`, with the
+    /// same schema
+    async fn shadow(primary: &Pool<Postgres>) -> (Pool<Postgres>, String) {
+        let name: String = sqlx::query_scalar("select current_database() || '_shadow'")
+            .fetch_one(primary)
+            .await
+            .unwrap();
+        primary
+            .execute(format!("create database {}", quote(&name)).as_str())
+            .await
+            .unwrap();
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+        let options = PgConnectOptions::from_str(&url).unwrap().database(&name);
+        let shadow = PgPoolOptions::new().connect_with(options).await.unwrap();
+        shadow.execute(SCHEMA).await.unwrap();
+        (shadow, name)
+    }
+
+    async fn gateways(pool: &Pool<Postgres>) -> Vec<(i64, String, i32)> {
+        sqlx::query_as("select id, address, gain from gateways order by id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert(pool: &Pool<Postgres>, address: &str, gain: i32) {
+        sqlx::query("insert into gateways (address, gain) values ($1, $2)")
+            .bind(address)
+            .bind(gain)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn shadow_converges_on_the_primary(primary: Pool<Postgres>) {
+        primary.execute(SCHEMA).await.unwrap();
+        let (shadow, shadow_name) = shadow(&primary).await;
+        insert(&primary, "before-install", 1).await;
+
+        let tables = install(&primary).await.unwrap();
+        assert_eq!(
+            vec!["gateways"],
+            tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["id"], tables[0].key);
+        // captured before the backfill, replayed over the copied rows
+        insert(&primary, "before-backfill", 2).await;
+        sqlx::query("update gateways set gain = 10 where address = 'before-install'")
+            .execute(&primary)
+            .await
+            .unwrap();
+        assert!(!state(&primary).await.unwrap().unwrap().is_mirroring());
+
+        let (_trigger, shutdown) = triggered::trigger();
+        assert_eq!(2, backfill(&primary, &shadow, 1, &shutdown).await.unwrap());
+        assert!(state(&primary).await.unwrap().unwrap().is_mirroring());
+
+        insert(&primary, "after-backfill", 3).await;
+        sqlx::query("delete from gateways where address = 'before-backfill'")
+            .execute(&primary)
+            .await
+            .unwrap();
+        // a key change replaces the row of the old key
+        sqlx::query("update gateways set id = 100 where address = 'after-backfill'")
+            .execute(&primary)
+            .await
+            .unwrap();
+        assert_eq!(5, pending(&primary).await.unwrap().0);
+
+        while replay(&primary, &shadow, &tables, 2).await.unwrap() > 0 {}
+        assert_eq!(0, pending(&primary).await.unwrap().0);
+        assert_eq!(gateways(&primary).await, gateways(&shadow).await);
+        assert_eq!(
+            vec![("gateways".to_string(), 2, 2)],
+            row_counts(&primary, &shadow, &tables).await.unwrap()
+        );
+
+        insert(&primary, "before-cutover", 4).await;
+        cutover(&primary, &shadow, 10, false).await.unwrap();
+        assert_eq!(gateways(&primary).await, gateways(&shadow).await);
+        // writes aren't captured anymore, and the shadow ids continue after
+        // the primary's
+        insert(&primary, "after-cutover", 5).await;
+        assert_eq!(0, pending(&primary).await.unwrap().0);
+        insert(&shadow, "on-shadow", 6).await;
+        let id: i64 = sqlx::query_scalar("select id from gateways where address = 'on-shadow'")
+            .fetch_one(&shadow)
+            .await
+            .unwrap();
+        assert_eq!(5, id);
+        assert!(matches!(
+            cutover(&primary, &shadow, 10, false).await,
+            Err(Error::DualWrite(_))
+        ));
+
+        shadow.close().await;
+        primary
+            .execute(format!("drop database {}", quote(&shadow_name)).as_str())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn key_only_tables_are_upserted_without_updates() {
+        let table = Table {
+            name: "gateway_shares".to_string(),
+            key: vec!["hotspot_key".to_string(), "poc_id".to_string()],
+            columns: vec!["hotspot_key".to_string(), "poc_id".to_string()],
+        };
+        assert!(table
+            .upsert_sql()
+            .ends_with("on conflict (\"hotspot_key\", \"poc_id\") do nothing"));
+
+        let table = Table {
+            columns: vec![
+                "hotspot_key".to_string(),
+                "poc_id".to_string(),
+                "reward_type".to_string(),
+            ],
+            ..table
+        };
+        assert!(table
+            .upsert_sql()
+            .ends_with("do update set \"reward_type\" = excluded.\"reward_type\""));
+    }
+}
@@ -20,6 +20,14 @@ pub enum Error {
     SigningError(#[from] aws_sig_auth::signer::SigningError),
     #[error("tokio join error")]
     JoinError(#[from] tokio::task::JoinError),
+    #[error("online migration {0}")]
+    MigrationPhase(String),
+    #[error("unknown online migration {0}")]
+    UnknownMigration(String),
+    #[error("dual write {0}")]
+    DualWrite(String),
+    #[error("database schema mismatch: {0}")]
+    SchemaMismatch(String),
     #[error("invalid auth token, does not start with http")]
     InvalidAuthToken(),
 }
@@ -0,0 +1,50 @@
+//! Database timeouts injected into connection pools, for integration tests of
+//! the recovery of services from a failing database.
+
+use serde::Deserialize;
+use sqlx::postgres::PgPoolOptions;
+use std::time::Duration;
+
+const TIMEOUT_COUNTER: &str = "db_store_injected_timeout";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// Rate, from 0 to 1, at which acquiring a connection times out.
+    /// Default 0
+    #[serde(default)]
+    pub timeout_rate: f64,
+    /// Seconds to wait for a connection before acquiring it times out,
+    /// replacing the acquire timeout of the pool. Default none, keeping the
+    /// pool's own
+    #[serde(default)]
+    pub acquire_timeout: Option<u64>,
+}
+
+impl Settings {
+    /// Have acquiring connections from pools built with `options` time out at
+    /// the configured rate. Leaves `options` untouched when no faults are
+    /// configured. Idle connections handed out again are held back until the
+    /// acquisition times out with `sqlx::Error::PoolTimedOut`, as against an
+    /// overloaded database, while freshly opened ones never are
+    pub fn apply(&self, options: PgPoolOptions) -> PgPoolOptions {
+        let options = match self.acquire_timeout {
+            Some(acquire_timeout) => options.acquire_timeout(Duration::from_secs(acquire_timeout)),
+            None => options,
+        };
+        let timeout_rate = self.timeout_rate;
+        if timeout_rate <= 0.0 {
+            return options;
+        }
+        options.before_acquire(move |_conn, _meta| {
+            let stall = rand::random::<f64>() < timeout_rate;
+            Box::pin(async move {
+                if stall {
+                    metrics::increment_counter!(TIMEOUT_COUNTER);
+                    // Never resolves, the acquire timeout fails the acquisition
+                    std::future::pending::<()>().await;
+                }
+                Ok(true)
+            })
+        })
+    }
+}
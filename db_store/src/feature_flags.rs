@@ -0,0 +1,204 @@
+//! Runtime feature flags backed by the `feature_flags` table, flipped without
+//! a redeploy. Flags that have never been set are disabled.
+
+use crate::{Error, Result};
+use futures::future::BoxFuture;
+use sqlx::{Pool, Postgres};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type Flags = Arc<HashMap<String, bool>>;
+
+/// A cheaply cloneable, read only view of the current feature flags.
+#[derive(Clone, Debug)]
+pub struct FeatureFlags {
+    receiver: watch::Receiver<Flags>,
+}
+
+impl FeatureFlags {
+    /// A fixed set of flags that never changes. Mostly useful for tests and
+    /// tools that run without a database.
+    pub fn fixed<I, S>(flags: I) -> Self
+    where
+        I: IntoIterator<Item = (S, bool)>,
+        S: Into<String>,
+    {
+        let flags = flags
+            .into_iter()
+            .map(|(name, enabled)| (name.into(), enabled))
+            .collect();
+        let (_, receiver) = watch::channel(Arc::new(flags));
+        Self { receiver }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.receiver.borrow().get(name).copied().unwrap_or(false)
+    }
+
+    /// Subscribe to flag changes. The receiver is marked changed whenever a
+    /// refresh observes a different set of flags.
+    pub fn subscribe(&self) -> watch::Receiver<Flags> {
+        self.receiver.clone()
+    }
+}
+
+/// Load the current flags and start a task refreshing them every
+/// `refresh_interval` until shutdown. Services opting in need a migration
+/// creating the table:
+///
+/// ```sql
+/// create table feature_flags (
+///     name text primary key not null,
+///     enabled boolean not null default false,
+///     updated_at timestamptz not null default now()
+/// );
+/// ```
+pub async fn start(
+    pool: Pool<Postgres>,
+    refresh_interval: Duration,
+    shutdown: triggered::Listener,
+) -> Result<(FeatureFlags, BoxFuture<'static, Result>)> {
+    let flags = fetch_all(&pool).await?;
+    let (sender, receiver) = watch::channel(Arc::new(flags));
+    let join_handle =
+        tokio::spawn(async move { run(sender, pool, refresh_interval, shutdown).await });
+
+    Ok((
+        FeatureFlags { receiver },
+        Box::pin(async move {
+            match join_handle.await {
+                Ok(()) => Ok(()),
+                Err(err) => Err(Error::from(err)),
+            }
+        }),
+    ))
+}
+
+async fn run(
+    sender: watch::Sender<Flags>,
+    pool: Pool<Postgres>,
+    refresh_interval: Duration,
+    shutdown: triggered::Listener,
+) {
+    let mut trigger = tokio::time::interval(refresh_interval);
+
+    loop {
+        let shutdown = shutdown.clone();
+
+        tokio::select! {
+            _ = shutdown => {
+                tracing::info!("db_store: FeatureFlags shutting down");
+                break;
+            }
+            _ = trigger.tick() => match fetch_all(&pool).await {
+                Ok(flags) => {
+                    sender.send_if_modified(|current| {
+                        if **current == flags {
+                            return false;
+                        }
+                        tracing::info!(?flags, "feature flags changed");
+                        *current = Arc::new(flags);
+                        true
+                    });
+                }
+                Err(err) => tracing::warn!(?err, "failed to refresh feature flags"),
+            }
+        }
+    }
+}
+
+pub async fn fetch_all(exec: impl sqlx::PgExecutor<'_>) -> Result<HashMap<String, bool>> {
+    let flags = sqlx::query_as::<_, (String, bool)>("select name, enabled from feature_flags")
+        .fetch_all(exec)
+        .await?;
+    Ok(flags.into_iter().collect())
+}
+
+/// Set the named flag, returning whether it was enabled before, none when it
+/// had never been set
+pub async fn set(
+    exec: impl sqlx::PgExecutor<'_>,
+    name: &str,
+    enabled: bool,
+) -> Result<Option<bool>> {
+    let previous = sqlx::query_scalar(
+        r#"
+            with previous as (select enabled from feature_flags where name = $1)
+            insert into feature_flags(name, enabled)
+            values ($1, $2)
+            on conflict (name) do update set
+            enabled = EXCLUDED.enabled,
+            updated_at = now()
+            returning (select enabled from previous)
+            "#,
+    )
+    .bind(name)
+    .bind(enabled)
+    .fetch_one(exec)
+    .await?;
+    Ok(previous)
+}
+
+/// Command line access to the feature flags of a service database.
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List all flags that have been set
+    List,
+    /// Enable the named flag
+    Enable {
+        name: String,
+        /// Who is making the change, logged with it. Default is $USER
+        #[clap(long)]
+        by: Option<String>,
+    },
+    /// Disable the named flag
+    Disable {
+        name: String,
+        /// Who is making the change, logged with it. Default is $USER
+        #[clap(long)]
+        by: Option<String>,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &Pool<Postgres>) -> Result {
+        match self {
+            Self::List => {
+                let mut flags = fetch_all(pool).await?.into_iter().collect::<Vec<_>>();
+                flags.sort();
+                for (name, enabled) in flags {
+                    println!("{name}: {enabled}");
+                }
+                Ok(())
+            }
+            Self::Enable { name, by } => toggle(pool, name, true, by.as_deref()).await,
+            Self::Disable { name, by } => toggle(pool, name, false, by.as_deref()).await,
+        }
+    }
+}
+
+async fn toggle(pool: &Pool<Postgres>, name: &str, enabled: bool, by: Option<&str>) -> Result {
+    let by = match by {
+        Some(by) => by.to_string(),
+        None => std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+    };
+    let previous = set(pool, name, enabled).await?;
+    tracing::info!(name, enabled, ?previous, by, "feature flag set");
+    println!("{name}: {enabled}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_flags_are_disabled() {
+        let flags = FeatureFlags::fixed([("on", true), ("off", false)]);
+        assert!(flags.is_enabled("on"));
+        assert!(!flags.is_enabled("off"));
+        assert!(!flags.is_enabled("unknown"));
+    }
+}
@@ -5,9 +5,17 @@ mod metric_tracker;
 mod settings;
 
 pub use error::{Error, Result};
+pub use feature_flags::FeatureFlags;
 pub use settings::Settings;
 
+pub mod dual_write;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+pub mod feature_flags;
 pub mod meta;
+pub mod online_migration;
+pub mod preflight;
+pub mod reward_holds;
 
 /// A key-value pair that is stored in the metadata table.
 pub struct MetaValue<T> {
@@ -0,0 +1,379 @@
+//! Online migrations renaming or reshaping a table that running services
+//! read and write, moving through [`Phase`]s tracked in `online_migrations`.
+
+use crate::{Error, Result};
+use sqlx::{types::chrono, Pool, Postgres, Transaction};
+use std::{fmt, str::FromStr};
+
+pub const DEFAULT_BACKFILL_BATCH_SIZE: u32 = 1000;
+
+/// Phases of an online migration, moved on with [`advance`] and [`backfill`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    NotStarted,
+    /// The new table exists, everything still uses the old one
+    Created,
+    /// Writers write both tables while the backfill copies the old rows over
+    DualWrite,
+    /// Every old row has been copied
+    Backfilled,
+    /// Readers use the new table, writers keep writing both so processes yet
+    /// to observe the switch still read complete data
+    ReadNew,
+    /// The old table has been dropped
+    Complete,
+}
+
+impl Phase {
+    pub fn writes_old(self) -> bool {
+        self < Self::Complete
+    }
+
+    pub fn writes_new(self) -> bool {
+        self >= Self::DualWrite
+    }
+
+    pub fn reads_new(self) -> bool {
+        self >= Self::ReadNew
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::NotStarted => "not_started",
+            Self::Created => "created",
+            Self::DualWrite => "dual_write",
+            Self::Backfilled => "backfilled",
+            Self::ReadNew => "read_new",
+            Self::Complete => "complete",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Phase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "not_started" => Ok(Self::NotStarted),
+            "created" => Ok(Self::Created),
+            "dual_write" => Ok(Self::DualWrite),
+            "backfilled" => Ok(Self::Backfilled),
+            "read_new" => Ok(Self::ReadNew),
+            "complete" => Ok(Self::Complete),
+            _ => Err(Error::DecodeError),
+        }
+    }
+}
+
+/// Rows copied by a single backfill batch
+pub struct BackfillBatch {
+    /// Position after the last copied row, handed to the next batch
+    pub cursor: String,
+    pub rows: u64,
+}
+
+/// The schema specific steps of an online migration. Each step runs in the
+/// transaction recording the phase change it belongs to.
+#[async_trait::async_trait]
+pub trait OnlineMigration: Send + Sync {
+    /// Unique name the migration's phase and progress are tracked under
+    fn name(&self) -> &'static str;
+
+    /// Create the new table alongside the old one
+    async fn create_new(&self, txn: &mut Transaction<'_, Postgres>) -> Result;
+
+    /// Copy at most `batch_size` old rows following `cursor`, or from the
+    /// start when there is no cursor yet, into the new table. None once there
+    /// is nothing left to copy. Must tolerate rows already written by dual
+    /// writes, e.g. with `on conflict do nothing`.
+    async fn backfill_batch(
+        &self,
+        txn: &mut Transaction<'_, Postgres>,
+        cursor: Option<&str>,
+        batch_size: u32,
+    ) -> Result<Option<BackfillBatch>>;
+
+    /// Drop the old table once nothing reads or writes it anymore
+    async fn drop_old(&self, txn: &mut Transaction<'_, Postgres>) -> Result;
+}
+
+/// A row of the `online_migrations` table, which services opting in create
+/// with a migration:
+///
+/// ```sql
+/// create table online_migrations (
+///     name text primary key not null,
+///     phase text not null,
+///     backfill_cursor text,
+///     backfilled_rows bigint not null default 0,
+///     updated_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(sqlx::FromRow)]
+pub struct Status {
+    pub name: String,
+    pub phase: String,
+    pub backfill_cursor: Option<String>,
+    pub backfilled_rows: i64,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The phase of a migration. Writers look it up within the transaction they
+/// write in
+pub async fn phase(exec: impl sqlx::PgExecutor<'_>, name: &str) -> Result<Phase> {
+    sqlx::query_scalar::<_, String>("select phase from online_migrations where name = $1")
+        .bind(name)
+        .fetch_optional(exec)
+        .await?
+        .map_or(Ok(Phase::NotStarted), |phase| phase.parse())
+}
+
+pub async fn fetch_all(exec: impl sqlx::PgExecutor<'_>) -> Result<Vec<Status>> {
+    let statuses = sqlx::query_as::<_, Status>("select * from online_migrations order by name")
+        .fetch_all(exec)
+        .await?;
+    Ok(statuses)
+}
+
+/// Move a migration on to its next phase, creating the new table when it is
+/// started and dropping the old one when it completes. The backfill moves a
+/// migration on from `dual_write`.
+pub async fn advance(pool: &Pool<Postgres>, migration: &dyn OnlineMigration) -> Result<Phase> {
+    let name = migration.name();
+    let mut txn = pool.begin().await?;
+    let current = lock(&mut txn, name).await?.phase;
+    let next = match current {
+        Phase::NotStarted => {
+            migration.create_new(&mut txn).await?;
+            Phase::Created
+        }
+        Phase::Created => Phase::DualWrite,
+        Phase::DualWrite => {
+            return Err(Error::MigrationPhase(format!(
+                "{name} has not been backfilled"
+            )))
+        }
+        Phase::Backfilled => Phase::ReadNew,
+        Phase::ReadNew => {
+            migration.drop_old(&mut txn).await?;
+            Phase::Complete
+        }
+        Phase::Complete => return Err(Error::MigrationPhase(format!("{name} is complete"))),
+    };
+    sqlx::query(
+        r#"
+            insert into online_migrations(name, phase)
+            values ($1, $2)
+            on conflict (name) do update set
+            phase = EXCLUDED.phase,
+            updated_at = now()
+            "#,
+    )
+    .bind(name)
+    .bind(next.as_str())
+    .execute(&mut txn)
+    .await?;
+    txn.commit().await?;
+    tracing::info!(migration = name, from = %current, to = %next, "online migration advanced");
+    Ok(next)
+}
+
+/// Copy old rows of a migration in the `dual_write` phase batch by batch,
+/// recording the cursor with every batch so an interrupted backfill resumes
+/// where it left off. Moves the migration on to `backfilled` once done.
+pub async fn backfill(
+    pool: &Pool<Postgres>,
+    migration: &dyn OnlineMigration,
+    batch_size: u32,
+    shutdown: &triggered::Listener,
+) -> Result<Phase> {
+    let name = migration.name();
+    loop {
+        if shutdown.is_triggered() {
+            tracing::info!(migration = name, "online migration backfill interrupted");
+            return Ok(Phase::DualWrite);
+        }
+
+        let mut txn = pool.begin().await?;
+        let progress = lock(&mut txn, name).await?;
+        if progress.phase != Phase::DualWrite {
+            return Err(Error::MigrationPhase(format!(
+                "{name} is {}, not {}",
+                progress.phase,
+                Phase::DualWrite
+            )));
+        }
+
+        let batch = migration
+            .backfill_batch(&mut txn, progress.cursor.as_deref(), batch_size)
+            .await?;
+        let Some(batch) = batch else {
+            sqlx::query(
+                "update online_migrations set phase = $2, updated_at = now() where name = $1",
+            )
+            .bind(name)
+            .bind(Phase::Backfilled.as_str())
+            .execute(&mut txn)
+            .await?;
+            txn.commit().await?;
+            tracing::info!(
+                migration = name,
+                rows = progress.rows,
+                "online migration backfilled"
+            );
+            return Ok(Phase::Backfilled);
+        };
+
+        let rows = progress.rows + batch.rows as i64;
+        sqlx::query(
+            r#"
+                update online_migrations set
+                backfill_cursor = $2,
+                backfilled_rows = $3,
+                updated_at = now()
+                where name = $1
+                "#,
+        )
+        .bind(name)
+        .bind(&batch.cursor)
+        .bind(rows)
+        .execute(&mut txn)
+        .await?;
+        txn.commit().await?;
+
+        metrics::gauge!(
+            "db_store_online_migration_backfilled_rows",
+            rows as f64,
+            "migration" => name
+        );
+        tracing::info!(
+            migration = name,
+            rows,
+            cursor = %batch.cursor,
+            "online migration backfill progress"
+        );
+    }
+}
+
+struct Progress {
+    phase: Phase,
+    cursor: Option<String>,
+    rows: i64,
+}
+
+async fn lock(txn: &mut Transaction<'_, Postgres>, name: &str) -> Result<Progress> {
+    let row = sqlx::query_as::<_, (String, Option<String>, i64)>(
+        r#"
+            select phase, backfill_cursor, backfilled_rows
+            from online_migrations where name = $1
+            for update
+            "#,
+    )
+    .bind(name)
+    .fetch_optional(txn)
+    .await?;
+    match row {
+        Some((phase, cursor, rows)) => Ok(Progress {
+            phase: phase.parse()?,
+            cursor,
+            rows,
+        }),
+        None => Ok(Progress {
+            phase: Phase::NotStarted,
+            cursor: None,
+            rows: 0,
+        }),
+    }
+}
+
+/// Command line driver for the online migrations of a service database.
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List the phase and backfill progress of every started migration
+    List,
+    /// Move the named migration on to its next phase
+    Advance { name: String },
+    /// Copy the old rows of the named migration into its new table. Can be
+    /// interrupted and rerun, picking up where the last run left off
+    Backfill {
+        name: String,
+        #[clap(long, default_value_t = DEFAULT_BACKFILL_BATCH_SIZE)]
+        batch_size: u32,
+    },
+}
+
+impl Cmd {
+    pub async fn run(
+        &self,
+        pool: &Pool<Postgres>,
+        migrations: &[Box<dyn OnlineMigration>],
+    ) -> Result {
+        let find = |name: &str| {
+            migrations
+                .iter()
+                .find(|migration| migration.name() == name)
+                .ok_or_else(|| Error::UnknownMigration(name.to_string()))
+        };
+        match self {
+            Self::List => {
+                for status in fetch_all(pool).await? {
+                    println!(
+                        "{}: {} ({} rows backfilled, updated {})",
+                        status.name, status.phase, status.backfilled_rows, status.updated_at
+                    );
+                }
+                Ok(())
+            }
+            Self::Advance { name } => {
+                let phase = advance(pool, find(name)?.as_ref()).await?;
+                println!("{name}: {phase}");
+                Ok(())
+            }
+            Self::Backfill { name, batch_size } => {
+                let migration = find(name)?;
+                let (shutdown_trigger, shutdown) = triggered::trigger();
+                tokio::spawn(async move {
+                    let _ = tokio::signal::ctrl_c().await;
+                    shutdown_trigger.trigger()
+                });
+                let phase = backfill(pool, migration.as_ref(), *batch_size, &shutdown).await?;
+                println!("{name}: {phase}");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_switch_tables_in_order() {
+        let phases = [
+            Phase::NotStarted,
+            Phase::Created,
+            Phase::DualWrite,
+            Phase::Backfilled,
+            Phase::ReadNew,
+            Phase::Complete,
+        ];
+        for phase in phases {
+            assert_eq!(phase, phase.to_string().parse::<Phase>().unwrap());
+            // at least one table is always written and reads only move once
+            // both are
+            assert!(phase.writes_old() || phase.writes_new());
+            assert!(!phase.reads_new() || phase.writes_new());
+        }
+        assert!(!Phase::Created.writes_new());
+        assert!(Phase::Backfilled.writes_old() && !Phase::Backfilled.reads_new());
+        assert!(Phase::ReadNew.writes_old() && Phase::ReadNew.reads_new());
+        assert!(!Phase::Complete.writes_old());
+    }
+}
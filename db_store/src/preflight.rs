@@ -0,0 +1,62 @@
+//! Database checks run by the startup preflight of the binaries
+
+use crate::{Error, Result};
+use sqlx::{migrate::Migrator, Pool, Postgres, Row};
+use std::collections::HashMap;
+
+/// Check the database is reachable, for databases whose schema is managed
+/// elsewhere
+pub async fn check_connection(pool: &Pool<Postgres>) -> Result {
+    sqlx::query("select 1").execute(pool).await?;
+    Ok(())
+}
+
+/// Check the database is reachable and its schema is one `migrator` can
+/// bring up to date: every applied migration succeeded and is known to the
+/// binary with the same checksum. Pending migrations are fine, the binary
+/// applies them on startup.
+pub async fn check_schema(pool: &Pool<Postgres>, migrator: &Migrator) -> Result {
+    let has_migrations = sqlx::query_scalar::<_, bool>(
+        r#"
+        select exists(
+            select 1 from information_schema.tables where table_name = '_sqlx_migrations'
+        )
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_migrations {
+        return Ok(());
+    }
+
+    let known: HashMap<i64, &[u8]> = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| (migration.version, migration.checksum.as_ref()))
+        .collect();
+    let applied = sqlx::query("select version, success, checksum from _sqlx_migrations")
+        .fetch_all(pool)
+        .await?;
+    for row in applied {
+        let version: i64 = row.get("version");
+        if !row.get::<bool, _>("success") {
+            return Err(Error::SchemaMismatch(format!(
+                "migration {version} failed to apply"
+            )));
+        }
+        match known.get(&version) {
+            None => {
+                return Err(Error::SchemaMismatch(format!(
+                    "migration {version} is newer than this binary"
+                )))
+            }
+            Some(checksum) if *checksum != row.get::<&[u8], _>("checksum") => {
+                return Err(Error::SchemaMismatch(format!(
+                    "migration {version} was modified after it was applied"
+                )))
+            }
+            Some(_) => (),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! Admin holds on reward epochs backed by the `reward_holds` table, leaving
+//! a held epoch unrewarded until the hold is released.
+
+use crate::Result;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    Pool, Postgres,
+};
+
+/// A hold on the epoch starting at `epoch_start`, kept once released as a
+/// record. Services opting in need a migration creating the table:
+///
+/// ```sql
+/// create table reward_holds (
+///     epoch_start timestamptz primary key not null,
+///     reason text not null,
+///     held_at timestamptz not null default now(),
+///     released_at timestamptz
+/// );
+/// ```
+#[derive(sqlx::FromRow)]
+pub struct RewardHold {
+    pub epoch_start: DateTime<Utc>,
+    pub reason: String,
+    pub held_at: DateTime<Utc>,
+    pub released_at: Option<DateTime<Utc>>,
+}
+
+/// Whether the epoch starting at `epoch_start` is held. A rewarder leaves a
+/// held epoch unrewarded without clearing anything, so its shares keep
+/// accumulating and are rewarded from their state at release
+pub async fn is_held(exec: impl sqlx::PgExecutor<'_>, epoch_start: DateTime<Utc>) -> Result<bool> {
+    let held = sqlx::query_scalar::<_, bool>(
+        r#"
+            select exists(
+                select 1 from reward_holds
+                where epoch_start = $1 and released_at is null
+            )
+            "#,
+    )
+    .bind(epoch_start)
+    .fetch_one(exec)
+    .await?;
+    Ok(held)
+}
+
+pub async fn fetch_all(exec: impl sqlx::PgExecutor<'_>) -> Result<Vec<RewardHold>> {
+    let holds = sqlx::query_as::<_, RewardHold>("select * from reward_holds order by epoch_start")
+        .fetch_all(exec)
+        .await?;
+    Ok(holds)
+}
+
+/// Hold the epoch starting at `epoch_start`, placing the hold again if it
+/// was released
+pub async fn hold(
+    exec: impl sqlx::PgExecutor<'_>,
+    epoch_start: DateTime<Utc>,
+    reason: &str,
+) -> Result {
+    sqlx::query(
+        r#"
+            insert into reward_holds(epoch_start, reason)
+            values ($1, $2)
+            on conflict (epoch_start) do update set
+            reason = EXCLUDED.reason,
+            held_at = now(),
+            released_at = null
+            "#,
+    )
+    .bind(epoch_start)
+    .bind(reason)
+    .execute(exec)
+    .await?;
+    Ok(())
+}
+
+/// Release the hold on the epoch starting at `epoch_start`. Returns false if
+/// the epoch was not held.
+pub async fn release(exec: impl sqlx::PgExecutor<'_>, epoch_start: DateTime<Utc>) -> Result<bool> {
+    let released = sqlx::query(
+        r#"
+            update reward_holds set released_at = now()
+            where epoch_start = $1 and released_at is null
+            "#,
+    )
+    .bind(epoch_start)
+    .execute(exec)
+    .await?
+    .rows_affected();
+    Ok(released > 0)
+}
+
+/// Command line access to the reward holds of a service database. Epochs are
+/// given by the start of their reward period, ie `2023-05-01T00:00:00Z`.
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List all holds, released or not
+    List,
+    /// Hold the rewards of an epoch
+    Hold {
+        epoch_start: DateTime<Utc>,
+        /// Why the epoch is held, kept with the hold
+        #[clap(long)]
+        reason: String,
+    },
+    /// Release the hold on an epoch, letting it be rewarded
+    Release { epoch_start: DateTime<Utc> },
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &Pool<Postgres>) -> Result {
+        match self {
+            Self::List => {
+                for hold in fetch_all(pool).await? {
+                    let status = match hold.released_at {
+                        Some(released_at) => format!("released {released_at}"),
+                        None => "held".to_string(),
+                    };
+                    println!(
+                        "{}: {status} (held {}: {})",
+                        hold.epoch_start, hold.held_at, hold.reason
+                    );
+                }
+                Ok(())
+            }
+            Self::Hold {
+                epoch_start,
+                reason,
+            } => {
+                hold(pool, *epoch_start, reason).await?;
+                println!("{epoch_start}: held");
+                Ok(())
+            }
+            Self::Release { epoch_start } => {
+                if release(pool, *epoch_start).await? {
+                    println!("{epoch_start}: released");
+                } else {
+                    println!("{epoch_start}: not held");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::chrono::TimeZone;
+
+    async fn create_table(pool: &Pool<Postgres>) {
+        sqlx::query(
+            r#"
+            create table reward_holds (
+                epoch_start timestamptz primary key not null,
+                reason text not null,
+                held_at timestamptz not null default now(),
+                released_at timestamptz
+            )
+            "#,
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn holds_until_released(pool: Pool<Postgres>) -> Result {
+        create_table(&pool).await;
+        let epoch = Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap();
+        let next_epoch = Utc.with_ymd_and_hms(2023, 5, 2, 0, 0, 0).unwrap();
+
+        assert!(!is_held(&pool, epoch).await?);
+        assert!(!release(&pool, epoch).await?);
+
+        hold(&pool, epoch, "investigating").await?;
+        assert!(is_held(&pool, epoch).await?);
+        assert!(!is_held(&pool, next_epoch).await?);
+
+        assert!(release(&pool, epoch).await?);
+        assert!(!is_held(&pool, epoch).await?);
+        assert!(!release(&pool, epoch).await?);
+
+        // released holds are kept as a record, and can be placed again
+        hold(&pool, epoch, "again").await?;
+        assert!(is_held(&pool, epoch).await?);
+        let holds = fetch_all(&pool).await?;
+        assert_eq!(1, holds.len());
+        assert_eq!("again", holds[0].reason);
+        assert_eq!(None, holds[0].released_at);
+        Ok(())
+    }
+}
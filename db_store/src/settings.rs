@@ -30,6 +30,11 @@ pub struct Settings {
     pub iam_role_session_name: Option<String>,
     pub iam_duration_seconds: Option<i32>,
     pub iam_region: Option<String>,
+
+    /// Database timeouts injected for testing. Default none
+    #[cfg(feature = "fault-injection")]
+    #[serde(default)]
+    pub faults: crate::fault_injection::Settings,
 }
 
 fn default_auth_type() -> AuthType {
@@ -83,7 +88,11 @@ impl Settings {
         Ok(pool)
     }
 
+    #[allow(clippy::let_and_return)]
     pub fn pool_options(&self) -> PgPoolOptions {
-        PgPoolOptions::new().max_connections(self.max_connections)
+        let options = PgPoolOptions::new().max_connections(self.max_connections);
+        #[cfg(feature = "fault-injection")]
+        let options = self.faults.apply(options);
+        options
     }
 }
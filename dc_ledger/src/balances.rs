@@ -0,0 +1,268 @@
+use crate::{
+    payer_ledger,
+    pending_burns::{Burn, PendingBurns},
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use helium_crypto::PublicKeyBinary;
+use solana::SolanaNetwork;
+use sqlx::{Pool, Postgres};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const BELOW_MINIMUM_COUNTER: &str = "dc_ledger_debit_below_minimum";
+
+#[async_trait]
+pub trait Debiter {
+    type Error;
+
+    /// Debit the balance from the account. If the debit was successful,
+    /// return the remaining amount. A debit leaving less than
+    /// `minimum_allowed_balance` disables the org, so it must not be based
+    /// on a stale balance.
+    async fn debit_if_sufficient(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+        minimum_allowed_balance: u64,
+    ) -> Result<Option<u64>, Self::Error>;
+}
+
+#[async_trait]
+impl Debiter for Arc<Mutex<HashMap<PublicKeyBinary, u64>>> {
+    type Error = Infallible;
+
+    async fn debit_if_sufficient(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+        _minimum_allowed_balance: u64,
+    ) -> Result<Option<u64>, Infallible> {
+        let map = self.lock().await;
+        let balance = map.get(payer).unwrap();
+        // Don't debit the amount if we're mocking. That is a job for the burner.
+        Ok((*balance >= amount).then(|| balance.saturating_sub(amount)))
+    }
+}
+
+/// Caches balances fetched from the solana chain and debits made by a
+/// packet verifier.
+pub struct BalanceCache<S> {
+    balances: BalanceStore,
+    solana: S,
+    ledger: Option<Pool<Postgres>>,
+    refetch_interval: Duration,
+}
+
+pub type BalanceStore = Arc<Mutex<HashMap<PublicKeyBinary, Balance>>>;
+
+impl<S> BalanceCache<S>
+where
+    S: SolanaNetwork,
+{
+    /// Fetch all of the current balances that have been actively burned so that
+    /// we have an accurate cache. Pending burns of a payer for every token use
+    /// count against its single balance.
+    pub async fn new<P>(pending_burns: &mut P, solana: S) -> anyhow::Result<Self>
+    where
+        P: PendingBurns,
+    {
+        let mut balances = HashMap::<PublicKeyBinary, Balance>::new();
+        let mut burns = pending_burns.fetch_all().await;
+
+        while let Some(Burn {
+            payer,
+            amount: burn_amount,
+            ..
+        }) = burns.next().await.transpose()?
+        {
+            if let Some(balance) = balances.get_mut(&payer) {
+                balance.burned += burn_amount as u64;
+                continue;
+            }
+            // Look up the current balance of the payer
+            let balance = solana.payer_balance(&payer).await?;
+            balances.insert(
+                payer,
+                Balance {
+                    burned: burn_amount as u64,
+                    balance,
+                    subscribed: false,
+                    last_debit: None,
+                    last_fetch: Some(Instant::now()),
+                },
+            );
+        }
+
+        Ok(Self {
+            balances: Arc::new(Mutex::new(balances)),
+            solana,
+            ledger: None,
+            refetch_interval: Duration::ZERO,
+        })
+    }
+
+    /// Record every balance fetched from the solana chain from now on in the
+    /// payer ledger
+    pub fn with_ledger(mut self, pool: Pool<Postgres>) -> Self {
+        self.ledger = Some(pool);
+        self
+    }
+
+    /// Fetch the balance of a payer running low at most once per `interval`,
+    /// rather than on every debit of a payer kept below its minimum allowed
+    /// balance. Balances reset by a burn are always fetched.
+    pub fn with_refetch_interval(mut self, interval: Duration) -> Self {
+        self.refetch_interval = interval;
+        self
+    }
+
+    async fn fetch_balance(&self, payer: &PublicKeyBinary) -> Result<u64, S::Error> {
+        let balance = self.solana.payer_balance(payer).await?;
+        if let Some(ref pool) = self.ledger {
+            if let Err(err) = payer_ledger::record_balance_refresh(pool, payer, balance).await {
+                tracing::warn!(%payer, ?err, "failed to record balance refresh");
+            }
+        }
+        Ok(balance)
+    }
+}
+
+impl<S> BalanceCache<S> {
+    pub fn balances(&self) -> BalanceStore {
+        self.balances.clone()
+    }
+
+    /// Set the burned amount of every payer to its pending burns over every
+    /// token use, counting the debits committed by the other packet verifier
+    /// and the burns it settled. The cached balance of a payer whose pending
+    /// burns dropped predates a burn, it's reset so the next debit fetches
+    /// it. Called holding the ledger lock, see
+    /// [`pending_burns::lock`](crate::pending_burns::lock).
+    pub async fn sync<P>(&self, mut pending_burns: P) -> Result<(), P::Error>
+    where
+        P: PendingBurns,
+    {
+        let mut pending = HashMap::<PublicKeyBinary, u64>::new();
+        {
+            let mut burns = pending_burns.fetch_all().await;
+            while let Some(Burn { payer, amount, .. }) = burns.next().await.transpose()? {
+                *pending.entry(payer).or_default() += amount as u64;
+            }
+        }
+
+        let mut balances = self.balances.lock().await;
+        for (payer, balance) in balances.iter_mut() {
+            let burned = pending.remove(payer).unwrap_or_default();
+            if burned < balance.burned {
+                balance.balance = 0;
+                balance.subscribed = false;
+                balance.last_fetch = None;
+            }
+            balance.burned = burned;
+        }
+        // Payers only debited by the other verifier so far, fetched on debit:
+        for (payer, burned) in pending {
+            balances.insert(
+                payer,
+                Balance {
+                    balance: 0,
+                    burned,
+                    subscribed: false,
+                    last_debit: None,
+                    last_fetch: None,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S> Debiter for BalanceCache<S>
+where
+    S: SolanaNetwork,
+{
+    type Error = S::Error;
+
+    /// Debits the balance from the cache, returning the remaining balance as an
+    /// option if there was enough and none otherwise. Debits leaving less than
+    /// `minimum_allowed_balance` are counted per payer.
+    async fn debit_if_sufficient(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+        minimum_allowed_balance: u64,
+    ) -> Result<Option<u64>, S::Error> {
+        let mut balances = self.balances.lock().await;
+
+        let balance = if !balances.contains_key(payer) {
+            let new_balance = self.fetch_balance(payer).await?;
+            balances.insert(payer.clone(), Balance::new(new_balance));
+            balances.get_mut(payer).unwrap()
+        } else {
+            let balance = balances.get_mut(payer).unwrap();
+
+            // If the balance is not sufficient, or would drop below the minimum
+            // allowed, check to see if it has been increased, unless its
+            // subscription would have pushed the increase or it was checked
+            // within the refetch interval
+            if balance.balance < amount + balance.burned + minimum_allowed_balance
+                && !balance.subscribed
+                && balance
+                    .last_fetch
+                    .map_or(true, |fetched| fetched.elapsed() >= self.refetch_interval)
+            {
+                balance.balance = self.fetch_balance(payer).await?;
+                balance.last_fetch = Some(Instant::now());
+            }
+
+            balance
+        };
+
+        Ok(if balance.balance >= amount + balance.burned {
+            balance.burned += amount;
+            balance.last_debit = Some(Instant::now());
+            let remaining_balance = balance.balance - balance.burned;
+            if remaining_balance < minimum_allowed_balance {
+                metrics::increment_counter!(BELOW_MINIMUM_COUNTER, "payer" => payer.to_string());
+            }
+            Some(remaining_balance)
+        } else {
+            None
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Balance {
+    pub balance: u64,
+    pub burned: u64,
+    /// The balance was pushed by an account subscription since the last burn
+    /// of the payer, and is kept current by it
+    pub subscribed: bool,
+    /// When the payer was last debited by this verifier, payers debited
+    /// recently are subscribed to
+    pub last_debit: Option<Instant>,
+    /// When the balance was last fetched from the chain, none once reset by
+    /// a burn
+    pub last_fetch: Option<Instant>,
+}
+
+impl Balance {
+    /// Balance just fetched from the chain
+    pub fn new(balance: u64) -> Self {
+        Self {
+            balance,
+            burned: 0,
+            subscribed: false,
+            last_debit: None,
+            last_fetch: Some(Instant::now()),
+        }
+    }
+}
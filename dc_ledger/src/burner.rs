@@ -0,0 +1,248 @@
+use crate::{
+    balances::{BalanceCache, BalanceStore},
+    pending_burns::{Burn, PendingBurns, TokenUse},
+};
+use helium_crypto::PublicKeyBinary;
+use solana::{BurnStatus, BurnTransaction, SolanaNetwork};
+use std::time::Duration;
+use tokio::task;
+
+/// Time between checks of a burn transaction for its finalization
+const CONFIRMATION_POLL: Duration = Duration::from_secs(5);
+
+/// Burns the pending burns of one token use
+pub struct Burner<P, S> {
+    pending_burns: P,
+    balances: BalanceStore,
+    token_use: TokenUse,
+    burn_period: Duration,
+    solana: S,
+    dry_run: bool,
+}
+
+/// A pending burn settled by a burn finalized on chain, or by a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settlement {
+    pub payer: PublicKeyBinary,
+    pub token_use: TokenUse,
+    pub amount: u64,
+    /// Amount of the pending burn still left to burn
+    pub remaining: u64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BurnError<P, S> {
+    #[error("Join error: {0}")]
+    JoinError(#[from] tokio::task::JoinError),
+    #[error("Sql error: {0}")]
+    SqlError(P),
+    #[error("Solana error: {0}")]
+    SolanaError(S),
+    #[error("Burn transaction failed: {0}")]
+    TransactionFailed(String),
+}
+
+impl<P, S> Burner<P, S> {
+    pub fn new(
+        pending_burns: P,
+        balances: &BalanceCache<S>,
+        token_use: TokenUse,
+        burn_period: u64,
+        solana: S,
+    ) -> Self {
+        Self {
+            pending_burns,
+            balances: balances.balances(),
+            token_use,
+            burn_period: Duration::from_secs(60 * burn_period),
+            solana,
+            dry_run: false,
+        }
+    }
+
+    /// Log burns instead of sending them, settling pending burns as if they
+    /// were finalized on chain
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl<P, S> Burner<P, S>
+where
+    P: PendingBurns + Send + Sync + 'static,
+    S: SolanaNetwork,
+{
+    pub async fn run(
+        mut self,
+        shutdown: &triggered::Listener,
+    ) -> Result<(), BurnError<P::Error, S::Error>> {
+        let burn_service = task::spawn(async move {
+            loop {
+                if let Err(e) = self.burn().await {
+                    tracing::error!("Failed to burn: {e:?}");
+                }
+                tokio::time::sleep(self.burn_period).await;
+            }
+        });
+
+        tokio::select! {
+            _ = shutdown.clone() => Ok(()),
+            service_result = burn_service => service_result?,
+        }
+    }
+
+    /// Burn the next pending burn due, returning its settlement, if any
+    pub async fn burn(&mut self) -> Result<Option<Settlement>, BurnError<P::Error, S::Error>> {
+        // Create burn transaction and execute it:
+
+        let Some(Burn {
+            payer,
+            token_use,
+            amount,
+        }) = self
+            .pending_burns
+            .fetch_next(self.token_use, self.token_use.burn_threshold())
+            .await
+            .map_err(BurnError::SqlError)?
+        else {
+            return Ok(None);
+        };
+
+        // A burn transaction of a previous attempt is tracked to finalization
+        // rather than burning again:
+        let mut transaction = match self
+            .pending_burns
+            .fetch_burn_transaction(&payer, token_use)
+            .await
+            .map_err(BurnError::SqlError)?
+        {
+            Some(transaction) => {
+                tracing::info!(%payer, signature = %transaction.signature, "Tracking burn");
+                transaction
+            }
+            None if self.dry_run => {
+                tracing::info!(
+                    %amount,
+                    %payer,
+                    token_use = token_use.as_str(),
+                    "Dry run, not burning DC"
+                );
+                return self.settle(&payer, token_use, amount as u64).await;
+            }
+            None => {
+                tracing::info!(%amount, %payer, token_use = token_use.as_str(), "Burning DC");
+                self.submit(&payer, token_use, amount as u64).await?
+            }
+        };
+
+        loop {
+            match self
+                .solana
+                .burn_status(&transaction)
+                .await
+                .map_err(BurnError::SolanaError)?
+            {
+                BurnStatus::Finalized => break,
+                BurnStatus::Pending => tokio::time::sleep(CONFIRMATION_POLL).await,
+                BurnStatus::Expired if self.dry_run => {
+                    // Sent before the dry run, its amount is settled next
+                    tracing::info!(
+                        %payer,
+                        signature = %transaction.signature,
+                        "Burn transaction expired, not retrying in dry run"
+                    );
+                    self.pending_burns
+                        .record_burn_transaction(&payer, token_use, None)
+                        .await
+                        .map_err(BurnError::SqlError)?;
+                    return Ok(None);
+                }
+                BurnStatus::Expired => {
+                    tracing::warn!(
+                        %payer,
+                        signature = %transaction.signature,
+                        "Burn transaction expired, retrying"
+                    );
+                    transaction = self.submit(&payer, token_use, transaction.amount).await?;
+                }
+                BurnStatus::Failed => {
+                    // Burned nothing, the pending amount is burned again later
+                    self.pending_burns
+                        .record_burn_transaction(&payer, token_use, None)
+                        .await
+                        .map_err(BurnError::SqlError)?;
+                    return Err(BurnError::TransactionFailed(transaction.signature));
+                }
+            }
+        }
+
+        self.settle(&payer, token_use, transaction.amount).await
+    }
+
+    /// Remove a finalized burn of `amount` DC from the balance cache and the
+    /// pending burns
+    async fn settle(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<Option<Settlement>, BurnError<P::Error, S::Error>> {
+        // Now that the burn is finalized and we are no long in sync land, we
+        // can remove the amount burned. The cache goes first: a sync in
+        // between counts the burn as still pending, never the other way.
+        if let Some(balance) = self.balances.lock().await.get_mut(payer) {
+            balance.burned = balance.burned.saturating_sub(amount);
+            // Zero the balance in order to force a reset, until the
+            // subscription pushes the balance left after the burn:
+            balance.balance = 0;
+            balance.subscribed = false;
+            balance.last_fetch = None;
+        }
+
+        let remaining = self
+            .pending_burns
+            .subtract_burned_amount(payer, token_use, amount)
+            .await
+            .map_err(BurnError::SqlError)?;
+
+        metrics::counter!(
+            "burned",
+            amount,
+            "payer" => payer.to_string(),
+            "token_use" => token_use.as_str()
+        );
+
+        Ok(Some(Settlement {
+            payer: payer.clone(),
+            token_use,
+            amount,
+            remaining,
+        }))
+    }
+
+    /// Sign a burn of `amount` DC and send it, recording its transaction in
+    /// between so a burn landing is never lost track of. Unsigned burns, of
+    /// shadow mode, record no transaction.
+    async fn submit(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<BurnTransaction, BurnError<P::Error, S::Error>> {
+        let signed = self
+            .solana
+            .sign_burn(payer, amount)
+            .await
+            .map_err(BurnError::SolanaError)?;
+        self.pending_burns
+            .record_burn_transaction(payer, token_use, signed.is_signed().then_some(&signed.burn))
+            .await
+            .map_err(BurnError::SqlError)?;
+        self.solana
+            .send_burn(&signed)
+            .await
+            .map_err(BurnError::SolanaError)?;
+        Ok(signed.burn)
+    }
+}
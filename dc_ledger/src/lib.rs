@@ -0,0 +1,7 @@
+//! Accounting of the DC the iot and mobile packet verifiers debit from
+//! payers, kept in the database of the iot packet verifier.
+
+pub mod balances;
+pub mod burner;
+pub mod payer_ledger;
+pub mod pending_burns;
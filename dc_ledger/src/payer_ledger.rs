@@ -0,0 +1,22 @@
+//! Entries of the `payer_ledger` table recorded by the shared ledger.
+
+use helium_crypto::PublicKeyBinary;
+
+/// Record the balance of `payer` fetched from the solana chain
+pub async fn record_balance_refresh(
+    exec: impl sqlx::PgExecutor<'_>,
+    payer: &PublicKeyBinary,
+    balance: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO payer_ledger (payer, kind, amount, balance)
+        VALUES ($1, 'balance_refresh', 0, $2)
+        "#,
+    )
+    .bind(payer)
+    .bind(balance as i64)
+    .execute(exec)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,532 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use solana::BurnTransaction;
+use sqlx::{FromRow, Pool, Postgres, Transaction};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    pin::Pin,
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+#[async_trait]
+pub trait PendingBurns {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    async fn fetch_all<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Burn, Self::Error>> + Send + 'a>>;
+
+    /// Next pending burn of `token_use` to burn, one with a burn transaction
+    /// in flight or of at least `threshold` DC
+    async fn fetch_next(
+        &mut self,
+        token_use: TokenUse,
+        threshold: u64,
+    ) -> Result<Option<Burn>, Self::Error>;
+
+    /// Subtract a burn finalized on chain from the pending amount, recording
+    /// it as a credit in the payer ledger and forgetting its transaction.
+    /// Returns the amount still pending.
+    async fn subtract_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<u64, Self::Error>;
+
+    async fn add_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<(), Self::Error>;
+
+    /// Burn transaction of a pending burn not finalized yet, if any
+    async fn fetch_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+    ) -> Result<Option<BurnTransaction>, Self::Error>;
+
+    /// Record the burn transaction signed for a pending burn, or forget it
+    /// with None
+    async fn record_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        transaction: Option<&BurnTransaction>,
+    ) -> Result<(), Self::Error>;
+}
+
+/// What the DC of a pending burn were debited for. Pending burns are kept
+/// per payer and token use so iot packets and mobile data sessions debiting
+/// the same payer are burned and accounted for separately, while sharing
+/// the balance of the payer.
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "token_use", rename_all = "snake_case")]
+pub enum TokenUse {
+    IotPackets,
+    MobileData,
+}
+
+impl TokenUse {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IotPackets => "iot_packets",
+            Self::MobileData => "mobile_data",
+        }
+    }
+
+    /// Least amount of a pending burn worth burning. Mobile data sessions
+    /// are burned every burn period, whatever their amount.
+    pub fn burn_threshold(&self) -> u64 {
+        match self {
+            Self::IotPackets => 10_000,
+            Self::MobileData => 1,
+        }
+    }
+}
+
+/// Key of the advisory lock debits of either packet verifier are made under
+const LEDGER_LOCK: i64 = 0x6463_6c65_6467_6572;
+
+/// Take the ledger lock until `transaction` ends. Debits are made within it
+/// after syncing the balance cache, see
+/// [`BalanceCache::sync`](crate::balances::BalanceCache::sync), so they
+/// count the pending burns the other verifier committed.
+pub async fn lock(transaction: &mut Transaction<'_, Postgres>) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(LEDGER_LOCK)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+/// Pending burns with a burn transaction are tracked to finalization first
+const FETCH_NEXT: &str = r#"
+    SELECT * FROM pending_burns
+    WHERE token_use = $2 AND (amount >= $1 OR burn_signature IS NOT NULL)
+    ORDER BY burn_signature IS NULL, last_burn ASC
+"#;
+
+const SUBTRACT_BURNED_AMOUNT: &str = r#"
+    WITH burned AS (
+      UPDATE pending_burns SET
+        amount = amount - $1,
+        last_burn = $2,
+        last_burn_signature = burn_signature,
+        burn_signature = NULL,
+        burn_amount = NULL,
+        burn_last_valid_block_height = NULL
+      WHERE payer = $3 AND token_use = $4
+      RETURNING payer, amount
+    ), credited AS (
+      INSERT INTO payer_ledger (payer, kind, amount)
+      SELECT payer, 'credit', $1 FROM burned
+    )
+    SELECT amount FROM burned
+"#;
+
+const FETCH_PENDING_AMOUNT: &str = r#"
+    SELECT COALESCE(SUM(amount), 0)::BIGINT FROM pending_burns WHERE payer = $1
+"#;
+
+const FETCH_LAST_BURN: &str = r#"
+    SELECT last_burn_signature, last_burn FROM pending_burns
+    WHERE payer = $1 AND last_burn_signature IS NOT NULL
+    ORDER BY last_burn DESC
+    LIMIT 1
+"#;
+
+const FETCH_BURN_TRANSACTION: &str = r#"
+    SELECT burn_signature, burn_amount, burn_last_valid_block_height FROM pending_burns
+    WHERE payer = $1 AND token_use = $2 AND burn_signature IS NOT NULL
+"#;
+
+const RECORD_BURN_TRANSACTION: &str = r#"
+    UPDATE pending_burns SET
+      burn_signature = $3,
+      burn_amount = $4,
+      burn_last_valid_block_height = $5
+    WHERE payer = $1 AND token_use = $2
+"#;
+
+fn burn_transaction(
+    (signature, amount, last_valid_block_height): (String, i64, i64),
+) -> BurnTransaction {
+    BurnTransaction {
+        signature,
+        amount: amount as u64,
+        last_valid_block_height: last_valid_block_height as u64,
+    }
+}
+
+#[async_trait]
+impl PendingBurns for Pool<Postgres> {
+    type Error = sqlx::Error;
+
+    async fn fetch_all<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Burn, Self::Error>> + Send + 'a>> {
+        sqlx::query_as("SELECT * FROM pending_burns").fetch(&*self)
+    }
+
+    async fn fetch_next(
+        &mut self,
+        token_use: TokenUse,
+        threshold: u64,
+    ) -> Result<Option<Burn>, Self::Error> {
+        sqlx::query_as(FETCH_NEXT)
+            .bind(threshold as i64)
+            .bind(token_use)
+            .fetch_optional(&*self)
+            .await
+    }
+
+    async fn subtract_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<u64, Self::Error> {
+        let remaining: Option<i64> = sqlx::query_scalar(SUBTRACT_BURNED_AMOUNT)
+            .bind(amount as i64)
+            .bind(Utc::now().naive_utc())
+            .bind(payer)
+            .bind(token_use)
+            .fetch_optional(&*self)
+            .await?;
+        Ok(remaining.unwrap_or_default() as u64)
+    }
+
+    async fn add_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<(), Self::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_burns (payer, token_use, amount, last_burn)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (payer, token_use) DO UPDATE SET
+            amount = pending_burns.amount + $3
+            RETURNING *
+            "#,
+        )
+        .bind(payer)
+        .bind(token_use)
+        .bind(amount as i64)
+        .bind(Utc::now().naive_utc())
+        .fetch_one(&*self)
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+    ) -> Result<Option<BurnTransaction>, Self::Error> {
+        Ok(
+            sqlx::query_as::<_, (String, i64, i64)>(FETCH_BURN_TRANSACTION)
+                .bind(payer)
+                .bind(token_use)
+                .fetch_optional(&*self)
+                .await?
+                .map(burn_transaction),
+        )
+    }
+
+    async fn record_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        transaction: Option<&BurnTransaction>,
+    ) -> Result<(), Self::Error> {
+        sqlx::query(RECORD_BURN_TRANSACTION)
+            .bind(payer)
+            .bind(token_use)
+            .bind(transaction.map(|transaction| transaction.signature.as_str()))
+            .bind(transaction.map(|transaction| transaction.amount as i64))
+            .bind(transaction.map(|transaction| transaction.last_valid_block_height as i64))
+            .execute(&*self)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PendingBurns for &'_ mut Transaction<'_, Postgres> {
+    type Error = sqlx::Error;
+
+    async fn fetch_all<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Burn, Self::Error>> + Send + 'a>> {
+        sqlx::query_as("SELECT * FROM pending_burns").fetch(&mut **self)
+    }
+
+    async fn fetch_next(
+        &mut self,
+        token_use: TokenUse,
+        threshold: u64,
+    ) -> Result<Option<Burn>, Self::Error> {
+        sqlx::query_as(FETCH_NEXT)
+            .bind(threshold as i64)
+            .bind(token_use)
+            .fetch_optional(&mut **self)
+            .await
+    }
+
+    async fn subtract_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<u64, Self::Error> {
+        let remaining: Option<i64> = sqlx::query_scalar(SUBTRACT_BURNED_AMOUNT)
+            .bind(amount as i64)
+            .bind(Utc::now().naive_utc())
+            .bind(payer)
+            .bind(token_use)
+            .fetch_optional(&mut **self)
+            .await?;
+        Ok(remaining.unwrap_or_default() as u64)
+    }
+
+    async fn add_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<(), Self::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_burns (payer, token_use, amount, last_burn)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (payer, token_use) DO UPDATE SET
+            amount = pending_burns.amount + $3
+            RETURNING *
+            "#,
+        )
+        .bind(payer)
+        .bind(token_use)
+        .bind(amount as i64)
+        .bind(Utc::now().naive_utc())
+        .fetch_one(&mut **self)
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+    ) -> Result<Option<BurnTransaction>, Self::Error> {
+        Ok(
+            sqlx::query_as::<_, (String, i64, i64)>(FETCH_BURN_TRANSACTION)
+                .bind(payer)
+                .bind(token_use)
+                .fetch_optional(&mut **self)
+                .await?
+                .map(burn_transaction),
+        )
+    }
+
+    async fn record_burn_transaction(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        transaction: Option<&BurnTransaction>,
+    ) -> Result<(), Self::Error> {
+        sqlx::query(RECORD_BURN_TRANSACTION)
+            .bind(payer)
+            .bind(token_use)
+            .bind(transaction.map(|transaction| transaction.signature.as_str()))
+            .bind(transaction.map(|transaction| transaction.amount as i64))
+            .bind(transaction.map(|transaction| transaction.last_valid_block_height as i64))
+            .execute(&mut **self)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PendingBurns for Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> {
+    type Error = Infallible;
+
+    async fn fetch_all<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Burn, Self::Error>> + Send + 'a>> {
+        stream::iter(
+            self.lock()
+                .await
+                .clone()
+                .into_iter()
+                .map(|((payer, token_use), amount)| {
+                    Ok(Burn {
+                        payer,
+                        token_use,
+                        amount: amount as i64,
+                    })
+                }),
+        )
+        .boxed()
+    }
+
+    /// Mocks burn any amount pending, whatever the threshold
+    async fn fetch_next(
+        &mut self,
+        token_use: TokenUse,
+        _threshold: u64,
+    ) -> Result<Option<Burn>, Self::Error> {
+        Ok(self
+            .lock()
+            .await
+            .iter()
+            .filter(|((_, burn_use), amount)| *burn_use == token_use && **amount > 0)
+            .max_by_key(|(_, amount)| **amount)
+            .map(|((payer, token_use), amount)| Burn {
+                payer: payer.clone(),
+                token_use: *token_use,
+                amount: *amount as i64,
+            }))
+    }
+
+    async fn subtract_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<u64, Self::Error> {
+        let mut map = self.lock().await;
+        let balance = map.get_mut(&(payer.clone(), token_use)).unwrap();
+        *balance -= amount;
+        Ok(*balance)
+    }
+
+    async fn add_burned_amount(
+        &mut self,
+        payer: &PublicKeyBinary,
+        token_use: TokenUse,
+        amount: u64,
+    ) -> Result<(), Self::Error> {
+        let mut map = self.lock().await;
+        *map.entry((payer.clone(), token_use)).or_default() += amount;
+        Ok(())
+    }
+
+    async fn fetch_burn_transaction(
+        &mut self,
+        _payer: &PublicKeyBinary,
+        _token_use: TokenUse,
+    ) -> Result<Option<BurnTransaction>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn record_burn_transaction(
+        &mut self,
+        _payer: &PublicKeyBinary,
+        _token_use: TokenUse,
+        _transaction: Option<&BurnTransaction>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(FromRow, Debug)]
+pub struct Burn {
+    pub payer: PublicKeyBinary,
+    pub token_use: TokenUse,
+    pub amount: i64,
+}
+
+/// Pending and last finalized burns of a payer, over every token use
+#[derive(Debug, Default)]
+pub struct PayerBurns {
+    pub pending_amount: u64,
+    pub last_burn: Option<(String, DateTime<Utc>)>,
+}
+
+pub async fn payer_burns(
+    db: &Pool<Postgres>,
+    payer: &PublicKeyBinary,
+) -> Result<PayerBurns, sqlx::Error> {
+    let pending_amount: i64 = sqlx::query_scalar(FETCH_PENDING_AMOUNT)
+        .bind(payer)
+        .fetch_one(db)
+        .await?;
+    let last_burn: Option<(String, NaiveDateTime)> = sqlx::query_as(FETCH_LAST_BURN)
+        .bind(payer)
+        .fetch_optional(db)
+        .await?;
+    Ok(PayerBurns {
+        pending_amount: pending_amount as u64,
+        last_burn: last_burn
+            .map(|(signature, last_burn)| (signature, Utc.from_utc_datetime(&last_burn))),
+    })
+}
+
+/// Lower the pending burns of `token_use` to the amounts still owed for
+/// them, as recorded by the verifier debiting them, skipping payers with a
+/// burn in flight. Missing pending burns are added. Pending burns are never
+/// raised, a burn settled before the verifier recorded it must not be burned
+/// again.
+pub async fn reconcile(
+    transaction: &mut Transaction<'_, Postgres>,
+    token_use: TokenUse,
+    owed: &HashMap<PublicKeyBinary, u64>,
+) -> Result<(), sqlx::Error> {
+    let payers: Vec<String> = owed.keys().map(ToString::to_string).collect();
+    sqlx::query(
+        r#"
+        UPDATE pending_burns SET amount = 0
+        WHERE token_use = $1 AND burn_signature IS NULL AND NOT payer = ANY($2)
+        "#,
+    )
+    .bind(token_use)
+    .bind(&payers)
+    .execute(&mut *transaction)
+    .await?;
+    for (payer, amount) in owed {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_burns (payer, token_use, amount, last_burn)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (payer, token_use) DO UPDATE SET
+            amount = LEAST(pending_burns.amount, EXCLUDED.amount)
+            WHERE pending_burns.burn_signature IS NULL
+            "#,
+        )
+        .bind(payer)
+        .bind(token_use)
+        .bind(*amount as i64)
+        .bind(Utc::now().naive_utc())
+        .execute(&mut *transaction)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Whether `payer` has a burn transaction sent and not finalized yet, of any
+/// token use
+pub async fn is_burning(db: &Pool<Postgres>, payer: &PublicKeyBinary) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pending_burns WHERE payer = $1 AND burn_signature IS NOT NULL)",
+    )
+    .bind(payer)
+    .fetch_one(db)
+    .await
+}
+
+/// Payers with a burn transaction sent and not finalized yet
+pub async fn payers_burning(db: &Pool<Postgres>) -> Result<HashSet<PublicKeyBinary>, sqlx::Error> {
+    sqlx::query_scalar("SELECT DISTINCT payer FROM pending_burns WHERE burn_signature IS NOT NULL")
+        .fetch(db)
+        .try_collect()
+        .await
+}
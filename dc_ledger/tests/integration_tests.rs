@@ -0,0 +1,421 @@
+use async_trait::async_trait;
+use dc_ledger::{
+    balances::{BalanceCache, Debiter},
+    burner::{BurnError, Burner, Settlement},
+    pending_burns::{self, PendingBurns, TokenUse},
+};
+use helium_crypto::PublicKeyBinary;
+use solana::{BurnStatus, BurnTransaction, SignedBurn, SolanaNetwork, SolanaRpc};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+type MockPendingBurns = Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>>;
+
+/// Network signing burns and reporting the statuses scripted for them, in
+/// order, finalizing any burn once the script is done
+#[derive(Clone, Default)]
+struct ScriptedNetwork {
+    statuses: Arc<Mutex<VecDeque<BurnStatus>>>,
+    sent: Arc<Mutex<Vec<BurnTransaction>>>,
+}
+
+impl ScriptedNetwork {
+    fn new(statuses: impl IntoIterator<Item = BurnStatus>) -> Self {
+        Self {
+            statuses: Arc::new(Mutex::new(statuses.into_iter().collect())),
+            sent: Default::default(),
+        }
+    }
+
+    async fn sent(&self) -> Vec<String> {
+        self.sent
+            .lock()
+            .await
+            .iter()
+            .map(|burn| burn.signature.clone())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl SolanaNetwork for ScriptedNetwork {
+    type Error = Infallible;
+
+    async fn payer_balance(&self, _payer: &PublicKeyBinary) -> Result<u64, Self::Error> {
+        Ok(1_000_000)
+    }
+
+    async fn burn_data_credits(
+        &self,
+        _payer: &PublicKeyBinary,
+        _amount: u64,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error> {
+        let signature = format!("burn-{}", self.sent.lock().await.len());
+        Ok(SignedBurn::unsent(
+            payer,
+            BurnTransaction {
+                signature,
+                amount,
+                last_valid_block_height: 0,
+            },
+        ))
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error> {
+        self.sent.lock().await.push(burn.burn.clone());
+        Ok(())
+    }
+
+    async fn burn_status(&self, _burn: &BurnTransaction) -> Result<BurnStatus, Self::Error> {
+        Ok(self
+            .statuses
+            .lock()
+            .await
+            .pop_front()
+            .unwrap_or(BurnStatus::Finalized))
+    }
+}
+
+#[tokio::test]
+async fn test_shared_payer_is_not_overspent() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let mut pending_burns = MockPendingBurns::default();
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 10_u64)])));
+
+    // The balance caches of the iot and mobile packet verifiers:
+    let iot = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+    let mobile = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+
+    // Packets spend 6 of the 10 DC of the payer:
+    iot.sync(pending_burns.clone()).await.unwrap();
+    assert_eq!(
+        iot.debit_if_sufficient(&payer, 6, 0).await.unwrap(),
+        Some(4)
+    );
+    pending_burns
+        .add_burned_amount(&payer, TokenUse::IotPackets, 6)
+        .await
+        .unwrap();
+
+    // Leaving sessions only the other 4:
+    mobile.sync(pending_burns.clone()).await.unwrap();
+    assert_eq!(
+        mobile.debit_if_sufficient(&payer, 5, 0).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        mobile.debit_if_sufficient(&payer, 4, 0).await.unwrap(),
+        Some(0)
+    );
+    pending_burns
+        .add_burned_amount(&payer, TokenUse::MobileData, 4)
+        .await
+        .unwrap();
+
+    iot.sync(pending_burns.clone()).await.unwrap();
+    assert_eq!(iot.debit_if_sufficient(&payer, 1, 0).await.unwrap(), None);
+
+    // The mobile burner only burns the sessions:
+    let mut burner = Burner::new(
+        pending_burns.clone(),
+        &mobile,
+        TokenUse::MobileData,
+        0,
+        solana_network.clone(),
+    );
+    assert_eq!(
+        burner.burn().await.unwrap(),
+        Some(Settlement {
+            payer: payer.clone(),
+            token_use: TokenUse::MobileData,
+            amount: 4,
+            remaining: 0,
+        })
+    );
+    assert!(burner.burn().await.unwrap().is_none());
+    assert_eq!(*solana_network.lock().await.get(&payer).unwrap(), 6);
+    assert_eq!(
+        *pending_burns
+            .lock()
+            .await
+            .get(&(payer.clone(), TokenUse::IotPackets))
+            .unwrap(),
+        6
+    );
+
+    // The balance the iot cache fetched predates the burn, syncing resets it
+    // so the 6 DC left on chain still cover the packets only:
+    iot.sync(pending_burns.clone()).await.unwrap();
+    {
+        let balances = iot.balances();
+        let balances = balances.lock().await;
+        let balance = balances.get(&payer).unwrap();
+        assert_eq!(balance.balance, 0);
+        assert_eq!(balance.burned, 6);
+    }
+    assert_eq!(iot.debit_if_sufficient(&payer, 1, 0).await.unwrap(), None);
+
+    *solana_network.lock().await.get_mut(&payer).unwrap() = 16;
+    assert_eq!(
+        iot.debit_if_sufficient(&payer, 10, 0).await.unwrap(),
+        Some(0)
+    );
+}
+
+#[tokio::test]
+async fn test_sync_adds_payers_of_the_other_verifier() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let mut pending_burns = MockPendingBurns::default();
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 10_u64)])));
+    let iot = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+
+    pending_burns
+        .add_burned_amount(&payer, TokenUse::MobileData, 7)
+        .await
+        .unwrap();
+    iot.sync(pending_burns.clone()).await.unwrap();
+
+    assert_eq!(iot.debit_if_sufficient(&payer, 4, 0).await.unwrap(), None);
+    assert_eq!(
+        iot.debit_if_sufficient(&payer, 3, 0).await.unwrap(),
+        Some(0)
+    );
+}
+
+async fn pending_amount(pool: &PgPool, payer: &PublicKeyBinary, token_use: TokenUse) -> i64 {
+    sqlx::query_scalar("SELECT amount FROM pending_burns WHERE payer = $1 AND token_use = $2")
+        .bind(payer)
+        .bind(token_use)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_pending_burns_of_each_token_use(pool: PgPool) -> anyhow::Result<()> {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let mut ledger = pool.clone();
+    ledger
+        .add_burned_amount(&payer, TokenUse::IotPackets, 20_000)
+        .await?;
+    ledger
+        .add_burned_amount(&payer, TokenUse::MobileData, 5)
+        .await?;
+
+    let next = ledger
+        .fetch_next(TokenUse::MobileData, TokenUse::MobileData.burn_threshold())
+        .await?
+        .expect("mobile data burn");
+    assert_eq!((next.token_use, next.amount), (TokenUse::MobileData, 5));
+    assert!(ledger
+        .fetch_next(TokenUse::IotPackets, 30_000)
+        .await?
+        .is_none());
+
+    let remaining = ledger
+        .subtract_burned_amount(&payer, TokenUse::MobileData, 3)
+        .await?;
+    assert_eq!(remaining, 2);
+    assert_eq!(
+        pending_amount(&pool, &payer, TokenUse::IotPackets).await,
+        20_000
+    );
+    let credited: i64 =
+        sqlx::query_scalar("SELECT amount FROM payer_ledger WHERE payer = $1 AND kind = 'credit'")
+            .bind(&payer)
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(credited, 3);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_reconcile_never_raises_pending_burns(pool: PgPool) -> anyhow::Result<()> {
+    let settled = PublicKeyBinary::from(vec![0]);
+    let overcounted = PublicKeyBinary::from(vec![1]);
+    let missing = PublicKeyBinary::from(vec![2]);
+    let gone = PublicKeyBinary::from(vec![3]);
+    let mut ledger = pool.clone();
+    ledger
+        .add_burned_amount(&settled, TokenUse::MobileData, 1)
+        .await?;
+    ledger
+        .subtract_burned_amount(&settled, TokenUse::MobileData, 1)
+        .await?;
+    ledger
+        .add_burned_amount(&overcounted, TokenUse::MobileData, 9)
+        .await?;
+    ledger
+        .add_burned_amount(&gone, TokenUse::MobileData, 4)
+        .await?;
+    ledger
+        .add_burned_amount(&gone, TokenUse::IotPackets, 4)
+        .await?;
+
+    let owed = HashMap::from([
+        (settled.clone(), 5),
+        (overcounted.clone(), 6),
+        (missing.clone(), 2),
+    ]);
+    let mut transaction = pool.begin().await?;
+    pending_burns::lock(&mut transaction).await?;
+    pending_burns::reconcile(&mut transaction, TokenUse::MobileData, &owed).await?;
+    transaction.commit().await?;
+
+    assert_eq!(
+        pending_amount(&pool, &settled, TokenUse::MobileData).await,
+        0
+    );
+    assert_eq!(
+        pending_amount(&pool, &overcounted, TokenUse::MobileData).await,
+        6
+    );
+    assert_eq!(
+        pending_amount(&pool, &missing, TokenUse::MobileData).await,
+        2
+    );
+    assert_eq!(pending_amount(&pool, &gone, TokenUse::MobileData).await, 0);
+    assert_eq!(pending_amount(&pool, &gone, TokenUse::IotPackets).await, 4);
+    Ok(())
+}
+
+async fn burner<S: SolanaNetwork + Clone>(pool: &PgPool, solana: S) -> Burner<PgPool, S> {
+    let mut ledger = pool.clone();
+    let balances = BalanceCache::new(&mut ledger, solana.clone())
+        .await
+        .unwrap();
+    Burner::new(ledger, &balances, TokenUse::IotPackets, 0, solana)
+}
+
+async fn burn_signatures(
+    pool: &PgPool,
+    payer: &PublicKeyBinary,
+) -> (Option<String>, Option<String>) {
+    sqlx::query_as(
+        "SELECT burn_signature, last_burn_signature FROM pending_burns WHERE payer = $1 AND token_use = $2",
+    )
+    .bind(payer)
+    .bind(TokenUse::IotPackets)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_expired_burns_are_sent_again(pool: PgPool) -> anyhow::Result<()> {
+    let payer = PublicKeyBinary::from(vec![0]);
+    pool.clone()
+        .add_burned_amount(&payer, TokenUse::IotPackets, 20_000)
+        .await?;
+    let solana = ScriptedNetwork::new([BurnStatus::Expired, BurnStatus::Finalized]);
+
+    let settlement = burner(&pool, solana.clone()).await.burn().await?;
+
+    assert_eq!(
+        settlement.map(|settlement| (settlement.amount, settlement.remaining)),
+        Some((20_000, 0))
+    );
+    assert_eq!(solana.sent().await, vec!["burn-0", "burn-1"]);
+    assert_eq!(
+        burn_signatures(&pool, &payer).await,
+        (None, Some("burn-1".to_string()))
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_failed_burns_are_burned_again_later(pool: PgPool) -> anyhow::Result<()> {
+    let payer = PublicKeyBinary::from(vec![0]);
+    pool.clone()
+        .add_burned_amount(&payer, TokenUse::IotPackets, 20_000)
+        .await?;
+    let solana = ScriptedNetwork::new([BurnStatus::Failed]);
+    let mut burner = burner(&pool, solana.clone()).await;
+
+    assert!(matches!(
+        burner.burn().await,
+        Err(BurnError::TransactionFailed(signature)) if signature == "burn-0"
+    ));
+    assert_eq!(burn_signatures(&pool, &payer).await, (None, None));
+    assert_eq!(
+        pending_amount(&pool, &payer, TokenUse::IotPackets).await,
+        20_000
+    );
+
+    // The next attempt signs a new burn of the whole amount:
+    let settlement = burner.burn().await?;
+    assert_eq!(settlement.map(|settlement| settlement.amount), Some(20_000));
+    assert_eq!(solana.sent().await, vec!["burn-0", "burn-1"]);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_recorded_burns_are_tracked_after_a_restart(pool: PgPool) -> anyhow::Result<()> {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let mut ledger = pool.clone();
+    ledger
+        .add_burned_amount(&payer, TokenUse::IotPackets, 20_000)
+        .await?;
+    ledger
+        .record_burn_transaction(
+            &payer,
+            TokenUse::IotPackets,
+            Some(&BurnTransaction {
+                signature: "before-restart".to_string(),
+                amount: 15_000,
+                last_valid_block_height: 0,
+            }),
+        )
+        .await?;
+    // Packets verified after the burn was signed are left pending:
+    ledger
+        .add_burned_amount(&payer, TokenUse::IotPackets, 5_000)
+        .await?;
+    let solana = ScriptedNetwork::new([BurnStatus::Finalized]);
+
+    let settlement = burner(&pool, solana.clone()).await.burn().await?;
+
+    assert_eq!(
+        settlement.map(|settlement| (settlement.amount, settlement.remaining)),
+        Some((15_000, 10_000))
+    );
+    assert!(solana.sent().await.is_empty());
+    assert_eq!(
+        burn_signatures(&pool, &payer).await,
+        (None, Some("before-restart".to_string()))
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../iot_packet_verifier/migrations")]
+async fn test_shadow_mode_records_no_signature(pool: PgPool) -> anyhow::Result<()> {
+    let payer = PublicKeyBinary::from(vec![0]);
+    pool.clone()
+        .add_burned_amount(&payer, TokenUse::IotPackets, 20_000)
+        .await?;
+
+    let settlement = burner(&pool, None::<Arc<SolanaRpc>>).await.burn().await?;
+
+    assert_eq!(settlement.map(|settlement| settlement.amount), Some(20_000));
+    assert_eq!(burn_signatures(&pool, &payer).await, (None, None));
+    Ok(())
+}
@@ -125,7 +125,8 @@ pub fn filter_from_bin(bin: &Vec<u8>) -> Result<Xor32> {
     }
 }
 
-fn public_key_hash<R: AsRef<[u8]>>(public_key: R) -> u64 {
+/// Hash of a public key as held by the denylist filter
+pub fn public_key_hash<R: AsRef<[u8]>>(public_key: R) -> u64 {
     let mut hasher = XxHash64::default();
     hasher.write(public_key.as_ref());
     hasher.finish()
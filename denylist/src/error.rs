@@ -23,8 +23,8 @@ pub enum Error {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("io error")]
     Io(#[from] std::io::Error),
-    #[error("config error")]
-    Config(#[from] config::ConfigError),
+    #[error("settings error: {0}")]
+    Settings(#[from] settings_loader::Error),
 }
 
 impl Error {
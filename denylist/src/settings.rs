@@ -1,5 +1,4 @@
 use crate::{Error, Result};
-use config::{Config, Environment, File};
 use serde::Deserialize;
 use std::{path::Path, time::Duration};
 
@@ -12,9 +11,13 @@ pub struct Settings {
     /// Listen address for http requests for entropy. Default "0.0.0.0:8080"
     #[serde(default = "default_denylist_url")]
     pub denylist_url: String,
-    /// Cadence at which we poll for an updated denylist (secs)
-    #[serde(default = "default_trigger_interval")]
-    pub trigger: u64,
+    /// Cadence at which we poll for an updated denylist. Accepts seconds or
+    /// a duration string such as "6h"
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_trigger_interval"
+    )]
+    pub trigger: Duration,
 }
 
 pub fn default_log() -> String {
@@ -25,8 +28,8 @@ pub fn default_denylist_url() -> String {
     "https://api.github.com/repos/helium/denylist/releases/latest".to_string()
 }
 
-fn default_trigger_interval() -> u64 {
-    21600
+fn default_trigger_interval() -> Duration {
+    Duration::from_secs(21600)
 }
 
 impl Settings {
@@ -37,23 +40,10 @@ impl Settings {
     /// file in uppercase and prefixed with "DENYLIST_". For example
     /// "DENYLIST_LOG" will override the log setting.
     pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `DENYLIST_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("DENYLIST").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
-            .map_err(Error::from)
+        settings_loader::load(path, "DENYLIST").map_err(Error::from)
     }
 
     pub fn trigger_interval(&self) -> Duration {
-        Duration::from_secs(self.trigger)
+        self.trigger
     }
 }
@@ -1,11 +1,18 @@
 use crate::{
-    heartbeat::CellHeartbeat, iot_beacon_report::IotBeaconIngestReport, iot_valid_poc::IotPoc,
-    iot_witness_report::IotWitnessIngestReport, speedtest::CellSpeedtest, traits::MsgDecode, Error,
-    FileInfoStream, FileStore, FileType, Result, Settings,
+    cli::print_json,
+    compaction::{self, Compactor, DEFAULT_MIN_FILES},
+    heartbeat::CellHeartbeat,
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_valid_poc::IotPoc,
+    iot_witness_report::IotWitnessIngestReport,
+    poc_archive::{PocArchive, POC_FILE_TYPES},
+    speedtest::CellSpeedtest,
+    traits::MsgDecode,
+    Error, FileInfoStream, FileStore, FileType, Result, Settings,
 };
-use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
 use futures::{stream::TryStreamExt, StreamExt, TryFutureExt};
-use helium_crypto::PublicKey;
+use helium_crypto::{PublicKey, PublicKeyBinary};
 use serde::{ser::SerializeSeq, Serializer};
 use std::{
     io,
@@ -27,6 +34,9 @@ pub enum BucketCmd {
     Put(Put),
     Get(Get),
     Locate(Locate),
+    Pocs(Pocs),
+    IndexPocs(IndexPocs),
+    Compact(Compact),
 }
 
 impl Cmd {
@@ -43,6 +53,9 @@ impl BucketCmd {
             Self::Put(cmd) => cmd.run(settings).await,
             Self::Get(cmd) => cmd.run(settings).await,
             Self::Locate(cmd) => cmd.run(settings).await,
+            Self::Pocs(cmd) => cmd.run(settings).await,
+            Self::IndexPocs(cmd) => cmd.run(settings).await,
+            Self::Compact(cmd) => cmd.run(settings).await,
         }
     }
 }
@@ -60,15 +73,21 @@ struct FileFilter {
     /// The file type to search for
     #[clap(long)]
     file_type: FileType,
+    /// List the compacted object of compacted hours in place of the files it
+    /// supersedes
+    #[clap(long)]
+    compacted: bool,
 }
 
 impl FileFilter {
     fn list(&self, store: &FileStore) -> FileInfoStream {
-        store.list(
-            self.file_type,
-            self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
-            self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt)),
-        )
+        let after = self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt));
+        let before = self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt));
+        if self.compacted {
+            compaction::stream_compacted(store, self.file_type, after, before)
+        } else {
+            store.list(self.file_type, after, before)
+        }
     }
 }
 
@@ -197,6 +216,121 @@ impl Locate {
     }
 }
 
+/// Locate the valid pocs and rejected reports of an iot gateway received in
+/// a time range, only fetching the verifier output files which can hold them
+#[derive(Debug, clap::Args)]
+pub struct Pocs {
+    gateway: PublicKeyBinary,
+    /// Start of the time range (inclusive)
+    #[clap(long)]
+    after: NaiveDateTime,
+    /// End of the time range (exclusive)
+    #[clap(long)]
+    before: NaiveDateTime,
+    /// Max hours between a report being received and verified
+    #[clap(long, default_value_t = crate::poc_archive::DEFAULT_MAX_VERIFICATION_DELAY_HOURS)]
+    max_verification_delay: i64,
+}
+
+impl Pocs {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let store = FileStore::from_settings(settings).await?;
+        let period = Utc.from_utc_datetime(&self.after)..Utc.from_utc_datetime(&self.before);
+        let mut records = PocArchive::new(store)
+            .max_verification_delay(Duration::hours(self.max_verification_delay))
+            .gateway_records(&self.gateway, period)
+            .await?;
+        let mut ser = serde_json::Serializer::new(io::stdout());
+        let mut seq = ser.serialize_seq(None)?;
+        while let Some(record) = records.try_next().await? {
+            seq.serialize_element(&record)?;
+        }
+        seq.end()?;
+        Ok(())
+    }
+}
+
+/// Index the gateways of the verifier output files written in the hours of
+/// a time range, so `pocs` only fetches the files of the gateway. Safe to
+/// re-run.
+#[derive(Debug, clap::Args)]
+pub struct IndexPocs {
+    /// Start of the time range, rounded down to the hour
+    #[clap(long)]
+    after: NaiveDateTime,
+    /// End of the time range (exclusive)
+    #[clap(long)]
+    before: NaiveDateTime,
+}
+
+impl IndexPocs {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let archive = PocArchive::new(FileStore::from_settings(settings).await?);
+        let before = Utc.from_utc_datetime(&self.before);
+        let mut hour = Utc.from_utc_datetime(&self.after);
+        while hour < before {
+            for file_type in POC_FILE_TYPES {
+                archive.index(file_type, hour).await?;
+            }
+            hour += Duration::hours(1);
+        }
+        Ok(())
+    }
+}
+
+/// Merge the files of a file type into one object per hour, superseding
+/// them with a compaction manifest. Safe to re-run.
+#[derive(Debug, clap::Args)]
+pub struct Compact {
+    #[clap(flatten)]
+    filter: FileFilter,
+    /// Hours with fewer files are left alone
+    #[clap(long, default_value_t = DEFAULT_MIN_FILES)]
+    min_files: usize,
+    /// Folder compacted files are staged in before upload. Defaults to the
+    /// system temp folder
+    #[clap(long)]
+    work_dir: Option<PathBuf>,
+    /// Only list the hours which would be compacted
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl Compact {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let store = FileStore::from_settings(settings).await?;
+        let compactor = Compactor::new(store).min_files(self.min_files);
+        let pending = compactor
+            .plan(
+                self.filter.file_type,
+                self.filter
+                    .after
+                    .as_ref()
+                    .map(|dt| Utc.from_utc_datetime(dt)),
+                self.filter
+                    .before
+                    .as_ref()
+                    .map(|dt| Utc.from_utc_datetime(dt)),
+            )
+            .await?;
+        if self.dry_run {
+            return print_json(&pending);
+        }
+        let work_dir = self.work_dir.clone().unwrap_or_else(std::env::temp_dir);
+        for pending in pending {
+            match compactor.compact(pending, &work_dir).await {
+                Ok(manifest) => print_json(&manifest)?,
+                // left to the concurrent run, or to the next one
+                Err(Error::CompactionConflict(conflict)) => {
+                    tracing::warn!(%conflict, "skipped compaction")
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
 fn locate(
     file_type: FileType,
     gateway: &PublicKey,
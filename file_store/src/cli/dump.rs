@@ -2,7 +2,21 @@ use crate::{
     cli::print_json,
     file_source,
     heartbeat::{CellHeartbeat, CellHeartbeatIngestReport},
+    iot_balance_warning::BalanceWarning,
+    iot_beacon_cadence::BeaconCadenceReport,
+    iot_gateway_reconciliation::GatewayReconciliation,
+    iot_gateway_reward_class::GatewayRewardClass,
+    iot_hex_density_snapshot::HexDensitySnapshot,
+    iot_hex_scale_comparison::HexScaleComparison,
     iot_packet::IotValidPacket,
+    iot_packet_price::PacketPrice,
+    iot_unallocated_reward::UnallocatedReward,
+    iot_verification_bypass::VerificationBypass,
+    iot_witness_inclusion::WitnessInclusion,
+    iot_witness_quality::WitnessQualityReport,
+    iot_witness_rssi_check::WitnessRssiCheck,
+    mobile_onboarding_rejection::OnboardingRejection,
+    mobile_reward_dust::RewardDust,
     mobile_session::{DataTransferSessionIngestReport, InvalidDataTransferIngestReport},
     mobile_subscriber::{SubscriberLocationIngestReport, VerifiedSubscriberLocationIngestReport},
     speedtest::{CellSpeedtest, CellSpeedtestIngestReport},
@@ -96,7 +110,7 @@ impl Cmd {
                         "event_timestamp": msg.report.report.data_transfer_usage.timestamp,
                     }))?;
                 }
-                FileType::ValidDataTransferSession => {
+                FileType::ValidDataTransferSession | FileType::UnpaidDataTransferSession => {
                     let msg = ValidDataTransferSessionProto::decode(msg)?;
                     print_json(&json!({
                         "pub_key": PublicKey::try_from(msg.pub_key)?,
@@ -267,6 +281,62 @@ impl Cmd {
                         "status": report.status,
                         "recv_timestamp": report.report.received_timestamp}))?;
                 }
+                FileType::IotBalanceWarning => {
+                    let warning = BalanceWarning::decode(msg)?;
+                    print_json(&warning)?;
+                }
+                FileType::IotBeaconCadenceReport => {
+                    let report = BeaconCadenceReport::decode(msg)?;
+                    print_json(&report)?;
+                }
+                FileType::IotPacketPrice => {
+                    let price = PacketPrice::decode(msg)?;
+                    print_json(&price)?;
+                }
+                FileType::IotGatewayReconciliation => {
+                    let reconciliation = GatewayReconciliation::decode(msg)?;
+                    print_json(&reconciliation)?;
+                }
+                FileType::IotWitnessInclusion => {
+                    let inclusion = WitnessInclusion::decode(msg)?;
+                    print_json(&inclusion)?;
+                }
+                FileType::IotVerificationBypass => {
+                    let bypass = VerificationBypass::decode(msg)?;
+                    print_json(&bypass)?;
+                }
+                FileType::IotHexScaleComparison => {
+                    let comparison = HexScaleComparison::decode(msg)?;
+                    print_json(&comparison)?;
+                }
+                FileType::IotHexDensitySnapshot => {
+                    let snapshot = HexDensitySnapshot::decode(msg)?;
+                    print_json(&snapshot)?;
+                }
+                FileType::IotWitnessQuality => {
+                    let quality = WitnessQualityReport::decode(msg)?;
+                    print_json(&quality)?;
+                }
+                FileType::IotWitnessRssiCheck => {
+                    let check = WitnessRssiCheck::decode(msg)?;
+                    print_json(&check)?;
+                }
+                FileType::IotGatewayRewardClass => {
+                    let class = GatewayRewardClass::decode(msg)?;
+                    print_json(&class)?;
+                }
+                FileType::MobileRewardDust => {
+                    let dust = RewardDust::decode(msg)?;
+                    print_json(&dust)?;
+                }
+                FileType::MobileOnboardingRejection => {
+                    let rejection = OnboardingRejection::decode(msg)?;
+                    print_json(&rejection)?;
+                }
+                FileType::IotUnallocatedReward => {
+                    let unallocated = UnallocatedReward::decode(msg)?;
+                    print_json(&unallocated)?;
+                }
                 _ => (),
             }
         }
@@ -0,0 +1,499 @@
+//! Compaction of fragmented output files, such as the thousands of small
+//! files an hour written by backfills, into one object per hour.
+
+use crate::{
+    file_sink::MAX_FRAME_LENGTH, Error, FileInfo, FileInfoStream, FileStore, FileType, Result,
+};
+use async_compression::tokio::write::GzipEncoder;
+use bytes::Bytes;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use futures::{stream, SinkExt, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufWriter},
+};
+use tokio_util::codec::{length_delimited::LengthDelimitedCodec, FramedWrite};
+
+/// Prefix of the compacted objects and their manifests, which listings of the
+/// file type don't see
+pub const COMPACTED_PREFIX: &str = "compacted";
+/// Hours with fewer files than this are left alone by default
+pub const DEFAULT_MIN_FILES: usize = 10;
+/// How long the lease on the compaction of an hour is held at most
+const LEASE_HOURS: i64 = 1;
+/// Time given to a concurrent run to overwrite a lease just written, before
+/// checking it is still held
+const LEASE_SETTLE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Record of the files of an hour merged into a compacted object, the
+/// tombstone of the originals, which are left in place for consumers listing
+/// the file type directly
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionManifest {
+    pub file_type: String,
+    pub hour: DateTime<Utc>,
+    /// Key of the compacted object
+    pub compacted: String,
+    /// Keys of the original files the compacted object supersedes
+    pub superseded: Vec<String>,
+    pub records: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The files of an hour due for compaction
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingCompaction {
+    pub file_type: FileType,
+    pub hour: DateTime<Utc>,
+    pub files: Vec<FileInfo>,
+    /// Manifest of an earlier compaction of the hour, replaced by this one
+    pub previous: Option<CompactionManifest>,
+}
+
+/// Lease on the compaction of an hour by one run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CompactionLease {
+    owner: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl CompactionLease {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            owner: format!("{}.{}", std::process::id(), now.timestamp_nanos()),
+            expires_at: now + Duration::hours(LEASE_HOURS),
+        }
+    }
+
+    fn is_held(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+pub(crate) fn hour_of(timestamp: DateTime<Utc>) -> DateTime<Utc> {
+    timestamp
+        .duration_trunc(Duration::hours(1))
+        .unwrap_or(timestamp)
+}
+
+fn manifest_key(file_type: FileType, hour: DateTime<Utc>) -> String {
+    format!(
+        "{COMPACTED_PREFIX}/{file_type}.{}.manifest.json",
+        hour.timestamp_millis()
+    )
+}
+
+fn lease_key(file_type: FileType, hour: DateTime<Utc>) -> String {
+    format!(
+        "{COMPACTED_PREFIX}/{file_type}.{}.lease.json",
+        hour.timestamp_millis()
+    )
+}
+
+fn compacted_key(file_type: FileType, hour: DateTime<Utc>, created_at: DateTime<Utc>) -> String {
+    format!(
+        "{COMPACTED_PREFIX}/{file_type}.{}.{}.gz",
+        hour.timestamp_millis(),
+        created_at.timestamp_millis()
+    )
+}
+
+async fn fetch_manifest(store: &FileStore, key: &str) -> Result<CompactionManifest> {
+    Ok(serde_json::from_slice(&store.get_bytes(key).await?)?)
+}
+
+/// The JSON object at `key`, if there is one
+async fn fetch_if_any<T: serde::de::DeserializeOwned>(
+    store: &FileStore,
+    key: &str,
+) -> Result<Option<T>> {
+    if !store.list_keys(key).await?.iter().any(|found| found == key) {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&store.get_bytes(key).await?)?))
+}
+
+/// The manifests of the compacted hours of `file_type`, by hour
+async fn manifests(
+    store: &FileStore,
+    file_type: FileType,
+) -> Result<BTreeMap<DateTime<Utc>, CompactionManifest>> {
+    let mut manifests = BTreeMap::new();
+    let prefix = format!("{COMPACTED_PREFIX}/{file_type}.");
+    for key in store.list_keys(&prefix).await? {
+        if key.ends_with(".manifest.json") {
+            let manifest = fetch_manifest(store, &key).await?;
+            manifests.insert(manifest.hour, manifest);
+        }
+    }
+    Ok(manifests)
+}
+
+/// Merges the files of a file type into one object per hour. Compaction is
+/// safe to re-run: an hour with files written since it was compacted is
+/// compacted again, originals only, into a new object, and its manifest is
+/// only replaced once that object is uploaded. Concurrent runs only compact
+/// an hour while holding its lease, and give up on it when its manifest
+/// changed since it was planned
+pub struct Compactor {
+    store: FileStore,
+    min_files: usize,
+}
+
+impl Compactor {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            store,
+            min_files: DEFAULT_MIN_FILES,
+        }
+    }
+
+    /// Hours with fewer files not superseded yet are left alone
+    pub fn min_files(self, min_files: usize) -> Self {
+        Self { min_files, ..self }
+    }
+
+    /// The hours of `file_type` between `after` and `before` with enough
+    /// files not superseded by an earlier compaction
+    pub async fn plan(
+        &self,
+        file_type: FileType,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PendingCompaction>> {
+        let mut hours: BTreeMap<DateTime<Utc>, Vec<FileInfo>> = BTreeMap::new();
+        for info in self.store.list_all(file_type, after, before).await? {
+            hours.entry(hour_of(info.timestamp)).or_default().push(info);
+        }
+        let manifests = manifests(&self.store, file_type).await?;
+        Ok(pending_hours(file_type, hours, manifests, self.min_files))
+    }
+
+    /// Merge the files of `pending` into a compacted object, staged in
+    /// `work_dir`, and supersede them with its manifest
+    pub async fn compact(
+        &self,
+        pending: PendingCompaction,
+        work_dir: &Path,
+    ) -> Result<CompactionManifest> {
+        let lease_key = lease_key(pending.file_type, pending.hour);
+        let lease = self.acquire(&lease_key).await?;
+        let result = self
+            .compact_leased(pending, work_dir, &lease_key, &lease)
+            .await;
+        // the lease expires anyway if it can't be released
+        match self.check_lease(&lease_key, &lease).await {
+            Ok(()) => {
+                if let Err(err) = self.store.remove(&lease_key).await {
+                    tracing::warn!(
+                        %lease_key,
+                        ?err,
+                        "failed to release compaction lease"
+                    );
+                }
+            }
+            Err(err) => tracing::warn!(%lease_key, ?err, "compaction lease lost"),
+        }
+        result
+    }
+
+    /// Take the lease at `key`, unless a concurrent run holds it
+    async fn acquire(&self, key: &str) -> Result<CompactionLease> {
+        let now = Utc::now();
+        if let Some(lease) = fetch_if_any::<CompactionLease>(&self.store, key).await? {
+            if lease.is_held(now) {
+                return Err(Error::CompactionConflict(format!("{key} held")));
+            }
+        }
+        let lease = CompactionLease::new(now);
+        self.store
+            .put_bytes(key, serde_json::to_vec(&lease)?)
+            .await?;
+        tokio::time::sleep(LEASE_SETTLE).await;
+        self.check_lease(key, &lease).await?;
+        Ok(lease)
+    }
+
+    /// Fail unless `lease` is still the unexpired lease at `key`
+    async fn check_lease(&self, key: &str, lease: &CompactionLease) -> Result {
+        match fetch_if_any::<CompactionLease>(&self.store, key).await? {
+            Some(current) if current == *lease && current.is_held(Utc::now()) => Ok(()),
+            _ => Err(Error::CompactionConflict(format!("{key} lost"))),
+        }
+    }
+
+    async fn compact_leased(
+        &self,
+        pending: PendingCompaction,
+        work_dir: &Path,
+        lease_key: &str,
+        lease: &CompactionLease,
+    ) -> Result<CompactionManifest> {
+        let manifest_key = manifest_key(pending.file_type, pending.hour);
+        let current: Option<CompactionManifest> = fetch_if_any(&self.store, &manifest_key).await?;
+        if current != pending.previous {
+            return Err(Error::CompactionConflict(format!(
+                "{manifest_key} replaced since planned"
+            )));
+        }
+
+        let created_at = Utc::now();
+        let compacted = compacted_key(pending.file_type, pending.hour, created_at);
+        let staged = work_dir.join(compacted.replace('/', "_"));
+
+        let records = match self.merge(&pending.files, &staged).await {
+            Ok(records) => records,
+            Err(err) => {
+                let _ = fs::remove_file(&staged).await;
+                return Err(err);
+            }
+        };
+        self.store.put_key(&staged, &compacted).await?;
+        fs::remove_file(&staged).await?;
+
+        let manifest = CompactionManifest {
+            file_type: pending.file_type.to_string(),
+            hour: pending.hour,
+            compacted,
+            superseded: pending.files.into_iter().map(|info| info.key).collect(),
+            records,
+            created_at,
+        };
+        if let Err(err) = self.check_lease(lease_key, lease).await {
+            self.store.remove(&manifest.compacted).await?;
+            return Err(err);
+        }
+        self.store
+            .put_bytes(&manifest_key, serde_json::to_vec_pretty(&manifest)?)
+            .await?;
+        if let Some(previous) = pending.previous {
+            self.store.remove(&previous.compacted).await?;
+        }
+        tracing::info!(
+            file_type = %pending.file_type,
+            hour = %pending.hour,
+            files = manifest.superseded.len(),
+            records,
+            "compacted output files"
+        );
+        Ok(manifest)
+    }
+
+    /// Write the records of `files`, in order, to a new file at `path`.
+    /// Returns the number of records written.
+    async fn merge(&self, files: &[FileInfo], path: &Path) -> Result<u64> {
+        let file = File::create(path).await?;
+        let mut transport = FramedWrite::new(
+            GzipEncoder::new(BufWriter::new(file)),
+            LengthDelimitedCodec::builder()
+                .max_frame_length(MAX_FRAME_LENGTH)
+                .new_codec(),
+        );
+        let infos: FileInfoStream = stream::iter(files.to_vec().into_iter().map(Ok)).boxed();
+        let mut records = self.store.source(infos);
+        let mut count = 0;
+        while let Some(record) = records.try_next().await? {
+            transport.send(record.freeze()).await?;
+            count += 1;
+        }
+        SinkExt::<Bytes>::flush(&mut transport).await?;
+        transport.get_mut().shutdown().await?;
+        Ok(count)
+    }
+}
+
+/// The files of `file_type` between `after` and `before` with the compacted
+/// object of each compacted hour in place of the files it supersedes. Hours
+/// only partially within the range are returned whole if compacted.
+pub async fn list_compacted(
+    store: &FileStore,
+    file_type: FileType,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<Vec<FileInfo>> {
+    let originals = store.list_all(file_type, after, before).await?;
+    let manifests = manifests(store, file_type).await?;
+    replace_superseded(originals, &manifests)
+}
+
+/// [`list_compacted`] as a stream, for readers of a range of files
+pub fn stream_compacted(
+    store: &FileStore,
+    file_type: FileType,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> FileInfoStream {
+    let store = store.clone();
+    stream::once(async move { list_compacted(&store, file_type, after, before).await })
+        .map_ok(|infos| stream::iter(infos.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+}
+
+/// The hours of `hours` with at least `min_files` files, some of which are
+/// not superseded by the manifest of the hour
+fn pending_hours(
+    file_type: FileType,
+    hours: BTreeMap<DateTime<Utc>, Vec<FileInfo>>,
+    mut manifests: BTreeMap<DateTime<Utc>, CompactionManifest>,
+    min_files: usize,
+) -> Vec<PendingCompaction> {
+    hours
+        .into_iter()
+        .filter_map(|(hour, files)| {
+            let previous = manifests.remove(&hour);
+            let superseded: HashSet<&String> = previous
+                .iter()
+                .flat_map(|manifest| &manifest.superseded)
+                .collect();
+            let fresh = files.iter().any(|info| !superseded.contains(&info.key));
+            (fresh && files.len() >= min_files).then_some(PendingCompaction {
+                file_type,
+                hour,
+                files,
+                previous,
+            })
+        })
+        .collect()
+}
+
+/// `originals` with the compacted object of each manifest in place of the
+/// files it supersedes, by timestamp
+fn replace_superseded(
+    originals: Vec<FileInfo>,
+    manifests: &BTreeMap<DateTime<Utc>, CompactionManifest>,
+) -> Result<Vec<FileInfo>> {
+    let mut superseded = HashSet::new();
+    let mut infos = Vec::new();
+    for info in originals {
+        if superseded.contains(&info.key) {
+            continue;
+        }
+        match manifests.get(&hour_of(info.timestamp)) {
+            Some(manifest) if manifest.superseded.contains(&info.key) => {
+                superseded.extend(manifest.superseded.iter().cloned());
+                infos.push(FileInfo::from_str(&manifest.compacted)?);
+            }
+            _ => infos.push(info),
+        }
+    }
+    infos.sort_by_key(|info| info.timestamp);
+    Ok(infos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn compacted_keys_decode_as_the_hour_of_their_file_type() {
+        let hour = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let created_at = Utc.with_ymd_and_hms(2023, 5, 2, 9, 30, 0).unwrap();
+        let key = compacted_key(FileType::IotPoc, hour, created_at);
+        let info = FileInfo::from_str(&key).expect("file info");
+        assert_eq!(info.key, key);
+        assert_eq!(info.file_type, FileType::IotPoc);
+        assert_eq!(info.timestamp, hour);
+    }
+
+    fn file(hour: DateTime<Utc>, minutes: i64) -> FileInfo {
+        FileInfo::from((FileType::IotPoc, hour + Duration::minutes(minutes)))
+    }
+
+    fn manifest(hour: DateTime<Utc>, superseded: &[&FileInfo]) -> CompactionManifest {
+        CompactionManifest {
+            file_type: FileType::IotPoc.to_string(),
+            hour,
+            compacted: compacted_key(FileType::IotPoc, hour, hour + Duration::days(1)),
+            superseded: superseded.iter().map(|info| info.key.clone()).collect(),
+            records: 10,
+            created_at: hour + Duration::days(1),
+        }
+    }
+
+    fn keys(infos: &[FileInfo]) -> Vec<&str> {
+        infos.iter().map(|info| info.key.as_str()).collect()
+    }
+
+    #[test]
+    fn only_hours_with_files_not_superseded_are_pending() {
+        let compacted = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let grown = compacted + Duration::hours(1);
+        let fresh = compacted + Duration::hours(2);
+        let small = compacted + Duration::hours(3);
+        let (c1, c2) = (file(compacted, 1), file(compacted, 2));
+        let (g1, g2, g3) = (file(grown, 1), file(grown, 2), file(grown, 3));
+        let hours = BTreeMap::from([
+            (compacted, vec![c1.clone(), c2.clone()]),
+            (grown, vec![g1.clone(), g2.clone(), g3]),
+            (fresh, vec![file(fresh, 1), file(fresh, 2)]),
+            (small, vec![file(small, 1)]),
+        ]);
+        let manifests = BTreeMap::from([
+            (compacted, manifest(compacted, &[&c1, &c2])),
+            (grown, manifest(grown, &[&g1, &g2])),
+        ]);
+
+        let pending = pending_hours(FileType::IotPoc, hours, manifests, 2);
+        let pending: Vec<_> = pending
+            .iter()
+            .map(|pending| {
+                (
+                    pending.hour,
+                    pending.files.len(),
+                    pending.previous.is_some(),
+                )
+            })
+            .collect();
+        // a grown hour is compacted again, replacing its manifest
+        assert_eq!(pending, vec![(grown, 3, true), (fresh, 2, false)]);
+    }
+
+    #[test]
+    fn compacted_objects_replace_the_files_they_supersede() {
+        let hour = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let next = hour + Duration::hours(1);
+        let (h1, h2, late) = (file(hour, 1), file(hour, 2), file(hour, 30));
+        let n1 = file(next, 1);
+        let manifest = manifest(hour, &[&h1, &h2]);
+        let manifests = BTreeMap::from([(hour, manifest.clone())]);
+
+        let infos = replace_superseded(vec![h1.clone(), h2, late.clone(), n1.clone()], &manifests)
+            .expect("listing");
+        // written after the hour was compacted, so not superseded
+        assert_eq!(
+            keys(&infos),
+            vec![
+                manifest.compacted.as_str(),
+                late.key.as_str(),
+                n1.key.as_str()
+            ]
+        );
+        assert_eq!(infos[0].timestamp, hour);
+
+        // the compacted object is listed once, whichever original is listed
+        let infos = replace_superseded(vec![h1], &manifests).expect("listing");
+        assert_eq!(keys(&infos), vec![manifest.compacted.as_str()]);
+    }
+
+    #[test]
+    fn leases_expire() {
+        let now = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let lease = CompactionLease::new(now);
+        assert!(lease.is_held(now + Duration::minutes(59)));
+        assert!(!lease.is_held(now + Duration::hours(LEASE_HOURS)));
+        assert_ne!(
+            lease.owner,
+            CompactionLease::new(now + Duration::seconds(1)).owner
+        );
+        // leases are never taken for manifests
+        assert!(!lease_key(FileType::IotPoc, now).ends_with(".manifest.json"));
+    }
+}
@@ -32,6 +32,15 @@ pub enum Error {
     SendTimeout,
     #[error("shutting down")]
     Shutdown,
+    #[error("file sink failed: {0}")]
+    SinkFailed(String),
+    #[error("namespace not allowed: {0}")]
+    NamespaceNotAllowed(String),
+    #[error("compaction conflict: {0}")]
+    CompactionConflict(String),
+    #[cfg(feature = "fault-injection")]
+    #[error("injected {0} fault")]
+    InjectedFault(&'static str),
 }
 
 #[derive(Error, Debug)]
@@ -56,6 +65,14 @@ pub enum DecodeError {
     UnsupportedStatusReason(String, i32),
     #[error("invalid unix timestamp {0}")]
     InvalidTimestamp(u64),
+    #[error("oversized frame in {key}, length: {length}, offset: {offset}")]
+    FrameTooLarge {
+        key: String,
+        length: usize,
+        offset: u64,
+    },
+    #[error("invalid hash length {0}")]
+    InvalidHashLength(usize),
 }
 
 #[derive(Error, Debug)]
@@ -124,6 +141,18 @@ impl DecodeError {
         Error::Decode(Self::InvalidTimestamp(v))
     }
 
+    pub fn frame_too_large<E: ToString>(key: E, length: usize, offset: u64) -> Error {
+        Error::Decode(Self::FrameTooLarge {
+            key: key.to_string(),
+            length,
+            offset,
+        })
+    }
+
+    pub fn invalid_hash_length(length: usize) -> Error {
+        Error::Decode(Self::InvalidHashLength(length))
+    }
+
     pub fn unsupported_status_reason<E: ToString>(msg1: E, msg2: i32) -> Error {
         Error::Decode(Self::UnsupportedInvalidReason(msg1.to_string(), msg2))
     }
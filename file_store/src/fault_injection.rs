@@ -0,0 +1,84 @@
+//! Failures injected into bucket and grpc requests, for integration tests of
+//! the recovery of services from a failing bucket or config service.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const FAULT_COUNTER: &str = "file_store_injected_fault";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// Rate, from 0 to 1, at which the requests of each operation fail, e.g.
+    /// `error_rates = { get = 0.1, put = 0.05 }`. Default none
+    #[serde(default)]
+    pub error_rates: HashMap<String, f64>,
+}
+
+impl Settings {
+    /// Fail a request of `operation` at its configured rate
+    pub fn inject(&self, operation: &'static str) -> Result {
+        if fails(&self.error_rates, operation) {
+            tracing::debug!(operation, "injecting bucket request failure");
+            return Err(Error::InjectedFault(operation));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RpcSettings {
+    /// Rate, from 0 to 1, at which the requests of each rpc fail, e.g.
+    /// `error_rates = { info = 0.1 }`. Default none
+    #[serde(default)]
+    pub error_rates: HashMap<String, f64>,
+}
+
+impl RpcSettings {
+    /// Fail a request of `rpc` at its configured rate, as an unavailable
+    /// service would
+    pub fn inject(&self, rpc: &'static str) -> std::result::Result<(), tonic::Status> {
+        if fails(&self.error_rates, rpc) {
+            tracing::debug!(rpc, "injecting rpc failure");
+            return Err(tonic::Status::unavailable(format!("injected {rpc} fault")));
+        }
+        Ok(())
+    }
+}
+
+fn fails(error_rates: &HashMap<String, f64>, operation: &'static str) -> bool {
+    let rate = error_rates.get(operation).copied().unwrap_or_default();
+    let fails = rate > 0.0 && rand::random::<f64>() < rate;
+    if fails {
+        metrics::increment_counter!(FAULT_COUNTER, "operation" => operation);
+    }
+    fails
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults_follow_rates() {
+        let settings = Settings {
+            error_rates: HashMap::from([("get".to_string(), 1.0), ("put".to_string(), 0.0)]),
+        };
+        assert!(matches!(
+            settings.inject("get"),
+            Err(Error::InjectedFault("get"))
+        ));
+        assert!(settings.inject("put").is_ok());
+        assert!(settings.inject("list").is_ok());
+    }
+
+    #[test]
+    fn rpc_faults_are_unavailable() {
+        let settings = RpcSettings {
+            error_rates: HashMap::from([("info".to_string(), 1.0)]),
+        };
+        let status = settings.inject("info").unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert!(settings.inject("info_batch").is_ok());
+    }
+}
@@ -124,10 +124,25 @@ pub const DATA_TRANSFER_SESSION_INGEST_REPORT: &str = "data_transfer_session_ing
 pub const INVALID_DATA_TRANSFER_SESSION_INGEST_REPORT: &str =
     "invalid_data_transfer_session_ingest_report";
 pub const VALID_DATA_TRANSFER_SESSION: &str = "valid_data_transfer_session";
+pub const UNPAID_DATA_TRANSFER_SESSION: &str = "unpaid_data_transfer_session";
 pub const PRICE_REPORT: &str = "price_report";
 pub const MOBILE_REWARD_SHARE: &str = "mobile_reward_share";
 pub const MAPPER_MSG: &str = "mapper_msg";
 pub const COVERAGE_OBJECT_INGEST_REPORT: &str = "coverage_object_ingest_report";
+pub const IOT_BALANCE_WARNING: &str = "iot_balance_warning";
+pub const IOT_BEACON_CADENCE_REPORT: &str = "iot_beacon_cadence_report";
+pub const IOT_PACKET_PRICE: &str = "iot_packet_price";
+pub const IOT_GATEWAY_RECONCILIATION: &str = "iot_gateway_reconciliation";
+pub const IOT_WITNESS_INCLUSION: &str = "iot_witness_inclusion";
+pub const IOT_VERIFICATION_BYPASS: &str = "iot_verification_bypass";
+pub const IOT_HEX_SCALE_COMPARISON: &str = "iot_hex_scale_comparison";
+pub const IOT_HEX_DENSITY_SNAPSHOT: &str = "iot_hex_density_snapshot";
+pub const IOT_WITNESS_QUALITY: &str = "iot_witness_quality";
+pub const IOT_WITNESS_RSSI_CHECK: &str = "iot_witness_rssi_check";
+pub const IOT_GATEWAY_REWARD_CLASS: &str = "iot_gateway_reward_class";
+pub const MOBILE_REWARD_DUST: &str = "mobile_reward_dust";
+pub const MOBILE_ONBOARDING_REJECTION: &str = "mobile_onboarding_rejection";
+pub const IOT_UNALLOCATED_REWARD: &str = "iot_unallocated_reward";
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Copy, strum::EnumCount)]
 #[serde(rename_all = "snake_case")]
@@ -157,6 +172,7 @@ pub enum FileType {
     DataTransferSessionIngestReport,
     InvalidDataTransferSessionIngestReport,
     ValidDataTransferSession,
+    UnpaidDataTransferSession,
     PriceReport,
     MobileRewardShare,
     SubscriberLocationReq,
@@ -164,6 +180,20 @@ pub enum FileType {
     VerifiedSubscriberLocationIngestReport,
     MapperMsg,
     CoverageObjectIngestReport,
+    IotBalanceWarning,
+    IotBeaconCadenceReport,
+    IotPacketPrice,
+    IotGatewayReconciliation,
+    IotWitnessInclusion,
+    IotVerificationBypass,
+    IotHexScaleComparison,
+    IotHexDensitySnapshot,
+    IotWitnessQuality,
+    IotWitnessRssiCheck,
+    IotGatewayRewardClass,
+    MobileRewardDust,
+    MobileOnboardingRejection,
+    IotUnallocatedReward,
 }
 
 impl fmt::Display for FileType {
@@ -201,10 +231,25 @@ impl fmt::Display for FileType {
                 INVALID_DATA_TRANSFER_SESSION_INGEST_REPORT
             }
             Self::ValidDataTransferSession => VALID_DATA_TRANSFER_SESSION,
+            Self::UnpaidDataTransferSession => UNPAID_DATA_TRANSFER_SESSION,
             Self::PriceReport => PRICE_REPORT,
             Self::MobileRewardShare => MOBILE_REWARD_SHARE,
             Self::MapperMsg => MAPPER_MSG,
             Self::CoverageObjectIngestReport => COVERAGE_OBJECT_INGEST_REPORT,
+            Self::IotBalanceWarning => IOT_BALANCE_WARNING,
+            Self::IotBeaconCadenceReport => IOT_BEACON_CADENCE_REPORT,
+            Self::IotPacketPrice => IOT_PACKET_PRICE,
+            Self::IotGatewayReconciliation => IOT_GATEWAY_RECONCILIATION,
+            Self::IotWitnessInclusion => IOT_WITNESS_INCLUSION,
+            Self::IotVerificationBypass => IOT_VERIFICATION_BYPASS,
+            Self::IotHexScaleComparison => IOT_HEX_SCALE_COMPARISON,
+            Self::IotHexDensitySnapshot => IOT_HEX_DENSITY_SNAPSHOT,
+            Self::IotWitnessQuality => IOT_WITNESS_QUALITY,
+            Self::IotWitnessRssiCheck => IOT_WITNESS_RSSI_CHECK,
+            Self::IotGatewayRewardClass => IOT_GATEWAY_REWARD_CLASS,
+            Self::MobileRewardDust => MOBILE_REWARD_DUST,
+            Self::MobileOnboardingRejection => MOBILE_ONBOARDING_REJECTION,
+            Self::IotUnallocatedReward => IOT_UNALLOCATED_REWARD,
         };
         f.write_str(s)
     }
@@ -245,10 +290,25 @@ impl FileType {
                 INVALID_DATA_TRANSFER_SESSION_INGEST_REPORT
             }
             Self::ValidDataTransferSession => VALID_DATA_TRANSFER_SESSION,
+            Self::UnpaidDataTransferSession => UNPAID_DATA_TRANSFER_SESSION,
             Self::PriceReport => PRICE_REPORT,
             Self::MobileRewardShare => MOBILE_REWARD_SHARE,
             Self::MapperMsg => MAPPER_MSG,
             Self::CoverageObjectIngestReport => COVERAGE_OBJECT_INGEST_REPORT,
+            Self::IotBalanceWarning => IOT_BALANCE_WARNING,
+            Self::IotBeaconCadenceReport => IOT_BEACON_CADENCE_REPORT,
+            Self::IotPacketPrice => IOT_PACKET_PRICE,
+            Self::IotGatewayReconciliation => IOT_GATEWAY_RECONCILIATION,
+            Self::IotWitnessInclusion => IOT_WITNESS_INCLUSION,
+            Self::IotVerificationBypass => IOT_VERIFICATION_BYPASS,
+            Self::IotHexScaleComparison => IOT_HEX_SCALE_COMPARISON,
+            Self::IotHexDensitySnapshot => IOT_HEX_DENSITY_SNAPSHOT,
+            Self::IotWitnessQuality => IOT_WITNESS_QUALITY,
+            Self::IotWitnessRssiCheck => IOT_WITNESS_RSSI_CHECK,
+            Self::IotGatewayRewardClass => IOT_GATEWAY_REWARD_CLASS,
+            Self::MobileRewardDust => MOBILE_REWARD_DUST,
+            Self::MobileOnboardingRejection => MOBILE_ONBOARDING_REJECTION,
+            Self::IotUnallocatedReward => IOT_UNALLOCATED_REWARD,
         }
     }
 }
@@ -289,10 +349,25 @@ impl FromStr for FileType {
                 Self::InvalidDataTransferSessionIngestReport
             }
             VALID_DATA_TRANSFER_SESSION => Self::ValidDataTransferSession,
+            UNPAID_DATA_TRANSFER_SESSION => Self::UnpaidDataTransferSession,
             PRICE_REPORT => Self::PriceReport,
             MOBILE_REWARD_SHARE => Self::MobileRewardShare,
             MAPPER_MSG => Self::MapperMsg,
             COVERAGE_OBJECT_INGEST_REPORT => Self::CoverageObjectIngestReport,
+            IOT_BALANCE_WARNING => Self::IotBalanceWarning,
+            IOT_BEACON_CADENCE_REPORT => Self::IotBeaconCadenceReport,
+            IOT_PACKET_PRICE => Self::IotPacketPrice,
+            IOT_GATEWAY_RECONCILIATION => Self::IotGatewayReconciliation,
+            IOT_WITNESS_INCLUSION => Self::IotWitnessInclusion,
+            IOT_VERIFICATION_BYPASS => Self::IotVerificationBypass,
+            IOT_HEX_SCALE_COMPARISON => Self::IotHexScaleComparison,
+            IOT_HEX_DENSITY_SNAPSHOT => Self::IotHexDensitySnapshot,
+            IOT_WITNESS_QUALITY => Self::IotWitnessQuality,
+            IOT_WITNESS_RSSI_CHECK => Self::IotWitnessRssiCheck,
+            IOT_GATEWAY_REWARD_CLASS => Self::IotGatewayRewardClass,
+            MOBILE_REWARD_DUST => Self::MobileRewardDust,
+            MOBILE_ONBOARDING_REJECTION => Self::MobileOnboardingRejection,
+            IOT_UNALLOCATED_REWARD => Self::IotUnallocatedReward,
             _ => return Err(Error::from(io::Error::from(io::ErrorKind::InvalidInput))),
         };
         Ok(result)
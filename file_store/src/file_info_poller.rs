@@ -27,6 +27,7 @@ where
         self,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     ) -> Result<BoxStream<'static, T>> {
+        poc_metrics::status::pending(self.file_info.file_type.to_str()).decrement();
         db::insert(transaction, self.file_info).await?;
         Ok(self.stream)
     }
@@ -95,11 +96,21 @@ where
                 }
                 _ = cleanup_trigger.tick() => self.clean(&cache).await?,
                 _ = poll_trigger.tick() => {
+                    if let Some(processed) = db::latest_ts(&self.db, self.file_type).await? {
+                        poc_metrics::status::processed_up_to(
+                            self.file_type.to_str(),
+                            processed.timestamp() as u64,
+                        );
+                    }
                     let files = self.store.list_all(self.file_type, after, before).await?;
                     for file in files {
                         if !is_already_processed(&self.db, &cache, &file).await? {
                             if send_stream(&sender, &self.store, file.clone()).await? {
                                 latest_ts = Some(file.timestamp);
+                                poc_metrics::status::watermark(
+                                    self.file_type.to_str(),
+                                    file.timestamp.timestamp() as u64,
+                                );
                                 cache_file(&cache, &file).await;
                             } else {
                                 tracing::info!("FileInfoPoller: channel full");
@@ -169,6 +180,10 @@ where
         })
         .boxed();
 
+    // counted as pending before sending so the consumer can't take it off
+    // the count first
+    let pending = poc_metrics::status::pending(file.file_type.to_str());
+    pending.increment();
     let incoming_data_stream = FileInfoStream {
         file_info: file,
         stream,
@@ -176,8 +191,14 @@ where
 
     match sender.try_send(incoming_data_stream) {
         Ok(_) => Ok(true),
-        Err(TrySendError::Full(_)) => Ok(false),
-        Err(TrySendError::Closed(_)) => Err(Error::channel()),
+        Err(TrySendError::Full(_)) => {
+            pending.decrement();
+            Ok(false)
+        }
+        Err(TrySendError::Closed(_)) => {
+            pending.decrement();
+            Err(Error::channel())
+        }
     }
 }
 
@@ -4,6 +4,7 @@ use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
 use futures::SinkExt;
 use metrics::Label;
+use poc_metrics::status::QueueDepth;
 use std::{
     io, mem,
     path::{Path, PathBuf},
@@ -13,7 +14,7 @@ use tokio::{
     io::{AsyncWriteExt, BufWriter},
     sync::{
         mpsc::{self, error::SendTimeoutError},
-        oneshot,
+        oneshot, watch,
     },
     time,
 };
@@ -56,6 +57,13 @@ fn message_channel(size: usize) -> (MessageSender, MessageReceiver) {
     mpsc::channel(size)
 }
 
+/// Limits of a block of coalesced writes, see [`FileSinkBuilder::coalesce`]
+#[derive(Debug, Clone, Copy)]
+struct Coalesce {
+    max_messages: usize,
+    max_delay: time::Duration,
+}
+
 pub struct FileSinkBuilder {
     prefix: String,
     target_path: PathBuf,
@@ -64,6 +72,8 @@ pub struct FileSinkBuilder {
     roll_time: Duration,
     deposits: Option<file_upload::MessageSender>,
     auto_commit: bool,
+    commit_on_close: bool,
+    coalesce: Option<Coalesce>,
     metric: &'static str,
     shutdown_listener: triggered::Listener,
 }
@@ -83,6 +93,8 @@ impl FileSinkBuilder {
             roll_time: Duration::minutes(DEFAULT_SINK_ROLL_MINS),
             deposits: None,
             auto_commit: true,
+            commit_on_close: false,
+            coalesce: None,
             metric,
             shutdown_listener,
         }
@@ -117,6 +129,16 @@ impl FileSinkBuilder {
         }
     }
 
+    /// Commit the active file once every client is dropped, handing it over
+    /// for upload before `run` returns. Auto committing sinks otherwise leave
+    /// it in the tmp path, to be deposited on the next start.
+    pub fn commit_on_close(self, commit_on_close: bool) -> Self {
+        Self {
+            commit_on_close,
+            ..self
+        }
+    }
+
     pub fn roll_time(self, duration: Duration) -> Self {
         Self {
             roll_time: duration,
@@ -124,12 +146,32 @@ impl FileSinkBuilder {
         }
     }
 
+    /// Coalesce writes into blocks of up to `max_messages` messages, written
+    /// and flushed through the compressor together once the block is full or
+    /// `max_delay` after its first message. Each write is otherwise flushed
+    /// on its own, which is costly for streams of many small messages. Writes
+    /// are only acknowledged once their block is written. Pending blocks are
+    /// written before commits, rollbacks, rolls and shutdown.
+    pub fn coalesce(self, max_messages: usize, max_delay: Duration) -> Self {
+        Self {
+            coalesce: Some(Coalesce {
+                max_messages: max_messages.max(1),
+                max_delay: max_delay.to_std().unwrap_or_default(),
+            }),
+            ..self
+        }
+    }
+
     pub async fn create(self) -> Result<(FileSinkClient, FileSink)> {
         let (tx, rx) = message_channel(50);
+        let (failure_tx, failure_rx) = watch::channel(None);
+        let queue_depth = poc_metrics::status::queue(self.metric);
 
         let client = FileSinkClient {
             sender: tx,
             metric: self.metric,
+            queue_depth: queue_depth.clone(),
+            failure: failure_rx,
             shutdown_listener: self.shutdown_listener.clone(),
         };
 
@@ -143,9 +185,15 @@ impl FileSinkBuilder {
             deposits: self.deposits,
             roll_time: self.roll_time,
             messages: rx,
+            queue_depth,
             staged_files: Vec::new(),
             auto_commit: self.auto_commit,
+            commit_on_close: self.commit_on_close,
+            coalesce: self.coalesce,
+            pending: Vec::new(),
+            pending_deadline: None,
             active_sink: None,
+            failure: failure_tx,
             shutdown_listener: self.shutdown_listener,
         };
         sink.init().await?;
@@ -157,6 +205,8 @@ impl FileSinkBuilder {
 pub struct FileSinkClient {
     sender: MessageSender,
     metric: &'static str,
+    queue_depth: QueueDepth,
+    failure: watch::Receiver<Option<String>>,
     shutdown_listener: triggered::Listener,
 }
 
@@ -174,8 +224,21 @@ impl FileSinkClient {
         let bytes = item.encode_to_vec();
         let labels = labels.into_iter().map(Label::from);
 
+        if let Some(reason) = self.failure() {
+            metrics::increment_counter!(
+                self.metric,
+                labels
+                    .chain(std::iter::once(ERROR_LABEL))
+                    .collect::<Vec<Label>>()
+            );
+            return Err(Error::SinkFailed(reason));
+        }
+
+        // counted before sending so the sink can't receive the message first
+        self.queue_depth.increment();
         tokio::select! {
             _ = self.shutdown_listener.clone() => {
+                self.queue_depth.decrement();
                 Err(Error::Shutdown)
             }
             result = self.sender.send_timeout(Message::Data(on_write_tx, bytes), SEND_TIMEOUT) => match result {
@@ -190,6 +253,7 @@ impl FileSinkClient {
                     Ok(on_write_rx)
                 }
                 Err(SendTimeoutError::Closed(_)) => {
+                    self.queue_depth.decrement();
                     metrics::increment_counter!(
                         self.metric,
                         labels
@@ -200,6 +264,7 @@ impl FileSinkClient {
                     Err(Error::channel())
                 }
                 Err(SendTimeoutError::Timeout(_)) => {
+                    self.queue_depth.decrement();
                     tracing::error!("file_sink write failed due to send timeout");
                     Err(Error::SendTimeout)
                 }
@@ -207,6 +272,28 @@ impl FileSinkClient {
         }
     }
 
+    /// Why the sink failed to store a write, if it did. Writes are refused
+    /// from then on, and commits fail, until a rollback discards the files
+    /// of the failed writes
+    pub fn failure(&self) -> Option<String> {
+        self.failure.borrow().clone()
+    }
+
+    /// Resolves once the sink fails to store a write, for producers to halt
+    /// or shed load rather than keep writing into a failed sink
+    pub async fn failed(&self) -> String {
+        let mut failure = self.failure.clone();
+        loop {
+            if let Some(reason) = failure.borrow_and_update().clone() {
+                return reason;
+            }
+            if failure.changed().await.is_err() {
+                // the sink is gone without failing
+                return std::future::pending().await;
+            }
+        }
+    }
+
     pub async fn commit(&self) -> Result<oneshot::Receiver<Result<FileManifest>>> {
         let (on_commit_tx, on_commit_rx) = oneshot::channel();
         self.sender
@@ -232,6 +319,30 @@ impl FileSinkClient {
     }
 }
 
+/// Resolves with the failure of the first of `sinks` to fail storing a
+/// write, naming the sink by its metric. Never resolves without sinks.
+pub async fn first_failure(sinks: &[FileSinkClient]) -> Error {
+    if sinks.is_empty() {
+        return std::future::pending().await;
+    }
+    let failures = sinks.iter().map(|sink| {
+        Box::pin(
+            async move { Error::SinkFailed(format!("{}: {}", sink.metric, sink.failed().await)) },
+        )
+    });
+    futures::future::select_all(failures).await.0
+}
+
+/// Fails once any of `sinks` fails to store a write, for servers to halt on
+/// rather than run on without their output, and ends with `shutdown`
+/// otherwise
+pub async fn halt_on_failure(sinks: Vec<FileSinkClient>, shutdown: triggered::Listener) -> Result {
+    tokio::select! {
+        _ = shutdown => Ok(()),
+        err = first_failure(&sinks) => Err(err),
+    }
+}
+
 #[derive(Debug)]
 pub struct FileSink {
     target_path: PathBuf,
@@ -241,11 +352,19 @@ pub struct FileSink {
     roll_time: Duration,
 
     messages: MessageReceiver,
+    queue_depth: QueueDepth,
     deposits: Option<file_upload::MessageSender>,
     staged_files: Vec<PathBuf>,
     auto_commit: bool,
+    commit_on_close: bool,
+
+    coalesce: Option<Coalesce>,
+    pending: Vec<(oneshot::Sender<Result>, Bytes)>,
+    pending_deadline: Option<time::Instant>,
 
     active_sink: Option<ActiveSink>,
+    /// Why storing a write failed, shared with the clients
+    failure: watch::Sender<Option<String>>,
     shutdown_listener: triggered::Listener,
 }
 
@@ -258,6 +377,8 @@ struct ActiveSink {
 
 impl ActiveSink {
     async fn shutdown(&mut self) -> Result {
+        // frames fed but not yet flushed are still buffered in the transport
+        SinkExt::<Bytes>::flush(&mut self.transport).await?;
         transport_sink(&mut self.transport).shutdown().await?;
         Ok(())
     }
@@ -323,20 +444,24 @@ impl FileSink {
         );
         rollover_timer.set_missed_tick_behavior(time::MissedTickBehavior::Burst);
 
+        let mut closed = false;
         loop {
+            let pending_deadline = self.pending_deadline.unwrap_or_else(time::Instant::now);
             tokio::select! {
                 _ = self.shutdown_listener.clone() => break,
-                _ = rollover_timer.tick() => self.maybe_roll().await?,
+                _ = rollover_timer.tick() => {
+                    if let Err(err) = self.maybe_roll().await {
+                        self.fail(&err);
+                        return Err(err);
+                    }
+                }
+                _ = time::sleep_until(pending_deadline), if self.pending_deadline.is_some() => {
+                    self.write_pending().await
+                }
                 msg = self.messages.recv() => match msg {
                     Some(Message::Data(on_write_tx, bytes)) => {
-                        let res = match self.write(Bytes::from(bytes)).await {
-                            Ok(_) => Ok(()),
-                            Err(err) => {
-                                tracing::error!("failed to store {}: {err:?}", &self.prefix);
-                                Err(err)
-                            }
-                        };
-                        let _ = on_write_tx.send(res);
+                        self.queue_depth.decrement();
+                        self.handle_data(on_write_tx, Bytes::from(bytes)).await;
                     }
                     Some(Message::Commit(on_commit_tx)) => {
                         let res = self.commit().await;
@@ -347,16 +472,26 @@ impl FileSink {
                         let _ = on_rollback_tx.send(res);
                     }
                     None => {
+                        closed = true;
                         break
                     }
                 }
             }
         }
         tracing::info!("stopping file sink {}", &self.prefix);
-        if let Some(active_sink) = self.active_sink.as_mut() {
-            let _ = active_sink.shutdown().await;
-            self.active_sink = None;
+        if closed && self.auto_commit && self.commit_on_close {
+            // every client is gone so nothing more is written, hand the
+            // files over for upload now rather than on the next start
+            self.commit().await?;
+        } else {
+            self.write_pending().await;
+            if let Some(active_sink) = self.active_sink.as_mut() {
+                let _ = active_sink.shutdown().await;
+                self.active_sink = None;
+            }
         }
+        // nothing more is deposited, which lets the uploader finish
+        self.deposits = None;
         Ok(())
     }
 
@@ -383,7 +518,83 @@ impl FileSink {
         Ok(())
     }
 
+    async fn handle_data(&mut self, on_write_tx: oneshot::Sender<Result>, buf: Bytes) {
+        // sent before the clients learned of the failure
+        if let Some(reason) = self.failure.borrow().clone() {
+            let _ = on_write_tx.send(Err(Error::SinkFailed(reason)));
+            return;
+        }
+        let Some(coalesce) = self.coalesce else {
+            let res = self.write(buf).await;
+            if let Err(ref err) = res {
+                tracing::error!("failed to store {}: {err:?}", &self.prefix);
+                self.fail(err);
+            }
+            let _ = on_write_tx.send(res);
+            return;
+        };
+        self.pending_deadline
+            .get_or_insert_with(|| time::Instant::now() + coalesce.max_delay);
+        self.pending.push((on_write_tx, buf));
+        if self.pending.len() >= coalesce.max_messages {
+            self.write_pending().await;
+        }
+    }
+
+    /// Write the block of coalesced messages, acknowledging every message of
+    /// the block with the outcome of the block as a whole
+    async fn write_pending(&mut self) {
+        self.pending_deadline = None;
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = mem::take(&mut self.pending);
+        let mut acks = Vec::with_capacity(pending.len());
+        let mut res = Ok(());
+        for (on_write_tx, buf) in pending {
+            if res.is_ok() {
+                res = self.feed(buf).await;
+            }
+            acks.push(on_write_tx);
+        }
+        if res.is_ok() {
+            res = self.flush().await;
+        }
+        match res {
+            Ok(()) => {
+                for on_write_tx in acks {
+                    let _ = on_write_tx.send(Ok(()));
+                }
+            }
+            Err(err) => {
+                tracing::error!("failed to store {} block: {err:?}", &self.prefix);
+                self.fail(&err);
+                for on_write_tx in acks {
+                    let _ = on_write_tx.send(Err(Error::from(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("block write failed: {err}"),
+                    ))));
+                }
+            }
+        }
+    }
+
+    /// Record that storing a write failed, refusing the writes of every
+    /// client until a rollback
+    fn fail(&self, err: &Error) {
+        self.failure.send_replace(Some(err.to_string()));
+    }
+
     pub async fn commit(&mut self) -> Result<FileManifest> {
+        self.write_pending().await;
+        // files holding failed writes are incomplete
+        if let Some(reason) = self.failure.borrow().clone() {
+            return Err(Error::SinkFailed(reason));
+        }
+        self.commit_staged().await
+    }
+
+    async fn commit_staged(&mut self) -> Result<FileManifest> {
         self.maybe_close_active_sink().await?;
 
         let mut manifest: FileManifest = Vec::new();
@@ -398,7 +609,13 @@ impl FileSink {
     }
 
     pub async fn rollback(&mut self) -> Result<FileManifest> {
-        self.maybe_close_active_sink().await?;
+        self.write_pending().await;
+        if self.failure.borrow().is_some() {
+            // the active sink failed, it is discarded rather than flushed
+            self.active_sink = None;
+        } else {
+            self.maybe_close_active_sink().await?;
+        }
 
         let mut manifest: FileManifest = Vec::new();
         let staged_files = mem::take(&mut self.staged_files);
@@ -407,16 +624,22 @@ impl FileSink {
             fs::remove_file(&staged_file).await?;
             manifest.push(file_name(&staged_file)?);
         }
+        self.failure.send_replace(None);
 
         Ok(manifest)
     }
 
     pub async fn maybe_roll(&mut self) -> Result {
+        // nothing rolls back an auto committed sink, it stops on failure
+        if let (true, Some(reason)) = (self.auto_commit, self.failure.borrow().clone()) {
+            return Err(Error::SinkFailed(reason));
+        }
         if let Some(active_sink) = self.active_sink.as_mut() {
             if (active_sink.time + self.roll_time) <= Utc::now() {
                 if self.auto_commit {
                     self.commit().await?;
                 } else {
+                    self.write_pending().await;
                     self.maybe_close_active_sink().await?;
                 }
             }
@@ -454,6 +677,13 @@ impl FileSink {
     }
 
     pub async fn write(&mut self, buf: Bytes) -> Result {
+        self.feed(buf).await?;
+        self.flush().await
+    }
+
+    /// Hand a frame to the active sink, rolling it first when the frame would
+    /// make it too large. The frame is only written out by a [`Self::flush`].
+    async fn feed(&mut self, buf: Bytes) -> Result {
         let buf_len = buf.len();
 
         match self.active_sink.as_mut() {
@@ -464,7 +694,7 @@ impl FileSink {
                 if active_sink.size + buf_len >= self.max_size {
                     active_sink.shutdown().await?;
                     if self.auto_commit {
-                        self.commit().await?;
+                        self.commit_staged().await?;
                     }
                     self.new_sink().await?;
                 }
@@ -476,7 +706,7 @@ impl FileSink {
         }
 
         if let Some(active_sink) = self.active_sink.as_mut() {
-            active_sink.transport.send(buf).await?;
+            active_sink.transport.feed(buf).await?;
             active_sink.size += buf_len;
             Ok(())
         } else {
@@ -486,6 +716,13 @@ impl FileSink {
             )))
         }
     }
+
+    async fn flush(&mut self) -> Result {
+        if let Some(active_sink) = self.active_sink.as_mut() {
+            SinkExt::<Bytes>::flush(&mut active_sink.transport).await?;
+        }
+        Ok(())
+    }
 }
 
 fn file_name(path_buf: &Path) -> Result<String> {
@@ -547,7 +784,7 @@ mod tests {
         shutdown_trigger.trigger();
         sink_thread.await.expect("file sink did not complete");
 
-        let entropy_file = get_entropy_file(&tmp_dir)
+        let entropy_file = get_entropy_file(tmp_dir.path())
             .await
             .expect("no entropy available");
         assert_eq!("hello", read_file(&entropy_file).await);
@@ -590,7 +827,7 @@ mod tests {
 
         tokio::time::sleep(time::Duration::from_millis(200)).await;
 
-        assert!(get_entropy_file(&tmp_dir).await.is_err());
+        assert!(get_entropy_file(tmp_dir.path()).await.is_err());
         assert_eq!(
             Err(tokio::sync::mpsc::error::TryRecvError::Empty),
             file_upload_rx.try_recv()
@@ -601,7 +838,7 @@ mod tests {
 
         assert!(file_upload_rx.try_recv().is_ok());
 
-        let entropy_file = get_entropy_file(&tmp_dir)
+        let entropy_file = get_entropy_file(tmp_dir.path())
             .await
             .expect("no entropy available");
         assert_eq!("hello", read_file(&entropy_file).await);
@@ -610,6 +847,254 @@ mod tests {
         sink_thread.await.expect("file sink did not complete");
     }
 
+    fn hello() -> Vec<u8> {
+        prost::Message::encode_to_vec(&String::from("hello"))
+    }
+
+    /// Write to a sink and drop its client, returning the dir of the sink
+    /// and whether a file was handed over for upload
+    async fn write_and_close(commit_on_close: bool) -> (TempDir, bool) {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (_shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (file_upload_tx, mut file_upload_rx) = file_upload::message_channel();
+
+        let (file_sink_client, mut file_sink_server) = FileSinkBuilder::new(
+            FileType::EntropyReport,
+            tmp_dir.path(),
+            "fake_metric",
+            shutdown_listener,
+        )
+        .deposits(Some(file_upload_tx))
+        .commit_on_close(commit_on_close)
+        .create()
+        .await
+        .expect("failed to create file sink");
+        let sink_thread = tokio::spawn(async move { file_sink_server.run().await });
+
+        file_sink_client
+            .write(String::from("hello"), [])
+            .await
+            .expect("write refused")
+            .await
+            .expect("write not acknowledged")
+            .expect("write failed");
+        drop(file_sink_client);
+
+        // the sink stops without a shutdown once its clients are gone
+        time::timeout(time::Duration::from_secs(1), sink_thread)
+            .await
+            .expect("file sink did not stop")
+            .expect("file sink panicked")
+            .expect("failed to complete file sink");
+        (tmp_dir, file_upload_rx.try_recv().is_ok())
+    }
+
+    #[tokio::test]
+    async fn commits_when_closed_with_commit_on_close() {
+        let (tmp_dir, uploaded) = write_and_close(true).await;
+        assert!(uploaded);
+        let entropy_file = get_entropy_file(tmp_dir.path())
+            .await
+            .expect("no entropy available");
+        assert_eq!(hello(), read_file(&entropy_file).await.to_vec());
+        assert!(get_entropy_file(&tmp_dir.path().join("tmp")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn leaves_the_active_file_when_closed_by_default() {
+        let (tmp_dir, uploaded) = write_and_close(false).await;
+        assert!(!uploaded);
+        assert!(get_entropy_file(tmp_dir.path()).await.is_err());
+        let entropy_file = get_entropy_file(&tmp_dir.path().join("tmp"))
+            .await
+            .expect("no entropy available");
+        assert_eq!(hello(), read_file(&entropy_file).await.to_vec());
+    }
+
+    #[tokio::test]
+    async fn writes_pending_coalesced_messages_on_shutdown() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, mut file_sink_server) = FileSinkBuilder::new(
+            FileType::EntropyReport,
+            tmp_dir.path(),
+            "fake_metric",
+            shutdown_listener.clone(),
+        )
+        .coalesce(1_000, chrono::Duration::hours(1))
+        .create()
+        .await
+        .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run()
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        let mut acks = Vec::new();
+        for i in 0..10 {
+            let (on_write_tx, on_write_rx) = oneshot::channel();
+            file_sink_client
+                .sender
+                .try_send(Message::Data(
+                    on_write_tx,
+                    format!("hello {i}").into_bytes(),
+                ))
+                .expect("failed to send bytes to file sink");
+            acks.push(on_write_rx);
+        }
+
+        tokio::time::sleep(time::Duration::from_millis(200)).await;
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+
+        for ack in acks {
+            ack.await
+                .expect("write not acknowledged")
+                .expect("write failed");
+        }
+        // the sink was never rolled, its file is left in the tmp path
+        let entropy_file = get_entropy_file(&tmp_dir.path().join("tmp"))
+            .await
+            .expect("no entropy available");
+        let records: Vec<bytes::BytesMut> = file_source::source([entropy_file.path()])
+            .map(|record| record.expect("invalid data in file"))
+            .collect()
+            .await;
+        let expected: Vec<String> = (0..10).map(|i| format!("hello {i}")).collect();
+        assert_eq!(expected, records);
+    }
+
+    #[tokio::test]
+    async fn refuses_writes_after_failing_to_store_until_rollback() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, mut file_sink_server) = FileSinkBuilder::new(
+            FileType::EntropyReport,
+            tmp_dir.path(),
+            "fake_metric",
+            shutdown_listener.clone(),
+        )
+        .auto_commit(false)
+        .create()
+        .await
+        .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run()
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        // no file can be created anymore, as on a full disk
+        fs::remove_dir_all(tmp_dir.path().join("tmp"))
+            .await
+            .expect("failed to remove tmp dir");
+        let ack = file_sink_client
+            .write(String::from("hello"), [])
+            .await
+            .expect("write refused");
+        assert!(ack.await.expect("write not acknowledged").is_err());
+
+        let reason = time::timeout(time::Duration::from_secs(1), file_sink_client.failed())
+            .await
+            .expect("failure not notified");
+        assert_eq!(Some(reason), file_sink_client.failure());
+        let halted = time::timeout(
+            time::Duration::from_secs(1),
+            halt_on_failure(vec![file_sink_client.clone()], shutdown_listener.clone()),
+        )
+        .await
+        .expect("failure did not halt");
+        assert!(
+            matches!(halted, Err(Error::SinkFailed(reason)) if reason.starts_with("fake_metric: "))
+        );
+        assert!(matches!(
+            file_sink_client.write(String::from("again"), []).await,
+            Err(Error::SinkFailed(_))
+        ));
+        let commit = file_sink_client.commit().await.expect("commit failed");
+        assert!(matches!(
+            commit.await.expect("commit not acknowledged"),
+            Err(Error::SinkFailed(_))
+        ));
+
+        // discarding the failed writes takes writes again
+        let rollback = file_sink_client.rollback().await.expect("rollback failed");
+        rollback
+            .await
+            .expect("rollback not acknowledged")
+            .expect("rollback failed");
+        assert_eq!(None, file_sink_client.failure());
+        fs::create_dir_all(tmp_dir.path().join("tmp"))
+            .await
+            .expect("failed to create tmp dir");
+        file_sink_client
+            .write(String::from("hello"), [])
+            .await
+            .expect("write refused")
+            .await
+            .expect("write not acknowledged")
+            .expect("write failed");
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+    }
+
+    /// Throughput of small writes with and without coalescing. Run with
+    /// `cargo test -p file-store --release -- --ignored --nocapture throughput`
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn small_message_throughput() {
+        const MESSAGES: u64 = 100_000;
+
+        for coalesce in [None, Some(256)] {
+            let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+            let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+            let mut builder = FileSinkBuilder::new(
+                FileType::EntropyReport,
+                tmp_dir.path(),
+                "fake_metric",
+                shutdown_listener.clone(),
+            );
+            if let Some(max_messages) = coalesce {
+                builder = builder.coalesce(max_messages, chrono::Duration::milliseconds(100));
+            }
+            let (file_sink_client, mut file_sink_server) =
+                builder.create().await.expect("failed to create file sink");
+            let sink_thread = tokio::spawn(async move { file_sink_server.run().await });
+
+            let start = std::time::Instant::now();
+            let mut last_ack = None;
+            for i in 0..MESSAGES {
+                last_ack = Some(file_sink_client.write(i, []).await.expect("write failed"));
+            }
+            // writes are acknowledged in order
+            if let Some(last_ack) = last_ack {
+                last_ack
+                    .await
+                    .expect("write not acknowledged")
+                    .expect("write failed");
+            }
+            let elapsed = start.elapsed();
+
+            println!(
+                "coalesce {coalesce:?}: {MESSAGES} messages in {elapsed:?}, {:.0} msg/s",
+                MESSAGES as f64 / elapsed.as_secs_f64()
+            );
+
+            shutdown_trigger.trigger();
+            let _ = sink_thread.await;
+        }
+    }
+
     async fn read_file(entry: &DirEntry) -> bytes::BytesMut {
         file_source::source([entry.path()])
             .next()
@@ -618,10 +1103,8 @@ mod tests {
             .expect("invalid data in file")
     }
 
-    async fn get_entropy_file(tmp_dir: &TempDir) -> std::result::Result<DirEntry, String> {
-        let mut entries = fs::read_dir(tmp_dir.path())
-            .await
-            .expect("failed to read tmp dir");
+    async fn get_entropy_file(path: &Path) -> std::result::Result<DirEntry, String> {
+        let mut entries = fs::read_dir(path).await.expect("failed to read tmp dir");
 
         while let Some(entry) = entries.next_entry().await.unwrap() {
             if is_entropy_file(&entry) {
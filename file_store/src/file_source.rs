@@ -1,12 +1,16 @@
-use crate::{file_info_poller::FileInfoPollerBuilder, file_sink, BytesMutStream, Error};
-use async_compression::tokio::bufread::GzipDecoder;
+use crate::{
+    error::DecodeError,
+    file_info_poller::FileInfoPollerBuilder,
+    file_sink,
+    frame::{self, Frame},
+    BytesMutStream, Error,
+};
 use futures::{
     stream::{self},
     StreamExt, TryFutureExt, TryStreamExt,
 };
 use std::path::{Path, PathBuf};
 use tokio::{fs::File, io::BufReader};
-use tokio_util::codec::{length_delimited::LengthDelimitedCodec, FramedRead};
 
 pub fn continuous_source<T>() -> FileInfoPollerBuilder<T>
 where
@@ -25,17 +29,22 @@ where
         .map(|path| path.as_ref().to_path_buf())
         .collect();
     stream::iter(paths)
-        .map(|path| File::open(path).map_err(Error::from))
+        .map(|path| {
+            File::open(path.clone())
+                .map_ok(|file| (path, file))
+                .map_err(Error::from)
+        })
         .buffered(2)
         .flat_map(|file| match file {
-            Ok(file) => {
+            Ok((path, file)) => {
                 let buf_reader = BufReader::new(file);
-                let codec = LengthDelimitedCodec::builder()
-                    .max_frame_length(file_sink::MAX_FRAME_LENGTH)
-                    .new_codec();
-
-                FramedRead::new(GzipDecoder::new(buf_reader), codec)
-                    .map_err(Error::from)
+                frame::frames(buf_reader, file_sink::MAX_FRAME_LENGTH)
+                    .map(move |frame| match frame? {
+                        Frame::Data(buf) => Ok(buf),
+                        Frame::Oversized { length, offset } => {
+                            Err(DecodeError::frame_too_large(path.display(), length, offset))
+                        }
+                    })
                     .boxed()
             }
             Err(err) => stream::once(async { Err(err) }).boxed(),
@@ -70,6 +79,8 @@ mod test {
             bucket: "devnet-poc5g-rewards".to_string(),
             endpoint: None,
             region: "us-east-1".to_string(),
+            max_frame_length: file_sink::MAX_FRAME_LENGTH,
+            quarantine_prefix: None,
             access_key_id: None,
             secret_access_key: None,
         };
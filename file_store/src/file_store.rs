@@ -1,19 +1,27 @@
 use crate::{
-    error::DecodeError, BytesMutStream, Error, FileInfo, FileInfoStream, FileType, Result, Settings,
+    error::DecodeError,
+    frame::{self, Frame},
+    telemetry, BytesMutStream, Error, FileInfo, FileInfoStream, FileType, Result, Settings,
 };
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::{types::ByteStream, Client, Endpoint, Region};
 use chrono::{DateTime, Utc};
-use futures::FutureExt;
-use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
+use futures::{stream, StreamExt, TryStreamExt};
 use http::Uri;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
 
 #[derive(Debug, Clone)]
 pub struct FileStore {
     pub(crate) bucket: String,
     client: Client,
+    max_frame_length: usize,
+    quarantine_prefix: Option<String>,
+    namespace: Option<String>,
+    #[cfg(feature = "fault-injection")]
+    faults: crate::fault_injection::Settings,
 }
 
 pub struct FileData {
@@ -23,6 +31,7 @@ pub struct FileData {
 
 impl FileStore {
     pub async fn from_settings(settings: &Settings) -> Result<Self> {
+        let namespace = checked_namespace(settings)?;
         let endpoint: Option<Endpoint> = match &settings.endpoint {
             Some(endpoint) => Uri::from_str(endpoint)
                 .map(Endpoint::immutable)
@@ -54,9 +63,31 @@ impl FileStore {
         Ok(Self {
             client,
             bucket: settings.bucket.clone(),
+            max_frame_length: settings.max_frame_length,
+            quarantine_prefix: settings.quarantine_prefix.clone(),
+            namespace,
+            #[cfg(feature = "fault-injection")]
+            faults: settings.faults.clone(),
         })
     }
 
+    /// Fail a request of `operation` as the fault settings of the store have
+    /// it, when built with the `fault-injection` feature
+    #[cfg(feature = "fault-injection")]
+    fn inject_fault(&self, operation: &'static str) -> Result {
+        self.faults.inject(operation)
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    fn inject_fault(&self, _operation: &'static str) -> Result {
+        Ok(())
+    }
+
+    /// The key in the bucket of `key` in the store's namespace
+    fn namespaced(&self, key: &str) -> String {
+        namespaced(&self.namespace, key)
+    }
+
     pub async fn list_all<A, B, F>(
         &self,
         file_type: F,
@@ -77,23 +108,36 @@ impl FileStore {
         A: Into<Option<DateTime<Utc>>> + Copy,
         B: Into<Option<DateTime<Utc>>> + Copy,
     {
+        if let Err(err) = self.inject_fault("list") {
+            return stream::once(async move { Err(err) }).boxed();
+        }
         let file_type = file_type.into();
         let before = before.into();
         let after = after.into();
+        let namespace = self.namespace.clone();
+        let bucket = self.bucket.clone();
 
         let request = self
             .client
             .list_objects_v2()
             .bucket(&self.bucket)
-            .prefix(file_type.to_string())
-            .set_start_after(after.map(|dt| FileInfo::from((file_type, dt)).into()));
+            .prefix(self.namespaced(file_type.to_str()))
+            .set_start_after(after.map(|dt| self.namespaced(&FileInfo::from((file_type, dt)).key)));
 
         futures::stream::unfold(
-            (request, true, None),
-            |(req, first_time, next)| async move {
+            (request, bucket, true, None),
+            move |(req, bucket, first_time, next)| async move {
                 if first_time || next.is_some() {
+                    let start = Instant::now();
                     let list_objects_response =
                         req.clone().set_continuation_token(next).send().await;
+                    telemetry::record_request(
+                        "list",
+                        &bucket,
+                        file_type.to_str(),
+                        start,
+                        &list_objects_response,
+                    );
 
                     let next_token = list_objects_response
                         .as_ref()
@@ -101,7 +145,7 @@ impl FileStore {
                         .and_then(|r| r.next_continuation_token())
                         .map(|x| x.to_owned());
 
-                    Some((list_objects_response, (req, false, next_token)))
+                    Some((list_objects_response, (req, bucket, false, next_token)))
                 } else {
                     None
                 }
@@ -114,8 +158,11 @@ impl FileStore {
                     .unwrap_or_default()
                     .into_iter()
                     .filter_map(|obj| {
-                        if FileInfo::matches(obj.key().unwrap_or_default()) {
-                            Some(FileInfo::try_from(&obj).unwrap())
+                        let key = without_namespace(&namespace, obj.key().unwrap_or_default())?;
+                        if FileInfo::matches(key) {
+                            let mut info = FileInfo::from_str(key).unwrap();
+                            info.size = obj.size() as usize;
+                            Some(info)
                         } else {
                             None
                         }
@@ -131,61 +178,207 @@ impl FileStore {
     }
 
     pub async fn put(&self, file: &Path) -> Result {
+        let key = file.file_name().unwrap().to_string_lossy().to_string();
+        self.put_key(file, &key).await
+    }
+
+    /// Upload `file` to the given key rather than to its file name
+    pub async fn put_key(&self, file: &Path, key: &str) -> Result {
+        let file_type = telemetry::file_type(key);
         let byte_stream = ByteStream::from_path(&file)
             .await
             .map_err(|_| Error::not_found(format!("could not open {}", file.display())))?;
-        poc_metrics::record_duration!(
-            "file_store_put_duration",
-            self.client
-                .put_object()
+        let size = tokio::fs::metadata(file)
+            .await
+            .map_or(0, |metadata| metadata.len());
+        self.inject_fault("put")?;
+
+        let start = Instant::now();
+        let result = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.namespaced(key))
+            .body(byte_stream)
+            .send()
+            .await;
+        telemetry::record_request("put", &self.bucket, file_type, start, &result);
+        metrics::histogram!("file_store_put_duration", start.elapsed());
+        result.map_err(Error::s3_error)?;
+        telemetry::record_bytes("put", &self.bucket, file_type, size);
+        Ok(())
+    }
+
+    /// Upload `bytes` as the object with the given key
+    pub async fn put_bytes(&self, key: &str, bytes: Vec<u8>) -> Result {
+        let file_type = telemetry::file_type(key);
+        let size = bytes.len() as u64;
+        self.inject_fault("put")?;
+        let start = Instant::now();
+        let result = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.namespaced(key))
+            .body(ByteStream::from(bytes))
+            .send()
+            .await;
+        telemetry::record_request("put", &self.bucket, file_type, start, &result);
+        metrics::histogram!("file_store_put_duration", start.elapsed());
+        result.map_err(Error::s3_error)?;
+        telemetry::record_bytes("put", &self.bucket, file_type, size);
+        Ok(())
+    }
+
+    /// All keys starting with `prefix`, whatever file type they hold if any
+    pub async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut next = None;
+        loop {
+            self.inject_fault("list")?;
+            let output = self
+                .client
+                .list_objects_v2()
                 .bucket(&self.bucket)
-                .key(file.file_name().map(|name| name.to_string_lossy()).unwrap())
-                .body(byte_stream)
+                .prefix(self.namespaced(prefix))
+                .set_continuation_token(next)
                 .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
                 .await
-        )
+                .map_err(Error::s3_error)?;
+            keys.extend(
+                output
+                    .contents()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|obj| without_namespace(&self.namespace, obj.key()?))
+                    .map(str::to_string),
+            );
+            next = output.next_continuation_token().map(str::to_string);
+            if next.is_none() {
+                return Ok(keys);
+            }
+        }
     }
 
     pub async fn remove(&self, key: &str) -> Result {
-        poc_metrics::record_duration!(
-            "file_store_remove_duration",
-            self.client
-                .delete_object()
-                .bucket(&self.bucket)
-                .key(key)
-                .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
-                .await
-        )
+        self.inject_fault("remove")?;
+        let start = Instant::now();
+        let result = self
+            .client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.namespaced(key))
+            .send()
+            .await;
+        let file_type = telemetry::file_type(key);
+        telemetry::record_request("remove", &self.bucket, file_type, start, &result);
+        metrics::histogram!("file_store_remove_duration", start.elapsed());
+        result.map(|_| ()).map_err(Error::s3_error)
+    }
+
+    /// Check the store's bucket can be listed, for preflight checks of
+    /// input buckets
+    pub async fn check_read(&self) -> Result {
+        self.client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(self.namespaced(""))
+            .max_keys(1)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Error::s3_error)
+    }
+
+    /// Check objects can be written to, read from and removed from the
+    /// store's bucket by round tripping a canary object, for preflight checks
+    /// of output buckets
+    pub async fn check_write(&self) -> Result {
+        let key = self.namespaced(&format!(
+            "preflight-canary.{}",
+            Utc::now().timestamp_millis()
+        ));
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .body(ByteStream::from(key.clone().into_bytes()))
+            .send()
+            .await
+            .map_err(Error::s3_error)?;
+        self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .send()
+            .await
+            .map_err(Error::s3_error)?;
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(Error::s3_error)
     }
 
     pub async fn get_raw<K>(&self, key: K) -> Result<ByteStream>
     where
         K: Into<String>,
     {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), key).await
+        let key = key.into();
+        let file_type = telemetry::file_type(&key);
+        self.inject_fault("get")?;
+        let start = Instant::now();
+        let result = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.namespaced(&key))
+            .send()
+            .await;
+        telemetry::record_request("get", &self.bucket, file_type, start, &result);
+        let output = result.map_err(Error::s3_error)?;
+        telemetry::record_bytes(
+            "get",
+            &self.bucket,
+            file_type,
+            output.content_length() as u64,
+        );
+        Ok(output.body)
+    }
+
+    /// The whole body of the object with the given key
+    pub async fn get_bytes<K>(&self, key: K) -> Result<Vec<u8>>
+    where
+        K: Into<String>,
+    {
+        let mut reader = tokio_util::io::StreamReader::new(self.get_raw(key).await?);
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+        Ok(buf)
     }
 
     pub async fn get<K>(&self, key: K) -> Result<BytesMutStream>
     where
         K: Into<String>,
     {
-        Ok(stream_source(self.get_raw(key).await?))
+        let key = key.into();
+        let stream = self.get_raw(key.clone()).await?;
+        Ok(self.stream_source(key, stream))
     }
 
     /// Stream a series of ordered items from the store from remote files with
     /// the given keys.
     pub fn source(&self, infos: FileInfoStream) -> BytesMutStream {
-        let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let store = self.clone();
+        let fetcher = self.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| fetcher.clone().get_keyed_stream(info.key))
             .try_buffered(2)
-            .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+            .flat_map(move |stream| match stream {
+                Ok((key, stream)) => store.stream_source(key, stream),
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
@@ -197,13 +390,13 @@ impl FileStore {
     /// stream of buffers to be produced as soon as available from up to
     /// "worker" number of remote files
     pub fn source_unordered(&self, workers: usize, infos: FileInfoStream) -> BytesMutStream {
-        let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let store = self.clone();
+        let fetcher = self.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| fetcher.clone().get_keyed_stream(info.key))
             .try_buffer_unordered(workers)
-            .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+            .flat_map(move |stream| match stream {
+                Ok((key, stream)) => store.stream_source(key, stream),
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
@@ -211,39 +404,154 @@ impl FileStore {
     }
 
     pub async fn stream_file(&self, file_info: FileInfo) -> Result<BytesMutStream> {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), file_info)
-            .await
-            .map(stream_source)
+        self.get(file_info.key).await
+    }
+
+    /// Copy the file with the given key to the configured quarantine prefix,
+    /// if any. The copy runs in the background and failures are only logged
+    fn quarantine(&self, key: &str) {
+        let Some(prefix) = &self.quarantine_prefix else {
+            return;
+        };
+        let request = self
+            .client
+            .copy_object()
+            .bucket(&self.bucket)
+            .copy_source(format!("{}/{}", self.bucket, self.namespaced(key)))
+            .key(self.namespaced(&format!("{prefix}/{key}")));
+        let key = key.to_string();
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(_) => tracing::info!("quarantined file {key}"),
+                Err(err) => tracing::error!("failed to quarantine file {key}: {err:?}"),
+            }
+        });
+    }
+
+    async fn get_keyed_stream(self, key: String) -> Result<(String, ByteStream)> {
+        let stream = self.get_raw(key.clone()).await?;
+        Ok((key, stream))
+    }
+
+    /// Decode frames one at a time from the given remote byte stream. Frames
+    /// over the maximum frame length are skipped and returned as errors, and
+    /// the file is quarantined the first time one is encountered.
+    fn stream_source(&self, key: String, stream: ByteStream) -> BytesMutStream {
+        use tokio_util::io::StreamReader;
+
+        let store = self.clone();
+        let mut quarantined = false;
+        frame::frames(StreamReader::new(stream), self.max_frame_length)
+            .map(move |frame| match frame? {
+                Frame::Data(buf) => Ok(buf),
+                Frame::Oversized { length, offset } => {
+                    metrics::increment_counter!("file_store_oversized_frame");
+                    tracing::error!(
+                        "skipping oversized frame in {key}, length: {length}, offset: {offset}"
+                    );
+                    if !quarantined {
+                        quarantined = true;
+                        store.quarantine(&key);
+                    }
+                    Err(DecodeError::frame_too_large(&key, length, offset))
+                }
+            })
+            .boxed()
     }
 }
 
-fn stream_source(stream: ByteStream) -> BytesMutStream {
-    use async_compression::tokio::bufread::GzipDecoder;
-    use tokio_util::{
-        codec::{length_delimited::LengthDelimitedCodec, FramedRead},
-        io::StreamReader,
-    };
-
-    Box::pin(
-        FramedRead::new(
-            GzipDecoder::new(StreamReader::new(stream)),
-            LengthDelimitedCodec::new(),
-        )
-        .map_err(Error::from),
-    )
+/// The namespace of the settings, refused when not in the allowed namespaces
+fn checked_namespace(settings: &Settings) -> Result<Option<String>> {
+    let namespace = settings
+        .namespace
+        .as_deref()
+        .map(|namespace| namespace.trim_matches('/'))
+        .filter(|namespace| !namespace.is_empty());
+    if let Some(allowed) = &settings.allowed_namespaces {
+        let is_allowed = namespace.map_or(false, |namespace| {
+            allowed
+                .iter()
+                .any(|allowed| allowed.trim_matches('/') == namespace)
+        });
+        if !is_allowed {
+            return Err(Error::NamespaceNotAllowed(
+                namespace.unwrap_or("<none>").to_string(),
+            ));
+        }
+    }
+    Ok(namespace.map(str::to_string))
+}
+
+fn namespaced(namespace: &Option<String>, key: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}/{key}"),
+        None => key.to_string(),
+    }
 }
 
-async fn get_byte_stream<K>(client: Client, bucket: String, key: K) -> Result<ByteStream>
-where
-    K: Into<String>,
-{
-    client
-        .get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .map_ok(|output| output.body)
-        .map_err(Error::s3_error)
-        .fuse()
-        .await
+/// The key within the namespace of a key in the bucket, None when the key is
+/// outside of the namespace
+fn without_namespace<'a>(namespace: &Option<String>, key: &'a str) -> Option<&'a str> {
+    match namespace {
+        Some(namespace) => key.strip_prefix(namespace)?.strip_prefix('/'),
+        None => Some(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(namespace: Option<&str>, allowed_namespaces: Option<&[&str]>) -> Settings {
+        Settings {
+            bucket: "bucket".to_string(),
+            endpoint: None,
+            region: "us-west-2".to_string(),
+            max_frame_length: crate::file_sink::MAX_FRAME_LENGTH,
+            quarantine_prefix: None,
+            namespace: namespace.map(str::to_string),
+            allowed_namespaces: allowed_namespaces.map(|allowed| {
+                allowed
+                    .iter()
+                    .map(|namespace| namespace.to_string())
+                    .collect()
+            }),
+            access_key_id: None,
+            secret_access_key: None,
+            #[cfg(feature = "fault-injection")]
+            faults: Default::default(),
+        }
+    }
+
+    #[test]
+    fn namespace_must_be_allowed() {
+        assert_eq!(None, checked_namespace(&settings(None, None)).unwrap());
+        assert_eq!(
+            Some("staging".to_string()),
+            checked_namespace(&settings(Some("/staging/"), Some(&["staging"]))).unwrap()
+        );
+        assert!(checked_namespace(&settings(Some("prod"), Some(&["staging"]))).is_err());
+        assert!(checked_namespace(&settings(None, Some(&["staging"]))).is_err());
+    }
+
+    #[test]
+    fn keys_are_namespaced() {
+        let namespace = Some("staging".to_string());
+        let key = "iot_poc.1680000000000.gz";
+        assert_eq!(
+            "staging/iot_poc.1680000000000.gz",
+            namespaced(&namespace, key)
+        );
+        assert_eq!(key, namespaced(&None, key));
+        assert_eq!(
+            Some(key),
+            without_namespace(&namespace, &namespaced(&namespace, key))
+        );
+        assert_eq!(None, without_namespace(&namespace, key));
+        assert_eq!(
+            None,
+            without_namespace(&namespace, "staging2/iot_poc.1680000000000.gz")
+        );
+        assert_eq!(Some(key), without_namespace(&None, key));
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{Error, FileStore, Result, Settings};
+use crate::{telemetry, Error, FileStore, Result, Settings};
 use futures::StreamExt;
 use std::{
     path::{Path, PathBuf},
@@ -47,6 +47,9 @@ impl FileUpload {
                     tracing::warn!("ignoring non file {path_str}");
                     return;
                 }
+                let file_type = path.file_name().map_or("unknown", |name| {
+                    telemetry::file_type(&name.to_string_lossy())
+                });
                 let mut retry = 0;
                 const MAX_RETRIES: u8 = 5;
                 const RETRY_WAIT: Duration = Duration::from_secs(10);
@@ -72,10 +75,15 @@ impl FileUpload {
                                 "failed to store {path_str} in {bucket} retry: {retry}: {err:?}"
                             );
                             retry += 1;
-                            time::sleep(RETRY_WAIT).await;
+                            if retry <= MAX_RETRIES {
+                                telemetry::count_upload_retry(bucket, file_type);
+                                time::sleep(RETRY_WAIT).await;
+                            }
                         }
                     }
                 }
+                telemetry::count_upload_failure(bucket, file_type);
+                tracing::error!("giving up storing {path_str} in {bucket}");
             });
 
         tokio::select! {
@@ -0,0 +1,191 @@
+//! Bounded, frame-by-frame decoding of length delimited file contents.
+
+use crate::{Error, Result};
+use async_compression::tokio::bufread::GzipDecoder;
+use bytes::{Buf, BytesMut};
+use tokio::io::AsyncBufRead;
+use tokio_util::codec::{Decoder, FramedRead};
+
+/// Initial capacity of the read buffer. The buffer only grows beyond this to
+/// hold a single frame up to the maximum frame length.
+pub const READ_BUFFER_CAPACITY: usize = 64 * 1024;
+
+const HEADER_LENGTH: usize = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    Data(BytesMut),
+    /// A frame with a length prefix above the maximum, skipped without being
+    /// buffered so callers can quarantine the file and carry on with the
+    /// remaining frames
+    Oversized {
+        length: usize,
+        offset: u64,
+    },
+}
+
+#[derive(Debug)]
+enum State {
+    Head,
+    Skip {
+        length: usize,
+        offset: u64,
+        remaining: usize,
+    },
+}
+
+/// Yields one frame at a time, so memory use is bounded by the largest
+/// accepted frame rather than the size of the file
+#[derive(Debug)]
+pub struct FrameDecoder {
+    max_frame_length: usize,
+    offset: u64,
+    state: State,
+}
+
+impl FrameDecoder {
+    pub fn new(max_frame_length: usize) -> Self {
+        Self {
+            max_frame_length,
+            offset: 0,
+            state: State::Head,
+        }
+    }
+
+    fn advance(&mut self, src: &mut BytesMut, count: usize) {
+        src.advance(count);
+        self.offset += count as u64;
+    }
+}
+
+impl Decoder for FrameDecoder {
+    type Item = Frame;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
+        if let State::Skip {
+            length,
+            offset,
+            remaining,
+        } = self.state
+        {
+            let count = remaining.min(src.len());
+            self.advance(src, count);
+            if count < remaining {
+                self.state = State::Skip {
+                    length,
+                    offset,
+                    remaining: remaining - count,
+                };
+                return Ok(None);
+            }
+            self.state = State::Head;
+            return Ok(Some(Frame::Oversized { length, offset }));
+        }
+
+        if src.len() < HEADER_LENGTH {
+            return Ok(None);
+        }
+        let mut header = [0u8; HEADER_LENGTH];
+        header.copy_from_slice(&src[..HEADER_LENGTH]);
+        let length = u32::from_be_bytes(header) as usize;
+
+        if length > self.max_frame_length {
+            let offset = self.offset;
+            self.advance(src, HEADER_LENGTH);
+            self.state = State::Skip {
+                length,
+                offset,
+                remaining: length,
+            };
+            return self.decode(src);
+        }
+
+        let frame_length = HEADER_LENGTH + length;
+        if src.len() < frame_length {
+            src.reserve(frame_length - src.len());
+            return Ok(None);
+        }
+        self.advance(src, HEADER_LENGTH);
+        self.offset += length as u64;
+        Ok(Some(Frame::Data(src.split_to(length))))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() && matches!(self.state, State::Head) => Ok(None),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("truncated frame at offset {}", self.offset),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Decode gzipped, length delimited frames from the given reader.
+pub fn frames<R>(reader: R, max_frame_length: usize) -> FramedRead<GzipDecoder<R>, FrameDecoder>
+where
+    R: AsyncBufRead,
+{
+    FramedRead::with_capacity(
+        GzipDecoder::new(reader),
+        FrameDecoder::new(max_frame_length),
+        READ_BUFFER_CAPACITY,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode(frames: &[&[u8]]) -> Vec<u8> {
+        frames.iter().fold(vec![], |mut acc, frame| {
+            acc.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            acc.extend_from_slice(frame);
+            acc
+        })
+    }
+
+    fn decode_all(decoder: &mut FrameDecoder, bytes: &[u8], chunk: usize) -> Vec<Frame> {
+        let mut src = BytesMut::new();
+        let mut frames = vec![];
+        for part in bytes.chunks(chunk) {
+            src.extend_from_slice(part);
+            while let Some(frame) = decoder.decode(&mut src).expect("decode") {
+                frames.push(frame);
+            }
+        }
+        assert!(decoder.decode_eof(&mut src).expect("eof").is_none());
+        frames
+    }
+
+    #[test]
+    fn skips_oversized_frames() {
+        let bytes = encode(&[b"one", b"far too large", b"two"]);
+        for chunk in [1, 3, bytes.len()] {
+            let frames = decode_all(&mut FrameDecoder::new(8), &bytes, chunk);
+            assert_eq!(
+                frames,
+                vec![
+                    Frame::Data(BytesMut::from(&b"one"[..])),
+                    Frame::Oversized {
+                        length: 13,
+                        offset: 7
+                    },
+                    Frame::Data(BytesMut::from(&b"two"[..])),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_frame_is_an_error() {
+        let bytes = encode(&[b"far too large"]);
+        let mut decoder = FrameDecoder::new(8);
+        let mut src = BytesMut::from(&bytes[..10]);
+        assert!(decoder.decode(&mut src).expect("decode").is_none());
+        assert!(decoder.decode_eof(&mut src).is_err());
+    }
+}
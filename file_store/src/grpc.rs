@@ -0,0 +1,7 @@
+//! Results of the handlers of the grpc services the oracles serve
+
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Response, Status};
+
+pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
+pub type GrpcStreamResult<T> = ReceiverStream<std::result::Result<T, Status>>;
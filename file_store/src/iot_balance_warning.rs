@@ -0,0 +1,71 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for balance warnings published by the iot packet verifier.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BalanceWarningV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payer: Vec<u8>,
+    /// Remaining balance in DC after pending burns
+    #[prost(uint64, tag = "3")]
+    pub balance: u64,
+    /// Average spend in DC per day
+    #[prost(uint64, tag = "4")]
+    pub daily_burn_rate: u64,
+    /// Unix timestamp in milliseconds at which the balance is projected to
+    /// run out at the current burn rate
+    #[prost(uint64, tag = "5")]
+    pub projected_exhaustion_timestamp: u64,
+    /// Unix timestamp in milliseconds at which the warning was raised
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BalanceWarning {
+    pub oui: u64,
+    pub payer: PublicKeyBinary,
+    pub balance: u64,
+    pub daily_burn_rate: u64,
+    pub projected_exhaustion: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl MsgDecode for BalanceWarning {
+    type Msg = BalanceWarningV1;
+}
+
+impl TryFrom<BalanceWarningV1> for BalanceWarning {
+    type Error = Error;
+
+    fn try_from(v: BalanceWarningV1) -> Result<Self> {
+        Ok(Self {
+            oui: v.oui,
+            payer: v.payer.into(),
+            balance: v.balance,
+            daily_burn_rate: v.daily_burn_rate,
+            projected_exhaustion: v.projected_exhaustion_timestamp.to_timestamp_millis()?,
+            timestamp: v.timestamp.to_timestamp_millis()?,
+        })
+    }
+}
+
+impl From<BalanceWarning> for BalanceWarningV1 {
+    fn from(v: BalanceWarning) -> Self {
+        Self {
+            oui: v.oui,
+            payer: v.payer.into(),
+            balance: v.balance,
+            daily_burn_rate: v.daily_burn_rate,
+            projected_exhaustion_timestamp: v.projected_exhaustion.encode_timestamp_millis(),
+            timestamp: v.timestamp.encode_timestamp_millis(),
+        }
+    }
+}
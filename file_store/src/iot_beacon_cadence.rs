@@ -0,0 +1,86 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for beacon cadence reports published by the iot verifier.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BeaconCadenceReportV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    /// Number of beacons of the gateway within the window
+    #[prost(uint32, tag = "2")]
+    pub beacon_count: u32,
+    /// Median time between consecutive beacons in seconds
+    #[prost(uint64, tag = "3")]
+    pub median_interval: u64,
+    /// Share of beacon intervals at the edge of the minimum allowed interval
+    #[prost(double, tag = "4")]
+    pub edge_ratio: f64,
+    /// Share of beacons sent within a network wide burst
+    #[prost(double, tag = "5")]
+    pub burst_ratio: f64,
+    #[prost(bool, tag = "6")]
+    pub at_interval_edge: bool,
+    #[prost(bool, tag = "7")]
+    pub in_bursts: bool,
+    /// Unix timestamps in milliseconds of the analysed window
+    #[prost(uint64, tag = "8")]
+    pub window_start: u64,
+    #[prost(uint64, tag = "9")]
+    pub window_end: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BeaconCadenceReport {
+    pub pub_key: PublicKeyBinary,
+    pub beacon_count: u32,
+    pub median_interval: u64,
+    pub edge_ratio: f64,
+    pub burst_ratio: f64,
+    pub at_interval_edge: bool,
+    pub in_bursts: bool,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+}
+
+impl MsgDecode for BeaconCadenceReport {
+    type Msg = BeaconCadenceReportV1;
+}
+
+impl TryFrom<BeaconCadenceReportV1> for BeaconCadenceReport {
+    type Error = Error;
+
+    fn try_from(v: BeaconCadenceReportV1) -> Result<Self> {
+        Ok(Self {
+            pub_key: v.pub_key.into(),
+            beacon_count: v.beacon_count,
+            median_interval: v.median_interval,
+            edge_ratio: v.edge_ratio,
+            burst_ratio: v.burst_ratio,
+            at_interval_edge: v.at_interval_edge,
+            in_bursts: v.in_bursts,
+            window_start: v.window_start.to_timestamp_millis()?,
+            window_end: v.window_end.to_timestamp_millis()?,
+        })
+    }
+}
+
+impl From<BeaconCadenceReport> for BeaconCadenceReportV1 {
+    fn from(v: BeaconCadenceReport) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            beacon_count: v.beacon_count,
+            median_interval: v.median_interval,
+            edge_ratio: v.edge_ratio,
+            burst_ratio: v.burst_ratio,
+            at_interval_edge: v.at_interval_edge,
+            in_bursts: v.in_bursts,
+            window_start: v.window_start.encode_timestamp_millis(),
+            window_end: v.window_end.encode_timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for the summary of a gateway info reconciliation run by the
+/// iot verifier, comparing a sample of its cached gateways to iot config.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayReconciliationV1 {
+    /// Unix timestamp in milliseconds of the run
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// Number of cached gateways compared to iot config
+    #[prost(uint64, tag = "2")]
+    pub sampled: u64,
+    /// Number of sampled gateways iot config could not be queried for
+    #[prost(uint64, tag = "3")]
+    pub errors: u64,
+    /// Whether drifted gateways were repaired in the cache
+    #[prost(bool, tag = "4")]
+    pub repaired: bool,
+    #[prost(message, repeated, tag = "5")]
+    pub drifted: Vec<GatewayDriftV1>,
+}
+
+/// A cached gateway whose info differs from iot config
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayDriftV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    /// The gateway is no longer known to iot config
+    #[prost(bool, tag = "2")]
+    pub missing: bool,
+    #[prost(bool, tag = "3")]
+    pub location: bool,
+    #[prost(bool, tag = "4")]
+    pub gain: bool,
+    #[prost(bool, tag = "5")]
+    pub region: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GatewayReconciliation {
+    pub timestamp: DateTime<Utc>,
+    pub sampled: u64,
+    pub errors: u64,
+    pub repaired: bool,
+    pub drifted: Vec<GatewayDrift>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GatewayDrift {
+    pub pub_key: PublicKeyBinary,
+    pub missing: bool,
+    pub location: bool,
+    pub gain: bool,
+    pub region: bool,
+}
+
+impl MsgDecode for GatewayReconciliation {
+    type Msg = GatewayReconciliationV1;
+}
+
+impl TryFrom<GatewayReconciliationV1> for GatewayReconciliation {
+    type Error = Error;
+
+    fn try_from(v: GatewayReconciliationV1) -> Result<Self> {
+        Ok(Self {
+            timestamp: v.timestamp.to_timestamp_millis()?,
+            sampled: v.sampled,
+            errors: v.errors,
+            repaired: v.repaired,
+            drifted: v.drifted.into_iter().map(GatewayDrift::from).collect(),
+        })
+    }
+}
+
+impl From<GatewayReconciliation> for GatewayReconciliationV1 {
+    fn from(v: GatewayReconciliation) -> Self {
+        Self {
+            timestamp: v.timestamp.encode_timestamp_millis(),
+            sampled: v.sampled,
+            errors: v.errors,
+            repaired: v.repaired,
+            drifted: v.drifted.into_iter().map(GatewayDriftV1::from).collect(),
+        }
+    }
+}
+
+impl From<GatewayDriftV1> for GatewayDrift {
+    fn from(v: GatewayDriftV1) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            missing: v.missing,
+            location: v.location,
+            gain: v.gain,
+            region: v.region,
+        }
+    }
+}
+
+impl From<GatewayDrift> for GatewayDriftV1 {
+    fn from(v: GatewayDrift) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            missing: v.missing,
+            location: v.location,
+            gain: v.gain,
+            region: v.region,
+        }
+    }
+}
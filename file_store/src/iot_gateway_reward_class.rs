@@ -0,0 +1,73 @@
+use crate::{
+    error::DecodeError,
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for the class a gateway was rewarded as by the iot verifier,
+/// written with the reward shares of every epoch. helium-proto's gateway
+/// reward has no class, so it is published alongside it.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayRewardClassV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot_key: Vec<u8>,
+    #[prost(enumeration = "GatewayClassV1", tag = "2")]
+    pub class: i32,
+    /// Unix timestamps in seconds of the reward period
+    #[prost(uint64, tag = "3")]
+    pub start_period: u64,
+    #[prost(uint64, tag = "4")]
+    pub end_period: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum GatewayClassV1 {
+    Full = 0,
+    Light = 1,
+    DataOnly = 2,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GatewayRewardClass {
+    pub hotspot_key: PublicKeyBinary,
+    pub class: GatewayClassV1,
+    pub start_period: DateTime<Utc>,
+    pub end_period: DateTime<Utc>,
+}
+
+impl MsgDecode for GatewayRewardClass {
+    type Msg = GatewayRewardClassV1;
+}
+
+impl TryFrom<GatewayRewardClassV1> for GatewayRewardClass {
+    type Error = Error;
+
+    fn try_from(v: GatewayRewardClassV1) -> Result<Self> {
+        let class = GatewayClassV1::from_i32(v.class)
+            .ok_or_else(|| DecodeError::unsupported_status_reason("gateway_class", v.class))?;
+        Ok(Self {
+            hotspot_key: v.hotspot_key.into(),
+            class,
+            start_period: v.start_period.to_timestamp()?,
+            end_period: v.end_period.to_timestamp()?,
+        })
+    }
+}
+
+impl From<GatewayRewardClass> for GatewayRewardClassV1 {
+    fn from(v: GatewayRewardClass) -> Self {
+        Self {
+            hotspot_key: v.hotspot_key.into(),
+            class: v.class as i32,
+            start_period: v.start_period.encode_timestamp(),
+            end_period: v.end_period.encode_timestamp(),
+        }
+    }
+}
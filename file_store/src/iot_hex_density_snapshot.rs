@@ -0,0 +1,98 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result, SCALING_PRECISION,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_decimal_macros::dec;
+use serde::Serialize;
+
+const SCALE_MULTIPLIER: Decimal = dec!(10000);
+
+/// Wire format for a snapshot of the transmit scaling map of the iot
+/// verifier, as swapped in on one refresh of the map. The map of a refresh
+/// may be split across several records sharing its `timestamp`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexDensitySnapshotV1 {
+    /// Unix timestamp in milliseconds of the refresh
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// Unix timestamp in milliseconds from which beacons made gateways count
+    /// as interactive
+    #[prost(uint64, tag = "2")]
+    pub active_since: u64,
+    /// Whether gateways were weighted by their valid witnesses
+    #[prost(bool, tag = "3")]
+    pub weighted: bool,
+    #[prost(message, repeated, tag = "4")]
+    pub scales: Vec<HexDensityScaleV1>,
+}
+
+/// Scale of a res 12 hex, multiplied by 10000
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexDensityScaleV1 {
+    #[prost(uint64, tag = "1")]
+    pub hex: u64,
+    #[prost(uint32, tag = "2")]
+    pub scale: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct HexDensitySnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub active_since: DateTime<Utc>,
+    pub weighted: bool,
+    pub scales: Vec<HexDensityScale>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct HexDensityScale {
+    pub hex: u64,
+    pub scale: Decimal,
+}
+
+impl MsgDecode for HexDensitySnapshot {
+    type Msg = HexDensitySnapshotV1;
+}
+
+impl TryFrom<HexDensitySnapshotV1> for HexDensitySnapshot {
+    type Error = Error;
+
+    fn try_from(v: HexDensitySnapshotV1) -> Result<Self> {
+        Ok(Self {
+            timestamp: v.timestamp.to_timestamp_millis()?,
+            active_since: v.active_since.to_timestamp_millis()?,
+            weighted: v.weighted,
+            scales: v.scales.into_iter().map(HexDensityScale::from).collect(),
+        })
+    }
+}
+
+impl From<HexDensitySnapshot> for HexDensitySnapshotV1 {
+    fn from(v: HexDensitySnapshot) -> Self {
+        Self {
+            timestamp: v.timestamp.encode_timestamp_millis(),
+            active_since: v.active_since.encode_timestamp_millis(),
+            weighted: v.weighted,
+            scales: v.scales.into_iter().map(HexDensityScaleV1::from).collect(),
+        }
+    }
+}
+
+impl From<HexDensityScaleV1> for HexDensityScale {
+    fn from(v: HexDensityScaleV1) -> Self {
+        Self {
+            hex: v.hex,
+            scale: Decimal::new(v.scale as i64, SCALING_PRECISION),
+        }
+    }
+}
+
+impl From<HexDensityScale> for HexDensityScaleV1 {
+    fn from(v: HexDensityScale) -> Self {
+        Self {
+            hex: v.hex,
+            scale: (v.scale * SCALE_MULTIPLIER).to_u32().unwrap_or(0),
+        }
+    }
+}
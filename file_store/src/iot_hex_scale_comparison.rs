@@ -0,0 +1,103 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result, SCALING_PRECISION,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_decimal_macros::dec;
+use serde::Serialize;
+
+const SCALE_MULTIPLIER: Decimal = dec!(10000);
+
+/// Wire format for the comparison of the transmit scaling maps of the iot
+/// verifier, by gateway presence and weighted by valid witnesses, on one
+/// refresh of the maps.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScaleComparisonV1 {
+    /// Unix timestamp in milliseconds of the refresh
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// Whether the weighted map was the one in effect
+    #[prost(bool, tag = "2")]
+    pub weighted: bool,
+    /// Number of hexes in both maps
+    #[prost(uint64, tag = "3")]
+    pub hexes: u64,
+    /// The hexes scaled differently by the maps
+    #[prost(message, repeated, tag = "4")]
+    pub differing: Vec<HexScalesV1>,
+}
+
+/// Scales of a hex, multiplied by 10000
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScalesV1 {
+    #[prost(uint64, tag = "1")]
+    pub hex: u64,
+    #[prost(uint32, tag = "2")]
+    pub presence_scale: u32,
+    #[prost(uint32, tag = "3")]
+    pub weighted_scale: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct HexScaleComparison {
+    pub timestamp: DateTime<Utc>,
+    pub weighted: bool,
+    pub hexes: u64,
+    pub differing: Vec<HexScales>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct HexScales {
+    pub hex: u64,
+    pub presence_scale: Decimal,
+    pub weighted_scale: Decimal,
+}
+
+impl MsgDecode for HexScaleComparison {
+    type Msg = HexScaleComparisonV1;
+}
+
+impl TryFrom<HexScaleComparisonV1> for HexScaleComparison {
+    type Error = Error;
+
+    fn try_from(v: HexScaleComparisonV1) -> Result<Self> {
+        Ok(Self {
+            timestamp: v.timestamp.to_timestamp_millis()?,
+            weighted: v.weighted,
+            hexes: v.hexes,
+            differing: v.differing.into_iter().map(HexScales::from).collect(),
+        })
+    }
+}
+
+impl From<HexScaleComparison> for HexScaleComparisonV1 {
+    fn from(v: HexScaleComparison) -> Self {
+        Self {
+            timestamp: v.timestamp.encode_timestamp_millis(),
+            weighted: v.weighted,
+            hexes: v.hexes,
+            differing: v.differing.into_iter().map(HexScalesV1::from).collect(),
+        }
+    }
+}
+
+impl From<HexScalesV1> for HexScales {
+    fn from(v: HexScalesV1) -> Self {
+        Self {
+            hex: v.hex,
+            presence_scale: Decimal::new(v.presence_scale as i64, SCALING_PRECISION),
+            weighted_scale: Decimal::new(v.weighted_scale as i64, SCALING_PRECISION),
+        }
+    }
+}
+
+impl From<HexScales> for HexScalesV1 {
+    fn from(v: HexScales) -> Self {
+        Self {
+            hex: v.hex,
+            presence_scale: (v.presence_scale * SCALE_MULTIPLIER).to_u32().unwrap_or(0),
+            weighted_scale: (v.weighted_scale * SCALE_MULTIPLIER).to_u32().unwrap_or(0),
+        }
+    }
+}
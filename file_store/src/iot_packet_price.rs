@@ -0,0 +1,87 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for the price applied to each valid packet by the iot packet
+/// verifier, written alongside the valid packets for billing transparency.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PacketPriceV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub gateway: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub payload_hash: Vec<u8>,
+    #[prost(uint32, tag = "4")]
+    pub payload_size: u32,
+    /// Name of the pricing rule applied, `default` when no rule matched
+    #[prost(string, tag = "5")]
+    pub rule: String,
+    /// Bytes priced per unit by the rule
+    #[prost(uint64, tag = "6")]
+    pub bytes_per_unit: u64,
+    /// DC charged per started unit by the rule
+    #[prost(uint64, tag = "7")]
+    pub dc_per_unit: u64,
+    /// DC debited for the packet
+    #[prost(uint64, tag = "8")]
+    pub num_dcs: u64,
+    /// Unix timestamp in milliseconds of the packet
+    #[prost(uint64, tag = "9")]
+    pub packet_timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PacketPrice {
+    pub oui: u64,
+    pub gateway: PublicKeyBinary,
+    pub payload_hash: Vec<u8>,
+    pub payload_size: u32,
+    pub rule: String,
+    pub bytes_per_unit: u64,
+    pub dc_per_unit: u64,
+    pub num_dcs: u64,
+    pub packet_timestamp: DateTime<Utc>,
+}
+
+impl MsgDecode for PacketPrice {
+    type Msg = PacketPriceV1;
+}
+
+impl TryFrom<PacketPriceV1> for PacketPrice {
+    type Error = Error;
+
+    fn try_from(v: PacketPriceV1) -> Result<Self> {
+        Ok(Self {
+            oui: v.oui,
+            gateway: v.gateway.into(),
+            payload_hash: v.payload_hash,
+            payload_size: v.payload_size,
+            rule: v.rule,
+            bytes_per_unit: v.bytes_per_unit,
+            dc_per_unit: v.dc_per_unit,
+            num_dcs: v.num_dcs,
+            packet_timestamp: v.packet_timestamp.to_timestamp_millis()?,
+        })
+    }
+}
+
+impl From<PacketPrice> for PacketPriceV1 {
+    fn from(v: PacketPrice) -> Self {
+        Self {
+            oui: v.oui,
+            gateway: v.gateway.into(),
+            payload_hash: v.payload_hash,
+            payload_size: v.payload_size,
+            rule: v.rule,
+            bytes_per_unit: v.bytes_per_unit,
+            dc_per_unit: v.dc_per_unit,
+            num_dcs: v.num_dcs,
+            packet_timestamp: v.packet_timestamp.encode_timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    error::DecodeError,
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Wire format for the bones of an iot reward pool paid to no gateway,
+/// written by the iot verifier with the reward shares of every epoch.
+/// helium-proto's iot reward share has no unallocated reward, so they are
+/// published alongside them.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UnallocatedRewardV1 {
+    #[prost(enumeration = "RewardClassV1", tag = "1")]
+    pub class: i32,
+    #[prost(enumeration = "UnallocatedReasonV1", tag = "2")]
+    pub reason: i32,
+    /// Bones left unallocated
+    #[prost(uint64, tag = "3")]
+    pub amount: u64,
+    /// Unix timestamps in seconds of the reward period
+    #[prost(uint64, tag = "4")]
+    pub start_period: u64,
+    #[prost(uint64, tag = "5")]
+    pub end_period: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum RewardClassV1 {
+    Beacon = 0,
+    Witness = 1,
+    DataTransfer = 2,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum UnallocatedReasonV1 {
+    /// Left over from rounding the rewards of the pool down to whole bones
+    Dust = 0,
+    /// The class was paused by its reward class switch
+    ClassDisabled = 1,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnallocatedReward {
+    pub class: RewardClassV1,
+    pub reason: UnallocatedReasonV1,
+    pub amount: u64,
+    pub start_period: DateTime<Utc>,
+    pub end_period: DateTime<Utc>,
+}
+
+impl MsgDecode for UnallocatedReward {
+    type Msg = UnallocatedRewardV1;
+}
+
+impl TryFrom<UnallocatedRewardV1> for UnallocatedReward {
+    type Error = Error;
+
+    fn try_from(v: UnallocatedRewardV1) -> Result<Self> {
+        let class = RewardClassV1::from_i32(v.class)
+            .ok_or_else(|| DecodeError::unsupported_status_reason("reward_class", v.class))?;
+        let reason = UnallocatedReasonV1::from_i32(v.reason).ok_or_else(|| {
+            DecodeError::unsupported_status_reason("unallocated_reason", v.reason)
+        })?;
+        Ok(Self {
+            class,
+            reason,
+            amount: v.amount,
+            start_period: v.start_period.to_timestamp()?,
+            end_period: v.end_period.to_timestamp()?,
+        })
+    }
+}
+
+impl From<UnallocatedReward> for UnallocatedRewardV1 {
+    fn from(v: UnallocatedReward) -> Self {
+        Self {
+            class: v.class as i32,
+            reason: v.reason as i32,
+            amount: v.amount,
+            start_period: v.start_period.encode_timestamp(),
+            end_period: v.end_period.encode_timestamp(),
+        }
+    }
+}
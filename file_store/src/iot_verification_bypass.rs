@@ -0,0 +1,61 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for a verification check skipped by the iot verifier for a
+/// gateway on the staging test gateway allow-list.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VerificationBypassV1 {
+    /// Unix timestamp in milliseconds of the beacon of the poc
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// `poc_id` of the valid poc the check was skipped in
+    #[prost(bytes = "vec", tag = "2")]
+    pub poc_id: Vec<u8>,
+    /// The test gateway the check was skipped for
+    #[prost(bytes = "vec", tag = "3")]
+    pub pub_key: Vec<u8>,
+    /// Name of the skipped check, ie "density_scaling" or "max_distance"
+    #[prost(string, tag = "4")]
+    pub check: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerificationBypass {
+    pub timestamp: DateTime<Utc>,
+    pub poc_id: Vec<u8>,
+    pub pub_key: PublicKeyBinary,
+    pub check: String,
+}
+
+impl MsgDecode for VerificationBypass {
+    type Msg = VerificationBypassV1;
+}
+
+impl TryFrom<VerificationBypassV1> for VerificationBypass {
+    type Error = Error;
+
+    fn try_from(v: VerificationBypassV1) -> Result<Self> {
+        Ok(Self {
+            timestamp: v.timestamp.to_timestamp_millis()?,
+            poc_id: v.poc_id,
+            pub_key: v.pub_key.into(),
+            check: v.check,
+        })
+    }
+}
+
+impl From<VerificationBypass> for VerificationBypassV1 {
+    fn from(v: VerificationBypass) -> Self {
+        Self {
+            timestamp: v.timestamp.encode_timestamp_millis(),
+            poc_id: v.poc_id,
+            pub_key: v.pub_key.into(),
+            check: v.check,
+        }
+    }
+}
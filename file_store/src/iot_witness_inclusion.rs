@@ -0,0 +1,297 @@
+//! Merkle inclusion proofs for the selected witnesses of valid PoCs, so
+//! anyone holding the `iot_poc` output can check a witness was rewarded.
+
+use crate::{error::DecodeError, traits::MsgDecode, Error, Result};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{services::poc_lora::LoraVerifiedWitnessReportV1, Message};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Wire format for the witness inclusion proofs of a valid PoC.
+///
+/// There is no helium-proto definition for this message yet, and no field
+/// for the root in `LoraPocV1`, so it is declared here with prost directly
+/// and published next to the poc. Field tags must remain stable.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessInclusionV1 {
+    /// `poc_id` of the poc the witnesses were selected for
+    #[prost(bytes = "vec", tag = "1")]
+    pub poc_id: Vec<u8>,
+    /// Merkle root over the selected witnesses
+    #[prost(bytes = "vec", tag = "2")]
+    pub witness_root: Vec<u8>,
+    /// Number of selected witnesses, the leaves of the tree
+    #[prost(uint32, tag = "3")]
+    pub witness_count: u32,
+    #[prost(message, repeated, tag = "4")]
+    pub proofs: Vec<WitnessProofV1>,
+}
+
+/// Inclusion proof of one selected witness
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessProofV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    /// Position of the witness in `selected_witnesses`
+    #[prost(uint32, tag = "2")]
+    pub index: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    pub leaf: Vec<u8>,
+    /// Sibling hashes from the leaf up to the root
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub siblings: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WitnessInclusion {
+    pub poc_id: Vec<u8>,
+    pub witness_root: Hash,
+    pub witness_count: u32,
+    pub proofs: Vec<WitnessProof>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WitnessProof {
+    pub pub_key: PublicKeyBinary,
+    pub index: u32,
+    pub leaf: Hash,
+    pub siblings: Vec<Hash>,
+}
+
+impl WitnessInclusion {
+    /// Build the root and proofs for the selected witnesses of a poc
+    pub fn new(poc_id: Vec<u8>, selected_witnesses: &[LoraVerifiedWitnessReportV1]) -> Self {
+        let leaves: Vec<Hash> = selected_witnesses.iter().map(witness_leaf).collect();
+        let proofs = selected_witnesses
+            .iter()
+            .zip(&leaves)
+            .enumerate()
+            .map(|(index, (witness, leaf))| WitnessProof {
+                pub_key: witness
+                    .report
+                    .as_ref()
+                    .map(|report| report.pub_key.clone())
+                    .unwrap_or_default()
+                    .into(),
+                index: index as u32,
+                leaf: *leaf,
+                siblings: proof(&leaves, index),
+            })
+            .collect();
+        Self {
+            poc_id,
+            witness_root: root(&leaves),
+            witness_count: leaves.len() as u32,
+            proofs,
+        }
+    }
+
+    /// Check the proof of the witness at `index` against the witness root
+    pub fn verify(&self, witness: &LoraVerifiedWitnessReportV1, index: usize) -> bool {
+        self.proofs.get(index).map_or(false, |proof| {
+            verify(
+                &self.witness_root,
+                &witness_leaf(witness),
+                index,
+                self.witness_count as usize,
+                &proof.siblings,
+            )
+        })
+    }
+}
+
+/// The leaf hash of a selected witness, the SHA-256 of `0x00` followed by the
+/// protobuf encoding of the witness as published in the poc. Leaves are in
+/// the order of `selected_witnesses`
+pub fn witness_leaf(witness: &LoraVerifiedWitnessReportV1) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(witness.encode_to_vec());
+    hasher.finalize().into()
+}
+
+fn node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// The Merkle root over `leaves`. Inner nodes are the SHA-256 of `0x01`
+/// followed by their left and right children, a node without a right sibling
+/// is carried up a level unchanged, and the root of no leaves is the SHA-256
+/// of no data
+pub fn root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Sha256::new().finalize().into();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// The sibling hashes proving the inclusion of the leaf at `index`
+pub fn proof(leaves: &[Hash], mut index: usize) -> Vec<Hash> {
+    let mut siblings = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            siblings.push(level[sibling]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        index /= 2;
+    }
+    siblings
+}
+
+/// Check that `leaf` is the leaf at `index` of a tree of `count` leaves with
+/// the given `root`
+pub fn verify(root: &Hash, leaf: &Hash, mut index: usize, count: usize, siblings: &[Hash]) -> bool {
+    if index >= count {
+        return false;
+    }
+    let mut siblings = siblings.iter();
+    let mut hash = *leaf;
+    let mut width = count;
+    while width > 1 {
+        if index % 2 == 1 {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            hash = node(sibling, &hash);
+        } else if index + 1 < width {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            hash = node(&hash, sibling);
+        }
+        index /= 2;
+        width = (width + 1) / 2;
+    }
+    siblings.next().is_none() && &hash == root
+}
+
+impl MsgDecode for WitnessInclusion {
+    type Msg = WitnessInclusionV1;
+}
+
+fn to_hash(bytes: Vec<u8>) -> Result<Hash> {
+    Hash::try_from(bytes).map_err(|bytes| DecodeError::invalid_hash_length(bytes.len()))
+}
+
+impl TryFrom<WitnessInclusionV1> for WitnessInclusion {
+    type Error = Error;
+
+    fn try_from(v: WitnessInclusionV1) -> Result<Self> {
+        Ok(Self {
+            poc_id: v.poc_id,
+            witness_root: to_hash(v.witness_root)?,
+            witness_count: v.witness_count,
+            proofs: v
+                .proofs
+                .into_iter()
+                .map(WitnessProof::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl From<WitnessInclusion> for WitnessInclusionV1 {
+    fn from(v: WitnessInclusion) -> Self {
+        Self {
+            poc_id: v.poc_id,
+            witness_root: v.witness_root.to_vec(),
+            witness_count: v.witness_count,
+            proofs: v.proofs.into_iter().map(WitnessProofV1::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<WitnessProofV1> for WitnessProof {
+    type Error = Error;
+
+    fn try_from(v: WitnessProofV1) -> Result<Self> {
+        Ok(Self {
+            pub_key: v.pub_key.into(),
+            index: v.index,
+            leaf: to_hash(v.leaf)?,
+            siblings: v.siblings.into_iter().map(to_hash).collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl From<WitnessProof> for WitnessProofV1 {
+    fn from(v: WitnessProof) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            index: v.index,
+            leaf: v.leaf.to_vec(),
+            siblings: v.siblings.iter().map(|sibling| sibling.to_vec()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| Sha256::digest([i]).into()).collect()
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let root = root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let siblings = proof(&leaves, index);
+                assert!(verify(&root, leaf, index, leaves.len(), &siblings));
+                // a proof only holds for the position it was made for
+                if leaves.len() > 1 {
+                    let other = (index + 1) % leaves.len();
+                    assert!(!verify(&root, leaf, other, leaves.len(), &siblings));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn modified_witness_fails_verification() {
+        let witnesses: Vec<LoraVerifiedWitnessReportV1> = (0..3)
+            .map(|i| LoraVerifiedWitnessReportV1 {
+                received_timestamp: i,
+                ..Default::default()
+            })
+            .collect();
+        let inclusion = WitnessInclusion::new(vec![1], &witnesses);
+        assert!(inclusion.verify(&witnesses[2], 2));
+
+        let mut modified = witnesses[2].clone();
+        modified.received_timestamp += 1;
+        assert!(!inclusion.verify(&modified, 2));
+    }
+}
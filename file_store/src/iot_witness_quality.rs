@@ -0,0 +1,95 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Wire format for the witness quality of a gateway, as published by the iot
+/// verifier with the rewards of every epoch.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessQualityReportV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    /// Witnesses of valid pocs verified valid within the window
+    #[prost(uint64, tag = "2")]
+    pub valid: u64,
+    /// Witnesses of valid pocs verified invalid within the window
+    #[prost(uint64, tag = "3")]
+    pub invalid: u64,
+    /// Share of the witnesses verified valid
+    #[prost(double, tag = "4")]
+    pub score: f64,
+    /// Invalid witnesses per invalid reason
+    #[prost(message, repeated, tag = "5")]
+    pub reasons: Vec<InvalidReasonCountV1>,
+    /// Unix timestamps in milliseconds of the window
+    #[prost(uint64, tag = "6")]
+    pub window_start: u64,
+    #[prost(uint64, tag = "7")]
+    pub window_end: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InvalidReasonCountV1 {
+    /// Name of the `InvalidReason`, ie "too_close"
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    #[prost(uint64, tag = "2")]
+    pub witnesses: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct WitnessQualityReport {
+    pub pub_key: PublicKeyBinary,
+    pub valid: u64,
+    pub invalid: u64,
+    pub score: f64,
+    pub reasons: BTreeMap<String, u64>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+}
+
+impl MsgDecode for WitnessQualityReport {
+    type Msg = WitnessQualityReportV1;
+}
+
+impl TryFrom<WitnessQualityReportV1> for WitnessQualityReport {
+    type Error = Error;
+
+    fn try_from(v: WitnessQualityReportV1) -> Result<Self> {
+        Ok(Self {
+            pub_key: v.pub_key.into(),
+            valid: v.valid,
+            invalid: v.invalid,
+            score: v.score,
+            reasons: v
+                .reasons
+                .into_iter()
+                .map(|count| (count.reason, count.witnesses))
+                .collect(),
+            window_start: v.window_start.to_timestamp_millis()?,
+            window_end: v.window_end.to_timestamp_millis()?,
+        })
+    }
+}
+
+impl From<WitnessQualityReport> for WitnessQualityReportV1 {
+    fn from(v: WitnessQualityReport) -> Self {
+        Self {
+            pub_key: v.pub_key.into(),
+            valid: v.valid,
+            invalid: v.invalid,
+            score: v.score,
+            reasons: v
+                .reasons
+                .into_iter()
+                .map(|(reason, witnesses)| InvalidReasonCountV1 { reason, witnesses })
+                .collect(),
+            window_start: v.window_start.encode_timestamp_millis(),
+            window_end: v.window_end.encode_timestamp_millis(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for the rssi check a witness of a valid poc was rejected by:
+/// the path loss model and margin of the beaconer's region it was checked
+/// against and the signal it was expected to stay under.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessRssiCheckV1 {
+    /// Unix timestamp in milliseconds of the beacon of the poc
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    /// `poc_id` of the valid poc the witness was rejected from
+    #[prost(bytes = "vec", tag = "2")]
+    pub poc_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub pub_key: Vec<u8>,
+    /// Name of the path loss model, ie "free_space" or "two_ray"
+    #[prost(string, tag = "4")]
+    pub path_loss_model: String,
+    /// Margin in dB the signal was allowed to exceed the expected rssi by
+    #[prost(double, tag = "5")]
+    pub margin_db: f64,
+    /// Expected rssi in dBm under the model, before the margin
+    #[prost(double, tag = "6")]
+    pub expected_rssi_dbm: f64,
+    /// Signal of the witness in ddBm, as reported
+    #[prost(sint32, tag = "7")]
+    pub signal: i32,
+    /// Distance in meters between the beaconer and the witness
+    #[prost(uint32, tag = "8")]
+    pub distance: u32,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct WitnessRssiCheck {
+    pub timestamp: DateTime<Utc>,
+    pub poc_id: Vec<u8>,
+    pub pub_key: PublicKeyBinary,
+    pub path_loss_model: String,
+    pub margin_db: f64,
+    pub expected_rssi_dbm: f64,
+    pub signal: i32,
+    pub distance: u32,
+}
+
+impl MsgDecode for WitnessRssiCheck {
+    type Msg = WitnessRssiCheckV1;
+}
+
+impl TryFrom<WitnessRssiCheckV1> for WitnessRssiCheck {
+    type Error = Error;
+
+    fn try_from(v: WitnessRssiCheckV1) -> Result<Self> {
+        Ok(Self {
+            timestamp: v.timestamp.to_timestamp_millis()?,
+            poc_id: v.poc_id,
+            pub_key: v.pub_key.into(),
+            path_loss_model: v.path_loss_model,
+            margin_db: v.margin_db,
+            expected_rssi_dbm: v.expected_rssi_dbm,
+            signal: v.signal,
+            distance: v.distance,
+        })
+    }
+}
+
+impl From<WitnessRssiCheck> for WitnessRssiCheckV1 {
+    fn from(v: WitnessRssiCheck) -> Self {
+        Self {
+            timestamp: v.timestamp.encode_timestamp_millis(),
+            poc_id: v.poc_id,
+            pub_key: v.pub_key.into(),
+            path_loss_model: v.path_loss_model,
+            margin_db: v.margin_db,
+            expected_rssi_dbm: v.expected_rssi_dbm,
+            signal: v.signal,
+            distance: v.distance,
+        }
+    }
+}
@@ -1,24 +1,45 @@
 pub mod cli;
+pub mod compaction;
 pub mod entropy_report;
 mod error;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
 mod file_info;
 pub mod file_info_poller;
 pub mod file_sink;
 pub mod file_source;
 pub mod file_store;
 pub mod file_upload;
+pub mod frame;
+pub mod grpc;
 pub mod heartbeat;
+pub mod iot_balance_warning;
+pub mod iot_beacon_cadence;
 pub mod iot_beacon_report;
+pub mod iot_gateway_reconciliation;
+pub mod iot_gateway_reward_class;
+pub mod iot_hex_density_snapshot;
+pub mod iot_hex_scale_comparison;
 pub mod iot_invalid_poc;
 pub mod iot_packet;
+pub mod iot_packet_price;
+pub mod iot_unallocated_reward;
 pub mod iot_valid_poc;
+pub mod iot_verification_bypass;
+pub mod iot_witness_inclusion;
+pub mod iot_witness_quality;
 pub mod iot_witness_report;
+pub mod iot_witness_rssi_check;
+pub mod mobile_onboarding_rejection;
+pub mod mobile_reward_dust;
 pub mod mobile_session;
 pub mod mobile_subscriber;
 pub mod mobile_transfer;
+pub mod poc_archive;
 pub mod reward_manifest;
 mod settings;
 pub mod speedtest;
+mod telemetry;
 pub mod traits;
 
 pub use crate::file_store::FileStore;
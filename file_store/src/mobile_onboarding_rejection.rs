@@ -0,0 +1,90 @@
+use crate::{
+    error::DecodeError,
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+/// Wire format for a heartbeat the mobile verifier rejected because its
+/// radio was not onboarded by an approved maker. helium-proto has no
+/// heartbeat validity for these, so they are written here with their reason
+/// rather than among the validated heartbeats.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OnboardingRejectionV1 {
+    #[prost(string, tag = "1")]
+    pub cbsd_id: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub pub_key: Vec<u8>,
+    /// Unix timestamp in seconds of the heartbeat
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(enumeration = "OnboardingRejectionReasonV1", tag = "4")]
+    pub reason: i32,
+    /// Address of the maker which onboarded the radio, empty if it was not
+    /// onboarded
+    #[prost(string, tag = "5")]
+    pub maker: String,
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum OnboardingRejectionReasonV1 {
+    RadioNotOnboarded = 0,
+    MakerNotApproved = 1,
+}
+
+impl OnboardingRejectionReasonV1 {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RadioNotOnboarded => "radio_not_onboarded",
+            Self::MakerNotApproved => "maker_not_approved",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct OnboardingRejection {
+    pub cbsd_id: String,
+    pub pub_key: PublicKeyBinary,
+    pub timestamp: DateTime<Utc>,
+    pub reason: OnboardingRejectionReasonV1,
+    pub maker: Option<String>,
+}
+
+impl MsgDecode for OnboardingRejection {
+    type Msg = OnboardingRejectionV1;
+}
+
+impl TryFrom<OnboardingRejectionV1> for OnboardingRejection {
+    type Error = Error;
+
+    fn try_from(v: OnboardingRejectionV1) -> Result<Self> {
+        let reason = OnboardingRejectionReasonV1::from_i32(v.reason).ok_or_else(|| {
+            DecodeError::unsupported_status_reason("onboarding_rejection_reason", v.reason)
+        })?;
+        Ok(Self {
+            cbsd_id: v.cbsd_id,
+            pub_key: v.pub_key.into(),
+            timestamp: v.timestamp.to_timestamp()?,
+            reason,
+            maker: (!v.maker.is_empty()).then_some(v.maker),
+        })
+    }
+}
+
+impl From<OnboardingRejection> for OnboardingRejectionV1 {
+    fn from(v: OnboardingRejection) -> Self {
+        Self {
+            cbsd_id: v.cbsd_id,
+            pub_key: v.pub_key.into(),
+            timestamp: v.timestamp.encode_timestamp(),
+            reason: v.reason as i32,
+            maker: v.maker.unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    traits::{MsgDecode, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Wire format for the dust of a mobile reward epoch, the bones of each pool
+/// left over from rounding its reward shares down, which no mobile reward
+/// share can hold, published by the mobile verifier with the epoch's
+/// manifest.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RewardDustV1 {
+    /// Unix timestamps in seconds of the reward period
+    #[prost(uint64, tag = "1")]
+    pub start_timestamp: u64,
+    #[prost(uint64, tag = "2")]
+    pub end_timestamp: u64,
+    #[prost(uint64, tag = "3")]
+    pub poc: u64,
+    #[prost(uint64, tag = "4")]
+    pub data_transfer: u64,
+    #[prost(uint64, tag = "5")]
+    pub mapping: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct RewardDust {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub poc: u64,
+    pub data_transfer: u64,
+    pub mapping: u64,
+}
+
+impl MsgDecode for RewardDust {
+    type Msg = RewardDustV1;
+}
+
+impl TryFrom<RewardDustV1> for RewardDust {
+    type Error = Error;
+
+    fn try_from(v: RewardDustV1) -> Result<Self> {
+        Ok(Self {
+            start: v.start_timestamp.to_timestamp()?,
+            end: v.end_timestamp.to_timestamp()?,
+            poc: v.poc,
+            data_transfer: v.data_transfer,
+            mapping: v.mapping,
+        })
+    }
+}
+
+impl From<RewardDust> for RewardDustV1 {
+    fn from(v: RewardDust) -> Self {
+        Self {
+            start_timestamp: v.start.encode_timestamp(),
+            end_timestamp: v.end.encode_timestamp(),
+            poc: v.poc,
+            data_transfer: v.data_transfer,
+            mapping: v.mapping,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Query the archived outputs of the iot verifier by gateway.
+
+use crate::{
+    compaction::hour_of,
+    iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_valid_poc::IotPoc,
+    traits::MsgDecode,
+    FileInfo, FileStore, FileType, Result, Stream,
+};
+use chrono::{DateTime, Duration, Utc};
+use futures::{future, stream, StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Range,
+};
+
+/// Default upper bound on the time between a report being received and its
+/// verification outcome being written
+pub const DEFAULT_MAX_VERIFICATION_DELAY_HOURS: i64 = 3;
+/// Prefix of the gateway indexes of the poc files
+pub const INDEX_PREFIX: &str = "index";
+const DEFAULT_WORKERS: usize = 5;
+
+pub const POC_FILE_TYPES: [FileType; 3] = [
+    FileType::IotPoc,
+    FileType::IotInvalidBeaconReport,
+    FileType::IotInvalidWitnessReport,
+];
+
+/// A verification outcome involving a gateway
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PocRecord {
+    /// A valid poc the gateway beaconed or witnessed in
+    Valid(IotPoc),
+    InvalidBeacon(IotInvalidBeaconReport),
+    InvalidWitness(IotInvalidWitnessReport),
+}
+
+impl PocRecord {
+    fn decode(file_type: FileType, buf: &[u8]) -> Result<Option<Self>> {
+        let record = match file_type {
+            FileType::IotPoc => Self::Valid(IotPoc::decode(buf)?),
+            FileType::IotInvalidBeaconReport => {
+                Self::InvalidBeacon(IotInvalidBeaconReport::decode(buf)?)
+            }
+            FileType::IotInvalidWitnessReport => {
+                Self::InvalidWitness(IotInvalidWitnessReport::decode(buf)?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(record))
+    }
+
+    /// When the beacon of a poc or the rejected report was received
+    pub fn received_timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Self::Valid(poc) => poc.beacon_report.received_timestamp,
+            Self::InvalidBeacon(report) => report.received_timestamp,
+            Self::InvalidWitness(report) => report.received_timestamp,
+        }
+    }
+
+    /// The gateways which beaconed or witnessed in the record
+    pub fn gateways(&self) -> Box<dyn Iterator<Item = &PublicKeyBinary> + '_> {
+        match self {
+            Self::Valid(poc) => Box::new(
+                std::iter::once(&poc.beacon_report.report.pub_key).chain(
+                    poc.selected_witnesses
+                        .iter()
+                        .chain(&poc.unselected_witnesses)
+                        .map(|witness| &witness.report.pub_key),
+                ),
+            ),
+            Self::InvalidBeacon(report) => Box::new(std::iter::once(&report.report.pub_key)),
+            Self::InvalidWitness(report) => Box::new(std::iter::once(&report.report.pub_key)),
+        }
+    }
+
+    pub fn involves(&self, gateway: &PublicKeyBinary) -> bool {
+        self.gateways().any(|pub_key| pub_key == gateway)
+    }
+}
+
+/// The gateways in the records of the files of a file type written within an
+/// hour
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayIndex {
+    pub file_type: String,
+    pub hour: DateTime<Utc>,
+    /// Keys of the indexed files
+    pub files: Vec<String>,
+    /// Positions in `files` of the files holding records of each gateway
+    pub gateways: BTreeMap<String, Vec<u32>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl GatewayIndex {
+    fn new(file_type: FileType, hour: DateTime<Utc>) -> Self {
+        Self {
+            file_type: file_type.to_string(),
+            hour,
+            files: vec![],
+            gateways: BTreeMap::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn insert<'a>(&mut self, key: String, gateways: impl IntoIterator<Item = &'a String>) {
+        let position = self.files.len() as u32;
+        self.files.push(key);
+        for gateway in gateways {
+            self.gateways
+                .entry(gateway.clone())
+                .or_default()
+                .push(position);
+        }
+    }
+
+    /// Whether the file with the given key can hold records of `gateway`:
+    /// it was indexed as holding some, or it was not indexed at all
+    fn may_hold(&self, key: &str, gateway: &str) -> bool {
+        let holding = self.gateways.get(gateway);
+        match self.files.iter().position(|indexed| indexed == key) {
+            Some(position) => holding.map_or(false, |positions| {
+                positions.binary_search(&(position as u32)).is_ok()
+            }),
+            None => true,
+        }
+    }
+}
+
+fn index_key(file_type: FileType, hour: DateTime<Utc>) -> String {
+    format!(
+        "{INDEX_PREFIX}/{file_type}.{}.json",
+        hour.timestamp_millis()
+    )
+}
+
+/// The files of `files` which can hold records of `gateway`, given the
+/// indexes of the hours they were written in
+fn select_files(
+    files: Vec<FileInfo>,
+    indexes: &HashMap<DateTime<Utc>, GatewayIndex>,
+    gateway: &str,
+) -> Vec<FileInfo> {
+    files
+        .into_iter()
+        .filter(|info| {
+            indexes
+                .get(&hour_of(info.timestamp))
+                .map_or(true, |index| index.may_hold(&info.key, gateway))
+        })
+        .collect()
+}
+
+/// Reader over the verified poc files of a bucket
+#[derive(Clone)]
+pub struct PocArchive {
+    store: FileStore,
+    max_verification_delay: Duration,
+    workers: usize,
+}
+
+impl PocArchive {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            store,
+            max_verification_delay: Duration::hours(DEFAULT_MAX_VERIFICATION_DELAY_HOURS),
+            workers: DEFAULT_WORKERS,
+        }
+    }
+
+    /// Upper bound on the time between a report being received and its
+    /// outcome being written. Records verified later than this after they
+    /// were received are missed.
+    pub fn max_verification_delay(self, max_verification_delay: Duration) -> Self {
+        Self {
+            max_verification_delay,
+            ..self
+        }
+    }
+
+    /// Number of files fetched concurrently
+    pub fn workers(self, workers: usize) -> Self {
+        Self { workers, ..self }
+    }
+
+    /// The index of the files of `file_type` written within `hour`, if the
+    /// hour was indexed
+    async fn fetch_index(
+        &self,
+        file_type: FileType,
+        hour: DateTime<Utc>,
+    ) -> Result<Option<GatewayIndex>> {
+        let key = index_key(file_type, hour);
+        if !self.store.list_keys(&key).await?.contains(&key) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(
+            &self.store.get_bytes(key).await?,
+        )?))
+    }
+
+    /// Index the gateways in the records of the files of `file_type` written
+    /// within the hour starting at `hour`, replacing any earlier index of
+    /// the hour. Of an indexed hour only the files holding records of the
+    /// gateway queried are fetched, along with any file written after the
+    /// hour was indexed. Hours without an index are fetched whole
+    pub async fn index(&self, file_type: FileType, hour: DateTime<Utc>) -> Result<GatewayIndex> {
+        let hour = hour_of(hour);
+        // listings exclude files written at exactly `after`
+        let files = self
+            .store
+            .list_all(
+                file_type,
+                hour - Duration::milliseconds(1),
+                hour + Duration::hours(1) - Duration::milliseconds(1),
+            )
+            .await?;
+        let mut gateways = stream::iter(files)
+            .map(|info| async move {
+                let mut gateways = BTreeSet::new();
+                let mut records = self.store.get(info.key.clone()).await?;
+                while let Some(buf) = records.try_next().await? {
+                    if let Some(record) = PocRecord::decode(file_type, &buf)? {
+                        gateways.extend(record.gateways().map(|gateway| gateway.to_string()));
+                    }
+                }
+                Ok::<_, crate::Error>((info, gateways))
+            })
+            .buffered(self.workers);
+
+        let mut index = GatewayIndex::new(file_type, hour);
+        while let Some((info, file_gateways)) = gateways.try_next().await? {
+            index.insert(info.key, &file_gateways);
+        }
+        self.store
+            .put_bytes(&index_key(file_type, hour), serde_json::to_vec(&index)?)
+            .await?;
+        tracing::info!(
+            %file_type,
+            %hour,
+            files = index.files.len(),
+            gateways = index.gateways.len(),
+            "indexed poc files"
+        );
+        Ok(index)
+    }
+
+    /// Stream the valid pocs and rejected reports involving `gateway` which
+    /// were received within `period`, in no particular order. The key of a
+    /// file carries the time it was written, never before the reports it holds
+    /// were received and at most the verification delay after, so only the
+    /// files which can hold reports received within `period` are listed
+    pub async fn gateway_records(
+        &self,
+        gateway: &PublicKeyBinary,
+        period: Range<DateTime<Utc>>,
+    ) -> Result<Stream<PocRecord>> {
+        let written_before = period.end + self.max_verification_delay;
+        let gateway_key = gateway.to_string();
+        let mut sources = Vec::with_capacity(POC_FILE_TYPES.len());
+        for file_type in POC_FILE_TYPES {
+            let files = self
+                .store
+                .list_all(file_type, period.start, written_before)
+                .await?;
+            let hours: BTreeSet<DateTime<Utc>> =
+                files.iter().map(|info| hour_of(info.timestamp)).collect();
+            let mut indexes = HashMap::new();
+            for hour in hours {
+                if let Some(index) = self.fetch_index(file_type, hour).await? {
+                    indexes.insert(hour, index);
+                }
+            }
+            let files = select_files(files, &indexes, &gateway_key);
+
+            let gateway = gateway.clone();
+            let period = period.clone();
+            let infos = stream::iter(files.into_iter().map(Ok)).boxed();
+            sources.push(
+                self.store
+                    .source_unordered(self.workers, infos)
+                    .try_filter_map(move |buf| {
+                        future::ready(PocRecord::decode(file_type, &buf).map(|record| {
+                            record.filter(|record| {
+                                period.contains(&record.received_timestamp())
+                                    && record.involves(&gateway)
+                            })
+                        }))
+                    })
+                    .boxed(),
+            );
+        }
+        Ok(stream::select_all(sources).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn file(file_type: FileType, timestamp: DateTime<Utc>) -> FileInfo {
+        FileInfo::from((file_type, timestamp))
+    }
+
+    #[test]
+    fn only_indexed_files_of_the_gateway_are_selected() {
+        let hour = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let holding = file(FileType::IotPoc, hour + Duration::minutes(5));
+        let other = file(FileType::IotPoc, hour + Duration::minutes(10));
+        let late = file(FileType::IotPoc, hour + Duration::minutes(50));
+        let unindexed = file(FileType::IotPoc, hour + Duration::minutes(70));
+
+        let mut index = GatewayIndex::new(FileType::IotPoc, hour);
+        index.insert(holding.key.clone(), &["gw1".to_string(), "gw2".to_string()]);
+        index.insert(other.key.clone(), &["gw2".to_string()]);
+        let indexes = HashMap::from([(hour, index)]);
+
+        let files = vec![
+            holding.clone(),
+            other.clone(),
+            late.clone(),
+            unindexed.clone(),
+        ];
+        let keys = |files: Vec<FileInfo>| -> Vec<String> {
+            files.into_iter().map(|info| info.key).collect()
+        };
+        assert_eq!(
+            keys(select_files(files.clone(), &indexes, "gw1")),
+            vec![holding.key.clone(), late.key.clone(), unindexed.key.clone()]
+        );
+        assert_eq!(
+            keys(select_files(files.clone(), &indexes, "gw2")),
+            vec![
+                holding.key,
+                other.key,
+                late.key.clone(),
+                unindexed.key.clone()
+            ]
+        );
+        assert_eq!(
+            keys(select_files(files, &indexes, "gw3")),
+            vec![late.key, unindexed.key]
+        );
+    }
+
+    #[test]
+    fn indexes_round_trip() {
+        let hour = Utc.with_ymd_and_hms(2023, 5, 1, 13, 0, 0).unwrap();
+        let mut index = GatewayIndex::new(FileType::IotInvalidBeaconReport, hour);
+        index.insert("a".to_string(), &["gw1".to_string()]);
+        let decoded: GatewayIndex =
+            serde_json::from_slice(&serde_json::to_vec(&index).unwrap()).unwrap();
+        assert_eq!(decoded, index);
+        assert_eq!(
+            index_key(FileType::IotInvalidBeaconReport, hour),
+            format!(
+                "index/iot_invalid_beacon_report.{}.json",
+                hour.timestamp_millis()
+            )
+        );
+    }
+}
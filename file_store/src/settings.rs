@@ -1,4 +1,4 @@
-use crate::{Error, Result};
+use crate::{Error, FileStore, Result};
 use config::{Config, File};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -12,6 +12,24 @@ pub struct Settings {
     /// Optional region for the endpoint. Default: us-west-2
     #[serde(default = "default_region")]
     pub region: String,
+    /// Largest frame accepted when reading files from the bucket. Larger
+    /// frames are skipped and reported as decode errors. Default 15_000_000
+    #[serde(default = "default_max_frame_length")]
+    pub max_frame_length: usize,
+    /// Optional key prefix to copy files containing oversized frames to for
+    /// later inspection. Default none
+    pub quarantine_prefix: Option<String>,
+    /// Optional namespace, e.g. the environment, keys are written, listed and
+    /// read under, as `<namespace>/<key>`. Default none
+    pub namespace: Option<String>,
+    /// Optional namespaces the store is allowed to use. When set the store
+    /// refuses to start with a namespace not listed, or without a namespace.
+    /// Default none
+    pub allowed_namespaces: Option<Vec<String>>,
+    /// Failures injected into bucket requests for testing. Default none
+    #[cfg(feature = "fault-injection")]
+    #[serde(default)]
+    pub faults: crate::fault_injection::Settings,
 
     /// Should only be used for local testing
     pub access_key_id: Option<String>,
@@ -22,6 +40,10 @@ fn default_region() -> String {
     "us-west-2".to_string()
 }
 
+fn default_max_frame_length() -> usize {
+    crate::file_sink::MAX_FRAME_LENGTH
+}
+
 impl Settings {
     /// Load Settings from a given path.
     ///
@@ -33,4 +55,15 @@ impl Settings {
             .and_then(|config| config.try_deserialize())
             .map_err(Error::from)
     }
+
+    /// Check the bucket can be listed, for preflight checks of input buckets
+    pub async fn check_read(&self) -> Result {
+        FileStore::from_settings(self).await?.check_read().await
+    }
+
+    /// Check the bucket can be written to, for preflight checks of output
+    /// buckets
+    pub async fn check_write(&self) -> Result {
+        FileStore::from_settings(self).await?.check_write().await
+    }
 }
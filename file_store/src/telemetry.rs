@@ -0,0 +1,94 @@
+//! Metrics of the S3 requests made by a [`FileStore`](crate::FileStore),
+//! labelled by bucket, operation and file type.
+
+use crate::FileInfo;
+use aws_sdk_s3::types::SdkError;
+use std::time::Instant;
+
+/// Latency of every request, with a `result` label of `ok` or the class of
+/// the error. The latency of a get covers the request up to the response
+/// headers, its body is streamed afterwards while it is decoded. The
+/// unlabelled `file_store_put_duration` and `file_store_remove_duration`
+/// histograms are still recorded alongside, for the dashboards built on them
+const DURATION_METRIC: &str = "file_store_s3_duration";
+/// Size of the objects put and got
+const BYTES_METRIC: &str = "file_store_s3_bytes";
+/// Failed requests by error class
+const ERRORS_METRIC: &str = "file_store_s3_errors";
+const UPLOAD_RETRIES_METRIC: &str = "file_store_upload_retries";
+const UPLOAD_FAILURES_METRIC: &str = "file_store_upload_failures";
+
+/// The file type label of a key, `unknown` for keys not named after a file
+/// type
+pub fn file_type(key: &str) -> &'static str {
+    key.parse::<FileInfo>()
+        .map_or("unknown", |info| info.file_type.to_str())
+}
+
+fn error_class<E>(err: &SdkError<E>) -> &'static str {
+    match err {
+        SdkError::TimeoutError { .. } => "timeout",
+        SdkError::DispatchFailure { .. } => "dispatch",
+        SdkError::ServiceError { .. } => "service",
+        _ => "other",
+    }
+}
+
+/// Record the latency and, on failure, the error class of a request started
+/// at `start`
+pub fn record_request<T, E>(
+    operation: &'static str,
+    bucket: &str,
+    file_type: &'static str,
+    start: Instant,
+    result: &Result<T, SdkError<E>>,
+) {
+    let outcome = match result {
+        Ok(_) => "ok",
+        Err(err) => {
+            let class = error_class(err);
+            metrics::increment_counter!(
+                ERRORS_METRIC,
+                "operation" => operation,
+                "bucket" => bucket.to_string(),
+                "file_type" => file_type,
+                "class" => class
+            );
+            class
+        }
+    };
+    metrics::histogram!(
+        DURATION_METRIC,
+        start.elapsed(),
+        "operation" => operation,
+        "bucket" => bucket.to_string(),
+        "file_type" => file_type,
+        "result" => outcome
+    );
+}
+
+pub fn record_bytes(operation: &'static str, bucket: &str, file_type: &'static str, bytes: u64) {
+    metrics::histogram!(
+        BYTES_METRIC,
+        bytes as f64,
+        "operation" => operation,
+        "bucket" => bucket.to_string(),
+        "file_type" => file_type
+    );
+}
+
+pub fn count_upload_retry(bucket: &str, file_type: &'static str) {
+    metrics::increment_counter!(
+        UPLOAD_RETRIES_METRIC,
+        "bucket" => bucket.to_string(),
+        "file_type" => file_type
+    );
+}
+
+pub fn count_upload_failure(bucket: &str, file_type: &'static str) {
+    metrics::increment_counter!(
+        UPLOAD_FAILURES_METRIC,
+        "bucket" => bucket.to_string(),
+        "file_type" => file_type
+    );
+}
@@ -1,30 +1,33 @@
-use crate::{Error, Result};
-use helium_crypto::{PublicKey, Verify};
+use crate::Result;
+use helium_crypto::PublicKey;
 use helium_proto::services::{
     iot_config, mobile_config,
     poc_lora::{LoraBeaconReportReqV1, LoraWitnessReportReqV1},
-};
-use helium_proto::{
-    services::poc_mobile::{
+    poc_mobile::{
         CellHeartbeatReqV1, CoverageObjectReqV1, DataTransferSessionReqV1, SpeedtestReqV1,
         SubscriberLocationReqV1,
     },
-    Message,
 };
 
 pub trait MsgVerify {
     fn verify(&self, verifier: &PublicKey) -> Result;
 }
 
+/// Implements [`MsgVerify`] for a message signed over its encoding with the
+/// signature field `$sig` left empty, also for the messages services define
+/// outside of helium-proto
+#[macro_export]
 macro_rules! impl_msg_verify {
     ($msg_type:ty, $sig: ident) => {
-        impl MsgVerify for $msg_type {
-            fn verify(&self, verifier: &PublicKey) -> Result {
-                let mut buf = vec![];
+        impl $crate::traits::MsgVerify for $msg_type {
+            fn verify(&self, verifier: &::helium_crypto::PublicKey) -> $crate::Result {
+                use ::helium_crypto::Verify;
+                use ::prost::Message;
                 let mut msg = self.clone();
                 msg.$sig = vec![];
-                msg.encode(&mut buf)?;
-                verifier.verify(&buf, &self.$sig).map_err(Error::from)
+                verifier
+                    .verify(&msg.encode_to_vec(), &self.$sig)
+                    .map_err($crate::Error::from)
             }
         }
     };
@@ -46,6 +49,7 @@ impl_msg_verify!(iot_config::OrgEnableResV1, signature);
 impl_msg_verify!(iot_config::OrgResV1, signature);
 impl_msg_verify!(iot_config::OrgListResV1, signature);
 impl_msg_verify!(iot_config::RouteStreamReqV1, signature);
+impl_msg_verify!(iot_config::RouteStreamResV1, signature);
 impl_msg_verify!(iot_config::RouteListReqV1, signature);
 impl_msg_verify!(iot_config::RouteGetReqV1, signature);
 impl_msg_verify!(iot_config::RouteCreateReqV1, signature);
@@ -68,6 +72,8 @@ impl_msg_verify!(iot_config::GatewayInfoStreamReqV1, signature);
 impl_msg_verify!(iot_config::RegionParamsReqV1, signature);
 impl_msg_verify!(iot_config::GatewayInfoResV1, signature);
 impl_msg_verify!(iot_config::GatewayInfoStreamResV1, signature);
+impl_msg_verify!(iot_config::GatewayLocationResV1, signature);
+impl_msg_verify!(iot_config::GatewayRegionParamsResV1, signature);
 impl_msg_verify!(iot_config::RegionParamsResV1, signature);
 impl_msg_verify!(mobile_config::AdminAddKeyReqV1, signature);
 impl_msg_verify!(mobile_config::AdminRemoveKeyReqV1, signature);
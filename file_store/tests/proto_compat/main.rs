@@ -0,0 +1,638 @@
+//! Compatibility of the messages we write with consumers of older helium-proto
+//! revisions, whose decoders silently skip fields they don't know.
+
+mod r065699f;
+
+use file_store::{
+    iot_balance_warning::BalanceWarningV1,
+    iot_beacon_cadence::BeaconCadenceReportV1,
+    iot_gateway_reconciliation::{GatewayDriftV1, GatewayReconciliationV1},
+    iot_gateway_reward_class::{GatewayClassV1, GatewayRewardClassV1},
+    iot_hex_density_snapshot::{HexDensityScaleV1, HexDensitySnapshotV1},
+    iot_hex_scale_comparison::{HexScaleComparisonV1, HexScalesV1},
+    iot_packet_price::PacketPriceV1,
+    iot_unallocated_reward::{RewardClassV1, UnallocatedReasonV1, UnallocatedRewardV1},
+    iot_verification_bypass::VerificationBypassV1,
+    iot_witness_inclusion::{WitnessInclusionV1, WitnessProofV1},
+    iot_witness_quality::{InvalidReasonCountV1, WitnessQualityReportV1},
+    iot_witness_rssi_check::WitnessRssiCheckV1,
+    mobile_onboarding_rejection::OnboardingRejectionV1,
+    mobile_reward_dust::RewardDustV1,
+    FileType,
+};
+use helium_proto::{
+    services::{
+        packet_verifier::{InvalidPacket, ValidDataTransferSession, ValidPacket},
+        poc_lora::{
+            self, iot_reward_share, IotRewardShare, LoraBeaconIngestReportV1,
+            LoraBeaconReportReqV1, LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1,
+            LoraPocV1, LoraValidBeaconReportV1, LoraVerifiedWitnessReportV1,
+            LoraWitnessIngestReportV1, LoraWitnessReportReqV1, NonRewardablePacket,
+            OperationalReward,
+        },
+        poc_mobile::{
+            self, mobile_reward_share, Heartbeat, MobileRewardShare, RadioReward, Speedtest,
+            SpeedtestAvg, SubscriberReward,
+        },
+    },
+    RewardManifest,
+};
+use prost::Message;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The pinned releases, by helium-proto revision. Each module `r<revision>`
+/// holds the outputs of a release still read by consumers of that revision as
+/// the release encoded them. Fixtures never change but to drop a field
+/// helium-proto removed, and are deleted once no consumer of their revision
+/// runs anymore
+const FIXTURES: &[(&str, fn() -> Vec<Sample>)] = &[(
+    "065699f13438ab7aa148df8d5b68efbaa53d6369",
+    r065699f::outputs,
+)];
+
+/// An encoded output message
+struct Sample {
+    file_type: FileType,
+    encoded: Vec<u8>,
+}
+
+impl Sample {
+    fn new(file_type: FileType, msg: impl Message) -> Self {
+        Self {
+            file_type,
+            encoded: msg.encode_to_vec(),
+        }
+    }
+
+    /// The `<tag path>:<wire type>` of every field emitted
+    fn fields(&self) -> BTreeSet<String> {
+        let mut fields = BTreeSet::new();
+        collect_fields(&self.encoded, "", &mut fields);
+        fields
+    }
+}
+
+fn beacon() -> LoraBeaconReportReqV1 {
+    LoraBeaconReportReqV1 {
+        pub_key: vec![1],
+        local_entropy: vec![1],
+        remote_entropy: vec![1],
+        data: vec![1],
+        frequency: 1,
+        channel: 1,
+        datarate: 1,
+        tx_power: 1,
+        timestamp: 1,
+        signature: vec![1],
+        tmst: 1,
+    }
+}
+
+fn witness() -> LoraWitnessReportReqV1 {
+    LoraWitnessReportReqV1 {
+        pub_key: vec![1],
+        data: vec![1],
+        timestamp: 1,
+        signal: 1,
+        snr: 1,
+        frequency: 1,
+        datarate: 1,
+        signature: vec![1],
+        tmst: 1,
+    }
+}
+
+fn verified_witness() -> LoraVerifiedWitnessReportV1 {
+    LoraVerifiedWitnessReportV1 {
+        received_timestamp: 1,
+        status: 1,
+        report: Some(witness()),
+        location: "1".to_string(),
+        gain: 1,
+        elevation: 1,
+        hex_scale: 1,
+        reward_unit: 1,
+        invalid_reason: 1,
+        participant_side: 1,
+    }
+}
+
+fn valid_packet() -> ValidPacket {
+    ValidPacket {
+        payload_size: 1,
+        gateway: vec![1],
+        payload_hash: vec![1],
+        num_dcs: 1,
+        packet_timestamp: 1,
+    }
+}
+
+/// Outputs of helium-proto messages
+fn proto_samples() -> Vec<Sample> {
+    vec![
+        Sample::new(
+            FileType::IotBeaconIngestReport,
+            LoraBeaconIngestReportV1 {
+                received_timestamp: 1,
+                report: Some(beacon()),
+            },
+        ),
+        Sample::new(
+            FileType::IotWitnessIngestReport,
+            LoraWitnessIngestReportV1 {
+                received_timestamp: 1,
+                report: Some(witness()),
+            },
+        ),
+        Sample::new(
+            FileType::IotPoc,
+            LoraPocV1 {
+                poc_id: vec![1],
+                beacon_report: Some(LoraValidBeaconReportV1 {
+                    received_timestamp: 1,
+                    location: "1".to_string(),
+                    gain: 1,
+                    elevation: 1,
+                    hex_scale: 1,
+                    report: Some(beacon()),
+                    reward_unit: 1,
+                }),
+                selected_witnesses: vec![verified_witness()],
+                unselected_witnesses: vec![verified_witness()],
+            },
+        ),
+        Sample::new(
+            FileType::IotInvalidBeaconReport,
+            LoraInvalidBeaconReportV1 {
+                received_timestamp: 1,
+                reason: 1,
+                report: Some(beacon()),
+            },
+        ),
+        Sample::new(
+            FileType::IotInvalidWitnessReport,
+            LoraInvalidWitnessReportV1 {
+                received_timestamp: 1,
+                reason: 1,
+                report: Some(witness()),
+                participant_side: 1,
+            },
+        ),
+        Sample::new(FileType::IotValidPacket, valid_packet()),
+        Sample::new(
+            FileType::InvalidPacket,
+            InvalidPacket {
+                payload_size: 1,
+                gateway: vec![1],
+                payload_hash: vec![1],
+                reason: 1,
+            },
+        ),
+        Sample::new(
+            FileType::NonRewardablePacket,
+            NonRewardablePacket {
+                packet: Some(valid_packet()),
+                reason: 1,
+                timestamp: 1,
+            },
+        ),
+        Sample::new(
+            FileType::IotRewardShare,
+            IotRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(iot_reward_share::Reward::GatewayReward(
+                    poc_lora::GatewayReward {
+                        hotspot_key: vec![1],
+                        beacon_amount: 1,
+                        witness_amount: 1,
+                        dc_transfer_amount: 1,
+                    },
+                )),
+            },
+        ),
+        Sample::new(
+            FileType::IotRewardShare,
+            IotRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(iot_reward_share::Reward::OperationalReward(
+                    OperationalReward { amount: 1 },
+                )),
+            },
+        ),
+        Sample::new(
+            FileType::RewardManifest,
+            RewardManifest {
+                start_timestamp: 1,
+                end_timestamp: 1,
+                written_files: vec!["1".to_string()],
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(mobile_reward_share::Reward::GatewayReward(
+                    poc_mobile::GatewayReward {
+                        hotspot_key: vec![1],
+                        dc_transfer_reward: 1,
+                    },
+                )),
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                // the remaining radio reward fields are never written
+                reward: Some(mobile_reward_share::Reward::RadioReward(RadioReward {
+                    hotspot_key: vec![1],
+                    cbsd_id: "1".to_string(),
+                    poc_reward: 1,
+                    ..Default::default()
+                })),
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(mobile_reward_share::Reward::SubscriberReward(
+                    SubscriberReward {
+                        subscriber_id: vec![1],
+                        discovery_location_amount: 1,
+                    },
+                )),
+            },
+        ),
+        Sample::new(
+            FileType::ValidDataTransferSession,
+            ValidDataTransferSession {
+                pub_key: vec![1],
+                payer: vec![1],
+                upload_bytes: 1,
+                download_bytes: 1,
+                num_dcs: 1,
+                first_timestamp: 1,
+                last_timestamp: 1,
+            },
+        ),
+        Sample::new(
+            FileType::ValidatedHeartbeat,
+            Heartbeat {
+                cbsd_id: "1".to_string(),
+                pub_key: vec![1],
+                reward_multiplier: 1.0,
+                cell_type: 1,
+                validity: 1,
+                timestamp: 1,
+                // written empty
+                coverage_object: Vec::new(),
+            },
+        ),
+        Sample::new(
+            FileType::SpeedtestAvg,
+            SpeedtestAvg {
+                pub_key: vec![1],
+                upload_speed_avg_bps: 1,
+                download_speed_avg_bps: 1,
+                latency_avg_ms: 1,
+                timestamp: 1,
+                speedtests: vec![Speedtest {
+                    timestamp: 1,
+                    upload_speed_bps: 1,
+                    download_speed_bps: 1,
+                    latency_ms: 1,
+                }],
+                validity: 1,
+                reward_multiplier: 1.0,
+            },
+        ),
+    ]
+}
+
+/// Outputs of the messages declared in file_store
+fn local_samples() -> Vec<Sample> {
+    vec![
+        Sample::new(
+            FileType::IotBalanceWarning,
+            BalanceWarningV1 {
+                oui: 1,
+                payer: vec![2],
+                balance: 3,
+                daily_burn_rate: 4,
+                projected_exhaustion_timestamp: 5,
+                timestamp: 6,
+            },
+        ),
+        Sample::new(
+            FileType::IotBeaconCadenceReport,
+            BeaconCadenceReportV1 {
+                pub_key: vec![1],
+                beacon_count: 2,
+                median_interval: 3,
+                edge_ratio: 0.4,
+                burst_ratio: 0.5,
+                at_interval_edge: true,
+                in_bursts: true,
+                window_start: 8,
+                window_end: 9,
+            },
+        ),
+        Sample::new(
+            FileType::IotGatewayReconciliation,
+            GatewayReconciliationV1 {
+                timestamp: 1,
+                sampled: 2,
+                errors: 3,
+                repaired: true,
+                drifted: vec![GatewayDriftV1 {
+                    pub_key: vec![1],
+                    missing: true,
+                    location: true,
+                    gain: true,
+                    region: true,
+                }],
+            },
+        ),
+        Sample::new(
+            FileType::IotHexScaleComparison,
+            HexScaleComparisonV1 {
+                timestamp: 1,
+                weighted: true,
+                hexes: 3,
+                differing: vec![HexScalesV1 {
+                    hex: 1,
+                    presence_scale: 2,
+                    weighted_scale: 3,
+                }],
+            },
+        ),
+        Sample::new(
+            FileType::IotHexDensitySnapshot,
+            HexDensitySnapshotV1 {
+                timestamp: 1,
+                active_since: 2,
+                weighted: true,
+                scales: vec![HexDensityScaleV1 { hex: 1, scale: 2 }],
+            },
+        ),
+        Sample::new(
+            FileType::IotPacketPrice,
+            PacketPriceV1 {
+                oui: 1,
+                gateway: vec![2],
+                payload_hash: vec![3],
+                payload_size: 4,
+                rule: "5".to_string(),
+                bytes_per_unit: 6,
+                dc_per_unit: 7,
+                num_dcs: 8,
+                packet_timestamp: 9,
+            },
+        ),
+        Sample::new(
+            FileType::IotVerificationBypass,
+            VerificationBypassV1 {
+                timestamp: 1,
+                poc_id: vec![2],
+                pub_key: vec![3],
+                check: "4".to_string(),
+            },
+        ),
+        Sample::new(
+            FileType::IotWitnessInclusion,
+            WitnessInclusionV1 {
+                poc_id: vec![1],
+                witness_root: vec![2],
+                witness_count: 3,
+                proofs: vec![WitnessProofV1 {
+                    pub_key: vec![1],
+                    index: 2,
+                    leaf: vec![3],
+                    siblings: vec![vec![4]],
+                }],
+            },
+        ),
+        Sample::new(
+            FileType::IotWitnessQuality,
+            WitnessQualityReportV1 {
+                pub_key: vec![1],
+                valid: 2,
+                invalid: 3,
+                score: 0.4,
+                reasons: vec![InvalidReasonCountV1 {
+                    reason: "1".to_string(),
+                    witnesses: 2,
+                }],
+                window_start: 6,
+                window_end: 7,
+            },
+        ),
+        Sample::new(
+            FileType::IotWitnessRssiCheck,
+            WitnessRssiCheckV1 {
+                timestamp: 1,
+                poc_id: vec![2],
+                pub_key: vec![3],
+                path_loss_model: "4".to_string(),
+                margin_db: 5.0,
+                expected_rssi_dbm: 6.0,
+                signal: 7,
+                distance: 8,
+            },
+        ),
+        Sample::new(
+            FileType::IotGatewayRewardClass,
+            GatewayRewardClassV1 {
+                hotspot_key: vec![1],
+                class: GatewayClassV1::DataOnly as i32,
+                start_period: 3,
+                end_period: 4,
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardDust,
+            RewardDustV1 {
+                start_timestamp: 1,
+                end_timestamp: 2,
+                poc: 3,
+                data_transfer: 4,
+                mapping: 5,
+            },
+        ),
+        Sample::new(
+            FileType::MobileOnboardingRejection,
+            OnboardingRejectionV1 {
+                cbsd_id: "1".to_string(),
+                pub_key: vec![2],
+                timestamp: 3,
+                reason: 1,
+                maker: "5".to_string(),
+            },
+        ),
+        Sample::new(
+            FileType::IotUnallocatedReward,
+            UnallocatedRewardV1 {
+                class: RewardClassV1::Witness as i32,
+                reason: UnallocatedReasonV1::ClassDisabled as i32,
+                amount: 3,
+                start_period: 4,
+                end_period: 5,
+            },
+        ),
+    ]
+}
+
+/// Samples set every field we write, so any field added to a message is
+/// emitted
+fn samples() -> Vec<Sample> {
+    let mut samples = proto_samples();
+    samples.extend(local_samples());
+    samples
+}
+
+/// The fields of an encoded message, as `(tag, wire type, value)`, if it
+/// parses as one. Samples set bytes and strings to values that don't.
+fn parse(mut msg: &[u8]) -> Option<Vec<(u64, u64, &[u8])>> {
+    fn varint(buf: &mut &[u8]) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = buf.split_first()?;
+            *buf = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+    fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if buf.len() < len {
+            return None;
+        }
+        let (value, rest) = buf.split_at(len);
+        *buf = rest;
+        Some(value)
+    }
+
+    let mut fields = Vec::new();
+    while !msg.is_empty() {
+        let key = varint(&mut msg)?;
+        let (tag, wire_type) = (key >> 3, key & 0x7);
+        if tag == 0 {
+            return None;
+        }
+        let value = match wire_type {
+            0 => {
+                varint(&mut msg)?;
+                &[][..]
+            }
+            1 => take(&mut msg, 8)?,
+            2 => {
+                let len = varint(&mut msg)? as usize;
+                take(&mut msg, len)?
+            }
+            5 => take(&mut msg, 4)?,
+            _ => return None,
+        };
+        fields.push((tag, wire_type, value));
+    }
+    Some(fields)
+}
+
+fn collect_fields(msg: &[u8], prefix: &str, fields: &mut BTreeSet<String>) {
+    for (tag, wire_type, value) in parse(msg).expect("encoded message") {
+        let path = if prefix.is_empty() {
+            tag.to_string()
+        } else {
+            format!("{prefix}.{tag}")
+        };
+        if wire_type == 2 && !value.is_empty() && parse(value).is_some() {
+            collect_fields(value, &path, fields);
+        }
+        fields.insert(format!("{path}:{wire_type}"));
+    }
+}
+
+/// Fields emitted per file type by the outputs of a release
+fn pinned_fields(outputs: Vec<Sample>) -> BTreeMap<String, BTreeSet<String>> {
+    let mut pinned: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for output in outputs {
+        pinned
+            .entry(output.file_type.to_string())
+            .or_default()
+            .extend(output.fields());
+    }
+    pinned
+}
+
+/// Every field a sample emits, by tag path and wire type and nested ones
+/// included, must be emitted by the fixtures writing the same file type.
+/// Emitting a new field or changing a wire type takes a version bump of the
+/// output `FileType`. A file type no pinned release writes has no consumer to
+/// break yet; the fixture of its first release pins it
+#[test]
+fn outputs_decode_with_pinned_revisions() {
+    let fixtures: Vec<_> = FIXTURES
+        .iter()
+        .map(|(revision, outputs)| (revision, pinned_fields(outputs())))
+        .collect();
+
+    let mut failures = Vec::new();
+    for sample in samples() {
+        let file_type = sample.file_type.to_string();
+        let fields = sample.fields();
+        for (revision, pinned) in &fixtures {
+            let Some(pinned) = pinned.get(&file_type) else {
+                continue;
+            };
+            for field in fields.difference(pinned) {
+                failures.push(format!(
+                    "{file_type}: field {field} unknown to consumers of {revision}"
+                ));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "outputs incompatible with pinned consumers, bump the file type:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn samples_cover_the_pinned_file_types() {
+    let written: BTreeSet<_> = samples()
+        .iter()
+        .map(|sample| sample.file_type.to_string())
+        .collect();
+    for (revision, outputs) in FIXTURES {
+        for file_type in pinned_fields(outputs()).keys() {
+            assert!(
+                written.contains(file_type),
+                "{file_type} of {revision} has no sample"
+            );
+        }
+    }
+}
+
+#[test]
+fn nested_messages_are_descended_into() {
+    let fields = Sample::new(
+        FileType::NonRewardablePacket,
+        NonRewardablePacket {
+            packet: Some(valid_packet()),
+            reason: 1,
+            timestamp: 1,
+        },
+    )
+    .fields();
+    // the packet, its five fields, the reason and the timestamp
+    assert_eq!(fields.len(), 8);
+    assert_eq!(fields.iter().filter(|field| field.contains('.')).count(), 5);
+}
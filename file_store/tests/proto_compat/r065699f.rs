@@ -0,0 +1,292 @@
+//! The outputs of the baseline release, built against helium-proto
+//! 065699f13438ab7aa148df8d5b68efbaa53d6369, as its consumers decode them.
+#![allow(clippy::needless_update)]
+
+use super::Sample;
+use file_store::FileType;
+use helium_proto::{
+    services::{
+        packet_verifier::{InvalidPacket, ValidDataTransferSession, ValidPacket},
+        poc_lora::{
+            self, iot_reward_share, IotRewardShare, LoraBeaconIngestReportV1,
+            LoraBeaconReportReqV1, LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1,
+            LoraPocV1, LoraValidBeaconReportV1, LoraVerifiedWitnessReportV1,
+            LoraWitnessIngestReportV1, LoraWitnessReportReqV1, NonRewardablePacket,
+            OperationalReward,
+        },
+        poc_mobile::{
+            self, mobile_reward_share, Heartbeat, MobileRewardShare, RadioReward, Speedtest,
+            SpeedtestAvg, SubscriberReward,
+        },
+    },
+    RewardManifest,
+};
+
+fn beacon() -> LoraBeaconReportReqV1 {
+    LoraBeaconReportReqV1 {
+        pub_key: vec![1],
+        local_entropy: vec![1],
+        remote_entropy: vec![1],
+        data: vec![1],
+        frequency: 1,
+        channel: 1,
+        datarate: 1,
+        tx_power: 1,
+        timestamp: 1,
+        signature: vec![1],
+        tmst: 1,
+        ..Default::default()
+    }
+}
+
+fn witness() -> LoraWitnessReportReqV1 {
+    LoraWitnessReportReqV1 {
+        pub_key: vec![1],
+        data: vec![1],
+        timestamp: 1,
+        signal: 1,
+        snr: 1,
+        frequency: 1,
+        datarate: 1,
+        signature: vec![1],
+        tmst: 1,
+        ..Default::default()
+    }
+}
+
+fn verified_witness() -> LoraVerifiedWitnessReportV1 {
+    LoraVerifiedWitnessReportV1 {
+        received_timestamp: 1,
+        status: 1,
+        report: Some(witness()),
+        location: "1".to_string(),
+        gain: 1,
+        elevation: 1,
+        hex_scale: 1,
+        reward_unit: 1,
+        invalid_reason: 1,
+        participant_side: 1,
+        ..Default::default()
+    }
+}
+
+fn valid_packet() -> ValidPacket {
+    ValidPacket {
+        payload_size: 1,
+        gateway: vec![1],
+        payload_hash: vec![1],
+        num_dcs: 1,
+        packet_timestamp: 1,
+        ..Default::default()
+    }
+}
+
+/// Frozen: each message sets exactly the fields the release wrote and leaves
+/// any field added to helium-proto since at its default
+pub fn outputs() -> Vec<Sample> {
+    vec![
+        Sample::new(
+            FileType::IotBeaconIngestReport,
+            LoraBeaconIngestReportV1 {
+                received_timestamp: 1,
+                report: Some(beacon()),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotWitnessIngestReport,
+            LoraWitnessIngestReportV1 {
+                received_timestamp: 1,
+                report: Some(witness()),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotPoc,
+            LoraPocV1 {
+                poc_id: vec![1],
+                beacon_report: Some(LoraValidBeaconReportV1 {
+                    received_timestamp: 1,
+                    location: "1".to_string(),
+                    gain: 1,
+                    elevation: 1,
+                    hex_scale: 1,
+                    report: Some(beacon()),
+                    reward_unit: 1,
+                    ..Default::default()
+                }),
+                selected_witnesses: vec![verified_witness()],
+                unselected_witnesses: vec![verified_witness()],
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotInvalidBeaconReport,
+            LoraInvalidBeaconReportV1 {
+                received_timestamp: 1,
+                reason: 1,
+                report: Some(beacon()),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotInvalidWitnessReport,
+            LoraInvalidWitnessReportV1 {
+                received_timestamp: 1,
+                reason: 1,
+                report: Some(witness()),
+                participant_side: 1,
+                ..Default::default()
+            },
+        ),
+        Sample::new(FileType::IotValidPacket, valid_packet()),
+        Sample::new(
+            FileType::InvalidPacket,
+            InvalidPacket {
+                payload_size: 1,
+                gateway: vec![1],
+                payload_hash: vec![1],
+                reason: 1,
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::NonRewardablePacket,
+            NonRewardablePacket {
+                packet: Some(valid_packet()),
+                reason: 1,
+                timestamp: 1,
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotRewardShare,
+            IotRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(iot_reward_share::Reward::GatewayReward(
+                    poc_lora::GatewayReward {
+                        hotspot_key: vec![1],
+                        beacon_amount: 1,
+                        witness_amount: 1,
+                        dc_transfer_amount: 1,
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::IotRewardShare,
+            IotRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(iot_reward_share::Reward::OperationalReward(
+                    OperationalReward {
+                        amount: 1,
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::RewardManifest,
+            RewardManifest {
+                start_timestamp: 1,
+                end_timestamp: 1,
+                written_files: vec!["1".to_string()],
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(mobile_reward_share::Reward::GatewayReward(
+                    poc_mobile::GatewayReward {
+                        hotspot_key: vec![1],
+                        dc_transfer_reward: 1,
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(mobile_reward_share::Reward::RadioReward(RadioReward {
+                    hotspot_key: vec![1],
+                    cbsd_id: "1".to_string(),
+                    poc_reward: 1,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::MobileRewardShare,
+            MobileRewardShare {
+                start_period: 1,
+                end_period: 1,
+                reward: Some(mobile_reward_share::Reward::SubscriberReward(
+                    SubscriberReward {
+                        subscriber_id: vec![1],
+                        discovery_location_amount: 1,
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::ValidDataTransferSession,
+            ValidDataTransferSession {
+                pub_key: vec![1],
+                payer: vec![1],
+                upload_bytes: 1,
+                download_bytes: 1,
+                num_dcs: 1,
+                first_timestamp: 1,
+                last_timestamp: 1,
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::ValidatedHeartbeat,
+            Heartbeat {
+                cbsd_id: "1".to_string(),
+                pub_key: vec![1],
+                reward_multiplier: 1.0,
+                cell_type: 1,
+                validity: 1,
+                timestamp: 1,
+                ..Default::default()
+            },
+        ),
+        Sample::new(
+            FileType::SpeedtestAvg,
+            SpeedtestAvg {
+                pub_key: vec![1],
+                upload_speed_avg_bps: 1,
+                download_speed_avg_bps: 1,
+                latency_avg_ms: 1,
+                timestamp: 1,
+                speedtests: vec![Speedtest {
+                    timestamp: 1,
+                    upload_speed_bps: 1,
+                    download_speed_bps: 1,
+                    latency_ms: 1,
+                    ..Default::default()
+                }],
+                validity: 1,
+                reward_multiplier: 1.0,
+                ..Default::default()
+            },
+        ),
+    ]
+}
@@ -0,0 +1,217 @@
+//! H3 hex helpers shared by the oracle servers, making the resolution that
+//! locations are compared at explicit.
+
+use h3o::{
+    error::{CompactionError, InvalidCellIndex, InvalidGeometry, LocalIjError},
+    geom::{Polygon, ToCells},
+    CellIndex, LatLng, Resolution,
+};
+
+pub use h3o;
+
+pub type Result<T = ()> = std::result::Result<T, Error>;
+
+/// Resolution mismatches fail loudly, where `CellIndex::parent` quietly
+/// returns `None` for a finer resolution
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid cell: {0}")]
+    InvalidCell(#[from] InvalidCellIndex),
+    #[error("cell {cell} at resolution {cell_res} has no parent at resolution {res}")]
+    ResolutionMismatch {
+        cell: CellIndex,
+        cell_res: Resolution,
+        res: Resolution,
+    },
+    #[error("uncomputable grid distance: {0}")]
+    GridDistance(#[from] LocalIjError),
+    #[error("invalid geometry: {0}")]
+    InvalidGeometry(#[from] InvalidGeometry),
+    #[error("compaction error: {0}")]
+    Compaction(#[from] CompactionError),
+}
+
+/// Parse a raw h3 index
+pub fn cell(index: u64) -> Result<CellIndex> {
+    Ok(CellIndex::try_from(index)?)
+}
+
+/// Parse an h3 index in its hex string form, as locations are carried in
+/// gateway metadata
+pub fn parse(index: &str) -> Result<CellIndex> {
+    Ok(index.parse()?)
+}
+
+/// The cell at `res` containing `cell`, the cell itself at its own
+/// resolution. Fails when `res` is finer than the resolution of `cell`.
+pub fn to_res(cell: CellIndex, res: Resolution) -> Result<CellIndex> {
+    cell.parent(res).ok_or(Error::ResolutionMismatch {
+        cell,
+        cell_res: cell.resolution(),
+        res,
+    })
+}
+
+/// The cell at `res` containing the raw h3 index `index`
+pub fn cell_at(index: u64, res: Resolution) -> Result<CellIndex> {
+    to_res(cell(index)?, res)
+}
+
+/// The children of `cell` at the finer resolution `res`
+pub fn children(cell: CellIndex, res: Resolution) -> Result<Vec<CellIndex>> {
+    if res < cell.resolution() {
+        return Err(Error::ResolutionMismatch {
+            cell,
+            cell_res: cell.resolution(),
+            res,
+        });
+    }
+    Ok(cell.children(res).collect())
+}
+
+/// `cell` and all cells within `k` grid steps of it
+pub fn k_ring(cell: CellIndex, k: u32) -> Vec<CellIndex> {
+    cell.grid_disk::<Vec<_>>(k)
+}
+
+/// Grid distance between the cells at `res` containing the raw indexes `a`
+/// and `b`
+pub fn grid_distance_at(a: u64, b: u64, res: Resolution) -> Result<u32> {
+    let a = cell_at(a, res)?;
+    let b = cell_at(b, res)?;
+    Ok(a.grid_distance(b)? as u32)
+}
+
+/// Great circle distance in meters between the centers of the cells of the
+/// raw indexes `a` and `b`, rounded to the meter
+pub fn distance_m(a: u64, b: u64) -> Result<u32> {
+    let a = LatLng::from(cell(a)?);
+    let b = LatLng::from(cell(b)?);
+    Ok(a.distance_m(b).round() as u32)
+}
+
+/// The cells at `res` whose centers fall within `polygon`, given in degrees
+pub fn polyfill(polygon: geo_types::Polygon<f64>, res: Resolution) -> Result<Vec<CellIndex>> {
+    let polygon = Polygon::from_degrees(polygon)?;
+    Ok(polygon.to_cells(res).collect())
+}
+
+/// Replace complete sets of sibling cells by their parents, recursively.
+/// All cells must be at the same resolution.
+pub fn compact(cells: impl IntoIterator<Item = CellIndex>) -> Result<Vec<CellIndex>> {
+    let mut cells: Vec<CellIndex> = cells.into_iter().collect();
+    cells.sort_unstable();
+    cells.dedup();
+    CellIndex::compact(&mut cells)?;
+    Ok(cells)
+}
+
+/// Expand compacted cells back to their children at `res`. Fails when a cell
+/// is finer than `res`.
+pub fn uncompact(cells: &[CellIndex], res: Resolution) -> Result<Vec<CellIndex>> {
+    if let Some(cell) = cells.iter().find(|cell| cell.resolution() > res) {
+        return Err(Error::ResolutionMismatch {
+            cell: *cell,
+            cell_res: cell.resolution(),
+            res,
+        });
+    }
+    Ok(CellIndex::uncompact(cells.iter().copied(), res).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn resolution() -> impl Strategy<Value = Resolution> {
+        (0_u8..=15).prop_map(|res| Resolution::try_from(res).unwrap())
+    }
+
+    fn cell_strategy() -> impl Strategy<Value = CellIndex> {
+        (-85.0_f64..85.0, -180.0_f64..180.0, resolution()).prop_map(|(lat, lng, res)| {
+            LatLng::new(lat, lng)
+                .expect("valid coordinates")
+                .to_cell(res)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn to_res_contains_cell(cell in cell_strategy(), res in resolution()) {
+            match to_res(cell, res) {
+                Ok(parent) => {
+                    prop_assert!(res <= cell.resolution());
+                    prop_assert_eq!(parent.resolution(), res);
+                    prop_assert!(children(parent, cell.resolution())?.contains(&cell));
+                }
+                Err(Error::ResolutionMismatch { .. }) => prop_assert!(res > cell.resolution()),
+                Err(err) => return Err(TestCaseError::fail(err.to_string())),
+            }
+        }
+
+        #[test]
+        fn parse_round_trips(index in cell_strategy()) {
+            prop_assert_eq!(parse(&index.to_string())?, index);
+            prop_assert_eq!(cell(u64::from(index))?, index);
+        }
+
+        #[test]
+        fn to_res_at_own_resolution_is_identity(cell in cell_strategy()) {
+            prop_assert_eq!(to_res(cell, cell.resolution())?, cell);
+        }
+
+        #[test]
+        fn k_ring_is_within_distance(cell in cell_strategy(), k in 0_u32..4) {
+            for neighbor in k_ring(cell, k) {
+                // distances across pentagon distortion may be uncomputable
+                if let Ok(distance) = cell.grid_distance(neighbor) {
+                    prop_assert!(distance as u32 <= k);
+                }
+            }
+        }
+
+        #[test]
+        fn grid_distance_is_symmetric(a in cell_strategy(), b in cell_strategy()) {
+            let res = Resolution::Five;
+            if let (Ok(ab), Ok(ba)) = (
+                grid_distance_at(a.into(), b.into(), res),
+                grid_distance_at(b.into(), a.into(), res),
+            ) {
+                prop_assert_eq!(ab, ba);
+            }
+        }
+
+        #[test]
+        fn uncompact_reverses_compact(cell in cell_strategy(), k in 0_u32..3) {
+            let cells: HashSet<CellIndex> = k_ring(cell, k).into_iter().collect();
+            let compacted = compact(cells.iter().copied())?;
+            let uncompacted: HashSet<CellIndex> =
+                uncompact(&compacted, cell.resolution())?.into_iter().collect();
+            prop_assert_eq!(cells, uncompacted);
+        }
+    }
+
+    #[test]
+    fn polyfill_covers_polygon_center() {
+        let polygon = geo_types::Polygon::new(
+            geo_types::LineString::from(vec![
+                (-122.42, 37.77),
+                (-122.40, 37.77),
+                (-122.40, 37.79),
+                (-122.42, 37.79),
+                (-122.42, 37.77),
+            ]),
+            vec![],
+        );
+        let cells = polyfill(polygon, Resolution::Nine).expect("valid polygon");
+        let center = LatLng::new(37.78, -122.41)
+            .expect("valid coordinates")
+            .to_cell(Resolution::Nine);
+        assert!(cells.contains(&center));
+        assert!(cells
+            .iter()
+            .all(|cell| cell.resolution() == Resolution::Nine));
+    }
+}
@@ -0,0 +1,461 @@
+//! Direct writes of iot reports into the report table of the iot verifier,
+//! for private deployments where ingest and verifier share a database.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use denylist::DenyList;
+use file_store::{
+    iot_beacon_report::IotBeaconIngestReport, iot_witness_report::IotWitnessIngestReport,
+    traits::IngestId,
+};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::poc_lora::{LoraBeaconIngestReportV1, LoraWitnessIngestReportV1},
+    Message,
+};
+use iot_config::{client::Client as IotConfigClient, gateway_info::GatewayInfoResolver};
+use retainer::Cache;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::RwLock,
+    time::{self, MissedTickBehavior},
+};
+
+const WRITE_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_direct_db_write");
+const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// Database of the iot verifier to write reports to
+    pub database: db_store::Settings,
+    /// Write reports to the output bucket too. Default true
+    #[serde(default = "default_write_output")]
+    pub write_output: bool,
+    /// Iot config service gateways are looked up in
+    pub iot_config_client: iot_config::client::Settings,
+    /// Denylist of the gateways whose reports are not written
+    pub denylist: denylist::Settings,
+    /// How long whether a gateway is known to iot config is cached for.
+    /// Default 5m
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_gateway_cache_ttl"
+    )]
+    pub gateway_cache_ttl: Duration,
+}
+
+fn default_write_output() -> bool {
+    true
+}
+
+fn default_gateway_cache_ttl() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+/// Why a report was not written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dropped {
+    Denied,
+    Unknown,
+}
+
+impl Dropped {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Denied => "denied",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Inserts every report accepted into the `poc_report` table of the verifier,
+/// ready for verification without waiting for the loader to pick it up from
+/// the output bucket. Reports loaded from the bucket later on are the same
+/// rows, and are left as written. Reports of gateways on the denylist or
+/// unknown to iot config are not written, as the loader would drop them too
+#[derive(Clone)]
+pub struct DirectDb<R = IotConfigClient> {
+    pool: Pool<Postgres>,
+    deny_list: Arc<RwLock<DenyList>>,
+    gateways: R,
+    known_gateways: Arc<Cache<PublicKeyBinary, bool>>,
+    gateway_cache_ttl: Duration,
+}
+
+/// Keeps the denylist of a [`DirectDb`] up to date. Updates are fetched into
+/// a denylist of its own, so writes aren't held up while they download
+pub struct DenyListUpdater {
+    latest: DenyList,
+    deny_list: Arc<RwLock<DenyList>>,
+    url: String,
+    interval: Duration,
+}
+
+impl DirectDb {
+    pub fn from_settings(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+    ) -> Result<(Self, DenyListUpdater)> {
+        let gateways = IotConfigClient::from_settings(&settings.iot_config_client)?;
+        let deny_list = Arc::new(RwLock::new(DenyList::new()?));
+        let updater = DenyListUpdater {
+            latest: DenyList::new()?,
+            deny_list: deny_list.clone(),
+            url: settings.denylist.denylist_url.clone(),
+            interval: settings.denylist.trigger_interval(),
+        };
+        let direct_db = Self::new(pool, deny_list, gateways, settings.gateway_cache_ttl);
+        Ok((direct_db, updater))
+    }
+}
+
+impl<R> DirectDb<R>
+where
+    R: GatewayInfoResolver + Clone + Send + Sync,
+    R::Error: std::error::Error + Send + Sync + 'static,
+{
+    pub fn new(
+        pool: Pool<Postgres>,
+        deny_list: Arc<RwLock<DenyList>>,
+        gateways: R,
+        gateway_cache_ttl: Duration,
+    ) -> Self {
+        let known_gateways = Arc::new(Cache::new());
+        let cache_clone = known_gateways.clone();
+        tokio::spawn(async move { cache_clone.monitor(4, 0.25, CACHE_EVICTION_FREQUENCY).await });
+        Self {
+            pool,
+            deny_list,
+            gateways,
+            known_gateways,
+            gateway_cache_ttl,
+        }
+    }
+
+    pub async fn write_beacon(&self, report: &LoraBeaconIngestReportV1) -> Result<()> {
+        let beacon = IotBeaconIngestReport::try_from(report.clone())?;
+        if let Some(dropped) = self.check_gateway(&beacon.report.pub_key).await? {
+            record_write("beacon", dropped.as_str());
+            return Ok(());
+        }
+        self.insert(
+            beacon.ingest_id(),
+            &beacon.report.remote_entropy,
+            &beacon.report.data,
+            report.encode_to_vec(),
+            beacon.received_timestamp,
+            "beacon",
+            &beacon.report.pub_key,
+        )
+        .await
+    }
+
+    pub async fn write_witness(&self, report: &LoraWitnessIngestReportV1) -> Result<()> {
+        let witness = IotWitnessIngestReport::try_from(report.clone())?;
+        if let Some(dropped) = self.check_gateway(&witness.report.pub_key).await? {
+            record_write("witness", dropped.as_str());
+            return Ok(());
+        }
+        self.insert(
+            witness.ingest_id(),
+            &[],
+            &witness.report.data,
+            report.encode_to_vec(),
+            witness.received_timestamp,
+            "witness",
+            &witness.report.pub_key,
+        )
+        .await
+    }
+
+    /// Whether reports of `gateway` are dropped, as the verifier's loader
+    /// drops those of denied gateways and of gateways unknown to iot config.
+    /// A lookup which fails is an error rather than an unknown gateway, the
+    /// report is then left to the loader or refused
+    async fn check_gateway(&self, gateway: &PublicKeyBinary) -> Result<Option<Dropped>> {
+        if self.deny_list.read().await.check_key(gateway).await {
+            tracing::debug!(%gateway, "not writing report of denied gateway");
+            return Ok(Some(Dropped::Denied));
+        }
+        let known = match self.known_gateways.get(gateway).await {
+            Some(known) => *known.value(),
+            None => {
+                let known = self
+                    .gateways
+                    .clone()
+                    .resolve_gateway_info(gateway)
+                    .await?
+                    .is_some();
+                self.known_gateways
+                    .insert(gateway.clone(), known, self.gateway_cache_ttl)
+                    .await;
+                known
+            }
+        };
+        if !known {
+            tracing::debug!(%gateway, "not writing report of unknown gateway");
+            return Ok(Some(Dropped::Unknown));
+        }
+        Ok(None)
+    }
+
+    /// Insert a report as the verifier's loader does, except that beacons are
+    /// ready right away. The loader holds them back until the witnesses in
+    /// later files are loaded, the witnesses written here are in well within
+    /// the lifespan of the beacon's entropy the runner waits out anyway
+    #[allow(clippy::too_many_arguments)]
+    async fn insert(
+        &self,
+        id: Vec<u8>,
+        remote_entropy: &[u8],
+        packet_data: &[u8],
+        report_data: Vec<u8>,
+        received_timestamp: DateTime<Utc>,
+        report_type: &'static str,
+        gateway: &PublicKeyBinary,
+    ) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            insert into poc_report (
+                id,
+                remote_entropy,
+                packet_data,
+                report_data,
+                report_timestamp,
+                report_type,
+                status,
+                gateway
+            ) values ($1, $2, $3, $4, $5, $6::reporttype, 'ready', $7)
+            on conflict (id) do nothing
+            "#,
+        )
+        .bind(id)
+        .bind(remote_entropy)
+        .bind(packet_data)
+        .bind(report_data)
+        .bind(received_timestamp)
+        .bind(report_type)
+        .bind(gateway)
+        .execute(&self.pool)
+        .await;
+        let status = if result.is_ok() { "ok" } else { "error" };
+        record_write(report_type, status);
+        result?;
+        Ok(())
+    }
+}
+
+impl DenyListUpdater {
+    /// Update the denylist every interval, starting right away. A failed
+    /// update keeps the previous denylist, as the verifier's loader does
+    pub async fn run(mut self, shutdown: triggered::Listener) -> Result<()> {
+        let mut timer = time::interval(self.interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = timer.tick() => self.update().await,
+            }
+        }
+        tracing::info!("stopping denylist updater");
+        Ok(())
+    }
+
+    async fn update(&mut self) {
+        if let Err(err) = self.latest.update_to_latest(&self.url).await {
+            tracing::warn!(?err, "failed to update denylist");
+            return;
+        }
+        let mut deny_list = self.deny_list.write().await;
+        if self.latest.tag_name > deny_list.tag_name {
+            // the filter left in the latest denylist is only ever replaced
+            deny_list.tag_name = self.latest.tag_name;
+            std::mem::swap(&mut deny_list.filter, &mut self.latest.filter);
+        }
+    }
+}
+
+fn record_write(report_type: &'static str, status: &'static str) {
+    metrics::increment_counter!(WRITE_COUNTER, "type" => report_type, "status" => status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+    use helium_proto::services::poc_lora::{LoraBeaconReportReqV1, LoraWitnessReportReqV1};
+    use iot_config::gateway_info::{GatewayInfo, GatewayInfoStream};
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use xorf::Xor32;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("iot config unavailable")]
+    struct Unavailable;
+
+    /// Gateways known to iot config, counting the lookups made
+    #[derive(Clone, Default)]
+    struct MockGateways {
+        known: Arc<HashSet<PublicKeyBinary>>,
+        lookups: Arc<AtomicUsize>,
+        unavailable: bool,
+    }
+
+    #[tonic::async_trait]
+    impl GatewayInfoResolver for MockGateways {
+        type Error = Unavailable;
+
+        async fn resolve_gateway_info(
+            &mut self,
+            address: &PublicKeyBinary,
+        ) -> Result<Option<GatewayInfo>, Self::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            if self.unavailable {
+                return Err(Unavailable);
+            }
+            Ok(self.known.contains(address).then(|| GatewayInfo {
+                address: address.clone(),
+                metadata: None,
+                is_full_hotspot: true,
+            }))
+        }
+
+        async fn stream_gateways_info(&mut self) -> Result<GatewayInfoStream, Self::Error> {
+            Ok(futures::stream::empty().boxed())
+        }
+    }
+
+    fn gateway(byte: u8) -> PublicKeyBinary {
+        PublicKeyBinary::from(vec![byte; 33])
+    }
+
+    fn deny_list(denied: &PublicKeyBinary) -> Arc<RwLock<DenyList>> {
+        let mut deny_list = DenyList::new().expect("denylist");
+        deny_list.filter = Xor32::from(&[denylist::denylist::public_key_hash(denied)][..]);
+        Arc::new(RwLock::new(deny_list))
+    }
+
+    fn direct_db(pool: Pool<Postgres>, gateways: MockGateways) -> DirectDb<MockGateways> {
+        DirectDb::new(
+            pool,
+            deny_list(&gateway(9)),
+            gateways,
+            Duration::from_secs(60),
+        )
+    }
+
+    fn beacon(gateway: &PublicKeyBinary, data: u8) -> LoraBeaconIngestReportV1 {
+        LoraBeaconIngestReportV1 {
+            received_timestamp: Utc::now().timestamp_millis() as u64,
+            report: Some(LoraBeaconReportReqV1 {
+                pub_key: gateway.clone().into(),
+                remote_entropy: vec![1, 2, 3],
+                data: vec![data],
+                timestamp: Utc::now().timestamp_nanos() as u64,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn witness(gateway: &PublicKeyBinary, data: u8) -> LoraWitnessIngestReportV1 {
+        LoraWitnessIngestReportV1 {
+            received_timestamp: Utc::now().timestamp_millis() as u64,
+            report: Some(LoraWitnessReportReqV1 {
+                pub_key: gateway.clone().into(),
+                data: vec![data],
+                timestamp: Utc::now().timestamp_nanos() as u64,
+                ..Default::default()
+            }),
+        }
+    }
+
+    async fn reports(pool: &Pool<Postgres>) -> Vec<(String, String, String)> {
+        sqlx::query_as(
+            r#"
+            select report_type::text, status::text, gateway from poc_report
+            order by report_type::text, gateway
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .expect("reports")
+    }
+
+    #[sqlx::test(migrations = "../iot_verifier/migrations")]
+    async fn reports_of_known_gateways_are_written_ready(pool: Pool<Postgres>) -> Result<()> {
+        let (beaconer, witness_gw) = (gateway(1), gateway(2));
+        let gateways = MockGateways {
+            known: Arc::new(HashSet::from([beaconer.clone(), witness_gw.clone()])),
+            ..Default::default()
+        };
+        let db = direct_db(pool.clone(), gateways.clone());
+
+        db.write_beacon(&beacon(&beaconer, 1)).await?;
+        db.write_witness(&witness(&witness_gw, 1)).await?;
+        db.write_witness(&witness(&witness_gw, 2)).await?;
+
+        assert_eq!(
+            reports(&pool).await,
+            vec![
+                (
+                    "beacon".to_string(),
+                    "ready".to_string(),
+                    beaconer.to_string()
+                ),
+                (
+                    "witness".to_string(),
+                    "ready".to_string(),
+                    witness_gw.to_string()
+                ),
+                (
+                    "witness".to_string(),
+                    "ready".to_string(),
+                    witness_gw.to_string()
+                ),
+            ]
+        );
+        // the second witness' gateway is known from the cache
+        assert_eq!(gateways.lookups.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../iot_verifier/migrations")]
+    async fn reports_of_denied_and_unknown_gateways_are_not_written(
+        pool: Pool<Postgres>,
+    ) -> Result<()> {
+        let (denied, unknown) = (gateway(9), gateway(3));
+        let gateways = MockGateways {
+            known: Arc::new(HashSet::from([denied.clone()])),
+            ..Default::default()
+        };
+        let db = direct_db(pool.clone(), gateways.clone());
+
+        db.write_beacon(&beacon(&denied, 1)).await?;
+        db.write_witness(&witness(&denied, 1)).await?;
+        db.write_beacon(&beacon(&unknown, 2)).await?;
+        db.write_witness(&witness(&unknown, 2)).await?;
+
+        assert!(reports(&pool).await.is_empty());
+        // denied gateways aren't looked up, unknown ones are cached too
+        assert_eq!(gateways.lookups.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "../iot_verifier/migrations")]
+    async fn failed_gateway_lookups_are_errors(pool: Pool<Postgres>) -> Result<()> {
+        let gateways = MockGateways {
+            unavailable: true,
+            ..Default::default()
+        };
+        let db = direct_db(pool.clone(), gateways);
+
+        assert!(db.write_beacon(&beacon(&gateway(1), 1)).await.is_err());
+        assert!(db.write_witness(&witness(&gateway(2), 1)).await.is_err());
+        assert!(reports(&pool).await.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Graceful draining of the ingest before deploys.
+
+use anyhow::Result;
+use std::{path::Path, time::Duration};
+use tokio::{fs, signal};
+
+/// Listeners for the start of a drain, and for the hard shutdown ending it.
+/// The first SIGTERM or ctrl-c starts a drain: the grpc server finishes the
+/// requests in flight, then the file sinks commit their files and the
+/// uploader uploads every file handed to it. A second signal, or
+/// `drain_timeout` passing, stops everything right away, leaving the files not
+/// uploaded yet in the cache for the next start
+pub struct Drain {
+    pub drain: triggered::Listener,
+    pub shutdown: triggered::Listener,
+}
+
+impl Drain {
+    pub fn on_signals(drain_timeout: Duration) -> Result<Self> {
+        let (drain_trigger, drain) = triggered::trigger();
+        let (shutdown_trigger, shutdown) = triggered::trigger();
+        let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigterm.recv() => (),
+                _ = signal::ctrl_c() => (),
+            }
+            tracing::info!(?drain_timeout, "draining ingest");
+            drain_trigger.trigger();
+            tokio::select! {
+                _ = sigterm.recv() => tracing::warn!("drain interrupted"),
+                _ = signal::ctrl_c() => tracing::warn!("drain interrupted"),
+                _ = tokio::time::sleep(drain_timeout) => tracing::warn!("drain timed out"),
+            }
+            shutdown_trigger.trigger();
+        });
+        Ok(Self { drain, shutdown })
+    }
+}
+
+/// Log what the drain left behind, failing if any file of the cache at
+/// `cache` is still waiting to be uploaded
+pub async fn summarize(cache: &Path, interrupted: bool) -> Result<()> {
+    let pending = pending_files(cache).await? + pending_files(&cache.join("tmp")).await?;
+    if pending > 0 {
+        tracing::error!(
+            pending,
+            interrupted,
+            "ingest stopped with files pending upload"
+        );
+        anyhow::bail!("{pending} files pending upload in {}", cache.display());
+    }
+    tracing::info!(interrupted, "ingest drained, every file uploaded");
+    Ok(())
+}
+
+async fn pending_files(dir: &Path) -> Result<usize> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+    let mut pending = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            pending += 1;
+        }
+    }
+    Ok(pending)
+}
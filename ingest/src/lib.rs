@@ -1,5 +1,20 @@
+pub mod direct_db;
+pub mod drain;
 pub mod server_iot;
 pub mod server_mobile;
 pub mod settings;
+pub mod signature_verifier;
 
 pub use settings::{Mode, Settings};
+
+/// Refuse a report once its sink failed to store earlier ones, so the client
+/// resubmits it rather than it being lost while the ingest halts. Other
+/// write errors are logged by the sink.
+pub fn check_sink_write<T>(result: file_store::Result<T>) -> Result<(), tonic::Status> {
+    match result {
+        Err(file_store::Error::SinkFailed(reason)) => Err(tonic::Status::unavailable(format!(
+            "report storage failed: {reason}"
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -1,28 +1,41 @@
-use crate::Settings;
+use crate::{
+    check_sink_write,
+    direct_db::DirectDb,
+    drain::{self, Drain},
+    signature_verifier::SignatureVerifier,
+    Settings,
+};
 use anyhow::{Error, Result};
 use chrono::{Duration, Utc};
 use file_store::{
     file_sink::{self, FileSinkClient},
     file_upload,
+    grpc::GrpcResult,
     traits::MsgVerify,
     FileType,
 };
+use futures::future::{self, FutureExt};
 use futures_util::TryFutureExt;
 use helium_crypto::{Network, PublicKey};
-use helium_proto::services::poc_lora::{
-    self, LoraBeaconIngestReportV1, LoraBeaconReportReqV1, LoraBeaconReportRespV1,
-    LoraWitnessIngestReportV1, LoraWitnessReportReqV1, LoraWitnessReportRespV1,
+use helium_proto::{
+    services::poc_lora::{
+        self, LoraBeaconIngestReportV1, LoraBeaconReportReqV1, LoraBeaconReportRespV1,
+        LoraWitnessIngestReportV1, LoraWitnessReportReqV1, LoraWitnessReportRespV1,
+    },
+    Message,
 };
 use std::{convert::TryFrom, path::Path};
 use tonic::{transport, Request, Response, Status};
 
-pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
 pub type VerifyResult<T> = std::result::Result<T, Status>;
 
 pub struct GrpcServer {
     beacon_report_sink: FileSinkClient,
     witness_report_sink: FileSinkClient,
     required_network: Network,
+    signature_verifier: SignatureVerifier,
+    direct_db: Option<DirectDb>,
+    write_output: bool,
 }
 
 impl GrpcServer {
@@ -30,14 +43,39 @@ impl GrpcServer {
         beacon_report_sink: FileSinkClient,
         witness_report_sink: FileSinkClient,
         required_network: Network,
+        signature_verifier: SignatureVerifier,
+        direct_db: Option<DirectDb>,
+        write_output: bool,
     ) -> Result<Self> {
         Ok(Self {
             beacon_report_sink,
             witness_report_sink,
             required_network,
+            signature_verifier,
+            direct_db,
+            write_output,
         })
     }
 
+    /// A failed direct write of a beacon is left to the verifier's loader
+    /// when the beacon went to the output bucket too, and refused otherwise
+    /// so the client resubmits it. Failed witnesses are always refused, their
+    /// beacon may be written ready already and verified before the loader
+    /// gets to them
+    fn check_direct_write(&self, result: Result<()>, leave_to_loader: bool) -> VerifyResult<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) if leave_to_loader => {
+                tracing::warn!(?err, "direct db write failed, report left to the loader");
+                Ok(())
+            }
+            Err(err) => {
+                tracing::error!(?err, "direct db write failed");
+                Err(Status::unavailable("report storage failed"))
+            }
+        }
+    }
+
     fn verify_network(&self, public_key: PublicKey) -> VerifyResult<PublicKey> {
         if self.required_network == public_key.network {
             Ok(public_key)
@@ -50,14 +88,15 @@ impl GrpcServer {
         PublicKey::try_from(bytes).map_err(|_| Status::invalid_argument("invalid public key"))
     }
 
-    fn verify_signature<E>(&self, public_key: PublicKey, event: E) -> VerifyResult<(PublicKey, E)>
+    async fn verify_signature<E>(&self, public_key: &PublicKey, event: &E) -> VerifyResult<()>
     where
-        E: MsgVerify,
+        E: MsgVerify + Message + Clone + Send + 'static,
     {
-        event
-            .verify(&public_key)
-            .map_err(|_| Status::invalid_argument("invalid signature"))?;
-        Ok((public_key, event))
+        match self.signature_verifier.verify(public_key, event).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Status::invalid_argument("invalid signature")),
+            Err(_) => Err(Status::unavailable("signature verification unavailable")),
+        }
     }
 }
 
@@ -70,16 +109,21 @@ impl poc_lora::PocLora for GrpcServer {
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
 
-        let report = self
+        let public_key = self
             .verify_public_key(event.pub_key.as_ref())
-            .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| LoraBeaconIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
-            })?;
+            .and_then(|public_key| self.verify_network(public_key))?;
+        self.verify_signature(&public_key, &event).await?;
+        let report = LoraBeaconIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
 
-        _ = self.beacon_report_sink.write(report, []).await;
+        if let Some(direct_db) = &self.direct_db {
+            self.check_direct_write(direct_db.write_beacon(&report).await, self.write_output)?;
+        }
+        if self.write_output {
+            check_sink_write(self.beacon_report_sink.write(report, []).await)?;
+        }
 
         let id = timestamp.to_string();
         Ok(Response::new(LoraBeaconReportRespV1 { id }))
@@ -92,23 +136,29 @@ impl poc_lora::PocLora for GrpcServer {
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
 
-        let report = self
+        let public_key = self
             .verify_public_key(event.pub_key.as_ref())
-            .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| LoraWitnessIngestReportV1 {
-                received_timestamp: timestamp,
-                report: Some(event),
-            })?;
+            .and_then(|public_key| self.verify_network(public_key))?;
+        self.verify_signature(&public_key, &event).await?;
+        let report = LoraWitnessIngestReportV1 {
+            received_timestamp: timestamp,
+            report: Some(event),
+        };
 
-        _ = self.witness_report_sink.write(report, []).await;
+        if let Some(direct_db) = &self.direct_db {
+            self.check_direct_write(direct_db.write_witness(&report).await, false)?;
+        }
+        if self.write_output {
+            check_sink_write(self.witness_report_sink.write(report, []).await)?;
+        }
 
         let id = timestamp.to_string();
         Ok(Response::new(LoraWitnessReportRespV1 { id }))
     }
 }
 
-pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) -> Result<()> {
+pub async fn grpc_server(drain: Drain, settings: &Settings) -> Result<()> {
+    let Drain { drain, shutdown } = drain;
     let grpc_addr = settings.listen_addr()?;
 
     // Initialize uploader
@@ -127,6 +177,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     )
     .deposits(Some(file_upload_tx.clone()))
     .roll_time(Duration::minutes(5))
+    .commit_on_close(true)
     .create()
     .await?;
 
@@ -137,12 +188,48 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         concat!(env!("CARGO_PKG_NAME"), "_witness_report"),
         shutdown.clone(),
     )
-    .deposits(Some(file_upload_tx.clone()))
+    .deposits(Some(file_upload_tx))
     .roll_time(Duration::minutes(5))
+    .commit_on_close(true)
     .create()
     .await?;
 
-    let grpc_server = GrpcServer::new(beacon_report_sink, witness_report_sink, settings.network)?;
+    let (signature_verifier, verifier_pool) = SignatureVerifier::from_settings(settings);
+
+    // reports are written to the db until the last request in flight is
+    // served, after which it is let go rather than held until the hard
+    // shutdown
+    let (served_trigger, served) = triggered::trigger();
+
+    let (direct_db, direct_db_handle, deny_list_updater, write_output) = match &settings.direct_db {
+        Some(direct_db) => {
+            let (pool, db_handle) = direct_db
+                .database
+                .connect(env!("CARGO_PKG_NAME"), served)
+                .await?;
+            let (db, deny_list_updater) = DirectDb::from_settings(direct_db, pool)?;
+            tracing::info!(
+                write_output = direct_db.write_output,
+                "writing reports directly to the verifier db"
+            );
+            (
+                Some(db),
+                db_handle,
+                deny_list_updater.run(shutdown.clone()).boxed(),
+                direct_db.write_output,
+            )
+        }
+        None => (None, future::ok(()).boxed(), future::ok(()).boxed(), true),
+    };
+
+    let grpc_server = GrpcServer::new(
+        beacon_report_sink,
+        witness_report_sink,
+        settings.network,
+        signature_verifier,
+        direct_db,
+        write_output,
+    )?;
 
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
@@ -152,14 +239,19 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     let server = transport::Server::builder()
         .layer(poc_metrics::request_layer!("ingest_server_iot_connection"))
         .add_service(poc_lora::Server::new(grpc_server))
-        .serve_with_shutdown(grpc_addr, shutdown.clone())
-        .map_err(Error::from);
+        .serve_with_shutdown(grpc_addr, drain)
+        .map_err(Error::from)
+        .inspect(move |_| served_trigger.trigger());
 
     tokio::try_join!(
         server,
         beacon_report_sink_server.run().map_err(Error::from),
         witness_report_sink_server.run().map_err(Error::from),
         file_upload.run(&shutdown).map_err(Error::from),
-    )
-    .map(|_| ())
+        verifier_pool.run(shutdown.clone()),
+        direct_db_handle.map_err(Error::from),
+        deny_list_updater,
+    )?;
+
+    drain::summarize(store_base_path, shutdown.is_triggered()).await
 }
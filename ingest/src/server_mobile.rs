@@ -1,9 +1,14 @@
-use crate::Settings;
+use crate::{
+    check_sink_write,
+    drain::{self, Drain},
+    Settings,
+};
 use anyhow::{bail, Error, Result};
 use chrono::{Duration, Utc};
 use file_store::{
     file_sink::{self, FileSinkClient},
     file_upload,
+    grpc::GrpcResult,
     traits::MsgVerify,
     FileType,
 };
@@ -21,7 +26,6 @@ use tonic::{metadata::MetadataValue, transport, Request, Response, Status};
 
 const INGEST_WAIT_DURATION_MINUTES: i64 = 15;
 
-pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
 pub type VerifyResult<T> = std::result::Result<T, Status>;
 
 pub struct GrpcServer {
@@ -93,7 +97,7 @@ impl poc_mobile::PocMobile for GrpcServer {
                 report: Some(event),
             })?;
 
-        _ = self.speedtest_report_sink.write(report, []).await;
+        check_sink_write(self.speedtest_report_sink.write(report, []).await)?;
 
         let id = timestamp.to_string();
         Ok(Response::new(SpeedtestRespV1 { id }))
@@ -115,7 +119,7 @@ impl poc_mobile::PocMobile for GrpcServer {
                 report: Some(event),
             })?;
 
-        _ = self.heartbeat_report_sink.write(report, []).await;
+        check_sink_write(self.heartbeat_report_sink.write(report, []).await)?;
 
         let id = timestamp.to_string();
         Ok(Response::new(CellHeartbeatRespV1 { id }))
@@ -137,7 +141,7 @@ impl poc_mobile::PocMobile for GrpcServer {
                 report: Some(event),
             })?;
 
-        _ = self.data_transfer_session_sink.write(report, []).await;
+        check_sink_write(self.data_transfer_session_sink.write(report, []).await)?;
 
         Ok(Response::new(DataTransferSessionRespV1 {
             id: timestamp.to_string(),
@@ -170,7 +174,7 @@ impl poc_mobile::PocMobile for GrpcServer {
                 status
             })?;
 
-        _ = self.subscriber_location_report_sink.write(report, []).await;
+        check_sink_write(self.subscriber_location_report_sink.write(report, []).await)?;
 
         Ok(Response::new(SubscriberLocationRespV1 {
             id: timestamp.to_string(),
@@ -193,14 +197,15 @@ impl poc_mobile::PocMobile for GrpcServer {
                 report: Some(event),
             })?;
 
-        _ = self.coverage_object_report_sink.write(report, []).await;
+        check_sink_write(self.coverage_object_report_sink.write(report, []).await)?;
 
         let id = timestamp.to_string();
         Ok(Response::new(CoverageObjectRespV1 { id }))
     }
 }
 
-pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) -> Result<()> {
+pub async fn grpc_server(drain: Drain, settings: &Settings) -> Result<()> {
+    let Drain { drain, shutdown } = drain;
     let grpc_addr = settings.listen_addr()?;
 
     // Initialize uploader
@@ -219,6 +224,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         )
         .deposits(Some(file_upload_tx.clone()))
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .commit_on_close(true)
         .create()
         .await?;
 
@@ -232,6 +238,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         )
         .deposits(Some(file_upload_tx.clone()))
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .commit_on_close(true)
         .create()
         .await?;
 
@@ -247,6 +254,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         )
         .deposits(Some(file_upload_tx.clone()))
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .commit_on_close(true)
         .create()
         .await?;
 
@@ -259,6 +267,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
         )
         .deposits(Some(file_upload_tx.clone()))
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .commit_on_close(true)
         .create()
         .await?;
 
@@ -269,8 +278,9 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
             concat!(env!("CARGO_PKG_NAME"), "_coverage_object_report"),
             shutdown.clone(),
         )
-        .deposits(Some(file_upload_tx.clone()))
+        .deposits(Some(file_upload_tx))
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .commit_on_close(true)
         .create()
         .await?;
 
@@ -286,13 +296,10 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
     let Some(api_token) = settings
         .token
         .as_ref()
-        .and_then(|token| {
-            format!("Bearer {token}")
-                .parse::<MetadataValue<_>>()
-                .ok()
-        }) else {
-            bail!("expected valid api token in settings");
-        };
+        .and_then(|token| format!("Bearer {token}").parse::<MetadataValue<_>>().ok())
+    else {
+        bail!("expected valid api token in settings");
+    };
 
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
@@ -310,7 +317,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
                 _ => Err(Status::unauthenticated("No valid auth token")),
             },
         ))
-        .serve_with_shutdown(grpc_addr, shutdown.clone())
+        .serve_with_shutdown(grpc_addr, drain)
         .map_err(Error::from);
 
     tokio::try_join!(
@@ -325,6 +332,7 @@ pub async fn grpc_server(shutdown: triggered::Listener, settings: &Settings) ->
             .run()
             .map_err(Error::from),
         file_upload.run(&shutdown).map_err(Error::from),
-    )
-    .map(|_| ())
+    )?;
+
+    drain::summarize(store_base_path, shutdown.is_triggered()).await
 }
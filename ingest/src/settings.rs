@@ -1,10 +1,10 @@
-use config::{Config, Environment, File};
 use helium_crypto::Network;
 use serde::Deserialize;
 use std::{
     net::{AddrParseError, SocketAddr},
     path::Path,
     str::FromStr,
+    time::Duration,
 };
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +30,27 @@ pub struct Settings {
     pub token: Option<String>,
     /// Target output bucket details Metrics settings
     pub metrics: poc_metrics::Settings,
+    /// Number of blocking workers verifying report signatures. Default is
+    /// the available parallelism of the host
+    pub verify_workers: Option<usize>,
+    /// Max number of queued signatures a worker verifies in one go. Default
+    /// 64
+    #[serde(default = "default_verify_batch_size")]
+    pub verify_batch_size: usize,
+    /// How long a signature verification result is cached for. Default 1m
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_verify_cache_ttl"
+    )]
+    pub verify_cache_ttl: Duration,
+    /// How long a drain started by SIGTERM is given to finish in flight
+    /// requests and upload every file before the ingest stops regardless.
+    /// Default 1m
+    #[serde(with = "settings_loader::duration", default = "default_drain_timeout")]
+    pub drain_timeout: Duration,
+    /// Write iot reports directly into the report table of an iot verifier
+    /// sharing its database. Disabled by default
+    pub direct_db: Option<crate::direct_db::Settings>,
 }
 
 pub fn default_listen_addr() -> String {
@@ -40,6 +61,18 @@ pub fn default_log() -> String {
     "ingest=debug,poc_store=info".to_string()
 }
 
+pub fn default_verify_batch_size() -> usize {
+    64
+}
+
+pub fn default_verify_cache_ttl() -> Duration {
+    Duration::from_secs(60)
+}
+
+pub fn default_drain_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
 pub fn default_sink() -> String {
     "/var/data/ingest".to_string()
 }
@@ -60,23 +93,18 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "ENTROPY_". For example
     /// "ENTROPY_LOG" will override the log setting.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `MI_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("INGEST").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "INGEST")
     }
 
     pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
         SocketAddr::from_str(&self.listen)
     }
+
+    pub fn verify_workers(&self) -> usize {
+        self.verify_workers
+            .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1)
+            .max(1)
+    }
 }
@@ -0,0 +1,135 @@
+//! Report signature verification off the grpc tasks.
+
+use crate::Settings;
+use file_store::traits::MsgVerify;
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::Message;
+use retainer::Cache;
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60);
+const VERIFY_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_signature_verify");
+const BATCH_SIZE_HISTOGRAM: &str = concat!(env!("CARGO_PKG_NAME"), "_signature_verify_batch");
+
+type VerifyJob = (Box<dyn FnOnce() -> bool + Send>, oneshot::Sender<bool>);
+
+#[derive(thiserror::Error, Debug)]
+#[error("signature verifier pool stopped")]
+pub struct PoolStopped;
+
+/// Verifies signatures on the [`VerifierPool`], caching the results by signer
+/// and hash of the signed report for a short time so resubmitted reports skip
+/// the ed25519 check
+#[derive(Clone)]
+pub struct SignatureVerifier {
+    cache: Arc<Cache<(PublicKeyBinary, [u8; 32]), bool>>,
+    cache_ttl: Duration,
+    jobs: mpsc::Sender<VerifyJob>,
+}
+
+/// Blocking workers each taking whatever has queued up to the batch size, so a
+/// burst of submissions is verified in a few thread hand offs rather than one
+/// per report
+pub struct VerifierPool {
+    jobs: mpsc::Receiver<VerifyJob>,
+    workers: Arc<Semaphore>,
+    batch_size: usize,
+}
+
+impl SignatureVerifier {
+    pub fn from_settings(settings: &Settings) -> (Self, VerifierPool) {
+        let cache = Arc::new(Cache::new());
+        let cache_clone = cache.clone();
+        tokio::spawn(async move { cache_clone.monitor(4, 0.25, CACHE_EVICTION_FREQUENCY).await });
+
+        let workers = settings.verify_workers();
+        let batch_size = settings.verify_batch_size.max(1);
+        let (tx, rx) = mpsc::channel(workers * batch_size);
+        let verifier = Self {
+            cache,
+            cache_ttl: settings.verify_cache_ttl,
+            jobs: tx,
+        };
+        let pool = VerifierPool {
+            jobs: rx,
+            workers: Arc::new(Semaphore::new(workers)),
+            batch_size,
+        };
+        (verifier, pool)
+    }
+
+    /// Whether `event` carries a valid signature by `public_key`
+    pub async fn verify<E>(&self, public_key: &PublicKey, event: &E) -> Result<bool, PoolStopped>
+    where
+        E: MsgVerify + Message + Clone + Send + 'static,
+    {
+        let key = (
+            PublicKeyBinary::from(public_key.clone()),
+            Sha256::digest(event.encode_to_vec()).into(),
+        );
+        if let Some(valid) = self.cache.get(&key).await {
+            let valid = *valid.value();
+            record_verify(valid, "hit");
+            return Ok(valid);
+        }
+
+        let (public_key, event) = (public_key.clone(), event.clone());
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.jobs
+            .send((
+                Box::new(move || event.verify(&public_key).is_ok()),
+                reply_tx,
+            ))
+            .await
+            .map_err(|_| PoolStopped)?;
+        let valid = reply_rx.await.map_err(|_| PoolStopped)?;
+        record_verify(valid, "miss");
+
+        self.cache.insert(key, valid, self.cache_ttl).await;
+        Ok(valid)
+    }
+}
+
+impl VerifierPool {
+    pub async fn run(mut self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!(
+            workers = self.workers.available_permits(),
+            batch_size = self.batch_size,
+            "starting signature verifier pool"
+        );
+        loop {
+            let job = tokio::select! {
+                _ = shutdown.clone() => break,
+                job = self.jobs.recv() => match job {
+                    Some(job) => job,
+                    None => break,
+                },
+            };
+            let mut batch = vec![job];
+            while batch.len() < self.batch_size {
+                match self.jobs.try_recv() {
+                    Ok(job) => batch.push(job),
+                    Err(_) => break,
+                }
+            }
+
+            let permit = self.workers.clone().acquire_owned().await?;
+            metrics::histogram!(BATCH_SIZE_HISTOGRAM, batch.len() as f64);
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                for (job, reply) in batch {
+                    _ = reply.send(job());
+                }
+            });
+        }
+        tracing::info!("stopping signature verifier pool");
+        Ok(())
+    }
+}
+
+fn record_verify(valid: bool, cache: &'static str) {
+    let result = if valid { "valid" } else { "invalid" };
+    metrics::increment_counter!(VERIFY_COUNTER, "result" => result, "cache" => cache);
+}
@@ -0,0 +1,272 @@
+// Services in `src/ext.rs` are not part of helium-proto. Their server and
+// client stubs are generated here from the prost messages defined there.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::ext::{input}"))
+        .output_type(format!("crate::ext::{output}"))
+        .codec_path("tonic::codec::ProstCodec")
+        .build()
+}
+
+fn server_streaming_method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::ext::{input}"))
+        .output_type(format!("crate::ext::{output}"))
+        .codec_path("tonic::codec::ProstCodec")
+        .server_streaming()
+        .build()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let devaddr = Service::builder()
+        .name("Devaddr")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "org_for_devaddr",
+            "OrgForDevaddr",
+            "OrgForDevaddrReqV1",
+            "OrgForDevaddrResV1",
+        ))
+        .method(method(
+            "orgs_for_devaddrs",
+            "OrgsForDevaddrs",
+            "OrgsForDevaddrsReqV1",
+            "OrgsForDevaddrsResV1",
+        ))
+        .build();
+
+    let org_payer = Service::builder()
+        .name("OrgPayer")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "update_payer",
+            "UpdatePayer",
+            "OrgUpdatePayerReqV1",
+            "OrgUpdatePayerResV1",
+        ))
+        .method(server_streaming_method(
+            "stream",
+            "Stream",
+            "OrgPayerStreamReqV1",
+            "OrgPayerStreamResV1",
+        ))
+        .build();
+
+    let org_owner = Service::builder()
+        .name("OrgOwner")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "update_owner",
+            "UpdateOwner",
+            "OrgUpdateOwnerReqV1",
+            "OrgUpdateOwnerResV1",
+        ))
+        .build();
+
+    let region_limits = Service::builder()
+        .name("RegionLimits")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "limits",
+            "Limits",
+            "RegionLimitsReqV1",
+            "RegionLimitsResV1",
+        ))
+        .method(method(
+            "update_limits",
+            "UpdateLimits",
+            "RegionUpdateLimitsReqV1",
+            "RegionLimitsResV1",
+        ))
+        .build();
+
+    let org_lock = Service::builder()
+        .name("OrgLock")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "unlock",
+            "Unlock",
+            "OrgUnlockReqV1",
+            "OrgUnlockResV1",
+        ))
+        .build();
+
+    let gateway_onboarding = Service::builder()
+        .name("GatewayOnboarding")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "onboarding",
+            "Onboarding",
+            "GatewayOnboardingReqV1",
+            "GatewayOnboardingResV1",
+        ))
+        .method(server_streaming_method(
+            "onboarding_stream",
+            "OnboardingStream",
+            "GatewayOnboardingStreamReqV1",
+            "GatewayOnboardingStreamResV1",
+        ))
+        .build();
+
+    let gateway_region_override = Service::builder()
+        .name("GatewayRegionOverride")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "set_override",
+            "SetOverride",
+            "GatewaySetRegionOverrideReqV1",
+            "GatewayRegionOverrideResV1",
+        ))
+        .method(method(
+            "clear_override",
+            "ClearOverride",
+            "GatewayClearRegionOverrideReqV1",
+            "GatewayRegionOverrideResV1",
+        ))
+        .build();
+
+    let gateway_changes = Service::builder()
+        .name("GatewayChanges")
+        .package("helium.iot_config.ext")
+        .method(server_streaming_method(
+            "changes",
+            "Changes",
+            "GatewayChangesReqV1",
+            "GatewayChangesResV1",
+        ))
+        .build();
+
+    let webhooks = Service::builder()
+        .name("Webhooks")
+        .package("helium.iot_config.ext")
+        .method(method("add", "Add", "WebhookAddReqV1", "WebhookResV1"))
+        .method(method(
+            "remove",
+            "Remove",
+            "WebhookRemoveReqV1",
+            "WebhookResV1",
+        ))
+        .method(method(
+            "list",
+            "List",
+            "WebhookListReqV1",
+            "WebhookListResV1",
+        ))
+        .build();
+
+    let org_devaddrs = Service::builder()
+        .name("OrgDevaddrs")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "allocate",
+            "Allocate",
+            "OrgAllocateDevaddrsReqV1",
+            "OrgDevaddrsResV1",
+        ))
+        .method(method(
+            "release",
+            "Release",
+            "OrgReleaseDevaddrsReqV1",
+            "OrgDevaddrsResV1",
+        ))
+        .build();
+
+    let audit_log = Service::builder()
+        .name("AuditLog")
+        .package("helium.iot_config.ext")
+        .method(server_streaming_method(
+            "stream",
+            "Stream",
+            "AuditLogStreamReqV1",
+            "AuditLogStreamResV1",
+        ))
+        .build();
+
+    let org_snapshot = Service::builder()
+        .name("OrgSnapshot")
+        .package("helium.iot_config.ext")
+        .method(server_streaming_method(
+            "stream",
+            "Stream",
+            "OrgSnapshotStreamReqV1",
+            "OrgSnapshotStreamResV1",
+        ))
+        .build();
+
+    let route_bulk = Service::builder()
+        .name("RouteBulk")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "export",
+            "Export",
+            "RouteExportReqV1",
+            "RouteExportResV1",
+        ))
+        .method(method(
+            "import",
+            "Import",
+            "RouteImportReqV1",
+            "RouteImportResV1",
+        ))
+        .build();
+
+    let skf_stream = Service::builder()
+        .name("SkfStream")
+        .package("helium.iot_config.ext")
+        .method(server_streaming_method(
+            "stream",
+            "Stream",
+            "SkfStreamReqV1",
+            "SkfStreamResV1",
+        ))
+        .build();
+
+    let route_euis = Service::builder()
+        .name("RouteEuis")
+        .package("helium.iot_config.ext")
+        .method(method(
+            "add",
+            "Add",
+            "RouteEuisBatchReqV1",
+            "RouteEuisBatchResV1",
+        ))
+        .method(method(
+            "remove",
+            "Remove",
+            "RouteEuisBatchReqV1",
+            "RouteEuisBatchResV1",
+        ))
+        .method(server_streaming_method(
+            "list",
+            "List",
+            "RouteEuisListReqV1",
+            "RouteEuisListResV1",
+        ))
+        .build();
+
+    Builder::new().compile(&[
+        devaddr,
+        org_payer,
+        org_owner,
+        region_limits,
+        org_lock,
+        gateway_onboarding,
+        gateway_region_override,
+        gateway_changes,
+        webhooks,
+        org_devaddrs,
+        audit_log,
+        org_snapshot,
+        route_bulk,
+        skf_stream,
+        route_euis,
+    ]);
+}
@@ -0,0 +1 @@
+create index devaddr_constraint_range_idx on organization_devaddr_constraints (start_addr, end_addr);
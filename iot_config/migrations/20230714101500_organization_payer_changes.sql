@@ -0,0 +1,17 @@
+create table organization_payer_changes (
+    id bigserial primary key not null,
+    oui bigint not null references organizations(oui) on delete cascade,
+    old_payer_pubkey text not null,
+    new_payer_pubkey text not null,
+    -- owner key that requested the change, co-signed by the new payer
+    owner_pubkey text not null,
+    effective_at timestamptz not null,
+    applied bool not null default false,
+
+    inserted_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+
+select trigger_updated_at('organization_payer_changes');
+
+create index payer_changes_pending_idx on organization_payer_changes (effective_at) where not applied;
@@ -0,0 +1,12 @@
+create table organization_owner_history (
+    id bigserial primary key not null,
+    oui bigint not null references organizations(oui) on delete cascade,
+    old_owner_pubkey text not null,
+    new_owner_pubkey text not null,
+    -- key that authorized the rotation; the old owner or an admin key
+    authorizer_pubkey text not null,
+
+    inserted_at timestamptz not null default now()
+);
+
+create index owner_history_oui_idx on organization_owner_history (oui);
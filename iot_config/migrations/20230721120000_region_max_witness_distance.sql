@@ -0,0 +1 @@
+alter table regions add column max_witness_distance integer;
@@ -0,0 +1,19 @@
+create table org_signature_failures (
+    id bigserial primary key not null,
+    -- not a foreign key, requests may name orgs that don't exist
+    oui bigint not null,
+    signer_pubkey text not null,
+    rpc text not null,
+
+    inserted_at timestamptz not null default now()
+);
+
+create index signature_failures_oui_inserted_at_idx on org_signature_failures (oui, inserted_at);
+
+create table org_mutation_locks (
+    oui bigint primary key not null references organizations(oui) on delete cascade,
+    -- signature failures within the window when the lock was taken
+    failures bigint not null,
+
+    inserted_at timestamptz not null default now()
+);
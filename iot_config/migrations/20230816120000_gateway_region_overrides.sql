@@ -0,0 +1,24 @@
+create table gateway_region_overrides (
+    address text primary key not null,
+    region text not null,
+    expires_at timestamptz not null,
+    reason text not null,
+    signer_pubkey text not null,
+
+    inserted_at timestamptz not null default now()
+);
+
+-- every override set or cleared, kept after the override itself is gone
+create table gateway_region_override_log (
+    id bigserial primary key not null,
+    address text not null,
+    -- null when the override was cleared
+    region text,
+    expires_at timestamptz,
+    reason text not null,
+    signer_pubkey text not null,
+
+    inserted_at timestamptz not null default now()
+);
+
+create index region_override_log_address_inserted_at_idx on gateway_region_override_log (address, inserted_at);
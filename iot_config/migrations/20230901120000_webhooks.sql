@@ -0,0 +1,20 @@
+create table webhooks (
+    id uuid primary key not null default uuid_generate_v1mc(),
+    oui bigint not null references organizations(oui) on delete cascade,
+    url text not null,
+    -- key of the hmac signing the payloads delivered to the url
+    secret text not null,
+    -- the change events delivered, see `webhooks::EVENTS`
+    events text[] not null,
+    -- consecutive failed deliveries, reset on success and when the circuit breaks
+    failures integer not null default 0,
+    -- deliveries are skipped until then after `failure_threshold` failures
+    disabled_until timestamptz,
+
+    inserted_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+
+select trigger_updated_at('webhooks');
+
+create index webhooks_oui_idx on webhooks (oui);
@@ -0,0 +1,21 @@
+-- every mutating rpc, chained by hash per org so any edit or deletion of an
+-- entry breaks the chain of its org from it on. see `audit::entry_hash`
+create table config_audit_log (
+    id bigserial primary key not null,
+    rpc text not null,
+    -- the org mutated, null for mutations not scoped to an org
+    oui bigint,
+    -- b58 key that signed the request
+    signer text not null,
+    -- sha256 of the request as received, signature included
+    request_hash bytea not null,
+    -- json of the state mutated, before and after the rpc
+    before_state text,
+    after_state text,
+    -- hash of the previous entry of the same org, all zeros for the first
+    prev_hash bytea not null,
+    hash bytea not null,
+    inserted_at timestamptz not null
+);
+
+create index config_audit_log_oui_idx on config_audit_log (oui, id);
@@ -0,0 +1 @@
+alter table regions add column witness_window integer;
@@ -0,0 +1,61 @@
+-- session key filters keyed by the org of their route, for routers to keep
+-- the filters of every org without joining the routes
+alter table route_session_key_filters add column oui bigint;
+
+update route_session_key_filters skf set oui = r.oui from routes r where r.id = skf.route_id;
+
+alter table route_session_key_filters alter column oui set not null;
+
+create index skf_oui_devaddr_session_key_idx on route_session_key_filters (oui, devaddr, session_key);
+
+create or replace function skf_set_oui() returns trigger as $$
+begin
+    select oui into new.oui from routes where id = new.route_id;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger skf_set_oui before insert on route_session_key_filters
+    for each row execute function skf_set_oui();
+
+-- every change of a session key filter, versioned in commit order. the
+-- advisory lock serializes the writing transactions from their first change
+-- on, so a version is never committed after a greater one. see `skf_versions`
+create table skf_changes (
+    version bigserial primary key not null,
+    action text not null check (action in ('add', 'remove')),
+    oui bigint not null,
+    route_id uuid not null,
+    devaddr int not null,
+    session_key text not null,
+    max_copies int not null,
+    inserted_at timestamptz not null default now()
+);
+
+create index skf_changes_inserted_at_idx on skf_changes (inserted_at);
+
+-- the greatest version pruned from skf_changes, streams resuming from
+-- before it get a snapshot instead
+create table skf_change_horizon (
+    pruned_through bigint not null
+);
+
+insert into skf_change_horizon (pruned_through) values (0);
+
+create or replace function skf_record_change() returns trigger as $$
+begin
+    perform pg_advisory_xact_lock(7564134 /* 'skf' */);
+    if tg_op = 'DELETE' then
+        insert into skf_changes (action, oui, route_id, devaddr, session_key, max_copies)
+            values ('remove', old.oui, old.route_id, old.devaddr, old.session_key, old.max_copies);
+        return old;
+    end if;
+    insert into skf_changes (action, oui, route_id, devaddr, session_key, max_copies)
+        values ('add', new.oui, new.route_id, new.devaddr, new.session_key, new.max_copies);
+    return new;
+end;
+$$ language plpgsql;
+
+-- deletes of a route cascade to its filters, and are recorded as removes
+create trigger skf_record_change after insert or update or delete on route_session_key_filters
+    for each row execute function skf_record_change();
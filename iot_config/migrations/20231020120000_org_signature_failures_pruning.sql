@@ -0,0 +1 @@
+create index signature_failures_inserted_at_idx on org_signature_failures (inserted_at);
@@ -1,12 +1,11 @@
-use crate::settings::Settings;
-use anyhow::anyhow;
+use crate::{settings::Settings, Error, Result};
 use file_store::traits::MsgVerify;
 use helium_crypto::{PublicKey, PublicKeyBinary};
 use helium_proto::services::iot_config::admin_add_key_req_v1::KeyTypeV1 as ProtoKeyType;
 use serde::Serialize;
-use sqlx::Row;
-use std::collections::HashMap;
-use tokio::sync::watch;
+use sqlx::{Pool, Postgres, Row};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::{watch, Mutex, MutexGuard};
 
 pub type CacheKeys = HashMap<PublicKey, KeyType>;
 
@@ -19,7 +18,7 @@ impl AuthCache {
     pub async fn new(
         settings: &Settings,
         db: impl sqlx::PgExecutor<'_> + Copy,
-    ) -> anyhow::Result<(watch::Sender<CacheKeys>, Self)> {
+    ) -> Result<(watch::Sender<CacheKeys>, Self)> {
         let config_admin = settings.admin_pubkey()?;
 
         let mut stored_keys = fetch_stored_keys(db)
@@ -33,7 +32,14 @@ impl AuthCache {
         Ok((cache_sender, Self { cache_receiver }))
     }
 
-    pub fn verify_signature<R>(&self, signer: &PublicKey, request: &R) -> anyhow::Result<()>
+    /// A cache of the given keys only, for callers managing the keys
+    /// themselves
+    pub fn from_keys(keys: CacheKeys) -> (watch::Sender<CacheKeys>, Self) {
+        let (cache_sender, cache_receiver) = watch::channel(keys);
+        (cache_sender, Self { cache_receiver })
+    }
+
+    pub fn verify_signature<R>(&self, signer: &PublicKey, request: &R) -> Result<()>
     where
         R: MsgVerify,
     {
@@ -41,7 +47,7 @@ impl AuthCache {
             tracing::debug!(pubkey = signer.to_string(), "request authorized");
             Ok(())
         } else {
-            Err(anyhow!("unauthorized request"))
+            Err(Error::unauthorized(signer))
         }
     }
 
@@ -50,7 +56,7 @@ impl AuthCache {
         key_type: KeyType,
         signer: &PublicKey,
         request: &R,
-    ) -> anyhow::Result<()>
+    ) -> Result<()>
     where
         R: MsgVerify,
     {
@@ -60,7 +66,7 @@ impl AuthCache {
             tracing::debug!(pubkey = signer.to_string(), "request authorized");
             Ok(())
         } else {
-            Err(anyhow!("unauthorized request"))
+            Err(Error::unauthorized(signer))
         }
     }
 
@@ -87,6 +93,97 @@ impl AuthCache {
     }
 }
 
+/// Updates the auth cache one update at a time, a key being written to the
+/// `admin_keys` table and the cache under the same lock as a reload of the
+/// table, so a reload reading the table before a key was added or removed
+/// can't undo it in the cache
+pub struct AuthCacheUpdater {
+    cache_sender: watch::Sender<CacheKeys>,
+    updates: Mutex<()>,
+}
+
+impl AuthCacheUpdater {
+    pub fn new(cache_sender: watch::Sender<CacheKeys>) -> Self {
+        Self {
+            cache_sender,
+            updates: Mutex::new(()),
+        }
+    }
+
+    /// Hold off other updates until the returned guard is dropped
+    pub async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.updates.lock().await
+    }
+
+    pub fn send_modify(&self, modify: impl FnOnce(&mut CacheKeys)) {
+        self.cache_sender.send_modify(modify)
+    }
+
+    pub fn send_if_modified(&self, modify: impl FnOnce(&mut CacheKeys) -> bool) -> bool {
+        self.cache_sender.send_if_modified(modify)
+    }
+}
+
+/// Reloads the auth cache from the `admin_keys` table, so keys added or removed
+/// through the admin apis of another instance take effect on this one
+pub struct AuthCacheRefresher {
+    pool: Pool<Postgres>,
+    config_admin: PublicKey,
+    cache_updater: Arc<AuthCacheUpdater>,
+    refresh_interval: Duration,
+}
+
+impl AuthCacheRefresher {
+    pub fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        cache_updater: Arc<AuthCacheUpdater>,
+    ) -> Result<Self> {
+        Ok(Self {
+            pool,
+            config_admin: settings.admin_pubkey()?,
+            cache_updater,
+            refresh_interval: settings.auth_cache_refresh_interval,
+        })
+    }
+
+    pub async fn run(self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting auth cache refresher");
+        let mut trigger = tokio::time::interval(self.refresh_interval);
+        // The cache is loaded at startup
+        trigger.tick().await;
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = trigger.tick() => match self.refresh().await {
+                    Ok(()) => (),
+                    Err(err) => tracing::warn!(?err, "failed to refresh auth cache"),
+                }
+            }
+        }
+        tracing::info!("stopping auth cache refresher");
+        Ok(())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let _updating = self.cache_updater.lock().await;
+        let mut stored_keys = fetch_stored_keys(&self.pool)
+            .await?
+            .into_iter()
+            .collect::<CacheKeys>();
+        stored_keys.insert(self.config_admin.clone(), KeyType::Administrator);
+        self.cache_updater.send_if_modified(|cache| {
+            if *cache == stored_keys {
+                return false;
+            }
+            tracing::info!(keys = stored_keys.len(), "auth keys changed");
+            *cache = stored_keys;
+            true
+        });
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, sqlx::Type)]
 #[sqlx(type_name = "key_type", rename_all = "snake_case")]
 pub enum KeyType {
@@ -96,10 +193,10 @@ pub enum KeyType {
 }
 
 impl KeyType {
-    pub fn from_i32(v: i32) -> anyhow::Result<Self> {
+    pub fn from_i32(v: i32) -> Result<Self> {
         ProtoKeyType::from_i32(v)
             .map(|kt| kt.into())
-            .ok_or_else(|| anyhow!("unsupported key type {}", v))
+            .ok_or_else(|| Error::decode(format!("unsupported key type {v}")))
     }
 }
 
@@ -140,9 +237,7 @@ impl std::fmt::Display for KeyType {
     }
 }
 
-pub async fn fetch_stored_keys(
-    db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Vec<(PublicKey, KeyType)>> {
+pub async fn fetch_stored_keys(db: impl sqlx::PgExecutor<'_>) -> Result<Vec<(PublicKey, KeyType)>> {
     Ok(sqlx::query(r#" select pubkey, key_type from admin_keys "#)
         .fetch_all(db)
         .await?
@@ -155,7 +250,7 @@ pub async fn insert_key(
     pubkey: PublicKeyBinary,
     key_type: KeyType,
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<()> {
+) -> Result<()> {
     Ok(
         sqlx::query(r#" insert into admin_keys (pubkey, key_type) values ($1, $2) "#)
             .bind(pubkey)
@@ -169,7 +264,7 @@ pub async fn insert_key(
 pub async fn remove_key(
     pubkey: PublicKeyBinary,
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Option<(PublicKey, KeyType)>> {
+) -> Result<Option<(PublicKey, KeyType)>> {
     Ok(sqlx::query(
         r#"
         delete from admin_keys
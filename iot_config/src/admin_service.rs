@@ -1,9 +1,9 @@
 use crate::{
-    admin::{self, AuthCache, CacheKeys, KeyType},
+    admin::{self, AuthCache, AuthCacheUpdater, KeyType},
     region_map::{self, RegionMap, RegionMapReader},
     telemetry, verify_public_key, GrpcResult, Settings,
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use chrono::Utc;
 use file_store::traits::{MsgVerify, TimestampEncode};
 use futures::future::TryFutureExt;
@@ -16,12 +16,13 @@ use helium_proto::{
     Message, Region,
 };
 use sqlx::{Pool, Postgres};
+use std::sync::Arc;
 use tokio::sync::watch;
 use tonic::{Request, Response, Status};
 
 pub struct AdminService {
     auth_cache: AuthCache,
-    auth_updater: watch::Sender<CacheKeys>,
+    auth_updater: Arc<AuthCacheUpdater>,
     pool: Pool<Postgres>,
     region_map: RegionMapReader,
     region_updater: watch::Sender<RegionMap>,
@@ -32,7 +33,7 @@ impl AdminService {
     pub fn new(
         settings: &Settings,
         auth_cache: AuthCache,
-        auth_updater: watch::Sender<CacheKeys>,
+        auth_updater: Arc<AuthCacheUpdater>,
         pool: Pool<Postgres>,
         region_map: RegionMapReader,
         region_updater: watch::Sender<RegionMap>,
@@ -91,27 +92,27 @@ impl iot_config::Admin for AdminService {
         let pubkey = verify_public_key(request.pubkey.as_ref())
             .map_err(|_| Status::invalid_argument("invalid pubkey supplied"))?;
 
+        let _updating = self.auth_updater.lock().await;
         admin::insert_key(request.pubkey.clone().into(), key_type, &self.pool)
-            .and_then(|_| async move {
-                if self.auth_updater.send_if_modified(|cache| {
-                    if let std::collections::hash_map::Entry::Vacant(key) = cache.entry(pubkey) {
-                        key.insert(key_type);
-                        true
-                    } else {
-                        false
-                    }
-                }) {
-                    Ok(())
-                } else {
-                    Err(anyhow!("key already registered"))
-                }
-            })
+            .await
             .map_err(|err| {
-                let pubkey: PublicKeyBinary = request.pubkey.into();
-                tracing::error!(pubkey = pubkey.to_string(), "pubkey add failed");
-                Status::internal(format!("error saving requested key: {pubkey}, {err:?}"))
-            })
-            .await?;
+                let pubkey: PublicKeyBinary = request.pubkey.clone().into();
+                tracing::error!(pubkey = pubkey.to_string(), "pubkey add failed: {err:?}");
+                Status::from(err)
+            })?;
+
+        if !self.auth_updater.send_if_modified(|cache| {
+            if let std::collections::hash_map::Entry::Vacant(key) = cache.entry(pubkey.clone()) {
+                key.insert(key_type);
+                true
+            } else {
+                false
+            }
+        }) {
+            return Err(Status::already_exists(format!(
+                "key already registered: {pubkey}"
+            )));
+        }
 
         let timestamp = Utc::now().encode_timestamp();
         let signer = self.signing_key.public_key().into();
@@ -132,6 +133,7 @@ impl iot_config::Admin for AdminService {
         let signer = verify_public_key(&request.signer)?;
         self.verify_admin_request_signature(&signer, &request)?;
 
+        let _updating = self.auth_updater.lock().await;
         admin::remove_key(request.pubkey.clone().into(), &self.pool)
             .and_then(|deleted| async move {
                 match deleted {
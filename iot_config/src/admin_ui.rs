@@ -0,0 +1,143 @@
+//! Pages of the read-only admin ui: the orgs with their routes, the key
+//! registry, the recent audit log entries and the stream subscribers.
+
+use crate::{admin::AuthCache, audit, org, telemetry};
+use poc_metrics::ui::{self, Page, Render, RenderResult};
+use sqlx::{Pool, Postgres, Row};
+use std::collections::HashMap;
+
+/// Audit log entries shown, newest first
+const RECENT_AUDIT_ENTRIES: i64 = 100;
+
+pub fn register(pool: Pool<Postgres>, auth_cache: AuthCache) {
+    ui::register("orgs", OrgsPage { pool: pool.clone() });
+    ui::register("keys", KeysPage { auth_cache });
+    ui::register("audit", AuditPage { pool });
+    ui::register("streams", StreamsPage);
+}
+
+struct OrgsPage {
+    pool: Pool<Postgres>,
+}
+
+impl Page for OrgsPage {
+    fn title(&self) -> &'static str {
+        "orgs"
+    }
+
+    fn render(&self) -> Render {
+        Box::pin(render_orgs(self.pool.clone()))
+    }
+}
+
+async fn render_orgs(pool: Pool<Postgres>) -> RenderResult {
+    let orgs = org::list(&pool).await?;
+    let route_counts: HashMap<i64, (i64, i64)> = sqlx::query(
+        r#"
+        select oui, count(*) as routes, count(*) filter (where active) as active
+        from routes group by oui
+        "#,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| (row.get("oui"), (row.get("routes"), row.get("active"))))
+    .collect();
+
+    let rows = orgs.into_iter().map(|org| {
+        let (routes, active) = route_counts
+            .get(&(org.oui as i64))
+            .copied()
+            .unwrap_or_default();
+        vec![
+            org.oui.to_string(),
+            org.owner.to_string(),
+            org.payer.to_string(),
+            org.locked.to_string(),
+            org.delegate_keys.map_or(0, |keys| keys.len()).to_string(),
+            org.constraints.map_or(0, |ranges| ranges.len()).to_string(),
+            routes.to_string(),
+            active.to_string(),
+        ]
+    });
+    let headers = [
+        "oui",
+        "owner",
+        "payer",
+        "locked",
+        "delegate keys",
+        "devaddr constraints",
+        "routes",
+        "active routes",
+    ];
+    Ok(ui::table(&headers, rows))
+}
+
+struct KeysPage {
+    auth_cache: AuthCache,
+}
+
+impl Page for KeysPage {
+    fn title(&self) -> &'static str {
+        "key registry"
+    }
+
+    fn render(&self) -> Render {
+        let mut keys = self.auth_cache.get_keys();
+        keys.sort_by_key(|(pubkey, key_type)| (key_type.to_string(), pubkey.to_string()));
+        let rows = keys
+            .into_iter()
+            .map(|(pubkey, key_type)| vec![key_type.to_string(), pubkey.to_string()]);
+        ui::ready(ui::table(&["type", "pubkey"], rows))
+    }
+}
+
+struct AuditPage {
+    pool: Pool<Postgres>,
+}
+
+impl Page for AuditPage {
+    fn title(&self) -> &'static str {
+        "audit log"
+    }
+
+    fn render(&self) -> Render {
+        Box::pin(render_audit(self.pool.clone()))
+    }
+}
+
+async fn render_audit(pool: Pool<Postgres>) -> RenderResult {
+    let entries = audit::recent(RECENT_AUDIT_ENTRIES, &pool).await?;
+    let rows = entries.into_iter().map(|entry| {
+        vec![
+            entry.id.to_string(),
+            entry.inserted_at.to_rfc3339(),
+            entry.rpc,
+            entry.oui.map(|oui| oui.to_string()).unwrap_or_default(),
+            entry.signer,
+        ]
+    });
+    Ok(ui::table(&["id", "at", "rpc", "oui", "signer"], rows))
+}
+
+struct StreamsPage;
+
+impl Page for StreamsPage {
+    fn title(&self) -> &'static str {
+        "streams"
+    }
+
+    fn render(&self) -> Render {
+        let rows = [
+            vec![
+                "route".to_string(),
+                telemetry::route_stream_subscribers().to_string(),
+            ],
+            vec![
+                "skf".to_string(),
+                telemetry::skf_stream_subscribers().to_string(),
+            ],
+        ];
+        ui::ready(ui::table(&["stream", "subscribers"], rows))
+    }
+}
@@ -0,0 +1,465 @@
+//! Hash chained log of the mutating rpcs of the config service, recording
+//! the signer, request and state mutated of every mutation.
+
+use crate::{ext::AuditLogEntryV1, telemetry};
+use chrono::{DateTime, SubsecRound, Utc};
+use futures::stream::{Stream, TryStreamExt};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::Message;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, FromRow, Pool, Postgres, Row, Transaction};
+use std::{collections::HashMap, str::FromStr};
+
+/// Class of the advisory locks serializing appends to the chain of an org,
+/// keyed by the oui
+const AUDIT_LOG_LOCK_CLASS: i32 = 0x6175_6469;
+
+/// Previous hash of the first entry
+pub const GENESIS_HASH: [u8; 32] = [0; 32];
+
+/// A mutation to record, built before the rpc mutates anything
+pub struct Entry {
+    rpc: &'static str,
+    oui: Option<u64>,
+    signer: String,
+    request_hash: Vec<u8>,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Entry {
+    pub fn new(
+        rpc: &'static str,
+        oui: Option<u64>,
+        signer: &PublicKey,
+        request: &impl Message,
+    ) -> Self {
+        Self {
+            rpc,
+            oui,
+            signer: signer.to_string(),
+            request_hash: Sha256::digest(request.encode_to_vec()).to_vec(),
+            before: None,
+            after: None,
+        }
+    }
+
+    /// An entry for a batch of `requests` streamed by one rpc, hashed
+    /// together in order
+    pub fn batch<M: Message>(
+        rpc: &'static str,
+        oui: Option<u64>,
+        signer: &PublicKey,
+        requests: &[M],
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        for request in requests {
+            hasher.update(request.encode_length_delimited_to_vec());
+        }
+        Self {
+            rpc,
+            oui,
+            signer: signer.to_string(),
+            request_hash: hasher.finalize().to_vec(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn oui(self, oui: u64) -> Self {
+        Self {
+            oui: Some(oui),
+            ..self
+        }
+    }
+
+    pub fn before(self, state: &impl Serialize) -> Self {
+        Self {
+            before: serde_json::to_string(state).ok(),
+            ..self
+        }
+    }
+
+    pub fn after(self, state: &impl Serialize) -> Self {
+        Self {
+            after: serde_json::to_string(state).ok(),
+            ..self
+        }
+    }
+}
+
+/// A recorded entry of the log
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub id: i64,
+    pub rpc: String,
+    pub oui: Option<u64>,
+    pub signer: String,
+    pub request_hash: Vec<u8>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub prev_hash: Vec<u8>,
+    pub hash: Vec<u8>,
+    pub inserted_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, PgRow> for LogEntry {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            rpc: row.try_get("rpc")?,
+            oui: row
+                .try_get::<Option<i64>, &str>("oui")?
+                .map(|oui| oui as u64),
+            signer: row.try_get("signer")?,
+            request_hash: row.try_get("request_hash")?,
+            before: row.try_get("before_state")?,
+            after: row.try_get("after_state")?,
+            prev_hash: row.try_get("prev_hash")?,
+            hash: row.try_get("hash")?,
+            inserted_at: row.try_get("inserted_at")?,
+        })
+    }
+}
+
+impl LogEntry {
+    /// Whether the entry follows the entry hashing to `prev_hash` and its own
+    /// hash covers its fields as stored
+    pub fn is_intact(&self, prev_hash: &[u8]) -> bool {
+        self.prev_hash == prev_hash && self.computed_hash() == self.hash
+    }
+
+    fn computed_hash(&self) -> Vec<u8> {
+        entry_hash(
+            &self.prev_hash,
+            &self.rpc,
+            self.oui,
+            &self.signer,
+            &self.request_hash,
+            self.before.as_deref(),
+            self.after.as_deref(),
+            self.inserted_at,
+        )
+    }
+}
+
+impl From<LogEntry> for AuditLogEntryV1 {
+    fn from(entry: LogEntry) -> Self {
+        Self {
+            id: entry.id,
+            rpc: entry.rpc,
+            oui: entry.oui,
+            signer: PublicKeyBinary::from_str(&entry.signer)
+                .map(Vec::from)
+                .unwrap_or_default(),
+            request_hash: entry.request_hash,
+            before: entry.before,
+            after: entry.after,
+            inserted_at: entry.inserted_at.timestamp_micros(),
+            prev_hash: entry.prev_hash,
+            hash: entry.hash,
+        }
+    }
+}
+
+/// Hash of an entry chained to the entry before it. Every field is length
+/// prefixed so no two different entries hash the same bytes.
+#[allow(clippy::too_many_arguments)]
+pub fn entry_hash(
+    prev_hash: &[u8],
+    rpc: &str,
+    oui: Option<u64>,
+    signer: &str,
+    request_hash: &[u8],
+    before: Option<&str>,
+    after: Option<&str>,
+    inserted_at: DateTime<Utc>,
+) -> Vec<u8> {
+    let oui = oui.map(u64::to_be_bytes);
+    let inserted_at = inserted_at.timestamp_micros().to_be_bytes();
+    let fields: [Option<&[u8]>; 8] = [
+        Some(prev_hash),
+        Some(rpc.as_bytes()),
+        oui.as_ref().map(|oui| oui.as_slice()),
+        Some(signer.as_bytes()),
+        Some(request_hash),
+        before.map(str::as_bytes),
+        after.map(str::as_bytes),
+        Some(inserted_at.as_slice()),
+    ];
+    let mut hasher = Sha256::new();
+    for field in fields {
+        match field {
+            Some(field) => {
+                hasher.update([1]);
+                hasher.update((field.len() as u64).to_be_bytes());
+                hasher.update(field);
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Append `entry` to the chain of its org in `txn`, the transaction of the
+/// mutation it records, returning its id. A mutation whose entry can't be
+/// written is rolled back with it. Appends to the chain of an org are
+/// serialized by a lock held until the mutation commits, keeping the chain
+/// linear, while mutations of other orgs go ahead
+pub async fn record(entry: Entry, txn: &mut Transaction<'_, Postgres>) -> Result<i64, sqlx::Error> {
+    let rpc = entry.rpc;
+    let oui = entry.oui;
+    append(entry, txn).await.map_err(|err| {
+        tracing::error!(rpc, ?oui, reason = ?err, "failed to record audit log entry");
+        telemetry::count_audit_record_failure(rpc);
+        err
+    })
+}
+
+async fn append(entry: Entry, txn: &mut Transaction<'_, Postgres>) -> Result<i64, sqlx::Error> {
+    // ouis beyond i32 share locks, which only serializes their appends
+    sqlx::query(" select pg_advisory_xact_lock($1, $2) ")
+        .bind(AUDIT_LOG_LOCK_CLASS)
+        .bind(entry.oui.map_or(-1, |oui| oui as i32))
+        .execute(&mut *txn)
+        .await?;
+    let prev_hash = match entry.oui {
+        Some(oui) => sqlx::query_scalar::<_, Vec<u8>>(
+            " select hash from config_audit_log where oui = $1 order by id desc limit 1 ",
+        )
+        .bind(oui as i64),
+        None => sqlx::query_scalar::<_, Vec<u8>>(
+            " select hash from config_audit_log where oui is null order by id desc limit 1 ",
+        ),
+    }
+    .fetch_optional(&mut *txn)
+    .await?
+    .unwrap_or_else(|| GENESIS_HASH.to_vec());
+
+    // stored with microsecond precision, which the hash has to match
+    let inserted_at = Utc::now().trunc_subsecs(6);
+    let hash = entry_hash(
+        &prev_hash,
+        entry.rpc,
+        entry.oui,
+        &entry.signer,
+        &entry.request_hash,
+        entry.before.as_deref(),
+        entry.after.as_deref(),
+        inserted_at,
+    );
+    sqlx::query_scalar(
+        r#"
+        insert into config_audit_log (
+            rpc, oui, signer, request_hash, before_state, after_state, prev_hash, hash, inserted_at
+        )
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        returning id
+        "#,
+    )
+    .bind(entry.rpc)
+    .bind(entry.oui.map(|oui| oui as i64))
+    .bind(&entry.signer)
+    .bind(&entry.request_hash)
+    .bind(&entry.before)
+    .bind(&entry.after)
+    .bind(&prev_hash)
+    .bind(&hash)
+    .bind(inserted_at)
+    .fetch_one(&mut *txn)
+    .await
+}
+
+/// The entries after `after_id`, of `oui` when set, oldest first
+pub fn entries<'a>(
+    oui: Option<u64>,
+    after_id: i64,
+    db: &'a Pool<Postgres>,
+) -> impl Stream<Item = Result<LogEntry, sqlx::Error>> + 'a {
+    sqlx::query_as::<_, LogEntry>(
+        r#"
+        select * from config_audit_log
+        where id > $1 and ($2::bigint is null or oui = $2)
+        order by id
+        "#,
+    )
+    .bind(after_id)
+    .bind(oui.map(|oui| oui as i64))
+    .fetch(db)
+}
+
+/// The `limit` most recent entries, newest first
+pub async fn recent(limit: i64, db: &Pool<Postgres>) -> Result<Vec<LogEntry>, sqlx::Error> {
+    sqlx::query_as::<_, LogEntry>("select * from config_audit_log order by id desc limit $1")
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+/// Checks entries read in id order against the chains of their orgs
+#[derive(Default)]
+pub struct ChainCheck {
+    heads: HashMap<Option<u64>, Vec<u8>>,
+    from_genesis: bool,
+}
+
+impl ChainCheck {
+    /// A check of the log read from its first entry, each chain starting from
+    /// [`GENESIS_HASH`]
+    pub fn from_genesis() -> Self {
+        Self {
+            from_genesis: true,
+            ..Default::default()
+        }
+    }
+
+    /// A check of the log read from the middle, the first entry of each chain
+    /// only checked against its own hash
+    pub fn resumed() -> Self {
+        Self::default()
+    }
+
+    /// Whether `entry` is intact and follows the last entry checked of its
+    /// chain
+    pub fn check(&mut self, entry: &LogEntry) -> bool {
+        let prev_hash = match self.heads.get(&entry.oui) {
+            Some(head) => head.as_slice(),
+            None if self.from_genesis => GENESIS_HASH.as_slice(),
+            None => entry.prev_hash.as_slice(),
+        };
+        let intact = entry.is_intact(prev_hash);
+        self.heads.insert(entry.oui, entry.hash.clone());
+        intact
+    }
+}
+
+/// Walk the chain of every org, returning the id of the first entry whose
+/// hash or link to the entry before it doesn't match.
+///
+/// Editing, inserting or deleting an entry breaks its chain from there on,
+/// but whoever can write the table can also recompute the hashes after it,
+/// and dropping the last entries of a chain leaves it intact. The chain only
+/// detects those against heads kept outside the database, like the hashes
+/// sent to the clients of the `audit_log` api.
+pub async fn verify_chain(db: &Pool<Postgres>) -> Result<Option<i64>, sqlx::Error> {
+    let mut chains = ChainCheck::from_genesis();
+    let mut entries = entries(None, 0, db);
+    while let Some(entry) = entries.try_next().await? {
+        if !chains.check(&entry) {
+            return Ok(Some(entry.id));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::keypair;
+    use chrono::TimeZone;
+
+    async fn record_locked(locked: bool, pool: &Pool<Postgres>) -> i64 {
+        record_org_locked(1, locked, pool).await
+    }
+
+    async fn record_org_locked(oui: u64, locked: bool, pool: &Pool<Postgres>) -> i64 {
+        let entry =
+            Entry::new("org.disable", Some(oui), keypair().public_key(), &()).after(&locked);
+        let mut txn = pool.begin().await.unwrap();
+        let id = record(entry, &mut txn).await.unwrap();
+        txn.commit().await.unwrap();
+        id
+    }
+
+    #[test]
+    fn hash_covers_every_field_and_the_chain() {
+        let inserted_at = Utc.timestamp_opt(1_693_900_800, 0).unwrap();
+        let hash = |prev_hash: &[u8], before: Option<&str>, after: Option<&str>| {
+            entry_hash(
+                prev_hash,
+                "org.disable",
+                Some(1),
+                "signer",
+                &[1, 2, 3],
+                before,
+                after,
+                inserted_at,
+            )
+        };
+        let locked = Some(r#"{"locked":true}"#);
+        let unlocked = Some(r#"{"locked":false}"#);
+
+        let entry = hash(&GENESIS_HASH, unlocked, locked);
+        assert_eq!(entry, hash(&GENESIS_HASH, unlocked, locked));
+        assert_ne!(entry, hash(&GENESIS_HASH, locked, unlocked));
+        assert_ne!(entry, hash(&entry, unlocked, locked));
+        // moving a value from one field to the next changes the hash
+        assert_ne!(
+            hash(&GENESIS_HASH, None, locked),
+            hash(&GENESIS_HASH, locked, None)
+        );
+    }
+
+    #[sqlx::test]
+    async fn edited_entries_break_the_chain(pool: Pool<Postgres>) {
+        let first = record_locked(true, &pool).await;
+        let second = record_locked(false, &pool).await;
+        assert_eq!(None, verify_chain(&pool).await.unwrap());
+        let entries = recent(2, &pool).await.unwrap();
+        assert_eq!(second, entries[0].id);
+        assert_eq!(entries[1].hash, entries[0].prev_hash);
+        assert_eq!(GENESIS_HASH.to_vec(), entries[1].prev_hash);
+
+        sqlx::query("update config_audit_log set after_state = 'false' where id = $1")
+            .bind(first)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(first), verify_chain(&pool).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn orgs_are_chained_apart(pool: Pool<Postgres>) {
+        record_org_locked(1, true, &pool).await;
+        let second = record_org_locked(2, true, &pool).await;
+        let third = record_org_locked(1, false, &pool).await;
+        let recorded = recent(3, &pool).await.unwrap();
+        assert_eq!(third, recorded[0].id);
+        assert_eq!(recorded[2].hash, recorded[0].prev_hash);
+        assert_eq!(second, recorded[1].id);
+        assert_eq!(GENESIS_HASH.to_vec(), recorded[1].prev_hash);
+        assert_eq!(None, verify_chain(&pool).await.unwrap());
+
+        // an org's entries check out on their own
+        let mut chains = ChainCheck::from_genesis();
+        let org = entries(Some(1), 0, &pool)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(org.iter().all(|entry| chains.check(entry)));
+
+        sqlx::query("delete from config_audit_log where id = $1")
+            .bind(recorded[2].id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(third), verify_chain(&pool).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn entries_roll_back_with_their_mutation(pool: Pool<Postgres>) {
+        let entry = Entry::new("org.disable", Some(1), keypair().public_key(), &());
+        let mut txn = pool.begin().await.unwrap();
+        record(entry, &mut txn).await.unwrap();
+        txn.rollback().await.unwrap();
+        assert!(recent(1, &pool).await.unwrap().is_empty());
+
+        // the chain starts over from the first committed entry
+        record_locked(true, &pool).await;
+        let entries = recent(2, &pool).await.unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(GENESIS_HASH.to_vec(), entries[0].prev_hash);
+        assert_eq!(None, verify_chain(&pool).await.unwrap());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    audit,
+    ext::{self, AuditLogEntryV1, AuditLogStreamReqV1, AuditLogStreamResV1},
+    telemetry, verify_public_key, GrpcResult, GrpcStreamResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use futures::stream::{StreamExt, TryStreamExt};
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+
+const DEFAULT_BATCH_SIZE: u32 = 500;
+
+/// Streams the audit log of the mutating rpcs to admins
+pub struct AuditLogService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Arc<Keypair>,
+}
+
+impl AuditLogService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: Arc::new(settings.signing_keypair()?),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ext::audit_log_server::AuditLog for AuditLogService {
+    type streamStream = GrpcStreamResult<AuditLogStreamResV1>;
+    async fn stream(
+        &self,
+        request: Request<AuditLogStreamReqV1>,
+    ) -> GrpcResult<Self::streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("audit-log", "stream");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, &request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))?;
+
+        let oui = (request.oui != 0).then_some(request.oui);
+        let batch_size = match request.batch_size {
+            0 => DEFAULT_BATCH_SIZE,
+            batch_size => batch_size,
+        };
+        tracing::debug!(?oui, after_id = request.after_id, "streaming audit log");
+
+        let pool = self.pool.clone();
+        let signing_key = self.signing_key.clone();
+        let (tx, rx) = mpsc::channel(20);
+        tokio::spawn(async move {
+            if let Err(err) =
+                stream_entries(&pool, &signing_key, oui, request.after_id, batch_size, &tx).await
+            {
+                tracing::error!(?oui, reason = ?err, "audit log stream failed");
+                _ = tx.send(Err(err)).await;
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+/// Stream the entries after `after_id` in signed batches, checking the chain
+/// of each org link by link on the way and ending the stream at the first
+/// entry not intact
+async fn stream_entries(
+    pool: &Pool<Postgres>,
+    signing_key: &Keypair,
+    oui: Option<u64>,
+    after_id: i64,
+    batch_size: u32,
+    tx: &mpsc::Sender<Result<AuditLogStreamResV1, Status>>,
+) -> Result<(), Status> {
+    let signer: Vec<u8> = signing_key.public_key().into();
+    let mut chains = if after_id == 0 {
+        audit::ChainCheck::from_genesis()
+    } else {
+        audit::ChainCheck::resumed()
+    };
+    let mut entries = audit::entries(oui, after_id, pool)
+        .map_err(|err| Status::internal(format!("audit log read failed: {err:?}")))
+        .chunks(batch_size as usize);
+    while let Some(batch) = entries.next().await {
+        let batch = batch.into_iter().collect::<Result<Vec<_>, Status>>()?;
+        let mut verified = Vec::with_capacity(batch.len());
+        for entry in batch {
+            if !chains.check(&entry) {
+                tracing::error!(id = entry.id, "audit log chain broken");
+                return Err(Status::data_loss(format!(
+                    "audit log chain broken at entry {}",
+                    entry.id
+                )));
+            }
+            verified.push(AuditLogEntryV1::from(entry));
+        }
+        let mut response = AuditLogStreamResV1 {
+            entries: verified,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: signer.clone(),
+            signature: vec![],
+        };
+        response.signature = signing_key
+            .sign(&response.encode_to_vec())
+            .map_err(|_| Status::internal("response signing error"))?;
+        if tx.send(Ok(response)).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,13 @@
-use crate::gateway_info;
+use crate::{ext, gateway_info, region_map::RegionLimits};
+use chrono::{DateTime, TimeZone, Utc};
 use file_store::traits::MsgVerify;
-use futures::stream::{self, StreamExt};
+use futures::stream::{self, BoxStream, StreamExt};
 use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
 use helium_proto::{
     services::{iot_config, Channel, Endpoint},
     BlockchainRegionParamV1, Message, Region,
 };
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc};
 
 pub mod org_client;
 mod settings;
@@ -26,10 +27,16 @@ pub enum ClientError {
     UndefinedRegionParams(String),
 }
 
+/// Gateways updated from chain, an error ends the changes received
+pub type GatewayChangeStream = BoxStream<'static, Result<gateway_info::GatewayInfo, ClientError>>;
+
 #[derive(Clone, Debug)]
 pub struct Client {
     pub gateway_client: iot_config::gateway_client::GatewayClient<Channel>,
     pub admin_client: iot_config::admin_client::AdminClient<Channel>,
+    pub region_limits_client: ext::region_limits_client::RegionLimitsClient<Channel>,
+    pub gateway_onboarding_client: ext::gateway_onboarding_client::GatewayOnboardingClient<Channel>,
+    pub gateway_changes_client: ext::gateway_changes_client::GatewayChangesClient<Channel>,
     signing_key: Arc<Keypair>,
     config_pubkey: PublicKey,
     batch_size: u32,
@@ -38,12 +45,19 @@ pub struct Client {
 impl Client {
     pub fn from_settings(settings: &Settings) -> Result<Self, Box<helium_crypto::Error>> {
         let channel = Endpoint::from(settings.url.clone())
-            .connect_timeout(Duration::from_secs(settings.connect_timeout))
-            .timeout(Duration::from_secs(settings.rpc_timeout))
+            .connect_timeout(settings.connect_timeout)
+            .timeout(settings.rpc_timeout)
             .connect_lazy();
         Ok(Self {
             gateway_client: iot_config::gateway_client::GatewayClient::new(channel.clone()),
-            admin_client: iot_config::admin_client::AdminClient::new(channel),
+            admin_client: iot_config::admin_client::AdminClient::new(channel.clone()),
+            region_limits_client: ext::region_limits_client::RegionLimitsClient::new(
+                channel.clone(),
+            ),
+            gateway_onboarding_client: ext::gateway_onboarding_client::GatewayOnboardingClient::new(
+                channel.clone(),
+            ),
+            gateway_changes_client: ext::gateway_changes_client::GatewayChangesClient::new(channel),
             signing_key: settings.signing_keypair()?,
             config_pubkey: settings.config_pubkey()?,
             batch_size: settings.batch_size,
@@ -62,14 +76,175 @@ impl Client {
         request.signature = self.signing_key.sign(&request.encode_to_vec())?;
         let response = self.admin_client.region_params(request).await?.into_inner();
         response.verify(&self.config_pubkey)?;
+        // the limits are an extension of the region params, a failure to
+        // fetch them falls back to the verifiers' defaults rather than
+        // failing the region params too
+        let limits = self
+            .resolve_region_limits(region)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!(%region, ?err, "failed to fetch region limits, using defaults");
+                RegionLimits::default()
+            });
         Ok(RegionParamsInfo {
             region: response.region(),
             region_params: response
                 .params
                 .ok_or_else(|| ClientError::UndefinedRegionParams(format!("{region}")))?
                 .region_params,
+            max_witness_distance: limits.max_witness_distance,
+            witness_window: limits.witness_window,
         })
     }
+
+    /// Limits of `region` applied by the verifiers
+    pub async fn resolve_region_limits(
+        &mut self,
+        region: Region,
+    ) -> Result<RegionLimits, ClientError> {
+        let mut request = ext::RegionLimitsReqV1 {
+            region: region.into(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        let response = self
+            .region_limits_client
+            .limits(request)
+            .await?
+            .into_inner();
+        response.verify(&self.config_pubkey)?;
+        let limit = |value| match value {
+            0 => None,
+            value => Some(value),
+        };
+        Ok(RegionLimits {
+            max_witness_distance: limit(response.max_witness_distance),
+            witness_window: limit(response.witness_window),
+        })
+    }
+
+    /// When the gateway was onboarded on chain and its metadata last updated
+    pub async fn resolve_onboarding(
+        &mut self,
+        address: &PublicKeyBinary,
+    ) -> Result<gateway_info::GatewayOnboarding, ClientError> {
+        let mut request = ext::GatewayOnboardingReqV1 {
+            address: address.clone().into(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        let response = self
+            .gateway_onboarding_client
+            .onboarding(request)
+            .await?
+            .into_inner();
+        response.verify(&self.config_pubkey)?;
+        Ok(response
+            .gateway
+            .map(|gateway| onboarding(&gateway))
+            .unwrap_or_default())
+    }
+
+    /// When every gateway was onboarded on chain and its metadata last
+    /// updated
+    pub async fn stream_onboarding(
+        &mut self,
+    ) -> Result<HashMap<PublicKeyBinary, gateway_info::GatewayOnboarding>, ClientError> {
+        let mut request = ext::GatewayOnboardingStreamReqV1 {
+            batch_size: self.batch_size,
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        let pubkey = Arc::new(self.config_pubkey.clone());
+        let onboarded = self
+            .gateway_onboarding_client
+            .onboarding_stream(request)
+            .await?
+            .into_inner()
+            .filter_map(|resp| async move { resp.ok() })
+            .map(move |resp| (resp, pubkey.clone()))
+            .filter_map(|(resp, pubkey)| async move { resp.verify(&pubkey).map(|_| resp).ok() })
+            .flat_map(|resp| stream::iter(resp.gateways.into_iter()))
+            .map(|gateway| (gateway.address.clone().into(), onboarding(&gateway)))
+            .collect()
+            .await;
+        Ok(onboarded)
+    }
+
+    /// The gateways whose metadata was updated from chain after `since`.
+    /// Responses failing or not verifying are yielded as errors rather than
+    /// skipped, so callers can tell they missed changes
+    pub async fn stream_gateway_changes(
+        &mut self,
+        since: DateTime<Utc>,
+    ) -> Result<GatewayChangeStream, ClientError> {
+        let mut request = ext::GatewayChangesReqV1 {
+            since: since.timestamp().max(0) as u64,
+            batch_size: self.batch_size,
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        tracing::debug!(%since, "fetching gateway changes");
+        let pubkey = Arc::new(self.config_pubkey.clone());
+        let response_stream = self
+            .gateway_changes_client
+            .changes(request)
+            .await?
+            .into_inner()
+            .map(
+                move |resp| -> Result<ext::GatewayChangesResV1, ClientError> {
+                    let resp = resp?;
+                    resp.verify(&pubkey)?;
+                    Ok(resp)
+                },
+            )
+            .flat_map(|resp| {
+                let changes: Vec<_> = match resp {
+                    Ok(resp) => resp
+                        .gateways
+                        .into_iter()
+                        .filter_map(gateway_change)
+                        .map(Ok)
+                        .collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                stream::iter(changes)
+            })
+            .boxed();
+
+        Ok(response_stream)
+    }
+}
+
+fn gateway_change(change: ext::GatewayChangeV1) -> Option<gateway_info::GatewayInfo> {
+    let mut info = gateway_info::GatewayInfo::from(change.info?);
+    if let Some(metadata) = info.metadata.as_mut() {
+        change
+            .onboarding
+            .as_ref()
+            .map(onboarding)
+            .unwrap_or_default()
+            .fill(metadata);
+    }
+    Some(info)
+}
+
+fn onboarding(gateway: &ext::GatewayOnboardedV1) -> gateway_info::GatewayOnboarding {
+    gateway_info::GatewayOnboarding {
+        onboarded_at: decode_timestamp(gateway.onboarded_at),
+        updated_at: decode_timestamp(gateway.metadata_updated_at),
+    }
+}
+
+fn decode_timestamp(timestamp: u64) -> Option<DateTime<Utc>> {
+    match timestamp {
+        0 => None,
+        timestamp => Utc.timestamp_opt(timestamp as i64, 0).single(),
+    }
 }
 
 #[async_trait::async_trait]
@@ -96,7 +271,22 @@ impl gateway_info::GatewayInfoResolver for Client {
             Err(status) if status.code() == tonic::Code::NotFound => None,
             Err(status) => Err(status)?,
         };
-        Ok(response)
+        let Some(mut info) = response else {
+            return Ok(None);
+        };
+        if let Some(metadata) = info.metadata.as_mut() {
+            // onboarding times are optional, a gateway whose onboarding can't
+            // be resolved is still resolved, without them
+            match self.resolve_onboarding(address).await {
+                Ok(onboarding) => onboarding.fill(metadata),
+                Err(err) => tracing::warn!(
+                    pubkey = address.to_string(),
+                    ?err,
+                    "failed to resolve gateway onboarding"
+                ),
+            }
+        }
+        Ok(Some(info))
     }
 
     async fn stream_gateways_info(
@@ -109,6 +299,10 @@ impl gateway_info::GatewayInfoResolver for Client {
         };
         request.signature = self.signing_key.sign(&request.encode_to_vec())?;
         tracing::debug!("fetching gateway info stream");
+        let onboarding = self.stream_onboarding().await.unwrap_or_else(|err| {
+            tracing::warn!(?err, "failed to stream gateway onboarding");
+            HashMap::new()
+        });
         let pubkey = Arc::new(self.config_pubkey.clone());
         let response_stream = self
             .gateway_client
@@ -120,6 +314,16 @@ impl gateway_info::GatewayInfoResolver for Client {
             .filter_map(|(resp, pubkey)| async move { resp.verify(&pubkey).map(|_| resp).ok() })
             .flat_map(|resp| stream::iter(resp.gateways.into_iter()))
             .map(gateway_info::GatewayInfo::from)
+            .map(move |mut info| {
+                if let Some(metadata) = info.metadata.as_mut() {
+                    onboarding
+                        .get(&info.address)
+                        .copied()
+                        .unwrap_or_default()
+                        .fill(metadata);
+                }
+                info
+            })
             .boxed();
 
         Ok(response_stream)
@@ -130,6 +334,13 @@ impl gateway_info::GatewayInfoResolver for Client {
 pub struct RegionParamsInfo {
     pub region: Region,
     pub region_params: Vec<BlockchainRegionParamV1>,
+    /// Max distance in km between a beaconer in the region and its
+    /// witnesses, None when the region has no limit of its own
+    pub max_witness_distance: Option<u32>,
+    /// Seconds from the start of a beacon's entropy within which witnesses
+    /// of a beaconer in the region are accepted, None when the region has
+    /// no window of its own
+    pub witness_window: Option<u32>,
 }
 
 #[derive(thiserror::Error, Debug)]
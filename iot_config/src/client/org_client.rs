@@ -1,9 +1,11 @@
 use super::{
-    iot_config, Arc, Channel, ClientError, Duration, Endpoint, Keypair, Message, MsgVerify,
-    PublicKey, Settings, Sign,
+    iot_config, Arc, Channel, ClientError, Endpoint, Keypair, Message, MsgVerify, PublicKey,
+    Settings, Sign,
 };
+use crate::ext::{org_payer_client::OrgPayerClient, OrgPayerChangeV1, OrgPayerStreamReqV1};
 use chrono::Utc;
 use file_store::traits::TimestampEncode;
+use futures::stream::{BoxStream, StreamExt};
 use helium_proto::services::iot_config::{
     OrgDisableReqV1, OrgEnableReqV1, OrgGetReqV1, OrgListReqV1, OrgResV1, OrgV1,
 };
@@ -11,6 +13,7 @@ use helium_proto::services::iot_config::{
 #[derive(Clone)]
 pub struct OrgClient {
     client: iot_config::config_org_client::OrgClient<Channel>,
+    payer_client: OrgPayerClient<Channel>,
     signing_key: Arc<Keypair>,
     config_pubkey: PublicKey,
 }
@@ -18,11 +21,12 @@ pub struct OrgClient {
 impl OrgClient {
     pub fn from_settings(settings: &Settings) -> Result<Self, Box<helium_crypto::Error>> {
         let channel = Endpoint::from(settings.url.clone())
-            .connect_timeout(Duration::from_secs(settings.connect_timeout))
-            .timeout(Duration::from_secs(settings.rpc_timeout))
+            .connect_timeout(settings.connect_timeout)
+            .timeout(settings.rpc_timeout)
             .connect_lazy();
         Ok(Self {
-            client: iot_config::config_org_client::OrgClient::new(channel),
+            client: iot_config::config_org_client::OrgClient::new(channel.clone()),
+            payer_client: OrgPayerClient::new(channel),
             signing_key: settings.signing_keypair()?,
             config_pubkey: settings.config_pubkey()?,
         })
@@ -74,4 +78,33 @@ impl OrgClient {
         res.verify(&self.config_pubkey)?;
         Ok(())
     }
+
+    /// Subscribe to org payer changes as they take effect. The stream ends
+    /// with an error when the server closes it or a change fails
+    /// verification
+    pub async fn payer_stream(
+        &mut self,
+    ) -> Result<BoxStream<'static, Result<OrgPayerChangeV1, ClientError>>, ClientError> {
+        tracing::debug!("subscribing to org payer changes");
+
+        let mut req = OrgPayerStreamReqV1 {
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        req.signature = self.signing_key.sign(&req.encode_to_vec())?;
+        let config_pubkey = self.config_pubkey.clone();
+        let stream = self
+            .payer_client
+            .stream(req)
+            .await?
+            .into_inner()
+            .map(move |res| {
+                let res = res?;
+                res.verify(&config_pubkey)?;
+                Ok(res.change.unwrap_or_default())
+            })
+            .boxed();
+        Ok(stream)
+    }
 }
@@ -1,5 +1,6 @@
+use helium_proto::services::Endpoint;
 use serde::Deserialize;
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Settings {
@@ -10,23 +11,26 @@ pub struct Settings {
     pub signing_keypair: String,
     /// B58 encoded public key of the iot config server for verifying responses
     pub config_pubkey: String,
-    /// Connect timeout for the iot config client in seconds. Default 5
-    #[serde(default = "default_connect_timeout")]
-    pub connect_timeout: u64,
-    /// RPC timeout for iot config client in seconds. Default 5
-    #[serde(default = "default_rpc_timeout")]
-    pub rpc_timeout: u64,
+    /// Connect timeout for the iot config client. Default 5s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_connect_timeout"
+    )]
+    pub connect_timeout: Duration,
+    /// RPC timeout for iot config client. Default 5s
+    #[serde(with = "settings_loader::duration", default = "default_rpc_timeout")]
+    pub rpc_timeout: Duration,
     /// Batch size for gateway info stream results. Default 1000
     #[serde(default = "default_batch_size")]
     pub batch_size: u32,
 }
 
-pub fn default_connect_timeout() -> u64 {
-    5
+pub fn default_connect_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
-pub fn default_rpc_timeout() -> u64 {
-    5
+pub fn default_rpc_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
 pub fn default_batch_size() -> u32 {
@@ -37,11 +41,19 @@ impl Settings {
     pub fn signing_keypair(
         &self,
     ) -> Result<Arc<helium_crypto::Keypair>, Box<helium_crypto::Error>> {
-        let data = std::fs::read(&self.signing_keypair).map_err(helium_crypto::Error::from)?;
-        Ok(Arc::new(helium_crypto::Keypair::try_from(&data[..])?))
+        Ok(Arc::new(settings_loader::keypair(&self.signing_keypair)?))
     }
 
     pub fn config_pubkey(&self) -> Result<helium_crypto::PublicKey, helium_crypto::Error> {
         helium_crypto::PublicKey::from_str(&self.config_pubkey)
     }
+
+    /// Check the config server accepts connections, for preflight checks
+    pub async fn check_reachable(&self) -> Result<(), tonic::transport::Error> {
+        Endpoint::from(self.url.clone())
+            .connect_timeout(self.connect_timeout)
+            .connect()
+            .await
+            .map(|_| ())
+    }
 }
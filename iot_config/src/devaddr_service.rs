@@ -0,0 +1,130 @@
+use crate::{
+    admin::AuthCache,
+    ext::{
+        self, DevaddrOrgV1, OrgForDevaddrReqV1, OrgForDevaddrResV1, OrgsForDevaddrsReqV1,
+        OrgsForDevaddrsResV1,
+    },
+    lora_field::DevAddrField,
+    org::{self, DevAddrOrg},
+    telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+/// Maximum number of devaddrs resolved by a single bulk request
+const BULK_LOOKUP_LIMIT: usize = 5_000;
+
+pub struct DevaddrService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl DevaddrService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+        })
+    }
+
+    fn verify_request_signature<R>(&self, signer: &PublicKey, request: &R) -> Result<(), Status>
+    where
+        R: MsgVerify,
+    {
+        self.auth_cache
+            .verify_signature(signer, request)
+            .map_err(|_| Status::permission_denied("invalid request signature"))?;
+        Ok(())
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::devaddr_server::Devaddr for DevaddrService {
+    async fn org_for_devaddr(
+        &self,
+        request: Request<OrgForDevaddrReqV1>,
+    ) -> GrpcResult<OrgForDevaddrResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("devaddr", "org-for-devaddr");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        let devaddr: DevAddrField = request.devaddr.into();
+        let org = org::org_for_devaddr(devaddr, &self.pool)
+            .await?
+            .ok_or_else(|| Status::not_found(format!("no org for devaddr: {devaddr}")))?;
+
+        let mut resp = OrgForDevaddrResV1 {
+            org: Some(org.into()),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+
+    async fn orgs_for_devaddrs(
+        &self,
+        request: Request<OrgsForDevaddrsReqV1>,
+    ) -> GrpcResult<OrgsForDevaddrsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("devaddr", "orgs-for-devaddrs");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        if request.devaddrs.len() > BULK_LOOKUP_LIMIT {
+            return Err(Status::invalid_argument(format!(
+                "too many devaddrs requested; limit {BULK_LOOKUP_LIMIT}"
+            )));
+        }
+
+        let devaddrs: Vec<DevAddrField> = request
+            .devaddrs
+            .iter()
+            .map(|&devaddr| devaddr.into())
+            .collect();
+        let orgs = org::orgs_for_devaddrs(&devaddrs, &self.pool)
+            .await?
+            .into_iter()
+            .map(DevaddrOrgV1::from)
+            .collect();
+
+        let mut resp = OrgsForDevaddrsResV1 {
+            orgs,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
+
+impl From<DevAddrOrg> for DevaddrOrgV1 {
+    fn from(org: DevAddrOrg) -> Self {
+        Self {
+            devaddr: org.devaddr.into(),
+            oui: org.oui,
+            net_id: org.net_id.into(),
+            constraint: Some(org.constraint.into()),
+        }
+    }
+}
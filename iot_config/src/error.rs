@@ -0,0 +1,94 @@
+use tonic::Status;
+
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// Errors of the storage and cache apis of the config service that can fail
+/// other than in the database, apis only querying it return `sqlx::Error`,
+/// which converts into `Db`. Variants are coarse enough for the grpc services
+/// to map them onto status codes without inspecting error messages.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("db error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("decode error: {0}")]
+    Decode(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    /// The request can never succeed as is
+    #[error("invalid: {0}")]
+    Invalid(String),
+    /// The request conflicts with the current state, e.g. with other orgs
+    #[error("conflict: {0}")]
+    Conflict(String),
+}
+
+impl Error {
+    pub fn decode<E: ToString>(msg: E) -> Self {
+        Self::Decode(msg.to_string())
+    }
+
+    pub fn not_found<E: ToString>(msg: E) -> Self {
+        Self::NotFound(msg.to_string())
+    }
+
+    pub fn unauthorized<E: ToString>(msg: E) -> Self {
+        Self::Unauthorized(msg.to_string())
+    }
+
+    pub fn invalid<E: ToString>(msg: E) -> Self {
+        Self::Invalid(msg.to_string())
+    }
+
+    pub fn conflict<E: ToString>(msg: E) -> Self {
+        Self::Conflict(msg.to_string())
+    }
+}
+
+macro_rules! decode_err {
+    ($from_type:ty) => {
+        impl From<$from_type> for Error {
+            fn from(err: $from_type) -> Self {
+                Self::decode(err)
+            }
+        }
+    };
+}
+
+decode_err!(sqlx::types::uuid::Error);
+decode_err!(serde_json::Error);
+decode_err!(helium_crypto::Error);
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Db(err) => {
+                tracing::error!("db error: {err:?}");
+                Status::internal("db error")
+            }
+            Error::Decode(msg) => Status::invalid_argument(msg),
+            Error::NotFound(msg) => Status::not_found(msg),
+            Error::Unauthorized(msg) => Status::permission_denied(msg),
+            Error::Invalid(msg) => Status::invalid_argument(msg),
+            Error::Conflict(msg) => Status::failed_precondition(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn maps_to_status_codes() {
+        let code = |err: Error| Status::from(err).code();
+        assert_eq!(Code::Internal, code(Error::Db(sqlx::Error::RowNotFound)));
+        assert_eq!(Code::InvalidArgument, code(Error::decode("bad uuid")));
+        assert_eq!(Code::NotFound, code(Error::not_found("route")));
+        assert_eq!(Code::PermissionDenied, code(Error::unauthorized("key")));
+        assert_eq!(Code::InvalidArgument, code(Error::invalid("devaddrs")));
+        assert_eq!(Code::FailedPrecondition, code(Error::conflict("net_id")));
+    }
+}
@@ -0,0 +1,958 @@
+//! Protocol messages and services of the config service that are not part of
+//! helium-proto, with stubs generated by the build script.
+
+use file_store::{impl_msg_verify, traits::MsgVerify};
+use helium_crypto::{PublicKey, Verify};
+use helium_proto::{
+    services::iot_config::{ActionV1, DevaddrConstraintV1, GatewayInfo, OrgV1, RouteV1, SkfV1},
+    Message, Region,
+};
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.Devaddr.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.OrgPayer.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.OrgOwner.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.RegionLimits.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.OrgLock.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.GatewayOnboarding.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.GatewayRegionOverride.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.GatewayChanges.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.Webhooks.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.OrgDevaddrs.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.AuditLog.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.OrgSnapshot.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.RouteBulk.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.SkfStream.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_config.ext.RouteEuis.rs"
+));
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgForDevaddrReqV1 {
+    #[prost(uint32, tag = "1")]
+    pub devaddr: u32,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DevaddrOrgV1 {
+    #[prost(uint32, tag = "1")]
+    pub devaddr: u32,
+    #[prost(uint64, tag = "2")]
+    pub oui: u64,
+    #[prost(uint32, tag = "3")]
+    pub net_id: u32,
+    #[prost(message, optional, tag = "4")]
+    pub constraint: Option<DevaddrConstraintV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgForDevaddrResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub org: Option<DevaddrOrgV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgsForDevaddrsReqV1 {
+    #[prost(uint32, repeated, tag = "1")]
+    pub devaddrs: Vec<u32>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Devaddrs not owned by any org are omitted from `orgs`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgsForDevaddrsResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub orgs: Vec<DevaddrOrgV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Request to change the payer of an org. Both the org owner (`signer`) and
+/// the new payer must sign the request, each over the encoded message with
+/// both `signature` and `payer_signature` cleared.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUpdatePayerReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payer: Vec<u8>,
+    /// Unix timestamp in seconds at which the change takes effect. Zero or a
+    /// time in the past applies the change immediately
+    #[prost(uint64, tag = "3")]
+    pub effective_at: u64,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub payer_signature: Vec<u8>,
+}
+
+impl OrgUpdatePayerReqV1 {
+    /// The bytes signed by both the owner and the new payer
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut msg = self.clone();
+        msg.signature = vec![];
+        msg.payer_signature = vec![];
+        msg.encode_to_vec()
+    }
+
+    /// Verify the approval of the new payer key named in the request
+    pub fn verify_payer(&self) -> file_store::Result {
+        let payer = PublicKey::try_from(self.payer.as_slice())?;
+        payer
+            .verify(&self.signing_bytes(), &self.payer_signature)
+            .map_err(file_store::Error::from)
+    }
+}
+
+impl MsgVerify for OrgUpdatePayerReqV1 {
+    fn verify(&self, verifier: &PublicKey) -> file_store::Result {
+        verifier
+            .verify(&self.signing_bytes(), &self.signature)
+            .map_err(file_store::Error::from)
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgPayerChangeV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub old_payer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub new_payer: Vec<u8>,
+    /// Unix timestamp in seconds at which the change takes effect
+    #[prost(uint64, tag = "4")]
+    pub effective_at: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUpdatePayerResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub change: Option<OrgPayerChangeV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgPayerStreamReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+/// A payer change that has taken effect
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgPayerStreamResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub change: Option<OrgPayerChangeV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Request to rotate the owner key of an org. Signed by the current owner,
+/// or by an administrator to recover an org whose owner key was lost
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUpdateOwnerReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub owner: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUpdateOwnerResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub org: Option<OrgV1>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub old_owner: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegionLimitsReqV1 {
+    #[prost(enumeration = "Region", tag = "1")]
+    pub region: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+/// Admin request setting the limits of a region that has been loaded
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegionUpdateLimitsReqV1 {
+    #[prost(enumeration = "Region", tag = "1")]
+    pub region: i32,
+    /// Max distance in km between a beaconer in the region and its
+    /// witnesses. Zero clears it, leaving the verifier default in place
+    #[prost(uint32, tag = "2")]
+    pub max_witness_distance: u32,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+    /// Seconds from the start of a beacon's entropy within which witnesses of
+    /// a beaconer in the region are accepted. Zero clears it, leaving the
+    /// verifier default in place
+    #[prost(uint32, tag = "6")]
+    pub witness_window: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RegionLimitsResV1 {
+    #[prost(enumeration = "Region", tag = "1")]
+    pub region: i32,
+    /// Max distance in km between a beaconer in the region and its
+    /// witnesses, zero when not set for the region
+    #[prost(uint32, tag = "2")]
+    pub max_witness_distance: u32,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+    /// Seconds from the start of a beacon's entropy within which witnesses of
+    /// a beaconer in the region are accepted, zero when not set for the region
+    #[prost(uint32, tag = "6")]
+    pub witness_window: u32,
+}
+
+/// Admin request clearing the mutation lock taken on an org after repeated
+/// signature failures
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUnlockReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgUnlockResV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    /// Whether the org was locked before the request
+    #[prost(bool, tag = "2")]
+    pub was_locked: bool,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// When a gateway was onboarded on chain and its metadata last updated
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayOnboardedV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    /// Unix timestamp in seconds, zero when not known
+    #[prost(uint64, tag = "2")]
+    pub onboarded_at: u64,
+    /// Unix timestamp in seconds, zero when not known
+    #[prost(uint64, tag = "3")]
+    pub metadata_updated_at: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayOnboardingReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayOnboardingResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub gateway: Option<GatewayOnboardedV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayOnboardingStreamReqV1 {
+    #[prost(uint32, tag = "1")]
+    pub batch_size: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayOnboardingStreamResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub gateways: Vec<GatewayOnboardedV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Region served to a gateway in place of the one its asserted location
+/// resolves to
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayRegionOverrideV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(enumeration = "Region", tag = "2")]
+    pub region: i32,
+    /// Unix timestamp in seconds after which the override no longer applies
+    #[prost(uint64, tag = "3")]
+    pub expires_at: u64,
+    #[prost(string, tag = "4")]
+    pub reason: String,
+}
+
+/// Admin request setting the region override of a gateway, replacing any
+/// previous one
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewaySetRegionOverrideReqV1 {
+    #[prost(message, optional, tag = "1")]
+    pub region_override: Option<GatewayRegionOverrideV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Admin request clearing the region override of a gateway ahead of its
+/// expiry
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayClearRegionOverrideReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub reason: String,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayRegionOverrideResV1 {
+    /// The override set, empty when an override was cleared
+    #[prost(message, optional, tag = "1")]
+    pub region_override: Option<GatewayRegionOverrideV1>,
+    /// Whether the gateway had an unexpired override before the request
+    #[prost(bool, tag = "2")]
+    pub was_overridden: bool,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// A gateway whose metadata was updated from chain
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayChangeV1 {
+    #[prost(message, optional, tag = "1")]
+    pub info: Option<GatewayInfo>,
+    #[prost(message, optional, tag = "2")]
+    pub onboarding: Option<GatewayOnboardedV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayChangesReqV1 {
+    /// Unix timestamp in seconds, gateways updated after it are streamed
+    #[prost(uint64, tag = "1")]
+    pub since: u64,
+    #[prost(uint32, tag = "2")]
+    pub batch_size: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayChangesResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub gateways: Vec<GatewayChangeV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// An endpoint to which an org's change events are pushed. The secret
+/// signing them is never returned
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookV1 {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(uint64, tag = "2")]
+    pub oui: u64,
+    #[prost(string, tag = "3")]
+    pub url: String,
+    /// Events delivered: org, route, eui_pairs, devaddr_ranges or skfs
+    #[prost(string, repeated, tag = "4")]
+    pub events: Vec<String>,
+    /// Unix timestamp in seconds until which deliveries are skipped after
+    /// repeated failures, zero when delivering
+    #[prost(uint64, tag = "5")]
+    pub disabled_until: u64,
+}
+
+/// Request of the owner of an org to push its change events to `url`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookAddReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(string, tag = "2")]
+    pub url: String,
+    /// Key of the hmac-sha256 signing every payload delivered
+    #[prost(string, tag = "3")]
+    pub secret: String,
+    #[prost(string, repeated, tag = "4")]
+    pub events: Vec<String>,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookRemoveReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(string, tag = "2")]
+    pub id: String,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookListReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// The webhook added or removed
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub webhook: Option<WebhookV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WebhookListResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub webhooks: Vec<WebhookV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Admin request allocating an additional devaddr range to a helium org
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgAllocateDevaddrsReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    /// Number of devaddrs in the range, a non-zero even number
+    #[prost(uint64, tag = "2")]
+    pub devaddrs: u64,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Admin request releasing a devaddr range of a helium org no route of the
+/// org uses
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgReleaseDevaddrsReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(message, optional, tag = "2")]
+    pub constraint: Option<DevaddrConstraintV1>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// The org with all of its devaddr ranges after an allocation or release,
+/// along with the range allocated or released
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgDevaddrsResV1 {
+    #[prost(message, optional, tag = "1")]
+    pub org: Option<OrgV1>,
+    #[prost(message, repeated, tag = "2")]
+    pub constraints: Vec<DevaddrConstraintV1>,
+    #[prost(message, optional, tag = "3")]
+    pub changed: Option<DevaddrConstraintV1>,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AuditLogStreamReqV1 {
+    /// Only entries of this org, all entries when 0
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    /// Entries with an id after it are streamed
+    #[prost(int64, tag = "2")]
+    pub after_id: i64,
+    #[prost(uint32, tag = "3")]
+    pub batch_size: u32,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+/// A recorded mutation. `before` and `after` are the json of the state
+/// mutated, empty when there was none
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AuditLogEntryV1 {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, tag = "2")]
+    pub rpc: String,
+    #[prost(uint64, optional, tag = "3")]
+    pub oui: Option<u64>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub request_hash: Vec<u8>,
+    #[prost(string, optional, tag = "6")]
+    pub before: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub after: Option<String>,
+    /// Unix timestamp in microseconds, as hashed
+    #[prost(int64, tag = "8")]
+    pub inserted_at: i64,
+    #[prost(bytes = "vec", tag = "9")]
+    pub prev_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "10")]
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AuditLogStreamResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub entries: Vec<AuditLogEntryV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgSnapshotStreamReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+/// An org with its devaddr constraints and routes, one per org of a snapshot
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrgSnapshotStreamResV1 {
+    /// The same for every org of a snapshot, greater for snapshots taken
+    /// after a change
+    #[prost(uint64, tag = "1")]
+    pub snapshot_version: u64,
+    #[prost(message, optional, tag = "2")]
+    pub org: Option<OrgV1>,
+    /// 0 for orgs without devaddr constraints
+    #[prost(uint32, tag = "3")]
+    pub net_id: u32,
+    #[prost(message, repeated, tag = "4")]
+    pub devaddr_constraints: Vec<DevaddrConstraintV1>,
+    /// Routes of the org, without their euis, devaddr ranges and skfs
+    #[prost(message, repeated, tag = "5")]
+    pub routes: Vec<RouteV1>,
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteExportReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// The routing configuration of an org as a json document of its routes,
+/// each with its eui pairs, devaddr ranges and skfs
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteExportResV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(string, tag = "2")]
+    pub document: String,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Import a document exported by `export`, from this org or another, under
+/// the org. With `replace`, the routes the org has are removed first
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteImportReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(string, tag = "2")]
+    pub document: String,
+    #[prost(bool, tag = "3")]
+    pub replace: bool,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+/// The routes imported, under their new ids, and the routes removed
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteImportResV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub routes: Vec<RouteV1>,
+    #[prost(message, repeated, tag = "3")]
+    pub removed: Vec<RouteV1>,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+/// Request to stream the session key filters of every org
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SkfStreamReqV1 {
+    /// Version of the filters the client holds, the changes after it are
+    /// streamed. Zero, or a version that can't be resumed from, streams a
+    /// snapshot first
+    #[prost(uint64, tag = "1")]
+    pub since_version: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SkfStreamKindV1 {
+    /// Filters of the snapshot, all adds at the version of the message
+    Snapshot = 0,
+    /// The snapshot is complete, filters held that it didn't include are
+    /// to be dropped
+    SnapshotEnd = 1,
+    /// Changes after the last version streamed
+    Delta = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SkfChangeV1 {
+    #[prost(uint64, tag = "1")]
+    pub version: u64,
+    #[prost(enumeration = "ActionV1", tag = "2")]
+    pub action: i32,
+    #[prost(uint64, tag = "3")]
+    pub oui: u64,
+    #[prost(message, optional, tag = "4")]
+    pub skf: Option<SkfV1>,
+}
+
+/// The version of a message is that of its snapshot, or of its last change
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SkfStreamResV1 {
+    #[prost(enumeration = "SkfStreamKindV1", tag = "1")]
+    pub kind: i32,
+    #[prost(uint64, tag = "2")]
+    pub version: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub changes: Vec<SkfChangeV1>,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EuiV1 {
+    #[prost(uint64, tag = "1")]
+    pub app_eui: u64,
+    #[prost(uint64, tag = "2")]
+    pub dev_eui: u64,
+}
+
+/// Request to add, or remove, a batch of the eui pairs of a route in one
+/// transaction, up to 10,000 pairs per request
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteEuisBatchReqV1 {
+    #[prost(string, tag = "1")]
+    pub route_id: String,
+    #[prost(message, repeated, tag = "2")]
+    pub euis: Vec<EuiV1>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteEuisBatchResV1 {
+    #[prost(string, tag = "1")]
+    pub route_id: String,
+    /// Pairs of the batch added, or removed, leaving out those the route
+    /// already had, or didn't have
+    #[prost(uint32, tag = "2")]
+    pub updated: u32,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteEuisListReqV1 {
+    #[prost(string, tag = "1")]
+    pub route_id: String,
+    /// Pairs per streamed message, 1000 when zero
+    #[prost(uint32, tag = "2")]
+    pub batch_size: u32,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RouteEuisListResV1 {
+    #[prost(string, tag = "1")]
+    pub route_id: String,
+    #[prost(message, repeated, tag = "2")]
+    pub euis: Vec<EuiV1>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+impl_msg_verify!(OrgForDevaddrReqV1, signature);
+impl_msg_verify!(OrgForDevaddrResV1, signature);
+impl_msg_verify!(OrgsForDevaddrsReqV1, signature);
+impl_msg_verify!(OrgsForDevaddrsResV1, signature);
+impl_msg_verify!(OrgUpdatePayerResV1, signature);
+impl_msg_verify!(OrgPayerStreamReqV1, signature);
+impl_msg_verify!(OrgPayerStreamResV1, signature);
+impl_msg_verify!(OrgUpdateOwnerReqV1, signature);
+impl_msg_verify!(OrgUpdateOwnerResV1, signature);
+impl_msg_verify!(RegionLimitsReqV1, signature);
+impl_msg_verify!(RegionUpdateLimitsReqV1, signature);
+impl_msg_verify!(RegionLimitsResV1, signature);
+impl_msg_verify!(OrgUnlockReqV1, signature);
+impl_msg_verify!(OrgUnlockResV1, signature);
+impl_msg_verify!(GatewayOnboardingReqV1, signature);
+impl_msg_verify!(GatewayOnboardingResV1, signature);
+impl_msg_verify!(GatewayOnboardingStreamReqV1, signature);
+impl_msg_verify!(GatewayOnboardingStreamResV1, signature);
+impl_msg_verify!(GatewaySetRegionOverrideReqV1, signature);
+impl_msg_verify!(GatewayClearRegionOverrideReqV1, signature);
+impl_msg_verify!(GatewayRegionOverrideResV1, signature);
+impl_msg_verify!(GatewayChangesReqV1, signature);
+impl_msg_verify!(GatewayChangesResV1, signature);
+impl_msg_verify!(WebhookAddReqV1, signature);
+impl_msg_verify!(WebhookRemoveReqV1, signature);
+impl_msg_verify!(WebhookListReqV1, signature);
+impl_msg_verify!(WebhookResV1, signature);
+impl_msg_verify!(WebhookListResV1, signature);
+impl_msg_verify!(OrgAllocateDevaddrsReqV1, signature);
+impl_msg_verify!(OrgReleaseDevaddrsReqV1, signature);
+impl_msg_verify!(OrgDevaddrsResV1, signature);
+impl_msg_verify!(AuditLogStreamReqV1, signature);
+impl_msg_verify!(AuditLogStreamResV1, signature);
+impl_msg_verify!(OrgSnapshotStreamReqV1, signature);
+impl_msg_verify!(OrgSnapshotStreamResV1, signature);
+impl_msg_verify!(RouteExportReqV1, signature);
+impl_msg_verify!(RouteExportResV1, signature);
+impl_msg_verify!(RouteImportReqV1, signature);
+impl_msg_verify!(RouteImportResV1, signature);
+impl_msg_verify!(SkfStreamReqV1, signature);
+impl_msg_verify!(SkfStreamResV1, signature);
+impl_msg_verify!(RouteEuisBatchReqV1, signature);
+impl_msg_verify!(RouteEuisBatchResV1, signature);
+impl_msg_verify!(RouteEuisListReqV1, signature);
+impl_msg_verify!(RouteEuisListResV1, signature);
@@ -1,5 +1,6 @@
 use crate::region_map;
 use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
 use futures::stream::BoxStream;
 use helium_crypto::PublicKeyBinary;
 use helium_proto::{
@@ -11,12 +12,76 @@ use helium_proto::{
 
 pub type GatewayInfoStream = BoxStream<'static, GatewayInfo>;
 
+/// Days a full hotspot is light for after onboarding, witnessing before it
+/// may beacon
+pub const LIGHT_PERIOD_DAYS: i64 = 7;
+
 #[derive(Clone, Debug)]
 pub struct GatewayMetadata {
     pub location: u64,
     pub elevation: i32,
     pub gain: i32,
     pub region: Region,
+    /// When the gateway was onboarded on chain, if known. Not part of the
+    /// helium-proto gateway metadata, clients fill it in from the
+    /// `gateway_onboarding` service, leaving it unknown when that fails
+    pub onboarded_at: Option<DateTime<Utc>>,
+    /// When the metadata was last updated from chain, if known. Like
+    /// `onboarded_at`, clients fill it in from the `gateway_onboarding`
+    /// service, so caches holding the metadata can tell how current it is
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// The chain timestamps of a gateway served by the `gateway_onboarding`
+/// service, which the helium-proto gateway metadata has no fields for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GatewayOnboarding {
+    pub onboarded_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl GatewayOnboarding {
+    pub fn fill(&self, metadata: &mut GatewayMetadata) {
+        metadata.onboarded_at = self.onboarded_at;
+        metadata.updated_at = self.updated_at;
+    }
+}
+
+/// What a gateway may participate in and be rewarded for. Every class earns
+/// data transfer rewards, PoC rewards are limited to the classes below taking
+/// part in PoC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(type_name = "gateway_class", rename_all = "snake_case")]
+pub enum GatewayClass {
+    /// Beacons and witnesses
+    #[default]
+    Full,
+    /// Witnesses, but does not beacon
+    Light,
+    /// Takes no part in PoC
+    DataOnly,
+}
+
+impl GatewayClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Light => "light",
+            Self::DataOnly => "data_only",
+        }
+    }
+
+    pub fn may_beacon(&self) -> bool {
+        matches!(self, Self::Full)
+    }
+
+    pub fn may_witness(&self) -> bool {
+        matches!(self, Self::Full | Self::Light)
+    }
+
+    pub fn earns_poc_rewards(&self) -> bool {
+        self.may_beacon() || self.may_witness()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -27,17 +92,42 @@ pub struct GatewayInfo {
 }
 
 impl GatewayInfo {
+    /// The class of the gateway at `at` by its chain metadata. Hotspots not
+    /// onboarded as full hotspots are data-only, whether or not they asserted
+    /// a location. Full hotspots are light for [`LIGHT_PERIOD_DAYS`] after
+    /// their onboarding and full after, or when it isn't known
+    pub fn class(&self, at: DateTime<Utc>) -> GatewayClass {
+        if !self.is_full_hotspot {
+            return GatewayClass::DataOnly;
+        }
+        match self.metadata.as_ref().and_then(|meta| meta.onboarded_at) {
+            Some(onboarded_at) if at < onboarded_at + Duration::days(LIGHT_PERIOD_DAYS) => {
+                GatewayClass::Light
+            }
+            _ => GatewayClass::Full,
+        }
+    }
+
+    /// Resolve the region of the gateway's asserted location, unless an
+    /// override is in place for it
     pub fn chain_metadata_to_info(
         meta: db::IotMetadata,
         region_map: &region_map::RegionMapReader,
+        region_override: Option<Region>,
     ) -> Self {
         let metadata = if let Some(location) = meta.location {
-            if let Ok(region) = h3index_to_region(location, region_map) {
+            let region = match region_override {
+                Some(region) => Ok(region),
+                None => h3index_to_region(location, region_map),
+            };
+            if let Ok(region) = region {
                 Some(GatewayMetadata {
                     location,
                     elevation: meta.elevation,
                     gain: meta.gain,
                     region,
+                    onboarded_at: meta.onboarded_at,
+                    updated_at: meta.updated_at,
                 })
             } else {
                 tracing::debug!(
@@ -89,6 +179,8 @@ impl From<GatewayInfoProto> for GatewayInfo {
                     elevation: metadata.elevation,
                     gain: metadata.gain,
                     region: metadata.region(),
+                    onboarded_at: None,
+                    updated_at: None,
                 })
                 .ok()
         } else {
@@ -125,6 +217,7 @@ impl TryFrom<GatewayInfo> for GatewayInfoProto {
 }
 
 pub(crate) mod db {
+    use chrono::{DateTime, Utc};
     use futures::stream::{Stream, StreamExt};
     use helium_crypto::PublicKeyBinary;
     use sqlx::{PgExecutor, Row};
@@ -141,24 +234,43 @@ pub(crate) mod db {
         pub elevation: i32,
         pub gain: i32,
         pub is_full_hotspot: bool,
+        pub onboarded_at: Option<DateTime<Utc>>,
+        pub updated_at: Option<DateTime<Utc>>,
     }
 
-    const GET_METADATA_SQL: &str = r#"
-            select kta.entity_key, infos.location::bigint, infos.elevation, infos.gain, infos.is_full_hotspot
+    // While an assertion is being synced a gateway can briefly have more than
+    // one info row with conflicting locations, the row of the latest block
+    // height wins
+    macro_rules! metadata_sql {
+        ($filter:literal) => {
+            concat!(
+                r#"
+            select distinct on (kta.entity_key)
+                kta.entity_key, infos.location::bigint, infos.elevation, infos.gain, infos.is_full_hotspot,
+                infos.created_at, infos.refreshed_at
             from iot_hotspot_infos infos
             join key_to_assets kta on infos.asset = kta.asset
-        "#;
+            "#,
+                $filter,
+                r#"
+            order by kta.entity_key, infos.last_block_height desc nulls last, infos.asset
+            "#
+            )
+        };
+    }
+
+    const GET_METADATA_SQL: &str = metadata_sql!("where kta.entity_key = $1");
+    const ALL_METADATA_SQL: &str = metadata_sql!("");
+    const UPDATED_METADATA_SQL: &str = metadata_sql!("where infos.refreshed_at > $1");
 
     pub async fn get_info(
         db: impl PgExecutor<'_>,
         address: &PublicKeyBinary,
-    ) -> anyhow::Result<Option<IotMetadata>> {
-        let entity_key = bs58::decode(address.to_string()).into_vec()?;
-        let mut query: sqlx::QueryBuilder<sqlx::Postgres> =
-            sqlx::QueryBuilder::new(GET_METADATA_SQL);
-        query.push(" where kta.entity_key = $1 ");
-        Ok(query
-            .build_query_as::<IotMetadata>()
+    ) -> crate::Result<Option<IotMetadata>> {
+        let entity_key = bs58::decode(address.to_string())
+            .into_vec()
+            .map_err(crate::Error::decode)?;
+        Ok(sqlx::query_as::<_, IotMetadata>(GET_METADATA_SQL)
             .bind(entity_key)
             .fetch_optional(db)
             .await?)
@@ -167,12 +279,25 @@ pub(crate) mod db {
     pub fn all_info_stream<'a>(
         db: impl PgExecutor<'a> + 'a,
     ) -> impl Stream<Item = IotMetadata> + 'a {
-        sqlx::query_as::<_, IotMetadata>(GET_METADATA_SQL)
+        sqlx::query_as::<_, IotMetadata>(ALL_METADATA_SQL)
             .fetch(db)
             .filter_map(|metadata| async move { metadata.ok() })
             .boxed()
     }
 
+    /// The gateways whose metadata was updated from chain after `since`.
+    /// Unlike the full stream errors are kept, a change skipped would not be
+    /// polled again
+    pub fn updated_info_stream<'a>(
+        db: impl PgExecutor<'a> + 'a,
+        since: DateTime<Utc>,
+    ) -> impl Stream<Item = Result<IotMetadata, sqlx::Error>> + 'a {
+        sqlx::query_as::<_, IotMetadata>(UPDATED_METADATA_SQL)
+            .bind(since)
+            .fetch(db)
+            .boxed()
+    }
+
     impl sqlx::FromRow<'_, sqlx::postgres::PgRow> for IotMetadata {
         fn from_row(row: &sqlx::postgres::PgRow) -> sqlx::Result<Self> {
             Ok(Self {
@@ -186,7 +311,51 @@ pub(crate) mod db {
                     .unwrap_or(DEFAULT_ELEVATION),
                 gain: row.get::<Option<i32>, &str>("gain").unwrap_or(DEFAULT_GAIN),
                 is_full_hotspot: row.get("is_full_hotspot"),
+                onboarded_at: row.get("created_at"),
+                updated_at: row.get("refreshed_at"),
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn gateway(is_full_hotspot: bool, onboarded_at: Option<DateTime<Utc>>) -> GatewayInfo {
+        GatewayInfo {
+            address: PublicKeyBinary::from_str(
+                "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp",
+            )
+            .unwrap(),
+            metadata: Some(GatewayMetadata {
+                location: 631_711_281_837_647_359,
+                elevation: 0,
+                gain: 12,
+                region: Region::Us915,
+                onboarded_at,
+                updated_at: None,
+            }),
+            is_full_hotspot,
+        }
+    }
+
+    #[test]
+    fn class_by_metadata() {
+        let now = Utc::now();
+        let light_period = Duration::days(LIGHT_PERIOD_DAYS);
+
+        let onboarded = gateway(true, Some(now - light_period));
+        assert_eq!(GatewayClass::Full, onboarded.class(now));
+        assert_eq!(GatewayClass::Full, gateway(true, None).class(now));
+
+        let recent = gateway(true, Some(now - light_period + Duration::seconds(1)));
+        assert_eq!(GatewayClass::Light, recent.class(now));
+        assert_eq!(GatewayClass::Full, recent.class(now + Duration::seconds(1)));
+
+        let data_only = gateway(false, Some(now));
+        assert_eq!(GatewayClass::DataOnly, data_only.class(now));
+        assert_eq!(GatewayClass::DataOnly, gateway(false, None).class(now));
+    }
+}
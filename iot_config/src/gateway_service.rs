@@ -1,12 +1,19 @@
 use crate::{
-    admin::AuthCache,
-    gateway_info::{self, GatewayInfo},
+    admin::{AuthCache, KeyType},
+    ext::{
+        self, GatewayChangeV1, GatewayChangesReqV1, GatewayChangesResV1,
+        GatewayClearRegionOverrideReqV1, GatewayOnboardedV1, GatewayOnboardingReqV1,
+        GatewayOnboardingResV1, GatewayOnboardingStreamReqV1, GatewayOnboardingStreamResV1,
+        GatewayRegionOverrideResV1, GatewayRegionOverrideV1, GatewaySetRegionOverrideReqV1,
+    },
+    gateway_info::{self, GatewayInfo, GatewayOnboarding},
     org,
     region_map::RegionMapReader,
+    region_override::{self, RegionOverride},
     telemetry, verify_public_key, GrpcResult, GrpcStreamResult, Settings,
 };
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, TimeZone, Utc};
 use file_store::traits::{MsgVerify, TimestampEncode};
 use futures::stream::StreamExt;
 use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
@@ -21,16 +28,20 @@ use helium_proto::{
 use hextree::Cell;
 use retainer::Cache;
 use sqlx::{Pool, Postgres};
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::sync::watch;
 use tonic::{Request, Response, Status};
 
 const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60 * 60);
 const CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 3);
+const NOT_FOUND_EVICTION_FREQUENCY: Duration = Duration::from_secs(60);
 
+#[derive(Clone)]
 pub struct GatewayService {
     auth_cache: AuthCache,
     gateway_cache: Arc<Cache<PublicKeyBinary, GatewayInfo>>,
+    not_found_cache: NotFoundCache,
+    pool: Pool<Postgres>,
     metadata_pool: Pool<Postgres>,
     region_map: RegionMapReader,
     signing_key: Arc<Keypair>,
@@ -40,6 +51,7 @@ pub struct GatewayService {
 impl GatewayService {
     pub fn new(
         settings: &Settings,
+        pool: Pool<Postgres>,
         metadata_pool: Pool<Postgres>,
         region_map: RegionMapReader,
         auth_cache: AuthCache,
@@ -48,10 +60,19 @@ impl GatewayService {
         let gateway_cache = Arc::new(Cache::new());
         let cache_clone = gateway_cache.clone();
         tokio::spawn(async move { cache_clone.monitor(4, 0.25, CACHE_EVICTION_FREQUENCY).await });
+        let not_found_cache = NotFoundCache::new(settings.gateway_not_found_ttl());
+        let cache_clone = not_found_cache.cache.clone();
+        tokio::spawn(async move {
+            cache_clone
+                .monitor(4, 0.25, NOT_FOUND_EVICTION_FREQUENCY)
+                .await
+        });
 
         Ok(Self {
             auth_cache,
             gateway_cache,
+            not_found_cache,
+            pool,
             metadata_pool,
             region_map,
             signing_key: Arc::new(settings.signing_keypair()?),
@@ -89,26 +110,62 @@ impl GatewayService {
             .ok_or_else(|| Status::permission_denied("unauthorized request signature"))?
     }
 
+    fn verify_admin_signature<R>(&self, signer: &PublicKey, request: &R) -> Result<(), Status>
+    where
+        R: MsgVerify,
+    {
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, signer, request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))
+    }
+
     async fn resolve_gateway_info(&self, pubkey: &PublicKeyBinary) -> Result<GatewayInfo, Status> {
         match self.gateway_cache.get(pubkey).await {
             Some(gateway) => Ok(gateway.value().clone()),
             None => {
+                if self.not_found_cache.contains(pubkey).await {
+                    telemetry::count_gateway_not_found_hit();
+                    return Err(Status::not_found(format!(
+                        "gateway not found: pubkey = {pubkey:}"
+                    )));
+                }
                 let metadata = tokio::select! {
                     query_result = gateway_info::db::get_info(&self.metadata_pool, pubkey) => {
-                        query_result.map_err(|_| Status::internal("error fetching gateway info"))?
-                            .ok_or_else(|| {
+                        let metadata = query_result
+                            .map_err(|_| Status::internal("error fetching gateway info"))?;
+                        match metadata {
+                            Some(metadata) => metadata,
+                            None => {
                                 telemetry::count_gateway_info_lookup("not-found");
-                                Status::not_found(format!("gateway not found: pubkey = {pubkey:}"))
-                            })?
+                                self.not_found_cache.insert(pubkey).await;
+                                return Err(Status::not_found(format!(
+                                    "gateway not found: pubkey = {pubkey:}"
+                                )));
+                            }
+                        }
                     }
                     _ = tokio::time::sleep(tokio::time::Duration::from_secs(3)) => {
                         tracing::warn!("gateway info request query timed out");
                         return Err(Status::deadline_exceeded("query timed out requesting info"))
                     }
                 };
-                let gateway = GatewayInfo::chain_metadata_to_info(metadata, &self.region_map);
+                let region_override = region_override::get_active(pubkey, &self.pool)
+                    .await
+                    .map_err(|_| Status::internal("error fetching region override"))?;
+                // an override mustn't outlive its expiry in the cache
+                let cache_ttl = region_override
+                    .as_ref()
+                    .and_then(|region_override| {
+                        (region_override.expires_at - Utc::now()).to_std().ok()
+                    })
+                    .map_or(CACHE_TTL, |until_expiry| until_expiry.min(CACHE_TTL));
+                let gateway = GatewayInfo::chain_metadata_to_info(
+                    metadata,
+                    &self.region_map,
+                    region_override.map(|region_override| region_override.region),
+                );
                 self.gateway_cache
-                    .insert(pubkey.clone(), gateway.clone(), CACHE_TTL)
+                    .insert(pubkey.clone(), gateway.clone(), cache_ttl)
                     .await;
                 if gateway.metadata.is_some() {
                     telemetry::count_gateway_info_lookup("asserted");
@@ -121,6 +178,38 @@ impl GatewayService {
     }
 }
 
+/// Gateways recently not found in the metadata db, remembered for the
+/// configured ttl unless it is disabled
+#[derive(Clone)]
+struct NotFoundCache {
+    cache: Arc<Cache<PublicKeyBinary, ()>>,
+    ttl: Option<Duration>,
+}
+
+impl NotFoundCache {
+    fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            cache: Arc::new(Cache::new()),
+            ttl,
+        }
+    }
+
+    async fn contains(&self, pubkey: &PublicKeyBinary) -> bool {
+        self.cache.get(pubkey).await.is_some()
+    }
+
+    async fn insert(&self, pubkey: &PublicKeyBinary) {
+        if let Some(ttl) = self.ttl {
+            self.cache.insert(pubkey.clone(), (), ttl).await;
+        }
+    }
+
+    /// Forget a gateway that was onboarded or updated since its lookup
+    async fn remove(&self, pubkey: &PublicKeyBinary) {
+        self.cache.remove(pubkey).await;
+    }
+}
+
 #[tonic::async_trait]
 impl iot_config::Gateway for GatewayService {
     async fn location(
@@ -271,6 +360,9 @@ impl iot_config::Gateway for GatewayService {
 
         tracing::debug!("fetching all gateways' info");
 
+        let region_overrides = region_override::all_active(&self.pool)
+            .await
+            .map_err(|_| Status::internal("error fetching region overrides"))?;
         let pool = self.metadata_pool.clone();
         let signing_key = self.signing_key.clone();
         let batch_size = request.batch_size;
@@ -284,6 +376,7 @@ impl iot_config::Gateway for GatewayService {
                 tx.clone(),
                 &signing_key,
                 region_map.clone(),
+                region_overrides,
                 batch_size,
             )
             .await
@@ -293,11 +386,319 @@ impl iot_config::Gateway for GatewayService {
     }
 }
 
+#[tonic::async_trait]
+impl ext::gateway_onboarding_server::GatewayOnboarding for GatewayService {
+    async fn onboarding(
+        &self,
+        request: Request<GatewayOnboardingReqV1>,
+    ) -> GrpcResult<GatewayOnboardingResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-onboarding", "onboarding");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        let address: PublicKeyBinary = request.address.into();
+        let gateway_info = self.resolve_gateway_info(&address).await?;
+        let onboarding = gateway_info
+            .metadata
+            .map(|metadata| GatewayOnboarding {
+                onboarded_at: metadata.onboarded_at,
+                updated_at: metadata.updated_at,
+            })
+            .unwrap_or_default();
+
+        let mut resp = GatewayOnboardingResV1 {
+            gateway: Some(gateway_onboarded(address, onboarding)),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+
+    type onboarding_streamStream = GrpcStreamResult<GatewayOnboardingStreamResV1>;
+    async fn onboarding_stream(
+        &self,
+        request: Request<GatewayOnboardingStreamReqV1>,
+    ) -> GrpcResult<Self::onboarding_streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-onboarding", "onboarding-stream");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        tracing::debug!("fetching all gateways' onboarding");
+
+        let pool = self.metadata_pool.clone();
+        let signing_key = self.signing_key.clone();
+        let batch_size = request.batch_size;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(20);
+
+        tokio::spawn(async move {
+            stream_all_gateways_onboarding(&pool, tx, &signing_key, batch_size).await
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::gateway_region_override_server::GatewayRegionOverride for GatewayService {
+    async fn set_override(
+        &self,
+        request: Request<GatewaySetRegionOverrideReqV1>,
+    ) -> GrpcResult<GatewayRegionOverrideResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-region-override", "set-override");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_admin_signature(&signer, &request)?;
+
+        let region_override = request
+            .region_override
+            .ok_or_else(|| Status::invalid_argument("missing region override"))
+            .and_then(|proto| RegionOverride::try_from_proto(proto, Utc::now()))?;
+
+        let was_overridden = region_override::get_active(&region_override.address, &self.pool)
+            .await
+            .map_err(|_| Status::internal("error fetching region override"))?
+            .is_some();
+        region_override::set(&region_override, &signer, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(reason = ?err, "setting region override failed");
+                Status::internal("setting region override failed")
+            })?;
+        self.gateway_cache.remove(&region_override.address).await;
+        tracing::info!(
+            pubkey = %region_override.address,
+            region = %region_override.region,
+            expires_at = %region_override.expires_at,
+            reason = %region_override.reason,
+            signer = signer.to_string(),
+            "gateway region override set"
+        );
+
+        self.region_override_response(Some(region_override), was_overridden)
+    }
+
+    async fn clear_override(
+        &self,
+        request: Request<GatewayClearRegionOverrideReqV1>,
+    ) -> GrpcResult<GatewayRegionOverrideResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-region-override", "clear-override");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_admin_signature(&signer, &request)?;
+        if request.reason.is_empty() {
+            return Err(Status::invalid_argument("a reason is required"));
+        }
+
+        let address: PublicKeyBinary = request.address.into();
+        let was_overridden = region_override::clear(&address, &request.reason, &signer, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(reason = ?err, "clearing region override failed");
+                Status::internal("clearing region override failed")
+            })?;
+        self.gateway_cache.remove(&address).await;
+        tracing::info!(
+            pubkey = %address,
+            was_overridden,
+            reason = %request.reason,
+            signer = signer.to_string(),
+            "gateway region override cleared"
+        );
+
+        self.region_override_response(None, was_overridden)
+    }
+}
+
+impl GatewayService {
+    fn region_override_response(
+        &self,
+        region_override: Option<RegionOverride>,
+        was_overridden: bool,
+    ) -> GrpcResult<GatewayRegionOverrideResV1> {
+        let mut resp = GatewayRegionOverrideResV1 {
+            region_override: region_override.map(|region_override| GatewayRegionOverrideV1 {
+                address: region_override.address.into(),
+                region: region_override.region.into(),
+                expires_at: region_override.expires_at.timestamp() as u64,
+                reason: region_override.reason,
+            }),
+            was_overridden,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
+
+fn gateway_onboarded(
+    address: PublicKeyBinary,
+    onboarding: GatewayOnboarding,
+) -> GatewayOnboardedV1 {
+    let encode = |timestamp: Option<DateTime<Utc>>| {
+        timestamp.map_or(0, |timestamp| timestamp.timestamp() as u64)
+    };
+    GatewayOnboardedV1 {
+        address: address.into(),
+        onboarded_at: encode(onboarding.onboarded_at),
+        metadata_updated_at: encode(onboarding.updated_at),
+    }
+}
+
+async fn stream_all_gateways_onboarding(
+    pool: &Pool<Postgres>,
+    tx: tokio::sync::mpsc::Sender<Result<GatewayOnboardingStreamResV1, Status>>,
+    signing_key: &Keypair,
+    batch_size: u32,
+) -> anyhow::Result<()> {
+    let timestamp = Utc::now().encode_timestamp();
+    let signer: Vec<u8> = signing_key.public_key().into();
+    let mut stream = gateway_info::db::all_info_stream(pool).chunks(batch_size as usize);
+    while let Some(infos) = stream.next().await {
+        let mut response = GatewayOnboardingStreamResV1 {
+            gateways: infos
+                .into_iter()
+                .map(|info| {
+                    let onboarding = GatewayOnboarding {
+                        onboarded_at: info.onboarded_at,
+                        updated_at: info.updated_at,
+                    };
+                    gateway_onboarded(info.address, onboarding)
+                })
+                .collect(),
+            timestamp,
+            signer: signer.clone(),
+            signature: vec![],
+        };
+        let Ok(signature) = signing_key.sign(&response.encode_to_vec()) else {
+            continue;
+        };
+        response.signature = signature;
+
+        tx.send(Ok(response)).await?;
+    }
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl ext::gateway_changes_server::GatewayChanges for GatewayService {
+    type changesStream = GrpcStreamResult<GatewayChangesResV1>;
+    async fn changes(
+        &self,
+        request: Request<GatewayChangesReqV1>,
+    ) -> GrpcResult<Self::changesStream> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-changes", "changes");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        let since = Utc
+            .timestamp_opt(request.since as i64, 0)
+            .single()
+            .ok_or_else(|| Status::invalid_argument("invalid since timestamp"))?;
+        tracing::debug!(%since, "fetching gateways updated since");
+
+        let region_overrides = region_override::all_active(&self.pool)
+            .await
+            .map_err(|_| Status::internal("error fetching region overrides"))?;
+        let service = self.clone();
+        let batch_size = request.batch_size;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(20);
+
+        tokio::spawn(async move {
+            service
+                .stream_gateway_changes(tx, since, region_overrides, batch_size)
+                .await
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+impl GatewayService {
+    /// Stream the gateways updated from chain after `since`, evicting them
+    /// from the gateway cache so the `gateway` apis don't serve their info
+    /// from before the update either
+    async fn stream_gateway_changes(
+        &self,
+        tx: tokio::sync::mpsc::Sender<Result<GatewayChangesResV1, Status>>,
+        since: DateTime<Utc>,
+        region_overrides: HashMap<PublicKeyBinary, Region>,
+        batch_size: u32,
+    ) -> anyhow::Result<()> {
+        let timestamp = Utc::now().encode_timestamp();
+        let signer: Vec<u8> = self.signing_key.public_key().into();
+        let mut stream = gateway_info::db::updated_info_stream(&self.metadata_pool, since)
+            .chunks(batch_size as usize);
+        while let Some(infos) = stream.next().await {
+            let infos = match infos.into_iter().collect::<Result<Vec<_>, _>>() {
+                Ok(infos) => infos,
+                Err(err) => {
+                    tracing::error!(?err, "failed to fetch gateway changes");
+                    tx.send(Err(Status::internal("error fetching gateway changes")))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let mut gateways = Vec::with_capacity(infos.len());
+            for info in infos {
+                self.gateway_cache.remove(&info.address).await;
+                self.not_found_cache.remove(&info.address).await;
+                let onboarding = GatewayOnboarding {
+                    onboarded_at: info.onboarded_at,
+                    updated_at: info.updated_at,
+                };
+                let address = info.address.clone();
+                let region_override = region_overrides.get(&address).copied();
+                let Ok(gateway_info) =
+                    GatewayInfo::chain_metadata_to_info(info, &self.region_map, region_override)
+                        .try_into()
+                else {
+                    continue;
+                };
+                gateways.push(GatewayChangeV1 {
+                    info: Some(gateway_info),
+                    onboarding: Some(gateway_onboarded(address, onboarding)),
+                });
+            }
+            let mut response = GatewayChangesResV1 {
+                gateways,
+                timestamp,
+                signer: signer.clone(),
+                signature: vec![],
+            };
+            let Ok(signature) = self.signing_key.sign(&response.encode_to_vec()) else {
+                tx.send(Err(Status::internal("error signing gateway changes")))
+                    .await?;
+                return Ok(());
+            };
+            response.signature = signature;
+
+            tx.send(Ok(response)).await?;
+        }
+        Ok(())
+    }
+}
+
 async fn stream_all_gateways_info(
     pool: &Pool<Postgres>,
     tx: tokio::sync::mpsc::Sender<Result<GatewayInfoStreamResV1, Status>>,
     signing_key: &Keypair,
     region_map: RegionMapReader,
+    region_overrides: HashMap<PublicKeyBinary, Region>,
     batch_size: u32,
 ) -> anyhow::Result<()> {
     let timestamp = Utc::now().encode_timestamp();
@@ -307,7 +708,8 @@ async fn stream_all_gateways_info(
         let gateway_infos = infos
             .into_iter()
             .filter_map(|info| {
-                GatewayInfo::chain_metadata_to_info(info, &region_map)
+                let region_override = region_overrides.get(&info.address).copied();
+                GatewayInfo::chain_metadata_to_info(info, &region_map, region_override)
                     .try_into()
                     .ok()
             })
@@ -334,3 +736,39 @@ async fn stream_all_gateways_info(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn not_found_gateways_are_remembered_for_the_ttl() {
+        let gateway = PublicKeyBinary::from(vec![1]);
+        let cache = NotFoundCache::new(Some(Duration::from_millis(50)));
+        assert!(!cache.contains(&gateway).await);
+
+        cache.insert(&gateway).await;
+        assert!(cache.contains(&gateway).await);
+        assert!(!cache.contains(&PublicKeyBinary::from(vec![2])).await);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!cache.contains(&gateway).await);
+    }
+
+    #[tokio::test]
+    async fn updated_gateways_are_forgotten() {
+        let gateway = PublicKeyBinary::from(vec![1]);
+        let cache = NotFoundCache::new(Some(Duration::from_secs(60)));
+        cache.insert(&gateway).await;
+        cache.remove(&gateway).await;
+        assert!(!cache.contains(&gateway).await);
+    }
+
+    #[tokio::test]
+    async fn a_zero_ttl_disables_the_cache() {
+        let gateway = PublicKeyBinary::from(vec![1]);
+        let cache = NotFoundCache::new(None);
+        cache.insert(&gateway).await;
+        assert!(!cache.contains(&gateway).await);
+    }
+}
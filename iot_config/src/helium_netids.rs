@@ -52,6 +52,11 @@ impl TryFrom<NetIdField> for HeliumNetId {
 pub trait AddressStore {
     type Error;
 
+    /// Serialize allocations of `net_id` until the end of the current
+    /// transaction, so no two allocations can pick the same free addrs
+    async fn lock_addrs(&mut self, _net_id: HeliumNetId) -> Result<(), Self::Error> {
+        Ok(())
+    }
     async fn get_used_addrs(&mut self, net_id: HeliumNetId) -> Result<Vec<u32>, Self::Error>;
     async fn claim_addrs(
         &mut self,
@@ -69,9 +74,23 @@ pub trait AddressStore {
 impl AddressStore for sqlx::Transaction<'_, sqlx::Postgres> {
     type Error = sqlx::Error;
 
+    async fn lock_addrs(&mut self, net_id: HeliumNetId) -> Result<(), Self::Error> {
+        // locking the used rows alone can't keep a concurrent allocation out
+        // of the gaps between them, so take a lock on the whole net id
+        sqlx::query(" select pg_advisory_xact_lock($1) ")
+            .bind(i64::from(i32::from(net_id.id())))
+            .execute(self)
+            .await
+            .map(|_| ())
+    }
+
     async fn get_used_addrs(&mut self, net_id: HeliumNetId) -> Result<Vec<u32>, Self::Error> {
         Ok(sqlx::query_scalar::<_, i32>(
-            " select devaddr from helium_used_devaddrs where net_id = $1 order by devaddr asc ",
+            r#"
+            select devaddr from helium_used_devaddrs where net_id = $1
+            order by devaddr asc
+            for update
+            "#,
         )
         .bind(i32::from(net_id.id()))
         .fetch_all(self)
@@ -129,6 +148,10 @@ where
     S: AddressStore,
 {
     let addr_range = net_id.addr_range();
+    addr_store
+        .lock_addrs(net_id)
+        .await
+        .map_err(DevAddrConstraintsError::AddressStore)?;
     let used_addrs = addr_store
         .get_used_addrs(net_id)
         .await
@@ -169,6 +192,73 @@ where
     Ok(new_constraints)
 }
 
+/// Allocate a single contiguous block of `size` addrs of `net_id`.
+///
+/// The block is taken from the smallest gap left between used addrs which
+/// fits it, so blocks released by deleted orgs are reused without breaking up
+/// larger gaps, and is appended after the last used addr when no gap fits.
+/// When `addr_store` is a transaction the net id stays locked until it ends.
+pub async fn allocate_helium_devaddr_block<S>(
+    addr_store: &mut S,
+    size: u64,
+    net_id: HeliumNetId,
+) -> Result<DevAddrConstraint, DevAddrConstraintsError<S::Error>>
+where
+    S: AddressStore,
+{
+    if size == 0 || size % 2 != 0 {
+        return Err(DevAddrConstraintsError::InvalidBlockSize(size));
+    }
+    addr_store
+        .lock_addrs(net_id)
+        .await
+        .map_err(DevAddrConstraintsError::AddressStore)?;
+    let mut used_addrs = addr_store
+        .get_used_addrs(net_id)
+        .await
+        .map_err(DevAddrConstraintsError::AddressStore)?;
+    used_addrs.sort_unstable();
+    used_addrs.dedup();
+
+    let start_addr = smallest_fitting_gap(net_id.addr_range(), &used_addrs, size)
+        .ok_or(DevAddrConstraintsError::NoAvailableAddrs)?;
+    let end_addr = start_addr + size as u32 - 1;
+    let claimed_addrs = (start_addr..=end_addr).collect::<Vec<u32>>();
+    addr_store
+        .claim_addrs(net_id, &claimed_addrs)
+        .await
+        .map_err(DevAddrConstraintsError::AddressStore)?;
+
+    DevAddrConstraint::new(start_addr.into(), end_addr.into())
+        .map_err(|err| DevAddrConstraintsError::InvalidConstraint(err.into()))
+}
+
+/// Start of a block of `size` addrs within the smallest gap of `addr_range`
+/// not covered by the sorted `used_addrs`. Blocks start on an even addr.
+fn smallest_fitting_gap(
+    addr_range: RangeInclusive<u32>,
+    used_addrs: &[u32],
+    size: u64,
+) -> Option<u32> {
+    let range_end = *addr_range.end() as u64;
+    let mut gap_start = *addr_range.start() as u64;
+    let mut best: Option<(u64, u64)> = None;
+    let gap_ends = used_addrs
+        .iter()
+        .filter(|&&addr| addr_range.contains(&addr))
+        .map(|&addr| (addr as u64, addr as u64 + 1))
+        .chain(std::iter::once((range_end + 1, range_end + 1)));
+    for (gap_end, next_start) in gap_ends {
+        let block_start = gap_start + gap_start % 2;
+        let gap_len = gap_end.saturating_sub(gap_start);
+        if block_start + size <= gap_end && best.map_or(true, |(len, _)| gap_len < len) {
+            best = Some((gap_len, block_start));
+        }
+        gap_start = next_start;
+    }
+    best.map(|(_, block_start)| block_start as u32)
+}
+
 pub async fn checkout_specified_devaddr_constraint<S>(
     addr_store: &mut S,
     net_id: HeliumNetId,
@@ -177,6 +267,10 @@ pub async fn checkout_specified_devaddr_constraint<S>(
 where
     S: AddressStore,
 {
+    addr_store
+        .lock_addrs(net_id)
+        .await
+        .map_err(DevAddrConstraintsError::AddressStore)?;
     let used_addrs = addr_store
         .get_used_addrs(net_id)
         .await
@@ -205,6 +299,8 @@ pub enum DevAddrConstraintsError<AS> {
     InvalidConstraint(#[from] ConstraintsBuildError),
     #[error("Requested constraint in use {0}")]
     ConstraintAddrInUse(String),
+    #[error("Invalid block size {0}; must be a non-zero even number")]
+    InvalidBlockSize(u64),
 }
 
 fn constraints_from_addrs(
@@ -433,6 +529,70 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn allocate_block_from_smallest_fitting_gap() {
+        let mut addr_store = HashMap::new();
+        let range_start = *HeliumNetId::Type0_0x00003c.addr_range().start();
+        // used: 0..8, 40..48, 56..64; free gaps: 8..40 (32) and 48..56 (8)
+        addr_store.insert(
+            HeliumNetId::Type0_0x00003c.id(),
+            (0..8)
+                .chain(40..48)
+                .chain(56..64)
+                .map(|offset| range_start + offset)
+                .collect::<Vec<u32>>(),
+        );
+        let block = allocate_helium_devaddr_block(&mut addr_store, 8, HeliumNetId::Type0_0x00003c)
+            .await
+            .expect("block allocated");
+        assert_eq!(
+            block,
+            DevAddrConstraint::new((range_start + 48).into(), (range_start + 55).into())
+                .expect("expected constraint")
+        );
+        let block = allocate_helium_devaddr_block(&mut addr_store, 16, HeliumNetId::Type0_0x00003c)
+            .await
+            .expect("block allocated");
+        assert_eq!(
+            block,
+            DevAddrConstraint::new((range_start + 8).into(), (range_start + 23).into())
+                .expect("expected constraint")
+        );
+    }
+
+    #[tokio::test]
+    async fn allocate_block_appends_when_no_gap_fits() {
+        let mut addr_store = HashMap::new();
+        let range_start = *HeliumNetId::Type0_0x00003c.addr_range().start();
+        addr_store.insert(
+            HeliumNetId::Type0_0x00003c.id(),
+            (0..8)
+                .chain(16..24)
+                .map(|offset| range_start + offset)
+                .collect::<Vec<u32>>(),
+        );
+        let block = allocate_helium_devaddr_block(&mut addr_store, 16, HeliumNetId::Type0_0x00003c)
+            .await
+            .expect("block allocated");
+        assert_eq!(
+            block,
+            DevAddrConstraint::new((range_start + 24).into(), (range_start + 39).into())
+                .expect("expected constraint")
+        );
+        assert!(matches!(
+            allocate_helium_devaddr_block(&mut addr_store, 7, HeliumNetId::Type0_0x00003c).await,
+            Err(DevAddrConstraintsError::InvalidBlockSize(7))
+        ));
+        addr_store.insert(
+            HeliumNetId::Type6_0xc00053.id(),
+            (4227943424..4227944440).collect::<Vec<_>>(),
+        );
+        assert!(matches!(
+            allocate_helium_devaddr_block(&mut addr_store, 16, HeliumNetId::Type6_0xc00053).await,
+            Err(DevAddrConstraintsError::NoAvailableAddrs)
+        ));
+    }
+
     #[tokio::test]
     async fn allocate_across_net_id() {
         let mut addr_store = HashMap::new();
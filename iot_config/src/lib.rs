@@ -1,32 +1,68 @@
 pub mod admin;
 pub mod admin_service;
+pub mod admin_ui;
+pub mod audit;
+pub mod audit_service;
 pub mod client;
+pub mod devaddr_service;
+mod error;
+pub mod ext;
 pub mod gateway_info;
 pub mod gateway_service;
 mod helium_netids;
 pub mod lora_field;
 pub mod org;
+pub mod org_devaddrs_service;
+pub mod org_lock_service;
+pub mod org_owner_service;
+pub mod org_payer_service;
 pub mod org_service;
+pub mod org_snapshot_service;
+pub mod proxy;
+pub mod region_limits_service;
 pub mod region_map;
+pub mod region_override;
 pub mod route;
+pub mod route_bulk;
+pub mod route_bulk_service;
+pub mod route_euis_service;
 pub mod route_service;
 pub mod settings;
+pub mod signature_guard;
+pub mod skf_stream_service;
+pub mod skf_versions;
 pub mod telemetry;
+#[cfg(test)]
+mod test_support;
+pub mod verify_requests;
+pub mod webhook_service;
+pub mod webhooks;
 
 pub use admin_service::AdminService;
+pub use audit_service::AuditLogService;
 pub use client::{Client, Settings as ClientSettings};
+pub use devaddr_service::DevaddrService;
+pub use error::{Error, Result};
 pub use gateway_service::GatewayService;
+pub use org_devaddrs_service::OrgDevaddrsService;
+pub use org_lock_service::OrgLockService;
+pub use org_owner_service::OrgOwnerService;
+pub use org_payer_service::OrgPayerService;
 pub use org_service::OrgService;
+pub use org_snapshot_service::OrgSnapshotService;
+pub use region_limits_service::RegionLimitsService;
+pub use route_bulk_service::RouteBulkService;
+pub use route_euis_service::RouteEuisService;
 pub use route_service::RouteService;
 pub use settings::Settings;
+pub use skf_stream_service::SkfStreamService;
+pub use webhook_service::WebhookService;
 
 use helium_crypto::PublicKey;
 use tokio::sync::broadcast;
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
+use tonic::Status;
 
-pub type GrpcResult<T> = Result<Response<T>, Status>;
-pub type GrpcStreamResult<T> = ReceiverStream<Result<T, Status>>;
+pub use file_store::grpc::{GrpcResult, GrpcStreamResult};
 pub type GrpcStreamRequest<T> = tonic::Request<tonic::Streaming<T>>;
 
 pub const BROADCAST_CHANNEL_QUEUE: usize = 1024;
@@ -3,11 +3,46 @@ use clap::Parser;
 use futures_util::TryFutureExt;
 use helium_proto::services::iot_config::{AdminServer, GatewayServer, OrgServer, RouteServer};
 use iot_config::{
-    admin::AuthCache, admin_service::AdminService, gateway_service::GatewayService, org,
-    org_service::OrgService, region_map::RegionMapReader, route_service::RouteService,
-    settings::Settings, telemetry,
+    admin::{AuthCache, AuthCacheRefresher, AuthCacheUpdater},
+    admin_service::AdminService,
+    admin_ui,
+    audit_service::AuditLogService,
+    devaddr_service::DevaddrService,
+    ext::{
+        audit_log_server::AuditLogServer, devaddr_server::DevaddrServer,
+        gateway_changes_server::GatewayChangesServer,
+        gateway_onboarding_server::GatewayOnboardingServer,
+        gateway_region_override_server::GatewayRegionOverrideServer,
+        org_devaddrs_server::OrgDevaddrsServer, org_lock_server::OrgLockServer,
+        org_owner_server::OrgOwnerServer, org_payer_server::OrgPayerServer,
+        org_snapshot_server::OrgSnapshotServer, region_limits_server::RegionLimitsServer,
+        route_bulk_server::RouteBulkServer, route_euis_server::RouteEuisServer,
+        skf_stream_server::SkfStreamServer, webhooks_server::WebhooksServer,
+    },
+    gateway_service::GatewayService,
+    org,
+    org_devaddrs_service::OrgDevaddrsService,
+    org_lock_service::OrgLockService,
+    org_owner_service::OrgOwnerService,
+    org_payer_service::OrgPayerService,
+    org_service::OrgService,
+    org_snapshot_service::OrgSnapshotService,
+    proxy::{self, GatewayProxy},
+    region_limits_service::RegionLimitsService,
+    region_map::RegionMapReader,
+    route_bulk,
+    route_bulk_service::RouteBulkService,
+    route_euis_service::RouteEuisService,
+    route_service::RouteService,
+    settings::Settings,
+    signature_guard::SignatureGuard,
+    skf_stream_service::SkfStreamService,
+    skf_versions, telemetry, update_channel, verify_requests,
+    webhook_service::WebhookService,
+    webhooks,
 };
-use std::{path::PathBuf, time::Duration};
+use poc_metrics::preflight::Preflight;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio::signal;
 use tonic::transport;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -28,24 +63,70 @@ pub struct Cli {
 
 impl Cli {
     pub async fn run(self) -> Result<()> {
-        let settings = Settings::new(self.config)?;
-        self.cmd.run(settings).await
+        self.cmd.run(self.config).await
     }
 }
 
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Daemon),
+    VerifyRequests(VerifyRequests),
+    Routes(Routes),
+    Proxy(Proxy),
 }
 
 impl Cmd {
-    pub async fn run(&self, settings: Settings) -> Result<()> {
+    pub async fn run(&self, config: Option<PathBuf>) -> Result<()> {
         match self {
-            Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Server(cmd) => cmd.run(&Settings::new(config)?).await,
+            Self::VerifyRequests(cmd) => cmd.run(&Settings::new(config)?).await,
+            Self::Routes(cmd) => cmd.run(&Settings::new(config)?).await,
+            Self::Proxy(cmd) => cmd.run(&proxy::Settings::new(config)?).await,
         }
     }
 }
 
+/// Check a file of captured signed requests against the current key registry,
+/// without touching any state
+#[derive(Debug, clap::Args)]
+pub struct VerifyRequests {
+    #[clap(flatten)]
+    cmd: verify_requests::Cmd,
+}
+
+impl VerifyRequests {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect("iot-config-store", shutdown)
+            .await?;
+        let (_auth_updater, auth_cache) = AuthCache::new(settings, &pool).await?;
+        self.cmd
+            .run(&pool, &auth_cache, settings.signature_guard.lock)
+            .await
+    }
+}
+
+/// Export or import the complete routing configuration of an org against the
+/// database
+#[derive(Debug, clap::Args)]
+pub struct Routes {
+    #[clap(subcommand)]
+    cmd: route_bulk::Cmd,
+}
+
+impl Routes {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect("iot-config-store", shutdown)
+            .await?;
+        self.cmd.run(&pool).await
+    }
+}
+
 #[derive(Debug, clap::Args)]
 pub struct Daemon;
 
@@ -75,7 +156,6 @@ impl Daemon {
             .database
             .connect("iot-config-store", shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
 
         // Create on-chain metadata pool
         let (metadata_pool, md_pool_handle) = settings
@@ -83,32 +163,116 @@ impl Daemon {
             .connect("iot-config-metadata", shutdown_listener.clone())
             .await?;
 
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check(
+                "metadata database",
+                db_store::preflight::check_connection(&metadata_pool),
+            )
+            .await;
+        preflight.check_result("signing keypair", settings.signing_keypair());
+        preflight.finish()?;
+
+        sqlx::migrate!().run(&pool).await?;
+
         let listen_addr = settings.listen_addr()?;
 
         let (auth_updater, auth_cache) = AuthCache::new(settings, &pool).await?;
+        let auth_updater = Arc::new(AuthCacheUpdater::new(auth_updater));
+        let auth_cache_refresher =
+            AuthCacheRefresher::new(settings, pool.clone(), auth_updater.clone())?;
         let (region_updater, region_map) = RegionMapReader::new(&pool).await?;
         let (delegate_key_updater, delegate_key_cache) = org::delegate_keys_cache(&pool).await?;
+        let signature_guard = SignatureGuard::new(&settings.signature_guard, pool.clone())?;
+        if settings.metrics.ui {
+            admin_ui::register(pool.clone(), auth_cache.clone());
+        }
 
         let gateway_svc = GatewayService::new(
             settings,
+            pool.clone(),
             metadata_pool,
             region_map.clone(),
             auth_cache.clone(),
             delegate_key_cache,
         )?;
+        let gateway_onboarding_svc = gateway_svc.clone();
+        let gateway_region_override_svc = gateway_svc.clone();
+        let gateway_changes_svc = gateway_svc.clone();
         let route_svc = RouteService::new(
             settings,
             auth_cache.clone(),
             pool.clone(),
             shutdown_listener.clone(),
+            signature_guard.clone(),
+        )?;
+        let org_update_channel = update_channel();
+        let webhook_dispatcher = webhooks::Dispatcher::new(
+            &settings.webhooks,
+            pool.clone(),
+            route_svc.clone_update_channel().subscribe(),
+            org_update_channel.subscribe(),
         )?;
         let org_svc = OrgService::new(
             settings,
             auth_cache.clone(),
             pool.clone(),
             route_svc.clone_update_channel(),
+            org_update_channel,
             delegate_key_updater,
+            signature_guard.clone(),
+        )?;
+        let devaddr_svc = DevaddrService::new(settings, auth_cache.clone(), pool.clone())?;
+        let org_payer_svc = OrgPayerService::new(
+            settings,
+            auth_cache.clone(),
+            pool.clone(),
+            shutdown_listener.clone(),
+            signature_guard.clone(),
+        )?;
+        let payer_change_scheduler = org_payer_svc.scheduler();
+        let org_owner_svc = OrgOwnerService::new(
+            settings,
+            auth_cache.clone(),
+            pool.clone(),
+            signature_guard.clone(),
+        )?;
+        let route_bulk_svc = RouteBulkService::new(
+            settings,
+            auth_cache.clone(),
+            pool.clone(),
+            route_svc.clone_update_channel(),
+            signature_guard.clone(),
+        )?;
+        let route_euis_svc = RouteEuisService::new(
+            settings,
+            auth_cache.clone(),
+            pool.clone(),
+            route_svc.clone_update_channel(),
+            signature_guard.clone(),
         )?;
+        let webhook_svc =
+            WebhookService::new(settings, auth_cache.clone(), pool.clone(), signature_guard)?;
+        let org_lock_svc = OrgLockService::new(settings, auth_cache.clone(), pool.clone())?;
+        let org_devaddrs_svc = OrgDevaddrsService::new(settings, auth_cache.clone(), pool.clone())?;
+        let audit_log_svc = AuditLogService::new(settings, auth_cache.clone(), pool.clone())?;
+        let org_snapshot_svc = OrgSnapshotService::new(settings, auth_cache.clone(), pool.clone())?;
+        let region_limits_svc =
+            RegionLimitsService::new(settings, auth_cache.clone(), pool.clone())?;
+        let skf_stream_svc = SkfStreamService::new(
+            settings,
+            auth_cache.clone(),
+            pool.clone(),
+            shutdown_listener.clone(),
+        )?;
+        let skf_pruner = skf_versions::Pruner::new(&settings.skf_stream, pool.clone());
         let admin_svc = AdminService::new(
             settings,
             auth_cache.clone(),
@@ -131,12 +295,33 @@ impl Daemon {
             .add_service(OrgServer::new(org_svc))
             .add_service(RouteServer::new(route_svc))
             .add_service(AdminServer::new(admin_svc))
-            .serve_with_shutdown(listen_addr, shutdown_listener)
+            .add_service(DevaddrServer::new(devaddr_svc))
+            .add_service(OrgPayerServer::new(org_payer_svc))
+            .add_service(OrgOwnerServer::new(org_owner_svc))
+            .add_service(RegionLimitsServer::new(region_limits_svc))
+            .add_service(OrgLockServer::new(org_lock_svc))
+            .add_service(OrgDevaddrsServer::new(org_devaddrs_svc))
+            .add_service(AuditLogServer::new(audit_log_svc))
+            .add_service(OrgSnapshotServer::new(org_snapshot_svc))
+            .add_service(GatewayOnboardingServer::new(gateway_onboarding_svc))
+            .add_service(GatewayRegionOverrideServer::new(
+                gateway_region_override_svc,
+            ))
+            .add_service(GatewayChangesServer::new(gateway_changes_svc))
+            .add_service(WebhooksServer::new(webhook_svc))
+            .add_service(RouteBulkServer::new(route_bulk_svc))
+            .add_service(RouteEuisServer::new(route_euis_svc))
+            .add_service(SkfStreamServer::new(skf_stream_svc))
+            .serve_with_shutdown(listen_addr, shutdown_listener.clone())
             .map_err(Error::from);
 
         tokio::try_join!(
             db_join_handle.map_err(Error::from),
             md_pool_handle.map_err(Error::from),
+            payer_change_scheduler.run(&shutdown_listener),
+            webhook_dispatcher.run(shutdown_listener.clone()),
+            auth_cache_refresher.run(shutdown_listener.clone()),
+            skf_pruner.run(shutdown_listener.clone()),
             server
         )?;
 
@@ -144,6 +329,56 @@ impl Daemon {
     }
 }
 
+/// Serve the gateway read apis from a cache in front of an upstream config
+/// service, without database access
+#[derive(Debug, clap::Args)]
+pub struct Proxy;
+
+impl Proxy {
+    pub async fn run(&self, settings: &proxy::Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        // Install prometheus metrics exporter
+        poc_metrics::start_metrics(&settings.metrics)?;
+        telemetry::initialize();
+
+        // Configure shutdown trigger
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigterm.recv() => shutdown_trigger.trigger(),
+                _ = signal::ctrl_c() => shutdown_trigger.trigger(),
+            }
+        });
+
+        // Check the upstream before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check("upstream config service", settings.check_upstream())
+            .await;
+        preflight.check_result("upstream pubkey", settings.upstream_pubkey());
+        preflight.finish()?;
+
+        let listen_addr = settings.listen_addr()?;
+        let gateway_proxy = GatewayProxy::new(settings)?;
+        tracing::debug!("listening on {listen_addr}");
+        tracing::debug!("proxying {}", settings.upstream);
+
+        transport::Server::builder()
+            .http2_keepalive_interval(Some(Duration::from_secs(250)))
+            .http2_keepalive_timeout(Some(Duration::from_secs(60)))
+            .add_service(GatewayServer::new(gateway_proxy))
+            .serve_with_shutdown(listen_addr, shutdown_listener)
+            .await?;
+
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -1,13 +1,16 @@
 use crate::{
-    helium_netids::{self, is_helium_netid, AddressStore, HeliumNetId},
+    audit,
+    helium_netids::{self, is_helium_netid, AddressStore, DevAddrConstraintsError, HeliumNetId},
     lora_field::{DevAddrConstraint, DevAddrField, NetIdField},
     org_service::UpdateAuthorizer,
+    Error, Result,
 };
+use chrono::{DateTime, Utc};
 use futures::stream::StreamExt;
 use helium_crypto::{PublicKey, PublicKeyBinary};
 use serde::Serialize;
 use sqlx::{postgres::PgRow, types::Uuid, FromRow, Row};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::watch;
 
 pub mod proto {
@@ -82,7 +85,7 @@ pub async fn create_org(
     net_id: NetIdField,
     devaddr_ranges: &[DevAddrConstraint],
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
-) -> Result<Org, OrgStoreError> {
+) -> Result<Org> {
     let mut txn = db.begin().await?;
 
     let oui = sqlx::query(
@@ -95,10 +98,7 @@ pub async fn create_org(
     .bind(&owner)
     .bind(&payer)
     .fetch_one(&mut txn)
-    .await
-    .map_err(|_| {
-        OrgStoreError::SaveOrg(format!("owner: {owner}, payer: {payer}, net_id: {net_id}"))
-    })?
+    .await?
     .get::<i64, &str>("oui");
 
     if !delegate_keys.is_empty() {
@@ -116,59 +116,151 @@ pub async fn create_org(
             .build()
             .execute(&mut txn)
             .await
-            .map_err(|_| {
-                OrgStoreError::SaveDelegates(format!(
-                    "owner: {owner}, payer: {payer}, net_id: {net_id}"
-                ))
+            .map_err(|err| {
+                if is_unique_violation(&err) {
+                    Error::conflict(format!(
+                        "delegate key in use by another org, owner: {owner}, payer: {payer}, net_id: {net_id}"
+                    ))
+                } else {
+                    Error::from(err)
+                }
             })
             .map(|_| ())?
     };
 
     if is_helium_netid(&net_id) {
-        insert_helium_constraints(oui as u64, net_id, devaddr_ranges, &mut txn).await
+        insert_helium_constraints(oui as u64, net_id, devaddr_ranges, &mut txn).await?;
     } else {
         let constraint = devaddr_ranges
             .first()
-            .ok_or(OrgStoreError::SaveConstraints(
-                "no devaddr constraints supplied".to_string(),
-            ))?;
+            .ok_or_else(|| Error::invalid("no devaddr constraints supplied"))?;
         if check_roamer_constraint_count(net_id, &mut txn).await? == 0 {
-            insert_roamer_constraint(oui as u64, net_id, constraint, &mut txn).await
+            insert_roamer_constraint(oui as u64, net_id, constraint, &mut txn).await?;
         } else {
-            return Err(OrgStoreError::SaveConstraints(format!(
+            return Err(Error::conflict(format!(
                 "constraint already in use {constraint:?}"
             )));
         }
     }
-    .map_err(|err| OrgStoreError::SaveConstraints(format!("{devaddr_ranges:?}: {err:?}")))?;
 
     let org = get(oui as u64, &mut txn)
         .await?
-        .ok_or_else(|| OrgStoreError::SaveOrg(format!("{oui}")))?;
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+
+    txn.commit().await?;
+
+    Ok(org)
+}
+
+/// Create a helium org along with a block of `devaddrs` addrs of `net_id`,
+/// all or nothing: the block is only claimed if the org is saved with it and
+/// `audit_entry` is recorded
+pub async fn create_helium_org(
+    owner: PublicKeyBinary,
+    payer: PublicKeyBinary,
+    delegate_keys: Vec<PublicKeyBinary>,
+    net_id: HeliumNetId,
+    devaddrs: u64,
+    audit_entry: audit::Entry,
+    db: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Org> {
+    if devaddrs < 8 || devaddrs % 2 != 0 {
+        return Err(Error::invalid(format!(
+            "{devaddrs} devaddrs requested; minimum 8, even number required"
+        )));
+    }
+
+    let mut txn = db.begin().await?;
+    let constraint = helium_netids::allocate_helium_devaddr_block(&mut txn, devaddrs, net_id)
+        .await
+        .map_err(|err| match err {
+            DevAddrConstraintsError::AddressStore(err) => Error::from(err),
+            DevAddrConstraintsError::NoAvailableAddrs
+            | DevAddrConstraintsError::ConstraintAddrInUse(_) => {
+                Error::conflict(format!("helium addresses unavailable: {err}"))
+            }
+            DevAddrConstraintsError::InvalidConstraint(_)
+            | DevAddrConstraintsError::InvalidBlockSize(_) => Error::invalid(err),
+        })?;
+    let org = create_org(
+        owner,
+        payer,
+        delegate_keys,
+        net_id.id(),
+        &[constraint],
+        &mut txn,
+    )
+    .await?;
+    audit::record(audit_entry.oui(org.oui).after(&org), &mut txn).await?;
+    txn.commit().await?;
+
+    Ok(org)
+}
 
+/// Create a roaming org holding the full devaddr range of `net_id`, all or
+/// nothing
+pub async fn create_roamer_org(
+    owner: PublicKeyBinary,
+    payer: PublicKeyBinary,
+    delegate_keys: Vec<PublicKeyBinary>,
+    net_id: NetIdField,
+    audit_entry: audit::Entry,
+    db: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Org> {
+    let devaddr_range = net_id
+        .full_range()
+        .map_err(|err| Error::invalid(format!("invalid net_id {net_id}: {err:?}")))?;
+    let mut txn = db.begin().await?;
+    let org = create_org(
+        owner,
+        payer,
+        delegate_keys,
+        net_id,
+        &[devaddr_range],
+        &mut txn,
+    )
+    .await?;
+    audit::record(audit_entry.oui(org.oui).after(&org), &mut txn).await?;
     txn.commit().await?;
 
     Ok(org)
 }
 
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|err| err.code())
+        .is_some_and(|code| code == "23505")
+}
+
+/// Apply `updates` to the org along with recording `audit_entry`, all or
+/// nothing
 pub async fn update_org(
     oui: u64,
     authorizer: UpdateAuthorizer,
+    authorizer_key: &PublicKeyBinary,
     updates: Vec<proto::UpdateV1>,
+    audit_entry: audit::Entry,
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
-) -> Result<Org, OrgStoreError> {
+) -> Result<Org> {
     let mut txn = db.begin().await?;
 
     let current_org = get(oui, &mut txn)
         .await?
-        .ok_or_else(|| OrgStoreError::NotFound(format!("{oui}")))?;
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
     let net_id = get_org_netid(oui, &mut txn).await?;
     let is_helium_org = is_helium_netid(&net_id);
 
     for update in updates {
         match update.update {
             Some(proto::Update::Owner(pubkeybin)) if authorizer == UpdateAuthorizer::Admin => {
-                update_owner(oui, pubkeybin.into(), &mut txn).await?
+                update_owner(
+                    oui,
+                    &current_org.owner,
+                    pubkeybin.into(),
+                    authorizer_key,
+                    &mut txn,
+                )
+                .await?
             }
             Some(proto::Update::Payer(pubkeybin)) if authorizer == UpdateAuthorizer::Admin => {
                 update_payer(oui, pubkeybin.into(), &mut txn).await?
@@ -176,7 +268,9 @@ pub async fn update_org(
             Some(proto::Update::Devaddrs(addr_count))
                 if authorizer == UpdateAuthorizer::Admin && is_helium_org =>
             {
-                add_devaddr_slab(oui, net_id, addr_count, &mut txn).await?
+                add_devaddr_slab(oui, net_id, addr_count, &mut txn)
+                    .await
+                    .map(|_| ())?
             }
             Some(proto::Update::Constraint(constraint_update))
                 if authorizer == UpdateAuthorizer::Admin && is_helium_org =>
@@ -184,7 +278,7 @@ pub async fn update_org(
                 match (constraint_update.action(), &constraint_update.constraint) {
                     (proto::ActionV1::Add, Some(ref constraint)) => add_constraint_update(oui, net_id, constraint.into(), &mut txn).await?,
                     (proto::ActionV1::Remove, Some(ref constraint)) => remove_constraint_update(oui, net_id, current_org.constraints.as_ref(), constraint.into(), &mut txn).await?,
-                    _ => return Err(OrgStoreError::InvalidUpdate(format!("invalid action or missing devaddr constraint update: {constraint_update:?}")))
+                    _ => return Err(Error::invalid(format!("invalid action or missing devaddr constraint update: {constraint_update:?}")))
                 }
             }
             Some(proto::Update::DelegateKey(delegate_key_update)) => {
@@ -200,7 +294,7 @@ pub async fn update_org(
                 }
             }
             _ => {
-                return Err(OrgStoreError::InvalidUpdate(format!(
+                return Err(Error::unauthorized(format!(
                     "update: {update:?}, authorizer: {authorizer:?}"
                 )))
             }
@@ -209,7 +303,80 @@ pub async fn update_org(
 
     let updated_org = get(oui, &mut txn)
         .await?
-        .ok_or_else(|| OrgStoreError::SaveOrg(format!("{oui}")))?;
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    audit::record(
+        audit_entry.before(&current_org).after(&updated_org),
+        &mut txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(updated_org)
+}
+
+/// Allocate an additional block of `devaddrs` addrs to a helium org, taken
+/// from the smallest free gap of its net id
+pub async fn allocate_devaddrs(
+    oui: u64,
+    devaddrs: u64,
+    audit_entry: audit::Entry,
+    db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
+) -> Result<(Org, DevAddrConstraint)> {
+    let mut txn = db.begin().await?;
+
+    let current_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    let net_id = get_org_netid(oui, &mut txn).await?;
+    let constraint = add_devaddr_slab(oui, net_id, devaddrs, &mut txn).await?;
+
+    let updated_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    audit::record(
+        audit_entry.before(&current_org).after(&updated_org),
+        &mut txn,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok((updated_org, constraint))
+}
+
+/// Release a devaddr range of a helium org back to its net id. Only ranges
+/// none of the routes of the org use are released, and an org keeps at least
+/// one range.
+pub async fn release_devaddrs(
+    oui: u64,
+    constraint: DevAddrConstraint,
+    audit_entry: audit::Entry,
+    db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
+) -> Result<Org> {
+    let mut txn = db.begin().await?;
+
+    let current_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    let net_id = get_org_netid(oui, &mut txn).await?;
+    remove_constraint_update(
+        oui,
+        net_id,
+        current_org.constraints.as_ref(),
+        constraint,
+        &mut txn,
+    )
+    .await?;
+
+    let updated_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    audit::record(
+        audit_entry.before(&current_org).after(&updated_org),
+        &mut txn,
+    )
+    .await?;
 
     txn.commit().await?;
 
@@ -229,17 +396,74 @@ pub async fn get_org_netid(
     Ok(netid.into())
 }
 
+/// Net id of every org with devaddr constraints
+pub async fn net_ids(
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<HashMap<u64, NetIdField>, sqlx::Error> {
+    Ok(sqlx::query_as::<_, (i64, i32)>(
+        " select distinct on (oui) oui, net_id from organization_devaddr_constraints ",
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(oui, net_id)| (oui as u64, net_id.into()))
+    .collect())
+}
+
+/// Rotate the owner key of an org, keeping the old key in the owner history
+pub async fn rotate_owner(
+    oui: u64,
+    new_owner: PublicKeyBinary,
+    authorizer_key: &PublicKeyBinary,
+    db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
+) -> Result<(Org, PublicKeyBinary)> {
+    let mut txn = db.begin().await?;
+
+    let current_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    if current_org.owner == new_owner {
+        return Err(Error::conflict(format!(
+            "{new_owner} is already the owner of org {oui}"
+        )));
+    }
+    update_owner(oui, &current_org.owner, new_owner, authorizer_key, &mut txn).await?;
+
+    let updated_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+
+    txn.commit().await?;
+
+    Ok((updated_org, current_org.owner))
+}
+
 async fn update_owner(
     oui: u64,
+    old_owner: &PublicKeyBinary,
     owner_pubkey: PublicKeyBinary,
+    authorizer_key: &PublicKeyBinary,
     db: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(" update organizations set owner_pubkey = $1 where oui = $2 ")
-        .bind(owner_pubkey)
+        .bind(&owner_pubkey)
         .bind(oui as i64)
-        .execute(db)
-        .await
-        .map(|_| ())
+        .execute(&mut *db)
+        .await?;
+    sqlx::query(
+        r#"
+        insert into organization_owner_history
+            (oui, old_owner_pubkey, new_owner_pubkey, authorizer_pubkey)
+        values ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(old_owner)
+    .bind(owner_pubkey)
+    .bind(authorizer_key)
+    .execute(db)
+    .await
+    .map(|_| ())
 }
 
 async fn update_payer(
@@ -255,30 +479,61 @@ async fn update_payer(
         .map(|_| ())
 }
 
+/// Delegate `delegate_pubkey` to the org. Adding a key the org already
+/// delegates is a no-op, a key delegated by another org is a conflict.
 async fn add_delegate_key(
     oui: u64,
     delegate_pubkey: PublicKeyBinary,
     db: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), sqlx::Error> {
-    sqlx::query(" insert into organization_delegate_keys (delegate_pubkey, oui) values ($1, $2) ")
-        .bind(delegate_pubkey)
-        .bind(oui as i64)
-        .execute(db)
-        .await
-        .map(|_| ())
+) -> Result<()> {
+    let delegated_by: i64 = sqlx::query_scalar(
+        r#"
+        insert into organization_delegate_keys (delegate_pubkey, oui) values ($1, $2)
+        on conflict (delegate_pubkey) do update set oui = organization_delegate_keys.oui
+        returning oui
+        "#,
+    )
+    .bind(&delegate_pubkey)
+    .bind(oui as i64)
+    .fetch_one(db)
+    .await?;
+    if delegated_by as u64 != oui {
+        return Err(Error::conflict(format!(
+            "delegate key {delegate_pubkey} already in use"
+        )));
+    }
+    Ok(())
 }
 
+/// Stop delegating `delegate_pubkey` to the org. Removing a key the org
+/// doesn't delegate is a no-op, so retried rotations succeed.
 async fn remove_delegate_key(
     oui: u64,
     delegate_pubkey: PublicKeyBinary,
     db: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), sqlx::Error> {
+) -> Result<()> {
     sqlx::query(" delete from organization_delegate_keys where delegate_pubkey = $1 and oui = $2 ")
-        .bind(delegate_pubkey)
+        .bind(&delegate_pubkey)
         .bind(oui as i64)
         .execute(db)
-        .await
-        .map(|_| ())
+        .await?;
+    Ok(())
+}
+
+/// The keys of `keys` delegated by any org
+pub async fn delegated_keys(
+    db: impl sqlx::PgExecutor<'_>,
+    keys: &[PublicKeyBinary],
+) -> Result<HashSet<PublicKeyBinary>, sqlx::Error> {
+    let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    Ok(sqlx::query_scalar::<_, PublicKeyBinary>(
+        " select delegate_pubkey from organization_delegate_keys where delegate_pubkey = any($1) ",
+    )
+    .bind(keys)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .collect())
 }
 
 async fn add_constraint_update(
@@ -286,13 +541,11 @@ async fn add_constraint_update(
     net_id: NetIdField,
     added_constraint: DevAddrConstraint,
     db: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), OrgStoreError> {
-    let helium_net_id: HeliumNetId = net_id
-        .try_into()
-        .map_err(|err: &'static str| OrgStoreError::InvalidUpdate(err.to_string()))?;
+) -> Result<()> {
+    let helium_net_id: HeliumNetId = net_id.try_into().map_err(Error::conflict)?;
     helium_netids::checkout_specified_devaddr_constraint(db, helium_net_id, &added_constraint)
         .await
-        .map_err(|err| OrgStoreError::InvalidUpdate(format!("{err:?}")))?;
+        .map_err(|err| Error::conflict(format!("{err:?}")))?;
     insert_helium_constraints(oui, net_id, &[added_constraint], db).await?;
     Ok(())
 }
@@ -303,12 +556,15 @@ async fn remove_constraint_update(
     org_constraints: Option<&Vec<DevAddrConstraint>>,
     removed_constraint: DevAddrConstraint,
     db: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), OrgStoreError> {
-    let helium_net_id: HeliumNetId = net_id
-        .try_into()
-        .map_err(|err: &'static str| OrgStoreError::InvalidUpdate(err.to_string()))?;
+) -> Result<()> {
+    let helium_net_id: HeliumNetId = net_id.try_into().map_err(Error::conflict)?;
     if let Some(org_constraints) = org_constraints {
         if org_constraints.contains(&removed_constraint) && org_constraints.len() > 1 {
+            if routes_use_constraint(oui, &removed_constraint, &mut *db).await? {
+                return Err(Error::conflict(
+                    "constraint in use by devaddr ranges of org routes".to_string(),
+                ));
+            }
             let remove_range = (u32::from(removed_constraint.start_addr)
                 ..=u32::from(removed_constraint.end_addr))
                 .collect::<Vec<u32>>();
@@ -316,34 +572,96 @@ async fn remove_constraint_update(
             remove_helium_constraints(oui, &[removed_constraint], db).await?;
             Ok(())
         } else if org_constraints.len() == 1 {
-            return Err(OrgStoreError::InvalidUpdate(
+            return Err(Error::conflict(
                 "org must have at least one constraint range".to_string(),
             ));
         } else {
-            return Err(OrgStoreError::InvalidUpdate(
+            return Err(Error::conflict(
                 "cannot remove constraint leased by other org".to_string(),
             ));
         }
     } else {
-        Err(OrgStoreError::InvalidUpdate(
-            "no org constraints defined".to_string(),
-        ))
+        Err(Error::conflict("no org constraints defined".to_string()))
     }
 }
 
+/// Claim a block of `addr_count` addrs from the smallest free gap of the
+/// helium `net_id` for the org, returning the claimed range
 async fn add_devaddr_slab(
     oui: u64,
     net_id: NetIdField,
     addr_count: u64,
     txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), OrgStoreError> {
-    let helium_net_id: HeliumNetId = net_id
-        .try_into()
-        .map_err(|err: &'static str| OrgStoreError::InvalidUpdate(err.to_string()))?;
-    let constraints = helium_netids::checkout_devaddr_constraints(txn, addr_count, helium_net_id)
+) -> Result<DevAddrConstraint> {
+    let helium_net_id: HeliumNetId = net_id.try_into().map_err(Error::conflict)?;
+    let constraint = helium_netids::allocate_helium_devaddr_block(txn, addr_count, helium_net_id)
         .await
-        .map_err(|err| OrgStoreError::SaveConstraints(format!("{err:?}")))?;
-    insert_helium_constraints(oui, net_id, &constraints, txn).await?;
+        .map_err(|err| match err {
+            DevAddrConstraintsError::AddressStore(err) => Error::from(err),
+            DevAddrConstraintsError::NoAvailableAddrs => {
+                Error::conflict(format!("no {addr_count} free devaddrs in net_id {net_id}"))
+            }
+            err => Error::invalid(err),
+        })?;
+    insert_helium_constraints(oui, net_id, &[constraint.clone()], txn).await?;
+    Ok(constraint)
+}
+
+/// Check that every range of `devaddr_ranges` is ordered and overlaps
+/// neither the others nor any constraint already saved for `net_id`
+async fn validate_constraints(
+    net_id: NetIdField,
+    devaddr_ranges: &[DevAddrConstraint],
+    txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    for (idx, range) in devaddr_ranges.iter().enumerate() {
+        let (start_addr, end_addr) = (u32::from(range.start_addr), u32::from(range.end_addr));
+        if start_addr > end_addr {
+            return Err(Error::invalid(format!(
+                "start_addr {} after end_addr {}",
+                range.start_addr, range.end_addr
+            )));
+        }
+        if let Some(other) = devaddr_ranges[idx + 1..].iter().find(|other| {
+            u32::from(other.start_addr) <= end_addr && u32::from(other.end_addr) >= start_addr
+        }) {
+            return Err(Error::conflict(format!(
+                "{}-{} overlaps requested {}-{}",
+                range.start_addr, range.end_addr, other.start_addr, other.end_addr
+            )));
+        }
+    }
+
+    let (start_addrs, end_addrs): (Vec<i32>, Vec<i32>) = devaddr_ranges
+        .iter()
+        .map(|range| (i32::from(range.start_addr), i32::from(range.end_addr)))
+        .unzip();
+    let existing = sqlx::query(
+        r#"
+        select saved.oui, saved.start_addr, saved.end_addr,
+            requested.start_addr as requested_start, requested.end_addr as requested_end
+        from organization_devaddr_constraints saved
+        join unnest($2::int[], $3::int[]) as requested(start_addr, end_addr)
+            on saved.start_addr <= requested.end_addr and saved.end_addr >= requested.start_addr
+        where saved.net_id = $1
+        limit 1
+        "#,
+    )
+    .bind(i32::from(net_id))
+    .bind(start_addrs)
+    .bind(end_addrs)
+    .fetch_optional(&mut *txn)
+    .await?;
+    if let Some(row) = existing {
+        return Err(Error::conflict(format!(
+            "{}-{} overlaps {}-{} of oui {}",
+            DevAddrField::from(row.get::<i32, &str>("requested_start")),
+            DevAddrField::from(row.get::<i32, &str>("requested_end")),
+            DevAddrField::from(row.get::<i32, &str>("start_addr")),
+            DevAddrField::from(row.get::<i32, &str>("end_addr")),
+            row.get::<i64, &str>("oui")
+        )));
+    }
     Ok(())
 }
 
@@ -351,8 +669,9 @@ async fn insert_helium_constraints(
     oui: u64,
     net_id: NetIdField,
     devaddr_ranges: &[DevAddrConstraint],
-    db: impl sqlx::PgExecutor<'_>,
-) -> Result<(), sqlx::Error> {
+    txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    validate_constraints(net_id, devaddr_ranges, txn).await?;
     let mut query_builder: sqlx::QueryBuilder<sqlx::Postgres> = sqlx::QueryBuilder::new(
         r#"
         insert into organization_devaddr_constraints (oui, net_id, start_addr, end_addr)
@@ -366,7 +685,12 @@ async fn insert_helium_constraints(
             .push_bind(i32::from(range.end_addr));
     });
 
-    query_builder.build().execute(db).await.map(|_| ())
+    query_builder
+        .build()
+        .execute(txn)
+        .await
+        .map(|_| ())
+        .map_err(Error::from)
 }
 
 async fn remove_helium_constraints(
@@ -391,6 +715,28 @@ async fn remove_helium_constraints(
     query_builder.build().execute(db).await.map(|_| ())
 }
 
+/// Whether a devaddr range of any route of the org overlaps `constraint`
+async fn routes_use_constraint(
+    oui: u64,
+    constraint: &DevAddrConstraint,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        select exists(
+            select 1 from route_devaddr_ranges devaddr
+            join routes r on devaddr.route_id = r.id
+            where r.oui = $1 and devaddr.start_addr <= $3 and devaddr.end_addr >= $2
+        )
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(i32::from(constraint.start_addr))
+    .bind(i32::from(constraint.end_addr))
+    .fetch_one(db)
+    .await
+}
+
 async fn check_roamer_constraint_count(
     net_id: NetIdField,
     db: impl sqlx::PgExecutor<'_>,
@@ -407,8 +753,9 @@ async fn insert_roamer_constraint(
     oui: u64,
     net_id: NetIdField,
     devaddr_range: &DevAddrConstraint,
-    db: impl sqlx::PgExecutor<'_>,
-) -> Result<(), sqlx::Error> {
+    txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    validate_constraints(net_id, std::slice::from_ref(devaddr_range), txn).await?;
     sqlx::query(
         r#"
         insert into organization_devaddr_constraints (oui, net_id, start_addr, end_addr)
@@ -419,9 +766,10 @@ async fn insert_roamer_constraint(
     .bind(i32::from(net_id))
     .bind(i32::from(devaddr_range.start_addr))
     .bind(i32::from(devaddr_range.end_addr))
-    .execute(db)
+    .execute(txn)
     .await
     .map(|_| ())
+    .map_err(Error::from)
 }
 
 const GET_ORG_SQL: &str = r#"
@@ -452,7 +800,7 @@ pub async fn get(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<Option<Org>,
 pub async fn get_constraints_by_route(
     route_id: &str,
     db: impl sqlx::PgExecutor<'_>,
-) -> Result<Vec<DevAddrConstraint>, OrgStoreError> {
+) -> Result<Vec<DevAddrConstraint>> {
     let uuid = Uuid::try_parse(route_id)?;
 
     let constraints = sqlx::query(
@@ -475,10 +823,22 @@ pub async fn get_constraints_by_route(
     Ok(constraints)
 }
 
+pub async fn get_oui_by_route(route_id: &str, db: impl sqlx::PgExecutor<'_>) -> Result<u64> {
+    let uuid = Uuid::try_parse(route_id)?;
+
+    let oui = sqlx::query_scalar::<_, i64>(" select oui from routes where id = $1 ")
+        .bind(uuid)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("route {route_id}")))?;
+
+    Ok(oui as u64)
+}
+
 pub async fn get_route_ids_by_route(
     route_id: &str,
     db: impl sqlx::PgExecutor<'_>,
-) -> Result<Vec<String>, OrgStoreError> {
+) -> Result<Vec<String>> {
     let uuid = Uuid::try_parse(route_id)?;
 
     let route_ids = sqlx::query(
@@ -527,33 +887,183 @@ pub async fn toggle_locked(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<()
     Ok(())
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum OrgStoreError {
-    #[error("error retrieving saved org row: {0}")]
-    FetchOrg(#[from] sqlx::Error),
-    #[error("org not found: {0}")]
-    NotFound(String),
-    #[error("error saving org: {0}")]
-    SaveOrg(String),
-    #[error("error saving delegate keys: {0}")]
-    SaveDelegates(String),
-    #[error("error saving devaddr constraints: {0}")]
-    SaveConstraints(String),
-    #[error("unable to deserialize pubkey: {0}")]
-    DecodeKey(#[from] helium_crypto::Error),
-    #[error("Route Id parse error: {0}")]
-    RouteIdParse(#[from] sqlx::types::uuid::Error),
-    #[error("Invalid update: {0}")]
-    InvalidUpdate(String),
-}
-
-pub async fn get_org_pubkeys(
-    oui: u64,
+/// The org owning a devaddr along with the constraint the devaddr falls in
+#[derive(Clone, Debug, Serialize)]
+pub struct DevAddrOrg {
+    pub devaddr: DevAddrField,
+    pub oui: u64,
+    pub net_id: NetIdField,
+    pub constraint: DevAddrConstraint,
+}
+
+impl FromRow<'_, PgRow> for DevAddrOrg {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            devaddr: row.get::<i32, &str>("devaddr").into(),
+            oui: row.get::<i64, &str>("oui") as u64,
+            net_id: row.get::<i32, &str>("net_id").into(),
+            constraint: DevAddrConstraint {
+                start_addr: row.get::<i32, &str>("start_addr").into(),
+                end_addr: row.get::<i32, &str>("end_addr").into(),
+            },
+        })
+    }
+}
+
+/// The org owning `devaddr`, the one with the lowest oui when constraints of
+/// several orgs overlap
+pub async fn org_for_devaddr(
+    devaddr: DevAddrField,
     db: impl sqlx::PgExecutor<'_>,
-) -> Result<Vec<PublicKey>, OrgStoreError> {
+) -> crate::Result<Option<DevAddrOrg>> {
+    Ok(orgs_for_devaddrs(&[devaddr], db).await?.into_iter().next())
+}
+
+/// Resolve the owning org for each of the given devaddrs. Devaddrs not
+/// covered by any org constraint are left out of the result, those covered by
+/// overlapping constraints of several orgs resolve to each of them in oui
+/// order
+pub async fn orgs_for_devaddrs(
+    devaddrs: &[DevAddrField],
+    db: impl sqlx::PgExecutor<'_>,
+) -> crate::Result<Vec<DevAddrOrg>> {
+    let devaddrs: Vec<i32> = devaddrs.iter().map(|&devaddr| devaddr.into()).collect();
+    Ok(sqlx::query_as::<_, DevAddrOrg>(
+        r#"
+        select d.devaddr, c.oui, c.net_id, c.start_addr, c.end_addr
+        from unnest($1::int[]) as d(devaddr)
+        join organization_devaddr_constraints c
+            on d.devaddr between c.start_addr and c.end_addr
+        order by d.devaddr, c.oui
+        "#,
+    )
+    .bind(devaddrs)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Audit record of a payer change approved by the org owner and new payer
+#[derive(Clone, Debug, Serialize)]
+pub struct PayerChange {
+    pub id: i64,
+    pub oui: u64,
+    pub old_payer: PublicKeyBinary,
+    pub new_payer: PublicKeyBinary,
+    pub owner: PublicKeyBinary,
+    pub effective_at: DateTime<Utc>,
+    pub applied: bool,
+}
+
+impl FromRow<'_, PgRow> for PayerChange {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.get("id"),
+            oui: row.get::<i64, &str>("oui") as u64,
+            old_payer: row.get("old_payer_pubkey"),
+            new_payer: row.get("new_payer_pubkey"),
+            owner: row.get("owner_pubkey"),
+            effective_at: row.get("effective_at"),
+            applied: row.get("applied"),
+        })
+    }
+}
+
+/// Record a payer change for the org, applying it right away if it is
+/// already effective. Only one change per org may be pending at a time.
+pub async fn record_payer_change(
+    oui: u64,
+    new_payer: PublicKeyBinary,
+    owner: PublicKeyBinary,
+    effective_at: DateTime<Utc>,
+    db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
+) -> Result<PayerChange> {
+    let mut txn = db.begin().await?;
+
+    let current_org = get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
+    if current_org.owner != owner {
+        return Err(Error::conflict(format!(
+            "{owner} is not the owner of org {oui}"
+        )));
+    }
+    if current_org.payer == new_payer {
+        return Err(Error::conflict(format!(
+            "{new_payer} is already the payer of org {oui}"
+        )));
+    }
+
+    let pending = sqlx::query_scalar::<_, i64>(
+        " select count(*) from organization_payer_changes where oui = $1 and not applied ",
+    )
+    .bind(oui as i64)
+    .fetch_one(&mut txn)
+    .await?;
+    if pending > 0 {
+        return Err(Error::conflict(format!(
+            "payer change already pending for org {oui}"
+        )));
+    }
+
+    let apply_now = effective_at <= Utc::now();
+    let change = sqlx::query_as::<_, PayerChange>(
+        r#"
+        insert into organization_payer_changes
+            (oui, old_payer_pubkey, new_payer_pubkey, owner_pubkey, effective_at, applied)
+        values ($1, $2, $3, $4, $5, $6)
+        returning *
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(&current_org.payer)
+    .bind(&new_payer)
+    .bind(&owner)
+    .bind(effective_at)
+    .bind(apply_now)
+    .fetch_one(&mut txn)
+    .await?;
+
+    if apply_now {
+        update_payer(oui, new_payer, &mut txn).await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(change)
+}
+
+/// Apply all pending payer changes that have become effective by `now`,
+/// returning the applied changes
+pub async fn apply_due_payer_changes(
+    now: DateTime<Utc>,
+    db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres>,
+) -> Result<Vec<PayerChange>, sqlx::Error> {
+    let mut txn = db.begin().await?;
+
+    let changes = sqlx::query_as::<_, PayerChange>(
+        r#"
+        update organization_payer_changes set applied = true
+        where not applied and effective_at <= $1
+        returning *
+        "#,
+    )
+    .bind(now)
+    .fetch_all(&mut txn)
+    .await?;
+
+    for change in changes.iter() {
+        update_payer(change.oui, change.new_payer.clone(), &mut txn).await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(changes)
+}
+
+pub async fn get_org_pubkeys(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<Vec<PublicKey>> {
     let org = get(oui, db)
         .await?
-        .ok_or_else(|| OrgStoreError::NotFound(format!("{oui}")))?;
+        .ok_or_else(|| Error::not_found(format!("oui: {oui}")))?;
 
     let mut pubkeys: Vec<PublicKey> = vec![
         PublicKey::try_from(org.owner)?,
@@ -578,7 +1088,7 @@ pub async fn get_org_pubkeys(
 pub async fn get_org_pubkeys_by_route(
     route_id: &str,
     db: impl sqlx::PgExecutor<'_>,
-) -> Result<Vec<PublicKey>, OrgStoreError> {
+) -> Result<Vec<PublicKey>> {
     let uuid = Uuid::try_parse(route_id)?;
 
     let org = sqlx::query_as::<_, Org>(
@@ -628,3 +1138,374 @@ impl From<Org> for proto::OrgV1 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lora_field::{devaddr, net_id},
+        test_support::{audit_entry, count, helium_org, helium_org_with_delegates, pubkey},
+    };
+    use helium_proto::services::iot_config::org_update_req_v1::DelegateKeyUpdateV1;
+    use sqlx::{Pool, Postgres};
+
+    fn delegate_update(key: &PublicKeyBinary, action: proto::ActionV1) -> proto::UpdateV1 {
+        proto::UpdateV1 {
+            update: Some(proto::Update::DelegateKey(DelegateKeyUpdateV1 {
+                delegate_key: key.clone().into(),
+                action: action as i32,
+            })),
+        }
+    }
+
+    fn delegates(org: &Org) -> HashSet<PublicKeyBinary> {
+        org.delegate_keys.iter().flatten().cloned().collect()
+    }
+
+    #[sqlx::test]
+    async fn helium_orgs_are_created_with_their_devaddrs(pool: Pool<Postgres>) {
+        let owner = pubkey();
+        let delegate = pubkey();
+        let org = create_helium_org(
+            owner.clone(),
+            owner.clone(),
+            vec![delegate.clone()],
+            HeliumNetId::Type0_0x00003c,
+            8,
+            audit_entry(),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(owner, org.owner);
+        assert_eq!(Some(vec![delegate]), org.delegate_keys);
+        let constraints = org.constraints.expect("org constraints");
+        assert_eq!(1, constraints.len());
+        assert_eq!(
+            7,
+            u64::from(constraints[0].end_addr) - u64::from(constraints[0].start_addr)
+        );
+        assert_eq!(8, count("helium_used_devaddrs", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn failed_helium_orgs_claim_no_devaddrs(pool: Pool<Postgres>) {
+        let delegate = pubkey();
+        create_helium_org(
+            pubkey(),
+            pubkey(),
+            vec![delegate.clone()],
+            HeliumNetId::Type0_0x00003c,
+            8,
+            audit_entry(),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let conflict = create_helium_org(
+            pubkey(),
+            pubkey(),
+            vec![delegate],
+            HeliumNetId::Type0_0x00003c,
+            8,
+            audit_entry(),
+            &pool,
+        )
+        .await;
+        assert!(matches!(conflict, Err(Error::Conflict(_))));
+
+        let invalid = create_helium_org(
+            pubkey(),
+            pubkey(),
+            vec![],
+            HeliumNetId::Type0_0x00003c,
+            7,
+            audit_entry(),
+            &pool,
+        )
+        .await;
+        assert!(matches!(invalid, Err(Error::Invalid(_))));
+
+        assert_eq!(1, count("organizations", &pool).await);
+        assert_eq!(1, count("organization_delegate_keys", &pool).await);
+        assert_eq!(8, count("helium_used_devaddrs", &pool).await);
+        // only the org created is audited
+        assert_eq!(1, count("config_audit_log", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn a_roaming_net_id_is_held_by_one_org(pool: Pool<Postgres>) {
+        let roamer = net_id(0x000024);
+        let org = create_roamer_org(pubkey(), pubkey(), vec![], roamer, audit_entry(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(vec![roamer.full_range().unwrap()]), org.constraints);
+
+        let conflict =
+            create_roamer_org(pubkey(), pubkey(), vec![], roamer, audit_entry(), &pool).await;
+        assert!(matches!(conflict, Err(Error::Conflict(_))));
+        assert_eq!(1, count("organizations", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn delegate_key_updates_are_idempotent(pool: Pool<Postgres>) {
+        let (kept, added, absent) = (pubkey(), pubkey(), pubkey());
+        let org = helium_org_with_delegates(vec![kept.clone()], &pool).await;
+
+        let updated = update_org(
+            org.oui,
+            UpdateAuthorizer::Org,
+            &org.owner,
+            vec![
+                delegate_update(&absent, proto::ActionV1::Remove),
+                delegate_update(&kept, proto::ActionV1::Add),
+                delegate_update(&added, proto::ActionV1::Add),
+            ],
+            audit_entry(),
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            HashSet::from([kept.clone(), added.clone()]),
+            delegates(&updated)
+        );
+
+        // a retried removal finds the key gone and still succeeds
+        for _ in 0..2 {
+            let updated = update_org(
+                org.oui,
+                UpdateAuthorizer::Org,
+                &org.owner,
+                vec![delegate_update(&added, proto::ActionV1::Remove)],
+                audit_entry(),
+                &pool,
+            )
+            .await
+            .unwrap();
+            assert_eq!(HashSet::from([kept.clone()]), delegates(&updated));
+        }
+    }
+
+    #[sqlx::test]
+    async fn delegate_keys_of_another_org_are_left_alone(pool: Pool<Postgres>) {
+        let (delegate, added) = (pubkey(), pubkey());
+        let first = helium_org_with_delegates(vec![delegate.clone()], &pool).await;
+        let second = helium_org(&pool).await;
+
+        let conflict = update_org(
+            second.oui,
+            UpdateAuthorizer::Org,
+            &second.owner,
+            vec![
+                delegate_update(&added, proto::ActionV1::Add),
+                delegate_update(&delegate, proto::ActionV1::Add),
+            ],
+            audit_entry(),
+            &pool,
+        )
+        .await;
+        assert!(matches!(conflict, Err(Error::Conflict(_))));
+        // the whole update was rolled back, its audit entry included
+        assert_eq!(1, count("organization_delegate_keys", &pool).await);
+        assert_eq!(2, count("config_audit_log", &pool).await);
+
+        let updated = update_org(
+            second.oui,
+            UpdateAuthorizer::Org,
+            &second.owner,
+            vec![delegate_update(&delegate, proto::ActionV1::Remove)],
+            audit_entry(),
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(delegates(&updated).is_empty());
+        let first = get(first.oui, &pool).await.unwrap().unwrap();
+        assert_eq!(HashSet::from([delegate.clone()]), delegates(&first));
+        assert_eq!(
+            HashSet::from([delegate.clone()]),
+            delegated_keys(&pool, &[delegate, added]).await.unwrap()
+        );
+    }
+
+    /// Give the org a route using `constraint` for its devaddrs
+    async fn route_using(oui: u64, constraint: &DevAddrConstraint, pool: &Pool<Postgres>) {
+        let route_id: Uuid = sqlx::query_scalar(
+            r#"
+            insert into routes (oui, net_id, max_copies, server_host, server_port, server_protocol_opts)
+            values ($1, $2, 1, 'localhost', 8080, '{}')
+            returning id
+            "#,
+        )
+        .bind(oui as i64)
+        .bind(i32::from(HeliumNetId::Type0_0x00003c.id()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into route_devaddr_ranges (route_id, start_addr, end_addr) values ($1, $2, $3)",
+        )
+        .bind(route_id)
+        .bind(i32::from(constraint.start_addr))
+        .bind(i32::from(constraint.end_addr))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn allocated_devaddrs_are_released_to_the_net_id(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let initial = org.constraints.clone().unwrap();
+
+        let (allocated, constraint) = allocate_devaddrs(org.oui, 8, audit_entry(), &pool)
+            .await
+            .unwrap();
+        let constraints = allocated.constraints.unwrap();
+        assert_eq!(2, constraints.len());
+        assert!(constraints.contains(&constraint));
+        assert!(!initial.contains(&constraint));
+        assert_eq!(16, count("helium_used_devaddrs", &pool).await);
+
+        let released = release_devaddrs(org.oui, constraint.clone(), audit_entry(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(initial), released.constraints);
+        assert_eq!(8, count("helium_used_devaddrs", &pool).await);
+
+        // the released range is the smallest free gap again
+        let (_, reallocated) = allocate_devaddrs(org.oui, 8, audit_entry(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(constraint, reallocated);
+    }
+
+    #[sqlx::test]
+    async fn devaddrs_in_use_or_last_are_not_released(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let initial = org.constraints.clone().unwrap().remove(0);
+
+        let last = release_devaddrs(org.oui, initial.clone(), audit_entry(), &pool).await;
+        assert!(matches!(last, Err(Error::Conflict(_))));
+        assert_eq!(8, count("helium_used_devaddrs", &pool).await);
+
+        let (_, allocated) = allocate_devaddrs(org.oui, 8, audit_entry(), &pool)
+            .await
+            .unwrap();
+        route_using(org.oui, &allocated, &pool).await;
+        assert!(routes_use_constraint(org.oui, &allocated, &pool)
+            .await
+            .unwrap());
+        assert!(!routes_use_constraint(org.oui, &initial, &pool)
+            .await
+            .unwrap());
+
+        let in_use = release_devaddrs(org.oui, allocated.clone(), audit_entry(), &pool).await;
+        assert!(matches!(in_use, Err(Error::Conflict(_))));
+        assert_eq!(16, count("helium_used_devaddrs", &pool).await);
+
+        let released = release_devaddrs(org.oui, initial, audit_entry(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(Some(vec![allocated]), released.constraints);
+        assert_eq!(8, count("helium_used_devaddrs", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn devaddrs_are_not_allocated_to_roaming_orgs(pool: Pool<Postgres>) {
+        let org = create_roamer_org(
+            pubkey(),
+            pubkey(),
+            vec![],
+            net_id(0x000024),
+            audit_entry(),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let allocated = allocate_devaddrs(org.oui, 8, audit_entry(), &pool).await;
+        assert!(matches!(allocated, Err(Error::Conflict(_))));
+        assert_eq!(0, count("helium_used_devaddrs", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn devaddrs_resolve_to_every_org_holding_them(pool: Pool<Postgres>) {
+        let first = helium_org(&pool).await;
+        let second = helium_org(&pool).await;
+        let held = first.constraints.unwrap().remove(0);
+        let (shared, owned, unowned) = (held.start_addr, held.end_addr, devaddr(0));
+        // constraints saved before they were validated may overlap
+        sqlx::query(
+            r#"
+            insert into organization_devaddr_constraints (oui, net_id, start_addr, end_addr)
+            values ($1, $2, $3, $3)
+            "#,
+        )
+        .bind(second.oui as i64)
+        .bind(i32::from(HeliumNetId::Type0_0x00003c.id()))
+        .bind(i32::from(shared))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let resolved = orgs_for_devaddrs(&[unowned, owned, shared], &pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|org| (u64::from(org.devaddr), org.oui))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (u64::from(shared), first.oui),
+                (u64::from(shared), second.oui),
+                (u64::from(owned), first.oui),
+            ],
+            resolved
+        );
+
+        let org = org_for_devaddr(shared, &pool).await.unwrap().unwrap();
+        assert_eq!(first.oui, org.oui);
+        assert_eq!(held, org.constraint);
+        assert!(org_for_devaddr(unowned, &pool).await.unwrap().is_none());
+    }
+
+    #[sqlx::test]
+    async fn constraints_are_validated_against_each_other_and_saved_ones(pool: Pool<Postgres>) {
+        let net_id = HeliumNetId::Type0_0x00003c.id();
+        let org = helium_org(&pool).await;
+        let saved = org.constraints.unwrap().remove(0);
+        let range = |start: u32, end: u32| DevAddrConstraint {
+            start_addr: devaddr(start),
+            end_addr: devaddr(end),
+        };
+        let (saved_start, saved_end) = (u32::from(saved.start_addr), u32::from(saved.end_addr));
+        let mut txn = pool.begin().await.unwrap();
+
+        let free = [
+            range(saved_end + 1, saved_end + 8),
+            range(saved_end + 9, saved_end + 16),
+        ];
+        validate_constraints(net_id, &free, &mut txn).await.unwrap();
+
+        let reversed =
+            validate_constraints(net_id, &[range(saved_end + 8, saved_end + 1)], &mut txn).await;
+        assert!(matches!(reversed, Err(Error::Invalid(_))));
+
+        let overlapping = [
+            range(saved_end + 1, saved_end + 8),
+            range(saved_end + 7, saved_end + 16),
+        ];
+        let requested = validate_constraints(net_id, &overlapping, &mut txn).await;
+        assert!(matches!(requested, Err(Error::Conflict(_))));
+
+        let overlapping = [
+            range(saved_end + 1, saved_end + 8),
+            range(saved_start + 2, saved_start + 3),
+        ];
+        let existing = validate_constraints(net_id, &overlapping, &mut txn).await;
+        assert!(matches!(existing, Err(Error::Conflict(_))));
+    }
+}
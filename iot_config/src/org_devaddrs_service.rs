@@ -0,0 +1,131 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    audit,
+    ext::{self, OrgAllocateDevaddrsReqV1, OrgDevaddrsResV1, OrgReleaseDevaddrsReqV1},
+    lora_field::DevAddrConstraint,
+    org::{self, Org},
+    telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+/// Allocates additional devaddr ranges to helium orgs and releases the ones
+/// they no longer use
+pub struct OrgDevaddrsService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl OrgDevaddrsService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+        })
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+
+    fn devaddrs_response(
+        &self,
+        org: Org,
+        changed: DevAddrConstraint,
+    ) -> GrpcResult<OrgDevaddrsResV1> {
+        let mut resp = OrgDevaddrsResV1 {
+            constraints: org
+                .constraints
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|constraint| constraint.into())
+                .collect(),
+            org: Some(org.into()),
+            changed: Some(changed.into()),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::org_devaddrs_server::OrgDevaddrs for OrgDevaddrsService {
+    async fn allocate(
+        &self,
+        request: Request<OrgAllocateDevaddrsReqV1>,
+    ) -> GrpcResult<OrgDevaddrsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("org-devaddrs", "allocate");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, &request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))?;
+        let audit_entry = audit::Entry::new(
+            "org.allocate-devaddrs",
+            Some(request.oui),
+            &signer,
+            &request,
+        );
+
+        let (org, constraint) =
+            org::allocate_devaddrs(request.oui, request.devaddrs, audit_entry, &self.pool)
+                .await
+                .map_err(Status::from)?;
+        tracing::info!(
+            oui = request.oui,
+            start_addr = %constraint.start_addr,
+            end_addr = %constraint.end_addr,
+            signer = signer.to_string(),
+            "org devaddrs allocated"
+        );
+
+        self.devaddrs_response(org, constraint)
+    }
+
+    async fn release(
+        &self,
+        request: Request<OrgReleaseDevaddrsReqV1>,
+    ) -> GrpcResult<OrgDevaddrsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("org-devaddrs", "release");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, &request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))?;
+        let constraint: DevAddrConstraint = request
+            .constraint
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("missing devaddr constraint"))?
+            .into();
+        let audit_entry =
+            audit::Entry::new("org.release-devaddrs", Some(request.oui), &signer, &request);
+
+        let org = org::release_devaddrs(request.oui, constraint.clone(), audit_entry, &self.pool)
+            .await
+            .map_err(Status::from)?;
+        tracing::info!(
+            oui = request.oui,
+            start_addr = %constraint.start_addr,
+            end_addr = %constraint.end_addr,
+            signer = signer.to_string(),
+            "org devaddrs released"
+        );
+
+        self.devaddrs_response(org, constraint)
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    ext::{self, OrgUnlockReqV1, OrgUnlockResV1},
+    signature_guard, telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+/// Clears the mutation locks taken by the signature guard
+pub struct OrgLockService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl OrgLockService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+        })
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::org_lock_server::OrgLock for OrgLockService {
+    async fn unlock(&self, request: Request<OrgUnlockReqV1>) -> GrpcResult<OrgUnlockResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("org-lock", "unlock");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, &request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))?;
+
+        let was_locked = signature_guard::unlock(request.oui, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(oui = request.oui, reason = ?err, "org unlock failed");
+                Status::internal("org unlock failed")
+            })?;
+        tracing::info!(
+            oui = request.oui,
+            was_locked,
+            signer = signer.to_string(),
+            "org mutation lock cleared"
+        );
+
+        let mut resp = OrgUnlockResV1 {
+            oui: request.oui,
+            was_locked,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    ext::{self, OrgUpdateOwnerReqV1, OrgUpdateOwnerResV1},
+    org,
+    signature_guard::SignatureGuard,
+    telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+pub struct OrgOwnerService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+    signature_guard: SignatureGuard,
+}
+
+impl OrgOwnerService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        signature_guard: SignatureGuard,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+            signature_guard,
+        })
+    }
+
+    /// The current owner authorizes a rotation; an administrator may stand in
+    /// for an owner that lost its key
+    async fn verify_rotation_signature(
+        &self,
+        signer: &PublicKey,
+        request: &OrgUpdateOwnerReqV1,
+    ) -> Result<(), Status> {
+        if self
+            .auth_cache
+            .verify_signature_with_type(KeyType::Administrator, signer, request)
+            .is_ok()
+        {
+            tracing::info!(
+                oui = request.oui,
+                signer = signer.to_string(),
+                "owner rotation authorized by admin"
+            );
+            return Ok(());
+        }
+
+        self.signature_guard.check_unlocked(request.oui).await?;
+        let org_owner = org::get(request.oui, &self.pool)
+            .await
+            .map_err(|_| Status::internal("auth verification error"))?
+            .ok_or_else(|| Status::not_found(format!("oui: {}", request.oui)))?
+            .owner;
+        if org_owner == signer.clone().into() && request.verify(signer).is_ok() {
+            return Ok(());
+        }
+
+        self.signature_guard
+            .record_failure(request.oui, signer, "org-owner")
+            .await;
+        Err(Status::permission_denied("unauthorized request signature"))
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::org_owner_server::OrgOwner for OrgOwnerService {
+    async fn update_owner(
+        &self,
+        request: Request<OrgUpdateOwnerReqV1>,
+    ) -> GrpcResult<OrgUpdateOwnerResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("org-owner", "update-owner");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_rotation_signature(&signer, &request).await?;
+        let new_owner = verify_public_key(&request.owner)?;
+
+        let (org, old_owner) =
+            org::rotate_owner(request.oui, new_owner.into(), &signer.into(), &self.pool)
+                .await
+                .map_err(Status::from)?;
+        tracing::info!(
+            oui = org.oui,
+            %old_owner,
+            new_owner = %org.owner,
+            "org owner rotated"
+        );
+
+        let mut resp = OrgUpdateOwnerResV1 {
+            org: Some(org.into()),
+            old_owner: old_owner.into(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
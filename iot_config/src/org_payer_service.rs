@@ -0,0 +1,239 @@
+use crate::{
+    admin::AuthCache,
+    ext::{
+        self, OrgPayerChangeV1, OrgPayerStreamReqV1, OrgPayerStreamResV1, OrgUpdatePayerReqV1,
+        OrgUpdatePayerResV1,
+    },
+    org::{self, PayerChange},
+    signature_guard::SignatureGuard,
+    telemetry, update_channel, verify_public_key, GrpcResult, GrpcStreamResult, Settings,
+};
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tonic::{Request, Response, Status};
+
+/// Cadence at which scheduled payer changes are checked for having become
+/// effective
+const APPLY_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct OrgPayerService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    update_channel: broadcast::Sender<OrgPayerStreamResV1>,
+    shutdown: triggered::Listener,
+    signing_key: Arc<Keypair>,
+    signature_guard: SignatureGuard,
+}
+
+impl OrgPayerService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        shutdown: triggered::Listener,
+        signature_guard: SignatureGuard,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            update_channel: update_channel(),
+            shutdown,
+            signing_key: Arc::new(settings.signing_keypair()?),
+            signature_guard,
+        })
+    }
+
+    /// Task applying scheduled payer changes once they become effective
+    pub fn scheduler(&self) -> PayerChangeScheduler {
+        PayerChangeScheduler {
+            pool: self.pool.clone(),
+            update_channel: self.update_channel.clone(),
+            signing_key: self.signing_key.clone(),
+        }
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::org_payer_server::OrgPayer for OrgPayerService {
+    async fn update_payer(
+        &self,
+        request: Request<OrgUpdatePayerReqV1>,
+    ) -> GrpcResult<OrgUpdatePayerResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("org-payer", "update-payer");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.signature_guard.check_unlocked(request.oui).await?;
+        if request.verify(&signer).is_err() {
+            self.signature_guard
+                .record_failure(request.oui, &signer, "org-payer")
+                .await;
+            return Err(Status::permission_denied("invalid owner signature"));
+        }
+        let payer = verify_public_key(&request.payer)?;
+        if request.verify_payer().is_err() {
+            self.signature_guard
+                .record_failure(request.oui, &payer, "org-payer")
+                .await;
+            return Err(Status::permission_denied("invalid payer signature"));
+        }
+
+        let effective_at = Utc
+            .timestamp_opt(request.effective_at as i64, 0)
+            .single()
+            .ok_or_else(|| Status::invalid_argument("invalid effective_at"))?;
+
+        let change = org::record_payer_change(
+            request.oui,
+            payer.into(),
+            signer.into(),
+            effective_at,
+            &self.pool,
+        )
+        .await
+        .map_err(Status::from)?;
+
+        tracing::info!(
+            oui = change.oui,
+            old_payer = %change.old_payer,
+            new_payer = %change.new_payer,
+            effective_at = %change.effective_at,
+            "payer change recorded"
+        );
+        if change.applied {
+            publish_change(&change, &self.signing_key, &self.update_channel)?;
+        }
+
+        let mut resp = OrgUpdatePayerResV1 {
+            change: Some((&change).into()),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+
+    type streamStream = GrpcStreamResult<OrgPayerStreamResV1>;
+    async fn stream(
+        &self,
+        request: Request<OrgPayerStreamReqV1>,
+    ) -> GrpcResult<Self::streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("org-payer", "stream");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+
+        tracing::info!("client subscribed to payer change stream");
+        let shutdown_listener = self.shutdown.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(20);
+        let mut payer_updates = self.update_channel.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                let shutdown = shutdown_listener.clone();
+
+                tokio::select! {
+                    _ = shutdown => return,
+                    msg = payer_updates.recv() => match msg {
+                        Ok(update) => {
+                            if tx.send(Ok(update)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            // changes were dropped for this subscriber, close
+                            // the stream so it refreshes its payers
+                            tracing::warn!(
+                                skipped,
+                                "payer stream subscriber lagged; closing stream"
+                            );
+                            _ = tx
+                                .send(Err(Status::aborted("payer stream lagged, resubscribe")))
+                                .await;
+                            return;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+pub struct PayerChangeScheduler {
+    pool: Pool<Postgres>,
+    update_channel: broadcast::Sender<OrgPayerStreamResV1>,
+    signing_key: Arc<Keypair>,
+}
+
+impl PayerChangeScheduler {
+    pub async fn run(self, shutdown: &triggered::Listener) -> Result<()> {
+        tracing::info!("starting payer change scheduler");
+        let mut trigger = tokio::time::interval(APPLY_INTERVAL);
+        loop {
+            let shutdown = shutdown.clone();
+            tokio::select! {
+                _ = shutdown => break,
+                _ = trigger.tick() => {
+                    let changes = org::apply_due_payer_changes(Utc::now(), &self.pool).await?;
+                    for change in changes {
+                        tracing::info!(oui = change.oui, "scheduled payer change applied");
+                        publish_change(&change, &self.signing_key, &self.update_channel)?;
+                    }
+                }
+            }
+        }
+        tracing::info!("stopping payer change scheduler");
+        Ok(())
+    }
+}
+
+fn publish_change(
+    change: &PayerChange,
+    signing_key: &Keypair,
+    update_channel: &broadcast::Sender<OrgPayerStreamResV1>,
+) -> Result<(), Status> {
+    let mut update = OrgPayerStreamResV1 {
+        change: Some(change.into()),
+        timestamp: Utc::now().encode_timestamp(),
+        signer: signing_key.public_key().into(),
+        signature: vec![],
+    };
+    update.signature = signing_key
+        .sign(&update.encode_to_vec())
+        .map_err(|_| Status::internal("payer change signing error"))?;
+    if update_channel.send(update).is_err() {
+        tracing::info!(oui = change.oui, "no payer stream subscribers");
+    }
+    Ok(())
+}
+
+impl From<&PayerChange> for OrgPayerChangeV1 {
+    fn from(change: &PayerChange) -> Self {
+        Self {
+            oui: change.oui,
+            old_payer: change.old_payer.clone().into(),
+            new_payer: change.new_payer.clone().into(),
+            effective_at: change.effective_at.encode_timestamp(),
+        }
+    }
+}
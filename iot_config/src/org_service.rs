@@ -1,13 +1,14 @@
 use crate::{
     admin::{AuthCache, KeyType},
-    helium_netids, lora_field, org,
+    audit, helium_netids, lora_field, org,
     route::list_routes,
+    signature_guard::SignatureGuard,
     telemetry, verify_public_key, GrpcResult, Settings,
 };
 use anyhow::Result;
 use chrono::Utc;
 use file_store::traits::{MsgVerify, TimestampEncode};
-use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
 use helium_proto::{
     services::iot_config::{
         self, route_stream_res_v1, ActionV1, DevaddrConstraintV1, OrgCreateHeliumReqV1,
@@ -24,8 +25,10 @@ pub struct OrgService {
     auth_cache: AuthCache,
     pool: Pool<Postgres>,
     route_update_tx: broadcast::Sender<RouteStreamResV1>,
+    org_update_tx: broadcast::Sender<org::Org>,
     signing_key: Keypair,
     delegate_updater: watch::Sender<org::DelegateCache>,
+    signature_guard: SignatureGuard,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -40,14 +43,18 @@ impl OrgService {
         auth_cache: AuthCache,
         pool: Pool<Postgres>,
         route_update_tx: broadcast::Sender<RouteStreamResV1>,
+        org_update_tx: broadcast::Sender<org::Org>,
         delegate_updater: watch::Sender<org::DelegateCache>,
+        signature_guard: SignatureGuard,
     ) -> Result<Self> {
         Ok(Self {
             auth_cache,
             pool,
             route_update_tx,
+            org_update_tx,
             signing_key: settings.signing_keypair()?,
             delegate_updater,
+            signature_guard,
         })
     }
 
@@ -89,6 +96,7 @@ impl OrgService {
             return Ok(UpdateAuthorizer::Admin);
         }
 
+        self.signature_guard.check_unlocked(request.oui).await?;
         let org_owner = org::get(request.oui, &self.pool)
             .await
             .transpose()
@@ -103,9 +111,24 @@ impl OrgService {
             return Ok(UpdateAuthorizer::Org);
         }
 
+        self.signature_guard
+            .record_failure(request.oui, signer, "org")
+            .await;
         Err(Status::permission_denied("unauthorized request signature"))
     }
 
+    /// Reflect delegate key updates of an org in the delegate cache
+    fn update_delegate_cache(&self, added: &[PublicKeyBinary], removed: &[PublicKeyBinary]) {
+        self.delegate_updater.send_if_modified(|cache| {
+            let removed = removed
+                .iter()
+                .fold(false, |acc, key| cache.remove(key) || acc);
+            added
+                .iter()
+                .fold(removed, |acc, key| cache.insert(key.clone()) || acc)
+        });
+    }
+
     fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
         self.signing_key
             .sign(response)
@@ -183,6 +206,7 @@ impl iot_config::Org for OrgService {
 
         let signer = verify_public_key(&request.signer)?;
         self.verify_admin_request_signature(&signer, &request)?;
+        let audit_entry = audit::Entry::new("org.create-helium", None, &signer, &request);
 
         let mut verify_keys: Vec<&[u8]> = vec![request.owner.as_ref(), request.payer.as_ref()];
         let mut verify_delegates: Vec<&[u8]> = request
@@ -204,29 +228,9 @@ impl iot_config::Org for OrgService {
         tracing::debug!("create helium org request: {request:?}");
 
         let net_id = request.net_id();
-        let requested_addrs = if request.devaddrs >= 8 && request.devaddrs % 2 == 0 {
-            request.devaddrs
-        } else {
-            return Err(Status::invalid_argument(format!(
-                "{} devaddrs requested; minimum 8, even number required",
-                request.devaddrs
-            )));
-        };
-
-        let mut txn = self
-            .pool
-            .begin()
-            .await
-            .map_err(|_| Status::internal("error saving org record"))?;
-        let devaddr_constraints = helium_netids::checkout_devaddr_constraints(&mut txn, requested_addrs, net_id.into())
-            .await
-            .map_err(|err| {
-                tracing::error!(?net_id, count = %requested_addrs, reason = ?err, "failed to retrieve available helium devaddrs");
-                Status::failed_precondition("helium addresses unavailable")
-            })?;
         let helium_netid_field = helium_netids::HeliumNetId::from(net_id).id();
 
-        let org = org::create_org(
+        let org = org::create_helium_org(
             request.owner.into(),
             request.payer.into(),
             request
@@ -234,20 +238,22 @@ impl iot_config::Org for OrgService {
                 .into_iter()
                 .map(|key| key.into())
                 .collect(),
-            helium_netid_field,
-            &devaddr_constraints,
-            &mut txn,
+            net_id.into(),
+            request.devaddrs,
+            audit_entry,
+            &self.pool,
         )
         .await
         .map_err(|err| {
-            tracing::error!(reason = ?err, "org save failed");
-            Status::internal(format!("org save failed: {err:?}"))
+            tracing::error!(
+                ?net_id,
+                count = %request.devaddrs,
+                reason = ?err,
+                "failed to create org"
+            );
+            Status::from(err)
         })?;
 
-        txn.commit()
-            .await
-            .map_err(|_| Status::internal("error saving org record"))?;
-
         org.delegate_keys.as_ref().map(|keys| {
             self.delegate_updater.send_if_modified(|cache| {
                 keys.iter().fold(
@@ -289,6 +295,7 @@ impl iot_config::Org for OrgService {
 
         let signer = verify_public_key(&request.signer)?;
         self.verify_admin_request_signature(&signer, &request)?;
+        let audit_entry = audit::Entry::new("org.create-roamer", None, &signer, &request);
 
         let mut verify_keys: Vec<&[u8]> = vec![request.owner.as_ref(), request.payer.as_ref()];
         let mut verify_delegates: Vec<&[u8]> = request
@@ -309,11 +316,8 @@ impl iot_config::Org for OrgService {
         tracing::debug!("create roamer org request: {request:?}");
 
         let net_id = lora_field::net_id(request.net_id);
-        let devaddr_range = net_id
-            .full_range()
-            .map_err(|_| Status::invalid_argument("invalid net_id"))?;
 
-        let org = org::create_org(
+        let org = org::create_roamer_org(
             request.owner.into(),
             request.payer.into(),
             request
@@ -322,13 +326,13 @@ impl iot_config::Org for OrgService {
                 .map(|key| key.into())
                 .collect(),
             net_id,
-            &[devaddr_range],
+            audit_entry,
             &self.pool,
         )
         .await
         .map_err(|err| {
             tracing::error!(reason = ?err, "failed to create org");
-            Status::internal(format!("org save failed: {err:?}"))
+            Status::from(err)
         })?;
 
         org.delegate_keys.as_ref().map(|keys| {
@@ -374,13 +378,48 @@ impl iot_config::Org for OrgService {
         let authorizer = self
             .verify_update_request_signature(&signer, &request)
             .await?;
+        let audit_entry = audit::Entry::new("org.update", Some(request.oui), &signer, &request);
+
+        let mut removed: Vec<PublicKeyBinary> = request
+            .updates
+            .iter()
+            .filter_map(|update| match &update.update {
+                Some(org::proto::Update::DelegateKey(delegate_key_update))
+                    if delegate_key_update.action() == ActionV1::Remove =>
+                {
+                    Some(delegate_key_update.delegate_key.clone().into())
+                }
+                _ => None,
+            })
+            .collect();
 
-        let org = org::update_org(request.oui, authorizer, request.updates, &self.pool)
+        let org = org::update_org(
+            request.oui,
+            authorizer,
+            &signer.clone().into(),
+            request.updates,
+            audit_entry,
+            &self.pool,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!(reason = ?err, "org update failed");
+            Status::from(err)
+        })?;
+        let delegate_keys = org.delegate_keys.clone().unwrap_or_default();
+        // removing a key the org didn't delegate is a no-op, a key another
+        // org delegates stays in the cache. Failing to tell, the keys are
+        // dropped from the cache rather than left signing for the org.
+        let still_delegated = org::delegated_keys(&self.pool, &removed)
             .await
-            .map_err(|err| {
-                tracing::error!(reason = ?err, "org update failed");
-                Status::internal(format!("org update failed: {err:?}"))
-            })?;
+            .unwrap_or_else(|err| {
+                tracing::error!(reason = ?err, "failed to fetch delegated keys");
+                Default::default()
+            });
+        removed.retain(|key| !still_delegated.contains(key));
+        self.update_delegate_cache(&delegate_keys, &removed);
+        // no receivers only means no webhook dispatcher is running
+        let _ = self.org_update_tx.send(org.clone());
 
         let net_id = org::get_org_netid(org.oui, &self.pool)
             .await
@@ -416,20 +455,30 @@ impl iot_config::Org for OrgService {
         let signer = verify_public_key(&request.signer)?;
         self.verify_request_signature(&signer, &request)?;
 
-        if !org::is_locked(request.oui, &self.pool)
+        let disable_failed = |err: sqlx::Error| {
+            tracing::error!(
+                org = request.oui,
+                reason = ?err,
+                "failed to disable org with reason"
+            );
+            Status::internal(format!("org disable failed for: {}", request.oui))
+        };
+        let mut txn = self.pool.begin().await.map_err(disable_failed)?;
+        if !org::is_locked(request.oui, &mut txn)
             .await
             .map_err(|_| Status::internal("error retrieving current status"))?
         {
-            org::toggle_locked(request.oui, &self.pool)
+            org::toggle_locked(request.oui, &mut txn)
                 .await
-                .map_err(|err| {
-                    tracing::error!(
-                        org = request.oui,
-                        reason = ?err,
-                        "failed to disable org with reason"
-                    );
-                    Status::internal(format!("org disable failed for: {}", request.oui))
-                })?;
+                .map_err(disable_failed)?;
+            let audit_entry =
+                audit::Entry::new("org.disable", Some(request.oui), &signer, &request)
+                    .before(&serde_json::json!({ "locked": false }))
+                    .after(&serde_json::json!({ "locked": true }));
+            audit::record(audit_entry, &mut txn)
+                .await
+                .map_err(disable_failed)?;
+            txn.commit().await.map_err(disable_failed)?;
 
             let org_routes = list_routes(request.oui, &self.pool).await.map_err(|err| {
                 tracing::error!(
@@ -484,20 +533,29 @@ impl iot_config::Org for OrgService {
         let signer = verify_public_key(&request.signer)?;
         self.verify_request_signature(&signer, &request)?;
 
-        if org::is_locked(request.oui, &self.pool)
+        let enable_failed = |err: sqlx::Error| {
+            tracing::error!(
+                org = request.oui,
+                reason = ?err,
+                "failed to enable org with reason"
+            );
+            Status::internal(format!("org enable failed for: {}", request.oui))
+        };
+        let mut txn = self.pool.begin().await.map_err(enable_failed)?;
+        if org::is_locked(request.oui, &mut txn)
             .await
             .map_err(|_| Status::internal("error retrieving current status"))?
         {
-            org::toggle_locked(request.oui, &self.pool)
+            org::toggle_locked(request.oui, &mut txn)
                 .await
-                .map_err(|err| {
-                    tracing::error!(
-                        org = request.oui,
-                        reason = ?err,
-                        "failed to enable org with reason"
-                    );
-                    Status::internal(format!("org enable failed for: {}", request.oui))
-                })?;
+                .map_err(enable_failed)?;
+            let audit_entry = audit::Entry::new("org.enable", Some(request.oui), &signer, &request)
+                .before(&serde_json::json!({ "locked": true }))
+                .after(&serde_json::json!({ "locked": false }));
+            audit::record(audit_entry, &mut txn)
+                .await
+                .map_err(enable_failed)?;
+            txn.commit().await.map_err(enable_failed)?;
 
             let org_routes = list_routes(request.oui, &self.pool).await.map_err(|err| {
                 tracing::error!(
@@ -0,0 +1,283 @@
+use crate::{
+    admin::AuthCache,
+    ext::{self, OrgSnapshotStreamReqV1, OrgSnapshotStreamResV1},
+    lora_field::NetIdField,
+    org::Org,
+    route::{Route, RouteServer},
+    telemetry, verify_public_key, GrpcResult, GrpcStreamResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use futures::TryStreamExt;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use sqlx::{postgres::PgRow, types::Uuid, FromRow, Pool, Postgres, Row};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+
+/// Streams every org along with its devaddr constraints and routes, for
+/// routers to bootstrap from in one pass
+pub struct OrgSnapshotService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Arc<Keypair>,
+}
+
+impl OrgSnapshotService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: Arc::new(settings.signing_keypair()?),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ext::org_snapshot_server::OrgSnapshot for OrgSnapshotService {
+    type streamStream = GrpcStreamResult<OrgSnapshotStreamResV1>;
+    async fn stream(
+        &self,
+        request: Request<OrgSnapshotStreamReqV1>,
+    ) -> GrpcResult<Self::streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("org-snapshot", "stream");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+
+        let pool = self.pool.clone();
+        let signing_key = self.signing_key.clone();
+        let (tx, rx) = mpsc::channel(20);
+        tokio::spawn(async move {
+            if let Err(err) = stream_snapshot(&pool, &signing_key, &tx).await {
+                tracing::error!(reason = ?err, "org snapshot stream failed");
+                _ = tx.send(Err(err)).await;
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+struct OrgSnapshot {
+    org: Org,
+    net_id: u32,
+    routes: Vec<Route>,
+}
+
+/// Every org with its constraints, delegate keys and routes, one row per
+/// route of the org, or a single row without a route for an org with none.
+/// The rows of an org are consecutive, ordered by oui
+const SNAPSHOT_SQL: &str = r#"
+    select org.oui, org.owner_pubkey, org.payer_pubkey, org.locked,
+        array(select (start_addr, end_addr) from organization_devaddr_constraints org_const where org_const.oui = org.oui) as constraints,
+        array(select delegate_pubkey from organization_delegate_keys org_delegates where org_delegates.oui = org.oui) as delegate_keys,
+        (select net_id from organization_devaddr_constraints org_const where org_const.oui = org.oui limit 1) as org_net_id,
+        r.id as route_id, r.net_id as route_net_id, r.max_copies, r.server_host, r.server_port, r.server_protocol_opts, r.active, r.ignore_empty_skf
+    from organizations org
+    left join routes r on r.oui = org.oui
+    order by org.oui, r.id
+"#;
+
+impl OrgSnapshot {
+    fn from_row(row: &PgRow) -> Result<Self> {
+        Ok(Self {
+            org: Org::from_row(row)?,
+            net_id: row
+                .try_get::<Option<i32>, &str>("org_net_id")?
+                .map_or(0, |net_id| NetIdField::from(net_id).into()),
+            routes: vec![],
+        })
+    }
+
+    /// Add the route of `row`, if any
+    fn add_route(&mut self, row: &PgRow) -> Result<()> {
+        let Some(id) = row.try_get::<Option<Uuid>, &str>("route_id")? else {
+            return Ok(());
+        };
+        self.routes.push(Route {
+            id: id.to_string(),
+            net_id: row.try_get::<i32, &str>("route_net_id")?.into(),
+            oui: self.org.oui,
+            server: RouteServer::new(
+                row.try_get("server_host")?,
+                row.try_get::<i32, &str>("server_port")? as u32,
+                serde_json::from_value(row.try_get("server_protocol_opts")?)?,
+            ),
+            max_copies: row.try_get::<i32, &str>("max_copies")? as u32,
+            active: row.try_get("active")?,
+            locked: self.org.locked,
+            ignore_empty_skf: row.try_get("ignore_empty_skf")?,
+        });
+        Ok(())
+    }
+}
+
+/// Stream every org with its constraints and routes, read in a single
+/// repeatable read transaction so they are consistent with each other. The
+/// snapshot version is the first transaction id not yet assigned when it was
+/// taken. Orgs are read one query row at a time and sent once all of their
+/// routes are read, never holding the whole snapshot in memory.
+async fn stream_snapshot(
+    pool: &Pool<Postgres>,
+    signing_key: &Keypair,
+    tx: &mpsc::Sender<Result<OrgSnapshotStreamResV1, Status>>,
+) -> Result<(), Status> {
+    let mut txn = pool.begin().await.map_err(read_error)?;
+    sqlx::query("set transaction isolation level repeatable read, read only")
+        .execute(&mut txn)
+        .await
+        .map_err(read_error)?;
+    let snapshot_version =
+        sqlx::query_scalar::<_, i64>(" select txid_snapshot_xmax(txid_current_snapshot()) ")
+            .fetch_one(&mut txn)
+            .await
+            .map_err(read_error)? as u64;
+
+    let signer: Vec<u8> = signing_key.public_key().into();
+    let mut orgs = 0;
+    {
+        let mut rows = sqlx::query(SNAPSHOT_SQL).fetch(&mut txn);
+        let mut snapshot: Option<OrgSnapshot> = None;
+        loop {
+            let row = rows.try_next().await.map_err(read_error)?;
+            let same_org = match (&row, &snapshot) {
+                (Some(row), Some(snapshot)) => {
+                    row.try_get::<i64, &str>("oui").map_err(read_error)? as u64 == snapshot.org.oui
+                }
+                _ => false,
+            };
+            if !same_org {
+                if let Some(done) = snapshot.take() {
+                    let response = signed_response(done, snapshot_version, &signer, signing_key)?;
+                    if tx.send(Ok(response)).await.is_err() {
+                        return Ok(());
+                    }
+                    orgs += 1;
+                }
+            }
+            let Some(row) = row else {
+                break;
+            };
+            if snapshot.is_none() {
+                snapshot = Some(OrgSnapshot::from_row(&row).map_err(read_error)?);
+            }
+            if let Some(current) = snapshot.as_mut() {
+                current.add_route(&row).map_err(read_error)?;
+            }
+        }
+    }
+    txn.commit().await.map_err(read_error)?;
+
+    tracing::debug!(snapshot_version, orgs, "streamed org snapshot");
+    Ok(())
+}
+
+fn read_error(err: impl Into<anyhow::Error>) -> Status {
+    Status::internal(format!("org snapshot read failed: {:?}", err.into()))
+}
+
+fn signed_response(
+    OrgSnapshot {
+        org,
+        net_id,
+        routes,
+    }: OrgSnapshot,
+    snapshot_version: u64,
+    signer: &[u8],
+    signing_key: &Keypair,
+) -> Result<OrgSnapshotStreamResV1, Status> {
+    let mut response = OrgSnapshotStreamResV1 {
+        snapshot_version,
+        net_id,
+        devaddr_constraints: org
+            .constraints
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|constraint| constraint.into())
+            .collect(),
+        routes: routes.into_iter().map(|route| route.into()).collect(),
+        org: Some(org.into()),
+        timestamp: Utc::now().encode_timestamp(),
+        signer: signer.to_vec(),
+        signature: vec![],
+    };
+    response.signature = signing_key
+        .sign(&response.encode_to_vec())
+        .map_err(|_| Status::internal("response signing error"))?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        org, route,
+        test_support::{self, helium_org, keypair},
+    };
+    use helium_crypto::Verify;
+
+    async fn add_route(oui: u64, max_copies: u32, pool: &Pool<Postgres>) {
+        route::insert_route(&test_support::route(oui, max_copies), pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn orgs_are_streamed_with_their_routes(pool: Pool<Postgres>) {
+        let with_routes = helium_org(&pool).await;
+        let without_routes = helium_org(&pool).await;
+        let locked = helium_org(&pool).await;
+        add_route(with_routes.oui, 1, &pool).await;
+        add_route(with_routes.oui, 2, &pool).await;
+        add_route(locked.oui, 3, &pool).await;
+        org::toggle_locked(locked.oui, &pool).await.unwrap();
+
+        let signing_key = keypair();
+        let (tx, mut rx) = mpsc::channel(20);
+        stream_snapshot(&pool, &signing_key, &tx).await.unwrap();
+        drop(tx);
+        let mut responses = vec![];
+        while let Some(response) = rx.recv().await {
+            responses.push(response.unwrap());
+        }
+
+        let orgs: Vec<(u64, Vec<u32>)> = responses
+            .iter()
+            .map(|response| {
+                let mut max_copies: Vec<u32> = response
+                    .routes
+                    .iter()
+                    .map(|route| route.max_copies)
+                    .collect();
+                max_copies.sort();
+                (response.org.as_ref().unwrap().oui, max_copies)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (with_routes.oui, vec![1, 2]),
+                (without_routes.oui, vec![]),
+                (locked.oui, vec![3]),
+            ],
+            orgs
+        );
+        assert!(responses[2].routes[0].locked);
+        assert!(responses.iter().all(|response| response.net_id == 0x00003c
+            && response.devaddr_constraints.len() == 1
+            && response.snapshot_version == responses[0].snapshot_version));
+
+        let mut unsigned = responses[0].clone();
+        let signature = std::mem::take(&mut unsigned.signature);
+        assert!(signing_key
+            .public_key()
+            .verify(&unsigned.encode_to_vec(), &signature)
+            .is_ok());
+    }
+}
@@ -0,0 +1,239 @@
+//! Read-only caching proxy of the `gateway` apis, needing no database access
+//! so it can be deployed close to the gateways of a region.
+
+use crate::{telemetry, GrpcResult, GrpcStreamResult};
+use config_proxy::{forward_stream, verify_request_signer, AcceptedSigners, ResponseCache};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::services::{
+    iot_config::{
+        self, gateway_client::GatewayClient, GatewayInfoReqV1, GatewayInfoResV1,
+        GatewayInfoStreamReqV1, GatewayInfoStreamResV1, GatewayLocationReqV1, GatewayLocationResV1,
+        GatewayRegionParamsReqV1, GatewayRegionParamsResV1,
+    },
+    Channel, Endpoint,
+};
+use serde::Deserialize;
+use std::{
+    net::{AddrParseError, SocketAddr},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tonic::{Request, Response};
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// RUST_LOG compatible settings string. Default to
+    /// "iot_config=info"
+    #[serde(default = "default_log")]
+    pub log: String,
+    /// Listen address. Default is 0.0.0.0:8080
+    #[serde(default = "crate::settings::default_listen_addr")]
+    pub listen: String,
+    /// grpc url of the upstream config service
+    #[serde(with = "http_serde::uri")]
+    pub upstream: http::Uri,
+    /// B58 encoded public key of the upstream config service, verifying the
+    /// responses it signs
+    pub upstream_pubkey: String,
+    /// Connect timeout to the upstream. Default 5s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_connect_timeout"
+    )]
+    pub connect_timeout: Duration,
+    /// RPC timeout to the upstream. Default 5s
+    #[serde(with = "settings_loader::duration", default = "default_rpc_timeout")]
+    pub rpc_timeout: Duration,
+    /// How long a response is served from the cache before it is revalidated
+    /// with the upstream. Default is 5m
+    #[serde(with = "settings_loader::duration", default = "default_ttl")]
+    pub ttl: Duration,
+    /// Max age of the cached responses served while the upstream fails.
+    /// Default is 1h
+    #[serde(with = "settings_loader::duration", default = "default_max_stale")]
+    pub max_stale: Duration,
+    pub metrics: poc_metrics::Settings,
+}
+
+pub fn default_log() -> String {
+    "iot_config=info".to_string()
+}
+
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_rpc_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_ttl() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_max_stale() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+impl Settings {
+    /// Settings can be loaded from a given optional path and
+    /// can be overridden with environment variables.
+    ///
+    /// Environment overrides have the same name as the entries
+    /// in the settings file in uppercase and prefixed with "CFG_".
+    /// Example: "CFG_UPSTREAM" will override the upstream url.
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::Loader::new("CFG")
+            .env_separator("__")
+            .file(path)
+            .load()
+    }
+
+    pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
+        SocketAddr::from_str(&self.listen)
+    }
+
+    pub fn upstream_pubkey(&self) -> Result<PublicKey, helium_crypto::Error> {
+        PublicKey::from_str(&self.upstream_pubkey)
+    }
+
+    fn upstream_endpoint(&self) -> Endpoint {
+        Endpoint::from(self.upstream.clone())
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.rpc_timeout)
+    }
+
+    /// Check the upstream accepts connections, for preflight checks
+    pub async fn check_upstream(&self) -> Result<(), tonic::transport::Error> {
+        self.upstream_endpoint().connect().await.map(|_| ())
+    }
+}
+
+/// Serves the location, region params and info apis from a cache in front of
+/// the upstream, see [`config_proxy`]. Region params requests are signed by
+/// the gateway itself and verified by the proxy, location and info requests
+/// are served to the signers in [`AcceptedSigners`]. `info_stream` is passed
+/// through uncached
+#[derive(Clone)]
+pub struct GatewayProxy {
+    upstream: GatewayClient<Channel>,
+    locations: ResponseCache<PublicKeyBinary, GatewayLocationResV1>,
+    region_params: ResponseCache<(PublicKeyBinary, i32), GatewayRegionParamsResV1>,
+    infos: ResponseCache<PublicKeyBinary, GatewayInfoResV1>,
+    location_signers: AcceptedSigners,
+    info_signers: AcceptedSigners,
+}
+
+impl GatewayProxy {
+    pub fn new(settings: &Settings) -> anyhow::Result<Self> {
+        let upstream_pubkey = Arc::new(settings.upstream_pubkey()?);
+        let response_cache = || {
+            ResponseCache::new(
+                upstream_pubkey.clone(),
+                settings.ttl,
+                settings.max_stale,
+                telemetry::count_proxy_lookup,
+            )
+        };
+        Ok(Self {
+            upstream: GatewayClient::new(settings.upstream_endpoint().connect_lazy()),
+            locations: response_cache(),
+            region_params: response_cache(),
+            infos: response_cache(),
+            location_signers: AcceptedSigners::new(settings.ttl),
+            info_signers: AcceptedSigners::new(settings.ttl),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl iot_config::Gateway for GatewayProxy {
+    async fn location(
+        &self,
+        request: Request<GatewayLocationReqV1>,
+    ) -> GrpcResult<GatewayLocationResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "location");
+
+        let signer = verify_request_signer(&request.signer, &request)?;
+        let authorized = self.location_signers.contains(&signer).await;
+
+        let address = request.gateway.clone().into();
+        let mut upstream = self.upstream.clone();
+        let response = self
+            .locations
+            .get_or_fetch("location", address, authorized, async move {
+                upstream.location(request).await
+            })
+            .await?;
+        if !authorized {
+            self.location_signers.insert(signer).await;
+        }
+
+        Ok(Response::new(response))
+    }
+
+    async fn region_params(
+        &self,
+        request: Request<GatewayRegionParamsReqV1>,
+    ) -> GrpcResult<GatewayRegionParamsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "region-params");
+
+        // signed by the gateway itself, which is all the upstream checks
+        let address = verify_request_signer(&request.address, &request)?;
+
+        let key = (address, request.region);
+        let mut upstream = self.upstream.clone();
+        let response = self
+            .region_params
+            .get_or_fetch("region-params", key, true, async move {
+                upstream.region_params(request).await
+            })
+            .await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn info(&self, request: Request<GatewayInfoReqV1>) -> GrpcResult<GatewayInfoResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "info");
+
+        let signer = verify_request_signer(&request.signer, &request)?;
+        let authorized = self.info_signers.contains(&signer).await;
+
+        let address = request.address.clone().into();
+        let mut upstream = self.upstream.clone();
+        let response = self
+            .infos
+            .get_or_fetch("info", address, authorized, async move {
+                upstream.info(request).await
+            })
+            .await?;
+        if !authorized {
+            self.info_signers.insert(signer).await;
+        }
+
+        Ok(Response::new(response))
+    }
+
+    type info_streamStream = GrpcStreamResult<GatewayInfoStreamResV1>;
+    async fn info_stream(
+        &self,
+        request: Request<GatewayInfoStreamReqV1>,
+    ) -> GrpcResult<Self::info_streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "info-stream");
+
+        let upstream = self
+            .upstream
+            .clone()
+            .info_stream(request)
+            .await?
+            .into_inner();
+
+        Ok(Response::new(forward_stream(upstream, 20)))
+    }
+}
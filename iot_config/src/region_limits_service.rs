@@ -0,0 +1,134 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    ext::{self, RegionLimitsReqV1, RegionLimitsResV1, RegionUpdateLimitsReqV1},
+    region_map::{self, RegionLimits},
+    telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::{Message, Region};
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+/// Per region limits applied by the verifiers that have no place in the
+/// region params served by the admin and gateway services
+pub struct RegionLimitsService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl RegionLimitsService {
+    pub fn new(settings: &Settings, auth_cache: AuthCache, pool: Pool<Postgres>) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+        })
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+
+    fn limits_response(
+        &self,
+        region: Region,
+        limits: RegionLimits,
+    ) -> GrpcResult<RegionLimitsResV1> {
+        let mut resp = RegionLimitsResV1 {
+            region: region.into(),
+            max_witness_distance: limits.max_witness_distance.unwrap_or_default(),
+            witness_window: limits.witness_window.unwrap_or_default(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
+
+/// Zero clears a limit
+fn limit(value: u32) -> Option<u32> {
+    match value {
+        0 => None,
+        value => Some(value),
+    }
+}
+
+fn parse_region(region: i32) -> Result<Region, Status> {
+    Region::from_i32(region)
+        .ok_or_else(|| Status::invalid_argument(format!("invalid lora region {region}")))
+}
+
+#[tonic::async_trait]
+impl ext::region_limits_server::RegionLimits for RegionLimitsService {
+    async fn limits(&self, request: Request<RegionLimitsReqV1>) -> GrpcResult<RegionLimitsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("region-limits", "limits");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("invalid request signature"))?;
+        let region = parse_region(request.region)?;
+
+        let limits = region_map::get_limits(region, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(%region, reason = ?err, "region limits lookup failed");
+                Status::internal("region limits lookup failed")
+            })?;
+
+        tracing::debug!(%region, ?limits, "returning region limits");
+        self.limits_response(region, limits)
+    }
+
+    async fn update_limits(
+        &self,
+        request: Request<RegionUpdateLimitsReqV1>,
+    ) -> GrpcResult<RegionLimitsResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("region-limits", "update-limits");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, &request)
+            .map_err(|_| Status::permission_denied("invalid admin signature"))?;
+        let region = parse_region(request.region)?;
+        if request.max_witness_distance > i32::MAX as u32 {
+            return Err(Status::invalid_argument(
+                "max witness distance out of range",
+            ));
+        }
+        if request.witness_window > i32::MAX as u32 {
+            return Err(Status::invalid_argument("witness window out of range"));
+        }
+
+        let limits = RegionLimits {
+            max_witness_distance: limit(request.max_witness_distance),
+            witness_window: limit(request.witness_window),
+        };
+        region_map::update_limits(region, &limits, &self.pool).await?;
+
+        tracing::info!(%region, ?limits, "region limits updated");
+        self.limits_response(region, limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_clears_limit() {
+        assert_eq!(None, limit(0));
+        assert_eq!(Some(1), limit(1));
+        assert_eq!(Some(u32::MAX), limit(u32::MAX));
+    }
+}
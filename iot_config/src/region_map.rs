@@ -1,4 +1,4 @@
-use anyhow::anyhow;
+use crate::{Error, Result};
 use futures::stream::TryStreamExt;
 use helium_proto::{BlockchainRegionParamsV1, Message, Region};
 use hextree::{compaction::EqCompactor, Cell, HexTreeMap};
@@ -20,7 +20,7 @@ pub struct RegionMapReader {
 impl RegionMapReader {
     pub async fn new(
         db: impl sqlx::PgExecutor<'_> + Copy,
-    ) -> anyhow::Result<(watch::Sender<RegionMap>, Self)> {
+    ) -> Result<(watch::Sender<RegionMap>, Self)> {
         let region_map = RegionMap::new(db).await?;
         let (map_sender, map_receiver) = watch::channel(region_map);
         Ok((map_sender, Self { map_receiver }))
@@ -36,7 +36,7 @@ impl RegionMapReader {
 }
 
 impl RegionMap {
-    pub async fn new(db: impl sqlx::PgExecutor<'_> + Copy) -> anyhow::Result<Self> {
+    pub async fn new(db: impl sqlx::PgExecutor<'_> + Copy) -> Result<Self> {
         let region_hextree = build_region_tree(db).await?;
         let params_map = build_params_map(db).await?;
         Ok(Self {
@@ -69,9 +69,13 @@ pub struct HexRegion {
     pub indexes: Option<Vec<u8>>,
 }
 
+fn parse_region(region: &str) -> Result<Region> {
+    Region::from_str(region).map_err(|_| Error::decode(format!("invalid region: {region}")))
+}
+
 pub async fn build_region_tree(
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<HexTreeMap<Region, EqCompactor>> {
+) -> Result<HexTreeMap<Region, EqCompactor>> {
     let mut region_tree = HexTreeMap::with_compactor(EqCompactor);
 
     let mut regions =
@@ -79,14 +83,16 @@ pub async fn build_region_tree(
 
     while let Some(region_row) = regions.try_next().await? {
         if let Some(indexes) = region_row.indexes {
-            let region = Region::from_str(&region_row.region)?;
-            let mut h3_idx_decoder = Decoder::new(&indexes[..])?;
+            let region = parse_region(&region_row.region)?;
+            let mut h3_idx_decoder = Decoder::new(&indexes[..]).map_err(Error::decode)?;
             let mut raw_h3_indices = Vec::new();
-            h3_idx_decoder.read_to_end(&mut raw_h3_indices)?;
+            h3_idx_decoder
+                .read_to_end(&mut raw_h3_indices)
+                .map_err(Error::decode)?;
 
             if raw_h3_indices.len() % std::mem::size_of::<u64>() != 0 {
                 tracing::error!("h3 index list malformed; indices are not an index-byte-size multiple; region: {region}");
-                return Err(anyhow!("malformed h3 indices"));
+                return Err(Error::decode("malformed h3 indices"));
             }
 
             let mut h3_idx_buf = [0_u8; 8];
@@ -99,7 +105,7 @@ pub async fn build_region_tree(
                         tracing::error!(
                             "h3 index list malformed; region, chunk, bits: {region}, {chunk_num}, {h3_idx:x}"
                         );
-                        return Err(anyhow!("malformed h3 indices"));
+                        return Err(Error::decode("malformed h3 indices"));
                     }
                 }
             }
@@ -111,14 +117,15 @@ pub async fn build_region_tree(
 
 pub async fn build_params_map(
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<HashMap<Region, BlockchainRegionParamsV1>> {
+) -> Result<HashMap<Region, BlockchainRegionParamsV1>> {
     let mut params_map: HashMap<Region, BlockchainRegionParamsV1> = HashMap::new();
 
     let mut regions = sqlx::query_as::<_, HexRegion>("select * from regions").fetch(db);
 
     while let Some(region_row) = regions.try_next().await? {
-        let region = Region::from_str(&region_row.region)?;
-        let params = BlockchainRegionParamsV1::decode(region_row.params.as_slice())?;
+        let region = parse_region(&region_row.region)?;
+        let params = BlockchainRegionParamsV1::decode(region_row.params.as_slice())
+            .map_err(Error::decode)?;
         params_map.insert(region, params);
     }
 
@@ -137,7 +144,7 @@ pub async fn update_region(
     params: &BlockchainRegionParamsV1,
     indexes: Option<&[u8]>,
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
-) -> anyhow::Result<Option<HexTreeMap<Region, EqCompactor>>> {
+) -> Result<Option<HexTreeMap<Region, EqCompactor>>> {
     let mut transaction = db.begin().await?;
 
     sqlx::query(
@@ -169,3 +176,52 @@ pub async fn update_region(
 
     Ok(updated_region)
 }
+
+/// Limits of a region applied by the verifiers, each None when the region has
+/// no limit of its own
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegionLimits {
+    /// Max distance in km between a beaconer in the region and its witnesses
+    pub max_witness_distance: Option<u32>,
+    /// Seconds from the start of a beacon's entropy within which witnesses of
+    /// a beaconer in the region are accepted
+    pub witness_window: Option<u32>,
+}
+
+pub async fn get_limits(region: Region, db: impl sqlx::PgExecutor<'_>) -> Result<RegionLimits> {
+    let limits = sqlx::query_as::<_, (Option<i32>, Option<i32>)>(
+        "select max_witness_distance, witness_window from regions where region = $1",
+    )
+    .bind(region.to_string())
+    .fetch_optional(db)
+    .await?;
+    Ok(match limits {
+        Some((max_witness_distance, witness_window)) => RegionLimits {
+            max_witness_distance: max_witness_distance.map(|distance| distance as u32),
+            witness_window: witness_window.map(|window| window as u32),
+        },
+        None => RegionLimits::default(),
+    })
+}
+
+/// Sets the limits of a region that has already been loaded, clearing those
+/// given as None
+pub async fn update_limits(
+    region: Region,
+    limits: &RegionLimits,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result {
+    let updated = sqlx::query(
+        "update regions set max_witness_distance = $2, witness_window = $3 where region = $1",
+    )
+    .bind(region.to_string())
+    .bind(limits.max_witness_distance.map(|distance| distance as i32))
+    .bind(limits.witness_window.map(|window| window as i32))
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(Error::not_found(format!("region not loaded: {region}")));
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+//! Per gateway region overrides set by administrators, for gateways close to
+//! a region border resolved to the wrong region by the region map.
+
+use crate::ext::GatewayRegionOverrideV1;
+use chrono::{DateTime, TimeZone, Utc};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::Region;
+use sqlx::{Pool, Postgres, Row};
+use std::{collections::HashMap, str::FromStr};
+use tonic::Status;
+
+/// The region to serve a gateway instead of the one of the region map, until
+/// `expires_at`
+#[derive(Clone, Debug)]
+pub struct RegionOverride {
+    pub address: PublicKeyBinary,
+    pub region: Region,
+    pub expires_at: DateTime<Utc>,
+    pub reason: String,
+}
+
+impl RegionOverride {
+    /// Validate an override requested at `now`: it must name a known region,
+    /// expire after `now` and give a reason
+    pub fn try_from_proto(
+        proto: GatewayRegionOverrideV1,
+        now: DateTime<Utc>,
+    ) -> Result<Self, Status> {
+        let region = Region::from_i32(proto.region)
+            .filter(|region| *region != Region::Unknown)
+            .ok_or_else(|| {
+                Status::invalid_argument(format!("invalid lora region {}", proto.region))
+            })?;
+        let expires_at = Utc
+            .timestamp_opt(proto.expires_at as i64, 0)
+            .single()
+            .filter(|expires_at| *expires_at > now)
+            .ok_or_else(|| Status::invalid_argument("expiry must be in the future"))?;
+        if proto.reason.is_empty() {
+            return Err(Status::invalid_argument("a reason is required"));
+        }
+        Ok(Self {
+            address: proto.address.into(),
+            region,
+            expires_at,
+            reason: proto.reason,
+        })
+    }
+}
+
+impl sqlx::FromRow<'_, sqlx::postgres::PgRow> for RegionOverride {
+    fn from_row(row: &sqlx::postgres::PgRow) -> sqlx::Result<Self> {
+        let decode_err = |err: String| sqlx::Error::Decode(err.into());
+        let address: String = row.get("address");
+        let region: String = row.get("region");
+        Ok(Self {
+            address: PublicKeyBinary::from_str(&address)
+                .map_err(|err| decode_err(err.to_string()))?,
+            region: Region::from_str(&region)
+                .map_err(|_| decode_err(format!("invalid region: {region}")))?,
+            expires_at: row.get("expires_at"),
+            reason: row.get("reason"),
+        })
+    }
+}
+
+/// The unexpired override of `address`, if any
+pub async fn get_active(
+    address: &PublicKeyBinary,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<Option<RegionOverride>, sqlx::Error> {
+    sqlx::query_as::<_, RegionOverride>(
+        r#"
+        select address, region, expires_at, reason from gateway_region_overrides
+        where address = $1 and expires_at > now()
+        "#,
+    )
+    .bind(address.to_string())
+    .fetch_optional(db)
+    .await
+}
+
+/// The regions of all unexpired overrides by gateway
+pub async fn all_active(
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<HashMap<PublicKeyBinary, Region>, sqlx::Error> {
+    Ok(sqlx::query_as::<_, RegionOverride>(
+        r#"
+        select address, region, expires_at, reason from gateway_region_overrides
+        where expires_at > now()
+        "#,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|region_override| (region_override.address, region_override.region))
+    .collect())
+}
+
+/// Set the override of a gateway, replacing any previous one. Every override
+/// set or cleared is recorded in the `gateway_region_override_log` audit log
+/// with the admin key and the reason given
+pub async fn set(
+    region_override: &RegionOverride,
+    signer: &PublicKey,
+    db: &Pool<Postgres>,
+) -> Result<(), sqlx::Error> {
+    let mut txn = db.begin().await?;
+    sqlx::query(
+        r#"
+        insert into gateway_region_overrides (address, region, expires_at, reason, signer_pubkey)
+        values ($1, $2, $3, $4, $5)
+        on conflict (address) do update set
+            region = excluded.region,
+            expires_at = excluded.expires_at,
+            reason = excluded.reason,
+            signer_pubkey = excluded.signer_pubkey,
+            inserted_at = now()
+        "#,
+    )
+    .bind(region_override.address.to_string())
+    .bind(region_override.region.to_string())
+    .bind(region_override.expires_at)
+    .bind(&region_override.reason)
+    .bind(signer.to_string())
+    .execute(&mut txn)
+    .await?;
+    insert_log(
+        &region_override.address,
+        Some((region_override.region, region_override.expires_at)),
+        &region_override.reason,
+        signer,
+        &mut txn,
+    )
+    .await?;
+    txn.commit().await
+}
+
+/// Clear the override of a gateway, returning whether an unexpired one was
+/// in place
+pub async fn clear(
+    address: &PublicKeyBinary,
+    reason: &str,
+    signer: &PublicKey,
+    db: &Pool<Postgres>,
+) -> Result<bool, sqlx::Error> {
+    let mut txn = db.begin().await?;
+    let was_active = sqlx::query_scalar::<_, bool>(
+        " delete from gateway_region_overrides where address = $1 returning expires_at > now() ",
+    )
+    .bind(address.to_string())
+    .fetch_optional(&mut txn)
+    .await?
+    .unwrap_or(false);
+    insert_log(address, None, reason, signer, &mut txn).await?;
+    txn.commit().await?;
+    Ok(was_active)
+}
+
+async fn insert_log(
+    address: &PublicKeyBinary,
+    region_override: Option<(Region, DateTime<Utc>)>,
+    reason: &str,
+    signer: &PublicKey,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
+    let (region, expires_at) = region_override.unzip();
+    sqlx::query(
+        r#"
+        insert into gateway_region_override_log
+            (address, region, expires_at, reason, signer_pubkey)
+        values ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(address.to_string())
+    .bind(region.map(|region| region.to_string()))
+    .bind(expires_at)
+    .bind(reason)
+    .bind(signer.to_string())
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_overrides_are_validated() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let proto = GatewayRegionOverrideV1 {
+            address: vec![1],
+            region: Region::Eu868.into(),
+            expires_at: 1_700_003_600,
+            reason: "border".to_string(),
+        };
+        let region_override =
+            RegionOverride::try_from_proto(proto.clone(), now).expect("valid override");
+        assert_eq!(Region::Eu868, region_override.region);
+        assert_eq!(now + chrono::Duration::hours(1), region_override.expires_at);
+
+        for invalid in [
+            GatewayRegionOverrideV1 {
+                region: Region::Unknown.into(),
+                ..proto.clone()
+            },
+            GatewayRegionOverrideV1 {
+                region: 10_000,
+                ..proto.clone()
+            },
+            GatewayRegionOverrideV1 {
+                expires_at: 1_700_000_000,
+                ..proto.clone()
+            },
+            GatewayRegionOverrideV1 {
+                reason: String::new(),
+                ..proto.clone()
+            },
+        ] {
+            let status = RegionOverride::try_from_proto(invalid, now).expect_err("invalid");
+            assert_eq!(tonic::Code::InvalidArgument, status.code());
+        }
+    }
+}
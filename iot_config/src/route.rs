@@ -1,6 +1,7 @@
 use crate::{
-    broadcast_update,
+    audit, broadcast_update,
     lora_field::{DevAddrField, DevAddrRange, EuiPair, NetIdField, Skf},
+    Error, Result,
 };
 use anyhow::anyhow;
 use chrono::Utc;
@@ -85,55 +86,23 @@ pub struct StorageRoute {
     pub ignore_empty_skf: bool,
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum RouteStorageError {
-    #[error("db persist failed: {0}")]
-    StorageError(#[from] sqlx::Error),
-    #[error("uuid parse error: {0}")]
-    UuidParse(#[from] sqlx::types::uuid::Error),
-    #[error("protocol serialize error: {0}")]
-    ProtocolSerde(#[from] serde_json::Error),
-    #[error("protocol error: {0}")]
-    ServerProtocol(String),
-}
-
 pub async fn create_route(
     route: Route,
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: &Keypair,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<Route> {
-    let net_id: i32 = route.net_id.into();
-    let protocol_opts = route
-        .server
-        .protocol
-        .as_ref()
-        .ok_or("no protocol defined")
-        .map_err(|e| RouteStorageError::ServerProtocol(e.to_string()))?;
-
+    audit_entry: audit::Entry,
+) -> Result<Route> {
     let mut transaction = db.begin().await?;
 
-    let row = sqlx::query(
-            r#"
-            insert into routes (oui, net_id, max_copies, server_host, server_port, server_protocol_opts, active, ignore_empty_skf)
-            values ($1, $2, $3, $4, $5, $6, $7, $8)
-            returning id
-            "#,
-        )
-        .bind(route.oui as i64)
-        .bind(net_id)
-        .bind(route.max_copies as i32)
-        .bind(&route.server.host)
-        .bind(route.server.port as i32)
-        .bind(json!(&protocol_opts))
-        .bind(route.active)
-        .bind(route.ignore_empty_skf)
-        .fetch_one(&mut transaction)
-        .await?;
-
-    let route_id = row.get::<Uuid, &str>("id").to_string();
+    let route_id = insert_route(&route, &mut transaction).await?;
 
     let new_route = get_route(&route_id, &mut transaction).await?;
+    audit::record(
+        audit_entry.oui(new_route.oui).after(&new_route),
+        &mut transaction,
+    )
+    .await?;
 
     transaction.commit().await?;
 
@@ -163,23 +132,55 @@ pub async fn create_route(
     Ok(new_route)
 }
 
+/// Insert `route` under a new id, returning the id
+pub(crate) async fn insert_route(route: &Route, db: impl sqlx::PgExecutor<'_>) -> Result<String> {
+    let net_id: i32 = route.net_id.into();
+    let protocol_opts = route
+        .server
+        .protocol
+        .as_ref()
+        .ok_or_else(|| Error::decode("no protocol defined"))?;
+
+    let row = sqlx::query(
+            r#"
+            insert into routes (oui, net_id, max_copies, server_host, server_port, server_protocol_opts, active, ignore_empty_skf)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
+            returning id
+            "#,
+        )
+        .bind(route.oui as i64)
+        .bind(net_id)
+        .bind(route.max_copies as i32)
+        .bind(&route.server.host)
+        .bind(route.server.port as i32)
+        .bind(json!(&protocol_opts))
+        .bind(route.active)
+        .bind(route.ignore_empty_skf)
+        .fetch_one(db)
+        .await?;
+
+    Ok(row.get::<Uuid, &str>("id").to_string())
+}
+
 pub async fn update_route(
     route: Route,
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: &Keypair,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<Route> {
+    audit_entry: audit::Entry,
+) -> Result<Route> {
     let protocol_opts = route
         .server
         .protocol
         .as_ref()
-        .ok_or("no protocol defined")
-        .map_err(|e| RouteStorageError::ServerProtocol(e.to_string()))?;
+        .ok_or_else(|| Error::decode("no protocol defined"))?;
 
     let uuid = Uuid::try_parse(&route.id)?;
 
     let mut transaction = db.begin().await?;
 
+    let current_route = get_route(&route.id, &mut transaction).await?;
+
     sqlx::query(
         r#"
         update routes
@@ -198,6 +199,14 @@ pub async fn update_route(
     .await?;
 
     let updated_route = get_route(&route.id, &mut transaction).await?;
+    audit::record(
+        audit_entry
+            .oui(updated_route.oui)
+            .before(&current_route)
+            .after(&updated_route),
+        &mut transaction,
+    )
+    .await?;
 
     transaction.commit().await?;
 
@@ -226,10 +235,10 @@ pub async fn update_route(
     Ok(updated_route)
 }
 
-async fn insert_euis(
+pub(crate) async fn insert_euis(
     euis: &[EuiPair],
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Vec<EuiPair>> {
+) -> Result<Vec<EuiPair>> {
     if euis.is_empty() {
         return Ok(vec![]);
     }
@@ -256,10 +265,7 @@ async fn insert_euis(
         .await?)
 }
 
-async fn remove_euis(
-    euis: &[EuiPair],
-    db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Vec<EuiPair>> {
+async fn remove_euis(euis: &[EuiPair], db: impl sqlx::PgExecutor<'_>) -> Result<Vec<EuiPair>> {
     if euis.is_empty() {
         return Ok(vec![]);
     }
@@ -292,27 +298,86 @@ pub async fn update_euis(
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: Arc<Keypair>,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<()> {
+    audit_entry: audit::Entry,
+) -> Result<()> {
     let mut transaction = db.begin().await?;
 
-    let added_euis: Vec<(EuiPair, proto::ActionV1)> = insert_euis(to_add, &mut transaction)
-        .await?
+    let added_euis = insert_euis(to_add, &mut transaction).await?;
+    let removed_euis = remove_euis(to_remove, &mut transaction).await?;
+    audit::record(
+        audit_entry.after(&json!({ "added": &added_euis, "removed": &removed_euis })),
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    let updates = added_euis
         .into_iter()
         .map(|added_eui| (added_eui, proto::ActionV1::Add))
+        .chain(
+            removed_euis
+                .into_iter()
+                .map(|removed_eui| (removed_eui, proto::ActionV1::Remove)),
+        )
         .collect();
+    broadcast_eui_updates(updates, signing_key, update_tx);
 
-    let removed_euis: Vec<(EuiPair, proto::ActionV1)> = remove_euis(to_remove, &mut transaction)
-        .await?
-        .into_iter()
-        .map(|removed_eui| (removed_eui, proto::ActionV1::Remove))
-        .collect();
+    Ok(())
+}
 
+/// Euis are added or removed this many at a time within a batch, keeping
+/// each statement under the bind parameter limit of postgres
+const EUI_BATCH_CHUNK_SIZE: usize = 5_000;
+
+/// Add or remove the `euis` of a batch in one transaction, pushing the pairs
+/// added or removed to the route streams. The pairs already present, or
+/// absent, are left out of those returned
+pub async fn update_eui_batch(
+    action: proto::ActionV1,
+    euis: &[EuiPair],
+    db: &sqlx::Pool<sqlx::Postgres>,
+    signing_key: Arc<Keypair>,
+    update_tx: Sender<proto::RouteStreamResV1>,
+    audit_entry: audit::Entry,
+) -> Result<Vec<EuiPair>> {
+    let mut transaction = db.begin().await?;
+    let mut updated = Vec::with_capacity(euis.len());
+    for chunk in euis.chunks(EUI_BATCH_CHUNK_SIZE) {
+        updated.extend(match action {
+            proto::ActionV1::Add => insert_euis(chunk, &mut transaction).await?,
+            proto::ActionV1::Remove => remove_euis(chunk, &mut transaction).await?,
+        });
+    }
+    let (added, removed): (&[EuiPair], &[EuiPair]) = match action {
+        proto::ActionV1::Add => (updated.as_slice(), &[][..]),
+        proto::ActionV1::Remove => (&[][..], updated.as_slice()),
+    };
+    audit::record(
+        audit_entry.after(&json!({ "added": added, "removed": removed })),
+        &mut transaction,
+    )
+    .await?;
     transaction.commit().await?;
 
+    broadcast_eui_updates(
+        updated.iter().map(|eui| (eui.clone(), action)).collect(),
+        signing_key,
+        update_tx,
+    );
+
+    Ok(updated)
+}
+
+fn broadcast_eui_updates(
+    updates: Vec<(EuiPair, proto::ActionV1)>,
+    signing_key: Arc<Keypair>,
+    update_tx: Sender<proto::RouteStreamResV1>,
+) {
     tokio::spawn(async move {
         let timestamp = Utc::now().encode_timestamp();
         let signer: Vec<u8> = signing_key.public_key().into();
-        stream::iter([added_euis, removed_euis].concat())
+        stream::iter(updates)
             .map(Ok)
             .try_for_each(|(update, action)| {
                 let mut update_res = proto::RouteStreamResV1 {
@@ -332,14 +397,12 @@ pub async fn update_euis(
             })
             .await
     });
-
-    Ok(())
 }
 
-async fn insert_devaddr_ranges(
+pub(crate) async fn insert_devaddr_ranges(
     ranges: &[DevAddrRange],
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Vec<DevAddrRange>> {
+) -> Result<Vec<DevAddrRange>> {
     if ranges.is_empty() {
         return Ok(vec![]);
     }
@@ -370,7 +433,7 @@ async fn insert_devaddr_ranges(
 async fn remove_devaddr_ranges(
     ranges: &[DevAddrRange],
     db: impl sqlx::PgExecutor<'_>,
-) -> anyhow::Result<Vec<DevAddrRange>> {
+) -> Result<Vec<DevAddrRange>> {
     if ranges.is_empty() {
         return Ok(vec![]);
     }
@@ -404,25 +467,29 @@ pub async fn update_devaddr_ranges(
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: Arc<Keypair>,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<()> {
+    audit_entry: audit::Entry,
+) -> Result<()> {
     let mut transaction = db.begin().await?;
 
-    let added_devaddrs: Vec<(DevAddrRange, proto::ActionV1)> =
-        insert_devaddr_ranges(to_add, &mut transaction)
-            .await?
-            .into_iter()
-            .map(|added_range| (added_range, proto::ActionV1::Add))
-            .collect();
-
-    let removed_devaddrs: Vec<(DevAddrRange, proto::ActionV1)> =
-        remove_devaddr_ranges(to_remove, &mut transaction)
-            .await?
-            .into_iter()
-            .map(|removed_range| (removed_range, proto::ActionV1::Remove))
-            .collect();
+    let added_ranges = insert_devaddr_ranges(to_add, &mut transaction).await?;
+    let removed_ranges = remove_devaddr_ranges(to_remove, &mut transaction).await?;
+    audit::record(
+        audit_entry.after(&json!({ "added": &added_ranges, "removed": &removed_ranges })),
+        &mut transaction,
+    )
+    .await?;
 
     transaction.commit().await?;
 
+    let added_devaddrs: Vec<(DevAddrRange, proto::ActionV1)> = added_ranges
+        .into_iter()
+        .map(|added_range| (added_range, proto::ActionV1::Add))
+        .collect();
+    let removed_devaddrs: Vec<(DevAddrRange, proto::ActionV1)> = removed_ranges
+        .into_iter()
+        .map(|removed_range| (removed_range, proto::ActionV1::Remove))
+        .collect();
+
     tokio::spawn(async move {
         let timestamp = Utc::now().encode_timestamp();
         let signer: Vec<u8> = signing_key.public_key().into();
@@ -453,7 +520,7 @@ pub async fn update_devaddr_ranges(
     Ok(())
 }
 
-pub async fn list_routes(oui: u64, db: impl sqlx::PgExecutor<'_>) -> anyhow::Result<Vec<Route>> {
+pub async fn list_routes(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Route>> {
     Ok(sqlx::query_as::<_, StorageRoute>(
         r#"
         select r.id, r.oui, r.net_id, r.max_copies, r.server_host, r.server_port, r.server_protocol_opts, r.active, r.ignore_empty_skf, o.locked
@@ -465,7 +532,7 @@ pub async fn list_routes(oui: u64, db: impl sqlx::PgExecutor<'_>) -> anyhow::Res
     )
     .bind(oui as i64)
     .fetch(db)
-    .map_err(RouteStorageError::from)
+    .map_err(Error::from)
     .and_then(|route| async move { Ok(Route {
             id: route.id.to_string(),
             net_id: route.net_id.into(),
@@ -484,7 +551,7 @@ pub async fn list_routes(oui: u64, db: impl sqlx::PgExecutor<'_>) -> anyhow::Res
 pub fn list_euis_for_route<'a>(
     id: &str,
     db: impl sqlx::PgExecutor<'a> + 'a + Copy,
-) -> Result<impl Stream<Item = Result<EuiPair, sqlx::Error>> + 'a, RouteStorageError> {
+) -> Result<impl Stream<Item = Result<EuiPair, sqlx::Error>> + 'a> {
     let id = Uuid::try_parse(id)?;
     const EUI_SELECT_SQL: &str = r#"
     select eui.route_id, eui.app_eui, eui.dev_eui
@@ -501,7 +568,7 @@ pub fn list_euis_for_route<'a>(
 pub fn list_devaddr_ranges_for_route<'a>(
     id: &str,
     db: impl sqlx::PgExecutor<'a> + 'a,
-) -> Result<impl Stream<Item = Result<DevAddrRange, sqlx::Error>> + 'a, RouteStorageError> {
+) -> Result<impl Stream<Item = Result<DevAddrRange, sqlx::Error>> + 'a> {
     let id = Uuid::try_parse(id)?;
     const DEVADDR_RANGE_SELECT_SQL: &str = r#"
     select devaddr.route_id, devaddr.start_addr, devaddr.end_addr
@@ -528,7 +595,7 @@ pub fn active_route_stream<'a>(
         "#,
     )
     .fetch(db)
-    .map_err(RouteStorageError::from)
+    .map_err(Error::from)
     .and_then(|route| async move { Ok(Route {
             id: route.id.to_string(),
             net_id: route.net_id.into(),
@@ -585,7 +652,7 @@ pub fn skf_stream<'a>(db: impl sqlx::PgExecutor<'a> + 'a + Copy) -> impl Stream<
     .boxed()
 }
 
-pub async fn get_route(id: &str, db: impl sqlx::PgExecutor<'_>) -> anyhow::Result<Route> {
+pub async fn get_route(id: &str, db: impl sqlx::PgExecutor<'_>) -> Result<Route> {
     let uuid = Uuid::try_parse(id)?;
     let route = sqlx::query_as::<_, StorageRoute>(
         r#"
@@ -597,8 +664,9 @@ pub async fn get_route(id: &str, db: impl sqlx::PgExecutor<'_>) -> anyhow::Resul
         "#,
     )
     .bind(uuid)
-    .fetch_one(db)
-    .await?;
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| Error::not_found(format!("route {id}")))?;
 
     let server = RouteServer::new(
         route.server_host,
@@ -618,12 +686,14 @@ pub async fn get_route(id: &str, db: impl sqlx::PgExecutor<'_>) -> anyhow::Resul
     })
 }
 
+/// Delete the route, returning it as it was before
 pub async fn delete_route(
     id: &str,
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: &Keypair,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<()> {
+    audit_entry: audit::Entry,
+) -> Result<Route> {
     let uuid = Uuid::try_parse(id)?;
     let mut transaction = db.begin().await?;
 
@@ -638,6 +708,7 @@ pub async fn delete_route(
     .bind(uuid)
     .execute(&mut transaction)
     .await?;
+    audit::record(audit_entry.oui(route.oui).before(&route), &mut transaction).await?;
 
     transaction.commit().await?;
 
@@ -663,13 +734,13 @@ pub async fn delete_route(
                 .map_err(|_| anyhow!("failed to broadcast route delete update"))
         });
 
-    Ok(())
+    Ok(route)
 }
 
 pub fn list_skfs_for_route<'a>(
     id: &str,
     db: impl sqlx::PgExecutor<'a> + 'a + Copy,
-) -> Result<impl Stream<Item = Result<Skf, sqlx::Error>> + 'a, RouteStorageError> {
+) -> Result<impl Stream<Item = Result<Skf, sqlx::Error>> + 'a> {
     let id = Uuid::try_parse(id)?;
     const SKF_SELECT_SQL: &str = r#"
         select skf.route_id, skf.devaddr, skf.session_key, skf.max_copies
@@ -687,7 +758,7 @@ pub fn list_skfs_for_route_and_devaddr<'a>(
     id: &str,
     devaddr: DevAddrField,
     db: impl sqlx::PgExecutor<'a> + 'a + Copy,
-) -> Result<impl Stream<Item = Result<Skf, sqlx::Error>> + 'a, RouteStorageError> {
+) -> Result<impl Stream<Item = Result<Skf, sqlx::Error>> + 'a> {
     let id = Uuid::try_parse(id)?;
 
     Ok(sqlx::query_as::<_, Skf>(
@@ -709,23 +780,32 @@ pub async fn update_skfs(
     db: impl sqlx::PgExecutor<'_> + sqlx::Acquire<'_, Database = sqlx::Postgres> + Copy,
     signing_key: Arc<Keypair>,
     update_tx: Sender<proto::RouteStreamResV1>,
-) -> anyhow::Result<()> {
+    audit_entry: audit::Entry,
+) -> Result<()> {
     let mut transaction = db.begin().await?;
 
-    let added_updates: Vec<(Skf, proto::ActionV1)> = insert_skfs(to_add, &mut transaction)
-        .await?
+    let added_skfs = insert_skfs(to_add, &mut transaction).await?;
+    let removed_skfs = remove_skfs(to_remove, &mut transaction).await?;
+    audit::record(
+        audit_entry.after(&json!({
+            "added": audited_skfs(&added_skfs),
+            "removed": audited_skfs(&removed_skfs),
+        })),
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    let added_updates: Vec<(Skf, proto::ActionV1)> = added_skfs
         .into_iter()
         .map(|added_skf| (added_skf, proto::ActionV1::Add))
         .collect();
-
-    let removed_updates: Vec<(Skf, proto::ActionV1)> = remove_skfs(to_remove, &mut transaction)
-        .await?
+    let removed_updates: Vec<(Skf, proto::ActionV1)> = removed_skfs
         .into_iter()
         .map(|removed_skf| (removed_skf, proto::ActionV1::Remove))
         .collect();
 
-    transaction.commit().await?;
-
     tokio::spawn(async move {
         let timestamp = Utc::now().encode_timestamp();
         let signer: Vec<u8> = signing_key.public_key().into();
@@ -753,7 +833,21 @@ pub async fn update_skfs(
     Ok(())
 }
 
-async fn insert_skfs(skfs: &[Skf], db: impl sqlx::PgExecutor<'_>) -> anyhow::Result<Vec<Skf>> {
+/// Session key filters as recorded in the audit log, without their session
+/// keys
+fn audited_skfs(skfs: &[Skf]) -> Vec<serde_json::Value> {
+    skfs.iter()
+        .map(|skf| {
+            json!({
+                "route_id": skf.route_id,
+                "devaddr": skf.devaddr,
+                "max_copies": skf.max_copies,
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn insert_skfs(skfs: &[Skf], db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Skf>> {
     if skfs.is_empty() {
         return Ok(vec![]);
     }
@@ -786,7 +880,7 @@ async fn insert_skfs(skfs: &[Skf], db: impl sqlx::PgExecutor<'_>) -> anyhow::Res
     Ok(query_builder.build_query_as::<Skf>().fetch_all(db).await?)
 }
 
-async fn remove_skfs(skfs: &[Skf], db: impl sqlx::PgExecutor<'_>) -> anyhow::Result<Vec<Skf>> {
+async fn remove_skfs(skfs: &[Skf], db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Skf>> {
     if skfs.is_empty() {
         return Ok(vec![]);
     }
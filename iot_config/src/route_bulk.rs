@@ -0,0 +1,520 @@
+//! Export and import of the complete routing configuration of an org as a
+//! single json document, so an LNS moves between OUIs in one step.
+
+use crate::{
+    audit, broadcast_update,
+    lora_field::{DevAddrRange, EuiPair, Skf},
+    org,
+    route::{self, proto, Route},
+    Error, Result,
+};
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::Message;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::sync::broadcast::Sender;
+
+/// Euis, ranges and skfs are inserted this many at a time, keeping each
+/// insert under the bind parameter limit of postgres
+const INSERT_BATCH_SIZE: usize = 5_000;
+
+/// The routing configuration of an org
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OrgRouting {
+    pub oui: u64,
+    pub routes: Vec<RouteConfig>,
+}
+
+/// A route with everything routed through it. The route ids of the euis,
+/// ranges and skfs are those of the exporting org, replaced on import
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RouteConfig {
+    pub route: Route,
+    #[serde(default)]
+    pub eui_pairs: Vec<EuiPair>,
+    #[serde(default)]
+    pub devaddr_ranges: Vec<DevAddrRange>,
+    #[serde(default)]
+    pub skfs: Vec<Skf>,
+}
+
+/// The routing configuration of the org `oui`, read within one repeatable read
+/// transaction
+pub async fn export(oui: u64, db: &sqlx::Pool<sqlx::Postgres>) -> Result<OrgRouting> {
+    let mut txn = db.begin().await?;
+    sqlx::query("set transaction isolation level repeatable read, read only")
+        .execute(&mut txn)
+        .await?;
+
+    if org::get(oui, &mut txn).await?.is_none() {
+        return Err(Error::not_found(format!("org {oui}")));
+    }
+    let routes = route::list_routes(oui, &mut txn).await?;
+    let mut euis = group_by_route(
+        sqlx::query_as::<_, EuiPair>(
+            r#"
+            select eui.route_id, eui.app_eui, eui.dev_eui
+                from route_eui_pairs eui
+                join routes r on r.id = eui.route_id
+                where r.oui = $1
+            "#,
+        )
+        .bind(oui as i64)
+        .fetch_all(&mut txn)
+        .await?,
+        |eui| &eui.route_id,
+    );
+    let mut ranges = group_by_route(
+        sqlx::query_as::<_, DevAddrRange>(
+            r#"
+            select devaddr.route_id, devaddr.start_addr, devaddr.end_addr
+                from route_devaddr_ranges devaddr
+                join routes r on r.id = devaddr.route_id
+                where r.oui = $1
+            "#,
+        )
+        .bind(oui as i64)
+        .fetch_all(&mut txn)
+        .await?,
+        |range| &range.route_id,
+    );
+    let mut skfs = group_by_route(
+        sqlx::query_as::<_, Skf>(
+            r#"
+            select skf.route_id, skf.devaddr, skf.session_key, skf.max_copies
+                from route_session_key_filters skf
+                join routes r on r.id = skf.route_id
+                where r.oui = $1
+            "#,
+        )
+        .bind(oui as i64)
+        .fetch_all(&mut txn)
+        .await?,
+        |skf| &skf.route_id,
+    );
+    txn.commit().await?;
+
+    Ok(OrgRouting {
+        oui,
+        routes: routes
+            .into_iter()
+            .map(|route| RouteConfig {
+                eui_pairs: euis.remove(&route.id).unwrap_or_default(),
+                devaddr_ranges: ranges.remove(&route.id).unwrap_or_default(),
+                skfs: skfs.remove(&route.id).unwrap_or_default(),
+                route,
+            })
+            .collect(),
+    })
+}
+
+fn group_by_route<T>(items: Vec<T>, route_id: impl Fn(&T) -> &String) -> HashMap<String, Vec<T>> {
+    let mut grouped: HashMap<String, Vec<T>> = HashMap::new();
+    for item in items {
+        grouped
+            .entry(route_id(&item).clone())
+            .or_default()
+            .push(item);
+    }
+    grouped
+}
+
+/// What an import changed, as imported
+pub struct Imported {
+    pub removed: Vec<Route>,
+    pub routes: Vec<RouteConfig>,
+}
+
+/// Import the routes of `routing` under the org `oui` with new ids, removing
+/// its current routes first when `replace` is set. Everything is written in
+/// one transaction, and nothing when any part of the document is invalid. The import is recorded with
+/// `audit_entry`, imports by the command have no signed request to record.
+pub async fn import(
+    oui: u64,
+    routing: OrgRouting,
+    replace: bool,
+    audit_entry: Option<audit::Entry>,
+    db: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<Imported> {
+    let mut txn = db.begin().await?;
+
+    let org = org::get(oui, &mut txn)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("org {oui}")))?;
+    if org.locked {
+        return Err(Error::unauthorized(format!("org {oui} is locked")));
+    }
+    let constraints = org.constraints.unwrap_or_default();
+
+    let removed = if replace {
+        let removed = route::list_routes(oui, &mut txn).await?;
+        sqlx::query(" delete from routes where oui = $1 ")
+            .bind(oui as i64)
+            .execute(&mut txn)
+            .await?;
+        removed
+    } else {
+        vec![]
+    };
+
+    let mut imported = Vec::with_capacity(routing.routes.len());
+    for config in routing.routes {
+        for range in &config.devaddr_ranges {
+            if !constraints
+                .iter()
+                .any(|constraint| constraint.contains_range(range))
+            {
+                return Err(Error::invalid(format!(
+                    "devaddr range {} -- {} outside of the constraints of org {oui}",
+                    range.start_addr, range.end_addr
+                )));
+            }
+        }
+        for skf in &config.skfs {
+            if !config
+                .devaddr_ranges
+                .iter()
+                .any(|range| range.contains_addr(skf.devaddr))
+            {
+                return Err(Error::invalid(format!(
+                    "skf devaddr {} not within the ranges of its route",
+                    skf.devaddr
+                )));
+            }
+        }
+
+        let route = Route {
+            oui,
+            ..config.route
+        };
+        let route_id = route::insert_route(&route, &mut txn).await?;
+
+        let eui_pairs: Vec<EuiPair> = config
+            .eui_pairs
+            .into_iter()
+            .map(|eui| EuiPair {
+                route_id: route_id.clone(),
+                ..eui
+            })
+            .collect();
+        let devaddr_ranges: Vec<DevAddrRange> = config
+            .devaddr_ranges
+            .into_iter()
+            .map(|range| DevAddrRange {
+                route_id: route_id.clone(),
+                ..range
+            })
+            .collect();
+        let skfs: Vec<Skf> = config
+            .skfs
+            .into_iter()
+            .map(|skf| Skf {
+                route_id: route_id.clone(),
+                ..skf
+            })
+            .collect();
+
+        let mut config = RouteConfig {
+            route: route::get_route(&route_id, &mut txn).await?,
+            eui_pairs: vec![],
+            devaddr_ranges: vec![],
+            skfs: vec![],
+        };
+        for batch in eui_pairs.chunks(INSERT_BATCH_SIZE) {
+            config
+                .eui_pairs
+                .extend(route::insert_euis(batch, &mut txn).await?);
+        }
+        for batch in devaddr_ranges.chunks(INSERT_BATCH_SIZE) {
+            config
+                .devaddr_ranges
+                .extend(route::insert_devaddr_ranges(batch, &mut txn).await?);
+        }
+        for batch in skfs.chunks(INSERT_BATCH_SIZE) {
+            config
+                .skfs
+                .extend(route::insert_skfs(batch, &mut txn).await?);
+        }
+        imported.push(config);
+    }
+
+    if let Some(audit_entry) = audit_entry {
+        let routes: Vec<&Route> = imported.iter().map(|config| &config.route).collect();
+        audit::record(audit_entry.before(&removed).after(&routes), &mut txn).await?;
+    }
+    txn.commit().await?;
+
+    Ok(Imported {
+        removed,
+        routes: imported,
+    })
+}
+
+/// Push the routes removed and imported to the route streams in the
+/// background, throttled like the updates of the individual route apis so a
+/// large import doesn't overrun the channel and lag its subscribers
+pub fn broadcast_import(
+    imported: &Imported,
+    signing_key: Arc<Keypair>,
+    update_tx: Sender<proto::RouteStreamResV1>,
+) {
+    use proto::route_stream_res_v1::Data;
+
+    let mut updates = vec![];
+    for route in &imported.removed {
+        updates.push((proto::ActionV1::Remove, Data::Route(route.clone().into())));
+    }
+    for config in &imported.routes {
+        if config.route.active && !config.route.locked {
+            updates.push((
+                proto::ActionV1::Add,
+                Data::Route(config.route.clone().into()),
+            ));
+        }
+        for eui in &config.eui_pairs {
+            updates.push((proto::ActionV1::Add, Data::EuiPair(eui.clone().into())));
+        }
+        for range in &config.devaddr_ranges {
+            updates.push((
+                proto::ActionV1::Add,
+                Data::DevaddrRange(range.clone().into()),
+            ));
+        }
+        for skf in &config.skfs {
+            updates.push((proto::ActionV1::Add, Data::Skf(skf.clone().into())));
+        }
+    }
+
+    tokio::spawn(async move {
+        let timestamp = Utc::now().encode_timestamp();
+        let signer: Vec<u8> = signing_key.public_key().into();
+        for (action, data) in updates {
+            let mut update = proto::RouteStreamResV1 {
+                action: action.into(),
+                data: Some(data),
+                timestamp,
+                signer: signer.clone(),
+                signature: vec![],
+            };
+            match signing_key.sign(&update.encode_to_vec()) {
+                Ok(signature) => update.signature = signature,
+                Err(err) => {
+                    tracing::error!("error signing route import update: {err:?}");
+                    continue;
+                }
+            }
+            if let Err(err) = broadcast_update(update, update_tx.clone()).await {
+                tracing::warn!("error broadcasting route import update: {err:?}");
+                return;
+            }
+        }
+    });
+}
+
+/// Export or import the routing configuration of an org against the database.
+/// Imports aren't pushed to the route streams of running servers, whose
+/// routers pick them up on reconnecting
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// Write the routing configuration of an org as json
+    Export {
+        #[clap(long)]
+        oui: u64,
+        /// File to write the document to, stdout when not set
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import a document written by `export` under an org
+    Import {
+        #[clap(long)]
+        oui: u64,
+        /// Remove the routes the org has before importing
+        #[clap(long)]
+        replace: bool,
+        file: PathBuf,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &sqlx::Pool<sqlx::Postgres>) -> anyhow::Result<()> {
+        match self {
+            Self::Export { oui, output } => {
+                let document = serde_json::to_string_pretty(&export(*oui, pool).await?)?;
+                match output {
+                    Some(path) => tokio::fs::write(path, document).await?,
+                    None => println!("{document}"),
+                }
+            }
+            Self::Import { oui, replace, file } => {
+                let routing: OrgRouting = serde_json::from_slice(&tokio::fs::read(file).await?)?;
+                let imported = import(*oui, routing, *replace, None, pool).await?;
+                for route in &imported.removed {
+                    println!("removed route {}", route.id);
+                }
+                for config in &imported.routes {
+                    println!(
+                        "imported route {} with {} eui pairs, {} devaddr ranges and {} skfs",
+                        config.route.id,
+                        config.eui_pairs.len(),
+                        config.devaddr_ranges.len(),
+                        config.skfs.len()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lora_field::{devaddr, eui, DevAddrField},
+        org::{toggle_locked, Org},
+        test_support::{audit_entry, count, helium_org, route},
+    };
+    use sqlx::{Pool, Postgres};
+
+    /// A route of `org` over its first devaddr constraint, ending the range
+    /// `overrun` addresses past it, with an eui pair and a skf of `skf_addr`
+    fn route_config(org: &Org, overrun: u32, skf_addr: DevAddrField) -> RouteConfig {
+        let constraint = &org.constraints.as_ref().expect("org constraints")[0];
+        let end_addr = devaddr(u64::from(constraint.end_addr) as u32 + overrun);
+        RouteConfig {
+            route: route(org.oui, 1),
+            eui_pairs: vec![EuiPair::new(String::new(), eui(0xaa), eui(0xbb))],
+            devaddr_ranges: vec![DevAddrRange::new(
+                String::new(),
+                constraint.start_addr,
+                end_addr,
+            )],
+            skfs: vec![Skf::new(String::new(), skf_addr, "key".to_string(), 1)],
+        }
+    }
+
+    fn routing(org: &Org, routes: Vec<RouteConfig>) -> OrgRouting {
+        OrgRouting {
+            oui: org.oui,
+            routes,
+        }
+    }
+
+    #[sqlx::test]
+    async fn imports_outside_the_org_constraints_write_nothing(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let start_addr = org.constraints.as_ref().unwrap()[0].start_addr;
+        let valid = route_config(&org, 0, start_addr);
+
+        let overrun = routing(&org, vec![valid.clone(), route_config(&org, 1, start_addr)]);
+        let result = import(org.oui, overrun, false, Some(audit_entry()), &pool).await;
+        assert!(matches!(result, Err(Error::Invalid(_))));
+
+        let stray_skf = route_config(&org, 0, devaddr(u64::from(start_addr) as u32 - 1));
+        let result = import(
+            org.oui,
+            routing(&org, vec![valid, stray_skf]),
+            false,
+            Some(audit_entry()),
+            &pool,
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Invalid(_))));
+
+        assert_eq!(0, count("routes", &pool).await);
+        assert_eq!(0, count("route_eui_pairs", &pool).await);
+        assert_eq!(0, count("route_devaddr_ranges", &pool).await);
+        assert_eq!(0, count("route_session_key_filters", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn replacing_imports_remove_the_current_routes(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let start_addr = org.constraints.as_ref().unwrap()[0].start_addr;
+        let document = routing(&org, vec![route_config(&org, 0, start_addr)]);
+
+        let first = import(org.oui, document.clone(), false, None, &pool)
+            .await
+            .unwrap();
+        assert!(first.removed.is_empty());
+        let added = import(org.oui, document.clone(), false, None, &pool)
+            .await
+            .unwrap();
+        assert!(added.removed.is_empty());
+        assert_eq!(2, count("routes", &pool).await);
+
+        let replaced = import(org.oui, document, true, Some(audit_entry()), &pool)
+            .await
+            .unwrap();
+        let mut removed: Vec<_> = replaced.removed.iter().map(|route| &route.id).collect();
+        removed.sort();
+        let mut expected = vec![&first.routes[0].route.id, &added.routes[0].route.id];
+        expected.sort();
+        assert_eq!(expected, removed);
+
+        let route_id = &replaced.routes[0].route.id;
+        assert_eq!(&replaced.routes[0].eui_pairs[0].route_id, route_id);
+        assert_eq!(&replaced.routes[0].devaddr_ranges[0].route_id, route_id);
+        assert_eq!(&replaced.routes[0].skfs[0].route_id, route_id);
+        assert_eq!(1, count("routes", &pool).await);
+        assert_eq!(1, count("route_eui_pairs", &pool).await);
+        assert_eq!(1, count("route_devaddr_ranges", &pool).await);
+        assert_eq!(1, count("route_session_key_filters", &pool).await);
+    }
+
+    #[sqlx::test]
+    async fn locked_orgs_import_nothing(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let start_addr = org.constraints.as_ref().unwrap()[0].start_addr;
+        toggle_locked(org.oui, &pool).await.unwrap();
+
+        let result = import(
+            org.oui,
+            routing(&org, vec![route_config(&org, 0, start_addr)]),
+            false,
+            Some(audit_entry()),
+            &pool,
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+        assert_eq!(0, count("routes", &pool).await);
+    }
+
+    #[test]
+    fn routing_document_round_trip() {
+        let document = r#"{
+            "oui": 7,
+            "routes": [{
+                "route": {
+                    "id": "2c9c7b1c-2b0e-4a7e-8c2e-9d5e3f1a6b4d",
+                    "net_id": "00003c",
+                    "oui": 7,
+                    "server": {
+                        "host": "lns.example.com",
+                        "port": 8080,
+                        "protocol": {"type": "packet_router"}
+                    },
+                    "max_copies": 2,
+                    "active": true,
+                    "locked": false,
+                    "ignore_empty_skf": false
+                },
+                "eui_pairs": [{
+                    "route_id": "2c9c7b1c-2b0e-4a7e-8c2e-9d5e3f1a6b4d",
+                    "app_eui": "00000000000000aa",
+                    "dev_eui": "00000000000000bb"
+                }]
+            }]
+        }"#;
+        let routing: OrgRouting = serde_json::from_str(document).expect("parsed document");
+        assert_eq!(7, routing.oui);
+        assert_eq!(1, routing.routes[0].eui_pairs.len());
+        assert!(routing.routes[0].devaddr_ranges.is_empty());
+
+        let exported = serde_json::to_string(&routing).expect("serialized document");
+        let reparsed: OrgRouting = serde_json::from_str(&exported).expect("reparsed document");
+        assert_eq!(routing, reparsed);
+    }
+}
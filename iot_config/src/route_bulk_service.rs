@@ -0,0 +1,177 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    audit,
+    ext::{self, RouteExportReqV1, RouteExportResV1, RouteImportReqV1, RouteImportResV1},
+    org,
+    route::proto::RouteStreamResV1,
+    route_bulk::{self, OrgRouting},
+    signature_guard::SignatureGuard,
+    telemetry, verify_public_key, GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tonic::{Request, Response, Status};
+
+/// Export and import of the routing configuration of an org in one call
+pub struct RouteBulkService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    update_channel: broadcast::Sender<RouteStreamResV1>,
+    signing_key: Arc<Keypair>,
+    signature_guard: SignatureGuard,
+}
+
+impl RouteBulkService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        update_channel: broadcast::Sender<RouteStreamResV1>,
+        signature_guard: SignatureGuard,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            update_channel,
+            signing_key: Arc::new(settings.signing_keypair()?),
+            signature_guard,
+        })
+    }
+
+    /// The routing of an org is exported and imported by the keys of the
+    /// org, as its routes are managed, or an administrator
+    async fn verify_request_signature<R>(
+        &self,
+        oui: u64,
+        signer: &PublicKey,
+        request: &R,
+    ) -> Result<(), Status>
+    where
+        R: MsgVerify,
+    {
+        if self
+            .auth_cache
+            .verify_signature_with_type(KeyType::Administrator, signer, request)
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        self.signature_guard.check_unlocked(oui).await?;
+        let org_keys = org::get_org_pubkeys(oui, &self.pool)
+            .await
+            .map_err(|_| Status::internal("auth verification error"))?;
+        if org_keys.contains(signer) && request.verify(signer).is_ok() {
+            return Ok(());
+        }
+
+        self.signature_guard
+            .record_failure(oui, signer, "route_bulk")
+            .await;
+        Err(Status::permission_denied("unauthorized request signature"))
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::route_bulk_server::RouteBulk for RouteBulkService {
+    async fn export(&self, request: Request<RouteExportReqV1>) -> GrpcResult<RouteExportResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("route-bulk", "export");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(request.oui, &signer, &request)
+            .await?;
+
+        let routing = route_bulk::export(request.oui, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(oui = request.oui, "route export failed: {err:?}");
+                Status::from(err)
+            })?;
+        let document = serde_json::to_string(&routing)
+            .map_err(|_| Status::internal("route export encoding failed"))?;
+
+        let mut resp = RouteExportResV1 {
+            oui: request.oui,
+            document,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+
+    async fn import(&self, request: Request<RouteImportReqV1>) -> GrpcResult<RouteImportResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("route-bulk", "import");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(request.oui, &signer, &request)
+            .await?;
+        let audit_entry = audit::Entry::new("route.import", Some(request.oui), &signer, &request);
+
+        let routing: OrgRouting = serde_json::from_str(&request.document)
+            .map_err(|err| Status::invalid_argument(format!("invalid document: {err}")))?;
+        tracing::info!(
+            oui = request.oui,
+            from_oui = routing.oui,
+            routes = routing.routes.len(),
+            replace = request.replace,
+            "route import"
+        );
+
+        let imported = route_bulk::import(
+            request.oui,
+            routing,
+            request.replace,
+            Some(audit_entry),
+            &self.pool,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!(oui = request.oui, "route import failed: {err:?}");
+            Status::from(err)
+        })?;
+        route_bulk::broadcast_import(
+            &imported,
+            self.signing_key.clone(),
+            self.update_channel.clone(),
+        );
+
+        let routes: Vec<_> = imported
+            .routes
+            .into_iter()
+            .map(|config| config.route)
+            .collect();
+
+        let mut resp = RouteImportResV1 {
+            oui: request.oui,
+            routes: routes.into_iter().map(|route| route.into()).collect(),
+            removed: imported
+                .removed
+                .into_iter()
+                .map(|route| route.into())
+                .collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
@@ -0,0 +1,439 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    audit,
+    ext::{
+        self, EuiV1, RouteEuisBatchReqV1, RouteEuisBatchResV1, RouteEuisListReqV1,
+        RouteEuisListResV1,
+    },
+    lora_field::EuiPair,
+    org,
+    route::{
+        self,
+        proto::{ActionV1, RouteStreamResV1},
+    },
+    signature_guard::SignatureGuard,
+    telemetry, verify_public_key, Error, GrpcResult, GrpcStreamResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use futures::stream::StreamExt;
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tonic::{Request, Response, Status};
+
+/// Eui pairs added or removed by one request, in one transaction
+const BATCH_LIMIT: usize = 10_000;
+const DEFAULT_LIST_BATCH_SIZE: u32 = 1_000;
+
+/// Adds and removes the eui pairs of a route in batches of up to
+/// [`BATCH_LIMIT`] per signed request, and lists them in signed batches, for
+/// onboarding large fleets of devices without a request per device
+pub struct RouteEuisService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    update_channel: broadcast::Sender<RouteStreamResV1>,
+    signing_key: Arc<Keypair>,
+    signature_guard: SignatureGuard,
+}
+
+impl RouteEuisService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        update_channel: broadcast::Sender<RouteStreamResV1>,
+        signature_guard: SignatureGuard,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            update_channel,
+            signing_key: Arc::new(settings.signing_keypair()?),
+            signature_guard,
+        })
+    }
+
+    /// The euis of a route are managed by the keys of its org, as its other
+    /// components are, or an administrator
+    async fn verify_request_signature<R>(
+        &self,
+        route_id: &str,
+        signer: &PublicKey,
+        request: &R,
+    ) -> Result<route::Route, Status>
+    where
+        R: MsgVerify,
+    {
+        let route = route::get_route(route_id, &self.pool).await?;
+        if self
+            .auth_cache
+            .verify_signature_with_type(KeyType::Administrator, signer, request)
+            .is_ok()
+        {
+            return Ok(route);
+        }
+
+        self.signature_guard.check_unlocked(route.oui).await?;
+        let org_keys = org::get_org_pubkeys(route.oui, &self.pool)
+            .await
+            .map_err(|_| Status::internal("auth verification error"))?;
+        if org_keys.contains(signer) && request.verify(signer).is_ok() {
+            return Ok(route);
+        }
+
+        self.signature_guard
+            .record_failure(route.oui, signer, "route_euis")
+            .await;
+        Err(Status::permission_denied("unauthorized request signature"))
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+
+    async fn update_batch(
+        &self,
+        action: ActionV1,
+        request: RouteEuisBatchReqV1,
+    ) -> GrpcResult<RouteEuisBatchResV1> {
+        if request.euis.is_empty() {
+            return Err(Status::invalid_argument("no eui pairs provided"));
+        }
+        if request.euis.len() > BATCH_LIMIT {
+            return Err(Status::invalid_argument(format!(
+                "batch of {} eui pairs over the limit of {BATCH_LIMIT}",
+                request.euis.len()
+            )));
+        }
+
+        let signer = verify_public_key(&request.signer)?;
+        let route = self
+            .verify_request_signature(&request.route_id, &signer, &request)
+            .await?;
+        if route.locked {
+            return Err(Error::unauthorized(format!("org {} is locked", route.oui)).into());
+        }
+        let rpc = match action {
+            ActionV1::Add => "route.add-euis",
+            ActionV1::Remove => "route.remove-euis",
+        };
+        let audit_entry = audit::Entry::new(rpc, Some(route.oui), &signer, &request);
+
+        let euis: Vec<EuiPair> = request
+            .euis
+            .iter()
+            .map(|eui| EuiPair::new(route.id.clone(), eui.app_eui.into(), eui.dev_eui.into()))
+            .collect();
+        tracing::debug!(
+            route_id = route.id,
+            ?action,
+            euis = euis.len(),
+            "updating eui pair batch"
+        );
+
+        let updated = route::update_eui_batch(
+            action,
+            &euis,
+            &self.pool,
+            self.signing_key.clone(),
+            self.update_channel.clone(),
+            audit_entry,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!(route_id = route.id, "eui pair batch update failed: {err:?}");
+            Status::from(err)
+        })?;
+        match action {
+            ActionV1::Add => telemetry::count_eui_updates(updated.len(), 0),
+            ActionV1::Remove => telemetry::count_eui_updates(0, updated.len()),
+        }
+
+        let mut resp = RouteEuisBatchResV1 {
+            route_id: route.id,
+            updated: updated.len() as u32,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::route_euis_server::RouteEuis for RouteEuisService {
+    async fn add(&self, request: Request<RouteEuisBatchReqV1>) -> GrpcResult<RouteEuisBatchResV1> {
+        telemetry::count_request("route-euis", "add");
+        self.update_batch(ActionV1::Add, request.into_inner()).await
+    }
+
+    async fn remove(
+        &self,
+        request: Request<RouteEuisBatchReqV1>,
+    ) -> GrpcResult<RouteEuisBatchResV1> {
+        telemetry::count_request("route-euis", "remove");
+        self.update_batch(ActionV1::Remove, request.into_inner())
+            .await
+    }
+
+    type listStream = GrpcStreamResult<RouteEuisListResV1>;
+    async fn list(&self, request: Request<RouteEuisListReqV1>) -> GrpcResult<Self::listStream> {
+        let request = request.into_inner();
+        telemetry::count_request("route-euis", "list");
+
+        let signer = verify_public_key(&request.signer)?;
+        let route = self
+            .verify_request_signature(&request.route_id, &signer, &request)
+            .await?;
+        let batch_size = match request.batch_size {
+            0 => DEFAULT_LIST_BATCH_SIZE,
+            batch_size => batch_size,
+        };
+        tracing::debug!(route_id = route.id, "listing eui pair batches");
+
+        let pool = self.pool.clone();
+        let signing_key = self.signing_key.clone();
+        let (tx, rx) = mpsc::channel(20);
+        tokio::spawn(async move {
+            if let Err(err) = stream_euis(&pool, &signing_key, &route.id, batch_size, &tx).await {
+                tracing::error!(route_id = route.id, reason = ?err, "eui pair list failed");
+                _ = tx.send(Err(err)).await;
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+async fn stream_euis(
+    pool: &Pool<Postgres>,
+    signing_key: &Keypair,
+    route_id: &str,
+    batch_size: u32,
+    tx: &mpsc::Sender<Result<RouteEuisListResV1, Status>>,
+) -> Result<(), Status> {
+    let signer: Vec<u8> = signing_key.public_key().into();
+    let mut euis = route::list_euis_for_route(route_id, pool)?.chunks(batch_size as usize);
+    while let Some(batch) = euis.next().await {
+        let euis = batch
+            .into_iter()
+            .map(|eui| {
+                eui.map(|eui| EuiV1 {
+                    app_eui: eui.app_eui.into(),
+                    dev_eui: eui.dev_eui.into(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| Status::internal(format!("eui pair read failed: {err:?}")))?;
+        let mut response = RouteEuisListResV1 {
+            route_id: route_id.to_string(),
+            euis,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: signer.clone(),
+            signature: vec![],
+        };
+        response.signature = signing_key
+            .sign(&response.encode_to_vec())
+            .map_err(|_| Status::internal("response signing error"))?;
+        if tx.send(Ok(response)).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        admin::CacheKeys,
+        org::toggle_locked,
+        route::Route,
+        signature_guard,
+        test_support::{self, helium_org, keypair},
+        update_channel,
+    };
+
+    /// The service, along with the administrator key signing its requests
+    fn service(pool: &Pool<Postgres>) -> (RouteEuisService, Keypair) {
+        let admin = keypair();
+        let (_, auth_cache) = AuthCache::from_keys(CacheKeys::from([(
+            admin.public_key().clone(),
+            KeyType::Administrator,
+        )]));
+        let service = RouteEuisService {
+            auth_cache,
+            pool: pool.clone(),
+            update_channel: update_channel(),
+            signing_key: Arc::new(keypair()),
+            signature_guard: SignatureGuard::new(
+                &signature_guard::Settings::default(),
+                pool.clone(),
+            )
+            .unwrap(),
+        };
+        (service, admin)
+    }
+
+    async fn route(pool: &Pool<Postgres>) -> Route {
+        let org = helium_org(pool).await;
+        let route_id = route::insert_route(&test_support::route(org.oui, 1), pool)
+            .await
+            .unwrap();
+        route::get_route(&route_id, pool).await.unwrap()
+    }
+
+    fn request(
+        route: &Route,
+        dev_euis: impl IntoIterator<Item = u64>,
+        signer: &Keypair,
+    ) -> RouteEuisBatchReqV1 {
+        let mut request = RouteEuisBatchReqV1 {
+            route_id: route.id.clone(),
+            euis: dev_euis
+                .into_iter()
+                .map(|dev_eui| EuiV1 {
+                    app_eui: 1,
+                    dev_eui,
+                })
+                .collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: signer.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = signer.sign(&request.encode_to_vec()).unwrap();
+        request
+    }
+
+    async fn updated(
+        service: &RouteEuisService,
+        action: ActionV1,
+        request: RouteEuisBatchReqV1,
+    ) -> u32 {
+        service
+            .update_batch(action, request)
+            .await
+            .unwrap()
+            .into_inner()
+            .updated
+    }
+
+    async fn eui_count(pool: &Pool<Postgres>) -> i64 {
+        test_support::count("route_eui_pairs", pool).await
+    }
+
+    async fn audited(pool: &Pool<Postgres>) -> i64 {
+        sqlx::query_scalar(" select count(*) from config_audit_log where rpc like 'route.%-euis' ")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn batches_over_the_limit_are_rejected(pool: Pool<Postgres>) {
+        let (service, admin) = service(&pool);
+        let route = route(&pool).await;
+
+        let status = service
+            .update_batch(
+                ActionV1::Add,
+                request(&route, 0..=BATCH_LIMIT as u64, &admin),
+            )
+            .await
+            .err()
+            .expect("batch over the limit");
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+        assert_eq!(0, eui_count(&pool).await);
+
+        let added = updated(
+            &service,
+            ActionV1::Add,
+            request(&route, 0..BATCH_LIMIT as u64, &admin),
+        )
+        .await;
+        assert_eq!(BATCH_LIMIT as u32, added);
+        assert_eq!(BATCH_LIMIT as i64, eui_count(&pool).await);
+    }
+
+    #[sqlx::test]
+    async fn counts_skip_pairs_already_present_or_absent(pool: Pool<Postgres>) {
+        let (service, admin) = service(&pool);
+        let route = route(&pool).await;
+
+        let batch = |dev_euis: std::ops::RangeInclusive<u64>| request(&route, dev_euis, &admin);
+        assert_eq!(3, updated(&service, ActionV1::Add, batch(1..=3)).await);
+        assert_eq!(2, updated(&service, ActionV1::Add, batch(2..=5)).await);
+        assert_eq!(2, updated(&service, ActionV1::Remove, batch(4..=7)).await);
+        assert_eq!(0, updated(&service, ActionV1::Remove, batch(4..=7)).await);
+
+        assert_eq!(3, eui_count(&pool).await);
+        assert_eq!(4, audited(&pool).await);
+    }
+
+    #[sqlx::test]
+    async fn locked_orgs_are_rejected(pool: Pool<Postgres>) {
+        let (service, admin) = service(&pool);
+        let route = route(&pool).await;
+        toggle_locked(route.oui, &pool).await.unwrap();
+
+        let status = service
+            .update_batch(ActionV1::Add, request(&route, 1..=3, &admin))
+            .await
+            .err()
+            .expect("locked org");
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+        assert_eq!(0, eui_count(&pool).await);
+        assert_eq!(0, audited(&pool).await);
+    }
+
+    #[sqlx::test]
+    async fn failed_batches_are_rolled_back(pool: Pool<Postgres>) {
+        let (service, admin) = service(&pool);
+        let route = route(&pool).await;
+        // the last pair of the batch fails, in a chunk after the first:
+        sqlx::query(
+            r#"
+            create function fail_last_eui() returns trigger as $$
+            begin
+                if new.dev_eui = 9999 then
+                    raise exception 'failed eui';
+                end if;
+                return new;
+            end;
+            $$ language plpgsql
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            create trigger fail_last_eui before insert on route_eui_pairs
+                for each row execute function fail_last_eui()
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let status = service
+            .update_batch(
+                ActionV1::Add,
+                request(&route, 0..BATCH_LIMIT as u64, &admin),
+            )
+            .await
+            .err()
+            .expect("failed batch");
+        assert_eq!(tonic::Code::Internal, status.code());
+        assert_eq!(0, eui_count(&pool).await);
+        assert_eq!(0, audited(&pool).await);
+    }
+}
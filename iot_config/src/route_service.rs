@@ -1,10 +1,12 @@
 use crate::{
     admin::{AuthCache, KeyType},
+    audit,
     lora_field::{DevAddrConstraint, DevAddrRange, EuiPair, Skf},
-    org::{self, OrgStoreError},
-    route::{self, Route, RouteStorageError},
-    telemetry, update_channel, verify_public_key, GrpcResult, GrpcStreamRequest, GrpcStreamResult,
-    Settings,
+    org,
+    route::{self, Route},
+    signature_guard::SignatureGuard,
+    telemetry, update_channel, verify_public_key, Error, GrpcResult, GrpcStreamRequest,
+    GrpcStreamResult, Settings,
 };
 use anyhow::{anyhow, Result};
 use chrono::Utc;
@@ -39,6 +41,7 @@ pub struct RouteService {
     update_channel: broadcast::Sender<RouteStreamResV1>,
     shutdown: triggered::Listener,
     signing_key: Arc<Keypair>,
+    signature_guard: SignatureGuard,
 }
 
 #[derive(Clone, Debug)]
@@ -53,6 +56,7 @@ impl RouteService {
         auth_cache: AuthCache,
         pool: Pool<Postgres>,
         shutdown: triggered::Listener,
+        signature_guard: SignatureGuard,
     ) -> Result<Self> {
         Ok(Self {
             auth_cache,
@@ -60,6 +64,7 @@ impl RouteService {
             update_channel: update_channel(),
             shutdown,
             signing_key: Arc::new(settings.signing_keypair()?),
+            signature_guard,
         })
     }
 
@@ -89,11 +94,19 @@ impl RouteService {
             return Ok(());
         }
 
-        let org_keys = match id {
-            OrgId::Oui(oui) => org::get_org_pubkeys(oui, &self.pool).await,
-            OrgId::RouteId(route_id) => org::get_org_pubkeys_by_route(route_id, &self.pool).await,
-        }
-        .map_err(|_| Status::internal("auth verification error"))?;
+        let oui = match id {
+            OrgId::Oui(oui) => oui,
+            OrgId::RouteId(route_id) => {
+                route::get_route(route_id, &self.pool)
+                    .await
+                    .map_err(|_| Status::internal("auth verification error"))?
+                    .oui
+            }
+        };
+        self.signature_guard.check_unlocked(oui).await?;
+        let org_keys = org::get_org_pubkeys(oui, &self.pool)
+            .await
+            .map_err(|_| Status::internal("auth verification error"))?;
 
         if org_keys.as_slice().contains(signer) && request.verify(signer).is_ok() {
             tracing::debug!(
@@ -103,6 +116,9 @@ impl RouteService {
             return Ok(());
         }
 
+        self.signature_guard
+            .record_failure(oui, signer, "route")
+            .await;
         Err(Status::permission_denied("unauthorized request signature"))
     }
 
@@ -132,7 +148,7 @@ impl RouteService {
         &self,
         route_id: &str,
         check_constraints: bool,
-    ) -> Result<DevAddrEuiValidator, OrgStoreError> {
+    ) -> crate::Result<DevAddrEuiValidator> {
         let admin_keys = self.auth_cache.get_keys_by_type(KeyType::Administrator);
 
         DevAddrEuiValidator::new(route_id, admin_keys, &self.pool, check_constraints).await
@@ -145,7 +161,7 @@ impl RouteService {
     ) -> Result<(), Status> {
         let ranges: Vec<DevAddrRange> = route::list_devaddr_ranges_for_route(route_id, &self.pool)
             .map_err(|err| match err {
-                RouteStorageError::UuidParse(_) => {
+                Error::Decode(_) => {
                     Status::invalid_argument(format!("unable to parse route_id: {route_id}"))
                 }
                 _ => Status::internal("error retrieving devaddrs for route"),
@@ -216,7 +232,7 @@ impl iot_config::Route for RouteService {
             .await
             .map_err(|err| {
                 tracing::warn!("fetch route failed: {err:?}");
-                Status::internal("fetch route failed")
+                Status::from(err)
             })?;
 
         let mut resp = RouteResV1 {
@@ -237,6 +253,7 @@ impl iot_config::Route for RouteService {
         let signer = verify_public_key(&request.signer)?;
         self.verify_request_signature(&signer, &request, OrgId::Oui(request.oui))
             .await?;
+        let audit_entry = audit::Entry::new("route.create", Some(request.oui), &signer, &request);
 
         let route: Route = request
             .route
@@ -261,6 +278,7 @@ impl iot_config::Route for RouteService {
             &self.pool,
             &self.signing_key,
             self.clone_update_channel(),
+            audit_entry,
         )
         .await
         .map_err(|err| {
@@ -298,17 +316,22 @@ impl iot_config::Route for RouteService {
         let signer = verify_public_key(&request.signer)?;
         self.verify_request_signature(&signer, &request, OrgId::RouteId(&route.id))
             .await?;
+        let audit_entry = audit::Entry::new("route.update", None, &signer, &request);
 
         let updated_route = route::update_route(
             route,
             &self.pool,
             &self.signing_key,
             self.clone_update_channel(),
+            audit_entry,
         )
         .await
         .map_err(|err| {
             tracing::error!("route update failed {err:?}");
-            Status::internal("update route failed")
+            match err {
+                Error::NotFound(_) | Error::Decode(_) => Status::from(err),
+                _ => Status::internal("update route failed"),
+            }
         })?;
 
         let mut resp = RouteResV1 {
@@ -332,20 +355,21 @@ impl iot_config::Route for RouteService {
 
         tracing::debug!(route_id = request.id, "route delete");
 
-        let route = route::get_route(&request.id, &self.pool)
-            .await
-            .map_err(|_| Status::internal("fetch route failed"))?;
-
-        route::delete_route(
+        let audit_entry = audit::Entry::new("route.delete", None, &signer, &request);
+        let route = route::delete_route(
             &request.id,
             &self.pool,
             &self.signing_key,
             self.clone_update_channel(),
+            audit_entry,
         )
         .await
         .map_err(|err| {
             tracing::error!("route delete failed {err:?}");
-            Status::internal("delete route failed")
+            match err {
+                Error::NotFound(_) | Error::Decode(_) => Status::from(err),
+                _ => Status::internal("delete route failed"),
+            }
         })?;
 
         let mut resp = RouteResV1 {
@@ -396,8 +420,27 @@ impl iot_config::Route for RouteService {
                         telemetry::route_stream_unsubscribe();
                         return
                     }
-                    msg = route_updates.recv() => if let Ok(update) = msg {
-                        if tx.send(Ok(update)).await.is_err() {
+                    msg = route_updates.recv() => match msg {
+                        Ok(update) => {
+                            if tx.send(Ok(update)).await.is_err() {
+                                telemetry::route_stream_unsubscribe();
+                                return;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            // updates were dropped for this subscriber, close
+                            // the stream so it resyncs from a fresh snapshot
+                            tracing::warn!(
+                                skipped,
+                                "route stream subscriber lagged; closing stream"
+                            );
+                            _ = tx
+                                .send(Err(Status::aborted("route stream lagged, resubscribe")))
+                                .await;
+                            telemetry::route_stream_unsubscribe();
+                            return;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
                             telemetry::route_stream_unsubscribe();
                             return;
                         }
@@ -429,10 +472,8 @@ impl iot_config::Route for RouteService {
         tokio::spawn(async move {
             let mut eui_stream = match route::list_euis_for_route(&request.route_id, &pool) {
                 Ok(euis) => euis,
-                Err(RouteStorageError::UuidParse(err)) => {
-                    _ = tx
-                        .send(Err(Status::invalid_argument(format!("{}", err))))
-                        .await;
+                Err(Error::Decode(err)) => {
+                    _ = tx.send(Err(Status::invalid_argument(err))).await;
                     return;
                 }
                 Err(_) => {
@@ -477,9 +518,7 @@ impl iot_config::Route for RouteService {
                         Some(ref eui_pair) => self
                             .update_validator(&eui_pair.route_id, false)
                             .await
-                            .map_err(|err| {
-                                Status::internal(format!("unable to verify updates: {err:?}"))
-                            }),
+                            .map_err(Status::from),
                         None => Err(Status::invalid_argument("no eui pairs provided")),
                     },
                     Err(_) => Err(Status::invalid_argument("no eui pairs provided")),
@@ -487,10 +526,11 @@ impl iot_config::Route for RouteService {
             })
             .ok_or_else(|| Status::invalid_argument("no eui pairs provided"))?
             .await?;
+        let oui = validator.oui;
 
         incoming_stream
             .map_ok(|update| match validator.validate_update(&update) {
-                Ok(()) => Ok(update),
+                Ok(signer) => Ok((signer, update)),
                 Err(reason) => Err(Status::invalid_argument(format!(
                     "invalid update request: {reason:?}"
                 ))),
@@ -500,10 +540,14 @@ impl iot_config::Route for RouteService {
             .and_then(|batch| async move {
                 batch
                     .into_iter()
-                    .collect::<Result<Vec<RouteUpdateEuisReqV1>, Status>>()
+                    .collect::<Result<Vec<(PublicKey, RouteUpdateEuisReqV1)>, Status>>()
             })
             .and_then(|batch| async move {
-                batch
+                let (signers, requests): (Vec<PublicKey>, Vec<RouteUpdateEuisReqV1>) =
+                    batch.into_iter().unzip();
+                let audit_entry =
+                    audit::Entry::batch("route.update-euis", Some(oui), &signers[0], &requests);
+                requests
                     .into_iter()
                     .map(
                         |update: RouteUpdateEuisReqV1| match (update.action(), update.eui_pair) {
@@ -513,37 +557,43 @@ impl iot_config::Route for RouteService {
                         },
                     )
                     .collect::<Result<Vec<(ActionV1, EuiPairV1)>, Status>>()
+                    .map(|batch| (audit_entry, batch))
             })
-            .try_for_each(|batch: Vec<(ActionV1, EuiPairV1)>| async move {
-                let (to_add, to_remove): (Vec<(ActionV1, EuiPairV1)>, Vec<(ActionV1, EuiPairV1)>) =
-                    batch
+            .try_for_each(
+                |(audit_entry, batch): (audit::Entry, Vec<(ActionV1, EuiPairV1)>)| async move {
+                    let (to_add, to_remove): (
+                        Vec<(ActionV1, EuiPairV1)>,
+                        Vec<(ActionV1, EuiPairV1)>,
+                    ) = batch
                         .into_iter()
                         .partition(|(action, _update)| action == &ActionV1::Add);
-                telemetry::count_eui_updates(to_add.len(), to_remove.len());
-                tracing::debug!(
-                    adding = to_add.len(),
-                    removing = to_remove.len(),
-                    "updating eui pairs"
-                );
-                let adds_update: Vec<EuiPair> =
-                    to_add.into_iter().map(|(_, add)| add.into()).collect();
-                let removes_update: Vec<EuiPair> = to_remove
-                    .into_iter()
-                    .map(|(_, remove)| remove.into())
-                    .collect();
-                route::update_euis(
-                    &adds_update,
-                    &removes_update,
-                    &self.pool,
-                    self.signing_key.clone(),
-                    self.clone_update_channel(),
-                )
-                .await
-                .map_err(|err| {
-                    tracing::error!("eui pair update failed: {err:?}");
-                    Status::internal(format!("eui pair update failed: {err:?}"))
-                })
-            })
+                    telemetry::count_eui_updates(to_add.len(), to_remove.len());
+                    tracing::debug!(
+                        adding = to_add.len(),
+                        removing = to_remove.len(),
+                        "updating eui pairs"
+                    );
+                    let adds_update: Vec<EuiPair> =
+                        to_add.into_iter().map(|(_, add)| add.into()).collect();
+                    let removes_update: Vec<EuiPair> = to_remove
+                        .into_iter()
+                        .map(|(_, remove)| remove.into())
+                        .collect();
+                    route::update_euis(
+                        &adds_update,
+                        &removes_update,
+                        &self.pool,
+                        self.signing_key.clone(),
+                        self.clone_update_channel(),
+                        audit_entry,
+                    )
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("eui pair update failed: {err:?}");
+                        Status::internal(format!("eui pair update failed: {err:?}"))
+                    })
+                },
+            )
             .await?;
 
         let mut resp = RouteEuisResV1 {
@@ -577,10 +627,8 @@ impl iot_config::Route for RouteService {
             let mut devaddrs = match route::list_devaddr_ranges_for_route(&request.route_id, &pool)
             {
                 Ok(devaddrs) => devaddrs,
-                Err(RouteStorageError::UuidParse(err)) => {
-                    _ = tx
-                        .send(Err(Status::invalid_argument(format!("{}", err))))
-                        .await;
+                Err(Error::Decode(err)) => {
+                    _ = tx.send(Err(Status::invalid_argument(err))).await;
                     return;
                 }
                 Err(_) => {
@@ -625,9 +673,7 @@ impl iot_config::Route for RouteService {
                         Some(ref devaddr_range) => self
                             .update_validator(&devaddr_range.route_id, true)
                             .await
-                            .map_err(|err| {
-                                Status::internal(format!("unable to verify update {err:?}"))
-                            }),
+                            .map_err(Status::from),
                         None => Err(Status::invalid_argument("no devaddr range provided")),
                     },
                     Err(_) => Err(Status::invalid_argument("no devaddr range provided")),
@@ -635,10 +681,11 @@ impl iot_config::Route for RouteService {
             })
             .ok_or_else(|| Status::invalid_argument("no devaddr range provided"))?
             .await?;
+        let oui = validator.oui;
 
         incoming_stream
             .map_ok(|update| match validator.validate_update(&update) {
-                Ok(()) => Ok(update),
+                Ok(signer) => Ok((signer, update)),
                 Err(reason) => Err(Status::invalid_argument(format!(
                     "invalid update request: {reason:?}"
                 ))),
@@ -650,10 +697,18 @@ impl iot_config::Route for RouteService {
             .and_then(|batch| async move {
                 batch
                     .into_iter()
-                    .collect::<Result<Vec<RouteUpdateDevaddrRangesReqV1>, Status>>()
+                    .collect::<Result<Vec<(PublicKey, RouteUpdateDevaddrRangesReqV1)>, Status>>()
             })
             .and_then(|batch| async move {
-                batch
+                let (signers, requests): (Vec<PublicKey>, Vec<RouteUpdateDevaddrRangesReqV1>) =
+                    batch.into_iter().unzip();
+                let audit_entry = audit::Entry::batch(
+                    "route.update-devaddr-ranges",
+                    Some(oui),
+                    &signers[0],
+                    &requests,
+                );
+                requests
                     .into_iter()
                     .map(|update: RouteUpdateDevaddrRangesReqV1| {
                         match (update.action(), update.devaddr_range) {
@@ -665,39 +720,43 @@ impl iot_config::Route for RouteService {
                         }
                     })
                     .collect::<Result<Vec<(ActionV1, DevaddrRangeV1)>, Status>>()
+                    .map(|batch| (audit_entry, batch))
             })
-            .try_for_each(|batch: Vec<(ActionV1, DevaddrRangeV1)>| async move {
-                let (to_add, to_remove): (
-                    Vec<(ActionV1, DevaddrRangeV1)>,
-                    Vec<(ActionV1, DevaddrRangeV1)>,
-                ) = batch
-                    .into_iter()
-                    .partition(|(action, _update)| action == &ActionV1::Add);
-                telemetry::count_devaddr_updates(to_add.len(), to_remove.len());
-                tracing::debug!(
-                    adding = to_add.len(),
-                    removing = to_remove.len(),
-                    "updating devaddr ranges"
-                );
-                let adds_update: Vec<DevAddrRange> =
-                    to_add.into_iter().map(|(_, add)| add.into()).collect();
-                let removes_update: Vec<DevAddrRange> = to_remove
-                    .into_iter()
-                    .map(|(_, remove)| remove.into())
-                    .collect();
-                route::update_devaddr_ranges(
-                    &adds_update,
-                    &removes_update,
-                    &self.pool,
-                    self.signing_key.clone(),
-                    self.clone_update_channel(),
-                )
-                .await
-                .map_err(|err| {
-                    tracing::error!("devaddr range update failed: {err:?}");
-                    Status::internal("devaddr range update failed")
-                })
-            })
+            .try_for_each(
+                |(audit_entry, batch): (audit::Entry, Vec<(ActionV1, DevaddrRangeV1)>)| async move {
+                    let (to_add, to_remove): (
+                        Vec<(ActionV1, DevaddrRangeV1)>,
+                        Vec<(ActionV1, DevaddrRangeV1)>,
+                    ) = batch
+                        .into_iter()
+                        .partition(|(action, _update)| action == &ActionV1::Add);
+                    telemetry::count_devaddr_updates(to_add.len(), to_remove.len());
+                    tracing::debug!(
+                        adding = to_add.len(),
+                        removing = to_remove.len(),
+                        "updating devaddr ranges"
+                    );
+                    let adds_update: Vec<DevAddrRange> =
+                        to_add.into_iter().map(|(_, add)| add.into()).collect();
+                    let removes_update: Vec<DevAddrRange> = to_remove
+                        .into_iter()
+                        .map(|(_, remove)| remove.into())
+                        .collect();
+                    route::update_devaddr_ranges(
+                        &adds_update,
+                        &removes_update,
+                        &self.pool,
+                        self.signing_key.clone(),
+                        self.clone_update_channel(),
+                        audit_entry,
+                    )
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("devaddr range update failed: {err:?}");
+                        Status::internal("devaddr range update failed")
+                    })
+                },
+            )
             .await?;
 
         let mut resp = RouteDevaddrRangesResV1 {
@@ -733,10 +792,8 @@ impl iot_config::Route for RouteService {
         tokio::spawn(async move {
             let mut skf_stream = match route::list_skfs_for_route(&request.route_id, &pool) {
                 Ok(skfs) => skfs,
-                Err(RouteStorageError::UuidParse(err)) => {
-                    _ = tx
-                        .send(Err(Status::invalid_argument(format!("{}", err))))
-                        .await;
+                Err(Error::Decode(err)) => {
+                    _ = tx.send(Err(Status::invalid_argument(err))).await;
                     return;
                 }
                 Err(_) => {
@@ -791,10 +848,8 @@ impl iot_config::Route for RouteService {
                 &pool,
             ) {
                 Ok(skfs) => skfs,
-                Err(RouteStorageError::UuidParse(err)) => {
-                    _ = tx
-                        .send(Err(Status::invalid_argument(format!("{}", err))))
-                        .await;
+                Err(Error::Decode(err)) => {
+                    _ = tx.send(Err(Status::invalid_argument(err))).await;
                     return;
                 }
                 Err(_) => {
@@ -841,6 +896,10 @@ impl iot_config::Route for RouteService {
 
         self.validate_skf_devaddrs(&request.route_id, &request.updates)
             .await?;
+        let oui = org::get_oui_by_route(&request.route_id, &self.pool)
+            .await
+            .map_err(Status::from)?;
+        let audit_entry = audit::Entry::new("route.update-skfs", Some(oui), &signer, &request);
 
         let (to_add, to_remove): (Vec<(ActionV1, Skf)>, Vec<(ActionV1, Skf)>) = request
             .updates
@@ -871,6 +930,7 @@ impl iot_config::Route for RouteService {
             &self.pool,
             self.signing_key.clone(),
             self.clone_update_channel(),
+            audit_entry,
         )
         .await
         .map_err(|err| {
@@ -889,6 +949,7 @@ impl iot_config::Route for RouteService {
 }
 
 struct DevAddrEuiValidator {
+    oui: u64,
     route_ids: Vec<String>,
     constraints: Option<Vec<DevAddrConstraint>>,
     signing_keys: Vec<PublicKey>,
@@ -912,7 +973,7 @@ impl DevAddrEuiValidator {
         mut admin_keys: Vec<PublicKey>,
         db: impl sqlx::PgExecutor<'_> + Copy,
         check_constraints: bool,
-    ) -> Result<Self, OrgStoreError> {
+    ) -> crate::Result<Self> {
         let constraints = if check_constraints {
             Some(org::get_constraints_by_route(route_id, db).await?)
         } else {
@@ -923,13 +984,15 @@ impl DevAddrEuiValidator {
         org_keys.append(&mut admin_keys);
 
         Ok(Self {
+            oui: org::get_oui_by_route(route_id, db).await?,
             route_ids: org::get_route_ids_by_route(route_id, db).await?,
             constraints,
             signing_keys: org_keys,
         })
     }
 
-    fn validate_update<'a, R>(&'a mut self, request: &'a R) -> Result<(), Status>
+    /// Validate the update, returning the key which signed it
+    fn validate_update<'a, R>(&'a mut self, request: &'a R) -> Result<PublicKey, Status>
     where
         R: MsgVerify + ValidateRouteComponent<'a> + std::fmt::Debug,
     {
@@ -937,7 +1000,8 @@ impl DevAddrEuiValidator {
             .and_then(|update| validate_range_bounds(update, self.constraints.as_ref()))
             .and_then(|update| validate_signature(update, &mut self.signing_keys))
             .map_err(|err| Status::invalid_argument(format!("{err:?}")))?;
-        Ok(())
+        // the key which verified the update is moved to the front
+        Ok(self.signing_keys[0].clone())
     }
 }
 
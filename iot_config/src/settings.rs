@@ -1,9 +1,9 @@
-use config::{Config, Environment, File};
 use serde::Deserialize;
 use std::{
     net::{AddrParseError, SocketAddr},
     path::Path,
     str::FromStr,
+    time::Duration,
 };
 
 #[derive(Debug, Deserialize)]
@@ -24,6 +24,30 @@ pub struct Settings {
     /// the database for Solana on-chain data
     pub metadata: db_store::Settings,
     pub metrics: poc_metrics::Settings,
+    /// How long a gateway not found in the metadata db is remembered as
+    /// such, sparing the db a lookup on every request for it, 0 disables.
+    /// Default is 1m
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_gateway_not_found_ttl"
+    )]
+    pub gateway_not_found_ttl: Duration,
+    /// Interval between reloads of the auth keys from the database, picking
+    /// up keys added or removed through other instances. Default is 1m
+    #[serde(
+        with = "settings_loader::duration::positive",
+        default = "default_auth_cache_refresh_interval"
+    )]
+    pub auth_cache_refresh_interval: Duration,
+    /// Settings for security events on repeated signature failures for an org
+    #[serde(default)]
+    pub signature_guard: crate::signature_guard::Settings,
+    /// Settings for the delivery of change events to the webhooks of orgs
+    #[serde(default)]
+    pub webhooks: crate::webhooks::Settings,
+    /// Settings for the versioned stream of session key filters
+    #[serde(default)]
+    pub skf_stream: crate::skf_versions::Settings,
 }
 
 pub fn default_log() -> String {
@@ -34,6 +58,14 @@ pub fn default_listen_addr() -> String {
     "0.0.0.0:8080".to_string()
 }
 
+pub fn default_gateway_not_found_ttl() -> Duration {
+    Duration::from_secs(60)
+}
+
+pub fn default_auth_cache_refresh_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
 impl Settings {
     /// Settings can be loaded from a given optional path and
     /// can be overridden with environment variables.
@@ -41,21 +73,11 @@ impl Settings {
     /// Environment overrides have the same name as the entries
     /// in the settings file in uppercase and prefixed with "CFG_".
     /// Example: "CFG_DATABASE_URL" will override the database url.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-
-        // Add in settings from the environment (with prefix of APP)
-        // E.g. `CFG_DEBUG=1 .target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("CFG").separator("__"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::Loader::new("CFG")
+            .env_separator("__")
+            .file(path)
+            .load()
     }
 
     pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
@@ -63,8 +85,11 @@ impl Settings {
     }
 
     pub fn signing_keypair(&self) -> Result<helium_crypto::Keypair, Box<helium_crypto::Error>> {
-        let data = std::fs::read(&self.keypair).map_err(helium_crypto::Error::from)?;
-        Ok(helium_crypto::Keypair::try_from(&data[..])?)
+        Ok(settings_loader::keypair(&self.keypair)?)
+    }
+
+    pub fn gateway_not_found_ttl(&self) -> Option<Duration> {
+        (!self.gateway_not_found_ttl.is_zero()).then_some(self.gateway_not_found_ttl)
     }
 
     pub fn admin_pubkey(&self) -> Result<helium_crypto::PublicKey, helium_crypto::Error> {
@@ -0,0 +1,267 @@
+//! Detection of repeated signature failures against an org.
+
+use crate::telemetry;
+use chrono::{DateTime, Duration, Utc};
+use helium_crypto::PublicKey;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use tonic::Status;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Signature failures for an org within the failure window raising a
+    /// security event. Default is 5
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Window over which signature failures are counted. Default is 10m
+    #[serde(with = "settings_loader::duration", default = "default_failure_window")]
+    pub failure_window: std::time::Duration,
+    /// Age past which recorded signature failures are pruned, never shorter
+    /// than the failure window. Default is 7d
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_failure_retention"
+    )]
+    pub failure_retention: std::time::Duration,
+    /// Lock the org's mutation apis when a security event is raised, until
+    /// an administrator clears the lock through the `org_lock` service.
+    /// Anyone can send badly signed requests naming any org, so locking lets
+    /// a third party freeze an org's mutations. Default is false
+    #[serde(default)]
+    pub lock: bool,
+    /// Optional url to which security events are POSTed as json
+    pub webhook: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            failure_window: default_failure_window(),
+            failure_retention: default_failure_retention(),
+            lock: false,
+            webhook: None,
+        }
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_failure_window() -> std::time::Duration {
+    std::time::Duration::from_secs(10 * 60)
+}
+
+fn default_failure_retention() -> std::time::Duration {
+    std::time::Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+/// Security events are delivered off the request path, bounded so a slow
+/// webhook can't pile up deliveries
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+pub struct SecurityEvent {
+    pub oui: u64,
+    /// Signature failures within the window, including the one raising the
+    /// event
+    pub failures: u64,
+    pub window_secs: u64,
+    /// Whether the org's mutation apis have been locked
+    pub locked: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct SignatureGuard {
+    pool: Pool<Postgres>,
+    failure_threshold: u64,
+    failure_window: Duration,
+    failure_retention: Duration,
+    lock: bool,
+    webhook: Option<String>,
+    http: reqwest::Client,
+}
+
+impl SignatureGuard {
+    pub fn new(settings: &Settings, pool: Pool<Postgres>) -> reqwest::Result<Self> {
+        let failure_window =
+            Duration::from_std(settings.failure_window).unwrap_or_else(|_| Duration::minutes(10));
+        let failure_retention = Duration::from_std(settings.failure_retention)
+            .unwrap_or_else(|_| Duration::days(7))
+            .max(failure_window);
+        Ok(Self {
+            pool,
+            failure_threshold: settings.failure_threshold.into(),
+            failure_window,
+            failure_retention,
+            lock: settings.lock,
+            webhook: settings.webhook.clone(),
+            http: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    /// Refuse mutations of a locked org. Call once the signer is known not to
+    /// be an administrator
+    pub async fn check_unlocked(&self, oui: u64) -> Result<(), Status> {
+        if !self.lock {
+            return Ok(());
+        }
+        let locked = is_locked(oui, &self.pool).await.map_err(|err| {
+            tracing::error!(oui, reason = ?err, "org lock lookup failed");
+            Status::internal("auth verification error")
+        })?;
+        if locked {
+            return Err(Status::failed_precondition(format!(
+                "oui {oui} locked after repeated signature failures"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record a failed signature on a mutation of `oui` in the
+    /// `org_signature_failures` audit log, raising a security event once the
+    /// org reaches the failure threshold: logged, counted and POSTed to the
+    /// webhook if configured. Failures past the retention are pruned as new
+    /// ones are recorded. Failures to record are logged, never surfaced to the
+    /// caller
+    pub async fn record_failure(&self, oui: u64, signer: &PublicKey, rpc: &'static str) {
+        telemetry::count_signature_failure(rpc);
+        if let Err(err) = self.try_record_failure(oui, signer, rpc).await {
+            tracing::error!(oui, reason = ?err, "failed to record signature failure");
+        }
+    }
+
+    async fn try_record_failure(
+        &self,
+        oui: u64,
+        signer: &PublicKey,
+        rpc: &'static str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let failures =
+            insert_failure(oui, signer, rpc, now - self.failure_window, &self.pool).await?;
+        prune_failures(now - self.failure_retention, &self.pool).await?;
+        tracing::warn!(oui, %signer, rpc, failures, "org signature failure");
+        if failures < self.failure_threshold {
+            return Ok(());
+        }
+
+        // an org unlocked while still failing is locked again by the next
+        // failure, which raises a new event
+        let locked = self.lock && lock(oui, failures, &self.pool).await?;
+        if failures == self.failure_threshold || locked {
+            self.raise(SecurityEvent {
+                oui,
+                failures,
+                window_secs: self.failure_window.num_seconds() as u64,
+                locked,
+                timestamp: now,
+            });
+        }
+        Ok(())
+    }
+
+    fn raise(&self, event: SecurityEvent) {
+        tracing::warn!(
+            oui = event.oui,
+            failures = event.failures,
+            window_secs = event.window_secs,
+            locked = event.locked,
+            "repeated signature failures for org"
+        );
+        telemetry::count_security_event(event.locked);
+        if let Some(url) = &self.webhook {
+            // webhook delivery is best effort; the audit log is the record
+            let oui = event.oui;
+            let request = self.http.post(url).json(&event);
+            tokio::spawn(async move {
+                let result = request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(err) = result {
+                    tracing::error!(oui, "failed to deliver security event: {err}");
+                }
+            });
+        }
+    }
+}
+
+/// Record a signature failure and return the number of failures for the org
+/// since `since`
+async fn insert_failure(
+    oui: u64,
+    signer: &PublicKey,
+    rpc: &str,
+    since: DateTime<Utc>,
+    db: &Pool<Postgres>,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        r#"
+        insert into org_signature_failures (oui, signer_pubkey, rpc)
+        values ($1, $2, $3)
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(signer.to_string())
+    .bind(rpc)
+    .execute(db)
+    .await?;
+
+    let failures = sqlx::query_scalar::<_, i64>(
+        "select count(*) from org_signature_failures where oui = $1 and inserted_at >= $2",
+    )
+    .bind(oui as i64)
+    .bind(since)
+    .fetch_one(db)
+    .await?;
+    Ok(failures as u64)
+}
+
+/// Delete the signature failures of every org recorded before `before`
+async fn prune_failures(before: DateTime<Utc>, db: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let deleted = sqlx::query("delete from org_signature_failures where inserted_at < $1")
+        .bind(before)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(deleted)
+}
+
+/// Lock the org's mutation apis, returning whether it wasn't locked already
+async fn lock(oui: u64, failures: u64, db: &Pool<Postgres>) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(
+        r#"
+        insert into org_mutation_locks (oui, failures)
+        values ($1, $2)
+        on conflict (oui) do nothing
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(failures as i64)
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(inserted > 0)
+}
+
+pub async fn is_locked(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("select exists(select 1 from org_mutation_locks where oui = $1)")
+        .bind(oui as i64)
+        .fetch_one(db)
+        .await
+}
+
+/// Clear the lock of an org, returning whether it was locked
+pub async fn unlock(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query("delete from org_mutation_locks where oui = $1")
+        .bind(oui as i64)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(deleted > 0)
+}
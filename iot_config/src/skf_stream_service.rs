@@ -0,0 +1,241 @@
+use crate::{
+    admin::AuthCache,
+    ext::{self, SkfChangeV1, SkfStreamKindV1, SkfStreamReqV1, SkfStreamResV1},
+    skf_versions::{self, Action, OrgSkf, SkfChange, Snapshot},
+    telemetry, verify_public_key, GrpcResult, GrpcStreamResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, Sign};
+use helium_proto::{services::iot_config::ActionV1, Message};
+use sqlx::{Pool, Postgres};
+use std::sync::Arc;
+use tokio::{sync::mpsc, time::MissedTickBehavior};
+use tonic::{Request, Response, Status};
+
+/// Streams the session key filters of every org, a snapshot followed by the
+/// versioned changes after it, for packet routers to keep a local copy
+pub struct SkfStreamService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    settings: skf_versions::Settings,
+    signing_key: Arc<Keypair>,
+    shutdown: triggered::Listener,
+}
+
+impl SkfStreamService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        shutdown: triggered::Listener,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            settings: settings.skf_stream.clone(),
+            signing_key: Arc::new(settings.signing_keypair()?),
+            shutdown,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ext::skf_stream_server::SkfStream for SkfStreamService {
+    type streamStream = GrpcStreamResult<SkfStreamResV1>;
+    async fn stream(&self, request: Request<SkfStreamReqV1>) -> GrpcResult<Self::streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("skf-stream", "stream");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.auth_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+        tracing::debug!(since_version = request.since_version, "streaming skfs");
+
+        let (tx, rx) = mpsc::channel(20);
+        let stream = SkfStream {
+            pool: self.pool.clone(),
+            settings: self.settings.clone(),
+            signer: self.signing_key.public_key().into(),
+            signing_key: self.signing_key.clone(),
+            tx,
+        };
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            telemetry::skf_stream_subscribe();
+            let result = tokio::select! {
+                result = stream.run(request.since_version) => result,
+                _ = shutdown => Ok(()),
+            };
+            if let Err(err) = result {
+                tracing::error!(reason = ?err, "skf stream failed");
+                _ = stream.tx.send(Err(err)).await;
+            }
+            telemetry::skf_stream_unsubscribe();
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+struct SkfStream {
+    pool: Pool<Postgres>,
+    settings: skf_versions::Settings,
+    signer: Vec<u8>,
+    signing_key: Arc<Keypair>,
+    tx: mpsc::Sender<Result<SkfStreamResV1, Status>>,
+}
+
+impl SkfStream {
+    /// Stream the changes after `since_version`, or a snapshot when it can't
+    /// be resumed from, then the changes committed from then on until the
+    /// client disconnects
+    async fn run(&self, since_version: u64) -> Result<(), Status> {
+        let batch_size = self.settings.batch_size;
+        let resumed = match since_version {
+            0 => None,
+            version => skf_versions::resume(version, batch_size, &self.pool)
+                .await
+                .map_err(read_error)?,
+        };
+        let (mut version, mut changes) = match resumed {
+            Some(changes) => (since_version, changes),
+            None => match self.stream_snapshot().await? {
+                Some(version) => (version, vec![]),
+                None => return Ok(()),
+            },
+        };
+
+        let mut poll = tokio::time::interval(self.settings.poll_interval);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let caught_up = changes.len() < batch_size as usize;
+            if let Some(last) = changes.last() {
+                version = last.version;
+                let changes = changes.into_iter().map(SkfChangeV1::from).collect();
+                if !self.send(SkfStreamKindV1::Delta, version, changes).await? {
+                    return Ok(());
+                }
+            }
+            if caught_up {
+                tokio::select! {
+                    _ = self.tx.closed() => return Ok(()),
+                    _ = poll.tick() => (),
+                }
+            }
+            changes = skf_versions::changes_after(version, batch_size, &self.pool)
+                .await
+                .map_err(read_error)?;
+        }
+    }
+
+    /// Stream every filter as of the version of the snapshot, followed by
+    /// the end of the snapshot. The version, unless the client disconnected
+    async fn stream_snapshot(&self) -> Result<Option<u64>, Status> {
+        let mut snapshot = Snapshot::begin(&self.pool).await.map_err(read_error)?;
+        let version = snapshot.version;
+        tracing::debug!(version, "streaming skf snapshot");
+
+        loop {
+            let skfs = snapshot
+                .next_page(self.settings.batch_size)
+                .await
+                .map_err(read_error)?;
+            if skfs.is_empty() {
+                break;
+            }
+            let changes = skfs
+                .into_iter()
+                .map(|filter| change_v1(version, ActionV1::Add, filter))
+                .collect();
+            if !self
+                .send(SkfStreamKindV1::Snapshot, version, changes)
+                .await?
+            {
+                return Ok(None);
+            }
+        }
+        if !self
+            .send(SkfStreamKindV1::SnapshotEnd, version, vec![])
+            .await?
+        {
+            return Ok(None);
+        }
+        Ok(Some(version))
+    }
+
+    /// Send a signed message, false when the client disconnected
+    async fn send(
+        &self,
+        kind: SkfStreamKindV1,
+        version: u64,
+        changes: Vec<SkfChangeV1>,
+    ) -> Result<bool, Status> {
+        let mut response = SkfStreamResV1 {
+            kind: kind.into(),
+            version,
+            changes,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signer.clone(),
+            signature: vec![],
+        };
+        response.signature = self
+            .signing_key
+            .sign(&response.encode_to_vec())
+            .map_err(|_| Status::internal("response signing error"))?;
+        Ok(self.tx.send(Ok(response)).await.is_ok())
+    }
+}
+
+fn read_error(err: sqlx::Error) -> Status {
+    Status::internal(format!("skf read failed: {err:?}"))
+}
+
+fn change_v1(version: u64, action: ActionV1, filter: OrgSkf) -> SkfChangeV1 {
+    SkfChangeV1 {
+        version,
+        action: action.into(),
+        oui: filter.oui,
+        skf: Some(filter.skf.into()),
+    }
+}
+
+impl From<SkfChange> for SkfChangeV1 {
+    fn from(change: SkfChange) -> Self {
+        let action = match change.action {
+            Action::Add => ActionV1::Add,
+            Action::Remove => ActionV1::Remove,
+        };
+        change_v1(change.version, action, change.filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lora_field::{devaddr, Skf};
+
+    #[test]
+    fn skf_change_keeps_version_and_oui() {
+        let change = SkfChange {
+            version: 42,
+            action: Action::Remove,
+            filter: OrgSkf {
+                oui: 7,
+                skf: Skf::new(
+                    "2c9c7b1c-2b0e-4a7e-8c2e-9d5e3f1a6b4d".to_string(),
+                    devaddr(0x48000001),
+                    "00112233445566778899aabbccddeeff".to_string(),
+                    2,
+                ),
+            },
+        };
+        let change = SkfChangeV1::from(change);
+        assert_eq!(42, change.version);
+        assert_eq!(7, change.oui);
+        assert_eq!(ActionV1::Remove as i32, change.action);
+        assert_eq!(0x48000001, change.skf.expect("skf").devaddr);
+    }
+}
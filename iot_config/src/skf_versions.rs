@@ -0,0 +1,464 @@
+//! Versioned changes of the session key filters, for packet routers to keep
+//! an up to date copy of every filter.
+
+use crate::lora_field::Skf;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::{postgres::PgRow, FromRow, Pool, Postgres, Row};
+use std::time::Duration;
+
+/// Interval between prunes of the changes past their retention
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Interval at which streams poll for the changes after the version they
+    /// sent last. Default is 1s
+    #[serde(with = "settings_loader::duration", default = "default_poll_interval")]
+    pub poll_interval: Duration,
+    /// Time changes are kept for streams to resume from. Default is 7 days
+    #[serde(with = "settings_loader::duration", default = "default_retention")]
+    pub retention: Duration,
+    /// Filters, or changes, sent per stream message. Default is 1000
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            poll_interval: default_poll_interval(),
+            retention: default_retention(),
+            batch_size: default_batch_size(),
+        }
+    }
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_retention() -> Duration {
+    Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+fn default_batch_size() -> u32 {
+    1_000
+}
+
+/// A change recorded by the trigger on every insert, update and delete of a
+/// filter in `skf_changes`, numbered by a version increasing in commit order,
+/// so the changes after a version are read once committed and never turn up
+/// below it later. A router streams a [`Snapshot`], then the changes after it,
+/// and resumes from the last version it applied when reconnecting
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    Add,
+    Remove,
+}
+
+/// A filter along with the org of its route
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrgSkf {
+    pub oui: u64,
+    pub skf: Skf,
+}
+
+impl FromRow<'_, PgRow> for OrgSkf {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            oui: row.try_get::<i64, &str>("oui")? as u64,
+            skf: Skf::from_row(row)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkfChange {
+    pub version: u64,
+    pub action: Action,
+    pub filter: OrgSkf,
+}
+
+impl FromRow<'_, PgRow> for SkfChange {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        let action = match row.try_get::<&str, &str>("action")? {
+            "add" => Action::Add,
+            "remove" => Action::Remove,
+            other => {
+                return Err(sqlx::Error::Decode(
+                    format!("invalid skf change action: {other}").into(),
+                ))
+            }
+        };
+        Ok(Self {
+            version: row.try_get::<i64, &str>("version")? as u64,
+            action,
+            filter: OrgSkf::from_row(row)?,
+        })
+    }
+}
+
+/// The latest version of the filters, the greatest version recorded or
+/// pruned
+async fn current_version(db: impl sqlx::PgExecutor<'_>) -> Result<u64, sqlx::Error> {
+    let version = sqlx::query_scalar::<_, i64>(
+        r#"
+        select greatest(
+            (select coalesce(max(version), 0) from skf_changes),
+            (select pruned_through from skf_change_horizon)
+        )
+        "#,
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(version as u64)
+}
+
+/// Every filter as of `version`, read a page at a time in key order.
+///
+/// Pages are read on their own rather than within a transaction held open
+/// for as long as a client takes to receive the snapshot, so they may already
+/// include changes after `version`. Each change adds or removes a single
+/// filter, so applying the changes after `version` once the snapshot has been
+/// applied brings the filters up to date all the same.
+pub struct Snapshot {
+    pub version: u64,
+    pool: Pool<Postgres>,
+    last: Option<Skf>,
+}
+
+impl Snapshot {
+    pub async fn begin(db: &Pool<Postgres>) -> Result<Self, sqlx::Error> {
+        let version = current_version(db).await?;
+        Ok(Self {
+            version,
+            pool: db.clone(),
+            last: None,
+        })
+    }
+
+    /// The next `limit` filters, none once every filter has been read
+    pub async fn next_page(&mut self, limit: u32) -> Result<Vec<OrgSkf>, sqlx::Error> {
+        let mut query: sqlx::QueryBuilder<Postgres> = sqlx::QueryBuilder::new(
+            " select oui, route_id, devaddr, session_key, max_copies from route_session_key_filters ",
+        );
+        if let Some(last) = self.last.clone() {
+            query
+                .push(" where (route_id, devaddr, session_key) > (")
+                .push_bind(last.route_id)
+                .push("::uuid, ")
+                .push_bind(i32::from(last.devaddr))
+                .push(", ")
+                .push_bind(last.session_key)
+                .push(") ");
+        }
+        query
+            .push(" order by route_id, devaddr, session_key limit ")
+            .push_bind(limit as i64);
+
+        let page = query
+            .build_query_as::<OrgSkf>()
+            .fetch_all(&self.pool)
+            .await?;
+        if let Some(last) = page.last() {
+            self.last = Some(last.skf.clone());
+        }
+        Ok(page)
+    }
+}
+
+/// Up to `limit` changes after `version`, in version order
+pub async fn changes_after(
+    version: u64,
+    limit: u32,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<Vec<SkfChange>, sqlx::Error> {
+    sqlx::query_as::<_, SkfChange>(
+        r#"
+        select version, action, oui, route_id, devaddr, session_key, max_copies
+            from skf_changes
+            where version > $1
+            order by version
+            limit $2
+        "#,
+    )
+    .bind(version as i64)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await
+}
+
+/// The first changes after `version` when a stream can resume from it, none
+/// when changes after it have been pruned or it is ahead of the current
+/// version, as after a restore of the database
+pub async fn resume(
+    version: u64,
+    limit: u32,
+    db: &Pool<Postgres>,
+) -> Result<Option<Vec<SkfChange>>, sqlx::Error> {
+    let mut txn = db.begin().await?;
+    sqlx::query("set transaction isolation level repeatable read, read only")
+        .execute(&mut txn)
+        .await?;
+    let pruned_through =
+        sqlx::query_scalar::<_, i64>(" select pruned_through from skf_change_horizon ")
+            .fetch_one(&mut txn)
+            .await? as u64;
+    if version < pruned_through || version > current_version(&mut txn).await? {
+        return Ok(None);
+    }
+    let changes = changes_after(version, limit, &mut txn).await?;
+    txn.commit().await?;
+    Ok(Some(changes))
+}
+
+/// Removes changes older than `retention`. A router resuming from a version
+/// pruned since gets a new snapshot
+pub struct Pruner {
+    pool: Pool<Postgres>,
+    retention: Duration,
+}
+
+impl Pruner {
+    pub fn new(settings: &Settings, pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            retention: settings.retention,
+        }
+    }
+
+    pub async fn run(self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting skf change pruner");
+        let mut trigger = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = trigger.tick() => match self.prune().await {
+                    Ok(pruned_through) => tracing::debug!(pruned_through, "pruned skf changes"),
+                    Err(err) => tracing::warn!(?err, "failed to prune skf changes"),
+                }
+            }
+        }
+        tracing::info!("stopping skf change pruner");
+        Ok(())
+    }
+
+    async fn prune(&self) -> anyhow::Result<i64> {
+        let before = Utc::now() - chrono::Duration::from_std(self.retention)?;
+        let pruned_through = sqlx::query_scalar::<_, i64>(
+            r#"
+            with pruned as (
+                delete from skf_changes where inserted_at < $1 returning version
+            )
+            update skf_change_horizon
+                set pruned_through = greatest(
+                    pruned_through,
+                    (select coalesce(max(version), 0) from pruned)
+                )
+                returning pruned_through
+            "#,
+        )
+        .bind(before)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(pruned_through)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lora_field::devaddr,
+        org::Org,
+        route,
+        test_support::{self, helium_org},
+    };
+
+    async fn add_route(org: &Org, pool: &Pool<Postgres>) -> String {
+        route::insert_route(&test_support::route(org.oui, 1), pool)
+            .await
+            .unwrap()
+    }
+
+    fn skf(route_id: &str, addr: u32) -> Skf {
+        Skf::new(
+            route_id.to_string(),
+            devaddr(addr),
+            "00112233445566778899aabbccddeeff".to_string(),
+            1,
+        )
+    }
+
+    async fn age_changes(through: u64, pool: &Pool<Postgres>) {
+        sqlx::query(
+            " update skf_changes set inserted_at = now() - interval '2 hours' where version <= $1 ",
+        )
+        .bind(through as i64)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn pruner(pool: &Pool<Postgres>) -> Pruner {
+        let settings = Settings {
+            retention: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        Pruner::new(&settings, pool.clone())
+    }
+
+    #[sqlx::test]
+    async fn every_filter_change_is_recorded_with_its_org(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let route_id = add_route(&org, &pool).await;
+        route::insert_skfs(&[skf(&route_id, 1), skf(&route_id, 2)], &pool)
+            .await
+            .unwrap();
+        sqlx::query(" update route_session_key_filters set max_copies = 3 where devaddr = 1 ")
+            .execute(&pool)
+            .await
+            .unwrap();
+        // deleting the route cascades to its filters:
+        sqlx::query(" delete from routes where id = $1::uuid ")
+            .bind(&route_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let changes = changes_after(0, 100, &pool).await.unwrap();
+        assert!(changes.windows(2).all(|w| w[0].version < w[1].version));
+        assert!(changes.iter().all(|change| change.filter.oui == org.oui));
+        let mut recorded: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.action == Action::Add,
+                    i32::from(change.filter.skf.devaddr),
+                    change.filter.skf.max_copies,
+                )
+            })
+            .collect();
+        recorded[..2].sort();
+        recorded[3..].sort();
+        assert_eq!(
+            vec![
+                (true, 1, 1),
+                (true, 2, 1),
+                (true, 1, 3),
+                (false, 1, 3),
+                (false, 2, 1)
+            ],
+            recorded
+        );
+    }
+
+    #[sqlx::test]
+    async fn versions_follow_commit_order(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let route_id = add_route(&org, &pool).await;
+
+        let mut first = pool.begin().await.unwrap();
+        route::insert_skfs(&[skf(&route_id, 1)], &mut first)
+            .await
+            .unwrap();
+
+        // a second writer waits for the first to commit, rather than
+        // committing a greater version before it:
+        let second = {
+            let (pool, route_id) = (pool.clone(), route_id.clone());
+            tokio::spawn(async move {
+                route::insert_skfs(&[skf(&route_id, 2)], &pool)
+                    .await
+                    .unwrap();
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!second.is_finished());
+        assert!(changes_after(0, 100, &pool).await.unwrap().is_empty());
+
+        first.commit().await.unwrap();
+        second.await.unwrap();
+        let committed: Vec<_> = changes_after(0, 100, &pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|change| i32::from(change.filter.skf.devaddr))
+            .collect();
+        assert_eq!(vec![1, 2], committed);
+    }
+
+    #[sqlx::test]
+    async fn streams_resume_until_their_version_is_pruned(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let route_id = add_route(&org, &pool).await;
+        route::insert_skfs(&[skf(&route_id, 1)], &pool)
+            .await
+            .unwrap();
+        let first = current_version(&pool).await.unwrap();
+        route::insert_skfs(&[skf(&route_id, 2)], &pool)
+            .await
+            .unwrap();
+        let second = current_version(&pool).await.unwrap();
+
+        let resumed = resume(0, 100, &pool).await.unwrap().expect("resumed");
+        assert_eq!(vec![first, second], versions(&resumed));
+        let resumed = resume(second, 100, &pool).await.unwrap();
+        assert_eq!(Some(vec![]), resumed);
+        // a version ahead of the current one, as after a restore:
+        assert_eq!(None, resume(second + 1, 100, &pool).await.unwrap());
+
+        age_changes(first, &pool).await;
+        assert_eq!(first as i64, pruner(&pool).prune().await.unwrap());
+        assert_eq!(None, resume(0, 100, &pool).await.unwrap());
+        let resumed = resume(first, 100, &pool).await.unwrap().expect("resumed");
+        assert_eq!(vec![second], versions(&resumed));
+
+        // the current version outlives the changes pruned:
+        age_changes(second, &pool).await;
+        assert_eq!(second as i64, pruner(&pool).prune().await.unwrap());
+        assert_eq!(second, current_version(&pool).await.unwrap());
+        assert_eq!(None, resume(first, 100, &pool).await.unwrap());
+        assert_eq!(Some(vec![]), resume(second, 100, &pool).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn snapshots_page_through_every_filter(pool: Pool<Postgres>) {
+        let org = helium_org(&pool).await;
+        let route_id = add_route(&org, &pool).await;
+        route::insert_skfs(
+            &[skf(&route_id, 1), skf(&route_id, 2), skf(&route_id, 3)],
+            &pool,
+        )
+        .await
+        .unwrap();
+        let version = current_version(&pool).await.unwrap();
+        // streams resuming from before the changes pruned get a snapshot:
+        age_changes(version, &pool).await;
+        pruner(&pool).prune().await.unwrap();
+
+        let mut snapshot = Snapshot::begin(&pool).await.unwrap();
+        assert_eq!(version, snapshot.version);
+        let mut filters = vec![];
+        loop {
+            let page = snapshot.next_page(2).await.unwrap();
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 2);
+            filters.extend(page);
+        }
+        assert!(filters.iter().all(|filter| filter.oui == org.oui));
+        let mut addrs: Vec<_> = filters
+            .iter()
+            .map(|filter| i32::from(filter.skf.devaddr))
+            .collect();
+        addrs.sort();
+        assert_eq!(vec![1, 2, 3], addrs);
+    }
+
+    fn versions(changes: &[SkfChange]) -> Vec<u64> {
+        changes.iter().map(|change| change.version).collect()
+    }
+}
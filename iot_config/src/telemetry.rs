@@ -1,5 +1,8 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 const RPC_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "grpc-request");
 const STREAM_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "grpc-stream");
+const SKF_STREAM_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "skf-stream");
 const REGION_HEX_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "region-hexes");
 const REGION_LOOKUP_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "region-lookup");
 const SKF_ADD_COUNT_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "skfs-added");
@@ -10,11 +13,24 @@ const DEVADDR_ADD_COUNT_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "dev
 const DEVADDR_REMOVE_COUNT_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "devaddrs-removed");
 const GATEWAY_CHAIN_LOOKUP_METRIC: &str =
     concat!(env!("CARGO_PKG_NAME"), "-", "gateway-info-lookup");
+const GATEWAY_NOT_FOUND_HIT_METRIC: &str =
+    concat!(env!("CARGO_PKG_NAME"), "-", "gateway-not-found-hit");
 const GATEWAY_CHAIN_LOOKUP_DURATION_METRIC: &str =
     concat!(env!("CARGO_PKG_NAME"), "-", "gateway-info-lookup-duration");
+const SIGNATURE_FAILURE_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "signature-failures");
+const SECURITY_EVENT_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "security-events");
+const PROXY_LOOKUP_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "proxy-lookup");
+const WEBHOOK_DELIVERY_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "webhook-deliveries");
+const AUDIT_RECORD_FAILURE_METRIC: &str =
+    concat!(env!("CARGO_PKG_NAME"), "-", "audit-record-failures");
+
+/// Route stream subscribers, as the stream metric only lives in the recorder
+static ROUTE_STREAM_SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
+static SKF_STREAM_SUBSCRIBERS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn initialize() {
     metrics::gauge!(STREAM_METRIC, 0.0);
+    metrics::gauge!(SKF_STREAM_METRIC, 0.0);
 }
 
 pub fn count_request(service: &'static str, rpc: &'static str) {
@@ -25,6 +41,10 @@ pub fn count_gateway_info_lookup(result: &'static str) {
     metrics::increment_counter!(GATEWAY_CHAIN_LOOKUP_METRIC, "result" => result);
 }
 
+pub fn count_gateway_not_found_hit() {
+    metrics::increment_counter!(GATEWAY_NOT_FOUND_HIT_METRIC);
+}
+
 pub fn gauge_hexes(cells: usize) {
     metrics::gauge!(REGION_HEX_METRIC, cells as f64);
 }
@@ -62,10 +82,53 @@ pub fn count_devaddr_updates(adds: usize, removes: usize) {
     metrics::counter!(DEVADDR_REMOVE_COUNT_METRIC, removes as u64);
 }
 
+pub fn count_audit_record_failure(rpc: &'static str) {
+    metrics::increment_counter!(AUDIT_RECORD_FAILURE_METRIC, "rpc" => rpc);
+}
+
+pub fn count_signature_failure(rpc: &'static str) {
+    metrics::increment_counter!(SIGNATURE_FAILURE_METRIC, "rpc" => rpc);
+}
+
+pub fn count_security_event(locked: bool) {
+    metrics::increment_counter!(SECURITY_EVENT_METRIC, "locked" => locked.to_string());
+}
+
+pub fn count_proxy_lookup(rpc: &'static str, result: &'static str) {
+    metrics::increment_counter!(PROXY_LOOKUP_METRIC, "rpc" => rpc, "result" => result);
+}
+
+pub fn count_webhook_delivery(event: &'static str, delivered: bool) {
+    metrics::increment_counter!(
+        WEBHOOK_DELIVERY_METRIC,
+        "event" => event, "delivered" => delivered.to_string()
+    );
+}
+
 pub fn route_stream_subscribe() {
+    ROUTE_STREAM_SUBSCRIBERS.fetch_add(1, Ordering::Relaxed);
     metrics::increment_gauge!(STREAM_METRIC, 1.0);
 }
 
 pub fn route_stream_unsubscribe() {
+    ROUTE_STREAM_SUBSCRIBERS.fetch_sub(1, Ordering::Relaxed);
     metrics::decrement_gauge!(STREAM_METRIC, 1.0);
 }
+
+pub fn route_stream_subscribers() -> usize {
+    ROUTE_STREAM_SUBSCRIBERS.load(Ordering::Relaxed)
+}
+
+pub fn skf_stream_subscribe() {
+    SKF_STREAM_SUBSCRIBERS.fetch_add(1, Ordering::Relaxed);
+    metrics::increment_gauge!(SKF_STREAM_METRIC, 1.0);
+}
+
+pub fn skf_stream_unsubscribe() {
+    SKF_STREAM_SUBSCRIBERS.fetch_sub(1, Ordering::Relaxed);
+    metrics::decrement_gauge!(SKF_STREAM_METRIC, 1.0);
+}
+
+pub fn skf_stream_subscribers() -> usize {
+    SKF_STREAM_SUBSCRIBERS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,71 @@
+//! Fixtures shared by the tests of the crate.
+
+use crate::{
+    audit,
+    helium_netids::HeliumNetId,
+    lora_field::net_id,
+    org::{self, Org},
+    route::{Protocol, Route, RouteServer},
+};
+use helium_crypto::{KeyTag, KeyType, Keypair, Network, PublicKeyBinary};
+use rand::rngs::OsRng;
+use sqlx::{Pool, Postgres};
+
+pub fn keypair() -> Keypair {
+    let key_tag = KeyTag {
+        network: Network::MainNet,
+        key_type: KeyType::Ed25519,
+    };
+    Keypair::generate(key_tag, &mut OsRng)
+}
+
+pub fn pubkey() -> PublicKeyBinary {
+    keypair().public_key().into()
+}
+
+/// An audit entry signed by a new key
+pub fn audit_entry() -> audit::Entry {
+    audit::Entry::new("test", None, keypair().public_key(), &())
+}
+
+pub async fn count(table: &str, pool: &Pool<Postgres>) -> i64 {
+    sqlx::query_scalar(&format!("select count(*) from {table}"))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+/// A helium org of a new owner, paying for itself, without delegate keys
+pub async fn helium_org(pool: &Pool<Postgres>) -> Org {
+    helium_org_with_delegates(vec![], pool).await
+}
+
+pub async fn helium_org_with_delegates(
+    delegate_keys: Vec<PublicKeyBinary>,
+    pool: &Pool<Postgres>,
+) -> Org {
+    let owner = pubkey();
+    org::create_helium_org(
+        owner.clone(),
+        owner,
+        delegate_keys,
+        HeliumNetId::Type0_0x00003c,
+        8,
+        audit_entry(),
+        pool,
+    )
+    .await
+    .unwrap()
+}
+
+/// A route of `oui` to a packet router, yet to be inserted
+pub fn route(oui: u64, max_copies: u32) -> Route {
+    Route {
+        server: RouteServer::new(
+            "lns.example.com".to_string(),
+            8080,
+            Protocol::default_packet_router(),
+        ),
+        ..Route::new(net_id(0x00003c), oui, max_copies)
+    }
+}
@@ -0,0 +1,454 @@
+//! Offline check of captured config service requests against the current
+//! key registry, writing nothing.
+
+use crate::{
+    admin::{AuthCache, KeyType},
+    org, route, signature_guard,
+};
+use anyhow::Result;
+use file_store::traits::MsgVerify;
+use helium_crypto::PublicKey;
+use helium_proto::{
+    services::iot_config::{
+        AdminAddKeyReqV1, AdminLoadRegionReqV1, AdminRemoveKeyReqV1, GatewayInfoReqV1,
+        GatewayInfoStreamReqV1, OrgCreateHeliumReqV1, OrgCreateRoamerReqV1, OrgDisableReqV1,
+        OrgEnableReqV1, OrgUpdateReqV1, RegionParamsReqV1, RouteCreateReqV1, RouteDeleteReqV1,
+        RouteGetDevaddrRangesReqV1, RouteGetEuisReqV1, RouteGetReqV1, RouteListReqV1,
+        RouteSkfGetReqV1, RouteSkfListReqV1, RouteSkfUpdateReqV1, RouteStreamReqV1,
+        RouteUpdateDevaddrRangesReqV1, RouteUpdateEuisReqV1, RouteUpdateReqV1,
+    },
+    Message,
+};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum RequestType {
+    RouteList,
+    RouteGet,
+    RouteCreate,
+    RouteUpdate,
+    RouteDelete,
+    RouteStream,
+    RouteGetEuis,
+    RouteUpdateEuis,
+    RouteGetDevaddrRanges,
+    RouteUpdateDevaddrRanges,
+    RouteSkfList,
+    RouteSkfGet,
+    RouteSkfUpdate,
+    OrgCreateHelium,
+    OrgCreateRoamer,
+    OrgUpdate,
+    OrgDisable,
+    OrgEnable,
+    AdminAddKey,
+    AdminRemoveKey,
+    AdminLoadRegion,
+    AdminRegionParams,
+    GatewayInfo,
+    GatewayInfoStream,
+}
+
+/// Check a file of captured signed requests against the current key registry,
+/// the admin keys, the keys registered through the admin service and the
+/// owner, payer and delegate keys of the orgs. Every request is reported as
+/// authorized or rejected by the rules the services apply, so rejected
+/// requests can be debugged without touching production state
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Type of the requests in the file
+    #[clap(long, value_enum)]
+    request_type: RequestType,
+    /// File of length delimited request protobufs
+    file: PathBuf,
+}
+
+/// Who a request must be signed by to be authorized
+enum Scope<'a> {
+    /// An admin key
+    Admin,
+    /// Any key registered with the admin service
+    AnyKey,
+    /// An admin key or a key of the org
+    Oui(u64),
+    /// An admin key or a key of the org owning the route
+    RouteId(&'a str),
+    /// An admin key or the owner of the org
+    OrgOwner(u64),
+    /// The request can't be authorized as it is missing a field
+    Missing(&'static str),
+}
+
+trait CapturedRequest: Message + Default + MsgVerify {
+    fn signer(&self) -> &[u8];
+    fn scope(&self) -> Scope<'_>;
+}
+
+macro_rules! captured_request {
+    ($msg:ty, |$request:ident| $scope:expr) => {
+        impl CapturedRequest for $msg {
+            fn signer(&self) -> &[u8] {
+                &self.signer
+            }
+
+            fn scope(&self) -> Scope<'_> {
+                let $request = self;
+                $scope
+            }
+        }
+    };
+}
+
+captured_request!(RouteListReqV1, |request| Scope::Oui(request.oui));
+captured_request!(RouteGetReqV1, |request| Scope::RouteId(&request.id));
+captured_request!(RouteCreateReqV1, |request| Scope::Oui(request.oui));
+captured_request!(RouteUpdateReqV1, |request| match request.route {
+    Some(ref route) => Scope::RouteId(&route.id),
+    None => Scope::Missing("route"),
+});
+captured_request!(RouteDeleteReqV1, |request| Scope::RouteId(&request.id));
+captured_request!(RouteStreamReqV1, |_request| Scope::AnyKey);
+captured_request!(RouteGetEuisReqV1, |request| Scope::RouteId(
+    &request.route_id
+));
+captured_request!(RouteUpdateEuisReqV1, |request| match request.eui_pair {
+    Some(ref eui_pair) => Scope::RouteId(&eui_pair.route_id),
+    None => Scope::Missing("eui_pair"),
+});
+captured_request!(RouteGetDevaddrRangesReqV1, |request| Scope::RouteId(
+    &request.route_id
+));
+captured_request!(
+    RouteUpdateDevaddrRangesReqV1,
+    |request| match request.devaddr_range {
+        Some(ref devaddr_range) => Scope::RouteId(&devaddr_range.route_id),
+        None => Scope::Missing("devaddr_range"),
+    }
+);
+captured_request!(RouteSkfListReqV1, |request| Scope::RouteId(
+    &request.route_id
+));
+captured_request!(RouteSkfGetReqV1, |request| Scope::RouteId(
+    &request.route_id
+));
+captured_request!(RouteSkfUpdateReqV1, |request| Scope::RouteId(
+    &request.route_id
+));
+captured_request!(OrgCreateHeliumReqV1, |_request| Scope::Admin);
+captured_request!(OrgCreateRoamerReqV1, |_request| Scope::Admin);
+captured_request!(OrgUpdateReqV1, |request| Scope::OrgOwner(request.oui));
+captured_request!(OrgDisableReqV1, |_request| Scope::AnyKey);
+captured_request!(OrgEnableReqV1, |_request| Scope::AnyKey);
+captured_request!(AdminAddKeyReqV1, |_request| Scope::Admin);
+captured_request!(AdminRemoveKeyReqV1, |_request| Scope::Admin);
+captured_request!(AdminLoadRegionReqV1, |_request| Scope::Admin);
+captured_request!(RegionParamsReqV1, |_request| Scope::AnyKey);
+captured_request!(GatewayInfoReqV1, |_request| Scope::AnyKey);
+captured_request!(GatewayInfoStreamReqV1, |_request| Scope::AnyKey);
+
+#[derive(Debug, Serialize)]
+struct Outcome {
+    index: usize,
+    signer: Option<String>,
+    oui: Option<u64>,
+    /// What authorized the request, if it is authorized
+    authorized_by: Option<&'static str>,
+    /// Why the request is rejected, if it is rejected
+    rejected: Option<String>,
+}
+
+/// What authorized a request, or why it is rejected
+type Verdict = std::result::Result<&'static str, String>;
+
+struct Registry<'a> {
+    pool: &'a Pool<Postgres>,
+    auth_cache: &'a AuthCache,
+    /// Whether orgs locked after repeated signature failures are refused
+    lock_enforced: bool,
+}
+
+impl Cmd {
+    pub async fn run(
+        &self,
+        pool: &Pool<Postgres>,
+        auth_cache: &AuthCache,
+        lock_enforced: bool,
+    ) -> Result<()> {
+        let registry = Registry {
+            pool,
+            auth_cache,
+            lock_enforced,
+        };
+        let bytes = tokio::fs::read(&self.file).await?;
+        match self.request_type {
+            RequestType::RouteList => registry.check_all::<RouteListReqV1>(&bytes).await,
+            RequestType::RouteGet => registry.check_all::<RouteGetReqV1>(&bytes).await,
+            RequestType::RouteCreate => registry.check_all::<RouteCreateReqV1>(&bytes).await,
+            RequestType::RouteUpdate => registry.check_all::<RouteUpdateReqV1>(&bytes).await,
+            RequestType::RouteDelete => registry.check_all::<RouteDeleteReqV1>(&bytes).await,
+            RequestType::RouteStream => registry.check_all::<RouteStreamReqV1>(&bytes).await,
+            RequestType::RouteGetEuis => registry.check_all::<RouteGetEuisReqV1>(&bytes).await,
+            RequestType::RouteUpdateEuis => {
+                registry.check_all::<RouteUpdateEuisReqV1>(&bytes).await
+            }
+            RequestType::RouteGetDevaddrRanges => {
+                registry
+                    .check_all::<RouteGetDevaddrRangesReqV1>(&bytes)
+                    .await
+            }
+            RequestType::RouteUpdateDevaddrRanges => {
+                registry
+                    .check_all::<RouteUpdateDevaddrRangesReqV1>(&bytes)
+                    .await
+            }
+            RequestType::RouteSkfList => registry.check_all::<RouteSkfListReqV1>(&bytes).await,
+            RequestType::RouteSkfGet => registry.check_all::<RouteSkfGetReqV1>(&bytes).await,
+            RequestType::RouteSkfUpdate => registry.check_all::<RouteSkfUpdateReqV1>(&bytes).await,
+            RequestType::OrgCreateHelium => {
+                registry.check_all::<OrgCreateHeliumReqV1>(&bytes).await
+            }
+            RequestType::OrgCreateRoamer => {
+                registry.check_all::<OrgCreateRoamerReqV1>(&bytes).await
+            }
+            RequestType::OrgUpdate => registry.check_all::<OrgUpdateReqV1>(&bytes).await,
+            RequestType::OrgDisable => registry.check_all::<OrgDisableReqV1>(&bytes).await,
+            RequestType::OrgEnable => registry.check_all::<OrgEnableReqV1>(&bytes).await,
+            RequestType::AdminAddKey => registry.check_all::<AdminAddKeyReqV1>(&bytes).await,
+            RequestType::AdminRemoveKey => registry.check_all::<AdminRemoveKeyReqV1>(&bytes).await,
+            RequestType::AdminLoadRegion => {
+                registry.check_all::<AdminLoadRegionReqV1>(&bytes).await
+            }
+            RequestType::AdminRegionParams => registry.check_all::<RegionParamsReqV1>(&bytes).await,
+            RequestType::GatewayInfo => registry.check_all::<GatewayInfoReqV1>(&bytes).await,
+            RequestType::GatewayInfoStream => {
+                registry.check_all::<GatewayInfoStreamReqV1>(&bytes).await
+            }
+        }
+    }
+}
+
+impl<'a> Registry<'a> {
+    async fn check_all<R: CapturedRequest>(&self, mut bytes: &[u8]) -> Result<()> {
+        let mut total = 0;
+        let mut authorized = 0;
+        while !bytes.is_empty() {
+            // a truncated or corrupt capture can't be resynchronized, report
+            // what was checked so far
+            let request = match R::decode_length_delimited(&mut bytes) {
+                Ok(request) => request,
+                Err(err) => {
+                    eprintln!("failed to decode request {total}: {err}");
+                    break;
+                }
+            };
+            let outcome = self.check(total, &request).await?;
+            if outcome.authorized_by.is_some() {
+                authorized += 1;
+            }
+            println!("{}", serde_json::to_string(&outcome)?);
+            total += 1;
+        }
+        println!(
+            "{}",
+            json!({
+                "total": total,
+                "authorized": authorized,
+                "rejected": total - authorized,
+            })
+        );
+        Ok(())
+    }
+
+    async fn check<R: CapturedRequest>(&self, index: usize, request: &R) -> Result<Outcome> {
+        let mut outcome = Outcome {
+            index,
+            signer: None,
+            oui: None,
+            authorized_by: None,
+            rejected: None,
+        };
+        let Ok(signer) = PublicKey::try_from(request.signer()) else {
+            outcome.rejected = Some("invalid signer public key".to_string());
+            return Ok(outcome);
+        };
+        outcome.signer = Some(signer.to_string());
+        if request.verify(&signer).is_err() {
+            outcome.rejected = Some("signature does not verify".to_string());
+            return Ok(outcome);
+        }
+
+        // the signature verifies, whether the request is authorized only
+        // depends on the signer from here
+        let is_admin = self
+            .auth_cache
+            .verify_signature_with_type(KeyType::Administrator, &signer, request)
+            .is_ok();
+        let result = match request.scope() {
+            Scope::Missing(field) => Err(format!("missing {field}")),
+            _ if is_admin => Ok("admin key"),
+            Scope::Admin => Err("not an admin key".to_string()),
+            Scope::AnyKey => match self.auth_cache.verify_signature(&signer, request) {
+                Ok(()) => Ok("registered key"),
+                Err(_) => Err("not a registered key".to_string()),
+            },
+            Scope::Oui(oui) => {
+                outcome.oui = Some(oui);
+                self.check_org_key(oui, &signer).await?
+            }
+            Scope::RouteId(route_id) => match route::get_route(route_id, self.pool).await {
+                Ok(route) => {
+                    outcome.oui = Some(route.oui);
+                    self.check_org_key(route.oui, &signer).await?
+                }
+                Err(_) => Err(format!("unknown route {route_id}")),
+            },
+            Scope::OrgOwner(oui) => {
+                outcome.oui = Some(oui);
+                self.check_org_owner(oui, &signer).await?
+            }
+        };
+        match result {
+            Ok(authorized_by) => outcome.authorized_by = Some(authorized_by),
+            Err(reason) => outcome.rejected = Some(reason),
+        }
+        Ok(outcome)
+    }
+
+    async fn is_locked(&self, oui: u64) -> Result<bool> {
+        Ok(self.lock_enforced && signature_guard::is_locked(oui, self.pool).await?)
+    }
+
+    async fn check_org_key(&self, oui: u64, signer: &PublicKey) -> Result<Verdict> {
+        if self.is_locked(oui).await? {
+            return Ok(Err(locked(oui)));
+        }
+        let Ok(org_keys) = org::get_org_pubkeys(oui, self.pool).await else {
+            return Ok(Err(format!("unknown oui {oui}")));
+        };
+        if org_keys.contains(signer) {
+            Ok(Ok("org key"))
+        } else {
+            Ok(Err(format!("not a key of oui {oui}")))
+        }
+    }
+
+    async fn check_org_owner(&self, oui: u64, signer: &PublicKey) -> Result<Verdict> {
+        if self.is_locked(oui).await? {
+            return Ok(Err(locked(oui)));
+        }
+        let Some(org) = org::get(oui, self.pool).await? else {
+            return Ok(Err(format!("unknown oui {oui}")));
+        };
+        if org.owner == signer.clone().into() {
+            Ok(Ok("org owner"))
+        } else {
+            Ok(Err(format!("not the owner of oui {oui}")))
+        }
+    }
+}
+
+fn locked(oui: u64) -> String {
+    format!("oui {oui} locked after repeated signature failures")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{admin::CacheKeys, test_support::keypair};
+    use helium_crypto::{Keypair, Sign};
+
+    fn route_list(oui: u64, keypair: &Keypair) -> RouteListReqV1 {
+        let mut request = RouteListReqV1 {
+            oui,
+            signer: keypair.public_key().into(),
+            ..Default::default()
+        };
+        request.signature = keypair.sign(&request.encode_to_vec()).unwrap();
+        request
+    }
+
+    async fn create_org(owner: &Keypair, pool: &Pool<Postgres>) -> u64 {
+        let oui: i64 = sqlx::query_scalar(
+            "insert into organizations (owner_pubkey, payer_pubkey) values ($1, $1) returning oui",
+        )
+        .bind(owner.public_key().to_string())
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        oui as u64
+    }
+
+    fn auth_cache(admin: &Keypair) -> AuthCache {
+        let keys = CacheKeys::from([(admin.public_key().clone(), KeyType::Administrator)]);
+        AuthCache::from_keys(keys).1
+    }
+
+    #[sqlx::test]
+    async fn org_keys_are_scoped_to_their_org(pool: Pool<Postgres>) {
+        let admin = keypair();
+        let owner = keypair();
+        let other = keypair();
+        let oui = create_org(&owner, &pool).await;
+        let other_oui = create_org(&other, &pool).await;
+        let auth_cache = auth_cache(&admin);
+        let registry = Registry {
+            pool: &pool,
+            auth_cache: &auth_cache,
+            lock_enforced: false,
+        };
+
+        let outcome = registry.check(0, &route_list(oui, &owner)).await.unwrap();
+        assert_eq!(Some("org key"), outcome.authorized_by);
+        assert_eq!(Some(oui), outcome.oui);
+
+        let outcome = registry
+            .check(1, &route_list(other_oui, &owner))
+            .await
+            .unwrap();
+        assert_eq!(None, outcome.authorized_by);
+        assert_eq!(
+            Some(format!("not a key of oui {other_oui}")),
+            outcome.rejected
+        );
+
+        let outcome = registry.check(2, &route_list(oui, &admin)).await.unwrap();
+        assert_eq!(Some("admin key"), outcome.authorized_by);
+    }
+
+    #[sqlx::test]
+    async fn bad_signatures_and_locked_orgs_are_rejected(pool: Pool<Postgres>) {
+        let owner = keypair();
+        let oui = create_org(&owner, &pool).await;
+        let auth_cache = auth_cache(&keypair());
+        sqlx::query("insert into org_mutation_locks (oui, failures) values ($1, 5)")
+            .bind(oui as i64)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut forged = route_list(oui, &owner);
+        forged.oui += 1;
+        let registry = Registry {
+            pool: &pool,
+            auth_cache: &auth_cache,
+            lock_enforced: false,
+        };
+        let outcome = registry.check(0, &forged).await.unwrap();
+        assert_eq!(
+            Some("signature does not verify".to_string()),
+            outcome.rejected
+        );
+        let outcome = registry.check(1, &route_list(oui, &owner)).await.unwrap();
+        assert_eq!(Some("org key"), outcome.authorized_by);
+
+        let registry = Registry {
+            lock_enforced: true,
+            ..registry
+        };
+        let outcome = registry.check(2, &route_list(oui, &owner)).await.unwrap();
+        assert_eq!(Some(locked(oui)), outcome.rejected);
+    }
+}
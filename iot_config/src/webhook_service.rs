@@ -0,0 +1,215 @@
+use crate::{
+    admin::{AuthCache, KeyType},
+    ext::{
+        self, WebhookAddReqV1, WebhookListReqV1, WebhookListResV1, WebhookRemoveReqV1,
+        WebhookResV1, WebhookV1,
+    },
+    org,
+    signature_guard::SignatureGuard,
+    telemetry, verify_public_key,
+    webhooks::{self, Webhook},
+    GrpcResult, Settings,
+};
+use anyhow::Result;
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::Message;
+use sqlx::{types::Uuid, Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+/// Shortest secret accepted for signing webhook payloads
+const MIN_SECRET_LEN: usize = 16;
+
+/// Registry of the webhooks receiving the change events of orgs
+pub struct WebhookService {
+    auth_cache: AuthCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+    signature_guard: SignatureGuard,
+}
+
+impl WebhookService {
+    pub fn new(
+        settings: &Settings,
+        auth_cache: AuthCache,
+        pool: Pool<Postgres>,
+        signature_guard: SignatureGuard,
+    ) -> Result<Self> {
+        Ok(Self {
+            auth_cache,
+            pool,
+            signing_key: settings.signing_keypair()?,
+            signature_guard,
+        })
+    }
+
+    /// Webhooks of an org are managed by its owner, or an administrator
+    async fn verify_owner_signature<R>(
+        &self,
+        oui: u64,
+        signer: &PublicKey,
+        request: &R,
+    ) -> Result<(), Status>
+    where
+        R: MsgVerify,
+    {
+        if self
+            .auth_cache
+            .verify_signature_with_type(KeyType::Administrator, signer, request)
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        self.signature_guard.check_unlocked(oui).await?;
+        let org_owner = org::get(oui, &self.pool)
+            .await
+            .map_err(|_| Status::internal("auth verification error"))?
+            .ok_or_else(|| Status::not_found(format!("oui: {oui}")))?
+            .owner;
+        if org_owner == signer.clone().into() && request.verify(signer).is_ok() {
+            return Ok(());
+        }
+
+        self.signature_guard
+            .record_failure(oui, signer, "webhooks")
+            .await;
+        Err(Status::permission_denied("unauthorized request signature"))
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+
+    fn webhook_response(&self, webhook: Webhook) -> GrpcResult<WebhookResV1> {
+        let mut resp = WebhookResV1 {
+            webhook: Some(webhook.into()),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
+
+async fn validate_webhook(request: &WebhookAddReqV1) -> Result<(), Status> {
+    webhooks::check_url(&request.url)
+        .await
+        .map_err(|err| Status::from(crate::Error::from(err)))?;
+    if request.secret.len() < MIN_SECRET_LEN {
+        return Err(Status::invalid_argument(format!(
+            "secret must be at least {MIN_SECRET_LEN} characters"
+        )));
+    }
+    if request.events.is_empty() {
+        return Err(Status::invalid_argument("no events to deliver"));
+    }
+    match request
+        .events
+        .iter()
+        .find(|event| !webhooks::EVENTS.contains(&event.as_str()))
+    {
+        Some(event) => Err(Status::invalid_argument(format!("unknown event {event}"))),
+        None => Ok(()),
+    }
+}
+
+impl From<Webhook> for WebhookV1 {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id.to_string(),
+            oui: webhook.oui,
+            url: webhook.url,
+            events: webhook.events,
+            disabled_until: webhook
+                .disabled_until
+                .filter(|disabled_until| *disabled_until > Utc::now())
+                .map_or(0, |disabled_until| disabled_until.timestamp() as u64),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ext::webhooks_server::Webhooks for WebhookService {
+    async fn add(&self, request: Request<WebhookAddReqV1>) -> GrpcResult<WebhookResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("webhooks", "add");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_owner_signature(request.oui, &signer, &request)
+            .await?;
+        validate_webhook(&request).await?;
+
+        let webhook = webhooks::insert(
+            request.oui,
+            &request.url,
+            &request.secret,
+            &request.events,
+            &self.pool,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!(oui = request.oui, reason = ?err, "webhook save failed");
+            Status::internal("webhook save failed")
+        })?;
+        tracing::info!(
+            oui = request.oui,
+            id = %webhook.id,
+            url = %webhook.url,
+            "webhook added"
+        );
+
+        self.webhook_response(webhook)
+    }
+
+    async fn remove(&self, request: Request<WebhookRemoveReqV1>) -> GrpcResult<WebhookResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("webhooks", "remove");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_owner_signature(request.oui, &signer, &request)
+            .await?;
+        let id = Uuid::try_parse(&request.id)
+            .map_err(|_| Status::invalid_argument(format!("invalid id: {}", request.id)))?;
+
+        let webhook = webhooks::remove(request.oui, id, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(oui = request.oui, reason = ?err, "webhook removal failed");
+                Status::internal("webhook removal failed")
+            })?
+            .ok_or_else(|| Status::not_found(format!("webhook: {id}")))?;
+        tracing::info!(oui = request.oui, %id, "webhook removed");
+
+        self.webhook_response(webhook)
+    }
+
+    async fn list(&self, request: Request<WebhookListReqV1>) -> GrpcResult<WebhookListResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("webhooks", "list");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_owner_signature(request.oui, &signer, &request)
+            .await?;
+
+        let webhooks = webhooks::list(request.oui, &self.pool)
+            .await
+            .map_err(|err| {
+                tracing::error!(oui = request.oui, reason = ?err, "webhook list failed");
+                Status::internal("webhook list failed")
+            })?;
+
+        let mut resp = WebhookListResV1 {
+            webhooks: webhooks.into_iter().map(WebhookV1::from).collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
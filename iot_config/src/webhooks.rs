@@ -0,0 +1,907 @@
+//! Push notifications of org and route changes to the webhooks registered by
+//! orgs through the `Webhooks` service.
+
+use crate::{
+    lora_field::{DevAddrRange, EuiPair, Skf},
+    org::Org,
+    route::{
+        proto::{route_stream_res_v1, ActionV1, RouteStreamResV1},
+        Route,
+    },
+    telemetry,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{postgres::PgRow, types::Uuid, FromRow, Pool, Postgres, Row};
+use std::{collections::HashMap, net::IpAddr};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+
+/// The change events webhooks subscribe to
+pub const EVENTS: [&str; 5] = ["org", "route", "eui_pairs", "devaddr_ranges", "skfs"];
+/// Header of the hmac-sha256 of a delivered payload, keyed by the secret of
+/// the webhook and hex encoded as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "x-helium-signature";
+/// Header of the event of a delivered payload
+pub const EVENT_HEADER: &str = "x-helium-event";
+/// Events queued for delivery to a webhook, beyond which its events are
+/// dropped
+const DELIVERY_QUEUE_SIZE: usize = 1_000;
+/// Interval of the reloads of the registered webhooks, webhooks added start
+/// receiving events after the next one
+const WEBHOOK_RELOAD_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Delivery attempts of an event to a webhook before the delivery
+    /// fails. Default is 3
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before retrying a delivery, doubled on every retry. Default is
+    /// 1s
+    #[serde(with = "settings_loader::duration", default = "default_retry_backoff")]
+    pub retry_backoff: std::time::Duration,
+    /// Timeout of a delivery attempt. Default is 10s
+    #[serde(with = "settings_loader::duration", default = "default_timeout")]
+    pub timeout: std::time::Duration,
+    /// Consecutive failed deliveries breaking the circuit of a webhook.
+    /// Default is 10
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Time deliveries to a webhook are skipped once its circuit breaks.
+    /// Default is 10m
+    #[serde(with = "settings_loader::duration", default = "default_circuit_break")]
+    pub circuit_break: std::time::Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            retry_backoff: default_retry_backoff(),
+            timeout: default_timeout(),
+            failure_threshold: default_failure_threshold(),
+            circuit_break: default_circuit_break(),
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_backoff() -> std::time::Duration {
+    std::time::Duration::from_secs(1)
+}
+
+fn default_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(10)
+}
+
+fn default_failure_threshold() -> u32 {
+    10
+}
+
+fn default_circuit_break() -> std::time::Duration {
+    std::time::Duration::from_secs(10 * 60)
+}
+
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub oui: u64,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub disabled_until: Option<DateTime<Utc>>,
+}
+
+impl FromRow<'_, PgRow> for Webhook {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            oui: row.try_get::<i64, &str>("oui")? as u64,
+            url: row.try_get("url")?,
+            secret: row.try_get("secret")?,
+            events: row.try_get("events")?,
+            disabled_until: row.try_get("disabled_until")?,
+        })
+    }
+}
+
+/// A change of an org, as delivered
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub event: &'static str,
+    pub action: &'static str,
+    pub oui: u64,
+    pub timestamp: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+/// Delivers the changes of orgs, followed on the route update channel
+/// streamed to the packet routers and the org update channel of the org
+/// `update` api, as json to the webhooks of the org subscribed to them.
+/// Session keys are never sent. Delivery is best effort: changes made while
+/// the service is down, or while the circuit of a webhook is broken, are not
+/// delivered
+pub struct Dispatcher {
+    pool: Pool<Postgres>,
+    http: reqwest::Client,
+    settings: Settings,
+    route_updates: broadcast::Receiver<RouteStreamResV1>,
+    org_updates: broadcast::Receiver<Org>,
+    /// Org of the routes of the events seen, routes never change org
+    route_ouis: HashMap<String, u64>,
+    /// Delivery queues of the registered webhooks, by org
+    queues: HashMap<u64, Vec<Queue>>,
+    /// Whether deliveries check their url resolves to public addresses,
+    /// only off in tests delivering to a local server
+    public_only: bool,
+}
+
+/// The delivery queue of a webhook, whose events are delivered one at a
+/// time in the order they were dispatched
+struct Queue {
+    id: Uuid,
+    events: Vec<String>,
+    sender: mpsc::Sender<Payload>,
+}
+
+/// An encoded event on its way to a webhook
+#[derive(Clone)]
+struct Payload {
+    event: &'static str,
+    body: Vec<u8>,
+}
+
+impl Dispatcher {
+    pub fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        route_updates: broadcast::Receiver<RouteStreamResV1>,
+        org_updates: broadcast::Receiver<Org>,
+    ) -> reqwest::Result<Self> {
+        Ok(Self {
+            pool,
+            // a redirect could lead deliveries past the checks of the url
+            http: reqwest::Client::builder()
+                .timeout(settings.timeout)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
+            settings: settings.clone(),
+            route_updates,
+            org_updates,
+            route_ouis: HashMap::new(),
+            queues: HashMap::new(),
+            public_only: true,
+        })
+    }
+
+    pub async fn run(mut self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting webhook dispatcher");
+        let mut reload_timer = tokio::time::interval(WEBHOOK_RELOAD_INTERVAL);
+        loop {
+            let event = tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = reload_timer.tick() => {
+                    self.reload_webhooks().await;
+                    None
+                }
+                update = self.route_updates.recv() => match update {
+                    Ok(update) => self.route_event(update).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "webhook dispatcher lagging, route events dropped");
+                        None
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                update = self.org_updates.recv() => match update {
+                    Ok(org) => Some(org_event(org)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "webhook dispatcher lagging, org events dropped");
+                        None
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            if let Some(event) = event {
+                self.dispatch(event);
+            }
+        }
+        tracing::info!("stopping webhook dispatcher");
+        Ok(())
+    }
+
+    /// Reload the registered webhooks, starting the delivery of those added
+    /// since the last reload. The queues of removed webhooks close once the
+    /// events already queued are delivered.
+    async fn reload_webhooks(&mut self) {
+        let webhooks = match all(&self.pool).await {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                tracing::error!(?err, "failed to reload webhooks");
+                return;
+            }
+        };
+        let mut senders: HashMap<Uuid, mpsc::Sender<Payload>> = self
+            .queues
+            .drain()
+            .flat_map(|(_, queues)| queues)
+            .map(|queue| (queue.id, queue.sender))
+            .collect();
+        for webhook in webhooks {
+            let sender = match senders.remove(&webhook.id) {
+                Some(sender) => sender,
+                None => self.start_delivery(webhook.clone()),
+            };
+            self.queues.entry(webhook.oui).or_default().push(Queue {
+                id: webhook.id,
+                events: webhook.events,
+                sender,
+            });
+        }
+    }
+
+    fn start_delivery(&self, webhook: Webhook) -> mpsc::Sender<Payload> {
+        let (sender, receiver) = mpsc::channel(DELIVERY_QUEUE_SIZE);
+        let delivery = Delivery {
+            pool: self.pool.clone(),
+            http: self.http.clone(),
+            settings: self.settings.clone(),
+            public_only: self.public_only,
+            webhook,
+        };
+        tokio::spawn(delivery.run(receiver));
+        sender
+    }
+
+    async fn route_event(&mut self, update: RouteStreamResV1) -> Option<Event> {
+        let action = match update.action() {
+            ActionV1::Add => "add",
+            ActionV1::Remove => "remove",
+        };
+        let (event, route_id, data) = match update.data? {
+            route_stream_res_v1::Data::Route(route) => {
+                let route = Route::from(route);
+                match update.action() {
+                    ActionV1::Add => self.route_ouis.insert(route.id.clone(), route.oui),
+                    ActionV1::Remove => self.route_ouis.remove(&route.id),
+                };
+                return Some(Event {
+                    event: "route",
+                    action,
+                    oui: route.oui,
+                    timestamp: Utc::now(),
+                    data: json!(route),
+                });
+            }
+            route_stream_res_v1::Data::EuiPair(eui_pair) => {
+                let eui_pair = EuiPair::from(eui_pair);
+                ("eui_pairs", eui_pair.route_id.clone(), json!(eui_pair))
+            }
+            route_stream_res_v1::Data::DevaddrRange(devaddr_range) => {
+                let devaddr_range = DevAddrRange::from(devaddr_range);
+                (
+                    "devaddr_ranges",
+                    devaddr_range.route_id.clone(),
+                    json!(devaddr_range),
+                )
+            }
+            route_stream_res_v1::Data::Skf(skf) => {
+                let skf = Skf::from(skf);
+                let data = json!({
+                    "route_id": skf.route_id,
+                    "devaddr": skf.devaddr,
+                    "max_copies": skf.max_copies,
+                });
+                ("skfs", skf.route_id, data)
+            }
+        };
+        let oui = self.route_oui(&route_id).await?;
+        Some(Event {
+            event,
+            action,
+            oui,
+            timestamp: Utc::now(),
+            data,
+        })
+    }
+
+    /// The org of `route_id`, looked up once per route rather than for every
+    /// entry of a bulk update. The routes of the entries removed with their
+    /// route are gone, the route event covers them.
+    async fn route_oui(&mut self, route_id: &str) -> Option<u64> {
+        if let Some(oui) = self.route_ouis.get(route_id) {
+            return Some(*oui);
+        }
+        match route_oui(route_id, &self.pool).await {
+            Ok(oui) => {
+                let oui = oui?;
+                self.route_ouis.insert(route_id.to_string(), oui);
+                Some(oui)
+            }
+            Err(err) => {
+                tracing::warn!(route_id, ?err, "failed to resolve org of route event");
+                None
+            }
+        }
+    }
+
+    /// Queue `event` for the webhooks of its org subscribed to it. A queue
+    /// full of undelivered events drops it rather than holding up the
+    /// others.
+    fn dispatch(&self, event: Event) {
+        let Some(queues) = self.queues.get(&event.oui) else {
+            return;
+        };
+        let mut subscribed = queues
+            .iter()
+            .filter(|queue| queue.events.iter().any(|e| e == event.event))
+            .peekable();
+        if subscribed.peek().is_none() {
+            return;
+        }
+        let payload = match serde_json::to_vec(&event) {
+            Ok(body) => Payload {
+                event: event.event,
+                body,
+            },
+            Err(err) => {
+                tracing::error!(oui = event.oui, ?err, "failed to encode webhook event");
+                return;
+            }
+        };
+        for queue in subscribed {
+            if queue.sender.try_send(payload.clone()).is_err() {
+                tracing::warn!(
+                    id = %queue.id,
+                    oui = event.oui,
+                    event = event.event,
+                    "webhook delivery queue full, event dropped"
+                );
+                telemetry::count_webhook_delivery(event.event, false);
+            }
+        }
+    }
+}
+
+fn org_event(org: Org) -> Event {
+    Event {
+        event: "org",
+        action: "update",
+        oui: org.oui,
+        timestamp: Utc::now(),
+        data: json!(org),
+    }
+}
+
+/// Delivers the queued events of a webhook
+struct Delivery {
+    pool: Pool<Postgres>,
+    http: reqwest::Client,
+    settings: Settings,
+    public_only: bool,
+    webhook: Webhook,
+}
+
+impl Delivery {
+    /// Deliver the events of `queue` in order until it closes. While the
+    /// circuit of the webhook is broken its events are dropped.
+    async fn run(self, mut queue: mpsc::Receiver<Payload>) {
+        let mut disabled_until = self.webhook.disabled_until;
+        while let Some(payload) = queue.recv().await {
+            if disabled_until.is_some_and(|disabled_until| disabled_until > Utc::now()) {
+                continue;
+            }
+            let delivered = self.deliver(&payload).await;
+            telemetry::count_webhook_delivery(payload.event, delivered);
+            match self.record(delivered).await {
+                Ok(Some(broken_until)) => disabled_until = Some(broken_until),
+                Ok(None) => (),
+                Err(err) => {
+                    tracing::error!(id = %self.webhook.id, ?err, "failed to record webhook delivery")
+                }
+            }
+        }
+    }
+
+    /// Record the outcome of a delivery, returning until when deliveries
+    /// are skipped if the circuit of the webhook broke
+    async fn record(&self, delivered: bool) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        if delivered {
+            record_success(self.webhook.id, &self.pool).await?;
+            return Ok(None);
+        }
+        let disabled_until = Utc::now()
+            + Duration::from_std(self.settings.circuit_break)
+                .unwrap_or_else(|_| Duration::minutes(10));
+        let broken = record_failure(
+            self.webhook.id,
+            self.settings.failure_threshold,
+            disabled_until,
+            &self.pool,
+        )
+        .await?;
+        if !broken {
+            return Ok(None);
+        }
+        tracing::warn!(
+            id = %self.webhook.id,
+            oui = self.webhook.oui,
+            %disabled_until,
+            "webhook failing, deliveries skipped"
+        );
+        Ok(Some(disabled_until))
+    }
+
+    async fn deliver(&self, payload: &Payload) -> bool {
+        // checked again on delivery, the host may resolve elsewhere since
+        // the webhook was added
+        if self.public_only {
+            if let Err(err) = check_url(&self.webhook.url).await {
+                tracing::warn!(id = %self.webhook.id, "webhook url refused: {err}");
+                return false;
+            }
+        }
+        let signature = format!("sha256={}", sign(&self.webhook.secret, &payload.body));
+        let mut backoff = self.settings.retry_backoff;
+        for attempt in 1..=self.settings.max_attempts.max(1) {
+            let result = self
+                .http
+                .post(&self.webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .header(EVENT_HEADER, payload.event)
+                .body(payload.body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => return true,
+                Err(err) => tracing::debug!(
+                    id = %self.webhook.id,
+                    attempt,
+                    "webhook delivery failed: {err}"
+                ),
+            }
+            if attempt < self.settings.max_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        tracing::warn!(
+            id = %self.webhook.id,
+            oui = self.webhook.oui,
+            event = payload.event,
+            "webhook delivery failed"
+        );
+        false
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UrlError {
+    #[error("invalid url: {0}")]
+    Invalid(String),
+    #[error("url must be https")]
+    NotHttps,
+    #[error("unresolvable host {0}")]
+    Unresolvable(String),
+    #[error("host {0} is not public")]
+    NotPublic(String),
+}
+
+impl From<UrlError> for crate::Error {
+    fn from(err: UrlError) -> Self {
+        Self::invalid(err)
+    }
+}
+
+/// Check `url` is an https url whose host only resolves to public
+/// addresses, so webhooks can't reach the network of the service
+pub async fn check_url(url: &str) -> Result<(), UrlError> {
+    let url = reqwest::Url::parse(url).map_err(|err| UrlError::Invalid(err.to_string()))?;
+    if url.scheme() != "https" {
+        return Err(UrlError::NotHttps);
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| UrlError::Invalid("no host".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    // ipv6 hosts are bracketed
+    let addrs: Vec<IpAddr> = match host.trim_matches(|c| c == '[' || c == ']').parse() {
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| UrlError::Unresolvable(host.to_string()))?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(UrlError::Unresolvable(host.to_string()));
+    }
+    if !addrs.into_iter().all(is_public) {
+        return Err(UrlError::NotPublic(host.to_string()));
+    }
+    Ok(())
+}
+
+/// Whether `ip` is a public address: not loopback, link-local, private,
+/// shared, unspecified, multicast or reserved for documentation
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+                let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || unique_local
+                    || link_local)
+            }
+        },
+    }
+}
+
+/// Hex encoded hmac-sha256 of `body` keyed by `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub async fn insert(
+    oui: u64,
+    url: &str,
+    secret: &str,
+    events: &[String],
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<Webhook, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(
+        r#"
+        insert into webhooks (oui, url, secret, events)
+        values ($1, $2, $3, $4)
+        returning *
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(url)
+    .bind(secret)
+    .bind(events)
+    .fetch_one(db)
+    .await
+}
+
+/// Remove a webhook of `oui`, returning it if it existed
+pub async fn remove(
+    oui: u64,
+    id: Uuid,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(" delete from webhooks where oui = $1 and id = $2 returning * ")
+        .bind(oui as i64)
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn list(oui: u64, db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(" select * from webhooks where oui = $1 order by inserted_at ")
+        .bind(oui as i64)
+        .fetch_all(db)
+        .await
+}
+
+/// The webhooks of every org
+async fn all(db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as::<_, Webhook>(" select * from webhooks order by inserted_at ")
+        .fetch_all(db)
+        .await
+}
+
+async fn route_oui(route_id: &str, db: impl sqlx::PgExecutor<'_>) -> crate::Result<Option<u64>> {
+    let oui = sqlx::query_scalar::<_, i64>(" select oui from routes where id = $1 ")
+        .bind(Uuid::try_parse(route_id)?)
+        .fetch_optional(db)
+        .await?;
+    Ok(oui.map(|oui| oui as u64))
+}
+
+async fn record_success(id: Uuid, db: impl sqlx::PgExecutor<'_>) -> Result<(), sqlx::Error> {
+    sqlx::query(" update webhooks set failures = 0 where id = $1 and failures > 0 ")
+        .bind(id)
+        .execute(db)
+        .await
+        .map(|_| ())
+}
+
+/// Count a failed delivery, breaking the circuit of the webhook until
+/// `disabled_until` once it reaches `threshold` consecutive failures.
+/// Returns whether the circuit broke
+async fn record_failure(
+    id: Uuid,
+    threshold: u32,
+    disabled_until: DateTime<Utc>,
+    db: impl sqlx::PgExecutor<'_>,
+) -> Result<bool, sqlx::Error> {
+    let broken = sqlx::query_scalar::<_, bool>(
+        r#"
+        update webhooks set
+            failures = case when failures + 1 >= $2 then 0 else failures + 1 end,
+            disabled_until = case when failures + 1 >= $2 then $3 else disabled_until end
+        where id = $1
+        returning failures = 0
+        "#,
+    )
+    .bind(id)
+    .bind(threshold as i32)
+    .bind(disabled_until)
+    .fetch_optional(db)
+    .await?;
+    Ok(broken.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lora_field::{eui, net_id},
+        org,
+        test_support::{audit_entry, pubkey},
+    };
+    use helium_proto::services::iot_config::EuiPairV1;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Answer the http requests to the returned url with `status`, sending
+    /// the body of each request to the returned receiver
+    async fn http_server(status: &'static str) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let body = loop {
+                    let read = socket.read(&mut buf).await.unwrap();
+                    if read == 0 {
+                        break String::new();
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, body)) = request.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse().ok())?
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                };
+                let response =
+                    format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                socket.write_all(response.as_bytes()).await.unwrap();
+                let _ = tx.send(body);
+            }
+        });
+        (url, rx)
+    }
+
+    async fn webhook(url: &str, pool: &Pool<Postgres>) -> Webhook {
+        let org = org::create_roamer_org(
+            pubkey(),
+            pubkey(),
+            vec![],
+            net_id(0x000024),
+            audit_entry(),
+            pool,
+        )
+        .await
+        .unwrap();
+        insert(org.oui, url, "0123456789abcdef", &["org".to_string()], pool)
+            .await
+            .unwrap()
+    }
+
+    fn delivery(webhook: Webhook, settings: Settings, pool: &Pool<Postgres>) -> Delivery {
+        Delivery {
+            pool: pool.clone(),
+            http: reqwest::Client::new(),
+            settings,
+            public_only: false,
+            webhook,
+        }
+    }
+
+    fn payload(n: u64) -> Payload {
+        Payload {
+            event: "org",
+            body: n.to_string().into_bytes(),
+        }
+    }
+
+    #[test]
+    fn signs_payloads_with_hmac_sha256() {
+        // rfc 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn only_https_urls_of_public_hosts_are_accepted() {
+        assert!(check_url("https://1.1.1.1/hook").await.is_ok());
+        assert!(check_url("https://[2606:4700:4700::1111]/hook")
+            .await
+            .is_ok());
+
+        assert!(matches!(
+            check_url("http://1.1.1.1/hook").await,
+            Err(UrlError::NotHttps)
+        ));
+        assert!(matches!(
+            check_url("not a url").await,
+            Err(UrlError::Invalid(_))
+        ));
+        for url in [
+            "https://127.0.0.1/hook",
+            "https://localhost:8443/hook",
+            "https://10.0.0.1/hook",
+            "https://172.16.0.1/hook",
+            "https://192.168.1.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.64.0.1/hook",
+            "https://0.0.0.0/hook",
+            "https://[::1]/hook",
+            "https://[fe80::1]/hook",
+            "https://[fd00::1]/hook",
+            "https://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(
+                matches!(check_url(url).await, Err(UrlError::NotPublic(_))),
+                "{url} accepted"
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn deliveries_keep_the_order_of_events(pool: Pool<Postgres>) {
+        let (url, mut received) = http_server("200 OK").await;
+        let webhook = webhook(&url, &pool).await;
+
+        let (sender, queue) = mpsc::channel(DELIVERY_QUEUE_SIZE);
+        let delivering = tokio::spawn(delivery(webhook, Settings::default(), &pool).run(queue));
+        for n in 0..10 {
+            sender.send(payload(n)).await.unwrap();
+        }
+        drop(sender);
+        delivering.await.unwrap();
+
+        for n in 0..10 {
+            assert_eq!(Some(n.to_string()), received.recv().await);
+        }
+    }
+
+    #[sqlx::test]
+    async fn a_broken_circuit_drops_the_events_queued(pool: Pool<Postgres>) {
+        let (url, mut received) = http_server("500 Internal Server Error").await;
+        let webhook = webhook(&url, &pool).await;
+        let id = webhook.id;
+        let settings = Settings {
+            max_attempts: 1,
+            failure_threshold: 2,
+            ..Settings::default()
+        };
+
+        let (sender, queue) = mpsc::channel(DELIVERY_QUEUE_SIZE);
+        let delivering = tokio::spawn(delivery(webhook, settings, &pool).run(queue));
+        for n in 0..4 {
+            sender.send(payload(n)).await.unwrap();
+        }
+        drop(sender);
+        delivering.await.unwrap();
+
+        // the second failure broke the circuit, the rest was skipped
+        assert_eq!(Some("0".to_string()), received.recv().await);
+        assert_eq!(Some("1".to_string()), received.recv().await);
+        assert!(received.try_recv().is_err());
+        let (failures, disabled_until): (i32, Option<DateTime<Utc>>) =
+            sqlx::query_as("select failures, disabled_until from webhooks where id = $1")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(0, failures);
+        assert!(disabled_until.is_some_and(|disabled_until| disabled_until > Utc::now()));
+    }
+
+    #[sqlx::test]
+    async fn route_events_resolve_their_org_once(pool: Pool<Postgres>) {
+        let (_, route_updates) = broadcast::channel(1);
+        let (_, org_updates) = broadcast::channel(1);
+        let mut dispatcher =
+            Dispatcher::new(&Settings::default(), pool, route_updates, org_updates).unwrap();
+        let route_id = "7d4d5d42-9a4b-11ee-b9d1-0242ac120002".to_string();
+        let mut route = Route::new(net_id(0x000024), 7, 1);
+        route.id = route_id.clone();
+        let update = |action: ActionV1, data| RouteStreamResV1 {
+            action: action.into(),
+            data: Some(data),
+            timestamp: 0,
+            signer: vec![],
+            signature: vec![],
+        };
+        let eui_pair = route_stream_res_v1::Data::EuiPair(EuiPairV1::from(EuiPair::new(
+            route_id.clone(),
+            eui(1),
+            eui(2),
+        )));
+
+        let event = dispatcher
+            .route_event(update(
+                ActionV1::Add,
+                route_stream_res_v1::Data::Route(route.clone().into()),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(("route", 7), (event.event, event.oui));
+
+        // the route isn't in the db, its org comes from the route event
+        let event = dispatcher
+            .route_event(update(ActionV1::Add, eui_pair.clone()))
+            .await
+            .unwrap();
+        assert_eq!(
+            ("eui_pairs", "add", 7),
+            (event.event, event.action, event.oui)
+        );
+
+        dispatcher
+            .route_event(update(
+                ActionV1::Remove,
+                route_stream_res_v1::Data::Route(route.into()),
+            ))
+            .await
+            .unwrap();
+        assert!(dispatcher
+            .route_event(update(ActionV1::Remove, eui_pair))
+            .await
+            .is_none());
+    }
+}
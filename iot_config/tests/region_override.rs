@@ -0,0 +1,124 @@
+use chrono::{Duration, Utc};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::Region;
+use iot_config::region_override::{self, RegionOverride};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+const SIGNER: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+// every test runs against its own database, so one gateway serves them all
+const GATEWAY: &str = "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp";
+
+fn region_override(
+    address: &PublicKeyBinary,
+    region: Region,
+    expires_in: Duration,
+) -> RegionOverride {
+    RegionOverride {
+        address: address.clone(),
+        region,
+        expires_at: Utc::now() + expires_in,
+        reason: "border".to_string(),
+    }
+}
+
+async fn log_regions(
+    pool: &PgPool,
+    address: &PublicKeyBinary,
+) -> anyhow::Result<Vec<Option<String>>> {
+    Ok(sqlx::query_scalar(
+        "select region from gateway_region_override_log where address = $1 order by id",
+    )
+    .bind(address.to_string())
+    .fetch_all(pool)
+    .await?)
+}
+
+#[sqlx::test]
+async fn set_replaces_the_override(pool: PgPool) -> anyhow::Result<()> {
+    let signer = PublicKey::from_str(SIGNER)?;
+    let address = PublicKeyBinary::from_str(GATEWAY)?;
+    assert!(region_override::get_active(&address, &pool)
+        .await?
+        .is_none());
+
+    region_override::set(
+        &region_override(&address, Region::Eu868, Duration::hours(1)),
+        &signer,
+        &pool,
+    )
+    .await?;
+    region_override::set(
+        &region_override(&address, Region::Us915, Duration::hours(1)),
+        &signer,
+        &pool,
+    )
+    .await?;
+
+    let active = region_override::get_active(&address, &pool)
+        .await?
+        .expect("active override");
+    assert_eq!(Region::Us915, active.region);
+    assert_eq!(
+        Some(&Region::Us915),
+        region_override::all_active(&pool).await?.get(&address)
+    );
+    assert_eq!(
+        vec![
+            Some(Region::Eu868.to_string()),
+            Some(Region::Us915.to_string())
+        ],
+        log_regions(&pool, &address).await?
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn expired_overrides_are_ignored(pool: PgPool) -> anyhow::Result<()> {
+    let signer = PublicKey::from_str(SIGNER)?;
+    let address = PublicKeyBinary::from_str(GATEWAY)?;
+    region_override::set(
+        &region_override(&address, Region::Eu868, Duration::hours(-1)),
+        &signer,
+        &pool,
+    )
+    .await?;
+
+    assert!(region_override::get_active(&address, &pool)
+        .await?
+        .is_none());
+    assert!(region_override::all_active(&pool).await?.is_empty());
+    // clearing an expired override reports none was in place
+    assert!(!region_override::clear(&address, "expired", &signer, &pool).await?);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn clear_is_audited(pool: PgPool) -> anyhow::Result<()> {
+    let signer = PublicKey::from_str(SIGNER)?;
+    let address = PublicKeyBinary::from_str(GATEWAY)?;
+    region_override::set(
+        &region_override(&address, Region::Eu868, Duration::hours(1)),
+        &signer,
+        &pool,
+    )
+    .await?;
+
+    assert!(region_override::clear(&address, "fixed", &signer, &pool).await?);
+    assert!(region_override::get_active(&address, &pool)
+        .await?
+        .is_none());
+    // clearing again is a no-op, still recorded
+    assert!(!region_override::clear(&address, "fixed", &signer, &pool).await?);
+
+    assert_eq!(
+        vec![Some(Region::Eu868.to_string()), None, None],
+        log_regions(&pool, &address).await?
+    );
+    let reasons: Vec<String> =
+        sqlx::query_scalar("select reason from gateway_region_override_log order by id")
+            .fetch_all(&pool)
+            .await?;
+    assert_eq!(vec!["border", "fixed", "fixed"], reasons);
+    Ok(())
+}
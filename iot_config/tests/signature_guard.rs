@@ -0,0 +1,90 @@
+use chrono::{Duration, Utc};
+use helium_crypto::PublicKey;
+use iot_config::signature_guard::{self, Settings, SignatureGuard};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+const SIGNER: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+
+fn settings(lock: bool) -> Settings {
+    Settings {
+        failure_threshold: 2,
+        lock,
+        ..Default::default()
+    }
+}
+
+async fn create_org(pool: &PgPool) -> anyhow::Result<u64> {
+    let oui: i64 = sqlx::query_scalar(
+        "insert into organizations (owner_pubkey, payer_pubkey) values ($1, $1) returning oui",
+    )
+    .bind(SIGNER)
+    .fetch_one(pool)
+    .await?;
+    Ok(oui as u64)
+}
+
+async fn failure_count(pool: &PgPool) -> anyhow::Result<i64> {
+    Ok(
+        sqlx::query_scalar("select count(*) from org_signature_failures")
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+#[sqlx::test]
+async fn locks_org_at_threshold(pool: PgPool) -> anyhow::Result<()> {
+    let oui = create_org(&pool).await?;
+    let guard = SignatureGuard::new(&settings(true), pool.clone())?;
+    let signer = PublicKey::from_str(SIGNER)?;
+
+    guard.record_failure(oui, &signer, "org").await;
+    assert!(guard.check_unlocked(oui).await.is_ok());
+
+    guard.record_failure(oui, &signer, "org").await;
+    let status = guard.check_unlocked(oui).await.expect_err("locked");
+    assert_eq!(tonic::Code::FailedPrecondition, status.code());
+
+    assert!(signature_guard::unlock(oui, &pool).await?);
+    assert!(guard.check_unlocked(oui).await.is_ok());
+    Ok(())
+}
+
+#[sqlx::test]
+async fn never_locks_when_disabled(pool: PgPool) -> anyhow::Result<()> {
+    let oui = create_org(&pool).await?;
+    let guard = SignatureGuard::new(&settings(false), pool.clone())?;
+    let signer = PublicKey::from_str(SIGNER)?;
+
+    for _ in 0..5 {
+        guard.record_failure(oui, &signer, "org").await;
+    }
+    assert!(guard.check_unlocked(oui).await.is_ok());
+    assert!(!signature_guard::is_locked(oui, &pool).await?);
+    assert_eq!(5, failure_count(&pool).await?);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn prunes_failures_past_retention(pool: PgPool) -> anyhow::Result<()> {
+    let oui = create_org(&pool).await?;
+    let guard = SignatureGuard::new(&settings(false), pool.clone())?;
+    let signer = PublicKey::from_str(SIGNER)?;
+
+    sqlx::query(
+        r#"
+        insert into org_signature_failures (oui, signer_pubkey, rpc, inserted_at)
+        values ($1, $2, 'org', $3), (42, $2, 'org', $3)
+        "#,
+    )
+    .bind(oui as i64)
+    .bind(SIGNER)
+    .bind(Utc::now() - Duration::days(8))
+    .execute(&pool)
+    .await?;
+    assert_eq!(2, failure_count(&pool).await?);
+
+    guard.record_failure(oui, &signer, "org").await;
+    assert_eq!(1, failure_count(&pool).await?);
+    Ok(())
+}
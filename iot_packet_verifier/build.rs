@@ -0,0 +1,36 @@
+// The payer ledger service is not part of helium-proto. Its server and client
+// stubs are generated here from the prost messages defined in `src/proto.rs`.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::proto::{input}"))
+        .output_type(format!("crate::proto::{output}"))
+        .codec_path("tonic::codec::ProstCodec")
+        .build()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let payer_ledger = Service::builder()
+        .name("PayerLedger")
+        .package("helium.packet_verifier")
+        .method(method(
+            "entries",
+            "Entries",
+            "PayerLedgerReqV1",
+            "PayerLedgerResV1",
+        ))
+        .method(method(
+            "burns",
+            "Burns",
+            "PayerBurnsReqV1",
+            "PayerBurnsResV1",
+        ))
+        .build();
+
+    Builder::new().compile(&[payer_ledger]);
+}
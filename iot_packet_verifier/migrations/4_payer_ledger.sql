@@ -0,0 +1,14 @@
+CREATE TYPE payer_ledger_kind AS ENUM ('debit', 'credit', 'balance_refresh');
+
+CREATE TABLE payer_ledger (
+       id BIGSERIAL PRIMARY KEY,
+       payer TEXT NOT NULL,
+       kind payer_ledger_kind NOT NULL,
+       amount BIGINT NOT NULL,
+       balance BIGINT,
+       oui BIGINT,
+       reference TEXT,
+       recorded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX payer_ledger_payer_recorded_at_idx ON payer_ledger (payer, recorded_at);
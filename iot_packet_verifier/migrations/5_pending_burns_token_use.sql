@@ -0,0 +1,5 @@
+CREATE TYPE token_use AS ENUM ('iot_packets', 'mobile_data');
+
+ALTER TABLE pending_burns ADD COLUMN token_use token_use NOT NULL DEFAULT 'iot_packets';
+ALTER TABLE pending_burns DROP CONSTRAINT pending_burns_pkey;
+ALTER TABLE pending_burns ADD PRIMARY KEY (payer, token_use);
@@ -0,0 +1,4 @@
+ALTER TABLE pending_burns
+      ADD COLUMN burn_signature TEXT,
+      ADD COLUMN burn_amount BIGINT,
+      ADD COLUMN burn_last_valid_block_height BIGINT;
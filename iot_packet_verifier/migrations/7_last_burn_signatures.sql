@@ -0,0 +1 @@
+ALTER TABLE pending_burns ADD COLUMN last_burn_signature TEXT;
@@ -0,0 +1,6 @@
+-- Burns of shadow mode were recorded with an empty signature
+UPDATE pending_burns
+SET burn_signature = NULL, burn_amount = NULL, burn_last_valid_block_height = NULL
+WHERE burn_signature = '';
+
+UPDATE pending_burns SET last_burn_signature = NULL WHERE last_burn_signature = '';
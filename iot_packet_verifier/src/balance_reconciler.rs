@@ -0,0 +1,201 @@
+//! Periodic reconciliation of the balance cache with the DC escrow balances
+//! on chain, which it drifts from after rpc failures.
+
+use anyhow::Result;
+use dc_ledger::{
+    balances::{Balance, BalanceStore},
+    payer_ledger, pending_burns,
+};
+use helium_crypto::PublicKeyBinary;
+use serde::Deserialize;
+use solana::SolanaNetwork;
+use sqlx::{Pool, Postgres};
+use std::time::{Duration, Instant};
+
+const DRIFT_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_balance_drift");
+const CORRECTION_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_balance_correction");
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Reconcile cached balances with the chain when the solana integration
+    /// is enabled. Default is true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// How often cached balances are reconciled. Default is 1 hour
+    #[serde(with = "settings_loader::duration", default = "default_period")]
+    pub period: Duration,
+    /// Cached balances differing from the chain by more than this many DC
+    /// are corrected. Default is 0, correcting any drift
+    #[serde(default)]
+    pub drift_threshold: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            period: default_period(),
+            drift_threshold: 0,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_period() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+/// Drift of the `on_chain` balance of a payer from its `cached` balance,
+/// correcting the cached balance when the drift exceeds `drift_threshold`.
+/// None for balances reset by a burn, which are fetched on the next debit
+pub fn reconcile_balance(cached: &mut Balance, on_chain: u64, drift_threshold: u64) -> Option<i64> {
+    if cached.balance == 0 && !cached.subscribed {
+        return None;
+    }
+    let drift = on_chain as i64 - cached.balance as i64;
+    if drift.unsigned_abs() > drift_threshold {
+        cached.balance = on_chain;
+    }
+    Some(drift)
+}
+
+/// What a cached balance was last set from, which debits leave as is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BalanceSource {
+    balance: u64,
+    subscribed: bool,
+    last_fetch: Option<Instant>,
+}
+
+impl BalanceSource {
+    fn of(balance: &Balance) -> Self {
+        Self {
+            balance: balance.balance,
+            subscribed: balance.subscribed,
+            last_fetch: balance.last_fetch,
+        }
+    }
+}
+
+/// Corrects the balance cache from the chain, fetching the escrow balance of
+/// every payer in the cache every `period`. The total drift of a pass is
+/// reported by the `balance_drift` gauge and drifting payers are logged.
+/// Payers with a burn in flight, or whose balance was reset by a burn, are
+/// left to the next pass as the chain and the cache disagree on them until
+/// the burn is settled
+pub struct BalanceReconciler<S> {
+    solana: S,
+    balances: BalanceStore,
+    ledger: Pool<Postgres>,
+    period: Duration,
+    drift_threshold: u64,
+}
+
+impl<S> BalanceReconciler<S>
+where
+    S: SolanaNetwork,
+{
+    pub fn new(
+        settings: &Settings,
+        solana: S,
+        balances: BalanceStore,
+        ledger: Pool<Postgres>,
+    ) -> Self {
+        Self {
+            solana,
+            balances,
+            ledger,
+            period: settings.period,
+            drift_threshold: settings.drift_threshold,
+        }
+    }
+
+    pub async fn run(self, shutdown: triggered::Listener) -> Result<()> {
+        let mut reconcile = tokio::time::interval(self.period);
+        // The cache is filled from the chain at startup
+        reconcile.tick().await;
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => return Ok(()),
+                _ = reconcile.tick() => {
+                    if let Err(err) = self.reconcile().await {
+                        tracing::warn!(?err, "balance reconciliation failed");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let burning = pending_burns::payers_burning(&self.ledger).await?;
+        let payers: Vec<PublicKeyBinary> = self
+            .balances
+            .lock()
+            .await
+            .keys()
+            .filter(|payer| !burning.contains(*payer))
+            .cloned()
+            .collect();
+
+        let (mut corrected, mut total_drift) = (0, 0);
+        for payer in &payers {
+            let Some(before) = self.balances.lock().await.get(payer).map(BalanceSource::of) else {
+                continue;
+            };
+            let on_chain = match self.solana.payer_balance(payer).await {
+                Ok(balance) => balance,
+                Err(err) => {
+                    tracing::warn!(%payer, ?err, "failed to fetch balance to reconcile");
+                    continue;
+                }
+            };
+            if let Err(err) =
+                payer_ledger::record_balance_refresh(&self.ledger, payer, on_chain).await
+            {
+                tracing::warn!(%payer, ?err, "failed to record balance refresh");
+            }
+
+            let mut balances = self.balances.lock().await;
+            let Some(cached) = balances.get_mut(payer) else {
+                continue;
+            };
+            // the balance fetched is only comparable to a cached balance
+            // which didn't change since, of a payer still not burning
+            if BalanceSource::of(cached) != before {
+                continue;
+            }
+            match pending_burns::is_burning(&self.ledger, payer).await {
+                Ok(false) => (),
+                Ok(true) => continue,
+                Err(err) => {
+                    tracing::warn!(%payer, ?err, "failed to check for a burn in flight");
+                    continue;
+                }
+            }
+            let Some(drift) = reconcile_balance(cached, on_chain, self.drift_threshold) else {
+                continue;
+            };
+            total_drift += drift.unsigned_abs();
+            if drift != 0 {
+                tracing::info!(%payer, drift, burned = cached.burned, "cached balance drifted");
+            }
+            if drift.unsigned_abs() > self.drift_threshold {
+                tracing::info!(%payer, drift, "corrected cached balance");
+                metrics::increment_counter!(CORRECTION_COUNTER);
+                corrected += 1;
+            }
+        }
+        metrics::gauge!(DRIFT_GAUGE, total_drift as f64);
+        tracing::info!(
+            payers = payers.len(),
+            corrected,
+            total_drift,
+            "reconciled cached balances"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Balances of payers pushed by the solana chain over the websocket of the
+//! rpc node, so debits of subscribed payers don't wait on fetching them.
+
+use anyhow::{bail, Result};
+use dc_ledger::{balances::BalanceStore, payer_ledger, pending_burns};
+use futures::stream::{self, AbortHandle, SelectAll, StreamExt};
+use helium_crypto::PublicKeyBinary;
+use serde::Deserialize;
+use solana::payer_subscriptions::{Subscribe, SubscriptionNetwork};
+use sqlx::{Pool, Postgres};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+const UPDATE_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_balance_subscription_update");
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Subscribe to the balances of payers when the solana integration is
+    /// enabled. Default is true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// How often payers entering the balance cache are subscribed to.
+    /// Default is 1 minute
+    #[serde(with = "settings_loader::duration", default = "default_refresh_period")]
+    pub refresh_period: Duration,
+    /// Payers not debited for this long are unsubscribed from until debited
+    /// again. Default is 1 hour
+    #[serde(with = "settings_loader::duration", default = "default_idle_timeout")]
+    pub idle_timeout: Duration,
+    /// Time to wait before reconnecting to the rpc node after losing the
+    /// connection. Default is 10 seconds
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_reconnect_delay"
+    )]
+    pub reconnect_delay: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            refresh_period: default_refresh_period(),
+            idle_timeout: default_idle_timeout(),
+            reconnect_delay: default_reconnect_delay(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_refresh_period() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_idle_timeout() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn default_reconnect_delay() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Keeps the balances of the payers in the balance cache current by
+/// subscribing to their DC escrow accounts. Every `refresh_period`, payers
+/// debited within `idle_timeout` are subscribed to and their balance fetched
+/// once subscribed, so no change before the subscription is missed. Payers no
+/// longer debited are unsubscribed from and fall back to having their balance
+/// fetched on insufficient debits
+pub struct BalanceSubscriptions<S> {
+    solana: Arc<S>,
+    balances: BalanceStore,
+    ledger: Pool<Postgres>,
+    refresh_period: Duration,
+    idle_timeout: Duration,
+    reconnect_delay: Duration,
+}
+
+/// An update of the subscriptions of a payer
+enum Update {
+    Balance(PublicKeyBinary, u64),
+    /// The subscription with this id ended, by being unsubscribed from or
+    /// on losing the connection
+    Ended(PublicKeyBinary, u64),
+}
+
+/// Payers to subscribe to and to unsubscribe from
+#[derive(Default)]
+struct Changes {
+    subscribe: Vec<PublicKeyBinary>,
+    unsubscribe: Vec<PublicKeyBinary>,
+}
+
+impl<S> BalanceSubscriptions<S>
+where
+    S: SubscriptionNetwork,
+{
+    pub fn new(
+        settings: &Settings,
+        solana: Arc<S>,
+        balances: BalanceStore,
+        ledger: Pool<Postgres>,
+    ) -> Self {
+        Self {
+            solana,
+            balances,
+            ledger,
+            refresh_period: settings.refresh_period,
+            idle_timeout: settings.idle_timeout,
+            reconnect_delay: settings.reconnect_delay,
+        }
+    }
+
+    /// Subscribe until shutdown, reconnecting after `reconnect_delay` when the
+    /// connection is lost. Until then every payer falls back to having its
+    /// balance fetched, as the updates pushed meanwhile are lost
+    pub async fn run(self, shutdown: triggered::Listener) -> Result<()> {
+        loop {
+            match self.subscribe(&shutdown).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    tracing::warn!(?err, "balance subscriptions lost");
+                    self.fall_back_to_fetching().await;
+                }
+            }
+            tokio::select! {
+                _ = shutdown.clone() => return Ok(()),
+                _ = tokio::time::sleep(self.reconnect_delay) => (),
+            }
+        }
+    }
+
+    /// Subscribe to the balances of the payers debited recently until
+    /// shutdown, failing once the connection to the rpc node is lost
+    async fn subscribe(&self, shutdown: &triggered::Listener) -> Result<()> {
+        let subscriptions = self.solana.payer_subscriptions().await?;
+        // The id of the subscription of every payer subscribed to, telling
+        // the end of a subscription unsubscribed from from a lost connection
+        let mut subscribed: HashMap<PublicKeyBinary, (u64, AbortHandle)> = HashMap::new();
+        let mut next_id = 0;
+        let mut updates = SelectAll::new();
+        let mut refresh = tokio::time::interval(self.refresh_period);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => return Ok(()),
+                _ = refresh.tick() => {
+                    let changes = self.changes(&subscribed).await;
+                    for payer in changes.unsubscribe {
+                        if let Some((_, subscription)) = subscribed.remove(&payer) {
+                            subscription.abort();
+                        }
+                    }
+                    for payer in changes.subscribe {
+                        let (balances, subscription) =
+                            stream::abortable(subscriptions.subscribe(&payer).await?);
+                        next_id += 1;
+                        let id = next_id;
+                        let update_payer = payer.clone();
+                        let ended_payer = payer.clone();
+                        updates.push(
+                            balances
+                                .map(move |balance| Update::Balance(update_payer.clone(), balance))
+                                .chain(stream::once(async move { Update::Ended(ended_payer, id) }))
+                                .boxed(),
+                        );
+                        subscribed.insert(payer.clone(), (id, subscription));
+                        match self.solana.payer_balance(&payer).await {
+                            Ok(balance) => self.set_balance(&payer, balance).await,
+                            Err(err) => {
+                                tracing::warn!(%payer, ?err, "failed to fetch subscribed balance");
+                            }
+                        }
+                    }
+                    tracing::debug!(payers = subscribed.len(), "subscribed to payer balances");
+                }
+                Some(update) = updates.next() => match update {
+                    Update::Balance(payer, balance) => self.set_balance(&payer, balance).await,
+                    Update::Ended(payer, id) => {
+                        if matches!(subscribed.get(&payer), Some((current, _)) if *current == id) {
+                            bail!("connection to the rpc node lost");
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Payers debited within the idle timeout and not subscribed to yet, and
+    /// payers subscribed to and no longer debited, which fall back to having
+    /// their balance fetched
+    async fn changes(&self, subscribed: &HashMap<PublicKeyBinary, (u64, AbortHandle)>) -> Changes {
+        let mut changes = Changes::default();
+        for (payer, balance) in self.balances.lock().await.iter_mut() {
+            let active = balance
+                .last_debit
+                .map_or(false, |debited| debited.elapsed() < self.idle_timeout);
+            match (active, subscribed.contains_key(payer)) {
+                (true, false) => changes.subscribe.push(payer.clone()),
+                (false, true) => {
+                    balance.subscribed = false;
+                    changes.unsubscribe.push(payer.clone());
+                }
+                _ => (),
+            }
+        }
+        changes
+    }
+
+    /// Have the balance of every payer fetched again on insufficient debits,
+    /// until their subscriptions push it again
+    async fn fall_back_to_fetching(&self) {
+        for balance in self.balances.lock().await.values_mut() {
+            balance.subscribed = false;
+        }
+    }
+
+    /// Set the cached balance of a payer, unless a burn of the payer is in
+    /// flight: the burn is still counted in its cached `burned` amount while
+    /// the balance pushed once it lands already excludes it, so the balance is
+    /// left for the burner to reset once the burn is settled
+    async fn set_balance(&self, payer: &PublicKeyBinary, balance: u64) {
+        metrics::increment_counter!(UPDATE_COUNTER);
+        match pending_burns::is_burning(&self.ledger, payer).await {
+            Ok(false) => {
+                if let Some(cached) = self.balances.lock().await.get_mut(payer) {
+                    cached.balance = balance;
+                    cached.subscribed = true;
+                }
+            }
+            // the balance pushed may exclude the burn still counted as burned
+            Ok(true) => tracing::debug!(%payer, balance, "burn in flight, balance left as is"),
+            Err(err) => tracing::warn!(%payer, ?err, "failed to check for a burn in flight"),
+        }
+        if let Err(err) = payer_ledger::record_balance_refresh(&self.ledger, payer, balance).await {
+            tracing::warn!(%payer, ?err, "failed to record balance refresh");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Early warnings for orgs whose balance is running low, before the verifier
+//! disables them.
+
+use crate::verifier::OrgDebits;
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_balance_warning::{BalanceWarning, BalanceWarningV1},
+};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Fraction of the average daily spend below which a warning is raised.
+    /// Default is 0.2
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    /// Minimum time between two warnings for the same org. Default is 6h
+    #[serde(with = "settings_loader::duration", default = "default_cooldown")]
+    pub cooldown: std::time::Duration,
+    /// Window over which the average daily spend is computed. Default is 7 days
+    #[serde(with = "settings_loader::duration", default = "default_spend_window")]
+    pub spend_window: std::time::Duration,
+    /// Optional url to which warnings are POSTed as json
+    pub webhook: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            cooldown: default_cooldown(),
+            spend_window: default_spend_window(),
+            webhook: None,
+        }
+    }
+}
+
+fn default_threshold() -> f64 {
+    0.2
+}
+
+fn default_cooldown() -> std::time::Duration {
+    std::time::Duration::from_secs(6 * 60 * 60)
+}
+
+fn default_spend_window() -> std::time::Duration {
+    std::time::Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+/// Timeout of a webhook delivery
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Spend must have been observed for at least this long before the burn
+/// rate is trusted enough to raise a warning.
+const MIN_OBSERVATION: i64 = 60 * 60;
+
+/// Tracks the spend of each org over a rolling window, and warns when the
+/// remaining balance falls below a fraction of the average daily spend.
+pub struct SpendTracker {
+    threshold: f64,
+    cooldown: Duration,
+    spend_window: Duration,
+    spend: HashMap<u64, OrgSpend>,
+    last_warned: HashMap<u64, DateTime<Utc>>,
+}
+
+struct OrgSpend {
+    first_seen: DateTime<Utc>,
+    debits: VecDeque<(DateTime<Utc>, u64)>,
+}
+
+impl OrgSpend {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            first_seen: now,
+            debits: VecDeque::new(),
+        }
+    }
+
+    /// Average spend per day over the observed part of the window, if it
+    /// has been observed long enough.
+    fn daily_burn_rate(&self, now: DateTime<Utc>, window: Duration) -> Option<u64> {
+        let start = self.first_seen.max(now - window);
+        let observed = (now - start).num_seconds();
+        if observed < MIN_OBSERVATION {
+            return None;
+        }
+        let total: u64 = self.debits.iter().map(|(_, amount)| amount).sum();
+        Some((total as u128 * Duration::days(1).num_seconds() as u128 / observed as u128) as u64)
+    }
+}
+
+impl SpendTracker {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            threshold: settings.threshold,
+            cooldown: Duration::from_std(settings.cooldown).unwrap_or_else(|_| Duration::hours(6)),
+            spend_window: Duration::from_std(settings.spend_window)
+                .unwrap_or_else(|_| Duration::days(7)),
+            spend: HashMap::new(),
+            last_warned: HashMap::new(),
+        }
+    }
+
+    /// Record the debits of a verified file and return the warnings that
+    /// should be published for it.
+    pub fn record(
+        &mut self,
+        now: DateTime<Utc>,
+        debits: &HashMap<u64, OrgDebits>,
+    ) -> Vec<BalanceWarning> {
+        let mut warnings = Vec::new();
+        for (oui, org_debits) in debits {
+            let spend = self.spend.entry(*oui).or_insert_with(|| OrgSpend::new(now));
+            spend.debits.push_back((now, org_debits.debited));
+            while matches!(spend.debits.front(), Some((ts, _)) if *ts < now - self.spend_window) {
+                spend.debits.pop_front();
+            }
+
+            let Some(daily_burn_rate) = spend.daily_burn_rate(now, self.spend_window) else {
+                continue;
+            };
+            if daily_burn_rate == 0
+                || org_debits.remaining_balance as f64 >= daily_burn_rate as f64 * self.threshold
+            {
+                continue;
+            }
+            if matches!(self.last_warned.get(oui), Some(last) if now - *last < self.cooldown) {
+                continue;
+            }
+            self.last_warned.insert(*oui, now);
+
+            let seconds_left = org_debits.remaining_balance as u128
+                * Duration::days(1).num_seconds() as u128
+                / daily_burn_rate as u128;
+            warnings.push(BalanceWarning {
+                oui: *oui,
+                payer: org_debits.payer.clone(),
+                balance: org_debits.remaining_balance,
+                daily_burn_rate,
+                projected_exhaustion: now + Duration::seconds(seconds_left as i64),
+                timestamp: now,
+            });
+        }
+        warnings
+    }
+}
+
+/// Publishes warnings raised by the [SpendTracker] to a file_store stream
+/// and, if configured, a webhook.
+pub struct BalanceWarnings {
+    tracker: SpendTracker,
+    warnings: FileSinkClient,
+    webhook: Option<String>,
+    http: reqwest::Client,
+}
+
+impl BalanceWarnings {
+    pub fn new(settings: &Settings, warnings: FileSinkClient) -> reqwest::Result<Self> {
+        Ok(Self {
+            tracker: SpendTracker::new(settings),
+            warnings,
+            webhook: settings.webhook.clone(),
+            http: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    pub async fn process(&mut self, debits: &HashMap<u64, OrgDebits>) -> file_store::Result {
+        for warning in self.tracker.record(Utc::now(), debits) {
+            tracing::warn!(
+                oui = warning.oui,
+                payer = %warning.payer,
+                balance = warning.balance,
+                daily_burn_rate = warning.daily_burn_rate,
+                projected_exhaustion = %warning.projected_exhaustion,
+                "Org balance running low"
+            );
+            metrics::increment_counter!("balance_warnings", "oui" => warning.oui.to_string());
+            if let Some(url) = &self.webhook {
+                // Webhook delivery is best effort, and kept off the verify
+                // path; the file_store stream is the record of truth.
+                let request = self.http.post(url).json(&warning);
+                let oui = warning.oui;
+                tokio::spawn(async move {
+                    let result = request
+                        .send()
+                        .await
+                        .and_then(|response| response.error_for_status());
+                    if let Err(err) = result {
+                        tracing::error!(oui, "Failed to deliver balance warning: {err}");
+                    }
+                });
+            }
+            self.warnings
+                .write(BalanceWarningV1::from(warning), [])
+                .await?;
+        }
+        Ok(())
+    }
+}
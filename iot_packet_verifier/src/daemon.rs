@@ -1,10 +1,22 @@
 use crate::{
-    balances::BalanceCache,
-    burner::Burner,
+    balance_reconciler::BalanceReconciler,
+    balance_subscriptions::BalanceSubscriptions,
+    balance_warnings::BalanceWarnings,
+    disable_hysteresis::DisableHysteresis,
+    ledger_service::LedgerService,
+    minimum_balances::MinimumBalances,
+    org_payers::CachedOrgClient,
+    payer_ledger,
+    pricing::RulePricing,
     settings::Settings,
     verifier::{ConfigServer, Verifier},
 };
 use anyhow::{bail, Error, Result};
+use dc_ledger::{
+    balances::BalanceCache,
+    burner::Burner,
+    pending_burns::{self, TokenUse},
+};
 use file_store::{
     file_info_poller::{FileInfoStream, LookbackBehavior},
     file_sink::FileSinkClient,
@@ -14,21 +26,21 @@ use file_store::{
 };
 use futures_util::TryFutureExt;
 use iot_config::client::OrgClient;
+use poc_metrics::preflight::Preflight;
 use solana::SolanaRpc;
 use sqlx::{Pool, Postgres};
 use std::{sync::Arc, time::Duration};
-use tokio::{
-    signal,
-    sync::{mpsc::Receiver, Mutex},
-};
+use tokio::{signal, sync::mpsc::Receiver};
 
 struct Daemon {
     pool: Pool<Postgres>,
-    verifier: Verifier<BalanceCache<Option<Arc<SolanaRpc>>>, Arc<Mutex<OrgClient>>>,
+    verifier: Verifier<BalanceCache<Option<Arc<SolanaRpc>>>, CachedOrgClient, RulePricing>,
     report_files: Receiver<FileInfoStream<PacketRouterPacketReport>>,
     valid_packets: FileSinkClient,
     invalid_packets: FileSinkClient,
-    minimum_allowed_balance: u64,
+    packet_prices: FileSinkClient,
+    balance_warnings: BalanceWarnings,
+    minimum_balances: MinimumBalances,
 }
 
 impl Daemon {
@@ -56,21 +68,32 @@ impl Daemon {
     ) -> Result<()> {
         tracing::info!(file = %report_file.file_info, "Verifying file");
 
+        let reference = report_file.file_info.key.clone();
         let mut transaction = self.pool.begin().await?;
+        pending_burns::lock(&mut transaction).await?;
+        self.verifier.debiter.sync(&mut transaction).await?;
         let reports = report_file.into_stream(&mut transaction).await?;
 
-        self.verifier
+        let debits = self
+            .verifier
             .verify(
-                self.minimum_allowed_balance,
+                &self.minimum_balances,
                 &mut transaction,
                 reports,
                 &self.valid_packets,
                 &self.invalid_packets,
+                &self.packet_prices,
             )
             .await?;
+        for (oui, org_debits) in &debits {
+            payer_ledger::record_debit(&mut transaction, *oui, org_debits, &reference).await?;
+        }
         transaction.commit().await?;
         self.valid_packets.commit().await?;
         self.invalid_packets.commit().await?;
+        self.packet_prices.commit().await?;
+
+        self.balance_warnings.process(&debits).await?;
 
         Ok(())
     }
@@ -97,17 +120,45 @@ impl Cmd {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
 
-        let solana = if settings.enable_solana_integration {
-            let Some(ref solana_settings) = settings.solana else {
-                bail!("Missing solana section in settings");
-            };
-            // Set up the solana RpcClient:
-            Some(SolanaRpc::new(solana_settings).await?)
-        } else {
-            None
-        };
+        // Check all external dependencies before starting, setting up the
+        // solana RpcClient on the way, none in shadow mode:
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        let solana = preflight
+            .check(
+                "solana burn keypair",
+                SolanaRpc::shadowable(settings.enable_solana_integration, settings.solana.as_ref()),
+            )
+            .await
+            .flatten();
+        if let Some(solana) = &solana {
+            preflight.check("solana rpc", solana.check_health()).await;
+        }
+        preflight
+            .check("ingest bucket", settings.ingest.check_read())
+            .await;
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        preflight
+            .check("iot config", settings.iot_config_client.check_reachable())
+            .await;
+        preflight.check_result(
+            "iot config keypair",
+            settings.iot_config_client.signing_keypair(),
+        );
+        if let Some(api_settings) = &settings.ledger_api {
+            preflight.check_result("ledger api keypair", api_settings.signing_keypair());
+        }
+        preflight.finish()?;
+
+        sqlx::migrate!().run(&pool).await?;
 
         let sol_balance_monitor = solana::balance_monitor::start(
             env!("CARGO_PKG_NAME"),
@@ -116,16 +167,23 @@ impl Cmd {
         )
         .await?;
 
+        let pricing = RulePricing::from_settings(&settings.pricing)?;
+
         // Set up the balance cache:
-        let balances = BalanceCache::new(&mut pool, solana.clone()).await?;
+        let balances = BalanceCache::new(&mut pool, solana.clone())
+            .await?
+            .with_ledger(pool.clone())
+            .with_refetch_interval(Duration::from_secs(settings.balance_refetch_interval));
 
         // Set up the balance burner:
         let burner = Burner::new(
             pool.clone(),
             &balances,
+            TokenUse::IotPackets,
             settings.burn_period,
             solana.clone(),
-        );
+        )
+        .with_dry_run(settings.dry_run);
 
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
@@ -156,9 +214,31 @@ impl Cmd {
         .create()
         .await?;
 
-        let org_client = Arc::new(Mutex::new(OrgClient::from_settings(
-            &settings.iot_config_client,
-        )?));
+        // Prices applied to verified packets:
+        let (packet_prices, mut packet_prices_server) = FileSinkBuilder::new(
+            FileType::IotPacketPrice,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_packet_prices"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Low balance warnings:
+        let (balance_warnings, mut balance_warnings_server) = FileSinkBuilder::new(
+            FileType::IotBalanceWarning,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_balance_warnings"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .create()
+        .await?;
+
+        let org_client =
+            CachedOrgClient::new(OrgClient::from_settings(&settings.iot_config_client)?);
 
         let file_store = FileStore::from_settings(&settings.ingest).await?;
 
@@ -172,17 +252,57 @@ impl Cmd {
                 .start(shutdown_listener.clone())
                 .await?;
 
+        let ledger_api = match settings.ledger_api {
+            Some(ref api_settings) => Some((
+                api_settings,
+                LedgerService::new(
+                    api_settings,
+                    pool.clone(),
+                    org_client.clone(),
+                    balances.balances(),
+                )?,
+            )),
+            None => None,
+        };
+
+        let balance_subscriptions = match solana {
+            Some(ref solana) if settings.balance_subscriptions.enabled => {
+                Some(BalanceSubscriptions::new(
+                    &settings.balance_subscriptions,
+                    solana.clone(),
+                    balances.balances(),
+                    pool.clone(),
+                ))
+            }
+            _ => None,
+        };
+
+        let balance_reconciler = match solana {
+            Some(_) if settings.balance_reconciliation.enabled => Some(BalanceReconciler::new(
+                &settings.balance_reconciliation,
+                solana.clone(),
+                balances.balances(),
+                pool.clone(),
+            )),
+            _ => None,
+        };
+
         let balance_store = balances.balances();
+        let minimum_balances = MinimumBalances::from_settings(settings);
         let verifier_daemon = Daemon {
             pool,
             report_files,
             valid_packets,
             invalid_packets,
+            packet_prices,
+            balance_warnings: BalanceWarnings::new(&settings.balance_warnings, balance_warnings)?,
             verifier: Verifier {
                 debiter: balances,
                 config_server: org_client.clone(),
+                pricing,
+                disable_hysteresis: DisableHysteresis::new(&settings.disable_hysteresis),
             },
-            minimum_allowed_balance: settings.minimum_allowed_balance,
+            minimum_balances: minimum_balances.clone(),
         };
 
         // Run the services:
@@ -193,17 +313,40 @@ impl Cmd {
             verifier_daemon.run(&shutdown_listener).map_err(Error::from),
             valid_packets_server.run().map_err(Error::from),
             invalid_packets_server.run().map_err(Error::from),
+            packet_prices_server.run().map_err(Error::from),
+            balance_warnings_server.run().map_err(Error::from),
+            org_client.clone().run(&shutdown_listener),
             org_client
                 .monitor_funds(
                     solana,
                     balance_store,
-                    settings.minimum_allowed_balance,
+                    minimum_balances,
                     Duration::from_secs(60 * settings.monitor_funds_period),
                     shutdown_listener.clone(),
                 )
                 .map_err(Error::from),
             source_join_handle.map_err(Error::from),
             sol_balance_monitor.map_err(Error::from),
+            async {
+                match balance_subscriptions {
+                    Some(subscriptions) => subscriptions.run(shutdown_listener.clone()).await,
+                    None => Ok(()),
+                }
+            },
+            async {
+                match balance_reconciler {
+                    Some(reconciler) => reconciler.run(shutdown_listener.clone()).await,
+                    None => Ok(()),
+                }
+            },
+            async {
+                match ledger_api {
+                    Some((api_settings, service)) => {
+                        service.serve(api_settings, shutdown_listener.clone()).await
+                    }
+                    None => Ok(()),
+                }
+            },
         )?;
 
         Ok(())
@@ -0,0 +1,125 @@
+//! Hysteresis for disabling orgs whose balance runs out, so an org whose
+//! payer tops up often doesn't flap.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const GRACE_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_org_balance_grace");
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Insufficient balance checks in a row before an org is disabled.
+    /// Default is 1, disabling on the first
+    #[serde(default = "default_consecutive_checks")]
+    pub consecutive_checks: u32,
+    /// Minimum time from the first of the insufficient checks in a row to the
+    /// one disabling the org. Default is 0
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_min_insufficient_duration"
+    )]
+    pub min_insufficient_duration: std::time::Duration,
+    /// Balance below which checks count in the grace metric. Default is 0,
+    /// counting none
+    #[serde(default)]
+    pub grace_balance: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            consecutive_checks: default_consecutive_checks(),
+            min_insufficient_duration: default_min_insufficient_duration(),
+            grace_balance: 0,
+        }
+    }
+}
+
+fn default_consecutive_checks() -> u32 {
+    1
+}
+
+fn default_min_insufficient_duration() -> std::time::Duration {
+    std::time::Duration::ZERO
+}
+
+/// Insufficient balance checks of an org in a row
+struct Streak {
+    checks: u32,
+    since: DateTime<Utc>,
+}
+
+/// Decides when the insufficient balance checks of an org disable it: once
+/// `consecutive_checks` insufficient checks in a row span at least
+/// `min_insufficient_duration` of packet time, a sufficient check in between
+/// starting over. A packet its payer can't pay for at all disables the org
+/// right away
+pub struct DisableHysteresis {
+    consecutive_checks: u32,
+    min_insufficient_duration: Duration,
+    grace_balance: u64,
+    streaks: HashMap<u64, Streak>,
+}
+
+impl Default for DisableHysteresis {
+    fn default() -> Self {
+        Self::new(&Settings::default())
+    }
+}
+
+impl DisableHysteresis {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            consecutive_checks: settings.consecutive_checks,
+            min_insufficient_duration: Duration::from_std(settings.min_insufficient_duration)
+                .unwrap_or_else(|_| Duration::zero()),
+            grace_balance: settings.grace_balance,
+            streaks: HashMap::new(),
+        }
+    }
+
+    /// Record a check of the balance left to org `oui` by a packet received
+    /// at `received`, `None` when the packet couldn't be paid for, returning
+    /// whether the org is to be disabled
+    pub fn check(
+        &mut self,
+        oui: u64,
+        remaining_balance: Option<u64>,
+        minimum_allowed_balance: u64,
+        received: DateTime<Utc>,
+    ) -> bool {
+        if remaining_balance.map_or(true, |balance| balance < self.grace_balance) {
+            metrics::increment_counter!(GRACE_COUNTER, "oui" => oui.to_string());
+        }
+        let Some(remaining_balance) = remaining_balance else {
+            tracing::warn!(oui, "Org balance insufficient for a packet, disabling");
+            self.streaks.remove(&oui);
+            return true;
+        };
+        if remaining_balance >= minimum_allowed_balance {
+            self.streaks.remove(&oui);
+            return false;
+        }
+        let streak = self.streaks.entry(oui).or_insert(Streak {
+            checks: 0,
+            since: received,
+        });
+        streak.checks += 1;
+        if streak.checks < self.consecutive_checks
+            || received - streak.since < self.min_insufficient_duration
+        {
+            tracing::warn!(
+                oui,
+                remaining_balance,
+                checks = streak.checks,
+                since = %streak.since,
+                "Org balance below minimum, disable pending"
+            );
+            return false;
+        }
+        // a disabled org starts over once re-enabled
+        self.streaks.remove(&oui);
+        true
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    org_payers::CachedOrgClient,
+    payer_ledger,
+    proto::{
+        self, payer_ledger_server::PayerLedgerServer, PayerBurnsReqV1, PayerBurnsResV1,
+        PayerLedgerReqV1, PayerLedgerResV1,
+    },
+    verifier::ConfigServerError,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use dc_ledger::{balances::BalanceStore, pending_burns};
+use file_store::{
+    grpc::GrpcResult,
+    traits::{MsgVerify, TimestampEncode},
+};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
+use prost::Message;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use std::{net::SocketAddr, ops::Range, str::FromStr};
+use tonic::{transport, Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_ledger_request");
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Listen address of the payer ledger api. Default "0.0.0.0:8080"
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// File from which to load the keypair signing responses
+    pub keypair: String,
+    /// Max number of entries returned by a single query. Default is 1000
+    #[serde(default = "default_max_query_limit")]
+    pub max_query_limit: u32,
+    /// How far the timestamp of a request may be from now, either way, for
+    /// a captured request not to be replayable. Default is 5m
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_max_request_skew"
+    )]
+    pub max_request_skew: std::time::Duration,
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn default_max_query_limit() -> u32 {
+    1000
+}
+
+fn default_max_request_skew() -> std::time::Duration {
+    std::time::Duration::from_secs(5 * 60)
+}
+
+impl Settings {
+    pub fn signing_keypair(&self) -> anyhow::Result<Keypair> {
+        Ok(settings_loader::keypair(&self.keypair)?)
+    }
+}
+
+/// Serves the payer ledger, and the balance and burns of payers, to the
+/// owners and payers of orgs
+pub struct LedgerService {
+    pool: Pool<Postgres>,
+    orgs: CachedOrgClient,
+    balances: BalanceStore,
+    signing_key: Keypair,
+    max_limit: u32,
+    max_request_skew: Duration,
+}
+
+impl LedgerService {
+    pub fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        orgs: CachedOrgClient,
+        balances: BalanceStore,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool,
+            orgs,
+            balances,
+            signing_key: settings.signing_keypair()?,
+            max_limit: settings.max_query_limit,
+            max_request_skew: Duration::from_std(settings.max_request_skew)?,
+        })
+    }
+
+    /// Serve the ledger api until shutdown
+    pub async fn serve(
+        self,
+        settings: &Settings,
+        shutdown: triggered::Listener,
+    ) -> anyhow::Result<()> {
+        let listen_addr = SocketAddr::from_str(&settings.listen)?;
+        tracing::info!("payer ledger api listening on {listen_addr}");
+        transport::Server::builder()
+            .layer(poc_metrics::request_layer!(
+                "iot_packet_verifier_ledger_connection"
+            ))
+            .add_service(PayerLedgerServer::new(self))
+            .serve_with_shutdown(listen_addr, shutdown)
+            .await?;
+        Ok(())
+    }
+
+    fn limit(&self, requested: u32) -> u32 {
+        match requested {
+            0 => self.max_limit,
+            limit => limit.min(self.max_limit),
+        }
+    }
+
+    /// The payer of the org, if the request is signed by the owner or the
+    /// payer of the org, and recent
+    async fn authorized_payer<R: MsgVerify>(
+        &self,
+        oui: u64,
+        timestamp: u64,
+        signer: &[u8],
+        request: &R,
+    ) -> Result<PublicKeyBinary, Status> {
+        verify_timestamp(timestamp, Utc::now(), self.max_request_skew)?;
+        let signer = PublicKey::try_from(signer)
+            .map_err(|_| Status::invalid_argument("invalid signer public key"))?;
+        let org = self.orgs.org(oui).await.map_err(|err| match err {
+            ConfigServerError::NotFound(oui) => Status::not_found(format!("oui: {oui}")),
+            err => {
+                tracing::error!(oui, ?err, "org lookup failed");
+                Status::internal("org lookup failed")
+            }
+        })?;
+        let signer_bytes: Vec<u8> = signer.clone().into();
+        let is_org_key = signer_bytes == org.owner || signer_bytes == org.payer;
+        if !is_org_key || request.verify(&signer).is_err() {
+            return Err(Status::permission_denied("unauthorized request signature"));
+        }
+        Ok(org.payer.into())
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl proto::payer_ledger_server::PayerLedger for LedgerService {
+    async fn entries(&self, request: Request<PayerLedgerReqV1>) -> GrpcResult<PayerLedgerResV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "entries");
+
+        let payer = self
+            .authorized_payer(request.oui, request.timestamp, &request.signer, &request)
+            .await?;
+        let period = period(request.start, request.end)?;
+        let entries = payer_ledger::fetch(&self.pool, &payer, &period, self.limit(request.limit))
+            .await
+            .map_err(|err| {
+                tracing::error!(?err, "payer ledger query failed");
+                Status::internal("payer ledger query failed")
+            })?;
+
+        let mut resp = PayerLedgerResV1 {
+            payer: payer.into(),
+            entries: entries.into_iter().map(Into::into).collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+
+    async fn burns(&self, request: Request<PayerBurnsReqV1>) -> GrpcResult<PayerBurnsResV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "burns");
+
+        let payer = self
+            .authorized_payer(request.oui, request.timestamp, &request.signer, &request)
+            .await?;
+        let balance = self
+            .balances
+            .lock()
+            .await
+            .get(&payer)
+            .map(|balance| balance.balance);
+        let burns = pending_burns::payer_burns(&self.pool, &payer)
+            .await
+            .map_err(|err| {
+                tracing::error!(?err, "pending burns query failed");
+                Status::internal("pending burns query failed")
+            })?;
+        let (last_burn_signature, last_burn_at) = match burns.last_burn {
+            Some((signature, burned_at)) => (signature, burned_at.encode_timestamp_millis()),
+            None => (String::new(), 0),
+        };
+
+        let mut resp = PayerBurnsResV1 {
+            payer: payer.into(),
+            balance,
+            pending_burn_amount: burns.pending_amount,
+            last_burn_signature,
+            last_burn_at,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+
+        Ok(Response::new(resp))
+    }
+}
+
+/// Requests are signed over their timestamp, in seconds, which must be
+/// within `max_skew` of `now`
+fn verify_timestamp(timestamp: u64, now: DateTime<Utc>, max_skew: Duration) -> Result<(), Status> {
+    let timestamp = Utc
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .ok_or_else(|| Status::invalid_argument("invalid timestamp"))?;
+    if timestamp < now - max_skew || timestamp > now + max_skew {
+        return Err(Status::invalid_argument(format!(
+            "request timestamp {timestamp} more than {}s from now",
+            max_skew.num_seconds()
+        )));
+    }
+    Ok(())
+}
+
+fn period(start: u64, end: u64) -> Result<Range<DateTime<Utc>>, Status> {
+    let to_datetime = |secs: u64| {
+        Utc.timestamp_opt(secs as i64, 0)
+            .single()
+            .ok_or_else(|| Status::invalid_argument("invalid timestamp"))
+    };
+    let period = to_datetime(start)?..to_datetime(end)?;
+    if period.is_empty() {
+        return Err(Status::invalid_argument("start must be before end"));
+    }
+    Ok(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_and_future_requests_are_rejected() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let skew = Duration::minutes(5);
+        let at = |offset: i64| (now.timestamp() + offset) as u64;
+        assert!(verify_timestamp(at(0), now, skew).is_ok());
+        assert!(verify_timestamp(at(-300), now, skew).is_ok());
+        assert!(verify_timestamp(at(300), now, skew).is_ok());
+        assert!(verify_timestamp(at(-301), now, skew).is_err());
+        assert!(verify_timestamp(at(301), now, skew).is_err());
+        assert!(verify_timestamp(0, now, skew).is_err());
+        assert!(verify_timestamp(u64::MAX, now, skew).is_err());
+    }
+
+    #[test]
+    fn period_must_not_be_empty() {
+        assert!(period(10, 20).is_ok());
+        assert!(period(20, 20).is_err());
+        assert!(period(20, 10).is_err());
+        assert!(period(u64::MAX, u64::MAX).is_err());
+    }
+}
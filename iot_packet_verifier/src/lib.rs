@@ -1,6 +1,13 @@
-pub mod balances;
-pub mod burner;
+pub mod balance_reconciler;
+pub mod balance_subscriptions;
+pub mod balance_warnings;
 pub mod daemon;
-pub mod pending_burns;
+pub mod disable_hysteresis;
+pub mod ledger_service;
+pub mod minimum_balances;
+pub mod org_payers;
+pub mod payer_ledger;
+pub mod pricing;
+pub mod proto;
 pub mod settings;
 pub mod verifier;
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use clap::Parser;
-use iot_packet_verifier::{daemon, settings::Settings};
+use iot_packet_verifier::{daemon, payer_ledger, settings::Settings};
 use std::path::PathBuf;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -32,12 +32,23 @@ impl Cli {
 #[derive(clap::Subcommand)]
 pub enum Cmd {
     Server(daemon::Cmd),
+    /// Inspect and export the payer ledger
+    #[clap(subcommand)]
+    Ledger(payer_ledger::Cmd),
 }
 
 impl Cmd {
     async fn run(self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Ledger(cmd) => {
+                let (_shutdown_trigger, shutdown) = triggered::trigger();
+                let (pool, _db_join_handle) = settings
+                    .database
+                    .connect(env!("CARGO_PKG_NAME"), shutdown)
+                    .await?;
+                cmd.run(&pool).await
+            }
         }
     }
 }
@@ -0,0 +1,53 @@
+//! Balance the payer of an org must keep for the org to stay enabled, giving
+//! owners time to top up before their packets are refused.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Override {
+    pub oui: u64,
+    pub minimum_allowed_balance: u64,
+}
+
+/// Minimum allowed balance of every org, `minimum_allowed_balance` unless
+/// overridden for it by `minimum_allowed_balance_overrides`
+#[derive(Debug, Clone, Default)]
+pub struct MinimumBalances {
+    default: u64,
+    overrides: HashMap<u64, u64>,
+}
+
+impl MinimumBalances {
+    pub fn new(default: u64) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    pub fn from_settings(settings: &crate::settings::Settings) -> Self {
+        Self {
+            default: settings.minimum_allowed_balance,
+            overrides: settings
+                .minimum_allowed_balance_overrides
+                .iter()
+                .map(
+                    |Override {
+                         oui,
+                         minimum_allowed_balance,
+                     }| (*oui, *minimum_allowed_balance),
+                )
+                .collect(),
+        }
+    }
+
+    pub fn with_override(mut self, oui: u64, minimum_allowed_balance: u64) -> Self {
+        self.overrides.insert(oui, minimum_allowed_balance);
+        self
+    }
+
+    pub fn for_org(&self, oui: u64) -> u64 {
+        self.overrides.get(&oui).copied().unwrap_or(self.default)
+    }
+}
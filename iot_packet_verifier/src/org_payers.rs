@@ -0,0 +1,109 @@
+//! Org payers updated from the payer change stream of the config service, so
+//! a change takes effect for the very next packet rather than the next file.
+
+use crate::verifier::{ConfigServer, ConfigServerError, Org};
+use async_trait::async_trait;
+use futures::StreamExt;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::iot_config::OrgV1;
+use iot_config::client::OrgClient;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, RwLock};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct CachedOrgClient {
+    client: Arc<Mutex<OrgClient>>,
+    payers: Arc<RwLock<HashMap<u64, PublicKeyBinary>>>,
+}
+
+impl CachedOrgClient {
+    pub fn new(client: OrgClient) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            payers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Follow the payer change stream until shutdown, reconnecting with
+    /// backoff when it fails
+    pub async fn run(self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting org payer change listener");
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            let result = tokio::select! {
+                _ = shutdown.clone() => break,
+                result = self.follow_changes(&mut backoff) => result,
+            };
+            if let Err(err) = result {
+                tracing::warn!(?err, "payer stream failed, reconnecting in {backoff:?}");
+            }
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = tokio::time::sleep(backoff) => (),
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        tracing::info!("stopping org payer change listener");
+        Ok(())
+    }
+
+    /// The current org `oui` as known to the config service, bypassing the
+    /// payer cache
+    pub async fn org(&self, oui: u64) -> Result<OrgV1, ConfigServerError> {
+        self.client
+            .lock()
+            .await
+            .get(oui)
+            .await?
+            .org
+            .ok_or(ConfigServerError::NotFound(oui))
+    }
+
+    async fn follow_changes(&self, backoff: &mut Duration) -> Result<(), ConfigServerError> {
+        let mut changes = self.client.lock().await.payer_stream().await?;
+        *backoff = INITIAL_BACKOFF;
+        // changes may have been missed while disconnected; payers are
+        // fetched again on demand
+        self.payers.write().await.clear();
+        while let Some(change) = changes.next().await {
+            let change = change?;
+            let new_payer = PublicKeyBinary::from(change.new_payer);
+            tracing::info!(oui = change.oui, %new_payer, "org payer changed");
+            self.payers.write().await.insert(change.oui, new_payer);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConfigServer for CachedOrgClient {
+    type Error = ConfigServerError;
+
+    async fn fetch_org(
+        &self,
+        oui: u64,
+        cache: &mut HashMap<u64, PublicKeyBinary>,
+    ) -> Result<PublicKeyBinary, Self::Error> {
+        if let Some(payer) = self.payers.read().await.get(&oui) {
+            return Ok(payer.clone());
+        }
+        let payer = self.client.fetch_org(oui, cache).await?;
+        self.payers.write().await.insert(oui, payer.clone());
+        Ok(payer)
+    }
+
+    async fn disable_org(&self, oui: u64) -> Result<(), Self::Error> {
+        self.client.disable_org(oui).await
+    }
+
+    async fn enable_org(&self, oui: u64) -> Result<(), Self::Error> {
+        self.client.enable_org(oui).await
+    }
+
+    async fn list_orgs(&self) -> Result<Vec<Org>, Self::Error> {
+        self.client.list_orgs().await
+    }
+}
@@ -0,0 +1,217 @@
+//! History of the DC accounting of every payer in the `payer_ledger` table,
+//! kept so orgs can reconcile their spend against ours.
+
+use crate::{proto::PayerLedgerEntryV1, verifier::OrgDebits};
+use chrono::{DateTime, Utc};
+use file_store::traits::TimestampEncode;
+use futures::{Stream, StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use prost::Message;
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Postgres};
+use std::{
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(sqlx::Type, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "payer_ledger_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerKind {
+    /// DC debited from a payer for the valid packets of one org in one report
+    /// file, recorded with the pending burn
+    Debit,
+    /// A burn confirmed on chain, settling that amount of pending debits
+    Credit,
+    /// A payer balance fetched from the solana chain by the balance cache,
+    /// see [`dc_ledger::payer_ledger`]
+    BalanceRefresh,
+}
+
+impl LedgerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debit => "debit",
+            Self::Credit => "credit",
+            Self::BalanceRefresh => "balance_refresh",
+        }
+    }
+}
+
+#[derive(FromRow, Serialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub payer: PublicKeyBinary,
+    pub kind: LedgerKind,
+    /// DC debited or burned, zero for balance refreshes
+    pub amount: i64,
+    /// Balance remaining after a debit or fetched by a balance refresh
+    pub balance: Option<i64>,
+    /// Org debited, only set for debits
+    pub oui: Option<i64>,
+    /// Report file a debit was made for
+    pub reference: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Record the debits made against an org while verifying the report file
+/// `reference`
+pub async fn record_debit(
+    exec: impl sqlx::PgExecutor<'_>,
+    oui: u64,
+    debits: &OrgDebits,
+    reference: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO payer_ledger (payer, kind, amount, balance, oui, reference)
+        VALUES ($1, 'debit', $2, $3, $4, $5)
+        "#,
+    )
+    .bind(&debits.payer)
+    .bind(debits.debited as i64)
+    .bind(debits.remaining_balance as i64)
+    .bind(oui as i64)
+    .bind(reference)
+    .execute(exec)
+    .await?;
+    Ok(())
+}
+
+/// Entries of `payer` recorded within `period`, oldest first
+pub async fn fetch(
+    exec: impl sqlx::PgExecutor<'_>,
+    payer: &PublicKeyBinary,
+    period: &Range<DateTime<Utc>>,
+    limit: u32,
+) -> Result<Vec<LedgerEntry>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT * FROM payer_ledger
+        WHERE payer = $1 AND recorded_at >= $2 AND recorded_at < $3
+        ORDER BY recorded_at, id
+        LIMIT $4
+        "#,
+    )
+    .bind(payer)
+    .bind(period.start)
+    .bind(period.end)
+    .bind(limit as i64)
+    .fetch_all(exec)
+    .await
+}
+
+/// All entries of `payer` recorded within `period`, oldest first
+pub fn stream<'a>(
+    pool: &'a Pool<Postgres>,
+    payer: &PublicKeyBinary,
+    period: &Range<DateTime<Utc>>,
+) -> impl Stream<Item = Result<LedgerEntry, sqlx::Error>> + 'a {
+    sqlx::query_as(
+        r#"
+        SELECT * FROM payer_ledger
+        WHERE payer = $1 AND recorded_at >= $2 AND recorded_at < $3
+        ORDER BY recorded_at, id
+        "#,
+    )
+    .bind(payer.clone())
+    .bind(period.start)
+    .bind(period.end)
+    .fetch(pool)
+}
+
+impl From<LedgerEntry> for PayerLedgerEntryV1 {
+    fn from(entry: LedgerEntry) -> Self {
+        Self {
+            id: entry.id as u64,
+            payer: entry.payer.into(),
+            kind: entry.kind.as_str().to_string(),
+            amount: entry.amount as u64,
+            balance: entry.balance.map(|balance| balance as u64),
+            oui: entry.oui.map(|oui| oui as u64),
+            reference: entry.reference.unwrap_or_default(),
+            recorded_at: entry.recorded_at.encode_timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// Length delimited `PayerLedgerEntryV1` messages
+    Proto,
+}
+
+/// Command line access to the payer ledger
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// Export the entries of a payer recorded within `start..end`
+    Export {
+        payer: PublicKeyBinary,
+        #[clap(long)]
+        start: DateTime<Utc>,
+        #[clap(long)]
+        end: DateTime<Utc>,
+        #[clap(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// File to write to, stdout if not given
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+        match self {
+            Self::Export {
+                payer,
+                start,
+                end,
+                format,
+                output,
+            } => {
+                let writer: Box<dyn Write> = match output {
+                    Some(path) => Box::new(create_file(path)?),
+                    None => Box::new(std::io::stdout().lock()),
+                };
+                let entries = stream(pool, payer, &(*start..*end)).map_err(anyhow::Error::from);
+                let count = export(entries, *format, writer).await?;
+                tracing::info!(%payer, count, "exported payer ledger");
+                Ok(())
+            }
+        }
+    }
+}
+
+fn create_file(path: &Path) -> std::io::Result<std::io::BufWriter<std::fs::File>> {
+    Ok(std::io::BufWriter::new(std::fs::File::create(path)?))
+}
+
+async fn export(
+    entries: impl Stream<Item = anyhow::Result<LedgerEntry>>,
+    format: ExportFormat,
+    mut writer: impl Write,
+) -> anyhow::Result<usize> {
+    tokio::pin!(entries);
+    let mut count = 0;
+    match format {
+        ExportFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            while let Some(entry) = entries.next().await {
+                csv.serialize(entry?)?;
+                count += 1;
+            }
+            csv.flush()?;
+        }
+        ExportFormat::Proto => {
+            while let Some(entry) = entries.next().await {
+                let entry = PayerLedgerEntryV1::from(entry?);
+                writer.write_all(&entry.encode_length_delimited_to_vec())?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(count)
+}
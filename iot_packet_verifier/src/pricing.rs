@@ -0,0 +1,278 @@
+//! Pricing of packets in DC, by default one DC per started 24 bytes of
+//! payload with a minimum of one DC.
+
+use file_store::iot_packet::PacketRouterPacketReport;
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub const BYTES_PER_DC: u64 = 24;
+pub const DEFAULT_RULE: &str = "default";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// Pricing rules in order of precedence. Default is none, pricing every
+    /// packet at one DC per 24 bytes
+    #[serde(default)]
+    pub rules: Vec<RuleSettings>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RuleSettings {
+    /// Name of the rule, recorded in the packet price reports
+    pub name: String,
+    /// Regions the rule applies to by name, ie "EU868". Default is all
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// Smallest payload size in bytes the rule applies to. Default is 0
+    #[serde(default)]
+    pub min_payload_size: u32,
+    /// Largest payload size in bytes the rule applies to. Default is no limit
+    #[serde(default = "default_max_payload_size")]
+    pub max_payload_size: u32,
+    /// Ouis of the routes the rule applies to. Default is all
+    #[serde(default)]
+    pub ouis: Vec<u64>,
+    /// Net ids of the routes the rule applies to. Default is all
+    #[serde(default)]
+    pub net_ids: Vec<u32>,
+    /// Bytes of payload charged as one unit, smaller payloads are charged one
+    /// unit. Default is 24
+    #[serde(default = "default_bytes_per_unit")]
+    pub bytes_per_unit: u64,
+    /// DC charged per started unit, must be positive so no packet is free.
+    /// Default is 1
+    #[serde(default = "default_dc_per_unit")]
+    pub dc_per_unit: u64,
+}
+
+fn default_max_payload_size() -> u32 {
+    u32::MAX
+}
+
+fn default_bytes_per_unit() -> u64 {
+    BYTES_PER_DC
+}
+
+fn default_dc_per_unit() -> u64 {
+    1
+}
+
+/// The price of a packet and the rule it was priced by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Price {
+    pub rule: Arc<str>,
+    pub bytes_per_unit: u64,
+    pub dc_per_unit: u64,
+    pub dc: u64,
+}
+
+pub trait PricingPolicy {
+    fn price(&self, report: &PacketRouterPacketReport) -> Price;
+}
+
+/// One DC per started 24 bytes for every packet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultPricing;
+
+impl PricingPolicy for DefaultPricing {
+    fn price(&self, report: &PacketRouterPacketReport) -> Price {
+        Price {
+            rule: DEFAULT_RULE.into(),
+            bytes_per_unit: BYTES_PER_DC,
+            dc_per_unit: 1,
+            dc: payload_size_to_dc(report.payload_size as u64),
+        }
+    }
+}
+
+pub fn payload_size_to_dc(payload_size: u64) -> u64 {
+    units(payload_size, BYTES_PER_DC)
+}
+
+fn units(payload_size: u64, bytes_per_unit: u64) -> u64 {
+    let payload_size = payload_size.max(bytes_per_unit);
+    // Integer div/ceil from: https://stackoverflow.com/a/2745086
+    (payload_size + bytes_per_unit - 1) / bytes_per_unit
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PricingError {
+    #[error("pricing rule {0}: bytes_per_unit must be positive")]
+    ZeroBytesPerUnit(String),
+    #[error("pricing rule {0}: dc_per_unit must be positive")]
+    ZeroDcPerUnit(String),
+    #[error("pricing rule {0}: min_payload_size above max_payload_size")]
+    EmptyPayloadSizeBand(String),
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    name: Arc<str>,
+    regions: Vec<String>,
+    min_payload_size: u32,
+    max_payload_size: u32,
+    ouis: Vec<u64>,
+    net_ids: Vec<u32>,
+    bytes_per_unit: u64,
+    dc_per_unit: u64,
+}
+
+impl Rule {
+    fn matches(&self, report: &PacketRouterPacketReport) -> bool {
+        (self.min_payload_size..=self.max_payload_size).contains(&report.payload_size)
+            && (self.regions.is_empty()
+                || self
+                    .regions
+                    .iter()
+                    .any(|region| region == report.region.as_str_name()))
+            && (self.ouis.is_empty() || self.ouis.contains(&report.oui))
+            && (self.net_ids.is_empty() || self.net_ids.contains(&report.net_id))
+    }
+}
+
+/// Prices packets by the configured rules, falling back to [`DefaultPricing`].
+/// Rules match packets by payload size band, region and route, the oui and
+/// net id a packet is routed to, and are tried in order with the first match
+/// pricing the packet
+#[derive(Debug, Clone, Default)]
+pub struct RulePricing {
+    rules: Vec<Rule>,
+}
+
+impl RulePricing {
+    pub fn from_settings(settings: &Settings) -> Result<Self, PricingError> {
+        let rules = settings
+            .rules
+            .iter()
+            .map(|rule| {
+                if rule.bytes_per_unit == 0 {
+                    return Err(PricingError::ZeroBytesPerUnit(rule.name.clone()));
+                }
+                if rule.dc_per_unit == 0 {
+                    return Err(PricingError::ZeroDcPerUnit(rule.name.clone()));
+                }
+                if rule.min_payload_size > rule.max_payload_size {
+                    return Err(PricingError::EmptyPayloadSizeBand(rule.name.clone()));
+                }
+                Ok(Rule {
+                    name: rule.name.as_str().into(),
+                    regions: rule.regions.iter().map(|r| r.to_uppercase()).collect(),
+                    min_payload_size: rule.min_payload_size,
+                    max_payload_size: rule.max_payload_size,
+                    ouis: rule.ouis.clone(),
+                    net_ids: rule.net_ids.clone(),
+                    bytes_per_unit: rule.bytes_per_unit,
+                    dc_per_unit: rule.dc_per_unit,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+}
+
+impl PricingPolicy for RulePricing {
+    fn price(&self, report: &PacketRouterPacketReport) -> Price {
+        match self.rules.iter().find(|rule| rule.matches(report)) {
+            Some(rule) => Price {
+                rule: rule.name.clone(),
+                bytes_per_unit: rule.bytes_per_unit,
+                dc_per_unit: rule.dc_per_unit,
+                dc: units(report.payload_size as u64, rule.bytes_per_unit) * rule.dc_per_unit,
+            },
+            None => DefaultPricing.price(report),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use helium_crypto::PublicKeyBinary;
+    use helium_proto::{DataRate, Region};
+
+    fn report(region: Region, net_id: u32, payload_size: u32) -> PacketRouterPacketReport {
+        PacketRouterPacketReport {
+            oui: 1,
+            net_id,
+            rssi: 0,
+            frequency: 0,
+            snr: 0.0,
+            data_rate: DataRate::Sf12bw125,
+            region,
+            gateway: PublicKeyBinary::from(vec![]),
+            payload_hash: vec![],
+            payload_size,
+            received_timestamp: Utc::now(),
+        }
+    }
+
+    fn rule(name: &str) -> RuleSettings {
+        RuleSettings {
+            name: name.to_string(),
+            regions: vec![],
+            min_payload_size: 0,
+            max_payload_size: default_max_payload_size(),
+            ouis: vec![],
+            net_ids: vec![],
+            bytes_per_unit: default_bytes_per_unit(),
+            dc_per_unit: default_dc_per_unit(),
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_prices_the_packet() {
+        let settings = Settings {
+            rules: vec![
+                RuleSettings {
+                    regions: vec!["eu868".to_string()],
+                    min_payload_size: 25,
+                    dc_per_unit: 2,
+                    ..rule("eu-large")
+                },
+                RuleSettings {
+                    net_ids: vec![0xC00053],
+                    bytes_per_unit: 12,
+                    ..rule("roaming")
+                },
+            ],
+        };
+        let pricing = RulePricing::from_settings(&settings).unwrap();
+
+        let price = pricing.price(&report(Region::Eu868, 0xC00053, 48));
+        assert_eq!(("eu-large", 4), (price.rule.as_ref(), price.dc));
+        let price = pricing.price(&report(Region::Eu868, 0xC00053, 24));
+        assert_eq!(("roaming", 2), (price.rule.as_ref(), price.dc));
+        let price = pricing.price(&report(Region::Us915, 0, 48));
+        assert_eq!((DEFAULT_RULE, 2), (price.rule.as_ref(), price.dc));
+        let price = pricing.price(&report(Region::Us915, 0, 1));
+        assert_eq!((DEFAULT_RULE, 1), (price.rule.as_ref(), price.dc));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let settings = |rule| Settings { rules: vec![rule] };
+        assert!(matches!(
+            RulePricing::from_settings(&settings(RuleSettings {
+                dc_per_unit: 0,
+                ..rule("free")
+            })),
+            Err(PricingError::ZeroDcPerUnit(name)) if name == "free"
+        ));
+        assert!(matches!(
+            RulePricing::from_settings(&settings(RuleSettings {
+                bytes_per_unit: 0,
+                ..rule("unbounded")
+            })),
+            Err(PricingError::ZeroBytesPerUnit(_))
+        ));
+        assert!(matches!(
+            RulePricing::from_settings(&settings(RuleSettings {
+                min_payload_size: 25,
+                max_payload_size: 24,
+                ..rule("empty")
+            })),
+            Err(PricingError::EmptyPayloadSizeBand(_))
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! Messages of the payer ledger service, which are not part of helium-proto.
+//! Timestamps are in milliseconds, but request time bounds in seconds.
+
+use file_store::impl_msg_verify;
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.packet_verifier.PayerLedger.rs"
+));
+
+/// Ledger entries of the payer of org `oui` recorded within `start..end`,
+/// signed by the owner or the payer of the org
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerLedgerReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(uint64, tag = "3")]
+    pub end: u64,
+    /// Max number of entries returned, oldest first. Zero or anything above
+    /// the service limit is capped to the service limit
+    #[prost(uint32, tag = "4")]
+    pub limit: u32,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerLedgerResV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub payer: Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    pub entries: Vec<PayerLedgerEntryV1>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Balance and burns of the payer of org `oui`, signed by the owner or the
+/// payer of the org
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerBurnsReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub oui: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerBurnsResV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub payer: Vec<u8>,
+    /// Balance of the payer in the balance cache, unset when not cached
+    #[prost(uint64, optional, tag = "2")]
+    pub balance: Option<u64>,
+    /// DC debited from the payer and not burned yet, over every token use
+    #[prost(uint64, tag = "3")]
+    pub pending_burn_amount: u64,
+    /// Signature of the last burn transaction finalized, empty when none
+    #[prost(string, tag = "4")]
+    pub last_burn_signature: String,
+    /// When the last burn was finalized, zero when none
+    #[prost(uint64, tag = "5")]
+    pub last_burn_at: u64,
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub signature: Vec<u8>,
+}
+
+/// A single ledger entry, also the record written by the proto export
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PayerLedgerEntryV1 {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payer: Vec<u8>,
+    /// One of `debit`, `credit` or `balance_refresh`
+    #[prost(string, tag = "3")]
+    pub kind: String,
+    #[prost(uint64, tag = "4")]
+    pub amount: u64,
+    #[prost(uint64, optional, tag = "5")]
+    pub balance: Option<u64>,
+    #[prost(uint64, optional, tag = "6")]
+    pub oui: Option<u64>,
+    #[prost(string, tag = "7")]
+    pub reference: String,
+    #[prost(uint64, tag = "8")]
+    pub recorded_at: u64,
+}
+
+impl_msg_verify!(PayerLedgerReqV1, signature);
+impl_msg_verify!(PayerLedgerResV1, signature);
+impl_msg_verify!(PayerBurnsReqV1, signature);
+impl_msg_verify!(PayerBurnsResV1, signature);
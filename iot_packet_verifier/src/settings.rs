@@ -1,5 +1,4 @@
 use chrono::{DateTime, TimeZone, Utc};
-use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 use std::path::Path;
 
@@ -21,9 +20,20 @@ pub struct Settings {
     pub metrics: poc_metrics::Settings,
     #[serde(default)]
     pub enable_solana_integration: bool,
+    /// Log burns instead of sending them to the solana chain, while debiting
+    /// and caching balances as usual. Default is false
+    #[serde(default)]
+    pub dry_run: bool,
     /// Minimum data credit balance required for a payer before we disable them
     #[serde(default = "default_minimum_allowed_balance")]
     pub minimum_allowed_balance: u64,
+    /// Minimum allowed balances of orgs differing from `minimum_allowed_balance`
+    #[serde(default)]
+    pub minimum_allowed_balance_overrides: Vec<crate::minimum_balances::Override>,
+    /// Minimum number of seconds between fetches of the balance of a payer
+    /// running low. Default is 30
+    #[serde(default = "default_balance_refetch_interval")]
+    pub balance_refetch_interval: u64,
     pub solana: Option<solana::Settings>,
     #[serde(default = "default_start_after")]
     pub start_after: u64,
@@ -31,6 +41,23 @@ pub struct Settings {
     /// any disabled orgs.
     #[serde(default = "default_monitor_funds_period")]
     pub monitor_funds_period: u64,
+    /// When orgs below the minimum allowed balance are disabled
+    #[serde(default)]
+    pub disable_hysteresis: crate::disable_hysteresis::Settings,
+    /// Balances of payers pushed over solana account subscriptions
+    #[serde(default)]
+    pub balance_subscriptions: crate::balance_subscriptions::Settings,
+    /// Periodic reconciliation of cached balances with the chain
+    #[serde(default)]
+    pub balance_reconciliation: crate::balance_reconciler::Settings,
+    /// Settings for low balance warnings sent to org owners
+    #[serde(default)]
+    pub balance_warnings: crate::balance_warnings::Settings,
+    /// Rules pricing packets in DC
+    #[serde(default)]
+    pub pricing: crate::pricing::Settings,
+    /// Signed api serving the payer ledger to orgs, disabled when not set
+    pub ledger_api: Option<crate::ledger_service::Settings>,
 }
 
 pub fn default_start_after() -> u64 {
@@ -53,6 +80,10 @@ pub fn default_monitor_funds_period() -> u64 {
     30
 }
 
+pub fn default_balance_refetch_interval() -> u64 {
+    30
+}
+
 impl Settings {
     /// Load Settings from a given path. Settings are loaded from a given
     /// optional path and can be overriden with environment variables.
@@ -60,20 +91,8 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "VERIFY_". For example
     /// "VERIFY_DATABASE_URL" will override the data base url.
-    pub fn new(path: Option<impl AsRef<Path>>) -> Result<Self, ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of VERIFY)
-        // Eg.. `INJECT_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("PACKET_VERIFY").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new(path: Option<impl AsRef<Path>>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "PACKET_VERIFY")
     }
 
     pub fn start_after(&self) -> DateTime<Utc> {
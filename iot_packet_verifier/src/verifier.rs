@@ -1,7 +1,15 @@
-use crate::pending_burns::PendingBurns;
+use crate::{
+    disable_hysteresis::DisableHysteresis, minimum_balances::MinimumBalances,
+    pricing::PricingPolicy,
+};
 use async_trait::async_trait;
+use dc_ledger::{
+    balances::Debiter,
+    pending_burns::{PendingBurns, TokenUse},
+};
 use file_store::{
-    file_sink::FileSinkClient, iot_packet::PacketRouterPacketReport, traits::MsgTimestamp,
+    file_sink::FileSinkClient, iot_packet::PacketRouterPacketReport,
+    iot_packet_price::PacketPriceV1, traits::MsgTimestamp,
 };
 use futures::{Stream, StreamExt};
 use helium_crypto::PublicKeyBinary;
@@ -9,8 +17,7 @@ use helium_proto::services::packet_verifier::{InvalidPacket, InvalidPacketReason
 use iot_config::client::{ClientError, OrgClient};
 use solana::SolanaNetwork;
 use std::{
-    collections::{hash_map::Entry, HashMap},
-    convert::Infallible,
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt::Debug,
     sync::Arc,
 };
@@ -20,13 +27,25 @@ use tokio::{
     time::{sleep_until, Duration, Instant},
 };
 
-pub struct Verifier<D, C> {
+pub struct Verifier<D, C, P> {
     pub debiter: D,
     pub config_server: C,
+    pub pricing: P,
+    pub disable_hysteresis: DisableHysteresis,
+}
+
+/// Debits made against an org while verifying a stream of reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrgDebits {
+    pub payer: PublicKeyBinary,
+    /// Total DC debited across all valid packets
+    pub debited: u64,
+    /// Balance remaining after the last debit
+    pub remaining_balance: u64,
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum VerificationError<DE, CE, BE, VPE, IPE> {
+pub enum VerificationError<DE, CE, BE, VPE, IPE, PPE> {
     #[error("Debit error: {0}")]
     DebitError(DE),
     #[error("Config server error: {0}")]
@@ -37,51 +56,81 @@ pub enum VerificationError<DE, CE, BE, VPE, IPE> {
     ValidPacketWriterError(VPE),
     #[error("Invalid packet writer error: {0}")]
     InvalidPacketWriterError(IPE),
+    #[error("Packet price writer error: {0}")]
+    PacketPriceWriterError(PPE),
 }
 
-impl<D, C> Verifier<D, C>
+impl<D, C, P> Verifier<D, C, P>
 where
     D: Debiter,
     C: ConfigServer,
+    P: PricingPolicy,
 {
-    /// Verify a stream of packet reports. Writes out `valid_packets` and `invalid_packets`.
-    pub async fn verify<B, R, VP, IP>(
+    /// Verify a stream of packet reports. Writes out `valid_packets` and `invalid_packets`,
+    /// and the price applied to every valid packet to `packet_prices`.
+    /// Returns the debits made against each org, keyed by oui.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn verify<B, R, VP, IP, PP>(
         &mut self,
-        minimum_allowed_balance: u64,
+        minimum_balances: &MinimumBalances,
         mut pending_burns: B,
         reports: R,
         mut valid_packets: VP,
         mut invalid_packets: IP,
-    ) -> Result<(), VerificationError<D::Error, C::Error, B::Error, VP::Error, IP::Error>>
+        mut packet_prices: PP,
+    ) -> Result<
+        HashMap<u64, OrgDebits>,
+        VerificationError<D::Error, C::Error, B::Error, VP::Error, IP::Error, PP::Error>,
+    >
     where
         B: PendingBurns,
         R: Stream<Item = PacketRouterPacketReport>,
         VP: PacketWriter<ValidPacket>,
         IP: PacketWriter<InvalidPacket>,
+        PP: PacketWriter<PacketPriceV1>,
     {
         let mut org_cache = HashMap::<u64, PublicKeyBinary>::new();
+        let mut debits = HashMap::<u64, OrgDebits>::new();
+        let mut disabled = HashSet::<u64>::new();
 
         tokio::pin!(reports);
 
         while let Some(report) = reports.next().await {
-            let debit_amount = payload_size_to_dc(report.payload_size as u64);
+            let price = self.pricing.price(&report);
+            let debit_amount = price.dc;
 
             let payer = self
                 .config_server
                 .fetch_org(report.oui, &mut org_cache)
                 .await
                 .map_err(VerificationError::ConfigError)?;
+            let received = report.received_timestamp;
+            let minimum_allowed_balance = minimum_balances.for_org(report.oui);
             let remaining_balance = self
                 .debiter
-                .debit_if_sufficient(&payer, debit_amount)
+                .debit_if_sufficient(&payer, debit_amount, minimum_allowed_balance)
                 .await
                 .map_err(VerificationError::DebitError)?;
 
             if let Some(remaining_balance) = remaining_balance {
                 pending_burns
-                    .add_burned_amount(&payer, debit_amount)
+                    .add_burned_amount(&payer, TokenUse::IotPackets, debit_amount)
                     .await
                     .map_err(VerificationError::BurnError)?;
+                packet_prices
+                    .write(PacketPriceV1 {
+                        oui: report.oui,
+                        gateway: report.gateway.clone().into(),
+                        payload_hash: report.payload_hash.clone(),
+                        payload_size: report.payload_size,
+                        rule: price.rule.to_string(),
+                        bytes_per_unit: price.bytes_per_unit,
+                        dc_per_unit: price.dc_per_unit,
+                        num_dcs: debit_amount,
+                        packet_timestamp: report.timestamp(),
+                    })
+                    .await
+                    .map_err(VerificationError::PacketPriceWriterError)?;
                 valid_packets
                     .write(ValidPacket {
                         packet_timestamp: report.timestamp(),
@@ -93,12 +142,13 @@ where
                     .await
                     .map_err(VerificationError::ValidPacketWriterError)?;
 
-                if remaining_balance < minimum_allowed_balance {
-                    self.config_server
-                        .disable_org(report.oui)
-                        .await
-                        .map_err(VerificationError::ConfigError)?;
-                }
+                let org_debits = debits.entry(report.oui).or_insert_with(|| OrgDebits {
+                    payer: payer.clone(),
+                    debited: 0,
+                    remaining_balance,
+                });
+                org_debits.debited += debit_amount;
+                org_debits.remaining_balance = remaining_balance;
             } else {
                 invalid_packets
                     .write(InvalidPacket {
@@ -110,46 +160,24 @@ where
                     .await
                     .map_err(VerificationError::InvalidPacketWriterError)?;
             }
-        }
-
-        Ok(())
-    }
-}
 
-pub const BYTES_PER_DC: u64 = 24;
-
-pub fn payload_size_to_dc(payload_size: u64) -> u64 {
-    let payload_size = payload_size.max(BYTES_PER_DC);
-    // Integer div/ceil from: https://stackoverflow.com/a/2745086
-    (payload_size + BYTES_PER_DC - 1) / BYTES_PER_DC
-}
-
-#[async_trait]
-pub trait Debiter {
-    type Error;
-
-    /// Debit the balance from the account. If the debit was successful,
-    /// return the remaining amount.
-    async fn debit_if_sufficient(
-        &self,
-        payer: &PublicKeyBinary,
-        amount: u64,
-    ) -> Result<Option<u64>, Self::Error>;
-}
-
-#[async_trait]
-impl Debiter for Arc<Mutex<HashMap<PublicKeyBinary, u64>>> {
-    type Error = Infallible;
+            // orgs stay disabled until the funds monitor re-enables them, the
+            // rest of their packets don't disable them again
+            if self.disable_hysteresis.check(
+                report.oui,
+                remaining_balance,
+                minimum_allowed_balance,
+                received,
+            ) && disabled.insert(report.oui)
+            {
+                self.config_server
+                    .disable_org(report.oui)
+                    .await
+                    .map_err(VerificationError::ConfigError)?;
+            }
+        }
 
-    async fn debit_if_sufficient(
-        &self,
-        payer: &PublicKeyBinary,
-        amount: u64,
-    ) -> Result<Option<u64>, Infallible> {
-        let map = self.lock().await;
-        let balance = map.get(payer).unwrap();
-        // Don't debit the amount if we're mocking. That is a job for the burner.
-        Ok((*balance >= amount).then(|| balance.saturating_sub(amount)))
+        Ok(debits)
     }
 }
 
@@ -181,7 +209,7 @@ pub trait ConfigServer: Sized + Send + Sync + 'static {
         self,
         solana: S,
         balances: B,
-        minimum_allowed_balance: u64,
+        minimum_balances: MinimumBalances,
         monitor_period: Duration,
         shutdown: triggered::Listener,
     ) -> Result<(), MonitorError<S::Error, Self::Error>>
@@ -204,7 +232,7 @@ pub trait ConfigServer: Sized + Send + Sync + 'static {
                             .payer_balance(&payer)
                             .await
                             .map_err(MonitorError::SolanaError)?;
-                        if balance >= minimum_allowed_balance {
+                        if balance >= minimum_balances.for_org(oui) {
                             balances.set_balance(&payer, balance).await;
                             self.enable_org(oui)
                                 .await
@@ -233,7 +261,7 @@ pub trait BalanceStore: Send + Sync + 'static {
 }
 
 #[async_trait]
-impl BalanceStore for crate::balances::BalanceStore {
+impl BalanceStore for dc_ledger::balances::BalanceStore {
     async fn set_balance(&self, payer: &PublicKeyBinary, balance: u64) {
         self.lock().await.entry(payer.clone()).or_default().balance = balance;
     }
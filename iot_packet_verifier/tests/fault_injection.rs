@@ -0,0 +1,144 @@
+//! Burns of the packet verifier against a database timing out, only built
+//! with the `fault-injection` feature.
+
+use async_trait::async_trait;
+use dc_ledger::{
+    balances::BalanceCache,
+    burner::Burner,
+    pending_burns::{PendingBurns, TokenUse},
+};
+use helium_crypto::PublicKeyBinary;
+use solana::{BurnStatus, BurnTransaction, SignedBurn, SolanaNetwork};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Mutex;
+
+/// Attempts made at an operation before giving up on it
+const ATTEMPTS: usize = 100;
+
+/// Await `$op` until it succeeds, evaluating it again after every failure
+macro_rules! retry {
+    ($op:expr) => {{
+        let mut attempt = 0;
+        loop {
+            match $op.await {
+                Ok(value) => break value,
+                Err(err) => {
+                    attempt += 1;
+                    assert!(attempt < ATTEMPTS, "still failing: {err}");
+                }
+            }
+        }
+    }};
+}
+
+/// Network signing burns which finalize as soon as they are sent
+#[derive(Clone, Default)]
+struct SigningNetwork {
+    signed: Arc<AtomicUsize>,
+    sent: Arc<Mutex<Vec<BurnTransaction>>>,
+}
+
+#[async_trait]
+impl SolanaNetwork for SigningNetwork {
+    type Error = Infallible;
+
+    async fn payer_balance(&self, _payer: &PublicKeyBinary) -> Result<u64, Self::Error> {
+        Ok(1_000_000)
+    }
+
+    async fn burn_data_credits(
+        &self,
+        _payer: &PublicKeyBinary,
+        _amount: u64,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error> {
+        let signature = format!("burn-{}", self.signed.fetch_add(1, Ordering::SeqCst));
+        Ok(SignedBurn::unsent(
+            payer,
+            BurnTransaction {
+                signature,
+                amount,
+                last_valid_block_height: 0,
+            },
+        ))
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error> {
+        self.sent.lock().await.push(burn.burn.clone());
+        Ok(())
+    }
+
+    async fn burn_status(&self, _burn: &BurnTransaction) -> Result<BurnStatus, Self::Error> {
+        Ok(BurnStatus::Finalized)
+    }
+}
+
+#[sqlx::test]
+async fn test_burns_are_sent_once_despite_db_timeouts(
+    pool_options: PgPoolOptions,
+    connect_options: PgConnectOptions,
+) {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let mut pool = pool_options
+        .clone()
+        .connect_with(connect_options.clone())
+        .await
+        .unwrap();
+    // Every other connection reused times out:
+    let mut faulty_pool = db_store::fault_injection::Settings {
+        timeout_rate: 0.5,
+        acquire_timeout: Some(1),
+    }
+    .apply(pool_options)
+    .connect_with(connect_options)
+    .await
+    .unwrap();
+
+    retry!(faulty_pool.add_burned_amount(&payer, TokenUse::IotPackets, 20_000));
+    retry!(faulty_pool.add_burned_amount(&payer, TokenUse::IotPackets, 5_000));
+
+    let solana = SigningNetwork::default();
+    let balance_cache = retry!(BalanceCache::new(&mut faulty_pool, solana.clone()));
+    let mut burner = Burner::new(
+        faulty_pool.clone(),
+        &balance_cache,
+        TokenUse::IotPackets,
+        0,
+        solana.clone(),
+    );
+
+    // A burn failing to settle after being sent is tracked to its
+    // finalization by the next attempt rather than sent again:
+    let settlement = retry!(burner.burn()).expect("settled burn");
+    assert_eq!((settlement.amount, settlement.remaining), (25_000, 0));
+    assert!(retry!(burner.burn()).is_none());
+
+    let sent = solana.sent.lock().await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].amount, 25_000);
+    assert_eq!(
+        pool.fetch_burn_transaction(&payer, TokenUse::IotPackets)
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(pool
+        .fetch_next(TokenUse::IotPackets, 1)
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -1,7 +1,12 @@
 use async_trait::async_trait;
 use chrono::{TimeZone, Utc};
+use dc_ledger::{
+    balances::{Balance, BalanceCache, Debiter},
+    burner::Burner,
+    pending_burns::{Burn, PendingBurns, TokenUse},
+};
 use file_store::iot_packet::PacketRouterPacketReport;
-use futures::{Stream, StreamExt};
+use futures::{stream::BoxStream, Stream, StreamExt};
 use futures_util::stream;
 use helium_crypto::PublicKeyBinary;
 use helium_proto::{
@@ -9,12 +14,26 @@ use helium_proto::{
     DataRate, Region,
 };
 use iot_packet_verifier::{
-    balances::BalanceCache,
-    burner::Burner,
-    pending_burns::{Burn, PendingBurns},
-    verifier::{payload_size_to_dc, ConfigServer, Debiter, Org, Verifier, BYTES_PER_DC},
+    balance_reconciler::reconcile_balance,
+    balance_subscriptions::{self, BalanceSubscriptions},
+    balance_warnings::{self, SpendTracker},
+    disable_hysteresis::{self, DisableHysteresis},
+    minimum_balances::MinimumBalances,
+    pricing::{payload_size_to_dc, DefaultPricing, BYTES_PER_DC, DEFAULT_RULE},
+    verifier::{ConfigServer, Org, OrgDebits, Verifier},
+};
+use solana::{
+    payer_subscriptions::{Subscribe, SubscriptionNetwork},
+    BurnStatus, BurnTransaction, SignedBurn, SolanaNetwork,
+};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
 };
-use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 
 struct MockConfig {
@@ -87,6 +106,7 @@ impl Debiter for InstantBurnedBalance {
         &self,
         payer: &PublicKeyBinary,
         amount: u64,
+        _minimum_allowed_balance: u64,
     ) -> Result<Option<u64>, ()> {
         let map = self.0.lock().await;
         let balance = map.get(payer).unwrap();
@@ -105,21 +125,27 @@ impl PendingBurns for InstantBurnedBalance {
         stream::iter(std::iter::empty()).boxed()
     }
 
-    async fn fetch_next(&mut self) -> Result<Option<Burn>, Self::Error> {
+    async fn fetch_next(
+        &mut self,
+        _token_use: TokenUse,
+        _threshold: u64,
+    ) -> Result<Option<Burn>, Self::Error> {
         Ok(None)
     }
 
     async fn subtract_burned_amount(
         &mut self,
         _payer: &PublicKeyBinary,
+        _token_use: TokenUse,
         _amount: u64,
-    ) -> Result<(), Self::Error> {
-        Ok(())
+    ) -> Result<u64, Self::Error> {
+        Ok(0)
     }
 
     async fn add_burned_amount(
         &mut self,
         payer: &PublicKeyBinary,
+        _token_use: TokenUse,
         amount: u64,
     ) -> Result<(), Self::Error> {
         let mut map = self.0.lock().await;
@@ -127,6 +153,23 @@ impl PendingBurns for InstantBurnedBalance {
         *balance -= amount;
         Ok(())
     }
+
+    async fn fetch_burn_transaction(
+        &mut self,
+        _payer: &PublicKeyBinary,
+        _token_use: TokenUse,
+    ) -> Result<Option<BurnTransaction>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn record_burn_transaction(
+        &mut self,
+        _payer: &PublicKeyBinary,
+        _token_use: TokenUse,
+        _transaction: Option<&BurnTransaction>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 fn packet_report(
@@ -187,12 +230,15 @@ async fn test_config_unlocking() {
     let mut verifier = Verifier {
         debiter: balances.clone(),
         config_server: orgs.clone(),
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::default(),
     };
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             balances.clone(),
             stream::iter(vec![
                 packet_report(0, 0, 24, vec![1]),
@@ -201,6 +247,7 @@ async fn test_config_unlocking() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -226,7 +273,7 @@ async fn test_config_unlocking() {
             .monitor_funds(
                 solana.clone(),
                 balance_cache,
-                1,
+                MinimumBalances::new(1),
                 Duration::from_secs(100),
                 listener,
             )
@@ -250,7 +297,7 @@ async fn test_config_unlocking() {
 
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             balances.clone(),
             stream::iter(vec![
                 packet_report(0, 0, 24, vec![1]),
@@ -259,6 +306,7 @@ async fn test_config_unlocking() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -303,20 +351,24 @@ async fn test_verifier() {
     // Set up output:
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
     // Set up verifier:
     let mut verifier = Verifier {
         debiter: balances.clone(),
         config_server: orgs,
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::default(),
     };
 
     // Run the verifier:
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             balances.clone(),
             stream::iter(packets),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -339,6 +391,19 @@ async fn test_verifier() {
 
     assert_eq!(invalid_packets, vec![invalid_packet(1, vec![3]),]);
 
+    // Every valid packet is priced, by the default rule:
+    assert!(packet_prices.iter().all(|price| price.rule == DEFAULT_RULE));
+    assert_eq!(
+        packet_prices
+            .iter()
+            .map(|price| (price.payload_hash.clone(), price.num_dcs))
+            .collect::<Vec<_>>(),
+        valid_packets
+            .iter()
+            .map(|packet| (packet.payload_hash.clone(), packet.num_dcs as u64))
+            .collect::<Vec<_>>()
+    );
+
     // Verify that only org #0 is disabled:
     let payers = verifier.config_server.payers.lock().await;
     assert!(!payers.get(&0).unwrap().enabled);
@@ -346,13 +411,156 @@ async fn test_verifier() {
     assert!(payers.get(&2).unwrap().enabled);
 }
 
+#[tokio::test]
+async fn test_disable_hysteresis() {
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert(0_u64, PublicKeyBinary::from(vec![0])).await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(PublicKeyBinary::from(vec![0]), 5);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    // Set up verifier, disabling after three packets below the minimum
+    // spanning at least a minute:
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs,
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::new(&disable_hysteresis::Settings {
+            consecutive_checks: 3,
+            min_insufficient_duration: Duration::from_secs(60),
+            ..Default::default()
+        }),
+    };
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
+
+    // Every packet leaves less than the minimum balance of 10, three within
+    // a minute don't disable the org:
+    verifier
+        .verify(
+            &MinimumBalances::new(10),
+            balances.clone(),
+            stream::iter(vec![
+                packet_report(0, 0, 1, vec![1]),
+                packet_report(0, 10, 1, vec![2]),
+                packet_report(0, 20, 1, vec![3]),
+            ]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            &mut packet_prices,
+        )
+        .await
+        .unwrap();
+    assert!(verifier.config_server.payers.lock().await[&0].enabled);
+
+    // the first of them was over a minute ago by the next:
+    verifier
+        .verify(
+            &MinimumBalances::new(10),
+            balances.clone(),
+            stream::iter(vec![packet_report(0, 61, 1, vec![4])]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            &mut packet_prices,
+        )
+        .await
+        .unwrap();
+    assert!(!verifier.config_server.payers.lock().await[&0].enabled);
+    assert_eq!(valid_packets.len(), 4);
+}
+
+#[tokio::test]
+async fn test_unpaid_packet_disables_without_hysteresis() {
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert(0_u64, PublicKeyBinary::from(vec![0])).await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(PublicKeyBinary::from(vec![0]), 1);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    // Set up verifier, disabling after three packets below the minimum:
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs,
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::new(&disable_hysteresis::Settings {
+            consecutive_checks: 3,
+            ..Default::default()
+        }),
+    };
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
+
+    // A packet costing two DC with one left is not paid for, which disables
+    // the org on the first check:
+    verifier
+        .verify(
+            &MinimumBalances::new(0),
+            balances.clone(),
+            stream::iter(vec![packet_report(0, 0, 2 * BYTES_PER_DC as u32, vec![1])]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            &mut packet_prices,
+        )
+        .await
+        .unwrap();
+    assert!(valid_packets.is_empty());
+    assert_eq!(invalid_packets.len(), 1);
+    assert!(!verifier.config_server.payers.lock().await[&0].enabled);
+}
+
+#[tokio::test]
+async fn test_minimum_balance_overrides() {
+    // Set up orgs:
+    let orgs = MockConfigServer::default();
+    orgs.insert(0_u64, PublicKeyBinary::from(vec![0])).await;
+    orgs.insert(1_u64, PublicKeyBinary::from(vec![1])).await;
+    // Set up balances:
+    let mut balances = HashMap::new();
+    balances.insert(PublicKeyBinary::from(vec![0]), 5);
+    balances.insert(PublicKeyBinary::from(vec![1]), 5);
+    let balances = InstantBurnedBalance(Arc::new(Mutex::new(balances)));
+    let mut verifier = Verifier {
+        debiter: balances.clone(),
+        config_server: orgs,
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::default(),
+    };
+    let mut valid_packets = Vec::new();
+    let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
+
+    // Both packets leave less than the default minimum, but org #1 has none:
+    verifier
+        .verify(
+            &MinimumBalances::new(10).with_override(1, 0),
+            balances.clone(),
+            stream::iter(vec![
+                packet_report(0, 0, 1, vec![1]),
+                packet_report(1, 0, 1, vec![2]),
+            ]),
+            &mut valid_packets,
+            &mut invalid_packets,
+            &mut packet_prices,
+        )
+        .await
+        .unwrap();
+    let payers = verifier.config_server.payers.lock().await;
+    assert!(!payers.get(&0).unwrap().enabled);
+    assert!(payers.get(&1).unwrap().enabled);
+}
+
 #[tokio::test]
 async fn test_end_to_end() {
     let payer = PublicKeyBinary::from(vec![0]);
 
     // Pending burns:
-    let mut pending_burns: Arc<Mutex<HashMap<PublicKeyBinary, u64>>> =
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
         Arc::new(Mutex::new(HashMap::new()));
+    let pending_key = (payer.clone(), TokenUse::IotPackets);
 
     // Solana network:
     let mut solana_network = HashMap::new();
@@ -368,6 +576,7 @@ async fn test_end_to_end() {
     let mut burner = Burner::new(
         pending_burns.clone(),
         &balance_cache,
+        TokenUse::IotPackets,
         0, // Burn period does not matter, we manually burn
         solana_network.clone(),
     );
@@ -379,17 +588,20 @@ async fn test_end_to_end() {
     // Packet output:
     let mut valid_packets = Vec::new();
     let mut invalid_packets = Vec::new();
+    let mut packet_prices = Vec::new();
 
     // Set up verifier:
     let mut verifier = Verifier {
         debiter: balance_cache,
         config_server: orgs,
+        pricing: DefaultPricing,
+        disable_hysteresis: DisableHysteresis::default(),
     };
 
     // Verify four packets, each costing one DC. The last one should be invalid
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             pending_burns.clone(),
             stream::iter(vec![
                 packet_report(0, 0, BYTES_PER_DC as u32, vec![1]),
@@ -399,6 +611,7 @@ async fn test_end_to_end() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -440,11 +653,12 @@ async fn test_end_to_end() {
     assert_eq!(balance.burned, 3);
 
     // Check that 3 DC are pending to be burned:
-    let pending_burn = *pending_burns.lock().await.get(&payer).unwrap();
+    let pending_burn = *pending_burns.lock().await.get(&pending_key).unwrap();
     assert_eq!(pending_burn, 3);
 
     // Initiate the burn:
-    burner.burn().await.unwrap();
+    let settlement = burner.burn().await.unwrap().expect("settled burn");
+    assert_eq!((settlement.amount, settlement.remaining), (3, 0));
 
     // Now that we've burn, the balances and burn amount should be reset:
     let balance = {
@@ -456,7 +670,7 @@ async fn test_end_to_end() {
     assert_eq!(balance.burned, 0);
 
     // Pending burns should be empty as well:
-    let pending_burn = *pending_burns.lock().await.get(&payer).unwrap();
+    let pending_burn = *pending_burns.lock().await.get(&pending_key).unwrap();
     assert_eq!(pending_burn, 0);
 
     // Additionally, the balance on the solana network should be zero:
@@ -469,11 +683,12 @@ async fn test_end_to_end() {
 
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             pending_burns.clone(),
             stream::iter(vec![packet_report(0, 4, BYTES_PER_DC as u32, vec![5])]),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -495,7 +710,7 @@ async fn test_end_to_end() {
     // should clear
     verifier
         .verify(
-            1,
+            &MinimumBalances::new(1),
             pending_burns.clone(),
             stream::iter(vec![
                 packet_report(0, 5, 2 * BYTES_PER_DC as u32, vec![6]),
@@ -503,6 +718,7 @@ async fn test_end_to_end() {
             ]),
             &mut valid_packets,
             &mut invalid_packets,
+            &mut packet_prices,
         )
         .await
         .unwrap();
@@ -524,3 +740,434 @@ async fn test_end_to_end() {
     assert_eq!(balance.balance, 1);
     assert_eq!(balance.burned, 1);
 }
+
+#[test]
+fn test_balance_warnings() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let debits = |debited, remaining_balance| {
+        HashMap::from([(
+            0_u64,
+            OrgDebits {
+                payer: payer.clone(),
+                debited,
+                remaining_balance,
+            },
+        )])
+    };
+    let mut tracker = SpendTracker::new(&balance_warnings::Settings::default());
+    let start = Utc.timestamp_opt(0, 0).unwrap();
+
+    // Nothing is raised until the burn rate has been observed long enough:
+    assert!(tracker.record(start, &debits(1_000, 100)).is_empty());
+
+    // 2_000 DC spent over two hours is a daily burn rate of 24_000 DC, and
+    // 100 DC remaining is well below 20% of that:
+    let now = start + chrono::Duration::hours(2);
+    let warnings = tracker.record(now, &debits(1_000, 100));
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].oui, 0);
+    assert_eq!(warnings[0].balance, 100);
+    assert_eq!(warnings[0].daily_burn_rate, 24_000);
+    assert_eq!(
+        warnings[0].projected_exhaustion,
+        now + chrono::Duration::seconds(360)
+    );
+
+    // Warnings are rate limited by the cooldown:
+    let now = start + chrono::Duration::hours(3);
+    assert!(tracker.record(now, &debits(1_000, 50)).is_empty());
+
+    // Healthy balances never warn:
+    let now = start + chrono::Duration::hours(12);
+    assert!(tracker.record(now, &debits(1_000, 1_000_000)).is_empty());
+}
+
+#[tokio::test]
+async fn test_pending_burns_of_every_token_use_count_against_balance() {
+    let payer = PublicKeyBinary::from(vec![0]);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::from([
+            ((payer.clone(), TokenUse::IotPackets), 2),
+            ((payer.clone(), TokenUse::MobileData), 3),
+        ])));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 10_u64)])));
+
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network)
+        .await
+        .unwrap();
+
+    // Five of the ten DC are already pending to be burned:
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 5, 0)
+            .await
+            .unwrap(),
+        Some(0)
+    );
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_subscribed_balances_are_not_refetched() {
+    let payer = PublicKeyBinary::from(vec![0]);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 1_u64)])));
+
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        Some(0)
+    );
+
+    // Pushed by the subscription of the payer:
+    {
+        let balances = balance_cache.balances();
+        let mut balances = balances.lock().await;
+        let balance = balances.get_mut(&payer).unwrap();
+        balance.balance = 1;
+        balance.subscribed = true;
+    }
+    // A top up not pushed yet is not fetched:
+    *solana_network.lock().await.get_mut(&payer).unwrap() = 10;
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Until a burn resets the balance:
+    balance_cache
+        .balances()
+        .lock()
+        .await
+        .get_mut(&payer)
+        .unwrap()
+        .subscribed = false;
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        Some(8)
+    );
+}
+
+#[tokio::test]
+async fn test_low_balances_are_refetched_once_per_interval() {
+    let payer = PublicKeyBinary::from(vec![0]);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 5_u64)])));
+
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap()
+        .with_refetch_interval(Duration::from_secs(60));
+    // Left below its minimum of 10 by the first debit:
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 10)
+            .await
+            .unwrap(),
+        Some(4)
+    );
+
+    // A top up is not fetched again within the interval:
+    *solana_network.lock().await.get_mut(&payer).unwrap() = 100;
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 10)
+            .await
+            .unwrap(),
+        Some(3)
+    );
+
+    // Unless a burn reset the balance:
+    {
+        let balances = balance_cache.balances();
+        let mut balances = balances.lock().await;
+        let balance = balances.get_mut(&payer).unwrap();
+        balance.balance = 0;
+        balance.last_fetch = None;
+    }
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 10)
+            .await
+            .unwrap(),
+        Some(97)
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_burns() {
+    let payer = PublicKeyBinary::from(vec![0]);
+    let pending_key = (payer.clone(), TokenUse::IotPackets);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::from([(pending_key.clone(), 3)])));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 10_u64)])));
+
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+    let mut burner = Burner::new(
+        pending_burns.clone(),
+        &balance_cache,
+        TokenUse::IotPackets,
+        0,
+        solana_network.clone(),
+    )
+    .with_dry_run(true);
+    burner.burn().await.unwrap();
+
+    // The pending burn is settled without burning anything on chain:
+    assert_eq!(*pending_burns.lock().await.get(&pending_key).unwrap(), 0);
+    assert_eq!(
+        balance_cache
+            .balances()
+            .lock()
+            .await
+            .get(&payer)
+            .unwrap()
+            .burned,
+        0
+    );
+    assert_eq!(*solana_network.lock().await.get(&payer).unwrap(), 10);
+}
+
+#[test]
+fn test_reconcile_balance() {
+    let mut cached = Balance {
+        balance: 100,
+        burned: 10,
+        subscribed: false,
+        last_debit: None,
+        last_fetch: None,
+    };
+
+    // Drift within the threshold is only reported:
+    assert_eq!(reconcile_balance(&mut cached, 95, 5), Some(-5));
+    assert_eq!(cached.balance, 100);
+
+    // Beyond it the cached balance is corrected, leaving the burned amount:
+    assert_eq!(reconcile_balance(&mut cached, 150, 5), Some(50));
+    assert_eq!(cached.balance, 150);
+    assert_eq!(cached.burned, 10);
+
+    // Balances reset by a burn are left to the next debit:
+    cached.balance = 0;
+    assert_eq!(reconcile_balance(&mut cached, 150, 5), None);
+    assert_eq!(cached.balance, 0);
+}
+
+/// Chain pushing the balances scripted for each connection subscribed over,
+/// every connection but the last one being lost once they are pushed
+struct ScriptedSubscriptions {
+    chain: Arc<Mutex<HashMap<PublicKeyBinary, u64>>>,
+    connections: std::sync::Mutex<VecDeque<Vec<u64>>>,
+}
+
+struct ScriptedConnection {
+    pushes: Vec<u64>,
+    lost: bool,
+}
+
+#[async_trait]
+impl SolanaNetwork for ScriptedSubscriptions {
+    type Error = Infallible;
+
+    async fn payer_balance(&self, payer: &PublicKeyBinary) -> Result<u64, Infallible> {
+        self.chain.payer_balance(payer).await
+    }
+
+    async fn burn_data_credits(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<(), Infallible> {
+        self.chain.burn_data_credits(payer, amount).await
+    }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Infallible> {
+        self.chain.sign_burn(payer, amount).await
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Infallible> {
+        self.chain.send_burn(burn).await
+    }
+
+    async fn burn_status(&self, burn: &BurnTransaction) -> Result<BurnStatus, Infallible> {
+        self.chain.burn_status(burn).await
+    }
+}
+
+#[async_trait]
+impl SubscriptionNetwork for ScriptedSubscriptions {
+    type Subscriptions = ScriptedConnection;
+
+    async fn payer_subscriptions(&self) -> Result<ScriptedConnection, Infallible> {
+        let mut connections = self.connections.lock().unwrap();
+        let pushes = connections.pop_front().unwrap_or_default();
+        Ok(ScriptedConnection {
+            pushes,
+            lost: !connections.is_empty(),
+        })
+    }
+}
+
+#[async_trait]
+impl Subscribe for ScriptedConnection {
+    type Error = Infallible;
+
+    async fn subscribe(&self, _payer: &PublicKeyBinary) -> Result<BoxStream<'_, u64>, Infallible> {
+        let pushes = stream::iter(self.pushes.clone());
+        Ok(if self.lost {
+            pushes.boxed()
+        } else {
+            pushes.chain(stream::pending()).boxed()
+        })
+    }
+}
+
+/// Wait for the cached balance of `payer` to be `balance`, subscribed or not
+async fn wait_for_balance(
+    balance_cache: &BalanceCache<Arc<Mutex<HashMap<PublicKeyBinary, u64>>>>,
+    payer: &PublicKeyBinary,
+    balance: u64,
+    subscribed: bool,
+) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let cached = *balance_cache.balances().lock().await.get(payer).unwrap();
+            if cached.balance == balance && cached.subscribed == subscribed {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("cached balance not set");
+}
+
+#[sqlx::test]
+async fn test_lost_subscriptions_fall_back_to_fetching(pool: PgPool) {
+    let payer = PublicKeyBinary::from(vec![0]);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 1_u64)])));
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+    // Debited, so subscribed to:
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        Some(0)
+    );
+
+    // The only connection is lost once it pushed a top up, and isn't made
+    // again before the end of the test:
+    let subscriptions = BalanceSubscriptions::new(
+        &balance_subscriptions::Settings {
+            reconnect_delay: Duration::from_secs(3600),
+            ..Default::default()
+        },
+        Arc::new(ScriptedSubscriptions {
+            chain: solana_network.clone(),
+            connections: std::sync::Mutex::new(VecDeque::from([vec![5], vec![]])),
+        }),
+        balance_cache.balances(),
+        pool,
+    );
+    let (trigger, shutdown) = triggered::trigger();
+    let subscriptions = tokio::spawn(subscriptions.run(shutdown));
+    wait_for_balance(&balance_cache, &payer, 5, false).await;
+
+    // So a top up no longer pushed is fetched on an insufficient debit:
+    *solana_network.lock().await.get_mut(&payer).unwrap() = 10;
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 6, 0)
+            .await
+            .unwrap(),
+        Some(3)
+    );
+
+    trigger.trigger();
+    subscriptions.await.unwrap().unwrap();
+}
+
+#[sqlx::test]
+async fn test_lost_subscriptions_are_made_again(pool: PgPool) {
+    let payer = PublicKeyBinary::from(vec![0]);
+
+    let mut pending_burns: Arc<Mutex<HashMap<(PublicKeyBinary, TokenUse), u64>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let solana_network = Arc::new(Mutex::new(HashMap::from([(payer.clone(), 1_u64)])));
+    let balance_cache = BalanceCache::new(&mut pending_burns, solana_network.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 1, 0)
+            .await
+            .unwrap(),
+        Some(0)
+    );
+
+    // The first connection is lost once it pushed a top up, the payer is
+    // subscribed to again over the second one:
+    let subscriptions = BalanceSubscriptions::new(
+        &balance_subscriptions::Settings {
+            reconnect_delay: Duration::from_millis(10),
+            ..Default::default()
+        },
+        Arc::new(ScriptedSubscriptions {
+            chain: solana_network.clone(),
+            connections: std::sync::Mutex::new(VecDeque::from([vec![5], vec![7]])),
+        }),
+        balance_cache.balances(),
+        pool,
+    );
+    let (trigger, shutdown) = triggered::trigger();
+    let subscriptions = tokio::spawn(subscriptions.run(shutdown));
+    wait_for_balance(&balance_cache, &payer, 7, true).await;
+
+    // Pushed balances aren't fetched again:
+    *solana_network.lock().await.get_mut(&payer).unwrap() = 10;
+    assert_eq!(
+        balance_cache
+            .debit_if_sufficient(&payer, 7, 0)
+            .await
+            .unwrap(),
+        None
+    );
+
+    trigger.trigger();
+    subscriptions.await.unwrap().unwrap();
+}
@@ -0,0 +1,77 @@
+// The hex density, witness quality, poc status and reverify services are not
+// part of helium-proto. Their server and client stubs are generated here from the
+// prost messages defined in `src/proto.rs`.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let hex_density = Service::builder()
+        .name("HexDensity")
+        .package("helium.iot_verifier")
+        .method(
+            Method::builder()
+                .name("scaling_factor")
+                .route_name("ScalingFactor")
+                .input_type("crate::proto::HexScaleReqV1")
+                .output_type("crate::proto::HexScaleRespV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("scaling_map")
+                .route_name("ScalingMap")
+                .input_type("crate::proto::HexScalingMapReqV1")
+                .output_type("crate::proto::HexScalingMapRespV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    let witness_quality = Service::builder()
+        .name("WitnessQuality")
+        .package("helium.iot_verifier")
+        .method(
+            Method::builder()
+                .name("quality")
+                .route_name("Quality")
+                .input_type("crate::proto::WitnessQualityReqV1")
+                .output_type("crate::proto::WitnessQualityRespV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .build();
+
+    let poc_status = Service::builder()
+        .name("PocStatus")
+        .package("helium.iot_verifier")
+        .method(
+            Method::builder()
+                .name("gateway_pocs")
+                .route_name("GatewayPocs")
+                .input_type("crate::proto::GatewayPocsReqV1")
+                .output_type("crate::proto::GatewayPocsRespV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    let reverify = Service::builder()
+        .name("Reverify")
+        .package("helium.iot_verifier")
+        .method(
+            Method::builder()
+                .name("reverify")
+                .route_name("Reverify")
+                .input_type("crate::proto::GatewayReverifyReqV1")
+                .output_type("crate::proto::GatewayReverifyRespV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .build();
+
+    Builder::new().compile(&[hex_density, witness_quality, poc_status, reverify]);
+}
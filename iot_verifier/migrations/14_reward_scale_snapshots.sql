@@ -0,0 +1,8 @@
+create table reward_scale_snapshots (
+    hotspot_key text not null,
+    snapshot_timestamp timestamptz not null,
+    hex_scale decimal not null,
+    primary key(hotspot_key, snapshot_timestamp)
+);
+
+create index idx_reward_scale_snapshots_timestamp on reward_scale_snapshots (snapshot_timestamp);
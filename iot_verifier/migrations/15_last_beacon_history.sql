@@ -0,0 +1,7 @@
+create table last_beacon_history (
+    id bytea not null,
+    timestamp timestamptz not null,
+    primary key(id, timestamp)
+);
+
+create index idx_last_beacon_history_timestamp on last_beacon_history (timestamp);
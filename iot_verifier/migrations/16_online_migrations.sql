@@ -0,0 +1,7 @@
+create table online_migrations (
+    name text primary key not null,
+    phase text not null,
+    backfill_cursor text,
+    backfilled_rows bigint not null default 0,
+    updated_at timestamptz not null default now()
+);
@@ -0,0 +1,9 @@
+create table location_changes (
+    address bytea not null,
+    old_location bigint,
+    new_location bigint,
+    changed_at timestamptz not null,
+    primary key(address, changed_at)
+);
+
+create index idx_location_changes_changed_at on location_changes (changed_at);
@@ -0,0 +1,10 @@
+create table gateway_reverifications (
+    id bigserial primary key,
+    gateway text not null,
+    reason text not null,
+    requested_at timestamptz not null default now(),
+    handled_at timestamptz,
+    requeued bigint
+);
+
+alter table poc_report add column prioritized boolean not null default false;
@@ -0,0 +1,8 @@
+create table valid_witness_counts (
+    hotspot_key bytea not null,
+    hour timestamptz not null,
+    witnesses bigint not null default 0,
+    primary key(hotspot_key, hour)
+);
+
+create index idx_valid_witness_counts_hour on valid_witness_counts (hour);
@@ -0,0 +1,3 @@
+-- key of the report body in the report_blobs bucket, report_data is empty
+-- when set
+alter table poc_report add column report_key text;
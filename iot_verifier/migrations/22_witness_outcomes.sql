@@ -0,0 +1,9 @@
+create table witness_outcomes (
+    hotspot_key bytea not null,
+    hour timestamptz not null,
+    outcome text not null,
+    witnesses bigint not null default 0,
+    primary key(hotspot_key, hour, outcome)
+);
+
+create index idx_witness_outcomes_hour on witness_outcomes (hour);
@@ -0,0 +1,3 @@
+create type gateway_class as enum ('full', 'light', 'data_only');
+
+alter table gateway_shares add column class gateway_class not null default 'full';
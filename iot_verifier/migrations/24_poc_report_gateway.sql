@@ -0,0 +1,3 @@
+alter table poc_report add column gateway text;
+
+create index idx_poc_report_gateway on poc_report (gateway);
@@ -0,0 +1,280 @@
+//! Beacon cadence monitor, flagging gateways whose beacons look scheduled
+//! rather than organic.
+
+use crate::{last_beacon::LastBeacon, telemetry, Settings};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_beacon_cadence::{BeaconCadenceReport, BeaconCadenceReportV1},
+};
+use helium_crypto::PublicKeyBinary;
+use sqlx::{Pool, Postgres};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+use tokio::time;
+
+/// Fewest beacons within the window for a gateway to be judged at all
+const MIN_BEACONS: usize = 8;
+/// Share of intervals at the edge for a gateway to be flagged
+const EDGE_RATIO_THRESHOLD: f64 = 0.8;
+/// Width of the buckets beacons are counted in to find bursts, in seconds
+const BURST_BUCKET_SECS: i64 = 60;
+/// A bucket is a burst when it holds this many times the average number of
+/// beacons per bucket, and at least `BURST_MIN_BEACONS`
+const BURST_FACTOR: f64 = 5.0;
+const BURST_MIN_BEACONS: u32 = 5;
+/// Share of a gateway's beacons in bursts for it to be flagged
+const BURST_RATIO_THRESHOLD: f64 = 0.5;
+
+/// Publishes the gateways [`analyze`] flags over a trailing window as cadence
+/// reports and counts them in metrics. Nothing is rejected on the back of them
+pub struct BeaconCadenceMonitor {
+    pool: Pool<Postgres>,
+    reports_sink: FileSinkClient,
+    check_interval: time::Duration,
+    window: Duration,
+    min_interval: Duration,
+    edge_margin: Duration,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CadenceFinding {
+    pub pub_key: PublicKeyBinary,
+    pub beacon_count: usize,
+    pub median_interval: Duration,
+    pub edge_ratio: f64,
+    pub burst_ratio: f64,
+}
+
+impl CadenceFinding {
+    pub fn at_interval_edge(&self) -> bool {
+        self.edge_ratio >= EDGE_RATIO_THRESHOLD
+    }
+
+    pub fn in_bursts(&self) -> bool {
+        self.burst_ratio >= BURST_RATIO_THRESHOLD
+    }
+}
+
+impl BeaconCadenceMonitor {
+    pub fn from_settings(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        reports_sink: FileSinkClient,
+    ) -> Self {
+        Self {
+            pool,
+            reports_sink,
+            check_interval: settings.beacon_cadence_check_interval,
+            window: settings.beacon_cadence_window(),
+            min_interval: settings.beacon_interval() - settings.beacon_interval_tolerance(),
+            edge_margin: settings.beacon_cadence_edge_margin(),
+        }
+    }
+
+    pub async fn run(&self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting beacon cadence monitor");
+        let mut check_timer = time::interval(self.check_interval);
+        check_timer.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = check_timer.tick() => self.check(Utc::now()).await?,
+            }
+        }
+        tracing::info!("stopping beacon cadence monitor");
+        Ok(())
+    }
+
+    async fn check(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let window = now - self.window..now;
+        let history = LastBeacon::history_since(window.start, &self.pool).await?;
+        let beacons = history
+            .into_iter()
+            .map(|beacon| (PublicKeyBinary::from(beacon.id), beacon.timestamp));
+        let findings = analyze(beacons, &window, self.min_interval, self.edge_margin);
+
+        let edge_count = findings.iter().filter(|f| f.at_interval_edge()).count();
+        let burst_count = findings.iter().filter(|f| f.in_bursts()).count();
+        tracing::info!(
+            at_interval_edge = edge_count,
+            in_bursts = burst_count,
+            "beacon cadence check complete"
+        );
+        telemetry::beacon_cadence_flagged(edge_count, burst_count);
+
+        for finding in findings {
+            let report = BeaconCadenceReport {
+                at_interval_edge: finding.at_interval_edge(),
+                in_bursts: finding.in_bursts(),
+                pub_key: finding.pub_key,
+                beacon_count: finding.beacon_count as u32,
+                median_interval: finding.median_interval.num_seconds() as u64,
+                edge_ratio: finding.edge_ratio,
+                burst_ratio: finding.burst_ratio,
+                window_start: window.start,
+                window_end: window.end,
+            };
+            self.reports_sink
+                .write(BeaconCadenceReportV1::from(report), [])
+                .await?;
+        }
+        self.reports_sink.commit().await?;
+
+        LastBeacon::purge_history(window.start, &self.pool).await?;
+        Ok(())
+    }
+}
+
+/// Gateways flagged for beaconing at the edge of `min_interval`, within
+/// `edge_margin` of it, or for beaconing in bursts, from the beacons in
+/// `window`
+pub fn analyze(
+    beacons: impl IntoIterator<Item = (PublicKeyBinary, DateTime<Utc>)>,
+    window: &Range<DateTime<Utc>>,
+    min_interval: Duration,
+    edge_margin: Duration,
+) -> Vec<CadenceFinding> {
+    let mut by_gateway: HashMap<PublicKeyBinary, Vec<DateTime<Utc>>> = HashMap::new();
+    let mut bucket_counts: HashMap<i64, u32> = HashMap::new();
+    let mut total: usize = 0;
+    for (pub_key, timestamp) in beacons {
+        *bucket_counts.entry(bucket(window, timestamp)).or_default() += 1;
+        by_gateway.entry(pub_key).or_default().push(timestamp);
+        total += 1;
+    }
+
+    let bucket_total = ((window.end - window.start).num_seconds() / BURST_BUCKET_SECS).max(1);
+    let burst_threshold =
+        (BURST_FACTOR * total as f64 / bucket_total as f64).max(BURST_MIN_BEACONS as f64);
+    let bursts: HashSet<i64> = bucket_counts
+        .into_iter()
+        .filter(|(_, count)| *count as f64 >= burst_threshold)
+        .map(|(bucket, _)| bucket)
+        .collect();
+
+    let edge = min_interval..min_interval + edge_margin;
+    by_gateway
+        .into_iter()
+        .filter(|(_, timestamps)| timestamps.len() >= MIN_BEACONS)
+        .map(|(pub_key, mut timestamps)| {
+            timestamps.sort();
+            let mut intervals: Vec<Duration> = timestamps.windows(2).map(|w| w[1] - w[0]).collect();
+            intervals.sort();
+            let edge_count = intervals
+                .iter()
+                .filter(|interval| edge.contains(*interval))
+                .count();
+            let burst_count = timestamps
+                .iter()
+                .filter(|timestamp| bursts.contains(&bucket(window, **timestamp)))
+                .count();
+            CadenceFinding {
+                pub_key,
+                beacon_count: timestamps.len(),
+                median_interval: intervals[intervals.len() / 2],
+                edge_ratio: edge_count as f64 / intervals.len() as f64,
+                burst_ratio: burst_count as f64 / timestamps.len() as f64,
+            }
+        })
+        .filter(|finding| finding.at_interval_edge() || finding.in_bursts())
+        .collect()
+}
+
+fn bucket(window: &Range<DateTime<Utc>>, timestamp: DateTime<Utc>) -> i64 {
+    (timestamp - window.start).num_seconds() / BURST_BUCKET_SECS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEYS: [&str; 9] = [
+        "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6",
+        "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp",
+        "11eX55faMbqZB7jzN4p67m6w7ScPMH6ubnvCjCPLh72J49PaJEL",
+        "112DJZiXvZ8FduiWrEi8siE3wJX6hpRjjtwbavyXUDkgutEUSLAE",
+        "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf",
+        "112j1iw1sV2B2Tz2DxPSeum9Cmc5kMKNdDTDg1zDRsdwuvZueq3B",
+        "112p1GbUtRLyfFaJr1XF8fH7yz9cSZ4exbrSpVDeu67DeGb31QUL",
+        "11fCasUk9XvU15ktsMMH64J9E7XuqQ2L5FJPv8HZMCDG6kdZ3SC",
+        "11z69eJ3czc92k6snrfR9ek7g2uRWXosFbnG9v4bXgwhfUCivUo",
+    ];
+
+    fn gateway(n: usize) -> PublicKeyBinary {
+        PUBKEYS[n].parse().expect("failed gateway parse")
+    }
+
+    fn schedule(
+        pub_key: &PublicKeyBinary,
+        start: DateTime<Utc>,
+        interval: Duration,
+        count: i32,
+    ) -> Vec<(PublicKeyBinary, DateTime<Utc>)> {
+        (0..count)
+            .map(|n| (pub_key.clone(), start + interval * n))
+            .collect()
+    }
+
+    #[test]
+    fn flags_beaconing_at_interval_edge() {
+        let now = Utc::now();
+        let window = now - Duration::days(7)..now;
+        let min_interval = Duration::hours(6) - Duration::minutes(10);
+        let edge_margin = Duration::minutes(5);
+
+        let mut beacons = schedule(
+            &gateway(0),
+            window.start,
+            min_interval + Duration::minutes(1),
+            20,
+        );
+        beacons.extend(schedule(&gateway(1), window.start, Duration::hours(7), 20));
+        // too few beacons to judge
+        beacons.extend(schedule(&gateway(2), window.start, min_interval, 4));
+
+        let findings = analyze(beacons, &window, min_interval, edge_margin);
+        assert_eq!(1, findings.len());
+        let finding = &findings[0];
+        assert_eq!(gateway(0), finding.pub_key);
+        assert!(finding.at_interval_edge());
+        assert!(!finding.in_bursts());
+        assert_eq!(min_interval + Duration::minutes(1), finding.median_interval);
+    }
+
+    #[test]
+    fn flags_synchronized_bursts() {
+        let now = Utc::now();
+        let window = now - Duration::days(7)..now;
+        let min_interval = Duration::hours(6) - Duration::minutes(10);
+
+        // eight gateways beaconing within the same minute every 8 hours,
+        // and one beaconing on its own schedule
+        let mut beacons: Vec<_> = (0..8)
+            .flat_map(|n| {
+                schedule(
+                    &gateway(n),
+                    window.start + Duration::seconds(n as i64),
+                    Duration::hours(8),
+                    10,
+                )
+            })
+            .collect();
+        beacons.extend(schedule(
+            &gateway(8),
+            window.start + Duration::hours(3),
+            Duration::hours(7),
+            10,
+        ));
+
+        let findings = analyze(beacons, &window, min_interval, Duration::minutes(5));
+        let flagged: Vec<_> = findings.iter().map(|f| f.pub_key.clone()).collect();
+        assert_eq!(8, flagged.len());
+        assert!(!flagged.contains(&gateway(8)));
+        assert!(findings
+            .iter()
+            .all(|f| f.in_bursts() && !f.at_interval_edge()));
+    }
+}
@@ -0,0 +1,117 @@
+//! Catchup mode, verifying recent PoC activity in time to be rewarded once
+//! verification falls behind.
+
+use crate::{poc_report::Report, telemetry, Settings};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+/// The order beacons ready for verification are selected in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkOrder {
+    #[default]
+    OldestFirst,
+    NewestFirst,
+    /// Alternate between the newest and the oldest beacons
+    Interleaved,
+}
+
+impl WorkOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OldestFirst => "oldest_first",
+            Self::NewestFirst => "newest_first",
+            Self::Interleaved => "interleaved",
+        }
+    }
+
+    /// Order by clause of the beacon selection
+    pub(crate) fn order_by(&self) -> &'static str {
+        match self {
+            Self::OldestFirst => "poc_report.created_at asc",
+            Self::NewestFirst => "poc_report.created_at desc",
+            Self::Interleaved => {
+                r#"least(
+                    row_number() over (order by poc_report.created_at asc),
+                    row_number() over (order by poc_report.created_at desc)
+                ), poc_report.created_at desc"#
+            }
+        }
+    }
+}
+
+/// Verification is in catchup while the oldest beacon ready for verification
+/// is older than `catchup_threshold`. In catchup the runner selects beacons by
+/// `catchup_policy` rather than oldest first, and the purger widens its stale
+/// periods by `catchup_stale_extension` so older reports get a chance too
+#[derive(Debug, Clone)]
+pub struct Catchup {
+    threshold: Duration,
+    policy: WorkOrder,
+    stale_extension: Duration,
+    beacon_max_retries: u64,
+    beacon_hold: Duration,
+}
+
+impl Catchup {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            threshold: settings.catchup_threshold(),
+            policy: settings.catchup_policy,
+            stale_extension: settings.catchup_stale_extension(),
+            beacon_max_retries: settings.beacon_max_retries,
+            beacon_hold: settings.max_witness_window(),
+        }
+    }
+
+    /// Whether verification is currently behind by more than the threshold
+    pub async fn is_active(&self, pool: &PgPool) -> anyhow::Result<bool> {
+        let lag = Report::oldest_ready_beacon(pool, self.beacon_max_retries, self.beacon_hold)
+            .await?
+            .map_or_else(Duration::zero, |oldest| Utc::now() - oldest);
+        let active = lag > self.threshold;
+        telemetry::catchup(active, lag);
+        if active {
+            tracing::info!(%lag, policy = self.policy.as_str(), "verifier in catchup");
+        }
+        Ok(active)
+    }
+
+    pub fn work_order(&self, active: bool) -> WorkOrder {
+        if active {
+            self.policy
+        } else {
+            WorkOrder::OldestFirst
+        }
+    }
+
+    /// Added to the purger stale periods
+    pub fn stale_extension(&self, active: bool) -> Duration {
+        if active {
+            self.stale_extension
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_applies_while_active() {
+        let catchup = Catchup {
+            threshold: Duration::minutes(30),
+            policy: WorkOrder::Interleaved,
+            stale_extension: Duration::hours(2),
+            beacon_max_retries: 60,
+            beacon_hold: Duration::minutes(3),
+        };
+        assert_eq!(WorkOrder::OldestFirst, catchup.work_order(false));
+        assert_eq!(WorkOrder::Interleaved, catchup.work_order(true));
+        assert_eq!(Duration::zero(), catchup.stale_extension(false));
+        assert_eq!(Duration::hours(2), catchup.stale_extension(true));
+    }
+}
@@ -0,0 +1,191 @@
+//! Api of the transmit scaling map of the density scaler, so tooling and
+//! hotspot operators don't have to derive it from reward files.
+
+use crate::{
+    hex_density::SharedHexDensityMap,
+    poc_status_service::PocStatusService,
+    proto::{
+        self, hex_density_server::HexDensityServer, poc_status_server::PocStatusServer,
+        reverify_server::ReverifyServer, witness_quality_server::WitnessQualityServer,
+        HexScaleReqV1, HexScaleRespV1, HexScaleV1, HexScalingMapReqV1, HexScalingMapRespV1,
+    },
+    reverify::ReverifyService,
+    tx_scaler,
+    witness_quality_service::WitnessQualityService,
+};
+use chrono::{DateTime, Utc};
+use file_store::{
+    grpc::{GrpcResult, GrpcStreamResult},
+    traits::TimestampEncode,
+};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
+use hex_utils::h3o::Resolution;
+use prost::Message;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::{collections::HashSet, net::SocketAddr, str::FromStr, sync::Arc};
+use tokio::sync::mpsc;
+use tonic::{transport, Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_density_request");
+/// Scales are sent in ten thousandths
+const SCALE_MULTIPLIER: Decimal = dec!(10000);
+/// Hexes per message of a streamed map
+const SCALES_PER_MESSAGE: usize = 5000;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Listen address of the hex density api. Default "0.0.0.0:8090"
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// File from which to load the keypair signing responses
+    pub keypair: String,
+    /// B58 encoded public keys allowed to request re-verifications. Default
+    /// is none
+    #[serde(default)]
+    pub admin_keys: Vec<String>,
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:8090".to_string()
+}
+
+impl Settings {
+    pub fn signing_keypair(&self) -> anyhow::Result<Keypair> {
+        Ok(settings_loader::keypair(&self.keypair)?)
+    }
+
+    pub fn admin_keys(&self) -> Result<HashSet<PublicKeyBinary>, helium_crypto::Error> {
+        self.admin_keys
+            .iter()
+            .map(|key| PublicKey::from_str(key).map(PublicKeyBinary::from))
+            .collect()
+    }
+}
+
+/// Serves the scaling map of the density scaler, a single hex at a time or
+/// the whole map in chunks. Responses are signed and carry when the map was
+/// last refreshed, hexes without an interactive gateway are not in the map
+pub struct DensityService {
+    hex_density_map: SharedHexDensityMap,
+    signing_key: Arc<Keypair>,
+}
+
+impl DensityService {
+    pub fn new(settings: &Settings, density_scaler: &tx_scaler::Server) -> anyhow::Result<Self> {
+        Ok(Self {
+            hex_density_map: density_scaler.shared_hex_density_map(),
+            signing_key: Arc::new(settings.signing_keypair()?),
+        })
+    }
+
+    /// Serve the hex density api, and the witness quality, poc status and
+    /// reverify apis alongside it, until shutdown
+    pub async fn serve(
+        self,
+        witness_quality: WitnessQualityService,
+        poc_status: PocStatusService,
+        reverify: ReverifyService,
+        settings: &Settings,
+        shutdown: triggered::Listener,
+    ) -> anyhow::Result<()> {
+        let listen_addr = SocketAddr::from_str(&settings.listen)?;
+        tracing::info!("hex density api listening on {listen_addr}");
+        transport::Server::builder()
+            .layer(poc_metrics::request_layer!(
+                "iot_verifier_density_connection"
+            ))
+            .add_service(HexDensityServer::new(self))
+            .add_service(WitnessQualityServer::new(witness_quality))
+            .add_service(PocStatusServer::new(poc_status))
+            .add_service(ReverifyServer::new(reverify))
+            .serve_with_shutdown(listen_addr, shutdown)
+            .await?;
+        Ok(())
+    }
+}
+
+fn scale_of(hex: u64, scale: Decimal) -> HexScaleV1 {
+    HexScaleV1 {
+        hex,
+        scale: (scale * SCALE_MULTIPLIER).to_u32().unwrap_or(0),
+    }
+}
+
+fn encode_refreshed_at(refreshed_at: Option<DateTime<Utc>>) -> u64 {
+    refreshed_at.map_or(0, |refreshed_at| refreshed_at.encode_timestamp())
+}
+
+pub(crate) fn sign<T: Message>(signing_key: &Keypair, msg: &T) -> Result<Vec<u8>, Status> {
+    signing_key.sign(&msg.encode_to_vec()).map_err(|err| {
+        tracing::error!(?err, "density response signing error");
+        Status::internal("response signing error")
+    })
+}
+
+#[tonic::async_trait]
+impl proto::hex_density_server::HexDensity for DensityService {
+    async fn scaling_factor(&self, request: Request<HexScaleReqV1>) -> GrpcResult<HexScaleRespV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "scaling_factor");
+
+        let cell = hex_utils::cell(request.hex)
+            .map_err(|_| Status::invalid_argument("invalid h3 index"))?;
+        if cell.resolution() != Resolution::Twelve {
+            return Err(Status::invalid_argument("hex must be of resolution 12"));
+        }
+        let (scale, refreshed_at) = self.hex_density_map.get_with_refresh(request.hex).await;
+        let scale = scale.ok_or_else(|| {
+            Status::not_found("no interactive gateway asserted in hex, it is not scaled")
+        })?;
+
+        let mut resp = HexScaleRespV1 {
+            scale: Some(scale_of(request.hex, scale)),
+            refreshed_at: encode_refreshed_at(refreshed_at),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = sign(&self.signing_key, &resp)?;
+        Ok(Response::new(resp))
+    }
+
+    type scaling_mapStream = GrpcStreamResult<HexScalingMapRespV1>;
+    async fn scaling_map(
+        &self,
+        _request: Request<HexScalingMapReqV1>,
+    ) -> GrpcResult<Self::scaling_mapStream> {
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "scaling_map");
+
+        // a copy, so the map can be refreshed while it is streamed
+        let (map, refreshed_at) = self.hex_density_map.snapshot().await;
+        let signing_key = self.signing_key.clone();
+        let (tx, rx) = mpsc::channel(20);
+        tokio::spawn(async move {
+            let scales: Vec<HexScaleV1> = map
+                .into_iter()
+                .map(|(hex, scale)| scale_of(hex, scale))
+                .collect();
+            for chunk in scales.chunks(SCALES_PER_MESSAGE) {
+                let mut resp = HexScalingMapRespV1 {
+                    scales: chunk.to_vec(),
+                    refreshed_at: encode_refreshed_at(refreshed_at),
+                    timestamp: Utc::now().encode_timestamp(),
+                    signer: signing_key.public_key().into(),
+                    signature: vec![],
+                };
+                let resp = sign(&signing_key, &resp).map(|signature| {
+                    resp.signature = signature;
+                    resp
+                });
+                let failed = resp.is_err();
+                if tx.send(resp).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
@@ -3,8 +3,8 @@ use serde::{Deserialize, Serialize};
 
 /// measurement in seconds of a piece of entropy
 /// its lifespan will be valid from entropy.timestamp to entropy.timestamp + ENTROPY_LIFESPAN
-/// any beacon or witness report received after this period and before the ENTROPY_STALE_PERIOD
-/// defined in the purger module will be rejected due to being outside of the entropy lifespan
+/// any beacon or witness report received after this period and before the entropy stale period
+/// of the purger will be rejected due to being outside of the entropy lifespan
 /// TODO: determine a sane value here
 pub const ENTROPY_LIFESPAN: i64 = 180;
 
@@ -75,6 +75,24 @@ impl Entropy {
         .await?)
     }
 
+    /// Number of entropy entries older than `stale_period`
+    pub async fn count_stale(
+        executor: impl sqlx::PgExecutor<'_>,
+        stale_period: Duration,
+    ) -> Result<u64, EntropyError> {
+        let stale_time = Utc::now() - stale_period;
+        Ok(sqlx::query_scalar::<_, i64>(
+            r#"
+            select count(*) from entropy
+            where timestamp < $1
+            "#,
+        )
+        .bind(stale_time)
+        .fetch_one(executor)
+        .await
+        .map(|count| count as u64)?)
+    }
+
     pub async fn purge<'c, 'q, E>(executor: E, stale_period: Duration) -> Result<(), EntropyError>
     where
         E: sqlx::Executor<'c, Database = sqlx::Postgres> + Clone,
@@ -35,3 +35,60 @@ impl GatewayCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway_updater::GatewayMap;
+    use chrono::{TimeZone, Utc};
+    use helium_proto::Region;
+    use iot_config::gateway_info::GatewayMetadata;
+    use tokio::sync::watch;
+
+    fn gateway(address: &PublicKeyBinary, location: u64) -> GatewayInfo {
+        GatewayInfo {
+            address: address.clone(),
+            metadata: Some(GatewayMetadata {
+                location,
+                elevation: 0,
+                gain: 12,
+                region: Region::Us915,
+                onboarded_at: None,
+                updated_at: Utc.timestamp_opt(location as i64, 0).single(),
+            }),
+            is_full_hotspot: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_the_latest_map_of_the_updater() {
+        let address = PublicKeyBinary::from(vec![1]);
+        let (sender, receiver) = watch::channel(GatewayMap::new());
+        let cache = GatewayCache::new(receiver);
+
+        assert!(matches!(
+            cache.resolve_gateway_info(&address).await,
+            Err(GatewayCacheError::GatewayNotFound(missing)) if missing == address
+        ));
+
+        sender.send_modify(|gateways| {
+            gateways.insert(address.clone(), gateway(&address, 1));
+        });
+        let metadata = cache.resolve_gateway_info(&address).await.unwrap().metadata;
+        assert_eq!(metadata.map(|metadata| metadata.location), Some(1));
+
+        // a change poll refreshing the gateway is served right away, with
+        // when its metadata was updated
+        sender.send_modify(|gateways| {
+            gateways.insert(address.clone(), gateway(&address, 2));
+        });
+        let metadata = cache
+            .resolve_gateway_info(&address)
+            .await
+            .unwrap()
+            .metadata
+            .unwrap();
+        assert_eq!(metadata.location, 2);
+        assert_eq!(metadata.updated_at, Utc.timestamp_opt(2, 0).single());
+    }
+}
@@ -0,0 +1,231 @@
+//! Reconciliation of the cached gateway info with iot config, to catch the
+//! cache drifting in between gateway refreshes.
+
+use crate::{
+    gateway_updater::{GatewayUpdaterError, MessageSender},
+    telemetry, Settings,
+};
+use chrono::Utc;
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_gateway_reconciliation::{GatewayDrift, GatewayReconciliation, GatewayReconciliationV1},
+};
+use helium_crypto::PublicKeyBinary;
+use iot_config::{
+    client::Client as IotConfigClient,
+    gateway_info::{GatewayInfo, GatewayInfoResolver},
+};
+use rand::seq::IteratorRandom;
+use task_scheduler::{Schedule, Scheduler};
+use tokio::time;
+
+/// Looks up a random sample of the cached gateways one by one in iot config on
+/// every run of the `gateway_reconcile_interval` schedule and compares their
+/// location, gain and region. Drift is logged, counted in metrics and
+/// summarized in a reconciliation report. With `gateway_reconcile_repair`
+/// drifted gateways are also replaced in the cache by their info in iot
+/// config, or removed when iot config no longer knows them, until the next
+/// refresh
+pub struct GatewayReconciler {
+    schedule: Schedule,
+    jitter: time::Duration,
+    sample_size: usize,
+    repair: bool,
+    reports_sink: FileSinkClient,
+}
+
+/// Fields of a cached gateway differing from iot config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Drift {
+    /// iot config no longer knows the gateway
+    pub missing: bool,
+    pub location: bool,
+    pub gain: bool,
+    pub region: bool,
+}
+
+impl Drift {
+    pub fn any(&self) -> bool {
+        self.missing || self.location || self.gain || self.region
+    }
+}
+
+/// Compare a cached gateway to its `current` info in iot config
+pub fn drift(cached: &GatewayInfo, current: Option<&GatewayInfo>) -> Drift {
+    let Some(current) = current else {
+        return Drift {
+            missing: true,
+            ..Default::default()
+        };
+    };
+    let cached = cached.metadata.as_ref();
+    let current = current.metadata.as_ref();
+    Drift {
+        missing: false,
+        location: cached.map(|m| m.location) != current.map(|m| m.location),
+        gain: cached.map(|m| m.gain) != current.map(|m| m.gain),
+        region: cached.map(|m| m.region) != current.map(|m| m.region),
+    }
+}
+
+impl GatewayReconciler {
+    pub fn from_settings(settings: &Settings, reports_sink: FileSinkClient) -> Self {
+        Self {
+            schedule: settings.gateway_reconcile_interval.clone(),
+            jitter: settings.gateway_reconcile_jitter(),
+            sample_size: settings.gateway_reconcile_sample_size,
+            repair: settings.gateway_reconcile_repair,
+            reports_sink,
+        }
+    }
+
+    /// Scheduler of the reconciliations, the first one at the first run of
+    /// the schedule
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler::new("gateway-reconciler", self.schedule.clone(), self.jitter)
+    }
+
+    /// Compare a sample of the gateways cached in `gateways` to iot config,
+    /// repairing the drifted ones if enabled
+    pub async fn reconcile(
+        &self,
+        iot_config_client: &mut IotConfigClient,
+        gateways: &MessageSender,
+    ) -> Result<(), GatewayUpdaterError> {
+        if self.sample_size == 0 {
+            return Ok(());
+        }
+        tracing::info!(sample_size = self.sample_size, "reconciling gateways");
+        let sample: Vec<GatewayInfo> = gateways
+            .borrow()
+            .values()
+            .choose_multiple(&mut rand::thread_rng(), self.sample_size)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut errors = 0;
+        let mut drifted = Vec::new();
+        let mut repairs: Vec<(PublicKeyBinary, Option<GatewayInfo>)> = Vec::new();
+        for cached in &sample {
+            let current = match iot_config_client
+                .resolve_gateway_info(&cached.address)
+                .await
+            {
+                Ok(current) => current,
+                Err(err) => {
+                    tracing::warn!(
+                        address = %cached.address,
+                        "failed to reconcile gateway: {err:?}"
+                    );
+                    errors += 1;
+                    continue;
+                }
+            };
+            let drift = drift(cached, current.as_ref());
+            if !drift.any() {
+                continue;
+            }
+            tracing::warn!(
+                address = %cached.address,
+                ?drift,
+                "gateway info drifted from iot config"
+            );
+            drifted.push(GatewayDrift {
+                pub_key: cached.address.clone(),
+                missing: drift.missing,
+                location: drift.location,
+                gain: drift.gain,
+                region: drift.region,
+            });
+            repairs.push((cached.address.clone(), current));
+        }
+
+        if self.repair && !repairs.is_empty() {
+            gateways.send_modify(|gateways| {
+                for (address, current) in repairs {
+                    match current {
+                        Some(info) => gateways.insert(address, info),
+                        None => gateways.remove(&address),
+                    };
+                }
+            });
+        }
+
+        tracing::info!(
+            sampled = sample.len(),
+            drifted = drifted.len(),
+            errors,
+            repaired = self.repair,
+            "completed reconciling gateways"
+        );
+        telemetry::gateway_drift(sample.len(), &drifted);
+
+        let report = GatewayReconciliation {
+            timestamp: Utc::now(),
+            sampled: sample.len() as u64,
+            errors,
+            repaired: self.repair,
+            drifted,
+        };
+        self.reports_sink
+            .write(GatewayReconciliationV1::from(report), [])
+            .await?;
+        self.reports_sink.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_proto::Region;
+    use iot_config::gateway_info::GatewayMetadata;
+
+    fn gateway(location: Option<u64>, gain: i32, region: Region) -> GatewayInfo {
+        GatewayInfo {
+            address: "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6"
+                .parse()
+                .unwrap(),
+            metadata: location.map(|location| GatewayMetadata {
+                location,
+                elevation: 0,
+                gain,
+                region,
+                onboarded_at: None,
+                updated_at: None,
+            }),
+            is_full_hotspot: true,
+        }
+    }
+
+    #[test]
+    fn compares_location_gain_and_region() {
+        let cached = gateway(Some(1), 12, Region::Us915);
+        assert!(!drift(&cached, Some(&cached)).any());
+        assert_eq!(
+            Drift {
+                missing: true,
+                ..Default::default()
+            },
+            drift(&cached, None)
+        );
+        assert_eq!(
+            Drift {
+                gain: true,
+                region: true,
+                ..Default::default()
+            },
+            drift(&cached, Some(&gateway(Some(1), 20, Region::Eu868)))
+        );
+        assert_eq!(
+            Drift {
+                location: true,
+                gain: true,
+                region: true,
+                ..Default::default()
+            },
+            drift(&cached, Some(&gateway(None, 12, Region::Us915)))
+        );
+    }
+}
@@ -1,11 +1,16 @@
-use crate::Settings;
-use chrono::Duration;
-use futures::stream::StreamExt;
+use crate::{
+    gateway_reconciler::GatewayReconciler, location_changes, report_store::ReportStore, reverify,
+    telemetry, Settings,
+};
+use chrono::{DateTime, Duration, Utc};
+use file_store::file_sink::FileSinkClient;
+use futures::stream::{Stream, StreamExt};
 use helium_crypto::PublicKeyBinary;
 use iot_config::{
     client::{Client as IotConfigClient, ClientError as IotConfigClientError},
     gateway_info::{GatewayInfo, GatewayInfoResolver},
 };
+use sqlx::PgPool;
 use std::collections::HashMap;
 use tokio::sync::watch;
 use tokio::time;
@@ -14,10 +19,25 @@ pub type GatewayMap = HashMap<PublicKeyBinary, GatewayInfo>;
 pub type MessageSender = watch::Sender<GatewayMap>;
 pub type MessageReceiver = watch::Receiver<GatewayMap>;
 
+/// Gateway changes are polled from this long before the previous poll, so
+/// updates committed late or stamped by a skewed clock aren't missed
+const CHANGE_POLL_OVERLAP: Duration = Duration::minutes(1);
+
 pub struct GatewayUpdater {
     iot_config_client: IotConfigClient,
-    refresh_interval: Duration,
+    refresh_interval: time::Duration,
+    /// Interval of the polls of the gateways updated in iot config, None
+    /// when only refreshing the whole map
+    change_poll_interval: Option<time::Duration>,
+    /// Start of the last refresh or change poll
+    changes_since: DateTime<Utc>,
     sender: MessageSender,
+    pool: PgPool,
+    flap_window: Duration,
+    flap_threshold: u64,
+    reconciler: GatewayReconciler,
+    report_store: ReportStore,
+    reverify_requests: reverify::RequestReceiver,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -26,21 +46,38 @@ pub enum GatewayUpdaterError {
     IotConfigClient(#[from] IotConfigClientError),
     #[error("error sending on channel")]
     SendError(#[from] watch::error::SendError<GatewayMap>),
+    #[error("database error")]
+    DbError(#[from] sqlx::Error),
+    #[error("file store error")]
+    FileStore(#[from] file_store::Error),
 }
 
 impl GatewayUpdater {
     pub async fn from_settings(
         settings: &Settings,
         mut iot_config_client: IotConfigClient,
+        pool: PgPool,
+        reconciliation_sink: FileSinkClient,
+        report_store: ReportStore,
+        reverify_requests: reverify::RequestReceiver,
     ) -> Result<(MessageReceiver, Self), GatewayUpdaterError> {
+        let changes_since = Utc::now();
         let gateway_map = refresh_gateways(&mut iot_config_client).await?;
         let (sender, receiver) = watch::channel(gateway_map);
         Ok((
             receiver,
             Self {
                 iot_config_client,
-                refresh_interval: settings.gateway_refresh_interval(),
+                refresh_interval: settings.gateway_refresh_interval,
+                change_poll_interval: settings.gateway_change_poll_interval(),
+                changes_since,
                 sender,
+                pool,
+                flap_window: settings.location_flap_window(),
+                flap_threshold: settings.location_flap_threshold,
+                reconciler: GatewayReconciler::from_settings(settings, reconciliation_sink),
+                report_store,
+                reverify_requests,
             },
         ))
     }
@@ -48,11 +85,20 @@ impl GatewayUpdater {
     pub async fn run(mut self, shutdown: &triggered::Listener) -> Result<(), GatewayUpdaterError> {
         tracing::info!("starting gateway_updater");
 
-        let mut trigger_timer = time::interval(
-            self.refresh_interval
-                .to_std()
-                .expect("valid interval in seconds"),
+        let mut trigger_timer = time::interval(self.refresh_interval);
+        // the map was just refreshed, the first reconciliation waits for its
+        // schedule
+        let mut reconcile_timer = self.reconciler.scheduler();
+        // the change poll timer is never polled when polling is disabled
+        let polling_changes = self.change_poll_interval.is_some();
+        let change_poll_interval = self
+            .change_poll_interval
+            .unwrap_or(time::Duration::from_secs(60));
+        let mut change_poll_timer = time::interval_at(
+            time::Instant::now() + change_poll_interval,
+            change_poll_interval,
         );
+        change_poll_timer.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
 
         loop {
             if shutdown.is_triggered() {
@@ -62,23 +108,155 @@ impl GatewayUpdater {
 
             tokio::select! {
                 _ = trigger_timer.tick() => self.handle_refresh_tick().await?,
+                _ = change_poll_timer.tick(), if polling_changes => {
+                    self.handle_change_poll_tick().await
+                }
+                _ = reconcile_timer.tick() => self
+                    .reconciler
+                    .reconcile(&mut self.iot_config_client, &self.sender)
+                    .await?,
+                Some(request) = self.reverify_requests.recv() => {
+                    self.handle_reverify_request(request).await
+                }
                 _ = shutdown.clone() => return Ok(()),
             }
         }
     }
 
+    /// Handle a re-verification, returning its failure to the requester
+    /// rather than stopping the updater
+    async fn handle_reverify_request(&mut self, request: reverify::ReverifyRequest) {
+        let result = reverify::handle(
+            &self.pool,
+            &self.report_store,
+            &mut self.iot_config_client,
+            &self.sender,
+            &request.gateway,
+            &request.reason,
+        )
+        .await;
+        if let Err(err) = &result {
+            tracing::warn!(gateway = %request.gateway, ?err, "failed to re-verify gateway");
+        }
+        // the requester may have gone, the re-verification stands
+        let _ = request.response.send(result);
+    }
+
     async fn handle_refresh_tick(&mut self) -> Result<(), GatewayUpdaterError> {
         tracing::info!("handling refresh tick");
+        let refresh_start = Utc::now();
         let updated_gateway_map = refresh_gateways(&mut self.iot_config_client).await?;
         let gateway_count = updated_gateway_map.len();
         if gateway_count > 0 {
             tracing::info!("completed refreshing gateways, total gateways: {gateway_count}");
+            // location tracking is best effort, a failure to record the
+            // changes mustn't keep the gateway map from being refreshed
+            if let Err(err) = self.track_location_changes(&updated_gateway_map).await {
+                tracing::error!(?err, "failed to track gateway location changes");
+            }
             self.sender.send(updated_gateway_map)?;
+            self.changes_since = refresh_start;
         } else {
             tracing::warn!("failed to refresh gateways, empty map...");
         }
         Ok(())
     }
+
+    /// Refresh the cached info of the gateways updated in iot config since
+    /// the last refresh or poll, recording the locations they changed. The
+    /// poll only moves on once every change was received, an interrupted one
+    /// is polled again from the same time.
+    async fn handle_change_poll_tick(&mut self) {
+        let poll_start = Utc::now();
+        let since = self.changes_since - CHANGE_POLL_OVERLAP;
+        let changes = match self.iot_config_client.stream_gateway_changes(since).await {
+            Ok(changes) => changes,
+            Err(err) => {
+                tracing::warn!(?err, "failed to poll gateway changes, retrying next tick");
+                return;
+            }
+        };
+        let (changed, complete) = collect_changes(changes).await;
+        if complete {
+            self.changes_since = poll_start;
+        }
+        apply_changes(&self.pool, &self.sender, changed, poll_start).await;
+    }
+
+    /// Record the locations that changed since the last refresh and flag the
+    /// gateways flapping between locations
+    async fn track_location_changes(
+        &self,
+        updated_gateway_map: &GatewayMap,
+    ) -> Result<(), GatewayUpdaterError> {
+        let now = Utc::now();
+        let changes = location_changes::diff(&self.sender.borrow(), updated_gateway_map);
+        location_changes::insert_all(&self.pool, &changes, now).await?;
+
+        let since = now - self.flap_window;
+        let flap_counts = location_changes::flap_counts(&self.pool, since).await?;
+        let mut flapping = 0;
+        for (address, flaps) in &flap_counts {
+            if *flaps >= self.flap_threshold {
+                flapping += 1;
+                tracing::warn!(%address, flaps, "gateway location flapping");
+            }
+        }
+        tracing::info!(
+            changes = changes.len(),
+            flapping,
+            "completed tracking gateway location changes"
+        );
+        telemetry::location_changes(changes.len() as u64, flap_counts.values().sum(), flapping);
+
+        location_changes::purge(&self.pool, since).await?;
+        Ok(())
+    }
+}
+
+/// The gateways of `changes` up to the first error, and whether there was
+/// none
+async fn collect_changes(
+    mut changes: impl Stream<Item = Result<GatewayInfo, IotConfigClientError>> + Unpin,
+) -> (GatewayMap, bool) {
+    let mut changed = GatewayMap::new();
+    while let Some(change) = changes.next().await {
+        match change {
+            Ok(gateway_info) => {
+                changed.insert(gateway_info.address.clone(), gateway_info);
+            }
+            Err(err) => {
+                tracing::warn!(?err, "gateway changes poll interrupted, retrying next tick");
+                return (changed, false);
+            }
+        }
+    }
+    (changed, true)
+}
+
+/// Refresh the gateways of `changed` in the map. Like on a full refresh,
+/// recording the locations they changed is best effort and doesn't keep the
+/// map from being refreshed.
+async fn apply_changes(
+    pool: &PgPool,
+    sender: &MessageSender,
+    changed: GatewayMap,
+    changed_at: DateTime<Utc>,
+) {
+    if changed.is_empty() {
+        return;
+    }
+    let location_changes = location_changes::diff(&sender.borrow(), &changed);
+    if let Err(err) = location_changes::insert_all(pool, &location_changes, changed_at).await {
+        tracing::error!(?err, "failed to record gateway location changes");
+    }
+    tracing::info!(
+        gateways = changed.len(),
+        locations = location_changes.len(),
+        "refreshed gateways changed in iot config"
+    );
+    telemetry::gateway_changes(changed.len() as u64);
+    sender.send_modify(|gateways| gateways.extend(changed));
 }
 
 pub async fn refresh_gateways(
@@ -92,3 +270,82 @@ pub async fn refresh_gateways(
     }
     Ok(gateways)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use helium_proto::Region;
+    use iot_config::gateway_info::GatewayMetadata;
+    use tonic::Status;
+
+    fn gateway(key: u8, location: u64) -> GatewayInfo {
+        GatewayInfo {
+            address: PublicKeyBinary::from(vec![key]),
+            metadata: Some(GatewayMetadata {
+                location,
+                elevation: 0,
+                gain: 12,
+                region: Region::Us915,
+                onboarded_at: None,
+                updated_at: None,
+            }),
+            is_full_hotspot: true,
+        }
+    }
+
+    fn location(gateways: &GatewayMap, key: u8) -> Option<u64> {
+        gateways
+            .get(&PublicKeyBinary::from(vec![key]))
+            .and_then(|info| info.metadata.as_ref())
+            .map(|metadata| metadata.location)
+    }
+
+    #[tokio::test]
+    async fn collects_changes_up_to_an_error() {
+        let (changed, complete) =
+            collect_changes(stream::iter(vec![Ok(gateway(1, 1)), Ok(gateway(2, 2))])).await;
+        assert!(complete);
+        assert_eq!(changed.len(), 2);
+
+        let (changed, complete) = collect_changes(stream::iter(vec![
+            Ok(gateway(1, 1)),
+            Err(IotConfigClientError::Rpc(Status::internal("gone"))),
+            Ok(gateway(2, 2)),
+        ]))
+        .await;
+        assert!(!complete);
+        assert_eq!(location(&changed, 1), Some(1));
+        assert_eq!(location(&changed, 2), None);
+    }
+
+    #[sqlx::test]
+    async fn changes_refresh_the_map_when_locations_cant_be_recorded(pool: PgPool) {
+        let (sender, receiver) = watch::channel(
+            [(PublicKeyBinary::from(vec![1]), gateway(1, 1))]
+                .into_iter()
+                .collect(),
+        );
+        let changed: GatewayMap = [(PublicKeyBinary::from(vec![1]), gateway(1, 2))]
+            .into_iter()
+            .collect();
+
+        apply_changes(&pool, &sender, changed, Utc::now()).await;
+        assert_eq!(location(&receiver.borrow(), 1), Some(2));
+        let recorded: i64 = sqlx::query_scalar("select count(*) from location_changes")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(recorded, 1);
+
+        sqlx::query("drop table location_changes")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let changed: GatewayMap = [(PublicKeyBinary::from(vec![1]), gateway(1, 3))]
+            .into_iter()
+            .collect();
+        apply_changes(&pool, &sender, changed, Utc::now()).await;
+        assert_eq!(location(&receiver.borrow(), 1), Some(3));
+    }
+}
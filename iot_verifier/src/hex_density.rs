@@ -1,5 +1,6 @@
+use chrono::{DateTime, Utc};
 use file_store::SCALING_PRECISION;
-use h3o::{CellIndex, Resolution};
+use hex_utils::h3o::{CellIndex, Resolution};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
@@ -25,6 +26,11 @@ impl HexResConfig {
 
 type HexMap = HashMap<CellIndex, u64>;
 
+/// Weight of a gateway with the full witness target in a weighted map.
+/// Weighted maps count gateways in fractions of it, their density targets and
+/// limits are scaled to match.
+pub const WEIGHT_UNIT: u64 = 100;
+
 const MAX_RES: Resolution = Resolution::Eleven;
 const USED_RES: [Resolution; 7] = [
     Resolution::Ten,
@@ -70,23 +76,58 @@ pub trait HexDensityMap: Clone {
     async fn swap(&self, new_map: HashMap<u64, Decimal>);
 }
 
+#[derive(Debug, Default)]
+struct ScalingMap {
+    scales: HashMap<u64, Decimal>,
+    refreshed_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone)]
-pub struct SharedHexDensityMap(Arc<RwLock<HashMap<u64, Decimal>>>);
+pub struct SharedHexDensityMap(Arc<RwLock<ScalingMap>>);
 
 impl SharedHexDensityMap {
     pub fn new() -> Self {
-        Self(Arc::new(RwLock::new(HashMap::new())))
+        Self(Arc::new(RwLock::new(ScalingMap::default())))
+    }
+
+    /// The scale of `hex` and when the map was last swapped in
+    pub async fn get_with_refresh(&self, hex: u64) -> (Option<Decimal>, Option<DateTime<Utc>>) {
+        let map = self.0.read().await;
+        (map.scales.get(&hex).cloned(), map.refreshed_at)
+    }
+
+    /// The number of hexes of the map `new_map` drops or changes the scale
+    /// of by more than `max_delta`, and the number of hexes of the map
+    pub async fn count_changed(
+        &self,
+        new_map: &HashMap<u64, Decimal>,
+        max_delta: Decimal,
+    ) -> (usize, usize) {
+        let map = self.0.read().await;
+        (
+            changed_scales(&map.scales, new_map, max_delta),
+            map.scales.len(),
+        )
+    }
+
+    /// A copy of the whole map and when it was last swapped in
+    pub async fn snapshot(&self) -> (HashMap<u64, Decimal>, Option<DateTime<Utc>>) {
+        let map = self.0.read().await;
+        (map.scales.clone(), map.refreshed_at)
     }
 }
 
 #[async_trait::async_trait]
 impl HexDensityMap for SharedHexDensityMap {
     async fn get(&self, hex: u64) -> Option<Decimal> {
-        self.0.read().await.get(&hex).cloned()
+        self.0.read().await.scales.get(&hex).cloned()
     }
 
     async fn swap(&self, new_map: HashMap<u64, Decimal>) {
-        *self.0.write().await = new_map;
+        *self.0.write().await = ScalingMap {
+            scales: new_map,
+            refreshed_at: Some(Utc::now()),
+        };
     }
 }
 
@@ -95,28 +136,47 @@ pub struct GlobalHexMap {
     clipped_hexes: HexMap,
     unclipped_hexes: HexMap,
     asserted_hexes: Vec<CellIndex>,
+    /// Count of a single gateway
+    unit: u64,
 }
 
 impl GlobalHexMap {
+    /// Map counting each gateway by its presence
     pub fn new() -> Self {
+        Self::with_unit(1)
+    }
+
+    /// Map counting each gateway by a weight of up to [`WEIGHT_UNIT`]
+    pub fn weighted() -> Self {
+        Self::with_unit(WEIGHT_UNIT)
+    }
+
+    fn with_unit(unit: u64) -> Self {
         Self {
             clipped_hexes: HashMap::new(),
             unclipped_hexes: HashMap::new(),
             asserted_hexes: Vec::new(),
+            unit,
         }
     }
 
     pub fn increment_unclipped(&mut self, index: u64) {
-        if let Ok(cell) = CellIndex::try_from(index) {
-            if let Some(parent) = cell.parent(MAX_RES) {
+        self.increment_unclipped_by(index, self.unit)
+    }
+
+    /// Count a gateway with `weight` rather than a whole gateway, in
+    /// fractions of the unit of the map
+    pub fn increment_unclipped_by(&mut self, index: u64, weight: u64) {
+        if let Ok(cell) = hex_utils::cell(index) {
+            if let Ok(parent) = hex_utils::to_res(cell, MAX_RES) {
                 self.unclipped_hexes
                     .entry(parent)
-                    .and_modify(|count| *count += 1)
-                    .or_insert(1);
+                    .and_modify(|count| *count += weight)
+                    .or_insert(weight);
                 self.clipped_hexes
                     .entry(parent)
-                    .and_modify(|count| *count += 1)
-                    .or_insert(1);
+                    .and_modify(|count| *count += weight)
+                    .or_insert(weight);
                 self.asserted_hexes.push(cell);
             }
         }
@@ -131,6 +191,7 @@ impl GlobalHexMap {
             &mut self.unclipped_hexes,
             &mut self.clipped_hexes,
             starting_hexes,
+            self.unit,
         )
     }
 }
@@ -156,24 +217,29 @@ fn rollup_child_count(
         .or_insert(cell_count);
 }
 
-fn reduce_hex_res(unclipped: &mut HexMap, clipped: &mut HexMap, hex_list: Vec<CellIndex>) {
+fn reduce_hex_res(
+    unclipped: &mut HexMap,
+    clipped: &mut HexMap,
+    hex_list: Vec<CellIndex>,
+    unit: u64,
+) {
     let mut hexes_at_res: Vec<CellIndex> = hex_list;
     for res in USED_RES {
         std::mem::take(&mut hexes_at_res)
             .into_iter()
             .for_each(|cell| {
-                if let Some(parent) = cell.parent(res) {
+                if let Ok(parent) = hex_utils::to_res(cell, res) {
                     rollup_child_count(unclipped, clipped, cell, parent);
                     hexes_at_res.push(parent);
                 }
             });
-        let density_tgt = get_res_tgt(&res);
+        let density_tgt = get_res_tgt(&res) * unit;
         hexes_at_res = hexes_at_res
             .into_iter()
             .unique()
             .map(|parent_cell| {
                 let occupied_count = occupied_count(clipped, &parent_cell, density_tgt);
-                let limit = limit(&res, occupied_count);
+                let limit = limit(&res, occupied_count) * unit;
                 if let Some(count) = unclipped.get(&parent_cell) {
                     let actual = cmp::min(limit, *count);
                     clipped.insert(parent_cell, actual);
@@ -185,19 +251,20 @@ fn reduce_hex_res(unclipped: &mut HexMap, clipped: &mut HexMap, hex_list: Vec<Ce
 }
 
 fn occupied_count(cell_map: &HexMap, hex: &CellIndex, density_tgt: u64) -> u64 {
-    let k_ring = hex.grid_disk::<Vec<_>>(1);
-    k_ring.into_iter().fold(0, |count, cell| {
-        cell_map.get(&cell).map_or(count, |population| {
-            if *population >= density_tgt {
-                count + 1
-            } else {
-                count
-            }
+    hex_utils::k_ring(*hex, 1)
+        .into_iter()
+        .fold(0, |count, cell| {
+            cell_map.get(&cell).map_or(count, |population| {
+                if *population >= density_tgt {
+                    count + 1
+                } else {
+                    count
+                }
+            })
         })
-    })
 }
 
-fn limit(res: &h3o::Resolution, occupied_count: u64) -> u64 {
+fn limit(res: &Resolution, occupied_count: u64) -> u64 {
     let res_config = HIP17_RES_CONFIG.get(res).unwrap();
     let occupied_neighbor_diff = occupied_count.saturating_sub(res_config.neighbors);
     let max = cmp::max((occupied_neighbor_diff) + 1, 1);
@@ -208,12 +275,13 @@ pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decima
     let mut map: HashMap<u64, Decimal> = HashMap::new();
     for hex in &global_map.asserted_hexes {
         let scale: Decimal = SCALING_RES.iter().fold(dec!(1.0), |scale, res| {
-            hex.parent(*res).map_or(scale, |parent| {
+            hex_utils::to_res(*hex, *res).map_or(scale, |parent| {
                 match (
                     global_map.unclipped_hexes.get(&parent),
                     global_map.clipped_hexes.get(&parent),
                 ) {
-                    (Some(unclipped), Some(clipped)) => {
+                    // hexes of gateways weighted zero alone are left unscaled
+                    (Some(unclipped), Some(clipped)) if *unclipped > 0 => {
                         scale
                             * (Decimal::new(*clipped as i64, SCALING_PRECISION)
                                 / Decimal::new(*unclipped as i64, SCALING_PRECISION))
@@ -228,6 +296,20 @@ pub fn compute_hex_density_map(global_map: &GlobalHexMap) -> HashMap<u64, Decima
     map
 }
 
+/// The number of hexes of `old` missing from `new` or scaled more than
+/// `max_delta` apart in it
+pub fn changed_scales(
+    old: &HashMap<u64, Decimal>,
+    new: &HashMap<u64, Decimal>,
+    max_delta: Decimal,
+) -> usize {
+    old.iter()
+        .filter(|(hex, scale)| {
+            !matches!(new.get(hex), Some(new_scale) if (*new_scale - **scale).abs() <= max_delta)
+        })
+        .count()
+}
+
 fn get_res_tgt(res: &Resolution) -> u64 {
     HIP17_RES_CONFIG
         .get(res)
@@ -323,4 +405,64 @@ mod tests {
         ]);
         assert_eq!(hex_density_map, expected_map);
     }
+
+    #[test]
+    fn full_weights_scale_as_presence() {
+        let indexes: Vec<u64> = vec![
+            631210990515536895,
+            631210990515536895,
+            631210990515537919,
+            631210990515722239,
+            631210990515722239,
+            631210990515727359,
+            631210990516955647,
+        ];
+        let mut presence_map = GlobalHexMap::new();
+        let mut weighted_map = GlobalHexMap::weighted();
+        for index in &indexes {
+            presence_map.increment_unclipped(*index);
+            weighted_map.increment_unclipped_by(*index, WEIGHT_UNIT);
+        }
+        presence_map.reduce_global();
+        weighted_map.reduce_global();
+        assert_eq!(
+            compute_hex_density_map(&presence_map),
+            compute_hex_density_map(&weighted_map)
+        );
+
+        // a gateway barely witnessing counts for less than a whole one
+        let mut weighted_map = GlobalHexMap::weighted();
+        for (i, index) in indexes.iter().enumerate() {
+            let weight = if i == 0 {
+                WEIGHT_UNIT / 10
+            } else {
+                WEIGHT_UNIT
+            };
+            weighted_map.increment_unclipped_by(*index, weight);
+        }
+        weighted_map.reduce_global();
+        let weighted = compute_hex_density_map(&weighted_map);
+        let presence = compute_hex_density_map(&presence_map);
+        assert!(weighted[&631210990515536895] > presence[&631210990515536895]);
+    }
+
+    #[test]
+    fn dropped_and_moved_scales_are_changed() {
+        let old = HashMap::from([
+            (1, dec!(1.0)),
+            (2, dec!(0.5)),
+            (3, dec!(0.8)),
+            (4, dec!(0.2)),
+        ]);
+        // 1 barely moves, 2 drops to zero, 3 is gone and 5 is new
+        let new = HashMap::from([
+            (1, dec!(0.9)),
+            (2, dec!(0.0)),
+            (4, dec!(0.2)),
+            (5, dec!(1.0)),
+        ]);
+        assert_eq!(changed_scales(&old, &new, dec!(0.25)), 2);
+        assert_eq!(changed_scales(&old, &new, dec!(0.05)), 3);
+        assert_eq!(changed_scales(&HashMap::new(), &new, dec!(0.25)), 0);
+    }
 }
@@ -110,4 +110,53 @@ impl LastBeacon {
         .await?;
         Ok(())
     }
+
+    /// Record a beacon in the history the cadence monitor analyses
+    pub async fn insert_history<'c, E>(
+        executor: E,
+        id: &[u8],
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), LastBeaconError>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        sqlx::query(
+            r#"
+            insert into last_beacon_history (id, timestamp)
+            values ($1, $2)
+            on conflict do nothing
+            "#,
+        )
+        .bind(id)
+        .bind(timestamp)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn history_since<'c, E>(
+        deadline: DateTime<Utc>,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres> + 'c,
+    {
+        sqlx::query_as::<_, Self>(
+            r#" select * from last_beacon_history where timestamp >= $1 order by timestamp; "#,
+        )
+        .bind(deadline)
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn purge_history<'c, E>(before: DateTime<Utc>, executor: E) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        sqlx::query(r#" delete from last_beacon_history where timestamp < $1; "#)
+            .bind(before)
+            .execute(executor)
+            .await?;
+        Ok(())
+    }
 }
@@ -1,20 +1,38 @@
+pub mod beacon_cadence;
+pub mod catchup;
+pub mod density_service;
 pub mod entropy;
 pub mod entropy_loader;
 pub mod gateway_cache;
+pub mod gateway_reconciler;
 pub mod gateway_updater;
 mod hex_density;
 pub mod last_beacon;
 pub mod loader;
+pub mod location_changes;
 pub mod meta;
+pub mod online_migrations;
 pub mod packet_loader;
+pub mod path_loss;
 pub mod poc;
+pub mod poc_indexer;
 pub mod poc_report;
+pub mod poc_status_service;
+pub mod proto;
 pub mod purger;
 pub mod region_cache;
+pub mod report_store;
+pub mod reverify;
+pub mod reward_classes;
+pub mod reward_scale;
 pub mod reward_share;
 pub mod rewarder;
 pub mod runner;
 mod settings;
 pub mod telemetry;
+pub mod test_gateways;
 pub mod tx_scaler;
+pub mod witness_counts;
+pub mod witness_quality;
+pub mod witness_quality_service;
 pub use settings::Settings;
@@ -2,6 +2,7 @@ use crate::{
     gateway_cache::GatewayCache,
     meta::Meta,
     poc_report::{InsertBindings, IotStatus, Report, ReportType},
+    report_store::ReportStore,
     telemetry::LoaderMetricTracker,
     Settings,
 };
@@ -37,6 +38,7 @@ pub struct Loader {
     deny_list_latest_url: String,
     deny_list_trigger_interval: Duration,
     deny_list: DenyList,
+    report_store: ReportStore,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -56,7 +58,11 @@ pub enum ValidGatewayResult {
 }
 
 impl Loader {
-    pub async fn from_settings(settings: &Settings, pool: PgPool) -> Result<Self, NewLoaderError> {
+    pub async fn from_settings(
+        settings: &Settings,
+        pool: PgPool,
+        report_store: ReportStore,
+    ) -> Result<Self, NewLoaderError> {
         tracing::info!("from_settings verifier loader");
         let ingest_store = FileStore::from_settings(&settings.ingest).await?;
         let poll_time = settings.poc_loader_poll_time();
@@ -74,6 +80,7 @@ impl Loader {
             deny_list_latest_url: settings.denylist.denylist_url.clone(),
             deny_list_trigger_interval: settings.denylist.trigger_interval(),
             deny_list,
+            report_store,
         })
     }
 
@@ -101,7 +108,7 @@ impl Loader {
                     }
                 },
                 _ = report_timer.tick() => match self.handle_report_tick(gateway_cache).await {
-                    Ok(()) => (),
+                    Ok(()) => poc_metrics::status::heartbeat("loader", "poc"),
                     Err(err) => {
                         tracing::error!("loader error, report_tick triggered: {err:?}");
                     }
@@ -164,10 +171,26 @@ impl Loader {
         self.process_window(gateway_cache, after, before).await?;
         Meta::update_last_timestamp(&self.pool, REPORTS_META_NAME, Some(before)).await?;
         Report::pending_beacons_to_ready(&self.pool, now).await?;
+        self.record_ingest_status(before).await?;
         tracing::info!("completed handling poc_report tick");
         Ok(())
     }
 
+    /// Beacons and witnesses are fully processed once loaded, verification
+    /// happens on the reports left in the db
+    async fn record_ingest_status(&self, processed_up_to: DateTime<Utc>) -> anyhow::Result<()> {
+        let processed_up_to = processed_up_to.timestamp() as u64;
+        for (file_type, report_type) in [
+            (FileType::IotBeaconIngestReport, ReportType::Beacon),
+            (FileType::IotWitnessIngestReport, ReportType::Witness),
+        ] {
+            let unverified = Report::count_unverified(&self.pool, report_type).await?;
+            poc_metrics::status::processed_up_to(file_type.to_str(), processed_up_to);
+            poc_metrics::status::pending(file_type.to_str()).set(unverified as usize);
+        }
+        Ok(())
+    }
+
     async fn process_window(
         &self,
         gateway_cache: &GatewayCache,
@@ -328,6 +351,7 @@ impl Loader {
                     }
                 }
                 if !inserts.is_empty() {
+                    let inserts = self.report_store.offload(inserts).await;
                     match Report::bulk_insert(tx.lock().await.deref_mut(), inserts).await {
                         Ok(_) => (),
                         Err(err) => tracing::warn!("error whilst inserting report to db,  error: {err:?}"),
@@ -364,9 +388,11 @@ impl Loader {
                             remote_entropy: beacon.report.remote_entropy,
                             packet_data,
                             buf: buf.to_vec(),
+                            report_key: None,
                             received_ts: beacon.received_timestamp,
                             report_type: ReportType::Beacon,
                             status: IotStatus::Pending,
+                            gateway: beacon.report.pub_key,
                         };
                         metrics.increment_beacons();
                         if let Some(xor_data) = xor_data {
@@ -403,9 +429,11 @@ impl Loader {
                                         remote_entropy: Vec::<u8>::with_capacity(0),
                                         packet_data,
                                         buf: buf.to_vec(),
+                                        report_key: None,
                                         received_ts: witness.received_timestamp,
                                         report_type: ReportType::Witness,
                                         status: IotStatus::Ready,
+                                        gateway: witness.report.pub_key,
                                     };
                                     metrics.increment_witnesses();
                                     Ok(Some(res))
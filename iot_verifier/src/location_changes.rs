@@ -0,0 +1,191 @@
+//! History of gateway location changes seen by the gateway updater, for
+//! flagging gateways whose location flaps while an assertion syncs.
+
+use crate::gateway_updater::GatewayMap;
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use iot_config::gateway_info::GatewayInfo;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationChange {
+    pub address: PublicKeyBinary,
+    pub old_location: Option<u64>,
+    pub new_location: Option<u64>,
+}
+
+fn location(info: &GatewayInfo) -> Option<u64> {
+    info.metadata.as_ref().map(|metadata| metadata.location)
+}
+
+/// Location changes of the gateways in both `old` and `new`, in address
+/// order. Gateways joining or leaving the map have not moved.
+pub fn diff(old: &GatewayMap, new: &GatewayMap) -> Vec<LocationChange> {
+    let mut changes: Vec<_> = new
+        .iter()
+        .filter_map(|(address, info)| {
+            let old_location = location(old.get(address)?);
+            let new_location = location(info);
+            (old_location != new_location).then(|| LocationChange {
+                address: address.clone(),
+                old_location,
+                new_location,
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.address.as_ref().cmp(b.address.as_ref()));
+    changes
+}
+
+pub async fn insert_all(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    changes: &[LocationChange],
+    changed_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let mut txn = pool.begin().await?;
+    for change in changes {
+        sqlx::query(
+            r#"
+            insert into location_changes (address, old_location, new_location, changed_at)
+            values ($1, $2, $3, $4)
+            on conflict (address, changed_at) do nothing
+            "#,
+        )
+        .bind(change.address.as_ref())
+        .bind(change.old_location.map(|location| location as i64))
+        .bind(change.new_location.map(|location| location as i64))
+        .bind(changed_at)
+        .execute(&mut txn)
+        .await?;
+    }
+    txn.commit().await
+}
+
+// a change is a flap when it moves a gateway back to where the previous
+// change moved it from
+const FLAPS_SQL: &str = r#"
+    select address from (
+        select address, new_location, lag(old_location) over (
+            partition by address order by changed_at
+        ) as previous_location
+        from location_changes
+        where changed_at >= $1
+    ) changes
+    where new_location is not distinct from previous_location
+"#;
+
+/// Number of flaps of every gateway that flapped since `since`. A flap is a
+/// change back to the location a gateway just left. Flap counts are
+/// informational, no report is rejected because of them
+pub async fn flap_counts<'c, E>(
+    executor: E,
+    since: DateTime<Utc>,
+) -> Result<HashMap<PublicKeyBinary, u64>, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    let query = format!("select address, count(*) from ({FLAPS_SQL}) flaps group by address");
+    let counts = sqlx::query_as::<_, (Vec<u8>, i64)>(&query)
+        .bind(since)
+        .fetch_all(executor)
+        .await?;
+    Ok(counts
+        .into_iter()
+        .map(|(address, count)| (PublicKeyBinary::from(address), count as u64))
+        .collect())
+}
+
+/// Number of flaps of `address` since `since`
+pub async fn flap_count<'c, E>(
+    executor: E,
+    address: &PublicKeyBinary,
+    since: DateTime<Utc>,
+) -> Result<u64, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    let query = format!("select count(*) from ({FLAPS_SQL}) flaps where address = $2");
+    let count = sqlx::query_scalar::<_, i64>(&query)
+        .bind(since)
+        .bind(address.as_ref())
+        .fetch_one(executor)
+        .await?;
+    Ok(count as u64)
+}
+
+pub async fn purge<'c, E>(executor: E, before: DateTime<Utc>) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query("delete from location_changes where changed_at < $1")
+        .bind(before)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_proto::Region;
+    use iot_config::gateway_info::GatewayMetadata;
+
+    fn gateway(address: &PublicKeyBinary, location: Option<u64>) -> GatewayInfo {
+        GatewayInfo {
+            address: address.clone(),
+            metadata: location.map(|location| GatewayMetadata {
+                location,
+                elevation: 0,
+                gain: 12,
+                region: Region::Us915,
+                onboarded_at: None,
+                updated_at: None,
+            }),
+            is_full_hotspot: true,
+        }
+    }
+
+    #[test]
+    fn diffs_locations_of_known_gateways() {
+        let keys: Vec<PublicKeyBinary> = [
+            "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6",
+            "112DJZiXvZ8FduiWrEi8siE3wJX6hpRjjtwbavyXUDkgutEUSLAE",
+            "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf",
+            "112uuvztDziVQyLEvFTWoCrTbQDnUpbzFjo8Vpz3xPvhfmjr3rmD",
+        ]
+        .iter()
+        .map(|key| key.parse().unwrap())
+        .collect();
+        let old: GatewayMap = [
+            (keys[0].clone(), gateway(&keys[0], Some(1))),
+            (keys[1].clone(), gateway(&keys[1], Some(2))),
+            (keys[2].clone(), gateway(&keys[2], Some(3))),
+        ]
+        .into_iter()
+        .collect();
+        let new: GatewayMap = [
+            (keys[0].clone(), gateway(&keys[0], Some(1))),
+            (keys[1].clone(), gateway(&keys[1], Some(4))),
+            (keys[2].clone(), gateway(&keys[2], None)),
+            (keys[3].clone(), gateway(&keys[3], Some(5))),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut expected = vec![
+            LocationChange {
+                address: keys[1].clone(),
+                old_location: Some(2),
+                new_location: Some(4),
+            },
+            LocationChange {
+                address: keys[2].clone(),
+                old_location: Some(3),
+                new_location: None,
+            },
+        ];
+        expected.sort_by(|a, b| a.address.as_ref().cmp(b.address.as_ref()));
+        assert_eq!(expected, diff(&old, &new));
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -1,18 +1,35 @@
 use crate::entropy_loader::EntropyLoader;
-use anyhow::{Error, Result};
+use anyhow::{anyhow, Error, Result};
 use clap::Parser;
+use db_store::{dual_write, feature_flags, online_migration, reward_holds};
 use file_store::{
     entropy_report::EntropyReport, file_info_poller::LookbackBehavior, file_sink, file_source,
-    file_upload, iot_packet::IotValidPacket, FileStore, FileType,
+    file_upload, iot_packet::IotValidPacket, poc_archive::PocArchive, FileStore, FileType,
 };
 use futures::TryFutureExt;
 use iot_config::client::Client as IotConfigClient;
 use iot_verifier::{
-    entropy_loader, gateway_cache::GatewayCache, gateway_updater::GatewayUpdater, loader,
-    packet_loader, purger, region_cache::RegionCache, rewarder::Rewarder, runner, telemetry,
-    tx_scaler::Server as DensityScaler, Settings,
+    beacon_cadence::BeaconCadenceMonitor,
+    density_service::DensityService,
+    entropy_loader,
+    gateway_cache::GatewayCache,
+    gateway_updater::GatewayUpdater,
+    loader, online_migrations, packet_loader,
+    poc_indexer::PocIndexer,
+    poc_status_service::PocStatusService,
+    purger,
+    region_cache::RegionCache,
+    report_store::ReportStore,
+    reverify::{self, ReverifyService},
+    rewarder::Rewarder,
+    runner, telemetry,
+    tx_scaler::Server as DensityScaler,
+    witness_quality_service::WitnessQualityService,
+    Settings,
 };
+use poc_metrics::{preflight::Preflight, status::supervise};
 use price::PriceTracker;
+use sqlx::{Pool, Postgres};
 use std::path;
 use tokio::signal;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -42,18 +59,33 @@ impl Cli {
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Server),
+    FeatureFlag(FeatureFlag),
+    OnlineMigration(OnlineMigration),
+    RewardHold(RewardHold),
+    Reverify(Reverify),
+    DualWrite(DualWrite),
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::FeatureFlag(cmd) => cmd.run(&settings).await,
+            Self::OnlineMigration(cmd) => cmd.run(&settings).await,
+            Self::RewardHold(cmd) => cmd.run(&settings).await,
+            Self::Reverify(cmd) => cmd.run(&settings).await,
+            Self::DualWrite(cmd) => cmd.run(&settings).await,
         }
     }
 }
 
 #[derive(Debug, clap::Args)]
-pub struct Server {}
+pub struct Server {
+    /// Only report what the purger would purge, writing and deleting
+    /// nothing, regardless of the `purger_dry_run` setting
+    #[clap(long)]
+    purger_dry_run: bool,
+}
 
 impl Server {
     pub async fn run(&self, settings: &Settings) -> Result<()> {
@@ -80,23 +112,99 @@ impl Server {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown.clone())
             .await?;
+
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check("ingest bucket", settings.ingest.check_read())
+            .await;
+        preflight
+            .check("packet ingest bucket", settings.packet_ingest.check_read())
+            .await;
+        preflight
+            .check("entropy bucket", settings.entropy.check_read())
+            .await;
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        if let Some(report_blobs) = &settings.report_blobs {
+            preflight
+                .check("report blobs bucket", report_blobs.check_write())
+                .await;
+        }
+        preflight
+            .check("price bucket", settings.price_tracker.check_store())
+            .await;
+        preflight
+            .check("iot config", settings.iot_config_client.check_reachable())
+            .await;
+        preflight.check_result(
+            "iot config keypair",
+            settings.iot_config_client.signing_keypair(),
+        );
+        if let Some(density_api) = &settings.density_api {
+            preflight.check_result("density api keypair", density_api.signing_keypair());
+        }
+        preflight.finish()?;
+
         sqlx::migrate!().run(&pool).await?;
+        // capture the writes to tables the migrations created while moving
+        // to the shadow database
+        dual_write::refresh_triggers(&pool).await?;
 
         telemetry::initialize(&pool).await?;
 
-        let iot_config_client = IotConfigClient::from_settings(&settings.iot_config_client)?;
-
-        let (gateway_updater_receiver, gateway_updater) =
-            GatewayUpdater::from_settings(settings, iot_config_client.clone()).await?;
-        let gateway_cache = GatewayCache::new(gateway_updater_receiver.clone());
+        let (feature_flags, feature_flags_join_handle) = feature_flags::start(
+            pool.clone(),
+            feature_flags::DEFAULT_REFRESH_INTERVAL,
+            shutdown.clone(),
+        )
+        .await?;
 
-        let region_cache = RegionCache::from_settings(settings, iot_config_client.clone())?;
+        let iot_config_client = IotConfigClient::from_settings(&settings.iot_config_client)?;
 
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
             file_upload::FileUpload::from_settings(&settings.output, file_upload_rx).await?;
 
+        let report_store = ReportStore::from_settings(settings).await?;
+
         let store_base_path = std::path::Path::new(&settings.cache);
+        // Gateway reconciliation reports
+        let (gateway_reconciliation_sink, mut gateway_reconciliation_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotGatewayReconciliation,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_gateway_reconciliation"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .auto_commit(false)
+            .create()
+            .await?;
+
+        // re-verifications requested through the api are handled by the
+        // gateway updater
+        let (reverify_sender, reverify_requests) = reverify::channel();
+        let (gateway_updater_receiver, gateway_updater) = GatewayUpdater::from_settings(
+            settings,
+            iot_config_client.clone(),
+            pool.clone(),
+            gateway_reconciliation_sink.clone(),
+            report_store.clone(),
+            reverify_requests,
+        )
+        .await?;
+        let gateway_cache = GatewayCache::new(gateway_updater_receiver.clone());
+
+        let region_cache = RegionCache::from_settings(settings, iot_config_client.clone())?;
+
         // Gateway reward shares sink
         let (rewards_sink, mut gateway_rewards_server) = file_sink::FileSinkBuilder::new(
             FileType::IotRewardShare,
@@ -121,12 +229,111 @@ impl Server {
         .create()
         .await?;
 
+        // Beacon cadence reports
+        let (beacon_cadence_sink, mut beacon_cadence_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotBeaconCadenceReport,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_beacon_cadence_report"),
+            shutdown.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Transmit scaling map comparisons
+        let (hex_scale_comparison_sink, mut hex_scale_comparison_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotHexScaleComparison,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_hex_scale_comparison"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .auto_commit(false)
+            .create()
+            .await?;
+
+        // Snapshots of the transmit scaling maps swapped in
+        let (hex_density_snapshot_sink, mut hex_density_snapshot_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotHexDensitySnapshot,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_hex_density_snapshot"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .auto_commit(false)
+            .create()
+            .await?;
+
+        // Witness quality of gateways, written with the rewards
+        let (witness_quality_sink, mut witness_quality_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotWitnessQuality,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_witness_quality"),
+            shutdown.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Class of every gateway rewarded for pocs, written with the rewards
+        let (gateway_class_sink, mut gateway_class_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotGatewayRewardClass,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_gateway_reward_class"),
+            shutdown.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Rewards left unallocated, written with the rewards
+        let (unallocated_rewards_sink, mut unallocated_rewards_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotUnallocatedReward,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_unallocated_reward"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .auto_commit(false)
+            .create()
+            .await?;
+
+        // the verifier halts on any of its sinks failing to store a write,
+        // those of the runner, loaders and purger halt them
+        let sink_failure = file_sink::halt_on_failure(
+            vec![
+                gateway_reconciliation_sink,
+                rewards_sink.clone(),
+                reward_manifests_sink.clone(),
+                beacon_cadence_sink.clone(),
+                hex_scale_comparison_sink.clone(),
+                hex_density_snapshot_sink.clone(),
+                witness_quality_sink.clone(),
+                gateway_class_sink.clone(),
+                unallocated_rewards_sink.clone(),
+            ],
+            shutdown.clone(),
+        );
+
         let rewarder = Rewarder {
             pool: pool.clone(),
             rewards_sink,
             reward_manifests_sink,
+            witness_quality_sink,
+            gateway_class_sink,
+            unallocated_rewards_sink,
             reward_period_hours: settings.rewards,
             reward_offset: settings.reward_offset_duration(),
+            reward_scale_window: settings.reward_scale_window(),
+            witness_quality_window: settings.witness_quality_window(),
+            reward_classes: settings.reward_classes.clone(),
+            feature_flags: feature_flags.clone(),
         };
 
         // setup the entropy loader continious source
@@ -163,38 +370,135 @@ impl Server {
                 .await?;
 
         // init da processes
-        let mut loader = loader::Loader::from_settings(settings, pool.clone()).await?;
-        let mut runner = runner::Runner::from_settings(settings, pool.clone()).await?;
-        let purger = purger::Purger::from_settings(settings, pool.clone()).await?;
-        let mut density_scaler =
-            DensityScaler::from_settings(settings, pool, gateway_updater_receiver.clone()).await?;
+        let mut loader =
+            loader::Loader::from_settings(settings, pool.clone(), report_store.clone()).await?;
+        let mut runner =
+            runner::Runner::from_settings(settings, pool.clone(), report_store.clone()).await?;
+        let mut purger =
+            purger::Purger::from_settings(settings, pool.clone(), report_store).await?;
+        if self.purger_dry_run {
+            purger = purger.dry_run(true);
+        }
+        let beacon_cadence_monitor =
+            BeaconCadenceMonitor::from_settings(settings, pool.clone(), beacon_cadence_sink);
+        let mut density_scaler = DensityScaler::from_settings(
+            settings,
+            pool.clone(),
+            gateway_updater_receiver.clone(),
+            hex_scale_comparison_sink,
+            hex_density_snapshot_sink,
+            feature_flags,
+        )
+        .await?;
         let (price_tracker, price_receiver) =
             PriceTracker::start(&settings.price_tracker, shutdown.clone()).await?;
+        let dual_write_mirror = mirror_shadow_database(settings, pool.clone(), shutdown.clone());
+
+        let poc_archive = PocArchive::new(FileStore::from_settings(&settings.output).await?);
+        let poc_indexer = async {
+            if settings.index_pocs {
+                PocIndexer::new(poc_archive.clone(), pool.clone())
+                    .run(&shutdown)
+                    .await
+            } else {
+                Ok(())
+            }
+        };
+
+        // Hex density, witness quality, poc status and reverify api
+        let density_services = settings
+            .density_api
+            .as_ref()
+            .map(|density_settings| -> Result<_> {
+                Ok((
+                    DensityService::new(density_settings, &density_scaler)?,
+                    WitnessQualityService::new(
+                        density_settings,
+                        pool.clone(),
+                        settings.witness_quality_window(),
+                    )?,
+                    PocStatusService::new(density_settings, poc_archive.clone())?,
+                    ReverifyService::new(density_settings, reverify_sender)?,
+                ))
+            })
+            .transpose()?;
+        let density_api = async {
+            match (density_services, &settings.density_api) {
+                (
+                    Some((density, witness_quality, poc_status, reverify)),
+                    Some(density_settings),
+                ) => {
+                    density
+                        .serve(
+                            witness_quality,
+                            poc_status,
+                            reverify,
+                            density_settings,
+                            shutdown.clone(),
+                        )
+                        .await
+                }
+                _ => Ok(()),
+            }
+        };
 
         tokio::try_join!(
             db_join_handle.map_err(Error::from),
+            feature_flags_join_handle.map_err(Error::from),
             gateway_updater.run(&shutdown).map_err(Error::from),
+            gateway_reconciliation_server.run().map_err(Error::from),
             gateway_rewards_server.run().map_err(Error::from),
             reward_manifests_server.run().map_err(Error::from),
+            witness_quality_server.run().map_err(Error::from),
+            gateway_class_server.run().map_err(Error::from),
+            unallocated_rewards_server.run().map_err(Error::from),
+            sink_failure.map_err(Error::from),
             file_upload.run(&shutdown).map_err(Error::from),
-            runner.run(
-                file_upload_tx.clone(),
-                &gateway_cache,
-                &region_cache,
-                density_scaler.hex_density_map(),
-                &shutdown
+            supervise(
+                "poc",
+                "runner",
+                runner.run(
+                    file_upload_tx.clone(),
+                    &gateway_cache,
+                    &region_cache,
+                    density_scaler.hex_density_map(),
+                    &shutdown
+                )
             ),
-            entropy_loader.run(entropy_loader_receiver, &shutdown),
-            loader.run(&shutdown, &gateway_cache),
-            packet_loader.run(
-                pk_loader_receiver,
-                &shutdown,
-                &gateway_cache,
-                file_upload_tx.clone()
+            supervise(
+                "loader",
+                "entropy",
+                entropy_loader.run(entropy_loader_receiver, &shutdown)
             ),
-            purger.run(&shutdown),
-            rewarder.run(price_tracker, &shutdown),
-            density_scaler.run(&shutdown).map_err(Error::from),
+            supervise("loader", "poc", loader.run(&shutdown, &gateway_cache)),
+            supervise(
+                "loader",
+                "packet",
+                packet_loader.run(
+                    pk_loader_receiver,
+                    &shutdown,
+                    &gateway_cache,
+                    file_upload_tx.clone()
+                )
+            ),
+            supervise("poc", "purger", purger.run(&shutdown)),
+            beacon_cadence_monitor.run(&shutdown),
+            beacon_cadence_server.run().map_err(Error::from),
+            supervise(
+                "rewards",
+                "rewarder",
+                rewarder.run(price_tracker, &shutdown)
+            ),
+            supervise(
+                "poc",
+                "density_scaler",
+                density_scaler.run(&shutdown).map_err(Error::from)
+            ),
+            hex_scale_comparison_server.run().map_err(Error::from),
+            hex_density_snapshot_server.run().map_err(Error::from),
+            dual_write_mirror,
+            density_api,
+            supervise("poc", "indexer", poc_indexer),
             price_receiver.map_err(Error::from),
             entropy_loader_source_join_handle.map_err(anyhow::Error::from),
             pk_loader_source_join_handle.map_err(anyhow::Error::from),
@@ -203,6 +507,136 @@ impl Server {
     }
 }
 
+/// List or flip the runtime feature flags of the verifier
+#[derive(Debug, clap::Args)]
+pub struct FeatureFlag {
+    #[clap(subcommand)]
+    cmd: feature_flags::Cmd,
+}
+
+impl FeatureFlag {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown)
+            .await?;
+        self.cmd.run(&pool).await?;
+        Ok(())
+    }
+}
+
+/// Drive the online migrations of the verifier database through their phases
+#[derive(Debug, clap::Args)]
+pub struct OnlineMigration {
+    #[clap(subcommand)]
+    cmd: online_migration::Cmd,
+}
+
+impl OnlineMigration {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown)
+            .await?;
+        self.cmd.run(&pool, &online_migrations::all()).await?;
+        Ok(())
+    }
+}
+
+/// Hold or release the rewards of an epoch while it is investigated
+#[derive(Debug, clap::Args)]
+pub struct RewardHold {
+    #[clap(subcommand)]
+    cmd: reward_holds::Cmd,
+}
+
+impl RewardHold {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown)
+            .await?;
+        self.cmd.run(&pool).await?;
+        Ok(())
+    }
+}
+
+/// List the re-verifications of gateways requested through the reverify api
+#[derive(Debug, clap::Args)]
+pub struct Reverify {
+    #[clap(subcommand)]
+    cmd: reverify::Cmd,
+}
+
+impl Reverify {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown)
+            .await?;
+        self.cmd.run(&pool).await
+    }
+}
+
+/// Mirror the writes to the verifier database to the shadow database, if
+/// one is set
+async fn mirror_shadow_database(
+    settings: &Settings,
+    pool: Pool<Postgres>,
+    shutdown: triggered::Listener,
+) -> Result<()> {
+    let Some(shadow_settings) = &settings.shadow_database else {
+        return Ok(());
+    };
+    let (shadow, shadow_join_handle) = shadow_settings
+        .connect(concat!(env!("CARGO_PKG_NAME"), "_shadow"), shutdown.clone())
+        .await?;
+    sqlx::migrate!().run(&shadow).await?;
+    tokio::try_join!(
+        shadow_join_handle.map_err(Error::from),
+        dual_write::Mirror::new(pool, shadow)
+            .run(&shutdown)
+            .map_err(Error::from),
+    )?;
+    Ok(())
+}
+
+/// Move the verifier to the shadow database with dual writes
+#[derive(Debug, clap::Args)]
+pub struct DualWrite {
+    #[clap(subcommand)]
+    cmd: dual_write::Cmd,
+}
+
+impl DualWrite {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let shadow_settings = settings
+            .shadow_database
+            .as_ref()
+            .ok_or_else(|| anyhow!("shadow_database is not set"))?;
+        let (_shutdown_trigger, shutdown) = triggered::trigger();
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown.clone())
+            .await?;
+        let (shadow, _shadow_join_handle) = shadow_settings
+            .connect(concat!(env!("CARGO_PKG_NAME"), "_shadow"), shutdown)
+            .await?;
+        sqlx::migrate!().run(&shadow).await?;
+        self.cmd.run(&pool, &shadow).await?;
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
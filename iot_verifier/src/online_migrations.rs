@@ -0,0 +1,10 @@
+//! Online migrations of the verifier database, driven through the
+//! `online-migration` subcommand.
+
+use db_store::online_migration::OnlineMigration;
+
+/// Every online migration the subcommand knows about, new ones are
+/// registered here
+pub fn all() -> Vec<Box<dyn OnlineMigration>> {
+    vec![]
+}
@@ -55,6 +55,7 @@ impl PacketLoader {
             .create()
             .await?;
         tokio::spawn(async move { non_rewardable_packet_server.run().await });
+        let sinks = [non_rewardable_packet_sink.clone()];
 
         loop {
             if shutdown.is_triggered() {
@@ -62,6 +63,7 @@ impl PacketLoader {
             }
             tokio::select! {
                 _ = shutdown.clone() => break,
+                err = file_sink::first_failure(&sinks) => return Err(err.into()),
                 msg = receiver.recv() => if let Some(stream) =  msg {
                     let metrics = LoaderMetricTracker::new();
                     match self.handle_packet_file(stream, gateway_cache, &non_rewardable_packet_sink, &metrics).await {
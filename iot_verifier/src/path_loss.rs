@@ -0,0 +1,136 @@
+//! Path loss models used by the witness rssi plausibility check.
+
+use crate::poc::C;
+use helium_proto::Region as ProtoRegion;
+use serde::Deserialize;
+use std::{collections::HashMap, f64::consts::PI};
+
+/// Antenna heights below this many meters are clamped to it so the two ray
+/// model stays defined for gateways without an asserted elevation
+const MIN_ANTENNA_HEIGHT_M: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathLossModel {
+    /// Free space path loss, ignoring antenna elevation
+    #[default]
+    FreeSpace,
+    /// Two ray ground reflection using the antenna elevations. Falls back to
+    /// free space path loss below the crossover distance
+    TwoRay,
+}
+
+impl PathLossModel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FreeSpace => "free_space",
+            Self::TwoRay => "two_ray",
+        }
+    }
+
+    /// Path loss in dB over the given distance
+    pub fn path_loss(
+        &self,
+        freq: u64,
+        distance_mtrs: u32,
+        beaconer_elevation: i32,
+        witness_elevation: i32,
+    ) -> f64 {
+        let fspl = free_space_path_loss(freq, distance_mtrs);
+        match self {
+            Self::FreeSpace => fspl,
+            Self::TwoRay => {
+                let tx_height = (beaconer_elevation as f64).max(MIN_ANTENNA_HEIGHT_M);
+                let rx_height = (witness_elevation as f64).max(MIN_ANTENNA_HEIGHT_M);
+                let wavelength = C / freq as f64;
+                let crossover = 4.0 * PI * tx_height * rx_height / wavelength;
+                let distance = distance_mtrs as f64;
+                if distance <= crossover {
+                    fspl
+                } else {
+                    40.0 * distance.log10() - 20.0 * (tx_height * rx_height).log10()
+                }
+            }
+        }
+    }
+}
+
+pub fn free_space_path_loss(freq: u64, distance_mtrs: u32) -> f64 {
+    20.0 * (4.0 * PI * distance_mtrs as f64 * (freq as f64) / C).log10()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct ModelParams {
+    #[serde(default)]
+    pub model: PathLossModel,
+    /// Margin in dB a witness signal may exceed the expected rssi by
+    #[serde(default)]
+    pub margin_db: f64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Settings {
+    /// Model used for regions without an override
+    #[serde(default)]
+    pub default: ModelParams,
+    /// Per region overrides keyed by region name, ie "EU868"
+    #[serde(default)]
+    pub regions: HashMap<String, ModelParams>,
+}
+
+impl Settings {
+    /// The model and margin of `region`, falling back to the default ones
+    pub fn for_region(&self, region: ProtoRegion) -> &ModelParams {
+        self.regions
+            .get(region.as_str_name())
+            .unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: u64 = 867900024;
+
+    #[test]
+    fn two_ray_matches_free_space_below_crossover() {
+        // crossover for 1m antennas at ~868Mhz is ~36m
+        assert_eq!(
+            free_space_path_loss(FREQ, 30),
+            PathLossModel::TwoRay.path_loss(FREQ, 30, 0, 0)
+        );
+        assert!(
+            PathLossModel::TwoRay.path_loss(FREQ, 5000, 0, 0) > free_space_path_loss(FREQ, 5000)
+        );
+    }
+
+    #[test]
+    fn elevation_reduces_two_ray_loss() {
+        let low = PathLossModel::TwoRay.path_loss(FREQ, 20000, 2, 2);
+        let high = PathLossModel::TwoRay.path_loss(FREQ, 20000, 30, 30);
+        assert!(high < low);
+    }
+
+    #[test]
+    fn region_overrides_default() {
+        let settings = Settings {
+            default: ModelParams::default(),
+            regions: HashMap::from([(
+                "EU868".to_string(),
+                ModelParams {
+                    model: PathLossModel::TwoRay,
+                    margin_db: 3.0,
+                },
+            )]),
+        };
+        assert_eq!(
+            PathLossModel::TwoRay,
+            settings.for_region(ProtoRegion::Eu868).model
+        );
+        assert_eq!(
+            PathLossModel::FreeSpace,
+            settings.for_region(ProtoRegion::Us915).model
+        );
+    }
+}
@@ -4,26 +4,30 @@ use crate::{
     gateway_cache::GatewayCacheError,
     hex_density::HexDensityMap,
     last_beacon::{LastBeacon, LastBeaconError},
+    path_loss,
     region_cache::{RegionCache, RegionCacheError},
+    telemetry,
+    test_gateways::{Bypass, BypassedCheck, TestGateways},
 };
 use beacon;
 use chrono::{DateTime, Duration, Utc};
 use file_store::{
     iot_beacon_report::{IotBeaconIngestReport, IotBeaconReport},
     iot_valid_poc::IotVerifiedWitnessReport,
-    iot_witness_report::IotWitnessIngestReport,
+    iot_witness_report::{IotWitnessIngestReport, IotWitnessReport},
 };
-use h3o::{CellIndex, LatLng, Resolution};
+use futures::stream::{self, StreamExt};
 use helium_crypto::PublicKeyBinary;
 use helium_proto::{
     services::poc_lora::{InvalidParticipantSide, InvalidReason, VerificationStatus},
-    BlockchainRegionParamV1, Region as ProtoRegion,
+    BlockchainRegionParamV1, DataRate, Region as ProtoRegion,
 };
-use iot_config::gateway_info::{GatewayInfo, GatewayMetadata};
+use hex_utils::h3o::Resolution;
+use iot_config::gateway_info::{GatewayClass, GatewayInfo, GatewayMetadata};
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
 use sqlx::PgPool;
-use std::f64::consts::PI;
+use std::collections::HashMap;
 
 pub type GenericVerifyResult<T = ()> = std::result::Result<T, InvalidReason>;
 
@@ -32,8 +36,6 @@ pub const C: f64 = 2.998e8;
 /// R is the (average) radius of the earth
 pub const R: f64 = 6.371e6;
 
-/// max permitted distance of a witness from a beaconer measured in KM
-const POC_DISTANCE_LIMIT: u32 = 100;
 /// the minimum distance in cells between a beaconer and witness
 const POC_CELL_DISTANCE_MINIMUM: u32 = 8;
 /// the resolution at which parent cell distance is derived
@@ -54,6 +56,21 @@ pub struct Poc {
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
     entropy_version: i32,
+    test_gateways: TestGateways,
+    bypasses: Vec<Bypass>,
+    rssi_checks: Vec<RssiCheck>,
+}
+
+/// The rssi check a witness was rejected by, the path loss model and margin
+/// of the beaconer's region and the rssi the signal was expected under
+#[derive(Clone, Debug, PartialEq)]
+pub struct RssiCheck {
+    pub pub_key: PublicKeyBinary,
+    pub path_loss_model: path_loss::PathLossModel,
+    pub margin_db: f64,
+    pub expected_rssi_dbm: f64,
+    pub signal: i32,
+    pub distance: u32,
 }
 
 pub struct VerifyBeaconResult {
@@ -66,6 +83,8 @@ pub struct VerifyBeaconResult {
 pub struct VerifyWitnessesResult {
     pub verified_witnesses: Vec<IotVerifiedWitnessReport>,
     pub failed_witnesses: Vec<IotWitnessIngestReport>,
+    /// witnesses not verified within the time budget
+    pub unverified_witnesses: Vec<IotWitnessIngestReport>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -75,7 +94,7 @@ pub enum VerificationError {
     #[error("last beacon error: {0}")]
     LastBeaconError(#[from] LastBeaconError),
     #[error("calc distance error: {0}")]
-    CalcDistanceError(#[from] CalcDistanceError),
+    CalcDistanceError(#[from] hex_utils::Error),
     #[error("error querying gateway info from iot config service")]
     GatewayCache(#[from] GatewayCacheError),
     #[error("error querying region info from iot config service")]
@@ -88,6 +107,7 @@ impl Poc {
         witness_reports: Vec<IotWitnessIngestReport>,
         entropy_start: DateTime<Utc>,
         entropy_version: i32,
+        test_gateways: TestGateways,
     ) -> Self {
         let entropy_end = entropy_start + Duration::seconds(ENTROPY_LIFESPAN);
         Self {
@@ -96,6 +116,78 @@ impl Poc {
             entropy_start,
             entropy_end,
             entropy_version,
+            test_gateways,
+            bypasses: Vec::new(),
+            rssi_checks: Vec::new(),
+        }
+    }
+
+    /// The checks skipped for test gateways by the verifications so far
+    pub fn bypasses(&self) -> &[Bypass] {
+        &self.bypasses
+    }
+
+    /// The rssi checks rejecting witnesses in the verifications so far
+    pub fn rssi_checks(&self) -> &[RssiCheck] {
+        &self.rssi_checks
+    }
+
+    fn record_bypass(&mut self, pub_key: &PublicKeyBinary, check: BypassedCheck) {
+        let bypass = Bypass {
+            pub_key: pub_key.clone(),
+            check,
+        };
+        if !self.bypasses.contains(&bypass) {
+            tracing::info!(%pub_key, check = check.as_str(), "check bypassed for test gateway");
+            self.bypasses.push(bypass);
+        }
+    }
+
+    /// The hex scale at the beaconer's `location`, fixed at 1 for test
+    /// beaconers
+    async fn hex_scale(
+        &mut self,
+        beaconer: &PublicKeyBinary,
+        location: u64,
+        hex_density_map: &impl HexDensityMap,
+    ) -> Decimal {
+        let (scale, bypassed) = self.scale_at(beaconer, location, hex_density_map).await;
+        if bypassed {
+            self.record_bypass(beaconer, BypassedCheck::DensityScaling);
+        }
+        scale
+    }
+
+    /// The hex scale at the beaconer's `location`, and whether density
+    /// scaling is bypassed for the beaconer
+    async fn scale_at(
+        &self,
+        beaconer: &PublicKeyBinary,
+        location: u64,
+        hex_density_map: &impl HexDensityMap,
+    ) -> (Decimal, bool) {
+        if self.test_gateways.contains(beaconer) {
+            return (Decimal::ONE, true);
+        }
+        let scale = hex_density_map
+            .get(location)
+            .await
+            .unwrap_or(*DEFAULT_TX_SCALE);
+        (scale, false)
+    }
+
+    /// The max distance between the beaconer and a witness, and whether the
+    /// check is bypassed as either of them is a test gateway
+    fn max_witness_distance(
+        &self,
+        beaconer: &PublicKeyBinary,
+        witness: &PublicKeyBinary,
+        limit: u32,
+    ) -> (u32, bool) {
+        if self.test_gateways.contains(beaconer) || self.test_gateways.contains(witness) {
+            (u32::MAX, true)
+        } else {
+            (limit, false)
         }
     }
 
@@ -121,10 +213,12 @@ impl Poc {
         let beaconer_metadata = match beaconer_info.metadata {
             Some(ref metadata) => metadata,
             None => {
-                return Ok(VerifyBeaconResult::invalid(
-                    InvalidReason::NotAsserted,
-                    beaconer_info,
-                ))
+                let reason = unasserted_reason(
+                    &beaconer_info,
+                    InvalidParticipantSide::Beaconer,
+                    self.beacon_report.received_timestamp,
+                );
+                return Ok(VerifyBeaconResult::invalid(reason, beaconer_info));
             }
         };
         let beaconer_region_info = match region_cache
@@ -148,84 +242,209 @@ impl Poc {
             beacon_interval_tolerance,
         ) {
             Ok(()) => {
-                let tx_scale = hex_density_map
-                    .get(beaconer_metadata.location)
-                    .await
-                    .unwrap_or(*DEFAULT_TX_SCALE);
+                let tx_scale = self
+                    .hex_scale(
+                        &beaconer_pub_key,
+                        beaconer_metadata.location,
+                        &hex_density_map,
+                    )
+                    .await;
                 Ok(VerifyBeaconResult::valid(beaconer_info, tx_scale))
             }
             Err(invalid_reason) => Ok(VerifyBeaconResult::invalid(invalid_reason, beaconer_info)),
         }
     }
 
+    /// Verify the witnesses of the beacon, up to `concurrency` gateways at a
+    /// time. Witnesses not verified within `time_budget` are left unverified,
+    /// to be verified again on the next run
+    #[allow(clippy::too_many_arguments)]
     pub async fn verify_witnesses(
         &mut self,
         beacon_info: &GatewayInfo,
         hex_density_map: impl HexDensityMap,
         gateway_cache: &GatewayCache,
+        region_cache: &RegionCache,
+        default_max_witness_distance: u32,
+        max_witness_window: Duration,
+        path_loss: &path_loss::Settings,
+        concurrency: usize,
+        time_budget: std::time::Duration,
     ) -> Result<VerifyWitnessesResult, VerificationError> {
+        // the regional plan, distance limit and witness window are those of
+        // the beaconer's region, resolved once for all witnesses; an
+        // unasserted beaconer fails each witness anyway
+        let (region_params, max_witness_distance, witness_window) = match beacon_info.metadata {
+            Some(ref metadata) => {
+                let region_info = region_cache
+                    .resolve_region_info(metadata.region)
+                    .await
+                    .map_err(VerificationError::RegionCache)?;
+                let max_witness_distance = region_info
+                    .max_witness_distance
+                    .unwrap_or(default_max_witness_distance);
+                let witness_window =
+                    region_witness_window(region_info.witness_window, max_witness_window);
+                (
+                    region_info.region_params,
+                    max_witness_distance,
+                    witness_window,
+                )
+            }
+            None => (
+                vec![],
+                default_max_witness_distance,
+                Duration::seconds(ENTROPY_LIFESPAN),
+            ),
+        };
+        // valid witnesses are scaled by the hex scale of the beaconer
+        let (tx_scale, scale_bypassed) = match beacon_info.metadata {
+            Some(ref metadata) => {
+                self.scale_at(&beacon_info.address, metadata.location, &hex_density_map)
+                    .await
+            }
+            None => (*DEFAULT_TX_SCALE, false),
+        };
+        let limits = WitnessLimits {
+            region_params: &region_params,
+            max_witness_distance,
+            witness_window_end: self.entropy_start + witness_window,
+            tx_scale,
+        };
+        let outcomes = self
+            .verify_witness_reports(
+                beacon_info,
+                gateway_cache,
+                &limits,
+                path_loss,
+                concurrency,
+                time_budget,
+            )
+            .await;
+
         let mut verified_witnesses: Vec<IotVerifiedWitnessReport> = Vec::new();
         let mut failed_witnesses: Vec<IotWitnessIngestReport> = Vec::new();
-        let mut existing_gateways: Vec<PublicKeyBinary> = Vec::new();
+        let mut unverified_witnesses: Vec<IotWitnessIngestReport> = Vec::new();
         let witnesses = self.witness_reports.clone();
-        for witness_report in witnesses {
-            // have we already processed a witness report from this gateway ?
-            // if not, run verifications
-            // if so, skip verifications and declare the report a dup
-            if !existing_gateways.contains(&witness_report.report.pub_key) {
-                // not a dup, run the verifications
-                match self
-                    .verify_witness(
-                        &witness_report,
-                        beacon_info,
-                        gateway_cache,
-                        &hex_density_map,
-                    )
-                    .await
-                {
-                    Ok(verified_witness) => {
-                        // track which gateways we have saw a witness report from
-                        existing_gateways.push(verified_witness.report.pub_key.clone());
-                        verified_witnesses.push(verified_witness)
+        for (witness_report, outcome) in witnesses.into_iter().zip(outcomes) {
+            match outcome {
+                Some(WitnessOutcome::Verified(WitnessVerdict {
+                    report: verified_witness,
+                    bypassed_distance,
+                    rssi_check,
+                })) => {
+                    if let Some(rssi_check) = rssi_check {
+                        self.rssi_checks.push(rssi_check);
+                    }
+                    if verified_witness.status == VerificationStatus::Valid && scale_bypassed {
+                        self.record_bypass(&beacon_info.address, BypassedCheck::DensityScaling);
+                    }
+                    if bypassed_distance {
+                        self.record_bypass(
+                            &verified_witness.report.pub_key,
+                            BypassedCheck::MaxDistance,
+                        );
                     }
-                    Err(_) => failed_witnesses.push(witness_report),
+                    verified_witnesses.push(verified_witness)
                 }
-            } else {
-                // the report is a dup
-                let dup_witness = IotVerifiedWitnessReport::invalid(
-                    InvalidReason::Duplicate,
-                    &witness_report.report,
-                    witness_report.received_timestamp,
-                    None,
-                    // if location is None, default gain and elevation to zero
-                    0,
-                    0,
-                    InvalidParticipantSide::Witness,
-                );
-                verified_witnesses.push(dup_witness)
+                Some(WitnessOutcome::Duplicate) => {
+                    let dup_witness = IotVerifiedWitnessReport::invalid(
+                        InvalidReason::Duplicate,
+                        &witness_report.report,
+                        witness_report.received_timestamp,
+                        None,
+                        // if location is None, default gain and elevation to zero
+                        0,
+                        0,
+                        InvalidParticipantSide::Witness,
+                    );
+                    verified_witnesses.push(dup_witness)
+                }
+                Some(WitnessOutcome::Failed) => failed_witnesses.push(witness_report),
+                None => unverified_witnesses.push(witness_report),
             }
         }
         let resp = VerifyWitnessesResult {
             verified_witnesses,
             failed_witnesses,
+            unverified_witnesses,
         };
         Ok(resp)
     }
 
+    /// The outcome of verifying each witness report, in the order of the
+    /// reports, None for those not verified within `time_budget`
+    async fn verify_witness_reports(
+        &self,
+        beaconer_info: &GatewayInfo,
+        gateway_cache: &GatewayCache,
+        limits: &WitnessLimits<'_>,
+        path_loss: &path_loss::Settings,
+        concurrency: usize,
+        time_budget: std::time::Duration,
+    ) -> Vec<Option<WitnessOutcome>> {
+        let reporters: Vec<&PublicKeyBinary> = self
+            .witness_reports
+            .iter()
+            .map(|witness_report| &witness_report.report.pub_key)
+            .collect();
+        let outcomes = verify_per_gateway(&reporters, concurrency, time_budget, move |index| {
+            self.verify_witness(
+                &self.witness_reports[index],
+                beaconer_info,
+                gateway_cache,
+                limits,
+                path_loss,
+            )
+        })
+        .await;
+        let pending = outcomes.iter().filter(|outcome| outcome.is_none()).count();
+        if pending > 0 {
+            tracing::warn!(
+                beaconer = %beaconer_info.address,
+                pending,
+                "witness verification time budget exceeded"
+            );
+        }
+        outcomes
+    }
+
+    /// Verify a witness report, returning its verdict
     async fn verify_witness(
-        &mut self,
+        &self,
         witness_report: &IotWitnessIngestReport,
         beaconer_info: &GatewayInfo,
         gateway_cache: &GatewayCache,
-        hex_density_map: &impl HexDensityMap,
-    ) -> Result<IotVerifiedWitnessReport, VerificationError> {
+        limits: &WitnessLimits<'_>,
+        path_loss: &path_loss::Settings,
+    ) -> Result<WitnessVerdict, VerificationError> {
         let witness = &witness_report.report;
         let witness_pub_key = witness.pub_key.clone();
+        // the checks needing no gateway info reject the witness before its
+        // info is resolved
+        if let Err(invalid_reason) = do_witness_prechecks(
+            self.entropy_start,
+            limits.witness_window_end,
+            witness_report,
+            &self.beacon_report,
+        ) {
+            let beaconer_metadata = beaconer_info.metadata.as_ref();
+            let verified_witness = IotVerifiedWitnessReport::invalid(
+                invalid_reason,
+                &witness_report.report,
+                witness_report.received_timestamp,
+                beaconer_metadata.map(|metadata| metadata.location),
+                beaconer_metadata.map_or(0, |metadata| metadata.gain),
+                beaconer_metadata.map_or(0, |metadata| metadata.elevation),
+                InvalidParticipantSide::Witness,
+            );
+            return Ok(WitnessVerdict::new(verified_witness, false));
+        }
         // pull the witness info from our follower
         let witness_info = match gateway_cache.resolve_gateway_info(&witness_pub_key).await {
             Ok(res) => res,
             Err(GatewayCacheError::GatewayNotFound(_)) => {
-                return Ok(IotVerifiedWitnessReport::invalid(
+                let verified_witness = IotVerifiedWitnessReport::invalid(
                     InvalidReason::GatewayNotFound,
                     &witness_report.report,
                     witness_report.received_timestamp,
@@ -234,14 +453,19 @@ impl Poc {
                     0,
                     0,
                     InvalidParticipantSide::Witness,
-                ));
+                );
+                return Ok(WitnessVerdict::new(verified_witness, false));
             }
         };
         let witness_metadata = match witness_info.metadata {
             Some(ref metadata) => metadata,
             None => {
-                return Ok(IotVerifiedWitnessReport::invalid(
-                    InvalidReason::NotAsserted,
+                let verified_witness = IotVerifiedWitnessReport::invalid(
+                    unasserted_reason(
+                        &witness_info,
+                        InvalidParticipantSide::Witness,
+                        witness_report.received_timestamp,
+                    ),
                     &witness_report.report,
                     witness_report.received_timestamp,
                     None,
@@ -249,13 +473,14 @@ impl Poc {
                     0,
                     0,
                     InvalidParticipantSide::Witness,
-                ));
+                );
+                return Ok(WitnessVerdict::new(verified_witness, false));
             }
         };
         // to avoid assuming beaconer location is set and to avoid unwrap
         // we explicity match location here again
         let Some(ref beaconer_metadata) = beaconer_info.metadata else {
-            return Ok(IotVerifiedWitnessReport::invalid(
+            let verified_witness = IotVerifiedWitnessReport::invalid(
                 InvalidReason::NotAsserted,
                 &witness_report.report,
                 witness_report.received_timestamp,
@@ -264,44 +489,162 @@ impl Poc {
                 0,
                 0,
                 InvalidParticipantSide::Beaconer,
-            ))
+            );
+            return Ok(WitnessVerdict::new(verified_witness, false));
         };
+        let (max_witness_distance, bypass_distance) = self.max_witness_distance(
+            &beaconer_info.address,
+            &witness_pub_key,
+            limits.max_witness_distance,
+        );
         // run the witness verifications
         match do_witness_verifications(
             self.entropy_start,
-            self.entropy_end,
+            limits.witness_window_end,
             witness_report,
             &witness_info,
             &self.beacon_report,
             beaconer_metadata,
+            limits.region_params,
+            max_witness_distance,
+            path_loss,
         ) {
             Ok(()) => {
-                let tx_scale = hex_density_map
-                    .get(beaconer_metadata.location)
-                    .await
-                    .unwrap_or(*DEFAULT_TX_SCALE);
-                Ok(IotVerifiedWitnessReport::valid(
+                let verified_witness = IotVerifiedWitnessReport::valid(
                     &witness_report.report,
                     witness_report.received_timestamp,
                     Some(witness_metadata.location),
                     witness_metadata.gain,
                     witness_metadata.elevation,
-                    tx_scale,
-                ))
+                    limits.tx_scale,
+                );
+                Ok(WitnessVerdict::new(verified_witness, bypass_distance))
             }
-            Err(invalid_reason) => Ok(IotVerifiedWitnessReport::invalid(
-                invalid_reason,
-                &witness_report.report,
-                witness_report.received_timestamp,
-                Some(beaconer_metadata.location),
-                beaconer_metadata.gain,
-                beaconer_metadata.elevation,
-                InvalidParticipantSide::Witness,
-            )),
+            Err(invalid_reason) => {
+                let verified_witness = IotVerifiedWitnessReport::invalid(
+                    invalid_reason,
+                    &witness_report.report,
+                    witness_report.received_timestamp,
+                    Some(beaconer_metadata.location),
+                    beaconer_metadata.gain,
+                    beaconer_metadata.elevation,
+                    InvalidParticipantSide::Witness,
+                );
+                let rssi_check = match invalid_reason {
+                    InvalidReason::BadRssi => rssi_check(
+                        &witness_report.report,
+                        self.beacon_report.report.tx_power,
+                        beaconer_metadata,
+                        witness_metadata,
+                        path_loss.for_region(beaconer_metadata.region),
+                    ),
+                    _ => None,
+                };
+                Ok(WitnessVerdict {
+                    report: verified_witness,
+                    bypassed_distance: false,
+                    rssi_check,
+                })
+            }
+        }
+    }
+}
+
+/// Limits every witness of a beacon is verified against, those of the
+/// beaconer's region
+struct WitnessLimits<'a> {
+    region_params: &'a [BlockchainRegionParamV1],
+    max_witness_distance: u32,
+    witness_window_end: DateTime<Utc>,
+    /// hex scale of the beaconer, applied to valid witnesses
+    tx_scale: Decimal,
+}
+
+/// The verdict on a witness
+struct WitnessVerdict {
+    report: IotVerifiedWitnessReport,
+    /// whether the distance check was bypassed for it
+    bypassed_distance: bool,
+    /// the rssi check it was rejected by, if it was
+    rssi_check: Option<RssiCheck>,
+}
+
+impl WitnessVerdict {
+    fn new(report: IotVerifiedWitnessReport, bypassed_distance: bool) -> Self {
+        Self {
+            report,
+            bypassed_distance,
+            rssi_check: None,
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum WitnessOutcome<V = WitnessVerdict> {
+    Verified(V),
+    /// An earlier report of the gateway was verified
+    Duplicate,
+    /// The witness couldn't be verified, the gateway cache failing
+    Failed,
+}
+
+/// The outcome of verifying each report, made by the gateways of
+/// `reporters` in order, with `verify`, None for those not verified within
+/// `time_budget`. The reports of a gateway are verified one after the other,
+/// while up to `concurrency` gateways are verified at once. The first report
+/// of a gateway verified, whether valid or not, has the later ones declared
+/// dups, while a report failing to be verified leaves the next one to be
+/// verified
+async fn verify_per_gateway<V, E, F, Fut>(
+    reporters: &[&PublicKeyBinary],
+    concurrency: usize,
+    time_budget: std::time::Duration,
+    verify: F,
+) -> Vec<Option<WitnessOutcome<V>>>
+where
+    F: Fn(usize) -> Fut,
+    Fut: std::future::Future<Output = Result<V, E>>,
+{
+    let mut gateways: HashMap<&PublicKeyBinary, Vec<usize>> = HashMap::new();
+    for (index, reporter) in reporters.iter().enumerate() {
+        gateways.entry(*reporter).or_default().push(index);
+    }
+
+    let verify = &verify;
+    let mut verifications = stream::iter(gateways.into_values())
+        .map(|indices| async move {
+            let mut outcomes = Vec::with_capacity(indices.len());
+            let mut verified = false;
+            for index in indices {
+                let outcome = if verified {
+                    WitnessOutcome::Duplicate
+                } else {
+                    match verify(index).await {
+                        Ok(verdict) => {
+                            verified = true;
+                            WitnessOutcome::Verified(verdict)
+                        }
+                        Err(_) => WitnessOutcome::Failed,
+                    }
+                };
+                outcomes.push((index, outcome));
+            }
+            outcomes
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut outcomes: Vec<Option<WitnessOutcome<V>>> = reporters.iter().map(|_| None).collect();
+    let deadline = tokio::time::Instant::now() + time_budget;
+    while let Ok(Some(gateway_outcomes)) =
+        tokio::time::timeout_at(deadline, verifications.next()).await
+    {
+        for (index, outcome) in gateway_outcomes {
+            outcomes[index] = Some(outcome);
+        }
+    }
+    outcomes
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn do_beacon_verifications(
     entropy_start: DateTime<Utc>,
@@ -319,18 +662,29 @@ pub fn do_beacon_verifications(
         beaconer_info.address.clone()
     );
     let beacon_received_ts = beacon_report.received_timestamp;
+    verify_gw_capability(
+        beaconer_info.class(beacon_received_ts),
+        InvalidParticipantSide::Beaconer,
+    )?;
     let beaconer_metadata = match beaconer_info.metadata {
         Some(ref metadata) => metadata,
         None => return Err(InvalidReason::NotAsserted),
     };
+    verify_onboarded("beacon", beaconer_metadata.onboarded_at, beacon_received_ts)?;
     verify_entropy(entropy_start, entropy_end, beacon_received_ts)?;
-    verify_gw_capability(beaconer_info.is_full_hotspot)?;
     verify_beacon_schedule(
         &last_beacon,
         beacon_received_ts,
         beacon_interval,
         beacon_interval_tolerance,
     )?;
+    verify_plan_conformance(
+        "beacon",
+        beacon_report.report.frequency,
+        beacon_report.report.datarate,
+        beacon_report.report.data.len(),
+        beaconer_region_params,
+    )?;
     verify_beacon_payload(
         &beacon_report.report,
         beaconer_metadata.region,
@@ -346,6 +700,26 @@ pub fn do_beacon_verifications(
     Ok(())
 }
 
+/// The witness checks needing neither the witness nor the beaconer info,
+/// run before the witness info is resolved
+pub fn do_witness_prechecks(
+    entropy_start: DateTime<Utc>,
+    entropy_end: DateTime<Utc>,
+    witness_report: &IotWitnessIngestReport,
+    beacon_report: &IotBeaconIngestReport,
+) -> GenericVerifyResult {
+    verify_self_witness(
+        &beacon_report.report.pub_key,
+        &witness_report.report.pub_key,
+    )?;
+    verify_entropy(
+        entropy_start,
+        entropy_end,
+        witness_report.received_timestamp,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn do_witness_verifications(
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
@@ -353,42 +727,57 @@ pub fn do_witness_verifications(
     witness_info: &GatewayInfo,
     beacon_report: &IotBeaconIngestReport,
     beaconer_metadata: &GatewayMetadata,
+    beaconer_region_params: &[BlockchainRegionParamV1],
+    max_witness_distance: u32,
+    path_loss: &path_loss::Settings,
 ) -> GenericVerifyResult {
     tracing::debug!(
         "verifying witness from gateway: {:?}",
         witness_info.address.clone()
     );
     let beacon_report = &beacon_report;
+    // the checks are ordered by cost, the first failing one rejecting the
+    // witness without running the costlier ones after it
+    do_witness_prechecks(entropy_start, entropy_end, witness_report, beacon_report)?;
+    verify_gw_capability(
+        witness_info.class(witness_report.received_timestamp),
+        InvalidParticipantSide::Witness,
+    )?;
     let witness_metadata = match witness_info.metadata {
         Some(ref metadata) => metadata,
         None => return Err(InvalidReason::NotAsserted),
     };
-    verify_self_witness(
-        &beacon_report.report.pub_key,
-        &witness_report.report.pub_key,
-    )?;
-    verify_entropy(
-        entropy_start,
-        entropy_end,
+    verify_onboarded(
+        "witness",
+        witness_metadata.onboarded_at,
         witness_report.received_timestamp,
     )?;
-    verify_witness_data(&beacon_report.report.data, &witness_report.report.data)?;
-    verify_gw_capability(witness_info.is_full_hotspot)?;
     verify_witness_freq(
         beacon_report.report.frequency,
         witness_report.report.frequency,
     )?;
     verify_witness_region(beaconer_metadata.region, witness_metadata.region)?;
+    verify_witness_data(&beacon_report.report.data, &witness_report.report.data)?;
+    verify_plan_conformance(
+        "witness",
+        witness_report.report.frequency,
+        witness_report.report.datarate,
+        witness_report.report.data.len(),
+        beaconer_region_params,
+    )?;
+    verify_witness_distance(
+        beaconer_metadata.location,
+        witness_metadata.location,
+        max_witness_distance,
+    )?;
     verify_witness_cell_distance(beaconer_metadata.location, witness_metadata.location)?;
-    verify_witness_distance(beaconer_metadata.location, witness_metadata.location)?;
     verify_witness_rssi(
         witness_report.report.signal,
         witness_report.report.frequency,
         beacon_report.report.tx_power,
-        beaconer_metadata.gain,
-        witness_metadata.gain,
-        beaconer_metadata.location,
-        witness_metadata.location,
+        beaconer_metadata,
+        witness_metadata,
+        path_loss.for_region(beaconer_metadata.region),
     )?;
     tracing::debug!(
         "valid witness from gateway: {:?}",
@@ -397,6 +786,15 @@ pub fn do_witness_verifications(
     Ok(())
 }
 
+/// Time from the start of a beacon's entropy within which its witnesses are
+/// accepted: the window of the beaconer's region capped to `max_window`, or
+/// the entropy lifespan for regions without one
+fn region_witness_window(window: Option<u32>, max_window: Duration) -> Duration {
+    window.map_or(Duration::seconds(ENTROPY_LIFESPAN), |window| {
+        Duration::seconds(window as i64).min(max_window)
+    })
+}
+
 /// verify beaconer is permitted to beacon at this time
 fn verify_beacon_schedule(
     last_beacon: &Option<LastBeacon>,
@@ -448,6 +846,29 @@ fn verify_entropy(
     Ok(())
 }
 
+/// verify the gateway was onboarded on chain by the time its report was
+/// received, a report received earlier has been backdated
+fn verify_onboarded(
+    report_type: &'static str,
+    onboarded_at: Option<DateTime<Utc>>,
+    received_ts: DateTime<Utc>,
+) -> GenericVerifyResult {
+    match onboarded_at {
+        Some(onboarded_at) if received_ts < onboarded_at => {
+            tracing::debug!(
+                "{report_type} verification failed, reason: received before onboarding. received_ts: {received_ts:?}, onboarded_at: {onboarded_at:?}"
+            );
+            telemetry::count_report_before_onboarding(report_type);
+            // helium-proto has no dedicated reason for backdated reports.
+            // Denylisted reports are dropped by ingest and never reach the
+            // verifier, so `Denied` sets these apart from the reports of
+            // gateways that can't be found
+            Err(InvalidReason::Denied)
+        }
+        _ => Ok(()),
+    }
+}
+
 /// verify beacon construction
 fn verify_beacon_payload(
     beacon_report: &IotBeaconReport,
@@ -510,13 +931,31 @@ fn verify_beacon_payload(
     Ok(())
 }
 
-/// verify gateway is permitted to participate in POC
-fn verify_gw_capability(is_full_hotspot: bool) -> GenericVerifyResult {
-    if !is_full_hotspot {
+/// the reason a gateway without an asserted location is invalid for, data-only
+/// gateways are invalid for their class whether asserted or not
+fn unasserted_reason(
+    info: &GatewayInfo,
+    side: InvalidParticipantSide,
+    received_ts: DateTime<Utc>,
+) -> InvalidReason {
+    verify_gw_capability(info.class(received_ts), side)
+        .err()
+        .unwrap_or(InvalidReason::NotAsserted)
+}
+
+/// verify the class of the gateway permits it to participate in POC on `side`
+fn verify_gw_capability(class: GatewayClass, side: InvalidParticipantSide) -> GenericVerifyResult {
+    let permitted = match side {
+        InvalidParticipantSide::Beaconer => class.may_beacon(),
+        InvalidParticipantSide::Witness => class.may_witness(),
+        InvalidParticipantSide::SideNone => false,
+    };
+    if !permitted {
         tracing::debug!(
-            "witness verification failed, reason: {:?}. is_full_hotspot: {:?}",
+            "{} verification failed, reason: {:?}. class: {}",
+            side.as_str_name(),
             InvalidReason::InvalidCapability,
-            is_full_hotspot
+            class.as_str()
         );
         return Err(InvalidReason::InvalidCapability);
     };
@@ -551,6 +990,93 @@ fn verify_witness_freq(beacon_freq: u64, witness_freq: u64) -> GenericVerifyResu
     Ok(())
 }
 
+/// Why a report does not conform to the regional plan of the beaconer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanViolation {
+    /// not within any channel of the plan
+    Frequency,
+    /// not a lora datarate, or its spreading factor or bandwidth is not that
+    /// of the channel
+    Datarate,
+    /// larger than the max packet size of the datarate in the channel
+    PayloadSize,
+}
+
+impl PlanViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Frequency => "frequency",
+            Self::Datarate => "datarate",
+            Self::PayloadSize => "payload_size",
+        }
+    }
+
+    // there are no plan specific invalid reasons, the violation itself is
+    // counted in metrics
+    fn invalid_reason(&self) -> InvalidReason {
+        match self {
+            Self::Frequency => InvalidReason::InvalidFrequency,
+            Self::Datarate | Self::PayloadSize => InvalidReason::InvalidPacket,
+        }
+    }
+}
+
+/// verify the frequency, datarate and payload size of a report conform to
+/// the regional plan of the beaconer
+fn verify_plan_conformance(
+    report_type: &'static str,
+    frequency: u64,
+    datarate: DataRate,
+    payload_size: usize,
+    region_params: &[BlockchainRegionParamV1],
+) -> GenericVerifyResult {
+    check_plan_conformance(frequency, datarate, payload_size, region_params).map_err(|violation| {
+        tracing::debug!(
+            "{report_type} verification failed, reason: nonconforming {}. frequency: {frequency}, datarate: {datarate:?}, payload size: {payload_size}",
+            violation.as_str()
+        );
+        telemetry::count_plan_violation(report_type, violation.as_str());
+        violation.invalid_reason()
+    })
+}
+
+pub fn check_plan_conformance(
+    frequency: u64,
+    datarate: DataRate,
+    payload_size: usize,
+    region_params: &[BlockchainRegionParamV1],
+) -> Result<(), PlanViolation> {
+    let channel = region_params
+        .iter()
+        .find(|param| frequency.abs_diff(param.channel_frequency) <= param.channel_bandwidth / 2)
+        .ok_or(PlanViolation::Frequency)?;
+    let (spreading_factor, bandwidth) = lora_datarate(datarate).ok_or(PlanViolation::Datarate)?;
+    if bandwidth != channel.channel_bandwidth {
+        return Err(PlanViolation::Datarate);
+    }
+    let max_packet_size = channel
+        .spreading
+        .iter()
+        .flat_map(|spreading| &spreading.tagged_spreading)
+        .find(|tagged| tagged.region_spreading().as_str_name() == spreading_factor)
+        .map(|tagged| tagged.max_packet_size as usize)
+        .ok_or(PlanViolation::Datarate)?;
+    if payload_size > max_packet_size {
+        return Err(PlanViolation::PayloadSize);
+    }
+    Ok(())
+}
+
+/// The spreading factor, named as in `RegionSpreading`, and the bandwidth in
+/// Hz of a lora datarate
+fn lora_datarate(datarate: DataRate) -> Option<(&'static str, u64)> {
+    // lora datarates are named SF<spreading factor>BW<bandwidth in kHz>
+    let name = datarate.as_str_name();
+    let bandwidth_start = name.strip_prefix("SF")?.find("BW")? + 2;
+    let bandwidth: u64 = name[bandwidth_start + 2..].parse().ok()?;
+    Some((&name[..bandwidth_start], bandwidth * 1000))
+}
+
 /// verify the witness is located in same region as beaconer
 fn verify_witness_region(
     beacon_region: ProtoRegion,
@@ -566,13 +1092,18 @@ fn verify_witness_region(
     Ok(())
 }
 
-/// verify witness does not exceed max distance from beaconer
-fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyResult {
+/// verify witness does not exceed the max distance in km from beaconer
+/// permitted in the beaconer's region
+fn verify_witness_distance(
+    beacon_loc: u64,
+    witness_loc: u64,
+    max_distance: u32,
+) -> GenericVerifyResult {
     let witness_distance = match calc_distance(beacon_loc, witness_loc) {
         Ok(d) => d,
         Err(_) => return Err(InvalidReason::MaxDistanceExceeded),
     };
-    if witness_distance / 1000 > POC_DISTANCE_LIMIT {
+    if witness_distance / 1000 > max_distance {
         tracing::debug!(
             "witness verification failed, reason: {:?}. distance {witness_distance}",
             InvalidReason::MaxDistanceExceeded
@@ -598,45 +1129,105 @@ fn verify_witness_cell_distance(beacon_loc: u64, witness_loc: u64) -> GenericVer
     Ok(())
 }
 
-/// verify witness rssi
+/// verify witness rssi against the path loss model selected for the region
 fn verify_witness_rssi(
     witness_signal: i32,
     witness_freq: u64,
     beacon_tx_power: i32,
-    beacon_gain: i32,
-    witness_gain: i32,
-    beacon_loc: u64,
-    witness_loc: u64,
+    beaconer_metadata: &GatewayMetadata,
+    witness_metadata: &GatewayMetadata,
+    model_params: &path_loss::ModelParams,
 ) -> GenericVerifyResult {
-    let distance = match calc_distance(beacon_loc, witness_loc) {
-        Ok(d) => d,
-        Err(_) => return Err(InvalidReason::BadRssi),
-    };
-    let min_rcv_signal = calc_expected_rssi(
-        beacon_tx_power,
+    let Ok((min_rcv_signal, _distance)) = expected_rssi(
         witness_freq,
-        distance,
-        beacon_gain,
-        witness_gain,
-    );
+        beacon_tx_power,
+        beaconer_metadata,
+        witness_metadata,
+        model_params,
+    ) else {
+        return Err(InvalidReason::BadRssi);
+    };
     // signal is submitted as DBM * 10
     // min_rcv_signal is plain old DBM
-    if witness_signal as f64 / 10.0 > min_rcv_signal {
+    if witness_signal as f64 / 10.0 > min_rcv_signal + model_params.margin_db {
         tracing::debug!(
             "witness verification failed, reason: {:?}
             beaconer tx_power: {beacon_tx_power},
-            beaconer gain: {beacon_gain},
-            witness gain: {witness_gain},
+            beaconer gain: {},
+            beaconer elevation: {},
+            witness gain: {},
+            witness elevation: {},
             witness signal: {witness_signal},
             witness freq: {witness_freq},
+            path loss model: {},
+            path loss margin: {},
             min_rcv_signal: {min_rcv_signal}",
-            InvalidReason::BadRssi
+            InvalidReason::BadRssi,
+            beaconer_metadata.gain,
+            beaconer_metadata.elevation,
+            witness_metadata.gain,
+            witness_metadata.elevation,
+            model_params.model.as_str(),
+            model_params.margin_db,
         );
+        telemetry::count_bad_rssi_witness(model_params.model.as_str());
         return Err(InvalidReason::BadRssi);
     }
     Ok(())
 }
 
+/// the rssi expected of a witness under the path loss model, in dBm, and
+/// its distance to the beaconer
+fn expected_rssi(
+    witness_freq: u64,
+    beacon_tx_power: i32,
+    beaconer_metadata: &GatewayMetadata,
+    witness_metadata: &GatewayMetadata,
+    model_params: &path_loss::ModelParams,
+) -> Result<(f64, u32), hex_utils::Error> {
+    let distance = calc_distance(beaconer_metadata.location, witness_metadata.location)?;
+    let path_loss_db = model_params.model.path_loss(
+        witness_freq,
+        distance,
+        beaconer_metadata.elevation,
+        witness_metadata.elevation,
+    );
+    let rssi = calc_expected_rssi(
+        beacon_tx_power,
+        path_loss_db,
+        beaconer_metadata.gain,
+        witness_metadata.gain,
+    );
+    Ok((rssi, distance))
+}
+
+/// the rssi check of a witness rejected for its rssi, none when the
+/// distance to the beaconer can't be derived
+fn rssi_check(
+    witness: &IotWitnessReport,
+    beacon_tx_power: i32,
+    beaconer_metadata: &GatewayMetadata,
+    witness_metadata: &GatewayMetadata,
+    model_params: &path_loss::ModelParams,
+) -> Option<RssiCheck> {
+    let (expected_rssi_dbm, distance) = expected_rssi(
+        witness.frequency,
+        beacon_tx_power,
+        beaconer_metadata,
+        witness_metadata,
+        model_params,
+    )
+    .ok()?;
+    Some(RssiCheck {
+        pub_key: witness.pub_key.clone(),
+        path_loss_model: model_params.model,
+        margin_db: model_params.margin_db,
+        expected_rssi_dbm,
+        signal: witness.signal,
+        distance,
+    })
+}
+
 fn verify_witness_data(beacon_data: &Vec<u8>, witness_data: &Vec<u8>) -> GenericVerifyResult {
     if witness_data != beacon_data {
         tracing::debug!(
@@ -651,50 +1242,21 @@ fn verify_witness_data(beacon_data: &Vec<u8>, witness_data: &Vec<u8>) -> Generic
 
 fn calc_expected_rssi(
     conducted_tx_power_dbm: i32,
-    freq: u64,
-    distance_mtrs: u32,
+    path_loss_db: f64,
     beaconer_gain_ddb: i32,
     witness_gain_ddb: i32,
 ) -> f64 {
     let beaconer_gain_db = beaconer_gain_ddb / 10;
     let witness_gain_db = witness_gain_ddb / 10;
-    let fpsl = calc_fpsl(freq, distance_mtrs);
-    conducted_tx_power_dbm as f64 + beaconer_gain_db as f64 - fpsl + witness_gain_db as f64
-}
-
-fn calc_fpsl(freq: u64, distance_mtrs: u32) -> f64 {
-    20.0 * (4.0 * PI * distance_mtrs as f64 * (freq as f64) / C).log10()
-}
-
-#[derive(thiserror::Error, Debug)]
-pub enum CalcDistanceError {
-    #[error("h3 invalid cell: {0}")]
-    H3CellError(#[from] h3o::error::InvalidCellIndex),
-    #[error("h3 invalid parent")]
-    H3ParentError,
-    #[error("uncomputable hex grid distance")]
-    H3DistanceError(#[from] h3o::error::LocalIjError),
+    conducted_tx_power_dbm as f64 + beaconer_gain_db as f64 - path_loss_db + witness_gain_db as f64
 }
 
-fn calc_cell_distance(p1: u64, p2: u64) -> Result<u32, CalcDistanceError> {
-    let p1_cell = CellIndex::try_from(p1)?;
-    let p2_cell = CellIndex::try_from(p2)?;
-    let source_parent = p1_cell
-        .parent(POC_CELL_PARENT_RES)
-        .ok_or(CalcDistanceError::H3ParentError)?;
-    let dest_parent = p2_cell
-        .parent(POC_CELL_PARENT_RES)
-        .ok_or(CalcDistanceError::H3ParentError)?;
-    let cell_distance = source_parent.grid_distance(dest_parent)? as u32;
-    Ok(cell_distance)
+fn calc_cell_distance(p1: u64, p2: u64) -> Result<u32, hex_utils::Error> {
+    hex_utils::grid_distance_at(p1, p2, POC_CELL_PARENT_RES)
 }
 
-fn calc_distance(p1: u64, p2: u64) -> Result<u32, CalcDistanceError> {
-    let p1_cell = CellIndex::try_from(p1)?;
-    let p2_cell = CellIndex::try_from(p2)?;
-    let p1_latlng: LatLng = p1_cell.into();
-    let p2_latlng: LatLng = p2_cell.into();
-    Ok(p1_latlng.distance_m(p2_latlng).round() as u32)
+fn calc_distance(p1: u64, p2: u64) -> Result<u32, hex_utils::Error> {
+    hex_utils::distance_m(p1, p2)
 }
 
 fn generate_beacon(
@@ -765,11 +1327,10 @@ impl VerifyBeaconResult {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::last_beacon::LastBeacon;
+    use crate::{hex_density::SharedHexDensityMap, last_beacon::LastBeacon};
     use chrono::{Duration, TimeZone};
     use file_store::iot_beacon_report::IotBeaconReport;
     use file_store::iot_witness_report::IotWitnessReport;
-    use helium_proto::DataRate;
     use std::str::FromStr;
 
     const EU868_PARAMS: &[u8] = &[
@@ -789,6 +1350,7 @@ mod tests {
     ];
 
     const BEACONER_GAIN: u64 = 20;
+    const MAX_WITNESS_DISTANCE: u32 = 100;
     const LOC0: u64 = 631615575095659519; // malta
     const LOC1: u64 = 631615576056478207; // malta but a lil out from LOC0
     const LOC2: u64 = 631278052025960447; // armenia
@@ -849,8 +1411,7 @@ mod tests {
         let witness1_freq = 867900024;
         let min_recv_signal = calc_expected_rssi(
             beacon1_tx_power,
-            witness1_freq,
-            witness1_distance,
+            path_loss::free_space_path_loss(witness1_freq, witness1_distance),
             beacon1_gain,
             witness1_gain,
         );
@@ -994,12 +1555,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_region_witness_window() {
+        let max_window = Duration::seconds(600);
+        assert_eq!(
+            Duration::seconds(ENTROPY_LIFESPAN),
+            region_witness_window(None, max_window)
+        );
+        assert_eq!(
+            Duration::seconds(300),
+            region_witness_window(Some(300), max_window)
+        );
+        assert_eq!(max_window, region_witness_window(Some(900), max_window));
+        // a window of the region equal to the max is kept as is
+        assert_eq!(max_window, region_witness_window(Some(600), max_window));
+    }
+
+    #[test]
+    fn test_witness_window_boundary() {
+        let entropy_start = Utc::now();
+        let window_end = entropy_start + region_witness_window(Some(300), Duration::seconds(600));
+        // a witness received on the last second of the region's window is
+        // accepted, one received after it is not
+        assert!(verify_entropy(entropy_start, window_end, window_end).is_ok());
+        assert_eq!(
+            Err(InvalidReason::EntropyExpired),
+            verify_entropy(entropy_start, window_end, window_end + Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn test_verify_onboarded() {
+        let now = Utc::now();
+        let onboarded_at = now - Duration::seconds(60);
+        assert!(verify_onboarded("beacon", None, now).is_ok());
+        assert!(verify_onboarded("beacon", Some(onboarded_at), now).is_ok());
+        assert!(verify_onboarded("beacon", Some(onboarded_at), onboarded_at).is_ok());
+        assert_eq!(
+            Err(InvalidReason::Denied),
+            verify_onboarded("witness", Some(onboarded_at), now - Duration::seconds(61))
+        );
+    }
+
     #[test]
     fn test_verify_capability() {
-        assert!(verify_gw_capability(true).is_ok());
+        let beaconer = InvalidParticipantSide::Beaconer;
+        let witness = InvalidParticipantSide::Witness;
+        assert!(verify_gw_capability(GatewayClass::Full, beaconer).is_ok());
+        assert!(verify_gw_capability(GatewayClass::Full, witness).is_ok());
         assert_eq!(
             Err(InvalidReason::InvalidCapability),
-            verify_gw_capability(false)
+            verify_gw_capability(GatewayClass::Light, beaconer)
+        );
+        assert!(verify_gw_capability(GatewayClass::Light, witness).is_ok());
+        assert_eq!(
+            Err(InvalidReason::InvalidCapability),
+            verify_gw_capability(GatewayClass::DataOnly, beaconer)
+        );
+        assert_eq!(
+            Err(InvalidReason::InvalidCapability),
+            verify_gw_capability(GatewayClass::DataOnly, witness)
         );
     }
 
@@ -1033,6 +1648,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_check_plan_conformance() {
+        let region_params = default_region_params();
+        // witnesses report the frequency they received at, slightly off the
+        // channel the beacon was sent on
+        assert!(check_plan_conformance(867900000, DataRate::Sf12bw125, 51, &region_params).is_ok());
+        assert!(check_plan_conformance(867900032, DataRate::Sf12bw125, 51, &region_params).is_ok());
+        assert!(check_plan_conformance(868500000, DataRate::Sf8bw125, 238, &region_params).is_ok());
+        assert_eq!(
+            Err(PlanViolation::Frequency),
+            check_plan_conformance(869000000, DataRate::Sf12bw125, 51, &region_params)
+        );
+        // no sf11 in the plan
+        assert_eq!(
+            Err(PlanViolation::Datarate),
+            check_plan_conformance(867900000, DataRate::Sf11bw125, 51, &region_params)
+        );
+        assert_eq!(
+            Err(PlanViolation::Datarate),
+            check_plan_conformance(867900000, DataRate::Sf12bw250, 51, &region_params)
+        );
+        assert_eq!(
+            Err(PlanViolation::Datarate),
+            check_plan_conformance(867900000, DataRate::Fsk50, 51, &region_params)
+        );
+        assert_eq!(
+            Err(PlanViolation::PayloadSize),
+            check_plan_conformance(867900000, DataRate::Sf12bw125, 66, &region_params)
+        );
+        assert_eq!(
+            Err(InvalidReason::InvalidPacket),
+            verify_plan_conformance("beacon", 867900000, DataRate::Sf9bw125, 130, &region_params)
+        );
+    }
+
     #[test]
     fn test_verify_witness_region() {
         let beacon_region = ProtoRegion::Us915;
@@ -1050,10 +1700,25 @@ mod tests {
         let beacon_loc = LOC0;
         let witness1_loc = LOC1;
         let witness2_loc = LOC2;
-        assert!(verify_witness_distance(beacon_loc, witness1_loc).is_ok());
+        assert!(verify_witness_distance(beacon_loc, witness1_loc, MAX_WITNESS_DISTANCE).is_ok());
+        assert_eq!(
+            Err(InvalidReason::MaxDistanceExceeded),
+            verify_witness_distance(beacon_loc, witness2_loc, MAX_WITNESS_DISTANCE)
+        );
+    }
+
+    #[test]
+    fn test_verify_witness_distance_region_limit() {
+        // ealing and finsbury park, london, 14318m apart
+        let beacon_loc = 644459695463521437;
+        let witness_loc = 644460986971331488;
+        // distances are compared in whole km, a limit of 14km still admits
+        // the witness while a tighter region limit rejects it
+        assert!(verify_witness_distance(beacon_loc, witness_loc, 15).is_ok());
+        assert!(verify_witness_distance(beacon_loc, witness_loc, 14).is_ok());
         assert_eq!(
             Err(InvalidReason::MaxDistanceExceeded),
-            verify_witness_distance(beacon_loc, witness2_loc)
+            verify_witness_distance(beacon_loc, witness_loc, 13)
         );
     }
 
@@ -1074,28 +1739,33 @@ mod tests {
 
     #[test]
     fn test_verify_witness_rssi() {
-        let beacon_loc = LOC0;
-        let witness1_loc = LOC1;
-        let witness2_loc = LOC2;
+        let metadata = |location, gain| GatewayMetadata {
+            location,
+            gain,
+            elevation: 0,
+            region: ProtoRegion::Us915,
+            onboarded_at: None,
+            updated_at: None,
+        };
+        let free_space = path_loss::ModelParams::default();
 
         let beacon1_tx_power = 27;
-        let beacon1_gain = 80;
-        let witness1_gain = 12;
+        let beacon1_metadata = metadata(LOC0, 80);
+        let witness1_metadata = metadata(LOC1, 12);
         let witness1_signal = -1060;
         let witness1_freq = 904700032;
         assert!(verify_witness_rssi(
             witness1_signal,
             witness1_freq,
             beacon1_tx_power,
-            beacon1_gain,
-            witness1_gain,
-            beacon_loc,
-            witness1_loc,
+            &beacon1_metadata,
+            &witness1_metadata,
+            &free_space,
         )
         .is_ok());
         let beacon2_tx_power = 27;
-        let beacon2_gain = 12;
-        let witness2_gain = 12;
+        let beacon2_metadata = metadata(LOC0, 12);
+        let witness2_metadata = metadata(LOC2, 12);
         let witness2_signal = -19;
         let witness2_freq = 904499968;
         assert_eq!(
@@ -1104,12 +1774,71 @@ mod tests {
                 witness2_signal,
                 witness2_freq,
                 beacon2_tx_power,
-                beacon2_gain,
-                witness2_gain,
-                beacon_loc,
-                witness2_loc,
+                &beacon2_metadata,
+                &witness2_metadata,
+                &free_space,
             )
         );
+        // a margin wide enough to cover the excess signal accepts the witness
+        let wide_margin = path_loss::ModelParams {
+            margin_db: 500.0,
+            ..free_space
+        };
+        assert!(verify_witness_rssi(
+            witness2_signal,
+            witness2_freq,
+            beacon2_tx_power,
+            &beacon2_metadata,
+            &witness2_metadata,
+            &wide_margin,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_rssi_check_records_model_and_margin() {
+        let metadata = |location| GatewayMetadata {
+            location,
+            gain: 12,
+            elevation: 10,
+            region: ProtoRegion::Us915,
+            onboarded_at: None,
+            updated_at: None,
+        };
+        let (beaconer_metadata, witness_metadata) = (metadata(LOC0), metadata(LOC2));
+        let two_ray = path_loss::ModelParams {
+            model: path_loss::PathLossModel::TwoRay,
+            margin_db: 3.0,
+        };
+        let witness = invalid_witness_bad_rssi(Utc::now()).report;
+        let check = rssi_check(
+            &witness,
+            27,
+            &beaconer_metadata,
+            &witness_metadata,
+            &two_ray,
+        )
+        .expect("rssi check");
+        let (expected_rssi_dbm, distance) = expected_rssi(
+            witness.frequency,
+            27,
+            &beaconer_metadata,
+            &witness_metadata,
+            &two_ray,
+        )
+        .expect("expected rssi");
+        assert_eq!(
+            RssiCheck {
+                pub_key: witness.pub_key.clone(),
+                path_loss_model: path_loss::PathLossModel::TwoRay,
+                margin_db: 3.0,
+                expected_rssi_dbm,
+                signal: witness.signal,
+                distance,
+            },
+            check
+        );
+        assert_eq!(calc_distance(LOC0, LOC2).expect("distance"), check.distance);
     }
 
     #[test]
@@ -1191,7 +1920,8 @@ mod tests {
         );
         assert_eq!(Err(InvalidReason::IrregularInterval), resp3);
 
-        // test capability verification is active in the beacon validation list
+        // test capability verification is active in the beacon validation list,
+        // data-only gateways don't beacon, asserted or not
         let beacon_report4 = valid_beacon_report(entropy_start + Duration::minutes(2));
         let beacon_info4 = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, false);
         let resp4 = do_beacon_verifications(
@@ -1207,6 +1937,24 @@ mod tests {
         );
         assert_eq!(Err(InvalidReason::InvalidCapability), resp4);
 
+        // nor do full hotspots while light after their onboarding
+        let mut light_info = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, true);
+        if let Some(metadata) = light_info.metadata.as_mut() {
+            metadata.onboarded_at = Some(beacon_report4.received_timestamp - Duration::days(1));
+        }
+        let resp4 = do_beacon_verifications(
+            entropy_start,
+            entropy_end,
+            ENTROPY_VERSION,
+            None,
+            &beacon_report4,
+            &light_info,
+            &default_region_params(),
+            beacon_interval,
+            beacon_interval_tolerance,
+        );
+        assert_eq!(Err(InvalidReason::InvalidCapability), resp4);
+
         // test beacon construction verification is active in the beacon validation list
         let beacon_report5 = invalid_beacon_bad_payload(entropy_start + Duration::minutes(2));
         let resp5 = do_beacon_verifications(
@@ -1255,6 +2003,7 @@ mod tests {
             .metadata
             .expect("beaconer should have metadata");
         let witness_info = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, true);
+        let region_params = default_region_params();
         let entropy_start = Utc.timestamp_millis_opt(1676381847900).unwrap();
         let entropy_end = entropy_start + Duration::minutes(3);
 
@@ -1267,6 +2016,9 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::SelfWitness), resp1);
 
@@ -1279,6 +2031,9 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::EntropyExpired), resp2);
 
@@ -1291,6 +2046,9 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidPacket), resp3);
 
@@ -1304,6 +2062,9 @@ mod tests {
             &witness_info4,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::NotAsserted), resp4);
 
@@ -1316,6 +2077,9 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidFrequency), resp5);
 
@@ -1329,6 +2093,9 @@ mod tests {
             &witness_info6,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidRegion), resp6);
 
@@ -1342,6 +2109,9 @@ mod tests {
             &witness_info7,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::BelowMinDistance), resp7);
 
@@ -1355,6 +2125,9 @@ mod tests {
             &witness_info8,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::MaxDistanceExceeded), resp8);
 
@@ -1367,11 +2140,30 @@ mod tests {
             &witness_info,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::BadRssi), resp9);
 
-        // test witness capability verification is active in the witness validation list
+        // test witness capability verification is active in the witness validation list,
+        // data-only gateways don't witness
         let witness_report10 = valid_witness_report(entropy_start + Duration::minutes(2));
+        let witness_info10 = witness_gateway_info(None, ProtoRegion::Eu868, false);
+        let resp10 = do_witness_verifications(
+            entropy_start,
+            entropy_end,
+            &witness_report10,
+            &witness_info10,
+            &beacon_report,
+            &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
+        );
+        assert_eq!(Err(InvalidReason::InvalidCapability), resp10);
+
+        // asserted or not
         let witness_info10 = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, false);
         let resp10 = do_witness_verifications(
             entropy_start,
@@ -1380,9 +2172,30 @@ mod tests {
             &witness_info10,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Err(InvalidReason::InvalidCapability), resp10);
 
+        // light hotspots do witness
+        let mut light_info = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, true);
+        if let Some(metadata) = light_info.metadata.as_mut() {
+            metadata.onboarded_at = Some(witness_report10.received_timestamp - Duration::days(1));
+        }
+        let resp10 = do_witness_verifications(
+            entropy_start,
+            entropy_end,
+            &witness_report10,
+            &light_info,
+            &beacon_report,
+            &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
+        );
+        assert_eq!(Ok(()), resp10);
+
         // for completeness, confirm our valid witness report is sane
         let witness_report11 = valid_witness_report(entropy_start + Duration::minutes(2));
         let witness_info11 = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, true);
@@ -1393,10 +2206,68 @@ mod tests {
             &witness_info11,
             &beacon_report,
             &beaconer_metadata,
+            &region_params,
+            MAX_WITNESS_DISTANCE,
+            &path_loss::Settings::default(),
         );
         assert_eq!(Ok(()), resp11);
     }
 
+    #[tokio::test]
+    async fn test_checks_bypassed_for_test_gateways() {
+        let beaconer = PublicKeyBinary::from_str(PUBKEY1).unwrap();
+        let witness = PublicKeyBinary::from_str(PUBKEY2).unwrap();
+        let test_gateways = TestGateways::from_iter([beaconer.clone()]);
+        let entropy_start = Utc.timestamp_millis_opt(ENTROPY_TIMESTAMP).unwrap();
+        let mut poc = Poc::new(
+            valid_beacon_report(entropy_start),
+            vec![],
+            entropy_start,
+            ENTROPY_VERSION,
+            test_gateways,
+        )
+        .await;
+        let hex_density_map = SharedHexDensityMap::new();
+        hex_density_map
+            .swap(HashMap::from([(LOC0, Decimal::new(5, 1))]))
+            .await;
+
+        // the test beaconer is scaled at 1, and the bypass recorded once
+        assert_eq!(
+            Decimal::ONE,
+            poc.hex_scale(&beaconer, LOC0, &hex_density_map).await
+        );
+        assert_eq!(
+            Decimal::ONE,
+            poc.hex_scale(&beaconer, LOC0, &hex_density_map).await
+        );
+        assert_eq!(
+            Decimal::new(5, 1),
+            poc.hex_scale(&witness, LOC0, &hex_density_map).await
+        );
+        assert_eq!(
+            &[Bypass {
+                pub_key: beaconer.clone(),
+                check: BypassedCheck::DensityScaling,
+            }],
+            poc.bypasses()
+        );
+
+        // the distance check is bypassed whichever side is a test gateway
+        assert_eq!(
+            (u32::MAX, true),
+            poc.max_witness_distance(&beaconer, &witness, MAX_WITNESS_DISTANCE)
+        );
+        assert_eq!(
+            (u32::MAX, true),
+            poc.max_witness_distance(&witness, &beaconer, MAX_WITNESS_DISTANCE)
+        );
+        assert_eq!(
+            (MAX_WITNESS_DISTANCE, false),
+            poc.max_witness_distance(&witness, &witness, MAX_WITNESS_DISTANCE)
+        );
+    }
+
     fn beaconer_gateway_info(
         location: Option<u64>,
         region: ProtoRegion,
@@ -1407,6 +2278,8 @@ mod tests {
             gain: 12,
             elevation: 100,
             region,
+            onboarded_at: None,
+            updated_at: None,
         });
         GatewayInfo {
             address: PublicKeyBinary::from_str(PUBKEY1).unwrap(),
@@ -1425,6 +2298,8 @@ mod tests {
             gain: 20,
             elevation: 100,
             region,
+            onboarded_at: None,
+            updated_at: None,
         });
         GatewayInfo {
             address: PublicKeyBinary::from_str(PUBKEY2).unwrap(),
@@ -1532,4 +2407,110 @@ mod tests {
             report,
         }
     }
+
+    fn reporter(byte: u8) -> PublicKeyBinary {
+        PublicKeyBinary::from(vec![byte])
+    }
+
+    #[tokio::test]
+    async fn later_reports_of_a_gateway_verified_are_dups() {
+        let (a, b) = (reporter(1), reporter(2));
+        let reporters = [&a, &b, &a, &a, &b];
+        // the first report of `a` fails to be verified, so its next is
+        let outcomes = verify_per_gateway(
+            &reporters,
+            2,
+            std::time::Duration::from_secs(10),
+            |index| async move {
+                match index {
+                    0 => Err(()),
+                    _ => Ok(index),
+                }
+            },
+        )
+        .await;
+        assert_eq!(
+            outcomes,
+            vec![
+                Some(WitnessOutcome::Failed),
+                Some(WitnessOutcome::Verified(1)),
+                Some(WitnessOutcome::Verified(2)),
+                Some(WitnessOutcome::Duplicate),
+                Some(WitnessOutcome::Duplicate),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn gateways_are_verified_concurrently_their_reports_in_turn() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        };
+        let gateways: Vec<PublicKeyBinary> = (0..6).map(reporter).collect();
+        // every gateway but the last reports twice, failing to be verified
+        // the first time
+        let reporters: Vec<&PublicKeyBinary> = gateways.iter().chain(&gateways[..5]).collect();
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let verifying: Mutex<Vec<&PublicKeyBinary>> = Mutex::default();
+        let outcomes =
+            verify_per_gateway(&reporters, 3, std::time::Duration::from_secs(10), |index| {
+                let (in_flight, max_in_flight, verifying) =
+                    (&in_flight, &max_in_flight, &verifying);
+                let reporter = reporters[index];
+                async move {
+                    {
+                        let mut verifying = verifying.lock().unwrap();
+                        assert!(!verifying.contains(&reporter));
+                        verifying.push(reporter);
+                    }
+                    let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(running, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    verifying
+                        .lock()
+                        .unwrap()
+                        .retain(|verified| *verified != reporter);
+                    if index < 5 {
+                        Err(())
+                    } else {
+                        Ok(index)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        let verified: Vec<usize> = outcomes
+            .iter()
+            .filter_map(|outcome| match outcome {
+                Some(WitnessOutcome::Verified(index)) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(verified, vec![5, 6, 7, 8, 9, 10]);
+    }
+
+    #[tokio::test]
+    async fn reports_past_the_time_budget_are_left_unverified() {
+        let gateways: Vec<PublicKeyBinary> = (0..3).map(reporter).collect();
+        let reporters: Vec<&PublicKeyBinary> = gateways.iter().collect();
+        let outcomes = verify_per_gateway(
+            &reporters,
+            3,
+            std::time::Duration::from_millis(100),
+            |index| async move {
+                if index > 0 {
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                }
+                Ok::<_, ()>(index)
+            },
+        )
+        .await;
+        assert_eq!(
+            outcomes,
+            vec![Some(WitnessOutcome::Verified(0)), None, None]
+        );
+    }
 }
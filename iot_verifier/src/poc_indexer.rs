@@ -0,0 +1,102 @@
+//! Hourly indexing of the gateways of the poc output files, so the poc
+//! status api only fetches the files of the queried gateway.
+
+use crate::meta::Meta;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use file_store::poc_archive::{PocArchive, POC_FILE_TYPES};
+use sqlx::PgPool;
+use tokio::time;
+
+const LAST_INDEXED_HOUR: &str = "last_indexed_poc_hour";
+/// Hours are indexed once this long past their end, leaving time for the
+/// files written within them to be uploaded
+const UPLOAD_DELAY_MINUTES: i64 = 30;
+const TICK_INTERVAL: time::Duration = time::Duration::from_secs(15 * 60);
+
+/// Indexes every hour once it is over and its files are uploaded, see
+/// [`PocArchive::index`]. The last indexed hour is kept in the meta table and
+/// hours missed while the verifier was down are caught up on start. Indexing
+/// is an optimisation only, a failed hour is logged and retried on the next
+/// tick
+pub struct PocIndexer {
+    archive: PocArchive,
+    pool: PgPool,
+}
+
+/// The hours after `last_indexed`, or the last complete hour when none was
+/// indexed yet, which ended at least the upload delay before `now`
+fn hours_due(last_indexed: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let hour = Duration::hours(1);
+    let Ok(incomplete) = (now - Duration::minutes(UPLOAD_DELAY_MINUTES)).duration_trunc(hour)
+    else {
+        return vec![];
+    };
+    let mut next = last_indexed.map_or(incomplete - hour, |last| last + hour);
+    let mut due = vec![];
+    while next < incomplete {
+        due.push(next);
+        next = next + hour;
+    }
+    due
+}
+
+impl PocIndexer {
+    pub fn new(archive: PocArchive, pool: PgPool) -> Self {
+        Self { archive, pool }
+    }
+
+    pub async fn run(self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting poc indexer");
+        let mut timer = time::interval(TICK_INTERVAL);
+        timer.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = timer.tick() => {
+                    if let Err(err) = self.index_due_hours().await {
+                        tracing::error!(?err, "failed to index poc files, retrying next tick");
+                    }
+                }
+            }
+        }
+        tracing::info!("stopping poc indexer");
+        Ok(())
+    }
+
+    async fn index_due_hours(&self) -> anyhow::Result<()> {
+        let last_indexed = Meta::last_timestamp(&self.pool, LAST_INDEXED_HOUR).await?;
+        for hour in hours_due(last_indexed, Utc::now()) {
+            for file_type in POC_FILE_TYPES {
+                self.archive.index(file_type, hour).await?;
+            }
+            Meta::update_last_timestamp(&self.pool, LAST_INDEXED_HOUR, Some(hour)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn hours_are_due_once_uploaded() {
+        let hour = |h| Utc.with_ymd_and_hms(2023, 5, 1, h, 0, 0).unwrap();
+        // 13:00 is not due until 14:30
+        assert_eq!(
+            hours_due(None, hour(14) + Duration::minutes(20)),
+            vec![hour(12)]
+        );
+        assert_eq!(
+            hours_due(None, hour(14) + Duration::minutes(30)),
+            vec![hour(13)]
+        );
+        assert!(hours_due(Some(hour(13)), hour(14) + Duration::minutes(40)).is_empty());
+        // catching up after downtime
+        assert_eq!(
+            hours_due(Some(hour(10)), hour(14) + Duration::minutes(40)),
+            vec![hour(11), hour(12), hour(13)]
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use crate::entropy::ENTROPY_LIFESPAN;
+use crate::catchup::WorkOrder;
 use chrono::{DateTime, Duration, Utc};
+use helium_crypto::PublicKeyBinary;
 use serde::{Deserialize, Serialize};
 
 const REPORT_INSERT_SQL: &str = "insert into poc_report (
@@ -7,9 +8,11 @@ const REPORT_INSERT_SQL: &str = "insert into poc_report (
     remote_entropy,
     packet_data,
     report_data,
+    report_key,
     report_timestamp,
     report_type,
-    status
+    status,
+    gateway
 ) ";
 
 #[derive(sqlx::Type, Serialize, Deserialize, Debug)]
@@ -33,9 +36,13 @@ pub struct InsertBindings {
     pub remote_entropy: Vec<u8>,
     pub packet_data: Vec<u8>,
     pub buf: Vec<u8>,
+    /// Key of `buf` once offloaded to the report store, `buf` is empty then
+    pub report_key: Option<String>,
     pub received_ts: DateTime<Utc>,
     pub report_type: ReportType,
     pub status: IotStatus,
+    /// Gateway which beaconed or witnessed
+    pub gateway: PublicKeyBinary,
 }
 
 #[derive(sqlx::FromRow, Deserialize, Serialize, Debug)]
@@ -45,6 +52,8 @@ pub struct Report {
     pub remote_entropy: Vec<u8>,
     pub packet_data: Vec<u8>,
     pub report_data: Vec<u8>,
+    #[sqlx(default)]
+    pub report_key: Option<String>,
     pub report_type: ReportType,
     pub status: IotStatus,
     pub attempts: i32,
@@ -116,9 +125,11 @@ impl Report {
                 .push_bind(insert.remote_entropy)
                 .push_bind(insert.packet_data)
                 .push_bind(insert.buf)
+                .push_bind(insert.report_key)
                 .push_bind(insert.received_ts)
                 .push_bind(insert.report_type)
-                .push_bind(insert.status);
+                .push_bind(insert.status)
+                .push_bind(insert.gateway);
         });
         // append conflict strategy to each insert row
         query_builder.push(" on conflict (id) do nothing ");
@@ -169,36 +180,44 @@ impl Report {
         Ok(())
     }
 
-    pub async fn delete_report(
+    /// Delete the reports with the ingest ids `ids`. Returns the number of
+    /// reports deleted
+    pub async fn delete_reports(
         executor: impl sqlx::PgExecutor<'_>,
-        id: &Vec<u8>,
-    ) -> Result<(), ReportError> {
-        sqlx::query(
+        ids: &[Vec<u8>],
+    ) -> Result<u64, ReportError> {
+        let deleted = sqlx::query(
             r#"
             delete from poc_report
-            where id = $1
+            where id = any($1)
             "#,
         )
-        .bind(id)
+        .bind(ids)
         .execute(executor)
-        .await?;
-        Ok(())
+        .await?
+        .rows_affected();
+        Ok(deleted)
     }
 
+    /// Beacons ready for verification, those whose entropy is older than
+    /// `hold`, prioritized by a re-verification first and then in `order`
     pub async fn get_next_beacons<'c, E>(
         executor: E,
         max_retries: u64,
+        hold: Duration,
+        order: WorkOrder,
     ) -> Result<Vec<Self>, ReportError>
     where
         E: sqlx::Executor<'c, Database = sqlx::Postgres>,
     {
-        let entropy_min_time = Utc::now() - Duration::seconds(ENTROPY_LIFESPAN);
-        Ok(sqlx::query_as::<_, Self>(
+        let entropy_min_time = Utc::now() - hold;
+        Ok(sqlx::query_as::<_, Self>(&format!(
             r#"
             select poc_report.id,
                 poc_report.remote_entropy,
                 poc_report.packet_data,
                 poc_report.report_data,
+                poc_report.report_key,
                 poc_report.report_type,
                 poc_report.status,
                 poc_report.attempts,
@@ -212,16 +231,40 @@ impl Report {
             where poc_report.report_type = 'beacon' and status = 'ready'
             and entropy.timestamp < $1
             and poc_report.attempts < $2
-            order by poc_report.created_at asc
+            order by poc_report.prioritized desc, {}
             limit 25000
             "#,
-        )
+            order.order_by()
+        ))
         .bind(entropy_min_time)
         .bind(max_retries as i64)
         .fetch_all(executor)
         .await?)
     }
 
+    /// Creation time of the oldest beacon that get_next_beacons can select
+    pub async fn oldest_ready_beacon(
+        executor: impl sqlx::PgExecutor<'_>,
+        max_retries: u64,
+        hold: Duration,
+    ) -> Result<Option<DateTime<Utc>>, ReportError> {
+        let entropy_min_time = Utc::now() - hold;
+        Ok(sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            r#"
+            select min(poc_report.created_at)
+            from poc_report
+            inner join entropy on poc_report.remote_entropy=entropy.data
+            where poc_report.report_type = 'beacon' and status = 'ready'
+            and entropy.timestamp < $1
+            and poc_report.attempts < $2
+            "#,
+        )
+        .bind(entropy_min_time)
+        .bind(max_retries as i64)
+        .fetch_one(executor)
+        .await?)
+    }
+
     pub async fn get_pending_beacon_packet_data<'c, E>(
         executor: E,
     ) -> Result<Vec<Self>, ReportError>
@@ -397,6 +440,23 @@ impl Report {
         .map(|count| count as u64)?)
     }
 
+    /// Reports of `report_type` waiting to be verified
+    pub async fn count_unverified(
+        executor: impl sqlx::PgExecutor<'_>,
+        report_type: ReportType,
+    ) -> Result<u64, ReportError> {
+        Ok(sqlx::query_scalar::<_, i64>(
+            r#"
+            select count(*) from poc_report
+            where report_type = $1 and status in ('pending','ready')
+            "#,
+        )
+        .bind(report_type)
+        .fetch_one(executor)
+        .await
+        .map(|count| count as u64)?)
+    }
+
     pub async fn get_stale_witnesses<'c, E>(
         executor: E,
         stale_period: Duration,
@@ -0,0 +1,169 @@
+//! Api of the verification outcomes of gateways, served alongside the hex
+//! density api.
+
+use crate::{
+    density_service::{self, sign},
+    proto::{self, GatewayPocsReqV1, GatewayPocsRespV1, PocRecordKindV1, PocRecordV1},
+};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    grpc::{GrpcResult, GrpcStreamResult},
+    poc_archive::{PocArchive, PocRecord},
+    traits::{TimestampDecode, TimestampEncode},
+};
+use futures::StreamExt;
+use helium_crypto::{Keypair, PublicKeyBinary};
+use helium_proto::{
+    services::poc_lora::{LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1, LoraPocV1},
+    Message,
+};
+use std::{ops::Range, sync::Arc};
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_poc_status_request");
+/// Longest period a single request may cover
+const MAX_PERIOD_HOURS: i64 = 24;
+/// Records per message of a streamed response
+const RECORDS_PER_MESSAGE: usize = 100;
+
+/// Serves the valid pocs a gateway beaconed or witnessed in and its rejected
+/// reports from the output bucket of the verifier, see [`PocArchive`].
+/// Responses are signed with the keypair of the hex density api
+pub struct PocStatusService {
+    archive: PocArchive,
+    signing_key: Arc<Keypair>,
+}
+
+impl PocStatusService {
+    pub fn new(settings: &density_service::Settings, archive: PocArchive) -> anyhow::Result<Self> {
+        Ok(Self {
+            archive,
+            signing_key: Arc::new(settings.signing_keypair()?),
+        })
+    }
+}
+
+fn period(request: &GatewayPocsReqV1) -> Result<Range<DateTime<Utc>>, Status> {
+    let start = request
+        .start
+        .to_timestamp()
+        .map_err(|_| Status::invalid_argument("invalid start"))?;
+    let end = request
+        .end
+        .to_timestamp()
+        .map_err(|_| Status::invalid_argument("invalid end"))?;
+    if end <= start {
+        return Err(Status::invalid_argument("end must be after start"));
+    }
+    if end - start > Duration::hours(MAX_PERIOD_HOURS) {
+        return Err(Status::invalid_argument(format!(
+            "period over the limit of {MAX_PERIOD_HOURS} hours"
+        )));
+    }
+    Ok(start..end)
+}
+
+fn record_of(record: PocRecord) -> PocRecordV1 {
+    let received_timestamp = record.received_timestamp().encode_timestamp_millis();
+    let (kind, report) = match record {
+        PocRecord::Valid(poc) => (PocRecordKindV1::Valid, LoraPocV1::from(poc).encode_to_vec()),
+        PocRecord::InvalidBeacon(report) => (
+            PocRecordKindV1::InvalidBeacon,
+            LoraInvalidBeaconReportV1::from(report).encode_to_vec(),
+        ),
+        PocRecord::InvalidWitness(report) => (
+            PocRecordKindV1::InvalidWitness,
+            LoraInvalidWitnessReportV1::from(report).encode_to_vec(),
+        ),
+    };
+    PocRecordV1 {
+        kind: kind as i32,
+        received_timestamp,
+        report,
+    }
+}
+
+#[tonic::async_trait]
+impl proto::poc_status_server::PocStatus for PocStatusService {
+    type gateway_pocsStream = GrpcStreamResult<GatewayPocsRespV1>;
+    async fn gateway_pocs(
+        &self,
+        request: Request<GatewayPocsReqV1>,
+    ) -> GrpcResult<Self::gateway_pocsStream> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "gateway_pocs");
+
+        let period = period(&request)?;
+        let hotspot_key = PublicKeyBinary::from(request.hotspot_key);
+        let records = self
+            .archive
+            .gateway_records(&hotspot_key, period)
+            .await
+            .map_err(|err| {
+                tracing::error!(%hotspot_key, ?err, "poc archive listing failed");
+                Status::internal("poc archive listing failed")
+            })?;
+
+        let signing_key = self.signing_key.clone();
+        let (tx, rx) = mpsc::channel(20);
+        tokio::spawn(async move {
+            let mut records = records.chunks(RECORDS_PER_MESSAGE);
+            while let Some(chunk) = records.next().await {
+                let result = chunk
+                    .into_iter()
+                    .map(|record| record.map(record_of))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        tracing::error!(%hotspot_key, ?err, "poc archive read failed");
+                        Status::internal("poc archive read failed")
+                    })
+                    .and_then(|records| {
+                        let mut resp = GatewayPocsRespV1 {
+                            records,
+                            timestamp: Utc::now().encode_timestamp(),
+                            signer: signing_key.public_key().into(),
+                            signature: vec![],
+                        };
+                        resp.signature = sign(&signing_key, &resp)?;
+                        Ok(resp)
+                    });
+                let failed = result.is_err();
+                if tx.send(result).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(start: u64, end: u64) -> GatewayPocsReqV1 {
+        GatewayPocsReqV1 {
+            hotspot_key: vec![],
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn periods_are_bounded() {
+        let start = 1_680_000_000;
+        let period = period(&request(start, start + 3600)).expect("valid period");
+        assert_eq!(period.end - period.start, Duration::hours(1));
+
+        for invalid in [
+            request(start, start),
+            request(start + 1, start),
+            request(start, start + 24 * 3600 + 1),
+        ] {
+            let status = super::period(&invalid).expect_err("invalid period");
+            assert_eq!(tonic::Code::InvalidArgument, status.code());
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Messages of the services of the verifier, which are not part of
+//! helium-proto. Timestamps are in seconds and scales in ten thousandths.
+
+use file_store::{impl_msg_verify, iot_witness_quality::WitnessQualityReportV1};
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_verifier.HexDensity.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_verifier.WitnessQuality.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.iot_verifier.PocStatus.rs"
+));
+include!(concat!(env!("OUT_DIR"), "/helium.iot_verifier.Reverify.rs"));
+
+/// Query of the transmit scale of the res 12 `hex`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScaleReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub hex: u64,
+}
+
+/// Transmit scale of a res 12 hex
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScaleV1 {
+    #[prost(uint64, tag = "1")]
+    pub hex: u64,
+    #[prost(uint32, tag = "2")]
+    pub scale: u32,
+}
+
+/// Transmit scale of the queried hex, as of the map refreshed at
+/// `refreshed_at`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScaleRespV1 {
+    #[prost(message, optional, tag = "1")]
+    pub scale: Option<HexScaleV1>,
+    #[prost(uint64, tag = "2")]
+    pub refreshed_at: u64,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Query of the whole scaling map
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScalingMapReqV1 {}
+
+/// A chunk of the scaling map refreshed at `refreshed_at`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexScalingMapRespV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub scales: Vec<HexScaleV1>,
+    #[prost(uint64, tag = "2")]
+    pub refreshed_at: u64,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Query of the witness quality of a gateway
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessQualityReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot_key: Vec<u8>,
+}
+
+/// Witness quality of the queried gateway over the window ending now, as
+/// written to `iot_witness_quality` files
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WitnessQualityRespV1 {
+    #[prost(message, optional, tag = "1")]
+    pub quality: Option<WitnessQualityReportV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Query of the verification outcomes involving a gateway of the reports
+/// received from `start` until `end`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayPocsReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot_key: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(uint64, tag = "3")]
+    pub end: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PocRecordKindV1 {
+    /// `report` is a `LoraPocV1` the gateway beaconed or witnessed in
+    Valid = 0,
+    /// `report` is a `LoraInvalidBeaconReportV1`
+    InvalidBeacon = 1,
+    /// `report` is a `LoraInvalidWitnessReportV1`
+    InvalidWitness = 2,
+}
+
+/// A verification outcome as written to the verifier output files
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PocRecordV1 {
+    #[prost(enumeration = "PocRecordKindV1", tag = "1")]
+    pub kind: i32,
+    /// Unix timestamp in milliseconds of when the beacon of the poc or the
+    /// rejected report was received
+    #[prost(uint64, tag = "2")]
+    pub received_timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub report: Vec<u8>,
+}
+
+/// A chunk of the verification outcomes involving the queried gateway, in
+/// no particular order
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayPocsRespV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub records: Vec<PocRecordV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Admin request to re-verify the unverified reports of `gateway`, signed
+/// by an admin key of the api
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayReverifyReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub gateway: Vec<u8>,
+    /// Why the gateway is re-verified, recorded with the request
+    #[prost(string, tag = "2")]
+    pub reason: String,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+/// Outcome of a re-verification: whether iot config still knows the gateway
+/// and how many of its pocs were re-queued
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayReverifyRespV1 {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(bool, tag = "2")]
+    pub found: bool,
+    #[prost(uint64, tag = "3")]
+    pub requeued: u64,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+impl_msg_verify!(HexScaleRespV1, signature);
+impl_msg_verify!(HexScalingMapRespV1, signature);
+impl_msg_verify!(WitnessQualityRespV1, signature);
+impl_msg_verify!(GatewayPocsRespV1, signature);
+impl_msg_verify!(GatewayReverifyReqV1, signature);
+impl_msg_verify!(GatewayReverifyRespV1, signature);
@@ -1,5 +1,8 @@
-use crate::{entropy::Entropy, poc_report::Report, telemetry, Settings};
-use chrono::Duration;
+use crate::{
+    catchup::Catchup, entropy::Entropy, poc_report::Report, report_store::ReportStore, telemetry,
+    Settings,
+};
+use chrono::{DateTime, Duration, Utc};
 use file_store::{
     file_sink::{self, FileSinkClient},
     file_upload,
@@ -10,35 +13,34 @@ use file_store::{
     traits::{IngestId, MsgDecode},
     FileType,
 };
-use futures::stream::{self, StreamExt};
+use futures::{
+    future,
+    stream::{self, StreamExt},
+};
 use helium_proto::services::poc_lora::{
     InvalidParticipantSide, InvalidReason, LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1,
 };
-use lazy_static::lazy_static;
-use sqlx::{PgPool, Postgres};
-use std::{ops::DerefMut, path::Path};
-use tokio::{
-    sync::Mutex,
-    time::{self, MissedTickBehavior},
-};
+use sqlx::PgPool;
+use std::path::Path;
+use task_scheduler::{Schedule, Scheduler};
+use tokio::time;
 
-const DB_POLL_TIME: time::Duration = time::Duration::from_secs(60 * 35);
 const PURGER_WORKERS: usize = 50;
 
-lazy_static! {
-    /// the period after which a beacon report in the DB will be deemed stale
-    static ref BEACON_STALE_PERIOD: Duration = Duration::minutes(45);
-    /// the period after which a witness report in the DB will be deemed stale
-    static ref WITNESS_STALE_PERIOD: Duration = Duration::minutes(45);
-    /// the period after which an entropy entry in the DB will be deemed stale
-    static ref ENTROPY_STALE_PERIOD: Duration = Duration::minutes(60);
-}
-
 pub struct Purger {
     pool: PgPool,
     cache: String,
     output: file_store::Settings,
     base_stale_period: Duration,
+    beacon_stale_period: Duration,
+    witness_stale_period: Duration,
+    entropy_stale_period: Duration,
+    delete_batch_size: usize,
+    dry_run: bool,
+    schedule: Schedule,
+    jitter: time::Duration,
+    catchup: Catchup,
+    report_store: ReportStore,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -46,23 +48,42 @@ pub struct Purger {
 pub struct NewPurgerError(#[from] db_store::Error);
 
 impl Purger {
-    pub async fn from_settings(settings: &Settings, pool: PgPool) -> Result<Self, NewPurgerError> {
+    pub async fn from_settings(
+        settings: &Settings,
+        pool: PgPool,
+        report_store: ReportStore,
+    ) -> Result<Self, NewPurgerError> {
         let cache = settings.cache.clone();
         let output = settings.output.clone();
         let base_stale_period = settings.base_stale_period();
+        let catchup = Catchup::from_settings(settings);
         Ok(Self {
             pool,
             cache,
             output,
             base_stale_period,
+            beacon_stale_period: settings.beacon_stale_period(),
+            witness_stale_period: settings.witness_stale_period(),
+            entropy_stale_period: settings.entropy_stale_period(),
+            delete_batch_size: settings.purger_delete_batch_size.max(1),
+            dry_run: settings.purger_dry_run,
+            schedule: settings.purger_schedule.clone(),
+            jitter: settings.purger_jitter(),
+            catchup,
+            report_store,
         })
     }
 
+    /// Only report what would be purged, see [`Purger::handle_dry_run_tick`]
+    pub fn dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
     pub async fn run(&self, shutdown: &triggered::Listener) -> anyhow::Result<()> {
-        tracing::info!("starting purger");
+        tracing::info!(dry_run = self.dry_run, "starting purger");
 
-        let mut db_timer = time::interval(DB_POLL_TIME);
-        db_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut db_timer =
+            Scheduler::new("purger", self.schedule.clone(), self.jitter).immediately();
 
         let store_base_path = Path::new(&self.cache);
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
@@ -97,6 +118,7 @@ impl Purger {
         tokio::spawn(async move { invalid_beacon_sink_server.run().await });
         tokio::spawn(async move { invalid_witness_sink_server.run().await });
         tokio::spawn(async move { file_upload.run(&upload_shutdown).await });
+        let sinks = [invalid_beacon_sink.clone(), invalid_witness_sink.clone()];
 
         loop {
             if shutdown.is_triggered() {
@@ -104,9 +126,10 @@ impl Purger {
             }
             tokio::select! {
                 _ = shutdown.clone() => break,
+                err = file_sink::first_failure(&sinks) => return Err(err.into()),
                 _ = db_timer.tick() =>
                     match self.handle_db_tick(&invalid_beacon_sink, &invalid_witness_sink).await {
-                    Ok(()) => (),
+                    Ok(()) => poc_metrics::status::heartbeat("poc", "purger"),
                     Err(err) => {
                         tracing::error!("fatal purger error: {err:?}");
                     }
@@ -126,7 +149,23 @@ impl Purger {
         // for each we have to write out an invalid report to S3
         // as these wont have previously resulted in a file going to s3
         // once the report is safely on s3 we can then proceed to purge from the db
-        let beacon_stale_period = self.base_stale_period + *BEACON_STALE_PERIOD;
+        // while the verifier is catching up the stale periods are widened so
+        // that reports still waiting to be verified are not purged
+        let in_catchup = self.catchup.is_active(&self.pool).await?;
+        let base_stale_period = self.base_stale_period + self.catchup.stale_extension(in_catchup);
+        let beacon_stale_period = base_stale_period + self.beacon_stale_period;
+        let witness_stale_period = base_stale_period + self.witness_stale_period;
+        let entropy_stale_period = base_stale_period + self.entropy_stale_period;
+        if self.dry_run {
+            return self
+                .handle_dry_run_tick(
+                    beacon_stale_period,
+                    witness_stale_period,
+                    entropy_stale_period,
+                )
+                .await;
+        }
+
         tracing::info!(
             "starting query get_stale_pending_beacons with stale period: {beacon_stale_period}"
         );
@@ -134,24 +173,29 @@ impl Purger {
         tracing::info!("completed query get_stale_beacons");
         tracing::info!("purging {:?} stale beacons", stale_beacons.len());
 
-        let tx = Mutex::new(self.pool.begin().await?);
-        stream::iter(stale_beacons)
-            .for_each_concurrent(PURGER_WORKERS, |report| async {
-                match self
-                    .handle_purged_beacon(&tx, report, invalid_beacon_sink)
-                    .await
-                {
-                    Ok(()) => (),
+        let purged_beacons: Vec<Vec<u8>> = stream::iter(stale_beacons)
+            .map(|report| async move {
+                match self.handle_purged_beacon(report, invalid_beacon_sink).await {
+                    Ok(beacon_id) => Some(beacon_id),
                     Err(err) => {
-                        tracing::warn!("failed to purge beacon: {err:?}")
+                        tracing::warn!("failed to purge beacon: {err:?}");
+                        None
                     }
                 }
             })
+            .buffer_unordered(PURGER_WORKERS)
+            .filter_map(future::ready)
+            .collect()
             .await;
-        invalid_beacon_sink.commit().await?;
-        tx.into_inner().commit().await?;
+        let deleted_beacons = commit_then_delete(
+            &self.pool,
+            invalid_beacon_sink,
+            &purged_beacons,
+            self.delete_batch_size,
+        )
+        .await?;
+        telemetry::decrement_num_beacons_by(deleted_beacons);
 
-        let witness_stale_period = self.base_stale_period + *WITNESS_STALE_PERIOD;
         tracing::info!(
             "starting query get_stale_pending_witnesses with stale period: {witness_stale_period}"
         );
@@ -160,37 +204,69 @@ impl Purger {
         let num_stale_witnesses = stale_witnesses.len();
         tracing::info!("purging {num_stale_witnesses} stale witnesses");
 
-        let tx = Mutex::new(self.pool.begin().await?);
-        stream::iter(stale_witnesses)
-            .for_each_concurrent(PURGER_WORKERS, |report| async {
+        let purged_witnesses: Vec<Vec<u8>> = stream::iter(stale_witnesses)
+            .map(|report| async move {
                 match self
-                    .handle_purged_witness(&tx, report, invalid_witness_sink)
+                    .handle_purged_witness(report, invalid_witness_sink)
                     .await
                 {
-                    Ok(()) => (),
+                    Ok(witness_id) => Some(witness_id),
                     Err(err) => {
-                        tracing::warn!("failed to purge witness: {err:?}")
+                        tracing::warn!("failed to purge witness: {err:?}");
+                        None
                     }
                 }
             })
+            .buffer_unordered(PURGER_WORKERS)
+            .filter_map(future::ready)
+            .collect()
             .await;
-        invalid_witness_sink.commit().await?;
-        tx.into_inner().commit().await?;
+        commit_then_delete(
+            &self.pool,
+            invalid_witness_sink,
+            &purged_witnesses,
+            self.delete_batch_size,
+        )
+        .await?;
         tracing::info!("completed purging {num_stale_witnesses} stale witnesses");
 
         // purge any stale entropy, no need to output anything to s3 here
-        _ = Entropy::purge(&self.pool, self.base_stale_period + *ENTROPY_STALE_PERIOD).await;
+        _ = Entropy::purge(&self.pool, entropy_stale_period).await;
+        Ok(())
+    }
+
+    /// Report the stale beacons, witnesses and entropy a purge would purge
+    /// with the given stale periods, in the logs and the
+    /// `purger_dry_run_stale` gauge. Writes nothing to S3 and deletes
+    /// nothing from the DB
+    async fn handle_dry_run_tick(
+        &self,
+        beacon_stale_period: Duration,
+        witness_stale_period: Duration,
+        entropy_stale_period: Duration,
+    ) -> anyhow::Result<()> {
+        let stale_beacons = Report::get_stale_beacons(&self.pool, beacon_stale_period).await?;
+        StaleSummary::of(&stale_beacons).report("beacon", beacon_stale_period);
+        let stale_witnesses = Report::get_stale_witnesses(&self.pool, witness_stale_period).await?;
+        StaleSummary::of(&stale_witnesses).report("witness", witness_stale_period);
+        let stale_entropy = Entropy::count_stale(&self.pool, entropy_stale_period).await?;
+        telemetry::purger_dry_run_stale("entropy", stale_entropy);
+        tracing::info!(
+            stale_period = %entropy_stale_period,
+            count = stale_entropy,
+            "dry run: would purge stale entropy"
+        );
         Ok(())
     }
 
+    /// Write out the invalid report of a stale beacon. Returns its ingest id
     async fn handle_purged_beacon(
         &self,
-        tx: &Mutex<sqlx::Transaction<'_, Postgres>>,
         db_beacon: Report,
         invalid_beacon_sink: &FileSinkClient,
-    ) -> anyhow::Result<()> {
-        let beacon_buf: &[u8] = &db_beacon.report_data;
-        let beacon_report = IotBeaconIngestReport::decode(beacon_buf)?;
+    ) -> anyhow::Result<Vec<u8>> {
+        let beacon_buf = self.report_store.body(&db_beacon).await?;
+        let beacon_report = IotBeaconIngestReport::decode(beacon_buf.as_ref())?;
         let beacon_id = beacon_report.ingest_id();
         let beacon = &beacon_report.report;
         let received_timestamp = beacon_report.received_timestamp;
@@ -207,20 +283,17 @@ impl Purger {
                 &[("reason", InvalidReason::Stale.as_str_name())],
             )
             .await?;
-        // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &beacon_id).await?;
-        telemetry::decrement_num_beacons();
-        Ok(())
+        Ok(beacon_id)
     }
 
+    /// Write out the invalid report of a stale witness. Returns its ingest id
     async fn handle_purged_witness(
         &self,
-        tx: &Mutex<sqlx::Transaction<'_, Postgres>>,
         db_witness: Report,
         invalid_witness_sink: &FileSinkClient,
-    ) -> anyhow::Result<()> {
-        let witness_buf: &[u8] = &db_witness.report_data;
-        let witness_report = IotWitnessIngestReport::decode(witness_buf)?;
+    ) -> anyhow::Result<Vec<u8>> {
+        let witness_buf = self.report_store.body(&db_witness).await?;
+        let witness_report = IotWitnessIngestReport::decode(witness_buf.as_ref())?;
         let witness_id = witness_report.ingest_id();
         let received_timestamp = witness_report.received_timestamp;
         let invalid_witness_report_proto: LoraInvalidWitnessReportV1 = IotInvalidWitnessReport {
@@ -237,9 +310,144 @@ impl Purger {
                 &[("reason", InvalidReason::Stale.as_str_name())],
             )
             .await?;
+        Ok(witness_id)
+    }
+}
 
-        // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &witness_id).await?;
-        Ok(())
+/// Commit the invalid reports written to `sink`, then delete the purged
+/// reports with the ingest ids `ids` from the DB, in chunks of `batch_size`.
+/// The reports stay in the DB, to be purged again, unless the sink stored
+/// the commit. Returns the number of reports deleted
+async fn commit_then_delete(
+    pool: &PgPool,
+    sink: &FileSinkClient,
+    ids: &[Vec<u8>],
+    batch_size: usize,
+) -> anyhow::Result<u64> {
+    sink.commit().await?.await??;
+    let mut deleted = 0;
+    for chunk in ids.chunks(batch_size) {
+        deleted += Report::delete_reports(pool, chunk).await?;
+    }
+    Ok(deleted)
+}
+
+/// What a purge of stale reports of a type would purge
+struct StaleSummary {
+    count: usize,
+    offloaded: usize,
+    oldest: Option<DateTime<Utc>>,
+    newest: Option<DateTime<Utc>>,
+}
+
+impl StaleSummary {
+    fn of(reports: &[Report]) -> Self {
+        let created = || reports.iter().filter_map(|report| report.created_at);
+        Self {
+            count: reports.len(),
+            offloaded: reports
+                .iter()
+                .filter(|report| report.report_key.is_some())
+                .count(),
+            oldest: created().min(),
+            newest: created().max(),
+        }
+    }
+
+    fn report(&self, kind: &'static str, stale_period: Duration) {
+        telemetry::purger_dry_run_stale(kind, self.count as u64);
+        tracing::info!(
+            kind,
+            stale_period = %stale_period,
+            count = self.count,
+            offloaded = self.offloaded,
+            oldest = ?self.oldest,
+            newest = ?self.newest,
+            "dry run: would purge stale reports"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poc_report::{IotStatus, ReportType};
+    use tempfile::TempDir;
+
+    async fn sink(dir: &TempDir, shutdown: triggered::Listener) -> FileSinkClient {
+        let (client, mut server) = file_sink::FileSinkBuilder::new(
+            FileType::IotInvalidBeaconReport,
+            dir.path(),
+            "purger_test",
+            shutdown,
+        )
+        .auto_commit(false)
+        .create()
+        .await
+        .expect("file sink");
+        tokio::spawn(async move { server.run().await });
+        client
+    }
+
+    async fn insert_stale_beacons(pool: &PgPool, ids: &[Vec<u8>]) {
+        for id in ids {
+            Report::insert_into(
+                pool,
+                id.clone(),
+                vec![],
+                id.clone(),
+                vec![],
+                &(Utc::now() - Duration::days(1)),
+                ReportType::Beacon,
+                IotStatus::Pending,
+            )
+            .await
+            .expect("insert report");
+        }
+    }
+
+    async fn count(pool: &PgPool) -> i64 {
+        sqlx::query_scalar("select count(*) from poc_report")
+            .fetch_one(pool)
+            .await
+            .expect("count reports")
+    }
+
+    #[sqlx::test]
+    async fn reports_are_kept_unless_the_commit_is_stored(pool: PgPool) {
+        let ids = vec![vec![1], vec![2], vec![3]];
+        insert_stale_beacons(&pool, &ids).await;
+        let (_trigger, shutdown) = triggered::trigger();
+
+        let dir = TempDir::new().expect("temp dir");
+        let failed = sink(&dir, shutdown.clone()).await;
+        // no file can be created anymore, as on a full disk
+        tokio::fs::remove_dir_all(dir.path().join("tmp"))
+            .await
+            .expect("remove tmp dir");
+        let ack = failed
+            .write(LoraInvalidBeaconReportV1::default(), [])
+            .await
+            .expect("write sent");
+        assert!(ack.await.expect("write acknowledged").is_err());
+        assert!(commit_then_delete(&pool, &failed, &ids, 2).await.is_err());
+        assert_eq!(3, count(&pool).await);
+
+        let dir = TempDir::new().expect("temp dir");
+        let healthy = sink(&dir, shutdown).await;
+        healthy
+            .write(LoraInvalidBeaconReportV1::default(), [])
+            .await
+            .expect("write sent")
+            .await
+            .expect("write acknowledged")
+            .expect("write stored");
+        assert_eq!(
+            3,
+            commit_then_delete(&pool, &healthy, &ids, 2)
+                .await
+                .expect("purged")
+        );
+        assert_eq!(0, count(&pool).await);
     }
 }
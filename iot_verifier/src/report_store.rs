@@ -0,0 +1,147 @@
+//! Storage of the bodies of beacon and witness reports, inline in
+//! `poc_report` or offloaded to the `report_blobs` bucket.
+
+use crate::{
+    poc_report::{InsertBindings, Report},
+    Settings,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use file_store::FileStore;
+use futures::stream::{self, StreamExt};
+use std::borrow::Cow;
+
+/// Prefix of the keys of offloaded report bodies. Objects are not removed
+/// with their report: reports are verified or purged within the stale
+/// periods, so the bucket is expected to expire objects under the prefix with
+/// a lifecycle rule
+pub const KEY_PREFIX: &str = "poc_report";
+const OFFLOAD_WORKERS: usize = 20;
+
+/// An offloaded report body could not be fetched. The runner retries it
+/// without counting an attempt of the report, as an outage of the bucket
+/// would otherwise exhaust the retries of every offloaded report
+#[derive(thiserror::Error, Debug)]
+#[error("report body unavailable: {0}")]
+pub struct FetchError(#[from] file_store::Error);
+
+#[derive(Clone)]
+pub struct ReportStore {
+    blobs: Option<FileStore>,
+    threshold: usize,
+}
+
+impl ReportStore {
+    pub async fn from_settings(settings: &Settings) -> file_store::Result<Self> {
+        let blobs = match &settings.report_blobs {
+            Some(blobs) => Some(FileStore::from_settings(blobs).await?),
+            None => None,
+        };
+        Ok(Self::new(blobs, settings.report_offload_threshold))
+    }
+
+    /// Store offloading bodies of at least `threshold` bytes to `blobs`, or
+    /// keeping them all inline without
+    pub fn new(blobs: Option<FileStore>, threshold: usize) -> Self {
+        Self { blobs, threshold }
+    }
+
+    /// Whether a body of `len` bytes is offloaded
+    fn offloads(&self, len: usize) -> bool {
+        self.blobs.is_some() && len >= self.threshold
+    }
+
+    fn key(id: &[u8]) -> String {
+        format!("{KEY_PREFIX}/{}", URL_SAFE_NO_PAD.encode(id))
+    }
+
+    /// Upload the bodies of the reports of `inserts` over the threshold and
+    /// point the reports at them. Bodies failing to upload stay inline.
+    pub async fn offload(&self, inserts: Vec<InsertBindings>) -> Vec<InsertBindings> {
+        let Some(blobs) = &self.blobs else {
+            return inserts;
+        };
+        stream::iter(inserts)
+            .map(|mut insert| async move {
+                if !self.offloads(insert.buf.len()) {
+                    return insert;
+                }
+                let key = Self::key(&insert.id);
+                match blobs.put_bytes(&key, insert.buf.clone()).await {
+                    Ok(()) => {
+                        insert.buf = vec![];
+                        insert.report_key = Some(key);
+                    }
+                    Err(err) => tracing::warn!(?err, "failed to offload report body, kept inline"),
+                }
+                insert
+            })
+            .buffer_unordered(OFFLOAD_WORKERS)
+            .collect()
+            .await
+    }
+
+    /// The body of `report`, fetched from the bucket if offloaded
+    pub async fn body<'a>(&self, report: &'a Report) -> Result<Cow<'a, [u8]>, FetchError> {
+        self.fetch(&report.report_data, report.report_key.as_deref())
+            .await
+    }
+
+    /// The body of a report from its `report_data` and `report_key` columns
+    pub async fn fetch<'a>(
+        &self,
+        report_data: &'a [u8],
+        report_key: Option<&str>,
+    ) -> Result<Cow<'a, [u8]>, FetchError> {
+        match (report_key, &self.blobs) {
+            (None, _) => Ok(Cow::Borrowed(report_data)),
+            (Some(key), Some(blobs)) => Ok(Cow::Owned(blobs.get_bytes(key).await?)),
+            (Some(key), None) => Err(file_store::Error::not_found(format!(
+                "report body {key} offloaded but report_blobs is not set"
+            ))
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn blobs() -> FileStore {
+        let settings: file_store::Settings = serde_json::from_value(serde_json::json!({
+            "bucket": "report-blobs",
+            "endpoint": "http://localhost:4566",
+        }))
+        .expect("settings");
+        FileStore::from_settings(&settings)
+            .await
+            .expect("file store")
+    }
+
+    #[tokio::test]
+    async fn bodies_from_the_threshold_up_are_offloaded() {
+        let store = ReportStore::new(Some(blobs().await), 100);
+        assert!(!store.offloads(0));
+        assert!(!store.offloads(99));
+        assert!(store.offloads(100));
+        assert!(store.offloads(10_000));
+
+        let inline = ReportStore::new(None, 100);
+        assert!(!inline.offloads(10_000));
+    }
+
+    #[tokio::test]
+    async fn inline_bodies_are_borrowed() {
+        let store = ReportStore::new(None, 100);
+        let body = store.fetch(&[1, 2, 3], None).await.expect("inline body");
+        assert!(matches!(body, Cow::Borrowed([1, 2, 3])));
+
+        let err = store
+            .fetch(&[], Some("poc_report/AQ"))
+            .await
+            .expect_err("no bucket to fetch from");
+        // the runner retries reports failing to fetch without counting an
+        // attempt
+        assert!(anyhow::Error::from(err).is::<FetchError>());
+    }
+}
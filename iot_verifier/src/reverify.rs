@@ -0,0 +1,527 @@
+//! Forced re-verification of the unverified reports of a gateway, once its
+//! metadata was corrected.
+
+use crate::{
+    density_service::{self, sign},
+    gateway_updater::{GatewayUpdaterError, MessageSender},
+    poc_report::ReportType,
+    proto::{self, GatewayReverifyReqV1, GatewayReverifyRespV1},
+    report_store::ReportStore,
+};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    grpc::GrpcResult,
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_witness_report::IotWitnessIngestReport,
+    traits::{MsgDecode, MsgVerify, TimestampDecode, TimestampEncode},
+};
+use futures::stream::TryStreamExt;
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary};
+use iot_config::{client::Client as IotConfigClient, gateway_info::GatewayInfoResolver};
+use sqlx::{PgPool, Pool, Postgres};
+use std::{collections::HashSet, sync::Arc};
+use tokio::sync::{mpsc, oneshot};
+use tonic::{Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_reverify_request");
+/// Requests waiting for the gateway updater
+const REQUEST_QUEUE: usize = 10;
+/// Largest difference between the timestamp of a request and its receipt
+const MAX_REQUEST_SKEW_MINUTES: i64 = 5;
+
+#[derive(sqlx::FromRow)]
+pub struct Reverification {
+    pub id: i64,
+    pub gateway: PublicKeyBinary,
+    pub reason: String,
+    pub requested_at: DateTime<Utc>,
+    pub handled_at: Option<DateTime<Utc>>,
+    /// Pocs re-queued once handled
+    pub requeued: Option<i64>,
+}
+
+/// Outcome of a handled re-verification
+#[derive(Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub id: i64,
+    /// Whether iot config still knows the gateway
+    pub found: bool,
+    pub requeued: u64,
+}
+
+/// A re-verification request on its way to the gateway updater
+pub struct ReverifyRequest {
+    pub gateway: PublicKeyBinary,
+    pub reason: String,
+    pub response: oneshot::Sender<Result<Outcome, GatewayUpdaterError>>,
+}
+
+pub type RequestSender = mpsc::Sender<ReverifyRequest>;
+pub type RequestReceiver = mpsc::Receiver<ReverifyRequest>;
+
+pub fn channel() -> (RequestSender, RequestReceiver) {
+    mpsc::channel(REQUEST_QUEUE)
+}
+
+async fn record(
+    exec: impl sqlx::PgExecutor<'_>,
+    gateway: &PublicKeyBinary,
+    reason: &str,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        r#"
+        insert into gateway_reverifications (gateway, reason)
+        values ($1, $2)
+        returning id
+        "#,
+    )
+    .bind(gateway)
+    .bind(reason)
+    .fetch_one(exec)
+    .await
+}
+
+pub async fn fetch_all(
+    exec: impl sqlx::PgExecutor<'_>,
+) -> Result<Vec<Reverification>, sqlx::Error> {
+    sqlx::query_as::<_, Reverification>("select * from gateway_reverifications order by id")
+        .fetch_all(exec)
+        .await
+}
+
+async fn mark_handled(
+    exec: impl sqlx::PgExecutor<'_>,
+    id: i64,
+    requeued: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        update gateway_reverifications set handled_at = now(), requeued = $2
+        where id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(requeued as i64)
+    .execute(exec)
+    .await?;
+    Ok(())
+}
+
+/// The packet data of the unverified pocs `gateway` beaconed or witnessed.
+/// Reports loaded before their gateway was recorded with them have none, they
+/// are decoded to find it.
+async fn unverified_pocs_of(
+    pool: &PgPool,
+    report_store: &ReportStore,
+    gateway: &PublicKeyBinary,
+) -> Result<HashSet<Vec<u8>>, GatewayUpdaterError> {
+    let mut pocs: HashSet<Vec<u8>> = sqlx::query_scalar::<_, Vec<u8>>(
+        r#"
+        select packet_data from poc_report
+        where gateway = $1 and status in ('pending', 'ready')
+        "#,
+    )
+    .bind(gateway)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut unattributed = sqlx::query_as::<_, (Vec<u8>, ReportType, Vec<u8>, Option<String>)>(
+        r#"
+        select packet_data, report_type, report_data, report_key from poc_report
+        where gateway is null and status in ('pending', 'ready')
+        "#,
+    )
+    .fetch(pool);
+    while let Some((packet_data, report_type, report_data, report_key)) =
+        unattributed.try_next().await?
+    {
+        let report_data = report_store
+            .fetch(&report_data, report_key.as_deref())
+            .await?;
+        let pub_key = match report_type {
+            ReportType::Beacon => IotBeaconIngestReport::decode(report_data.as_ref())
+                .map(|beacon| beacon.report.pub_key),
+            ReportType::Witness => IotWitnessIngestReport::decode(report_data.as_ref())
+                .map(|witness| witness.report.pub_key),
+        };
+        // undecodable reports are failed by the runner, not re-queued
+        if matches!(pub_key, Ok(pub_key) if &pub_key == gateway) {
+            pocs.insert(packet_data);
+        }
+    }
+    Ok(pocs)
+}
+
+/// Reset the retries of every report of the unverified pocs of `gateway` and
+/// prioritize their beacons. Returns the number of pocs re-queued.
+pub async fn requeue(
+    pool: &PgPool,
+    report_store: &ReportStore,
+    gateway: &PublicKeyBinary,
+) -> Result<u64, GatewayUpdaterError> {
+    let pocs: Vec<Vec<u8>> = unverified_pocs_of(pool, report_store, gateway)
+        .await?
+        .into_iter()
+        .collect();
+    if pocs.is_empty() {
+        return Ok(0);
+    }
+    sqlx::query(
+        r#"
+        update poc_report set attempts = 0, prioritized = true
+        where packet_data = any($1) and status in ('pending', 'ready')
+        "#,
+    )
+    .bind(&pocs)
+    .execute(pool)
+    .await?;
+    Ok(pocs.len() as u64)
+}
+
+/// Record the re-verification of `gateway` in `gateway_reverifications`,
+/// refresh it in the cache behind `gateways` and re-queue its unverified
+/// pocs, whose reports may have been judged against the cached metadata or
+/// burned their retries failing against it. Their beacons are prioritized, so
+/// the runner verifies them ahead of their normal order. Verified reports are
+/// left alone
+pub async fn handle(
+    pool: &PgPool,
+    report_store: &ReportStore,
+    iot_config_client: &mut IotConfigClient,
+    gateways: &MessageSender,
+    gateway: &PublicKeyBinary,
+    reason: &str,
+) -> Result<Outcome, GatewayUpdaterError> {
+    let id = record(pool, gateway, reason).await?;
+    let current = iot_config_client.resolve_gateway_info(gateway).await?;
+    let found = current.is_some();
+    gateways.send_modify(|gateways| {
+        match current {
+            Some(info) => gateways.insert(gateway.clone(), info),
+            None => gateways.remove(gateway),
+        };
+    });
+
+    let requeued = requeue(pool, report_store, gateway).await?;
+    mark_handled(pool, id, requeued).await?;
+    tracing::info!(%gateway, found, requeued, %reason, "gateway re-verification handled");
+    Ok(Outcome {
+        id,
+        found,
+        requeued,
+    })
+}
+
+/// Serves the re-verification requests of admins alongside the hex density
+/// api, signed by one of its `admin_keys`. Requests are handled by the gateway
+/// updater right away, and a failed re-verification is returned to the admin
+pub struct ReverifyService {
+    admin_keys: HashSet<PublicKeyBinary>,
+    requests: RequestSender,
+    signing_key: Arc<Keypair>,
+}
+
+impl ReverifyService {
+    pub fn new(
+        settings: &density_service::Settings,
+        requests: RequestSender,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            admin_keys: settings.admin_keys()?,
+            requests,
+            signing_key: Arc::new(settings.signing_keypair()?),
+        })
+    }
+
+    fn verify_request(
+        &self,
+        request: &GatewayReverifyReqV1,
+        now: DateTime<Utc>,
+    ) -> Result<(), Status> {
+        let signer = PublicKey::try_from(request.signer.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid signer"))?;
+        if !self.admin_keys.contains(&signer.clone().into()) {
+            return Err(Status::permission_denied("unauthorized signer"));
+        }
+        request
+            .verify(&signer)
+            .map_err(|_| Status::permission_denied("invalid request signature"))?;
+        let timestamp = request
+            .timestamp
+            .to_timestamp()
+            .map_err(|_| Status::invalid_argument("invalid timestamp"))?;
+        let skew = Duration::minutes(MAX_REQUEST_SKEW_MINUTES);
+        if timestamp < now - skew || timestamp > now + skew {
+            return Err(Status::invalid_argument(
+                "request timestamp too far from now",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl proto::reverify_server::Reverify for ReverifyService {
+    async fn reverify(
+        &self,
+        request: Request<GatewayReverifyReqV1>,
+    ) -> GrpcResult<GatewayReverifyRespV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "reverify");
+
+        self.verify_request(&request, Utc::now())?;
+        let gateway: PublicKeyBinary = PublicKey::try_from(request.gateway.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid gateway"))?
+            .into();
+        if request.reason.is_empty() {
+            return Err(Status::invalid_argument("a reason is required"));
+        }
+
+        let (response, outcome) = oneshot::channel();
+        self.requests
+            .send(ReverifyRequest {
+                gateway: gateway.clone(),
+                reason: request.reason,
+                response,
+            })
+            .await
+            .map_err(|_| Status::unavailable("gateway updater stopped"))?;
+        let outcome = outcome
+            .await
+            .map_err(|_| Status::unavailable("gateway updater stopped"))?
+            .map_err(|err| {
+                tracing::error!(%gateway, ?err, "gateway re-verification failed");
+                Status::internal("re-verification failed")
+            })?;
+
+        let mut resp = GatewayReverifyRespV1 {
+            id: outcome.id as u64,
+            found: outcome.found,
+            requeued: outcome.requeued,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = sign(&self.signing_key, &resp)?;
+        Ok(Response::new(resp))
+    }
+}
+
+/// Command line access to the re-verification requests
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List all requests, handled or not
+    List,
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                for reverification in fetch_all(pool).await? {
+                    let status = match reverification.handled_at {
+                        Some(handled_at) => format!(
+                            "handled {handled_at}, {} pocs re-queued",
+                            reverification.requeued.unwrap_or_default()
+                        ),
+                        None => "failed".to_string(),
+                    };
+                    println!(
+                        "{}: {}: {status} (requested {}: {})",
+                        reverification.id,
+                        reverification.gateway,
+                        reverification.requested_at,
+                        reverification.reason
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poc_report::IotStatus;
+    use helium_crypto::{KeyTag, KeyType, Network, Sign};
+    use helium_proto::{
+        services::poc_lora::{LoraBeaconIngestReportV1, LoraBeaconReportReqV1},
+        Message,
+    };
+    use rand::rngs::OsRng;
+
+    fn keypair() -> Keypair {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed25519,
+        };
+        Keypair::generate(key_tag, &mut OsRng)
+    }
+
+    struct Row<'a> {
+        id: u8,
+        packet_data: u8,
+        report_type: ReportType,
+        status: IotStatus,
+        gateway: Option<&'a PublicKeyBinary>,
+        report_data: Vec<u8>,
+    }
+
+    async fn insert(pool: &PgPool, row: Row<'_>) {
+        sqlx::query(
+            r#"
+            insert into poc_report (
+                id, packet_data, report_data, report_timestamp, report_type, status,
+                gateway, attempts
+            ) values ($1, $2, $3, now(), $4, $5, $6, 3)
+            "#,
+        )
+        .bind(vec![row.id])
+        .bind(vec![row.packet_data])
+        .bind(row.report_data)
+        .bind(row.report_type)
+        .bind(row.status)
+        .bind(row.gateway)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn row(
+        id: u8,
+        packet_data: u8,
+        report_type: ReportType,
+        status: IotStatus,
+        gateway: Option<&PublicKeyBinary>,
+    ) -> Row<'_> {
+        Row {
+            id,
+            packet_data,
+            report_type,
+            status,
+            gateway,
+            report_data: vec![],
+        }
+    }
+
+    async fn attempts(pool: &PgPool, id: u8) -> (i32, bool) {
+        sqlx::query_as("select attempts, prioritized from poc_report where id = $1")
+            .bind(vec![id])
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn unverified_pocs_of_the_gateway_are_requeued(pool: PgPool) {
+        let gateway = PublicKeyBinary::from(vec![1]);
+        let other = PublicKeyBinary::from(vec![2]);
+        // beaconed by the gateway, with the witness of another
+        insert(
+            &pool,
+            row(1, 1, ReportType::Beacon, IotStatus::Pending, Some(&gateway)),
+        )
+        .await;
+        insert(
+            &pool,
+            row(2, 1, ReportType::Witness, IotStatus::Ready, Some(&other)),
+        )
+        .await;
+        // witnessed by the gateway
+        insert(
+            &pool,
+            row(3, 2, ReportType::Beacon, IotStatus::Pending, Some(&other)),
+        )
+        .await;
+        insert(
+            &pool,
+            row(4, 2, ReportType::Witness, IotStatus::Ready, Some(&gateway)),
+        )
+        .await;
+        // verified already
+        insert(
+            &pool,
+            row(5, 3, ReportType::Beacon, IotStatus::Valid, Some(&gateway)),
+        )
+        .await;
+        // of another gateway only
+        insert(
+            &pool,
+            row(6, 4, ReportType::Beacon, IotStatus::Pending, Some(&other)),
+        )
+        .await;
+        // loaded before gateways were recorded with reports
+        let legacy = LoraBeaconIngestReportV1 {
+            received_timestamp: 0,
+            report: Some(LoraBeaconReportReqV1 {
+                pub_key: gateway.clone().into(),
+                ..Default::default()
+            }),
+        };
+        insert(
+            &pool,
+            Row {
+                report_data: legacy.encode_to_vec(),
+                ..row(7, 5, ReportType::Beacon, IotStatus::Pending, None)
+            },
+        )
+        .await;
+
+        let report_store = ReportStore::new(None, usize::MAX);
+        assert_eq!(3, requeue(&pool, &report_store, &gateway).await.unwrap());
+
+        for id in [1, 2, 3, 4, 7] {
+            assert_eq!((0, true), attempts(&pool, id).await, "report {id}");
+        }
+        for id in [5, 6] {
+            assert_eq!((3, false), attempts(&pool, id).await, "report {id}");
+        }
+    }
+
+    fn service(admin: &Keypair) -> ReverifyService {
+        ReverifyService {
+            admin_keys: HashSet::from([admin.public_key().clone().into()]),
+            requests: channel().0,
+            signing_key: Arc::new(keypair()),
+        }
+    }
+
+    fn signed(signer: &Keypair, timestamp: DateTime<Utc>) -> GatewayReverifyReqV1 {
+        let mut request = GatewayReverifyReqV1 {
+            gateway: keypair().public_key().into(),
+            reason: "corrected location".to_string(),
+            timestamp: timestamp.encode_timestamp(),
+            signer: signer.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = signer.sign(&request.encode_to_vec()).unwrap();
+        request
+    }
+
+    #[test]
+    fn only_fresh_requests_of_admins_are_accepted() {
+        let admin = keypair();
+        let service = service(&admin);
+        let now = Utc::now();
+
+        assert!(service.verify_request(&signed(&admin, now), now).is_ok());
+
+        let code = |request, now| service.verify_request(&request, now).unwrap_err().code();
+        assert_eq!(
+            tonic::Code::PermissionDenied,
+            code(signed(&keypair(), now), now)
+        );
+        let forged = GatewayReverifyReqV1 {
+            reason: "other".to_string(),
+            ..signed(&admin, now)
+        };
+        assert_eq!(tonic::Code::PermissionDenied, code(forged, now));
+        assert_eq!(
+            tonic::Code::InvalidArgument,
+            code(signed(&admin, now - Duration::minutes(10)), now)
+        );
+    }
+}
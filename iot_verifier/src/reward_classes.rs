@@ -0,0 +1,92 @@
+//! Switches pausing the rewards of a class, e.g. witness rewards paused per
+//! governance. Every class is enabled by default.
+
+use chrono::{DateTime, Utc};
+use file_store::iot_unallocated_reward::RewardClassV1;
+use serde::Deserialize;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardClass {
+    Beacon,
+    Witness,
+    DataTransfer,
+}
+
+impl RewardClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Beacon => "beacon",
+            Self::Witness => "witness",
+            Self::DataTransfer => "data_transfer",
+        }
+    }
+}
+
+impl From<RewardClass> for RewardClassV1 {
+    fn from(class: RewardClass) -> Self {
+        match class {
+            RewardClass::Beacon => Self::Beacon,
+            RewardClass::Witness => Self::Witness,
+            RewardClass::DataTransfer => Self::DataTransfer,
+        }
+    }
+}
+
+/// A class switched off from its `effective_from` epoch earns nothing in the
+/// reward periods starting at or after it, the rewards its shares would have
+/// been allocated are left unallocated rather than paid to the other classes
+/// or the operations fund. Earlier reward periods are rewarded as if the class
+/// was enabled, so a pause can be configured ahead of the epoch it applies to
+#[derive(Debug, Clone, Deserialize)]
+pub struct Switch {
+    /// Whether the class is rewarded. Default true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Start of the first reward period the switch applies to, every reward
+    /// period when not set
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            effective_from: None,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Switch {
+    fn is_enabled(&self, reward_period: &Range<DateTime<Utc>>) -> bool {
+        match self.effective_from {
+            Some(effective_from) if reward_period.start < effective_from => true,
+            _ => self.enabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub beacon: Switch,
+    #[serde(default)]
+    pub witness: Switch,
+    #[serde(default)]
+    pub data_transfer: Switch,
+}
+
+impl Settings {
+    /// Whether `class` is rewarded in `reward_period`
+    pub fn is_enabled(&self, class: RewardClass, reward_period: &Range<DateTime<Utc>>) -> bool {
+        match class {
+            RewardClass::Beacon => self.beacon.is_enabled(reward_period),
+            RewardClass::Witness => self.witness.is_enabled(reward_period),
+            RewardClass::DataTransfer => self.data_transfer.is_enabled(reward_period),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Snapshots of the transmit reward scale of interactive gateways, averaged
+//! over a window at reward time.
+
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use helium_crypto::PublicKeyBinary;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use sqlx::{Postgres, Transaction};
+use std::{collections::HashMap, ops::Range};
+
+/// Snapshot rows inserted per statement, within the bind parameter limit of
+/// postgres at 3 binds a row
+const SNAPSHOT_INSERT_BATCH: usize = 10_000;
+
+#[derive(sqlx::FromRow)]
+pub struct RewardScaleSnapshot {
+    pub hotspot_key: PublicKeyBinary,
+    pub snapshot_timestamp: DateTime<Utc>,
+    pub hex_scale: Decimal,
+}
+
+/// Save the scale of every gateway at `snapshot_timestamp`, replacing any
+/// already saved. A gateway may appear at most once in `scales`
+pub async fn save_snapshot(
+    db: &mut Transaction<'_, Postgres>,
+    snapshot_timestamp: DateTime<Utc>,
+    scales: impl IntoIterator<Item = (PublicKeyBinary, Decimal)>,
+) -> Result<(), sqlx::Error> {
+    for batch in &scales.into_iter().chunks(SNAPSHOT_INSERT_BATCH) {
+        let mut query_builder: sqlx::QueryBuilder<Postgres> = sqlx::QueryBuilder::new(
+            " insert into reward_scale_snapshots (hotspot_key, snapshot_timestamp, hex_scale) ",
+        );
+        query_builder.push_values(batch, |mut row, (hotspot_key, hex_scale)| {
+            row.push_bind(hotspot_key)
+                .push_bind(snapshot_timestamp)
+                .push_bind(hex_scale);
+        });
+        query_builder.push(
+            r#"
+            on conflict (hotspot_key, snapshot_timestamp) do update set
+                hex_scale = EXCLUDED.hex_scale
+            "#,
+        );
+        query_builder.build().execute(&mut *db).await?;
+    }
+    Ok(())
+}
+
+/// Window of `width` ending with the reward period. Snapshots taken before
+/// the period started are included when the window is wider than the period
+pub fn averaging_window(
+    reward_period: &Range<DateTime<Utc>>,
+    width: Duration,
+) -> Range<DateTime<Utc>> {
+    reward_period.end - width..reward_period.end
+}
+
+/// Average scale per gateway across the snapshots taken within `window`. Poc
+/// shares are scaled by it rather than by the scale in effect when each poc
+/// was verified, so a density change late in the epoch doesn't dominate the
+/// whole period. Gateways without a snapshot in the window keep the per poc
+/// scale
+pub async fn average_scales(
+    db: impl sqlx::PgExecutor<'_>,
+    window: &Range<DateTime<Utc>>,
+) -> Result<HashMap<PublicKeyBinary, Decimal>, sqlx::Error> {
+    let snapshots = sqlx::query_as::<_, RewardScaleSnapshot>(
+        r#"
+        select * from reward_scale_snapshots
+        where snapshot_timestamp > $1 and snapshot_timestamp <= $2
+        "#,
+    )
+    .bind(window.start)
+    .bind(window.end)
+    .fetch(db)
+    .try_collect::<Vec<_>>()
+    .await?;
+    Ok(average(snapshots))
+}
+
+/// Remove snapshots no longer covered by any future averaging window
+pub async fn clear_snapshots(
+    tx: &mut Transaction<'_, Postgres>,
+    before: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("delete from reward_scale_snapshots where snapshot_timestamp <= $1")
+        .bind(before)
+        .execute(&mut *tx)
+        .await
+        .map(|_| ())
+}
+
+pub fn average(
+    snapshots: impl IntoIterator<Item = RewardScaleSnapshot>,
+) -> HashMap<PublicKeyBinary, Decimal> {
+    let mut sums: HashMap<PublicKeyBinary, (Decimal, u32)> = HashMap::new();
+    for snapshot in snapshots {
+        let (sum, count) = sums.entry(snapshot.hotspot_key).or_default();
+        *sum += snapshot.hex_scale;
+        *count += 1;
+    }
+    sums.into_iter()
+        .map(|(hotspot_key, (sum, count))| (hotspot_key, sum / Decimal::from(count)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn snapshot(
+        hotspot_key: &PublicKeyBinary,
+        snapshot_timestamp: DateTime<Utc>,
+        hex_scale: Decimal,
+    ) -> RewardScaleSnapshot {
+        RewardScaleSnapshot {
+            hotspot_key: hotspot_key.clone(),
+            snapshot_timestamp,
+            hex_scale,
+        }
+    }
+
+    #[test]
+    fn averages_only_snapshots_where_interactive() {
+        let gw1: PublicKeyBinary = "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6"
+            .parse()
+            .expect("failed gw1 parse");
+        let gw2: PublicKeyBinary = "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp"
+            .parse()
+            .expect("failed gw2 parse");
+        // gw2 was only interactive for the last two snapshots of the window
+        let now = Utc::now();
+        let averages = average([
+            snapshot(&gw1, now - Duration::hours(3), dec!(1.0)),
+            snapshot(&gw1, now - Duration::hours(2), dec!(0.5)),
+            snapshot(&gw1, now - Duration::hours(1), dec!(0.5)),
+            snapshot(&gw1, now, dec!(0.2)),
+            snapshot(&gw2, now - Duration::hours(1), dec!(0.4)),
+            snapshot(&gw2, now, dec!(0.2)),
+        ]);
+        assert_eq!(Some(&dec!(0.55)), averages.get(&gw1));
+        assert_eq!(Some(&dec!(0.3)), averages.get(&gw2));
+    }
+
+    #[test]
+    fn averaging_window_extends_before_reward_period() {
+        let end = Utc::now();
+        let reward_period = end - Duration::hours(24)..end;
+        assert_eq!(
+            end - Duration::hours(48)..end,
+            averaging_window(&reward_period, Duration::hours(48))
+        );
+        assert_eq!(
+            end - Duration::hours(6)..end,
+            averaging_window(&reward_period, Duration::hours(6))
+        );
+    }
+}
@@ -1,11 +1,21 @@
-use crate::poc_report::ReportType as PocReportType;
+use crate::{
+    poc_report::ReportType as PocReportType,
+    reward_classes::{self, RewardClass},
+};
 use chrono::{DateTime, Duration, Utc};
-use file_store::{iot_packet::IotValidPacket, iot_valid_poc::IotPoc, traits::TimestampEncode};
+use file_store::{
+    iot_packet::IotValidPacket,
+    iot_unallocated_reward::{UnallocatedReasonV1, UnallocatedReward},
+    iot_valid_poc::IotPoc,
+    traits::TimestampEncode,
+};
 use futures::stream::TryStreamExt;
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_lora as proto;
 use helium_proto::services::poc_lora::iot_reward_share::Reward as ProtoReward;
+use iot_config::gateway_info::GatewayClass;
 use lazy_static::lazy_static;
+use reward_scheduler::reward_share::RewardPool;
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
 use sqlx::{Postgres, Transaction};
@@ -13,18 +23,28 @@ use std::{collections::HashMap, ops::Range};
 
 const DEFAULT_PREC: u32 = 15;
 
+const EXCLUDED_POC_SHARE_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_excluded_poc_share");
+const UNALLOCATED_REWARDS_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_unallocated_rewards");
+
 // rewards in IoT Bones ( iot @ 10^6 ) per 24 hours based on emission curve year 1
 // TODO: expand to cover the full multi-year emission curve
 lazy_static! {
     // TODO: year 1 emissions allocate 30% of total to PoC with 6% to beacons and 24% to witnesses but subsequent years back
     // total PoC percentage off 1.5% each year; determine how beacons and witnesses will split the subsequent years' allocations
-    static ref REWARDS_PER_DAY: Decimal = (Decimal::from(65_000_000_000_u64) / Decimal::from(365)) * Decimal::from(1_000_000); // 178_082_191_780_822
+    pub static ref REWARDS_PER_DAY: Decimal = (Decimal::from(65_000_000_000_u64) / Decimal::from(365)) * Decimal::from(1_000_000); // 178_082_191_780_822
     static ref BEACON_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.06);
     static ref WITNESS_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.24);
     // Data transfer is allocated 50% of daily rewards
     static ref DATA_TRANSFER_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.50);
     // Operations fund is allocated 7% of daily rewards
     static ref OPERATIONS_REWARDS_PER_DAY_PERCENT: Decimal = dec!(0.07);
+    // Share of the daily rewards allocated every epoch: the data transfer
+    // remainder goes to beacons and witnesses, so all of it is rewarded up to
+    // rounding
+    pub static ref REWARDED_SHARE: Decimal = *BEACON_REWARDS_PER_DAY_PERCENT
+        + *WITNESS_REWARDS_PER_DAY_PERCENT
+        + *DATA_TRANSFER_REWARDS_PER_DAY_PERCENT
+        + *OPERATIONS_REWARDS_PER_DAY_PERCENT;
     // dc remainer distributed at ration of 4:1 in favour of witnesses
     // ie WITNESS_REWARDS_PER_DAY_PERCENT:BEACON_REWARDS_PER_DAY_PERCENT
     static ref WITNESS_DC_REMAINER_PERCENT: Decimal = dec!(0.80);
@@ -77,6 +97,8 @@ pub struct GatewayPocShare {
     pub hex_scale: Decimal,
     pub reward_unit: Decimal,
     pub poc_id: Vec<u8>,
+    /// Class of the gateway when the poc was verified
+    pub class: GatewayClass,
 }
 
 #[derive(sqlx::FromRow)]
@@ -103,13 +125,14 @@ impl GatewayPocShare {
     ) -> Result<bool, SaveGatewayShareError> {
         Ok(sqlx::query_as::<_, GatewayShareSaveResult>(
             r#"
-            insert into gateway_shares (hotspot_key, reward_type, reward_timestamp, hex_scale, reward_unit, poc_id)
-            values ($1, $2, $3, $4, $5, $6)
+            insert into gateway_shares (hotspot_key, reward_type, reward_timestamp, hex_scale, reward_unit, poc_id, class)
+            values ($1, $2, $3, $4, $5, $6, $7)
             on conflict (hotspot_key, poc_id) do update set
                 reward_type = EXCLUDED.reward_type,
                 reward_timestamp = EXCLUDED.reward_timestamp,
                 hex_scale = EXCLUDED.hex_scale,
-                reward_unit = EXCLUDED.reward_unit
+                reward_unit = EXCLUDED.reward_unit,
+                class = EXCLUDED.class
             returning (xmax = 0) as inserted;
             "#,
         )
@@ -119,12 +142,25 @@ impl GatewayPocShare {
         .bind(self.hex_scale)
         .bind(self.reward_unit)
         .bind(self.poc_id)
+        .bind(self.class)
         .fetch_one(&mut *db)
         .await?
         .inserted)
     }
 
-    pub fn shares_from_poc(report: &IotPoc) -> impl Iterator<Item = Self> {
+    /// The shares of the beaconer and valid selected witnesses of a poc,
+    /// recorded with their class in `classes`. A gateway missing from it
+    /// earns no poc rewards, recorded as data-only
+    pub fn shares_from_poc(
+        report: &IotPoc,
+        classes: &HashMap<PublicKeyBinary, GatewayClass>,
+    ) -> impl Iterator<Item = Self> {
+        let class = |pub_key: &PublicKeyBinary| {
+            classes
+                .get(pub_key)
+                .copied()
+                .unwrap_or(GatewayClass::DataOnly)
+        };
         let mut shares: Vec<Self> = Vec::new();
         let beacon_scaling_factor = report.beacon_report.hex_scale;
         let beacon_reward_unit = report.beacon_report.reward_unit;
@@ -136,6 +172,7 @@ impl GatewayPocShare {
                 hex_scale: beacon_scaling_factor,
                 reward_unit: beacon_reward_unit,
                 poc_id: report.poc_id.clone(),
+                class: class(&report.beacon_report.report.pub_key),
             })
         };
         for witness in &report.selected_witnesses {
@@ -152,6 +189,7 @@ impl GatewayPocShare {
                     hex_scale: witness_hex_scale,
                     reward_unit: witness_reward_unit,
                     poc_id: report.poc_id.clone(),
+                    class: class(&witness.report.pub_key),
                 })
             }
         }
@@ -201,8 +239,10 @@ pub struct RewardShares {
 }
 
 impl RewardShares {
-    pub fn add_poc_reward(&mut self, share: &GatewayPocShare) {
-        let rewards = share.hex_scale * share.reward_unit;
+    /// Add the share's reward units scaled by `hex_scale`, which is either
+    /// the scale at verification time or the gateway's averaged scale
+    pub fn add_poc_reward(&mut self, share: &GatewayPocShare, hex_scale: Decimal) {
+        let rewards = hex_scale * share.reward_unit;
         match share.reward_type {
             PocReportType::Beacon => self.beacon_shares += rewards,
             PocReportType::Witness => self.witness_shares += rewards,
@@ -216,16 +256,24 @@ impl RewardShares {
 #[derive(Default)]
 pub struct GatewayShares {
     pub shares: HashMap<PublicKeyBinary, RewardShares>,
+    /// Class of every gateway with poc shares, as of its latest share
+    pub classes: HashMap<PublicKeyBinary, GatewayClass>,
 }
 
 impl GatewayShares {
+    /// Aggregate the shares of the reward period. Poc shares of gateways with
+    /// an entry in `reward_scales` are scaled by it instead of the scale
+    /// recorded with the share
     pub async fn aggregate(
         db: impl sqlx::PgExecutor<'_> + Copy,
         reward_period: &Range<DateTime<Utc>>,
+        reward_scales: &HashMap<PublicKeyBinary, Decimal>,
     ) -> Result<Self, sqlx::Error> {
         let mut shares = Self::default();
         // get all the shares, poc and dc
-        shares.aggregate_poc_shares(db, reward_period).await?;
+        shares
+            .aggregate_poc_shares(db, reward_period, reward_scales)
+            .await?;
         shares.aggregate_dc_shares(db, reward_period).await?;
         Ok(shares)
     }
@@ -264,22 +312,46 @@ impl GatewayShares {
         &mut self,
         db: impl sqlx::PgExecutor<'_> + Copy,
         reward_period: &Range<DateTime<Utc>>,
+        reward_scales: &HashMap<PublicKeyBinary, Decimal>,
     ) -> Result<(), sqlx::Error> {
         let mut rows = sqlx::query_as::<_, GatewayPocShare>(
-            "select * from gateway_shares where reward_timestamp > $1 and reward_timestamp <= $2",
+            "select * from gateway_shares where reward_timestamp > $1 and reward_timestamp <= $2 order by reward_timestamp",
         )
         .bind(reward_period.start)
         .bind(reward_period.end)
         .fetch(db);
         while let Some(gateway_share) = rows.try_next().await? {
-            self.shares
-                .entry(gateway_share.hotspot_key.clone())
-                .or_default()
-                .add_poc_reward(&gateway_share)
+            self.add_poc_share(&gateway_share, reward_scales);
         }
         Ok(())
     }
 
+    fn add_poc_share(
+        &mut self,
+        gateway_share: &GatewayPocShare,
+        reward_scales: &HashMap<PublicKeyBinary, Decimal>,
+    ) {
+        self.classes
+            .insert(gateway_share.hotspot_key.clone(), gateway_share.class);
+        // the verifier rejects the pocs of gateways whose class earns no poc
+        // rewards, this guards against shares recorded otherwise
+        if !gateway_share.class.earns_poc_rewards() {
+            metrics::increment_counter!(
+                EXCLUDED_POC_SHARE_COUNTER,
+                "class" => gateway_share.class.as_str()
+            );
+            return;
+        }
+        let hex_scale = reward_scales
+            .get(&gateway_share.hotspot_key)
+            .copied()
+            .unwrap_or(gateway_share.hex_scale);
+        self.shares
+            .entry(gateway_share.hotspot_key.clone())
+            .or_default()
+            .add_poc_reward(gateway_share, hex_scale)
+    }
+
     async fn aggregate_dc_shares(
         &mut self,
         db: impl sqlx::PgExecutor<'_> + Copy,
@@ -300,11 +372,19 @@ impl GatewayShares {
         Ok(())
     }
 
+    /// The rewards of every gateway for the reward period, in hotspot key
+    /// order, along with what each class left unallocated: the dust left over
+    /// from rounding them down to whole bones, or the whole pool of a class
+    /// disabled by `reward_classes`, which is allocated nothing
     pub fn into_iot_reward_shares(
         self,
         reward_period: &'_ Range<DateTime<Utc>>,
         iot_price: Decimal,
-    ) -> impl Iterator<Item = proto::IotRewardShare> + '_ {
+        reward_classes: &reward_classes::Settings,
+    ) -> (
+        impl Iterator<Item = proto::IotRewardShare> + '_,
+        Vec<UnallocatedReward>,
+    ) {
         // the total number of shares for beacons, witnesses and data transfer
         // dc shares here is the sum of all spent data transfer DC this epoch
         let (total_beacon_shares, total_witness_shares, total_dc_shares) = self.total_shares();
@@ -331,33 +411,48 @@ impl GatewayShares {
         );
 
         // work out the rewards per share for beacons, witnesses and dc transfer
-        let beacon_rewards_per_share = rewards_per_share(total_beacon_rewards, total_beacon_shares);
-        let witness_rewards_per_share =
-            rewards_per_share(total_witness_rewards, total_witness_shares);
-        let dc_transfer_rewards_per_share =
-            rewards_per_share(total_dc_transfer_rewards_capped, total_dc_shares);
-        // compute the awards per hotspot
+        let mut beacon_pool = RewardPool::new(total_beacon_rewards, total_beacon_shares);
+        let mut witness_pool = RewardPool::new(total_witness_rewards, total_witness_shares);
+        let mut dc_transfer_pool =
+            RewardPool::new(total_dc_transfer_rewards_capped, total_dc_shares);
         tracing::info!(
             %total_dc_shares,
             %total_dc_transfer_rewards_used,
             %dc_transfer_rewards_unused,
-            %dc_transfer_rewards_per_share,
+            dc_transfer_rewards_per_share = %dc_transfer_pool.rewards_per_share(),
             "data transfer rewards"
         );
-        self.shares
+
+        let beacons_enabled = reward_classes.is_enabled(RewardClass::Beacon, reward_period);
+        let witnesses_enabled = reward_classes.is_enabled(RewardClass::Witness, reward_period);
+        let dc_transfer_enabled =
+            reward_classes.is_enabled(RewardClass::DataTransfer, reward_period);
+
+        // compute the awards per hotspot, in key order so any capping of the
+        // last allocations of a pool always hits the same hotspots
+        let mut shares: Vec<(Vec<u8>, RewardShares)> = self
+            .shares
             .into_iter()
-            .map(move |(hotspot_key, reward_shares)| proto::GatewayReward {
-                hotspot_key: hotspot_key.into(),
-                beacon_amount: compute_rewards(
-                    beacon_rewards_per_share,
+            .map(|(hotspot_key, reward_shares)| (hotspot_key.into(), reward_shares))
+            .collect();
+        shares.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let gateway_rewards: Vec<proto::GatewayReward> = shares
+            .into_iter()
+            .map(|(hotspot_key, reward_shares)| proto::GatewayReward {
+                hotspot_key,
+                beacon_amount: allocate(
+                    &mut beacon_pool,
+                    beacons_enabled,
                     reward_shares.beacon_shares,
                 ),
-                witness_amount: compute_rewards(
-                    witness_rewards_per_share,
+                witness_amount: allocate(
+                    &mut witness_pool,
+                    witnesses_enabled,
                     reward_shares.witness_shares,
                 ),
-                dc_transfer_amount: compute_rewards(
-                    dc_transfer_rewards_per_share,
+                dc_transfer_amount: allocate(
+                    &mut dc_transfer_pool,
+                    dc_transfer_enabled,
                     reward_shares.dc_shares,
                 ),
             })
@@ -366,11 +461,66 @@ impl GatewayShares {
                     || reward_share.witness_amount > 0
                     || reward_share.dc_transfer_amount > 0
             })
+            .collect();
+
+        let mut unallocated_rewards = vec![];
+        for (class, enabled, pool) in [
+            (RewardClass::Beacon, beacons_enabled, &beacon_pool),
+            (RewardClass::Witness, witnesses_enabled, &witness_pool),
+            (
+                RewardClass::DataTransfer,
+                dc_transfer_enabled,
+                &dc_transfer_pool,
+            ),
+        ] {
+            let unallocated = if enabled {
+                tracing::info!(
+                    class = class.as_str(),
+                    dust = pool.dust(),
+                    "gateway reward dust"
+                );
+                (UnallocatedReasonV1::Dust, pool.dust())
+            } else {
+                tracing::warn!(
+                    class = class.as_str(),
+                    unallocated = pool.total(),
+                    "reward class disabled, rewards left unallocated"
+                );
+                (UnallocatedReasonV1::ClassDisabled, pool.total())
+            };
+            let (reason, amount) = unallocated;
+            metrics::gauge!(
+                UNALLOCATED_REWARDS_GAUGE,
+                if enabled { 0.0 } else { amount as f64 },
+                "class" => class.as_str()
+            );
+            if amount > 0 {
+                unallocated_rewards.push(UnallocatedReward {
+                    class: class.into(),
+                    reason,
+                    amount,
+                    start_period: reward_period.start,
+                    end_period: reward_period.end,
+                });
+            }
+        }
+        let rewards = gateway_rewards
+            .into_iter()
             .map(|gateway_reward| proto::IotRewardShare {
                 start_period: reward_period.start.encode_timestamp(),
                 end_period: reward_period.end.encode_timestamp(),
                 reward: Some(ProtoReward::GatewayReward(gateway_reward)),
-            })
+            });
+        (rewards, unallocated_rewards)
+    }
+}
+
+/// Allocate the rewards for `shares` from `pool` if its class is enabled
+fn allocate(pool: &mut RewardPool, enabled: bool, shares: Decimal) -> u64 {
+    if enabled {
+        pool.allocate(shares)
+    } else {
+        0
     }
 }
 
@@ -430,25 +580,10 @@ pub fn normalize_dc_transfer_rewards(
     }
 }
 
-fn rewards_per_share(total_rewards: Decimal, total_shares: Decimal) -> Decimal {
-    if total_shares > Decimal::ZERO {
-        (total_rewards / total_shares)
-            .round_dp_with_strategy(DEFAULT_PREC, RoundingStrategy::MidpointNearestEven)
-    } else {
-        Decimal::ZERO
-    }
-}
-
-fn compute_rewards(rewards_per_share: Decimal, shares: Decimal) -> u64 {
-    (rewards_per_share * shares)
-        .round_dp_with_strategy(0, RoundingStrategy::ToZero)
-        .to_u64()
-        .unwrap_or(0)
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
+    use file_store::iot_unallocated_reward::RewardClassV1;
 
     fn reward_shares_in_dec(
         beacon_shares: Decimal,
@@ -466,6 +601,126 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_poc_shares_by_gateway_class() {
+        let full: PublicKeyBinary = "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6"
+            .parse()
+            .expect("failed full parse");
+        let light: PublicKeyBinary = "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp"
+            .parse()
+            .expect("failed light parse");
+        let data_only: PublicKeyBinary = "112DJZiXvZ8FduiWrEi8siE3wJX6hpRjjtwbavyXUDkgutEUSLAE"
+            .parse()
+            .expect("failed data_only parse");
+        let now = Utc::now();
+        let share = |hotspot_key: &PublicKeyBinary, reward_type, class| GatewayPocShare {
+            hotspot_key: hotspot_key.clone(),
+            reward_type,
+            reward_timestamp: now,
+            hex_scale: dec!(1.0),
+            reward_unit: dec!(1.0),
+            poc_id: vec![],
+            class,
+        };
+
+        // data-only gateways earn no poc rewards
+        let mut shares = GatewayShares::default();
+        for share in [
+            share(&full, PocReportType::Beacon, GatewayClass::Full),
+            share(&full, PocReportType::Witness, GatewayClass::Full),
+            share(&light, PocReportType::Witness, GatewayClass::Light),
+            share(&data_only, PocReportType::Witness, GatewayClass::DataOnly),
+        ] {
+            shares.add_poc_share(&share, &HashMap::new());
+        }
+        assert_eq!(shares.shares[&full].beacon_shares, dec!(1.0));
+        assert_eq!(shares.shares[&full].witness_shares, dec!(1.0));
+        assert_eq!(shares.shares[&light].witness_shares, dec!(1.0));
+        assert!(!shares.shares.contains_key(&data_only));
+        // but their class is reported with the rewards
+        assert_eq!(shares.classes[&data_only], GatewayClass::DataOnly);
+        assert_eq!(shares.classes[&light], GatewayClass::Light);
+    }
+
+    #[test]
+    fn test_disabled_reward_class_is_unallocated() {
+        let gw1: PublicKeyBinary = "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6"
+            .parse()
+            .expect("failed gw1 parse");
+        let gw2: PublicKeyBinary = "11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp"
+            .parse()
+            .expect("failed gw2 parse");
+        let now = Utc::now();
+        let reward_period = (now - Duration::hours(24))..now;
+        let rewards = |reward_classes: &reward_classes::Settings| {
+            let mut shares = HashMap::new();
+            shares.insert(
+                gw1.clone(),
+                reward_shares_in_dec(dec!(10), dec!(300), dec!(0)),
+            );
+            shares.insert(
+                gw2.clone(),
+                reward_shares_in_dec(dec!(20), dec!(100), dec!(0)),
+            );
+            let (rewards, unallocated) = GatewayShares {
+                shares,
+                ..Default::default()
+            }
+            .into_iot_reward_shares(&reward_period, dec!(359), reward_classes);
+            let rewards: Vec<proto::GatewayReward> = rewards
+                .filter_map(|reward| match reward.reward {
+                    Some(ProtoReward::GatewayReward(gateway_reward)) => Some(gateway_reward),
+                    _ => None,
+                })
+                .collect();
+            (rewards, unallocated)
+        };
+
+        let (enabled_rewards, enabled_unallocated) = rewards(&Default::default());
+        let (_, witness_pool) = get_scheduled_poc_tokens(
+            reward_period.end - reward_period.start,
+            get_scheduled_dc_tokens(reward_period.end - reward_period.start),
+        );
+        let enabled_witness_rewards: u64 = enabled_rewards.iter().map(|r| r.witness_amount).sum();
+        let witness_dust = UnallocatedReward {
+            class: RewardClassV1::Witness,
+            reason: UnallocatedReasonV1::Dust,
+            amount: witness_pool.to_u64().unwrap() - enabled_witness_rewards,
+            start_period: reward_period.start,
+            end_period: reward_period.end,
+        };
+        assert!(enabled_unallocated.contains(&witness_dust));
+
+        // witnesses paused from the start of the reward period
+        let mut reward_classes = reward_classes::Settings::default();
+        reward_classes.witness.enabled = false;
+        reward_classes.witness.effective_from = Some(reward_period.start);
+        let (disabled_rewards, disabled_unallocated) = rewards(&reward_classes);
+        assert_eq!(enabled_rewards.len(), disabled_rewards.len());
+        for (enabled, disabled) in enabled_rewards.iter().zip(&disabled_rewards) {
+            assert_eq!(0, disabled.witness_amount);
+            assert_eq!(enabled.beacon_amount, disabled.beacon_amount);
+        }
+        // the witness pool is neither allocated nor part of the dust, it is
+        // recorded as unallocated in whole
+        let mut expected: Vec<_> = enabled_unallocated
+            .into_iter()
+            .filter(|dust| dust.class != RewardClassV1::Witness)
+            .collect();
+        expected.push(UnallocatedReward {
+            reason: UnallocatedReasonV1::ClassDisabled,
+            amount: witness_pool.to_u64().unwrap(),
+            ..witness_dust
+        });
+        expected.sort_by_key(|unallocated| unallocated.class);
+        assert_eq!(expected, disabled_unallocated);
+
+        // a pause taking effect after the reward period doesn't apply yet
+        reward_classes.witness.effective_from = Some(reward_period.end);
+        let (later_rewards, _) = rewards(&reward_classes);
+        assert_eq!(enabled_rewards, later_rewards);
+    }
+
     #[test]
     fn test_non_gateway_reward_shares() {
         let epoch_duration = Duration::hours(1);
@@ -552,11 +807,13 @@ mod test {
             reward_shares_in_dec(dec!(150), dec!(350), gw6_dc_spend),
         ); // 0.0150, 0.0350
 
-        let gw_shares = GatewayShares { shares };
+        let gw_shares = GatewayShares {
+            shares,
+            ..Default::default()
+        };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
-        let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
-            .collect();
+        let (gw_reward_shares, _dust) =
+            gw_shares.into_iot_reward_shares(&reward_period, iot_price, &Default::default());
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
                 rewards.insert(
@@ -598,7 +855,7 @@ mod test {
         let data_transfer_diff =
             total_used_data_transfer_tokens.to_i64().unwrap() - sum_dc_amounts as i64;
         // the sum of rewards distributed should not exceed total allocation
-        // but due to rounding whilst going to u64 in compute_rewards,
+        // but due to rounding whilst going to u64 in RewardPool::allocate,
         // is permitted to be a few bones less
         // tolerance here is 1
         assert_eq!(data_transfer_diff, 1);
@@ -655,7 +912,7 @@ mod test {
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
         let poc_diff = exp_sum_poc_tokens.to_i64().unwrap() - sum_poc_amounts as i64;
         // the sum of rewards distributed should not exceed the epoch amount
-        // but due to rounding whilst going to u64 in compute_rewards,
+        // but due to rounding whilst going to u64 in RewardPool::allocate,
         // is permitted to be a few bones less
         // tolerance here is 4
         assert_eq!(poc_diff, 3);
@@ -738,11 +995,13 @@ mod test {
             reward_shares_in_dec(dec!(150), dec!(350), gw6_dc_spend),
         ); // 0.0150, 0.0350
 
-        let gw_shares = GatewayShares { shares };
+        let gw_shares = GatewayShares {
+            shares,
+            ..Default::default()
+        };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
-        let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
-            .collect();
+        let (gw_reward_shares, _dust) =
+            gw_shares.into_iot_reward_shares(&reward_period, iot_price, &Default::default());
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
                 rewards.insert(
@@ -787,7 +1046,7 @@ mod test {
         let data_transfer_diff = total_data_transfer_tokens_for_period.to_i64().unwrap()
             - sum_data_transfer_amounts as i64;
         // the sum of rewards distributed should not exceed the epoch amount
-        // but due to rounding whilst going to u64 in compute_rewards,
+        // but due to rounding whilst going to u64 in RewardPool::allocate,
         // is permitted to be a few bones less
         // tolerance here is 2
         assert_eq!(data_transfer_diff, 3);
@@ -833,7 +1092,7 @@ mod test {
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
         let poc_diff = exp_sum_poc_tokens.to_i64().unwrap() - sum_poc_amounts as i64;
         // the sum of rewards distributed should not exceed the epoch amount
-        // but due to rounding whilst going to u64 in compute_rewards,
+        // but due to rounding whilst going to u64 in RewardPool::allocate,
         // is permitted to be a few bones less
         // tolerance here is 3
         assert_eq!(poc_diff, 3);
@@ -908,11 +1167,13 @@ mod test {
             reward_shares_in_dec(dec!(150), dec!(350), gw6_dc_spend),
         ); // 0.0150, 0.0350
 
-        let gw_shares = GatewayShares { shares };
+        let gw_shares = GatewayShares {
+            shares,
+            ..Default::default()
+        };
         let mut rewards: HashMap<PublicKeyBinary, proto::GatewayReward> = HashMap::new();
-        let gw_reward_shares: Vec<proto::IotRewardShare> = gw_shares
-            .into_iot_reward_shares(&reward_period, iot_price)
-            .collect();
+        let (gw_reward_shares, _dust) =
+            gw_shares.into_iot_reward_shares(&reward_period, iot_price, &Default::default());
         for reward in gw_reward_shares {
             if let Some(ProtoReward::GatewayReward(gateway_reward)) = reward.reward {
                 rewards.insert(
@@ -1000,7 +1261,7 @@ mod test {
         println!("total actual poc rewards distributed: {sum_poc_amounts}");
         let poc_diff = exp_sum_poc_tokens.to_u64().unwrap() - sum_poc_amounts;
         // the sum of rewards distributed should not exceed the epoch amount
-        // but due to rounding whilst going to u64 in compute_rewards,
+        // but due to rounding whilst going to u64 in RewardPool::allocate,
         // is permitted to be a few bones less
         // tolerance here is 3
         assert_eq!(poc_diff, 4);
@@ -1,11 +1,19 @@
 use crate::{
+    reward_classes, reward_scale,
     reward_share::{operational_rewards, GatewayShares},
-    telemetry,
+    telemetry, witness_quality,
 };
 use chrono::{DateTime, Duration, TimeZone, Utc};
-use db_store::meta;
-use file_store::{file_sink, traits::TimestampEncode};
+use db_store::{meta, reward_holds, FeatureFlags};
+use file_store::{
+    file_sink,
+    iot_gateway_reward_class::{GatewayClassV1, GatewayRewardClass, GatewayRewardClassV1},
+    iot_unallocated_reward::UnallocatedRewardV1,
+    iot_witness_quality::WitnessQualityReportV1,
+    traits::TimestampEncode,
+};
 use helium_proto::RewardManifest;
+use iot_config::gateway_info::GatewayClass;
 use price::PriceTracker;
 use reward_scheduler::Scheduler;
 use rust_decimal::prelude::*;
@@ -15,12 +23,27 @@ use tokio::time::sleep;
 
 const REWARDS_NOT_CURRENT_DELAY_PERIOD: i64 = 5;
 
+/// Feature flag skipping the complete data checks before rewarding a period
+pub const SKIP_COMPLETE_DATA_CHECKS_FLAG: &str = "iot_verifier.skip_complete_data_checks";
+
 pub struct Rewarder {
     pub pool: Pool<Postgres>,
     pub rewards_sink: file_sink::FileSinkClient,
     pub reward_manifests_sink: file_sink::FileSinkClient,
+    pub witness_quality_sink: file_sink::FileSinkClient,
+    pub gateway_class_sink: file_sink::FileSinkClient,
+    pub unallocated_rewards_sink: file_sink::FileSinkClient,
     pub reward_period_hours: i64,
     pub reward_offset: Duration,
+    /// Width of the window, ending with the reward period, over which the
+    /// reward scale snapshots of a gateway are averaged
+    pub reward_scale_window: Duration,
+    /// Width of the window, ending with the reward period, over which the
+    /// witness quality of gateways is scored
+    pub witness_quality_window: Duration,
+    /// Reward classes paused by governance
+    pub reward_classes: reward_classes::Settings,
+    pub feature_flags: FeatureFlags,
 }
 
 impl Rewarder {
@@ -34,6 +57,7 @@ impl Rewarder {
         let reward_period_length = Duration::hours(self.reward_period_hours);
 
         loop {
+            poc_metrics::status::heartbeat("rewards", "rewarder");
             let now = Utc::now();
 
             let scheduler = Scheduler::new(
@@ -51,7 +75,9 @@ impl Rewarder {
                     "Rewarding for period: {:?} with iot_price: {iot_price}",
                     scheduler.reward_period
                 );
-                if self.data_current_check(&scheduler.reward_period).await? {
+                if !self.is_held(&scheduler.reward_period).await?
+                    && self.data_current_check(&scheduler.reward_period).await?
+                {
                     self.reward(&scheduler, Decimal::from(iot_price)).await?;
                     scheduler.sleep_duration(Utc::now())?
                 } else {
@@ -82,12 +108,23 @@ impl Rewarder {
         scheduler: &Scheduler,
         iot_price: Decimal,
     ) -> anyhow::Result<()> {
-        let gateway_reward_shares =
-            GatewayShares::aggregate(&self.pool, &scheduler.reward_period).await?;
+        let scale_window =
+            reward_scale::averaging_window(&scheduler.reward_period, self.reward_scale_window);
+        let reward_scales = reward_scale::average_scales(&self.pool, &scale_window).await?;
+        tracing::info!(
+            gateways = reward_scales.len(),
+            "averaged reward scales over {scale_window:?}"
+        );
+        let mut gateway_reward_shares =
+            GatewayShares::aggregate(&self.pool, &scheduler.reward_period, &reward_scales).await?;
+        let classes = std::mem::take(&mut gateway_reward_shares.classes);
 
-        for reward_share in
-            gateway_reward_shares.into_iot_reward_shares(&scheduler.reward_period, iot_price)
-        {
+        let (reward_shares, unallocated_rewards) = gateway_reward_shares.into_iot_reward_shares(
+            &scheduler.reward_period,
+            iot_price,
+            &self.reward_classes,
+        );
+        for reward_share in reward_shares {
             self.rewards_sink
                 .write(reward_share, [])
                 .await?
@@ -102,9 +139,82 @@ impl Rewarder {
             .await??;
         let written_files = self.rewards_sink.commit().await?.await??;
 
+        // the class every gateway with poc shares was rewarded as, which the
+        // reward shares have no field for
+        for (hotspot_key, class) in classes {
+            let class: GatewayRewardClassV1 = GatewayRewardClass {
+                hotspot_key,
+                class: class_proto(class),
+                start_period: scheduler.reward_period.start,
+                end_period: scheduler.reward_period.end,
+            }
+            .into();
+            self.gateway_class_sink
+                .write(class, [])
+                .await?
+                // Await the returned oneshot to ensure we wrote the file
+                .await??;
+        }
+        self.gateway_class_sink.commit().await?.await??;
+
+        // the dust left from rounding down gateway rewards and the pools of
+        // disabled classes, recorded rather than paid to any other reward
+        for unallocated in unallocated_rewards {
+            self.unallocated_rewards_sink
+                .write(UnallocatedRewardV1::from(unallocated), [])
+                .await?
+                // Await the returned oneshot to ensure we wrote the file
+                .await??;
+        }
+        self.unallocated_rewards_sink.commit().await?.await??;
+
+        // the witness qualities are committed once the period is saved as
+        // rewarded, a period failing to be saved is rewarded again and
+        // writes them again
+        let saved = match self.write_witness_qualities(&scheduler.reward_period).await {
+            Ok(()) => self.save_rewarded(scheduler).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = saved {
+            self.witness_quality_sink.rollback().await?.await??;
+            return Err(err);
+        }
+        self.witness_quality_sink.commit().await?.await??;
+
+        // now that the db has been purged, safe to write out the manifest
+        self.reward_manifests_sink
+            .write(
+                RewardManifest {
+                    start_timestamp: scheduler.reward_period.start.encode_timestamp(),
+                    end_timestamp: scheduler.reward_period.end.encode_timestamp(),
+                    written_files,
+                },
+                [],
+            )
+            .await?
+            .await??;
+        self.reward_manifests_sink.commit().await?;
+        telemetry::last_rewarded_end_time(scheduler.reward_period.end);
+        Ok(())
+    }
+
+    /// Purge what the reward period no longer needs and save it as rewarded
+    async fn save_rewarded(&self, scheduler: &Scheduler) -> anyhow::Result<()> {
         let mut transaction = self.pool.begin().await?;
         // Clear gateway shares table period to end of reward period
         GatewayShares::clear_rewarded_shares(&mut transaction, scheduler.reward_period.end).await?;
+        // Snapshots before the start of the next averaging window are no longer needed
+        let next_scale_window = reward_scale::averaging_window(
+            &scheduler.next_reward_period(),
+            self.reward_scale_window,
+        );
+        reward_scale::clear_snapshots(&mut transaction, next_scale_window.start).await?;
+        // as are witness outcomes before the next witness quality window
+        witness_quality::purge(
+            &mut transaction,
+            scheduler.next_reward_period().end - self.witness_quality_window,
+        )
+        .await?;
         save_rewarded_timestamp(
             "last_rewarded_end_time",
             &scheduler.reward_period.end,
@@ -118,28 +228,53 @@ impl Rewarder {
         )
         .await?;
         transaction.commit().await?;
+        Ok(())
+    }
 
-        // now that the db has been purged, safe to write out the manifest
-        self.reward_manifests_sink
-            .write(
-                RewardManifest {
-                    start_timestamp: scheduler.reward_period.start.encode_timestamp(),
-                    end_timestamp: scheduler.reward_period.end.encode_timestamp(),
-                    written_files,
-                },
-                [],
-            )
-            .await?
-            .await??;
-        self.reward_manifests_sink.commit().await?;
-        telemetry::last_rewarded_end_time(scheduler.reward_period.end);
+    /// Write, without committing, the witness quality of every gateway over
+    /// the window ending with the reward period
+    async fn write_witness_qualities(
+        &self,
+        reward_period: &Range<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        let window = reward_period.end - self.witness_quality_window..reward_period.end;
+        let qualities = witness_quality::qualities(&self.pool, &window, None).await?;
+        tracing::info!(
+            gateways = qualities.len(),
+            "writing witness qualities over {window:?}"
+        );
+        for quality in qualities {
+            let report: WitnessQualityReportV1 = quality.into_report(&window).into();
+            self.witness_quality_sink.write(report, []).await?;
+        }
         Ok(())
     }
 
+    /// Whether an admin holds the rewards of the epoch, leaving it to be
+    /// rewarded once released
+    async fn is_held(&self, reward_period: &Range<DateTime<Utc>>) -> anyhow::Result<bool> {
+        let held = reward_holds::is_held(&self.pool, reward_period.start).await?;
+        if held {
+            tracing::info!(
+                "rewards for epoch starting {} are held",
+                reward_period.start
+            );
+        }
+        telemetry::reward_held(held);
+        Ok(held)
+    }
+
     async fn data_current_check(
         &self,
         reward_period: &Range<DateTime<Utc>>,
     ) -> anyhow::Result<bool> {
+        if self
+            .feature_flags
+            .is_enabled(SKIP_COMPLETE_DATA_CHECKS_FLAG)
+        {
+            tracing::info!("data validity checks are skipped by feature flag");
+            return Ok(true);
+        }
         // Check if we have gateway shares past the end of the reward period
         if reward_period.end >= self.disable_complete_data_checks_until().await? {
             if sqlx::query_scalar::<_, i64>(
@@ -181,6 +316,14 @@ impl Rewarder {
     }
 }
 
+fn class_proto(class: GatewayClass) -> GatewayClassV1 {
+    match class {
+        GatewayClass::Full => GatewayClassV1::Full,
+        GatewayClass::Light => GatewayClassV1::Light,
+        GatewayClass::DataOnly => GatewayClassV1::DataOnly,
+    }
+}
+
 pub async fn fetch_rewarded_timestamp(
     timestamp_key: &str,
     db: impl PgExecutor<'_>,
@@ -1,9 +1,19 @@
 use crate::{
-    gateway_cache::GatewayCache, hex_density::HexDensityMap, last_beacon::LastBeacon, poc::Poc,
-    poc_report::Report, region_cache::RegionCache, reward_share::GatewayPocShare, telemetry,
-    Settings,
+    catchup::Catchup,
+    gateway_cache::GatewayCache,
+    hex_density::HexDensityMap,
+    last_beacon::LastBeacon,
+    path_loss,
+    poc::{Poc, RssiCheck},
+    poc_report::Report,
+    region_cache::RegionCache,
+    report_store::{FetchError, ReportStore},
+    reward_share::GatewayPocShare,
+    telemetry,
+    test_gateways::{Bypass, TestGateways},
+    witness_counts, witness_quality, Settings,
 };
-use chrono::{Duration as ChronoDuration, Utc};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use file_store::{
     file_sink,
     file_sink::FileSinkClient,
@@ -11,24 +21,32 @@ use file_store::{
     iot_beacon_report::IotBeaconIngestReport,
     iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
     iot_valid_poc::{IotPoc, IotValidBeaconReport, IotVerifiedWitnessReport},
+    iot_verification_bypass::{VerificationBypass, VerificationBypassV1},
+    iot_witness_inclusion::{WitnessInclusion, WitnessInclusionV1},
     iot_witness_report::IotWitnessIngestReport,
+    iot_witness_rssi_check::{WitnessRssiCheck, WitnessRssiCheckV1},
     traits::{IngestId, MsgDecode, ReportId},
     FileType, SCALING_PRECISION,
 };
 use futures::stream::{self, StreamExt};
+use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_lora::{
     InvalidParticipantSide, InvalidReason, LoraInvalidBeaconReportV1, LoraInvalidWitnessReportV1,
     LoraPocV1, VerificationStatus,
 };
+use iot_config::gateway_info::GatewayClass;
 use rust_decimal::{Decimal, MathematicalOps};
 use rust_decimal_macros::dec;
 use sqlx::PgPool;
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 use tokio::time::{self, MissedTickBehavior};
 
 /// the cadence in seconds at which the DB is polled for ready POCs
 const DB_POLL_TIME: time::Duration = time::Duration::from_secs(30);
 const BEACON_WORKERS: usize = 100;
+/// invalid witness reports are small and many, their writes are coalesced
+/// into blocks of this many reports
+const INVALID_WITNESS_BLOCK_SIZE: usize = 500;
 
 const WITNESS_REDUNDANCY: u32 = 4;
 const POC_REWARD_DECAY_RATE: Decimal = dec!(0.8);
@@ -42,6 +60,14 @@ pub struct Runner {
     max_witnesses_per_poc: u64,
     beacon_max_retries: u64,
     witness_max_retries: u64,
+    max_witness_distance: u32,
+    max_witness_window: ChronoDuration,
+    path_loss: path_loss::Settings,
+    witness_verification_concurrency: usize,
+    witness_verification_budget: std::time::Duration,
+    catchup: Catchup,
+    test_gateways: TestGateways,
+    report_store: ReportStore,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -58,14 +84,61 @@ pub enum FilterStatus {
     Drop,
     Include,
 }
+
+/// The outcome of verifying a single beacon and its witnesses. Outcomes for a
+/// batch of beacons are collected and sequenced before anything is written to
+/// the output sinks so that reruns over the same input produce the same output
+enum VerifiedPoc {
+    Valid {
+        beacon_report: IotValidBeaconReport,
+        selected_witnesses: Vec<IotVerifiedWitnessReport>,
+        unselected_witnesses: Vec<IotVerifiedWitnessReport>,
+        bypasses: Vec<Bypass>,
+        rssi_checks: Vec<RssiCheck>,
+    },
+    Invalid {
+        beacon_report: IotBeaconIngestReport,
+        witness_reports: Vec<IotWitnessIngestReport>,
+        invalid_reason: InvalidReason,
+    },
+}
+
+impl VerifiedPoc {
+    fn received_timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Self::Valid { beacon_report, .. } => beacon_report.received_timestamp,
+            Self::Invalid { beacon_report, .. } => beacon_report.received_timestamp,
+        }
+    }
+
+    fn beacon_id(&self) -> Vec<u8> {
+        match self {
+            Self::Valid { beacon_report, .. } => beacon_report
+                .report
+                .report_id(beacon_report.received_timestamp),
+            Self::Invalid { beacon_report, .. } => beacon_report.ingest_id(),
+        }
+    }
+}
 impl Runner {
-    pub async fn from_settings(settings: &Settings, pool: PgPool) -> Result<Self, NewRunnerError> {
+    pub async fn from_settings(
+        settings: &Settings,
+        pool: PgPool,
+        report_store: ReportStore,
+    ) -> Result<Self, NewRunnerError> {
         let cache = settings.cache.clone();
         let beacon_interval = settings.beacon_interval();
         let beacon_interval_tolerance = settings.beacon_interval_tolerance();
         let max_witnesses_per_poc = settings.max_witnesses_per_poc;
         let beacon_max_retries = settings.beacon_max_retries;
         let witness_max_retries = settings.witness_max_retries;
+        let max_witness_distance = settings.max_witness_distance;
+        let max_witness_window = settings.max_witness_window();
+        let path_loss = settings.path_loss.clone();
+        let witness_verification_concurrency = settings.witness_verification_concurrency;
+        let witness_verification_budget = settings.witness_verification_budget();
+        let catchup = Catchup::from_settings(settings);
+        let test_gateways = TestGateways::from_settings(settings);
         Ok(Self {
             pool,
             cache,
@@ -74,6 +147,14 @@ impl Runner {
             max_witnesses_per_poc,
             beacon_max_retries,
             witness_max_retries,
+            max_witness_distance,
+            max_witness_window,
+            path_loss,
+            witness_verification_concurrency,
+            witness_verification_budget,
+            catchup,
+            test_gateways,
+            report_store,
         })
     }
 
@@ -113,6 +194,7 @@ impl Runner {
             )
             .deposits(Some(file_upload_tx.clone()))
             .roll_time(ChronoDuration::minutes(5))
+            .coalesce(INVALID_WITNESS_BLOCK_SIZE, ChronoDuration::seconds(1))
             .create()
             .await?;
 
@@ -127,9 +209,56 @@ impl Runner {
         .create()
         .await?;
 
+        let (iot_witness_inclusion_sink, mut iot_witness_inclusion_sink_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotWitnessInclusion,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_witness_inclusion"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .roll_time(ChronoDuration::minutes(2))
+            .create()
+            .await?;
+
+        let (iot_verification_bypass_sink, mut iot_verification_bypass_sink_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotVerificationBypass,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_verification_bypass"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .roll_time(ChronoDuration::minutes(2))
+            .create()
+            .await?;
+
         tokio::spawn(async move { iot_invalid_beacon_sink_server.run().await });
         tokio::spawn(async move { iot_invalid_witness_sink_server.run().await });
         tokio::spawn(async move { iot_poc_sink_server.run().await });
+        tokio::spawn(async move { iot_witness_inclusion_sink_server.run().await });
+        let (iot_witness_rssi_check_sink, mut iot_witness_rssi_check_sink_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotWitnessRssiCheck,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_witness_rssi_check"),
+                shutdown.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .roll_time(ChronoDuration::minutes(2))
+            .create()
+            .await?;
+
+        tokio::spawn(async move { iot_verification_bypass_sink_server.run().await });
+        tokio::spawn(async move { iot_witness_rssi_check_sink_server.run().await });
+        let sinks = [
+            iot_invalid_beacon_sink.clone(),
+            iot_invalid_witness_sink.clone(),
+            iot_poc_sink.clone(),
+            iot_witness_inclusion_sink.clone(),
+            iot_verification_bypass_sink.clone(),
+            iot_witness_rssi_check_sink.clone(),
+        ];
 
         loop {
             if shutdown.is_triggered() {
@@ -137,15 +266,19 @@ impl Runner {
             }
             tokio::select! {
                 _ = shutdown.clone() => break,
+                err = file_sink::first_failure(&sinks) => return Err(err.into()),
                 _ = db_timer.tick() =>
                     match self.handle_db_tick(  shutdown.clone(),
                                                 &iot_invalid_beacon_sink,
                                                 &iot_invalid_witness_sink,
                                                 &iot_poc_sink,
+                                                &iot_witness_inclusion_sink,
+                                                &iot_verification_bypass_sink,
+                                                &iot_witness_rssi_check_sink,
                                                 gateway_cache,
                                                 region_cache,
                                                 hex_density_map.clone()).await {
-                    Ok(()) => (),
+                    Ok(()) => poc_metrics::status::heartbeat("poc", "runner"),
                     Err(err) => {
                         tracing::error!("fatal db runner error: {err:?}");
                     }
@@ -163,13 +296,26 @@ impl Runner {
         iot_invalid_beacon_sink: &FileSinkClient,
         iot_invalid_witness_sink: &FileSinkClient,
         iot_poc_sink: &FileSinkClient,
+        iot_witness_inclusion_sink: &FileSinkClient,
+        iot_verification_bypass_sink: &FileSinkClient,
+        iot_witness_rssi_check_sink: &FileSinkClient,
         gateway_cache: &GatewayCache,
         region_cache: &RegionCache,
         hex_density_map: impl HexDensityMap,
     ) -> anyhow::Result<()> {
-        tracing::info!("starting query get_next_beacons");
-        let db_beacon_reports =
-            Report::get_next_beacons(&self.pool, self.beacon_max_retries).await?;
+        let in_catchup = self.catchup.is_active(&self.pool).await?;
+        let work_order = self.catchup.work_order(in_catchup);
+        tracing::info!(
+            work_order = work_order.as_str(),
+            "starting query get_next_beacons"
+        );
+        let db_beacon_reports = Report::get_next_beacons(
+            &self.pool,
+            self.beacon_max_retries,
+            self.max_witness_window,
+            work_order,
+        )
+        .await?;
         tracing::info!("completed query get_next_beacons");
         if db_beacon_reports.is_empty() {
             tracing::info!("no beacons ready for verification");
@@ -183,59 +329,105 @@ impl Runner {
         let beacon_len = db_beacon_reports.len();
         tracing::info!("{beacon_len} beacons ready for verification");
 
-        stream::iter(db_beacon_reports)
-            .for_each_concurrent(BEACON_WORKERS, |db_beacon| {
+        // verify concurrently but hold on to the results so that they
+        // can be written out in a deterministic order below
+        let mut verified_pocs: Vec<VerifiedPoc> = stream::iter(db_beacon_reports)
+            .map(|db_beacon| {
                 let hdm = hex_density_map.clone();
                 async move {
                     let beacon_id = db_beacon.id.clone();
                     match self
-                        .handle_beacon_report(
-                            db_beacon,
-                            iot_invalid_beacon_sink,
-                            iot_invalid_witness_sink,
-                            iot_poc_sink,
-                            gateway_cache,
-                            region_cache,
-                            hdm,
-                        )
+                        .verify_beacon_report(db_beacon, gateway_cache, region_cache, hdm)
                         .await
                     {
-                        Ok(()) => (),
+                        Ok(verified_poc) => verified_poc,
+                        // retried next tick, an unavailable report store
+                        // shouldn't exhaust the attempts of its reports
+                        Err(err) if err.is::<FetchError>() => {
+                            tracing::warn!("failed to fetch beacon reports: {err:?}");
+                            None
+                        }
                         Err(err) => {
                             tracing::warn!("failed to handle beacon: {err:?}");
                             _ = Report::update_attempts(&self.pool, &beacon_id, Utc::now()).await;
+                            None
                         }
                     }
                 }
             })
+            .buffer_unordered(BEACON_WORKERS)
+            .filter_map(|verified_poc| async move { verified_poc })
+            .collect()
             .await;
+
+        sequence_pocs(&mut verified_pocs);
+        for verified_poc in verified_pocs {
+            let beacon_id = verified_poc.beacon_id();
+            let result = match verified_poc {
+                VerifiedPoc::Valid {
+                    beacon_report,
+                    selected_witnesses,
+                    unselected_witnesses,
+                    bypasses,
+                    rssi_checks,
+                } => {
+                    self.handle_valid_poc(
+                        beacon_report,
+                        selected_witnesses,
+                        unselected_witnesses,
+                        bypasses,
+                        rssi_checks,
+                        iot_poc_sink,
+                        iot_witness_inclusion_sink,
+                        iot_verification_bypass_sink,
+                        iot_witness_rssi_check_sink,
+                        gateway_cache,
+                    )
+                    .await
+                }
+                VerifiedPoc::Invalid {
+                    beacon_report,
+                    witness_reports,
+                    invalid_reason,
+                } => {
+                    self.handle_invalid_poc(
+                        &beacon_report,
+                        witness_reports,
+                        invalid_reason,
+                        iot_invalid_beacon_sink,
+                        iot_invalid_witness_sink,
+                    )
+                    .await
+                }
+            };
+            if let Err(err) = result {
+                tracing::warn!("failed to handle beacon: {err:?}");
+                _ = Report::update_attempts(&self.pool, &beacon_id, Utc::now()).await;
+            }
+        }
         tracing::info!("completed processing {beacon_len} beacons");
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn handle_beacon_report(
+    async fn verify_beacon_report(
         &self,
         db_beacon: Report,
-        iot_invalid_beacon_sink: &FileSinkClient,
-        iot_invalid_witness_sink: &FileSinkClient,
-        iot_poc_sink: &FileSinkClient,
         gateway_cache: &GatewayCache,
         region_cache: &RegionCache,
         hex_density_map: impl HexDensityMap,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<Option<VerifiedPoc>> {
         let entropy_start_time = match db_beacon.timestamp {
             Some(v) => v,
-            None => return Ok(()),
+            None => return Ok(None),
         };
         let entropy_version = match db_beacon.version {
             Some(v) => v,
-            None => return Ok(()),
+            None => return Ok(None),
         };
         let packet_data = &db_beacon.packet_data;
 
-        let beacon_buf: &[u8] = &db_beacon.report_data;
-        let beacon_report = IotBeaconIngestReport::decode(beacon_buf)?;
+        let beacon_buf = self.report_store.body(&db_beacon).await?;
+        let beacon_report = IotBeaconIngestReport::decode(beacon_buf.as_ref())?;
         let beacon = &beacon_report.report;
         let beacon_received_ts = beacon_report.received_timestamp;
 
@@ -248,8 +440,8 @@ impl Runner {
         // get the beacon and witness report PBs from the db reports
         let mut witnesses: Vec<IotWitnessIngestReport> = Vec::new();
         for db_witness in db_witnesses {
-            let witness_buf: &[u8] = &db_witness.report_data;
-            witnesses.push(IotWitnessIngestReport::decode(witness_buf)?);
+            let witness_buf = self.report_store.body(&db_witness).await?;
+            witnesses.push(IotWitnessIngestReport::decode(witness_buf.as_ref())?);
         }
 
         // create the struct defining this POC
@@ -258,6 +450,7 @@ impl Runner {
             witnesses.clone(),
             entropy_start_time,
             entropy_version,
+            self.test_gateways.clone(),
         )
         .await;
 
@@ -277,7 +470,17 @@ impl Runner {
                 // beacon is valid, verify the POC witnesses
                 if let Some(beacon_info) = beacon_verify_result.gateway_info {
                     let verified_witnesses_result = poc
-                        .verify_witnesses(&beacon_info, hex_density_map, gateway_cache)
+                        .verify_witnesses(
+                            &beacon_info,
+                            hex_density_map,
+                            gateway_cache,
+                            region_cache,
+                            self.max_witness_distance,
+                            self.max_witness_window,
+                            &self.path_loss,
+                            self.witness_verification_concurrency,
+                            self.witness_verification_budget,
+                        )
                         .await?;
                     // check if there are any failed witnesses
                     // if so update the DB attempts count
@@ -285,7 +488,11 @@ impl Runner {
                     // if a witness continues to fail it will eventually
                     // be discarded from the list returned for the beacon
                     // thus one or more failing witnesses will not block the overall POC
-                    if !verified_witnesses_result.failed_witnesses.is_empty() {
+                    // witnesses left unverified by the time budget are
+                    // retried without counting against their attempts
+                    if !verified_witnesses_result.failed_witnesses.is_empty()
+                        || !verified_witnesses_result.unverified_witnesses.is_empty()
+                    {
                         tracing::warn!("failed to handle witness");
                         for failed_witness_report in verified_witnesses_result.failed_witnesses {
                             let failed_witness = failed_witness_report.report;
@@ -293,7 +500,7 @@ impl Runner {
                                 failed_witness.report_id(failed_witness_report.received_timestamp);
                             Report::update_attempts(&self.pool, &id, Utc::now()).await?;
                         }
-                        return Ok(());
+                        return Ok(None);
                     };
 
                     let max_witnesses_per_poc = self.max_witnesses_per_poc as usize;
@@ -354,28 +561,25 @@ impl Runner {
                         report: beacon.clone(),
                         reward_unit: beaconer_reward_units,
                     };
-                    self.handle_valid_poc(
-                        valid_beacon_report,
+                    return Ok(Some(VerifiedPoc::Valid {
+                        beacon_report: valid_beacon_report,
                         selected_witnesses,
                         unselected_witnesses,
-                        iot_poc_sink,
-                    )
-                    .await?;
+                        bypasses: poc.bypasses().to_vec(),
+                        rssi_checks: poc.rssi_checks().to_vec(),
+                    }));
                 }
+                Ok(None)
             }
             VerificationStatus::Invalid => {
                 // the beacon is invalid, which in turn renders all witnesses invalid
-                self.handle_invalid_poc(
-                    &beacon_report,
-                    witnesses,
-                    beacon_verify_result.invalid_reason,
-                    iot_invalid_beacon_sink,
-                    iot_invalid_witness_sink,
-                )
-                .await?;
+                Ok(Some(VerifiedPoc::Invalid {
+                    beacon_report,
+                    witness_reports: witnesses,
+                    invalid_reason: beacon_verify_result.invalid_reason,
+                }))
             }
         }
-        Ok(())
     }
 
     async fn handle_invalid_poc(
@@ -445,12 +649,19 @@ impl Runner {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn handle_valid_poc(
         &self,
         valid_beacon_report: IotValidBeaconReport,
         selected_witnesses: Vec<IotVerifiedWitnessReport>,
         unselected_witnesses: Vec<IotVerifiedWitnessReport>,
+        bypasses: Vec<Bypass>,
+        rssi_checks: Vec<RssiCheck>,
         iot_poc_sink: &FileSinkClient,
+        iot_witness_inclusion_sink: &FileSinkClient,
+        iot_verification_bypass_sink: &FileSinkClient,
+        iot_witness_rssi_check_sink: &FileSinkClient,
+        gateway_cache: &GatewayCache,
     ) -> anyhow::Result<()> {
         let received_timestamp = valid_beacon_report.received_timestamp;
         let pub_key = valid_beacon_report.report.pub_key.clone();
@@ -464,14 +675,30 @@ impl Runner {
             unselected_witnesses: unselected_witnesses.clone(),
         };
 
+        let classes = gateway_classes(gateway_cache, &iot_poc).await?;
         let mut transaction = self.pool.begin().await?;
-        for reward_share in GatewayPocShare::shares_from_poc(&iot_poc) {
+        for reward_share in GatewayPocShare::shares_from_poc(&iot_poc, &classes) {
             reward_share.save(&mut transaction).await?;
         }
+        witness_counts::record(&mut transaction, &iot_poc).await?;
+        witness_quality::record(&mut transaction, &iot_poc).await?;
         // TODO: expand this transaction to cover all of the database access below?
         transaction.commit().await?;
 
         let poc_proto: LoraPocV1 = iot_poc.into();
+        let poc_id = poc_proto.poc_id.clone();
+        // the inclusion proofs go out first, a poc reprocessed after a failed
+        // write then at worst repeats its proofs, never the poc itself
+        let witness_inclusion: WitnessInclusionV1 =
+            WitnessInclusion::new(poc_id.clone(), &poc_proto.selected_witnesses).into();
+        if let Err(err) = iot_witness_inclusion_sink
+            .write(witness_inclusion, [])
+            .await
+        {
+            tracing::error!("failed to save witness_inclusion to s3, {err}");
+            Report::update_attempts(&self.pool, &beacon_report_id, Utc::now()).await?;
+            return Ok(());
+        }
         // save the poc to s3, if write fails update attempts and go no further
         // allow the poc to be reprocessed next tick
         match iot_poc_sink.write(poc_proto, []).await {
@@ -482,6 +709,45 @@ impl Runner {
                 return Ok(());
             }
         }
+        // record the checks skipped for test gateways, ignoring failed writes
+        // as with invalid witnesses, the poc itself is already out
+        for bypass in bypasses {
+            let bypass_proto: VerificationBypassV1 = VerificationBypass {
+                timestamp: received_timestamp,
+                poc_id: poc_id.clone(),
+                pub_key: bypass.pub_key,
+                check: bypass.check.as_str().to_string(),
+            }
+            .into();
+            if let Err(err) = iot_verification_bypass_sink
+                .write(bypass_proto, &[("check", bypass.check.as_str())])
+                .await
+            {
+                tracing::error!("ignoring failed s3 write of verification_bypass: {err}");
+            }
+        }
+        // record the model and margin witnesses were rejected for their rssi
+        // under, ignoring failed writes as well
+        for rssi_check in rssi_checks {
+            let model = rssi_check.path_loss_model.as_str();
+            let rssi_check_proto: WitnessRssiCheckV1 = WitnessRssiCheck {
+                timestamp: received_timestamp,
+                poc_id: poc_id.clone(),
+                pub_key: rssi_check.pub_key,
+                path_loss_model: model.to_string(),
+                margin_db: rssi_check.margin_db,
+                expected_rssi_dbm: rssi_check.expected_rssi_dbm,
+                signal: rssi_check.signal,
+                distance: rssi_check.distance,
+            }
+            .into();
+            if let Err(err) = iot_witness_rssi_check_sink
+                .write(rssi_check_proto, &[("path_loss_model", model)])
+                .await
+            {
+                tracing::error!("ignoring failed s3 write of witness_rssi_check: {err}");
+            }
+        }
         // write out metrics for any witness which failed verification
         // TODO: work our approach that doesnt require the prior cloning of
         // the selected and unselected witnesses vecs
@@ -491,6 +757,7 @@ impl Runner {
         fire_invalid_witness_metric(&unselected_witnesses);
         // update timestamp of last beacon for the beaconer
         LastBeacon::update_last_timestamp(&self.pool, pub_key.as_ref(), received_timestamp).await?;
+        LastBeacon::insert_history(&self.pool, pub_key.as_ref(), received_timestamp).await?;
         Report::delete_poc(&self.pool, &packet_data).await?;
         telemetry::decrement_num_beacons();
         Ok(())
@@ -501,13 +768,7 @@ fn poc_beaconer_reward_unit(num_witnesses: u32) -> anyhow::Result<Decimal> {
     let reward_units = if num_witnesses == 0 {
         Decimal::ZERO
     } else if num_witnesses <= WITNESS_REDUNDANCY {
-        if let Some(sub_redundancy_units) =
-            Decimal::from_f32_retain(num_witnesses as f32 / WITNESS_REDUNDANCY as f32)
-        {
-            sub_redundancy_units
-        } else {
-            anyhow::bail!("invalid fractional division: {num_witnesses} / {WITNESS_REDUNDANCY}");
-        }
+        Decimal::from(num_witnesses) / Decimal::from(WITNESS_REDUNDANCY)
     } else {
         let exp = num_witnesses - WITNESS_REDUNDANCY;
         if let Some(to_sub) = POC_REWARD_DECAY_RATE.checked_powu(exp as u64) {
@@ -550,11 +811,68 @@ fn sort_and_split_witnesses(
     if witnesses.len() <= max_count {
         return Ok(Vec::new());
     }
-    witnesses.sort_by(|a, b| a.received_timestamp.cmp(&b.received_timestamp));
+    sort_witnesses(witnesses);
     let unselected_witnesses = witnesses.split_off(max_count);
     Ok(unselected_witnesses)
 }
 
+/// order verified pocs by the received timestamp of the beacon, then by
+/// beacon report id, along with the witnesses of each poc
+fn sequence_pocs(pocs: &mut [VerifiedPoc]) {
+    pocs.sort_by_cached_key(|poc| (poc.received_timestamp(), poc.beacon_id()));
+    for poc in pocs.iter_mut() {
+        match poc {
+            VerifiedPoc::Valid {
+                selected_witnesses,
+                unselected_witnesses,
+                ..
+            } => {
+                sort_witnesses(selected_witnesses);
+                sort_witnesses(unselected_witnesses);
+            }
+            VerifiedPoc::Invalid {
+                witness_reports, ..
+            } => {
+                witness_reports.sort_by_cached_key(|witness| {
+                    (witness.received_timestamp, witness.ingest_id())
+                });
+            }
+        }
+    }
+}
+
+/// the classes of the beaconer and selected witnesses of a poc when it was
+/// beaconed, as cached when it was verified. A gateway failing to resolve fails the poc, which is
+/// retried rather than rewarded under a class it may not have
+async fn gateway_classes(
+    gateway_cache: &GatewayCache,
+    poc: &IotPoc,
+) -> anyhow::Result<HashMap<PublicKeyBinary, GatewayClass>> {
+    let mut classes = HashMap::new();
+    let pub_keys = std::iter::once(&poc.beacon_report.report.pub_key).chain(
+        poc.selected_witnesses
+            .iter()
+            .map(|witness| &witness.report.pub_key),
+    );
+    for pub_key in pub_keys {
+        let info = gateway_cache.resolve_gateway_info(pub_key).await?;
+        classes.insert(
+            pub_key.clone(),
+            info.class(poc.beacon_report.received_timestamp),
+        );
+    }
+    Ok(classes)
+}
+
+fn sort_witnesses(witnesses: &mut [IotVerifiedWitnessReport]) {
+    witnesses.sort_by_cached_key(|witness| {
+        (
+            witness.received_timestamp,
+            witness.report.report_id(witness.received_timestamp),
+        )
+    });
+}
+
 fn filter_witnesses(
     witnesses: Vec<IotVerifiedWitnessReport>,
 ) -> (Vec<IotVerifiedWitnessReport>, Vec<IotVerifiedWitnessReport>) {
@@ -592,10 +910,12 @@ fn fire_invalid_witness_metric(witnesses: &[IotVerifiedWitnessReport]) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use file_store::iot_witness_report::IotWitnessReport;
+    use crate::gateway_updater::GatewayMap;
+    use file_store::{iot_beacon_report::IotBeaconReport, iot_witness_report::IotWitnessReport};
     use helium_crypto::PublicKeyBinary;
     use helium_proto::services::poc_lora::InvalidReason;
     use helium_proto::DataRate;
+    use iot_config::gateway_info::GatewayInfo;
     use rust_decimal::Decimal;
     use std::str::FromStr;
 
@@ -686,6 +1006,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn verified_pocs_are_sequenced() {
+        let key1 =
+            PublicKeyBinary::from_str("112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf")
+                .unwrap();
+        let now = Utc::now();
+        let invalid_poc = |received_timestamp, data: u8| VerifiedPoc::Invalid {
+            beacon_report: IotBeaconIngestReport {
+                received_timestamp,
+                report: IotBeaconReport {
+                    pub_key: key1.clone(),
+                    local_entropy: vec![],
+                    remote_entropy: vec![],
+                    data: vec![data],
+                    frequency: 68000,
+                    channel: 0,
+                    datarate: DataRate::Sf11bw125,
+                    tx_power: 27,
+                    timestamp: now,
+                    signature: vec![],
+                    tmst: 1,
+                },
+            },
+            witness_reports: vec![],
+            invalid_reason: InvalidReason::Stale,
+        };
+
+        let later = now + ChronoDuration::seconds(1);
+        let mut pocs = vec![
+            invalid_poc(later, 1),
+            invalid_poc(now, 2),
+            invalid_poc(later, 3),
+            invalid_poc(now, 4),
+        ];
+        let mut expected: Vec<(DateTime<Utc>, Vec<u8>)> = pocs
+            .iter()
+            .map(|poc| (poc.received_timestamp(), poc.beacon_id()))
+            .collect();
+        expected.sort();
+
+        pocs.reverse();
+        sequence_pocs(&mut pocs);
+        let sequenced: Vec<(DateTime<Utc>, Vec<u8>)> = pocs
+            .iter()
+            .map(|poc| (poc.received_timestamp(), poc.beacon_id()))
+            .collect();
+        assert_eq!(expected, sequenced);
+        assert_eq!(now, sequenced[0].0);
+        assert_eq!(later, sequenced[3].0);
+    }
+
+    #[tokio::test]
+    async fn unresolved_gateway_classes_fail_the_poc() {
+        let beaconer = PublicKeyBinary::from(vec![1]);
+        let witness = PublicKeyBinary::from(vec![2]);
+        let now = Utc::now();
+        let poc = IotPoc {
+            poc_id: vec![1],
+            beacon_report: IotValidBeaconReport {
+                received_timestamp: now,
+                location: None,
+                gain: 12,
+                elevation: 0,
+                hex_scale: Decimal::ONE,
+                report: IotBeaconReport {
+                    pub_key: beaconer.clone(),
+                    local_entropy: vec![],
+                    remote_entropy: vec![],
+                    data: vec![1],
+                    frequency: 68000,
+                    channel: 0,
+                    datarate: DataRate::Sf11bw125,
+                    tx_power: 27,
+                    timestamp: now,
+                    signature: vec![],
+                    tmst: 1,
+                },
+                reward_unit: Decimal::ONE,
+            },
+            selected_witnesses: vec![IotVerifiedWitnessReport {
+                received_timestamp: now,
+                report: IotWitnessReport {
+                    pub_key: witness.clone(),
+                    data: vec![1],
+                    timestamp: now,
+                    tmst: 1,
+                    signal: 100,
+                    snr: 10,
+                    frequency: 68000,
+                    datarate: DataRate::Sf11bw125,
+                    signature: vec![],
+                },
+                location: None,
+                gain: 20,
+                elevation: 100,
+                hex_scale: Decimal::ONE,
+                reward_unit: Decimal::ONE,
+                status: VerificationStatus::Valid,
+                invalid_reason: InvalidReason::ReasonNone,
+                participant_side: InvalidParticipantSide::SideNone,
+            }],
+            unselected_witnesses: vec![],
+        };
+        let gateway = |address: &PublicKeyBinary, is_full_hotspot| GatewayInfo {
+            address: address.clone(),
+            metadata: None,
+            is_full_hotspot,
+        };
+        let (sender, receiver) = tokio::sync::watch::channel(GatewayMap::new());
+        let gateway_cache = GatewayCache::new(receiver);
+        sender.send_modify(|gateways| {
+            gateways.insert(beaconer.clone(), gateway(&beaconer, true));
+        });
+
+        // a witness missing from the cache isn't rewarded as any class
+        assert!(gateway_classes(&gateway_cache, &poc).await.is_err());
+
+        sender.send_modify(|gateways| {
+            gateways.insert(witness.clone(), gateway(&witness, false));
+        });
+        let classes = gateway_classes(&gateway_cache, &poc).await.unwrap();
+        assert_eq!(classes[&beaconer], GatewayClass::Full);
+        assert_eq!(classes[&witness], GatewayClass::DataOnly);
+    }
+
     #[test]
     fn max_witnesses_per_poc_test() {
         let key1 =
@@ -773,28 +1218,40 @@ mod tests {
             beacon_rewards.push(beacon_reward);
         }
 
-        let expected_witness_rewards: Vec<Decimal> = vec![
-            1.0000, 1.0000, 1.0000, 1.0000, 0.7600, 0.6067, 0.5017, 0.4262, 0.3697, 0.3262, 0.2918,
-            0.2640, 0.2411, 0.2220, 0.2057,
-        ]
-        .into_iter()
-        .map(|float| {
-            Decimal::from_f32_retain(float)
-                .expect("failed float to decimal conversion")
-                .round_dp(SCALING_PRECISION)
-        })
-        .collect();
-        let expected_beacon_rewards: Vec<Decimal> = vec![
-            0.25, 0.50, 0.75, 1.0000, 1.2000, 1.3600, 1.4880, 1.5904, 1.6723, 1.7379, 1.7903,
-            1.8322, 1.8658, 1.8926, 1.9141,
-        ]
-        .into_iter()
-        .map(|float| {
-            Decimal::from_f32_retain(float)
-                .expect("failed float to decimal conversion")
-                .round_dp(SCALING_PRECISION)
-        })
-        .collect();
+        let expected_witness_rewards = vec![
+            dec!(1.0000),
+            dec!(1.0000),
+            dec!(1.0000),
+            dec!(1.0000),
+            dec!(0.7600),
+            dec!(0.6067),
+            dec!(0.5017),
+            dec!(0.4262),
+            dec!(0.3697),
+            dec!(0.3262),
+            dec!(0.2918),
+            dec!(0.2640),
+            dec!(0.2411),
+            dec!(0.2220),
+            dec!(0.2057),
+        ];
+        let expected_beacon_rewards = vec![
+            dec!(0.25),
+            dec!(0.50),
+            dec!(0.75),
+            dec!(1.0000),
+            dec!(1.2000),
+            dec!(1.3600),
+            dec!(1.4880),
+            dec!(1.5904),
+            dec!(1.6723),
+            dec!(1.7379),
+            dec!(1.7903),
+            dec!(1.8322),
+            dec!(1.8658),
+            dec!(1.8926),
+            dec!(1.9141),
+        ];
         assert_eq!(expected_witness_rewards, witness_rewards);
         assert_eq!(expected_beacon_rewards, beacon_rewards);
     }
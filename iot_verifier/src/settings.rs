@@ -1,7 +1,10 @@
+use crate::{catchup::WorkOrder, entropy::ENTROPY_LIFESPAN, tx_scaler::Weighting};
 use chrono::Duration;
-use config::{Config, Environment, File};
+use helium_crypto::PublicKeyBinary;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
 use serde::Deserialize;
-use std::path::Path;
+use std::{collections::HashSet, num::NonZeroU64, path::Path, str::FromStr};
+use task_scheduler::Schedule;
 use tokio::time;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -12,13 +15,69 @@ pub struct Settings {
     pub log: String,
     /// Cache location for generated verified reports
     pub cache: String,
-    /// the base_stale period in seconds
+    /// the base_stale period
     /// if this is set, this value will be added to the entropy and report
     /// stale periods and is to prevent data being unnecessarily purged
     /// in the event the verifier is down for an extended period of time
-    #[serde(default = "default_base_stale_period")]
-    pub base_stale_period: i64,
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_base_stale_period"
+    )]
+    pub base_stale_period: time::Duration,
+    /// Period after which a beacon report in the DB is deemed stale and
+    /// purged. (Default is 45m)
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_beacon_stale_period"
+    )]
+    pub beacon_stale_period: time::Duration,
+    /// Period after which a witness report in the DB is deemed stale and
+    /// purged. Must not be shorter than `beacon_stale_period`. (Default is
+    /// 45m)
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_witness_stale_period"
+    )]
+    pub witness_stale_period: time::Duration,
+    /// Period after which an entropy entry in the DB is deemed stale and
+    /// purged. Must not be shorter than `beacon_stale_period`. (Default is
+    /// 1h)
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_entropy_stale_period"
+    )]
+    pub entropy_stale_period: time::Duration,
+    /// Max number of stale reports the purger deletes per statement, once
+    /// their invalid reports are written out. (Default is 1000)
+    #[serde(default = "default_purger_delete_batch_size")]
+    pub purger_delete_batch_size: usize,
+    /// Only report the stale reports and entropy the purger would purge,
+    /// writing and deleting nothing. (Default is false)
+    #[serde(default)]
+    pub purger_dry_run: bool,
+    /// When the purger looks for stale reports and entropy, every number of
+    /// seconds or duration like "35m", or on a cron expression like
+    /// "0 */30 * * * *". (Default is 2100; 35 minutes)
+    #[serde(default = "default_purger_schedule")]
+    pub purger_schedule: Schedule,
+    /// Up to how long each purge is delayed at random past its schedule (in
+    /// seconds). (Default is 0)
+    #[serde(default)]
+    pub purger_jitter: u64,
+    /// Bucket the bodies of large beacon and witness reports are stored in
+    /// instead of the DB, see `report_offload_threshold`. Disabled when not
+    /// set
+    pub report_blobs: Option<file_store::Settings>,
+    /// Reports with bodies of at least this many bytes are stored in the
+    /// `report_blobs` bucket, the DB only pointing at them. (Default is 2048)
+    #[serde(default = "default_report_offload_threshold")]
+    pub report_offload_threshold: usize,
     pub database: db_store::Settings,
+    /// Database the writes to `database` are mirrored to while moving the
+    /// verifier to it, see `db_store::dual_write`. Disabled when not set
+    pub shadow_database: Option<db_store::Settings>,
+    /// Signed api serving the transmit scaling map, disabled when not set
+    pub density_api: Option<crate::density_service::Settings>,
     pub iot_config_client: iot_config::client::Settings,
     pub ingest: file_store::Settings,
     pub packet_ingest: file_store::Settings,
@@ -43,9 +102,84 @@ pub struct Settings {
     /// Tolerance applied to beacon intervals within which beacons will be accepted (in seconds)
     #[serde(default = "default_beacon_interval_tolerance")]
     pub beacon_interval_tolerance: i64,
-    /// Trigger interval for generating a transmit scaling map
+    /// Schedule on which the transmit scaling map is regenerated even
+    /// without gateway changes, every number of seconds or on a cron
+    /// expression. (Default is 1800; 30 minutes)
     #[serde(default = "default_transmit_scale_interval")]
-    pub transmit_scale_interval: i64,
+    pub transmit_scale_interval: Schedule,
+    /// Up to how long each scheduled regeneration is delayed at random (in
+    /// seconds). (Default is 0)
+    #[serde(default)]
+    pub transmit_scale_jitter: u64,
+    /// Interval at which the reward scale of interactive gateways is
+    /// snapshotted (in seconds). (Default is 3600; 1 hour)
+    #[serde(default = "default_reward_scale_snapshot_interval")]
+    pub reward_scale_snapshot_interval: u64,
+    /// Width of the window, ending with the reward period, over which a
+    /// gateway's reward scale snapshots are averaged. (Default to 24h)
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_reward_scale_window"
+    )]
+    pub reward_scale_window: time::Duration,
+    /// Width of the window over which the witness quality of a gateway is
+    /// scored, ending with the reward period in the rewards and now in the
+    /// api (in hours). (Default to 168)
+    #[serde(default = "default_witness_quality_window")]
+    pub witness_quality_window: i64,
+    /// Index the gateways of the poc output files of every hour, so lookups
+    /// of the pocs of a gateway only fetch the files holding them. (Default
+    /// is true)
+    #[serde(default = "default_index_pocs")]
+    pub index_pocs: bool,
+    /// Reward classes paused from an epoch, every class is rewarded by
+    /// default
+    #[serde(default)]
+    pub reward_classes: crate::reward_classes::Settings,
+    /// What gateways count for in transmit scaling density, "presence" or
+    /// "witnesses", weighting each by its valid witnesses over
+    /// `transmit_scale_witness_window`. (Default is "presence")
+    #[serde(default)]
+    pub transmit_scale_weighting: Weighting,
+    /// Valid witnesses over the window at which a gateway counts fully when
+    /// weighting by witnesses, fewer count proportionally less. Must be
+    /// positive. (Default is 10)
+    #[serde(default = "default_transmit_scale_witness_target")]
+    pub transmit_scale_witness_target: NonZeroU64,
+    /// Window of valid witnesses counted when weighting by witnesses (in
+    /// hours). (Default to 24)
+    #[serde(default = "default_transmit_scale_witness_window")]
+    pub transmit_scale_witness_window: i64,
+    /// Compute both the presence and the witness weighted scaling maps on
+    /// every refresh and write a comparison of them, for a trial of the
+    /// weighting. (Default is false)
+    #[serde(default)]
+    pub transmit_scale_comparison: bool,
+    /// A refreshed scaling map changing the scale of more than this ratio of
+    /// the hexes of the current map, by more than
+    /// `transmit_scale_guard_scale_delta` or by dropping them, is refused.
+    /// (Default is 0.25)
+    #[serde(default = "default_transmit_scale_guard_changed_ratio")]
+    pub transmit_scale_guard_changed_ratio: f64,
+    /// Change of the scale of a hex counting towards
+    /// `transmit_scale_guard_changed_ratio`. (Default is 0.25)
+    #[serde(default = "default_transmit_scale_guard_scale_delta")]
+    pub transmit_scale_guard_scale_delta: f64,
+    /// Interval at which the beacon cadence of gateways is checked. (Default
+    /// is 24h)
+    #[serde(
+        with = "settings_loader::duration::positive",
+        default = "default_beacon_cadence_check_interval"
+    )]
+    pub beacon_cadence_check_interval: time::Duration,
+    /// Window of beacon history the cadence check looks at (in hours).
+    /// (Default is 168; 7 days)
+    #[serde(default = "default_beacon_cadence_window")]
+    pub beacon_cadence_window: i64,
+    /// Beacon intervals less than this far above the minimum allowed interval
+    /// count as beaconing at the edge of it (in seconds). (Default is 300)
+    #[serde(default = "default_beacon_cadence_edge_margin")]
+    pub beacon_cadence_edge_margin: i64,
     // roll up time defined in the ingestors ( in seconds )
     // ie the time after which they will write out files to s3
     // this will be used when padding out the witness
@@ -83,23 +217,173 @@ pub struct Settings {
     #[serde(default = "default_witness_max_retries")]
     pub witness_max_retries: u64,
     /// interval at which gateways are refreshed
-    #[serde(default = "default_gateway_refresh_interval")]
-    pub gateway_refresh_interval: i64,
+    #[serde(
+        with = "settings_loader::duration::positive",
+        default = "default_gateway_refresh_interval"
+    )]
+    pub gateway_refresh_interval: time::Duration,
+    /// Window gateway location flaps are counted over, and location changes
+    /// are kept for (in seconds). (Default is 86400; 24 hours)
+    #[serde(default = "default_location_flap_window")]
+    pub location_flap_window: i64,
+    /// Flaps within the window for a gateway to be flagged as flapping.
+    /// (Default is 3)
+    #[serde(default = "default_location_flap_threshold")]
+    pub location_flap_threshold: u64,
+    /// Schedule on which a sample of the cached gateways is reconciled with
+    /// iot config, every number of seconds or on a cron expression. (Default
+    /// is 3600; 1 hour)
+    #[serde(default = "default_gateway_reconcile_interval")]
+    pub gateway_reconcile_interval: Schedule,
+    /// Up to how long each reconciliation is delayed at random (in seconds).
+    /// (Default is 0)
+    #[serde(default)]
+    pub gateway_reconcile_jitter: u64,
+    /// Number of cached gateways reconciled per run, 0 disables
+    /// reconciliation. (Default is 500)
+    #[serde(default = "default_gateway_reconcile_sample_size")]
+    pub gateway_reconcile_sample_size: usize,
+    /// Replace drifted gateways in the cache by their info in iot config.
+    /// (Default is false)
+    #[serde(default)]
+    pub gateway_reconcile_repair: bool,
+    /// Interval at which iot config is polled for the gateways updated since
+    /// the last poll, which are refreshed in the cache between full gateway
+    /// refreshes (in seconds), 0 disables polling. (Default is 60)
+    #[serde(default = "default_gateway_change_poll_interval")]
+    pub gateway_change_poll_interval: u64,
     /// interval at which region params in the cache are refreshed
     #[serde(default = "default_region_params_refresh_interval")]
     pub region_params_refresh_interval: u64,
+    /// max distance in km between a beaconer and its witnesses, applied to
+    /// beaconers in regions without a limit of their own in iot config
+    #[serde(default = "default_max_witness_distance")]
+    pub max_witness_distance: u32,
+    /// cap in seconds on the witness window of any region in iot config,
+    /// beacons are held back until their entropy is this old so late
+    /// witnesses are verified along with them. never less than the entropy
+    /// lifespan
+    #[serde(default = "default_max_witness_window")]
+    pub max_witness_window: i64,
+    /// path loss model and margin used by the witness rssi check, selectable
+    /// per region
+    #[serde(default)]
+    pub path_loss: crate::path_loss::Settings,
+    /// max number of witnessing gateways of a beacon verified at once
+    #[serde(default = "default_witness_verification_concurrency")]
+    pub witness_verification_concurrency: usize,
+    /// time in seconds the witnesses of a beacon are verified within, those
+    /// not verified by then are retried on the next run
+    #[serde(default = "default_witness_verification_budget")]
+    pub witness_verification_budget: u64,
+    /// Age of the oldest beacon ready for verification beyond which the
+    /// verifier is in catchup (in seconds). (Default is 1800; 30 minutes)
+    #[serde(default = "default_catchup_threshold")]
+    pub catchup_threshold: i64,
+    /// Order beacons are verified in while in catchup, "oldest_first",
+    /// "newest_first" or "interleaved". (Default is "newest_first")
+    #[serde(default = "default_catchup_policy")]
+    pub catchup_policy: WorkOrder,
+    /// Added to the purger stale periods while in catchup (in seconds).
+    /// (Default is 7200; 2 hours)
+    #[serde(default = "default_catchup_stale_extension")]
+    pub catchup_stale_extension: i64,
+    /// Keys of trusted test gateways skipping density scaling and the max
+    /// witness distance check. Staging only, refused without a non
+    /// production VERIFY_PROFILE. (Default is none)
+    #[serde(default)]
+    pub test_gateways: Vec<String>,
 }
 
 // Default: 30 minutes
-fn default_gateway_refresh_interval() -> i64 {
+fn default_catchup_threshold() -> i64 {
     30 * 60
 }
 
+fn default_catchup_policy() -> WorkOrder {
+    WorkOrder::NewestFirst
+}
+
+// Default: 2 hours
+fn default_catchup_stale_extension() -> i64 {
+    2 * 60 * 60
+}
+
+// Default: 30 minutes
+fn default_gateway_refresh_interval() -> time::Duration {
+    time::Duration::from_secs(30 * 60)
+}
+
+// Default: 24 hours
+fn default_location_flap_window() -> i64 {
+    24 * 60 * 60
+}
+
+// Default: 3
+fn default_location_flap_threshold() -> u64 {
+    3
+}
+
+fn validate_test_gateways(
+    test_gateways: &[String],
+    is_production: bool,
+) -> Result<(), settings_loader::Error> {
+    let invalid = |reason: String| settings_loader::Error::Invalid {
+        key: "test_gateways".to_string(),
+        reason,
+    };
+    if test_gateways.is_empty() {
+        return Ok(());
+    }
+    if is_production {
+        return Err(invalid("not allowed in production settings".to_string()));
+    }
+    for key in test_gateways {
+        PublicKeyBinary::from_str(key).map_err(|err| invalid(format!("{key}: {err}")))?;
+    }
+    Ok(())
+}
+
+// Default: 1 hour
+fn default_gateway_reconcile_interval() -> Schedule {
+    Schedule::Every(time::Duration::from_secs(60 * 60))
+}
+
+// Default: 500
+fn default_gateway_reconcile_sample_size() -> usize {
+    500
+}
+
+// Default: 1 minute
+fn default_gateway_change_poll_interval() -> u64 {
+    60
+}
+
 // Default: 30 minutes
 fn default_region_params_refresh_interval() -> u64 {
     30 * 60
 }
 
+// Default: 100 km
+fn default_max_witness_distance() -> u32 {
+    100
+}
+
+// Default: 3 minutes, the entropy lifespan
+fn default_max_witness_window() -> i64 {
+    ENTROPY_LIFESPAN
+}
+
+// Default: 16 gateways
+fn default_witness_verification_concurrency() -> usize {
+    16
+}
+
+// Default: 30 seconds
+fn default_witness_verification_budget() -> u64 {
+    30
+}
+
 // Default: 60 minutes
 // this should be at least poc_loader_window_width * 2
 pub fn default_loader_window_max_lookback_age() -> i64 {
@@ -147,16 +431,95 @@ pub fn default_beacon_interval() -> i64 {
 }
 
 // Default: 30 min
-pub fn default_transmit_scale_interval() -> i64 {
-    1800
+pub fn default_transmit_scale_interval() -> Schedule {
+    Schedule::Every(time::Duration::from_secs(1800))
+}
+
+// Default: 1 hour
+fn default_reward_scale_snapshot_interval() -> u64 {
+    60 * 60
+}
+
+// Default: 24 hours
+fn default_reward_scale_window() -> time::Duration {
+    time::Duration::from_secs(24 * 60 * 60)
+}
+
+fn default_transmit_scale_witness_target() -> NonZeroU64 {
+    NonZeroU64::new(10).expect("positive witness target")
+}
+
+// Default: 7 days
+fn default_witness_quality_window() -> i64 {
+    7 * 24
+}
+
+fn default_index_pocs() -> bool {
+    true
+}
+
+// Default: 24 hours
+fn default_transmit_scale_witness_window() -> i64 {
+    24
+}
+
+fn default_transmit_scale_guard_changed_ratio() -> f64 {
+    0.25
+}
+
+fn default_transmit_scale_guard_scale_delta() -> f64 {
+    0.25
+}
+
+// Default: 24 hours
+fn default_beacon_cadence_check_interval() -> time::Duration {
+    time::Duration::from_secs(24 * 60 * 60)
+}
+
+// Default: 7 days
+fn default_beacon_cadence_window() -> i64 {
+    7 * 24
+}
+
+// Default: 5 minutes
+fn default_beacon_cadence_edge_margin() -> i64 {
+    5 * 60
 }
 
 pub fn default_log() -> String {
     "iot_verifier=debug,poc_store=info".to_string()
 }
 
-pub fn default_base_stale_period() -> i64 {
-    0
+pub fn default_base_stale_period() -> time::Duration {
+    time::Duration::ZERO
+}
+
+// Default: 45 minutes
+fn default_beacon_stale_period() -> time::Duration {
+    time::Duration::from_secs(45 * 60)
+}
+
+// Default: 45 minutes
+fn default_witness_stale_period() -> time::Duration {
+    time::Duration::from_secs(45 * 60)
+}
+
+// Default: 1 hour
+fn default_entropy_stale_period() -> time::Duration {
+    time::Duration::from_secs(60 * 60)
+}
+
+fn default_purger_delete_batch_size() -> usize {
+    1000
+}
+
+// Default: 35 minutes
+fn default_purger_schedule() -> Schedule {
+    Schedule::Every(time::Duration::from_secs(35 * 60))
+}
+
+fn default_report_offload_threshold() -> usize {
+    2048
 }
 
 fn default_reward_period() -> i64 {
@@ -197,20 +560,45 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "VERIFY_". For example
     /// "VERIFY_DATABASE_URL" will override the data base url.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        let loader = settings_loader::Loader::new("VERIFY");
+        let is_production = loader.is_production();
+        let settings: Self = loader.file(path).load()?;
+        settings.validate_test_gateways(is_production)?;
+        settings.validate_stale_periods()?;
+        Ok(settings)
+    }
 
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
+    fn validate_test_gateways(&self, is_production: bool) -> Result<(), settings_loader::Error> {
+        validate_test_gateways(&self.test_gateways, is_production)
+    }
+
+    /// Witnesses and entropy must outlive the beacons they are verified with
+    fn validate_stale_periods(&self) -> Result<(), settings_loader::Error> {
+        for (key, period) in [
+            ("witness_stale_period", self.witness_stale_period),
+            ("entropy_stale_period", self.entropy_stale_period),
+        ] {
+            if period < self.beacon_stale_period {
+                return Err(settings_loader::Error::Invalid {
+                    key: key.to_string(),
+                    reason: format!(
+                        "{} is shorter than beacon_stale_period of {}",
+                        humantime::format_duration(period),
+                        humantime::format_duration(self.beacon_stale_period)
+                    ),
+                });
+            }
         }
-        // Add in settings from the environment (with a prefix of VERIFY)
-        // Eg.. `INJECT_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("VERIFY").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+        Ok(())
+    }
+
+    /// The trusted test gateways, validated when the settings are loaded
+    pub fn test_gateways(&self) -> HashSet<PublicKeyBinary> {
+        self.test_gateways
+            .iter()
+            .filter_map(|key| PublicKeyBinary::from_str(key).ok())
+            .collect()
     }
 
     pub fn reward_offset_duration(&self) -> Duration {
@@ -241,12 +629,32 @@ impl Settings {
         Duration::seconds(self.loader_window_max_lookback_age)
     }
 
+    pub fn max_witness_window(&self) -> Duration {
+        Duration::seconds(self.max_witness_window.max(ENTROPY_LIFESPAN))
+    }
+
+    pub fn witness_verification_budget(&self) -> time::Duration {
+        time::Duration::from_secs(self.witness_verification_budget)
+    }
+
     pub fn entropy_lifespan(&self) -> Duration {
         Duration::seconds(self.entropy_lifespan)
     }
 
     pub fn base_stale_period(&self) -> Duration {
-        Duration::seconds(self.base_stale_period)
+        chrono_duration(self.base_stale_period)
+    }
+
+    pub fn beacon_stale_period(&self) -> Duration {
+        chrono_duration(self.beacon_stale_period)
+    }
+
+    pub fn witness_stale_period(&self) -> Duration {
+        chrono_duration(self.witness_stale_period)
+    }
+
+    pub fn entropy_stale_period(&self) -> Duration {
+        chrono_duration(self.entropy_stale_period)
     }
 
     pub fn entropy_interval(&self) -> Duration {
@@ -255,10 +663,70 @@ impl Settings {
     pub fn packet_interval(&self) -> Duration {
         Duration::seconds(self.packet_interval)
     }
-    pub fn gateway_refresh_interval(&self) -> Duration {
-        Duration::seconds(self.gateway_refresh_interval)
+    pub fn location_flap_window(&self) -> Duration {
+        Duration::seconds(self.location_flap_window)
+    }
+    pub fn gateway_reconcile_jitter(&self) -> time::Duration {
+        time::Duration::from_secs(self.gateway_reconcile_jitter)
+    }
+    pub fn purger_jitter(&self) -> time::Duration {
+        time::Duration::from_secs(self.purger_jitter)
+    }
+    pub fn transmit_scale_jitter(&self) -> time::Duration {
+        time::Duration::from_secs(self.transmit_scale_jitter)
+    }
+    pub fn gateway_change_poll_interval(&self) -> Option<time::Duration> {
+        (self.gateway_change_poll_interval > 0)
+            .then(|| time::Duration::from_secs(self.gateway_change_poll_interval))
     }
     pub fn region_params_refresh_interval(&self) -> time::Duration {
         time::Duration::from_secs(self.region_params_refresh_interval)
     }
+    pub fn reward_scale_snapshot_interval(&self) -> time::Duration {
+        time::Duration::from_secs(self.reward_scale_snapshot_interval)
+    }
+    pub fn reward_scale_window(&self) -> Duration {
+        chrono_duration(self.reward_scale_window)
+    }
+    pub fn witness_quality_window(&self) -> Duration {
+        Duration::hours(self.witness_quality_window)
+    }
+    pub fn transmit_scale_witness_window(&self) -> Duration {
+        Duration::hours(self.transmit_scale_witness_window)
+    }
+    pub fn transmit_scale_guard_scale_delta(&self) -> Decimal {
+        Decimal::from_f64(self.transmit_scale_guard_scale_delta).unwrap_or(Decimal::ONE)
+    }
+    pub fn beacon_cadence_window(&self) -> Duration {
+        Duration::hours(self.beacon_cadence_window)
+    }
+    pub fn beacon_cadence_edge_margin(&self) -> Duration {
+        Duration::seconds(self.beacon_cadence_edge_margin)
+    }
+    pub fn catchup_threshold(&self) -> Duration {
+        Duration::seconds(self.catchup_threshold)
+    }
+    pub fn catchup_stale_extension(&self) -> Duration {
+        Duration::seconds(self.catchup_stale_extension)
+    }
+}
+
+/// A duration setting as a chrono duration, saturating rather than failing on
+/// durations chrono can't hold
+fn chrono_duration(duration: time::Duration) -> Duration {
+    Duration::from_std(duration).unwrap_or_else(|_| Duration::max_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gateways_are_refused_in_production() {
+        let gateways = vec!["112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf".to_string()];
+        assert!(validate_test_gateways(&[], true).is_ok());
+        assert!(validate_test_gateways(&gateways, false).is_ok());
+        assert!(validate_test_gateways(&gateways, true).is_err());
+        assert!(validate_test_gateways(&["not a key".to_string()], false).is_err());
+    }
 }
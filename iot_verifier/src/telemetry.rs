@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use file_store::iot_gateway_reconciliation::GatewayDrift;
 use sqlx::{Pool, Postgres};
 
 use crate::{poc_report::Report, rewarder};
@@ -16,6 +17,23 @@ const LOADER_DROPPED_WITNESS_COUNTER: &str =
 const BEACON_GUAGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "num_beacons");
 const INVALID_WITNESS_COUNTER: &str =
     concat!(env!("CARGO_PKG_NAME"), "_", "invalid_witness_report");
+const BAD_RSSI_WITNESS_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_", "bad_rssi_witness");
+const BEACON_CADENCE_FLAGGED_GAUGE: &str =
+    concat!(env!("CARGO_PKG_NAME"), "_", "beacon_cadence_flagged");
+const CATCHUP_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "catchup");
+const VERIFICATION_LAG_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "verification_lag");
+const PLAN_VIOLATION_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_", "plan_violation");
+const BEFORE_ONBOARDING_COUNTER: &str =
+    concat!(env!("CARGO_PKG_NAME"), "_", "report_before_onboarding");
+const LOCATION_CHANGE_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_", "location_changes");
+const LOCATION_FLAPS_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "location_flaps");
+const FLAPPING_GATEWAYS_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "flapping_gateways");
+const GATEWAYS_RECONCILED_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "gateways_reconciled");
+const GATEWAY_DRIFT_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "gateway_drift");
+const GATEWAY_CHANGES_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_", "gateway_changes");
+const REWARD_HELD_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "reward_held");
+const SCALING_MAP_REFUSED_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "scaling_map_refused");
+const PURGER_DRY_RUN_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "purger_dry_run_stale");
 const LAST_REWARDED_END_TIME: &str = "last_rewarded_end_time";
 
 pub async fn initialize(db: &Pool<Postgres>) -> anyhow::Result<()> {
@@ -61,14 +79,79 @@ pub fn decrement_num_beacons() {
     metrics::decrement_gauge!(BEACON_GUAGE, 1.0)
 }
 
+pub fn decrement_num_beacons_by(count: u64) {
+    metrics::decrement_gauge!(BEACON_GUAGE, count as f64)
+}
+
 pub fn increment_invalid_witnesses(labels: &[(&'static str, &'static str)]) {
     metrics::increment_counter!(INVALID_WITNESS_COUNTER, labels);
 }
 
+pub fn count_bad_rssi_witness(model: &'static str) {
+    metrics::increment_counter!(BAD_RSSI_WITNESS_COUNTER, "path_loss_model" => model);
+}
+
+pub fn count_plan_violation(report_type: &'static str, reason: &'static str) {
+    metrics::increment_counter!(
+        PLAN_VIOLATION_COUNTER,
+        "report_type" => report_type,
+        "reason" => reason
+    );
+}
+
+pub fn count_report_before_onboarding(report_type: &'static str) {
+    metrics::increment_counter!(BEFORE_ONBOARDING_COUNTER, "report_type" => report_type);
+}
+
 pub fn last_rewarded_end_time(datetime: DateTime<Utc>) {
     metrics::gauge!(LAST_REWARDED_END_TIME, datetime.timestamp() as f64);
 }
 
+pub fn reward_held(held: bool) {
+    metrics::gauge!(REWARD_HELD_GAUGE, if held { 1.0 } else { 0.0 });
+}
+
+pub fn beacon_cadence_flagged(at_interval_edge: usize, in_bursts: usize) {
+    metrics::gauge!(
+        BEACON_CADENCE_FLAGGED_GAUGE,
+        at_interval_edge as f64,
+        "reason" => "interval_edge"
+    );
+    metrics::gauge!(BEACON_CADENCE_FLAGGED_GAUGE, in_bursts as f64, "reason" => "burst");
+}
+
+pub fn scaling_map_refused(refused: bool) {
+    metrics::gauge!(SCALING_MAP_REFUSED_GAUGE, if refused { 1.0 } else { 0.0 });
+}
+
+pub fn purger_dry_run_stale(kind: &'static str, count: u64) {
+    metrics::gauge!(PURGER_DRY_RUN_GAUGE, count as f64, "type" => kind);
+}
+
+pub fn catchup(active: bool, lag: Duration) {
+    metrics::gauge!(CATCHUP_GAUGE, if active { 1.0 } else { 0.0 });
+    metrics::gauge!(VERIFICATION_LAG_GAUGE, lag.num_seconds() as f64);
+}
+
+pub fn location_changes(changes: u64, flaps: u64, flapping_gateways: usize) {
+    metrics::counter!(LOCATION_CHANGE_COUNTER, changes);
+    metrics::gauge!(LOCATION_FLAPS_GAUGE, flaps as f64);
+    metrics::gauge!(FLAPPING_GATEWAYS_GAUGE, flapping_gateways as f64);
+}
+
+pub fn gateway_changes(changed: u64) {
+    metrics::counter!(GATEWAY_CHANGES_COUNTER, changed);
+}
+
+pub fn gateway_drift(sampled: usize, drifted: &[GatewayDrift]) {
+    let count = |field: fn(&GatewayDrift) -> bool| drifted.iter().filter(|d| field(d)).count();
+    metrics::gauge!(GATEWAYS_RECONCILED_GAUGE, sampled as f64);
+    metrics::gauge!(GATEWAY_DRIFT_GAUGE, count(|d| d.missing) as f64, "field" => "missing");
+    metrics::gauge!(GATEWAY_DRIFT_GAUGE, count(|d| d.location) as f64, "field" => "location");
+    metrics::gauge!(GATEWAY_DRIFT_GAUGE, count(|d| d.gain) as f64, "field" => "gain");
+    metrics::gauge!(GATEWAY_DRIFT_GAUGE, count(|d| d.region) as f64, "field" => "region");
+}
+
 #[derive(Default)]
 pub struct LoaderMetricTracker {
     beacons: RefCell<u64>,
@@ -0,0 +1,57 @@
+//! Trusted test gateways for staging deployments, refused in production
+//! settings.
+
+use crate::Settings;
+use helium_crypto::PublicKeyBinary;
+use std::{collections::HashSet, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BypassedCheck {
+    DensityScaling,
+    MaxDistance,
+}
+
+impl BypassedCheck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DensityScaling => "density_scaling",
+            Self::MaxDistance => "max_distance",
+        }
+    }
+}
+
+/// A check skipped for a test gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bypass {
+    pub pub_key: PublicKeyBinary,
+    pub check: BypassedCheck,
+}
+
+/// The `test_gateways` allow-list. Its gateways skip density scaling, getting
+/// a hex scale of 1, and the max witness distance check, as beaconer or as
+/// witness, so staging produces predictable outputs. Every skipped check of a
+/// valid poc is written out as an `iot_verification_bypass` record
+#[derive(Debug, Clone, Default)]
+pub struct TestGateways {
+    gateways: Arc<HashSet<PublicKeyBinary>>,
+}
+
+impl TestGateways {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            gateways: Arc::new(settings.test_gateways()),
+        }
+    }
+
+    pub fn contains(&self, pub_key: &PublicKeyBinary) -> bool {
+        self.gateways.contains(pub_key)
+    }
+}
+
+impl FromIterator<PublicKeyBinary> for TestGateways {
+    fn from_iter<I: IntoIterator<Item = PublicKeyBinary>>(iter: I) -> Self {
+        Self {
+            gateways: Arc::new(iter.into_iter().collect()),
+        }
+    }
+}
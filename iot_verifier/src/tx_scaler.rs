@@ -1,23 +1,68 @@
 use crate::{
     gateway_updater::MessageReceiver,
-    hex_density::{compute_hex_density_map, GlobalHexMap, HexDensityMap, SharedHexDensityMap},
+    hex_density::{
+        compute_hex_density_map, GlobalHexMap, HexDensityMap, SharedHexDensityMap, WEIGHT_UNIT,
+    },
     last_beacon::LastBeacon,
-    Settings,
+    reward_scale, telemetry, witness_counts, Settings,
 };
 use chrono::{DateTime, Duration, Utc};
+use db_store::FeatureFlags;
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_hex_density_snapshot::{HexDensityScale, HexDensitySnapshot, HexDensitySnapshotV1},
+    iot_hex_scale_comparison::{HexScaleComparison, HexScaleComparisonV1, HexScales},
+};
 use helium_crypto::PublicKeyBinary;
+use rust_decimal::Decimal;
+use serde::Deserialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use task_scheduler::{Schedule, Scheduler};
+use tokio::time;
 
 // The number in minutes within which the gateway has registered a beacon
 // to the oracle for inclusion in transmit scaling density calculations
 const HIP_17_INTERACTIVITY_LIMIT: i64 = 3600;
 
+/// Hexes per record of a scaling map snapshot, keeping records well within
+/// the frame length of file sinks
+const SNAPSHOT_HEXES_PER_RECORD: usize = 100_000;
+
+/// Feature flag swapping in refreshed scaling maps refused by the guard
+pub const FORCE_SCALING_MAP_SWAP_FLAG: &str = "iot_verifier.force_scaling_map_swap";
+
+/// What an interactive gateway counts for in the density of its hexes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    /// Every gateway counts as one
+    #[default]
+    Presence,
+    /// Gateways count in proportion to their recent valid witnesses, up to
+    /// one at the witness target
+    Witnesses,
+}
+
 pub struct Server {
     hex_density_map: SharedHexDensityMap,
     pool: PgPool,
     refresh_offset: Duration,
     gateway_cache_receiver: MessageReceiver,
+    snapshot_interval: time::Duration,
+    /// Schedule of the refreshes of the map between gateway changes
+    refresh_schedule: Schedule,
+    refresh_jitter: time::Duration,
+    weighting: Weighting,
+    witness_target: u64,
+    witness_window: Duration,
+    /// Sink of the map comparisons, while comparing
+    comparison_sink: Option<FileSinkClient>,
+    /// Sink of the snapshots of every map swapped in
+    snapshot_sink: FileSinkClient,
+    guard_changed_ratio: f64,
+    guard_scale_delta: Decimal,
+    feature_flags: FeatureFlags,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -26,6 +71,12 @@ pub enum TxScalerError {
     DbConnect(#[from] db_store::Error),
     #[error("txn scaler error retrieving recent activity")]
     RecentActivity(#[from] sqlx::Error),
+    #[error("tx scaler error saving reward scale snapshot")]
+    Snapshot(#[source] sqlx::Error),
+    #[error("tx scaler error retrieving witness counts")]
+    WitnessCounts(#[source] sqlx::Error),
+    #[error("tx scaler error writing map comparison or snapshot")]
+    FileSink(#[from] file_store::Error),
 }
 
 impl Server {
@@ -33,12 +84,28 @@ impl Server {
         settings: &Settings,
         pool: PgPool,
         gateway_cache_receiver: MessageReceiver,
+        comparison_sink: FileSinkClient,
+        snapshot_sink: FileSinkClient,
+        feature_flags: FeatureFlags,
     ) -> Result<Self, TxScalerError> {
         let mut server = Self {
             hex_density_map: SharedHexDensityMap::new(),
             pool,
             refresh_offset: settings.loader_window_max_lookback_age(),
             gateway_cache_receiver,
+            snapshot_interval: settings.reward_scale_snapshot_interval(),
+            refresh_schedule: settings.transmit_scale_interval.clone(),
+            refresh_jitter: settings.transmit_scale_jitter(),
+            weighting: settings.transmit_scale_weighting,
+            witness_target: settings.transmit_scale_witness_target.get(),
+            witness_window: settings.transmit_scale_witness_window(),
+            comparison_sink: settings
+                .transmit_scale_comparison
+                .then_some(comparison_sink),
+            snapshot_sink,
+            guard_changed_ratio: settings.transmit_scale_guard_changed_ratio,
+            guard_scale_delta: settings.transmit_scale_guard_scale_delta(),
+            feature_flags,
         };
 
         server.refresh_scaling_map().await?;
@@ -50,9 +117,23 @@ impl Server {
         self.hex_density_map.clone()
     }
 
+    pub(crate) fn shared_hex_density_map(&self) -> SharedHexDensityMap {
+        self.hex_density_map.clone()
+    }
+
     pub async fn run(&mut self, shutdown: &triggered::Listener) -> Result<(), TxScalerError> {
         tracing::info!("density_scaler: starting transmit scaler process");
 
+        let mut snapshot_timer = time::interval(self.snapshot_interval);
+        snapshot_timer.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        // the map was just refreshed, the first fallback refresh waits for
+        // its schedule
+        let mut refresh_timer = Scheduler::new(
+            "tx-scaler-refresh",
+            self.refresh_schedule.clone(),
+            self.refresh_jitter,
+        );
+
         loop {
             if shutdown.is_triggered() {
                 tracing::info!("density_scaler: stopping transmit scaler");
@@ -61,6 +142,14 @@ impl Server {
 
             tokio::select! {
                 _ = self.gateway_cache_receiver.changed() => self.refresh_scaling_map().await?,
+                _ = snapshot_timer.tick() => {
+                    // a missed snapshot only leaves a gap in the averaging
+                    // window, the next one is taken on schedule
+                    if let Err(err) = self.snapshot_reward_scales().await {
+                        tracing::warn!(?err, "density_scaler: failed to snapshot reward scales");
+                    }
+                }
+                _ = refresh_timer.tick() => self.refresh_scaling_map().await?,
                 _ = shutdown.clone() => return Ok(()),
             }
         }
@@ -69,26 +158,52 @@ impl Server {
     pub async fn refresh_scaling_map(&mut self) -> Result<(), TxScalerError> {
         let refresh_start = Utc::now() - self.refresh_offset;
         tracing::info!("density_scaler: generating hex scaling map, starting at {refresh_start:?}");
-        let mut global_map = GlobalHexMap::new();
         let active_gateways = self
             .gateways_recent_activity(refresh_start)
             .await
             .map_err(sqlx::Error::from)?;
-        for k in active_gateways.keys() {
-            let pubkey = PublicKeyBinary::from(k.clone());
-            if let Some(gateway_info) = self.gateway_cache_receiver.borrow().get(&pubkey) {
-                if let Some(metadata) = &gateway_info.metadata {
-                    global_map.increment_unclipped(metadata.location)
-                }
-            }
+        let locations: Vec<(PublicKeyBinary, u64)> = {
+            let gateways = self.gateway_cache_receiver.borrow();
+            active_gateways
+                .into_keys()
+                .filter_map(|k| {
+                    let pubkey = PublicKeyBinary::from(k);
+                    let location = gateways.get(&pubkey)?.metadata.as_ref()?.location;
+                    Some((pubkey, location))
+                })
+                .collect()
+        };
+
+        let comparing = self.comparison_sink.is_some();
+        let presence_map = (self.weighting == Weighting::Presence || comparing)
+            .then(|| density_map(&locations, None));
+        let witness_map = if self.weighting == Weighting::Witnesses || comparing {
+            let weights = self.witness_weights().await?;
+            Some(density_map(&locations, Some(&weights)))
+        } else {
+            None
+        };
+        if let (Some(sink), Some(presence_map), Some(witness_map)) =
+            (&self.comparison_sink, &presence_map, &witness_map)
+        {
+            let weighted = self.weighting == Weighting::Witnesses;
+            write_comparison(sink, weighted, presence_map, witness_map).await?;
+        }
+        // the map of the configured weighting is always computed
+        let new_map = match self.weighting {
+            Weighting::Presence => presence_map,
+            Weighting::Witnesses => witness_map,
         }
-        global_map.reduce_global();
-        let new_map = compute_hex_density_map(&global_map);
+        .unwrap_or_default();
         tracing::info!(
             "density_scaler: scaling factor map entries: {}",
             new_map.len()
         );
+        if !self.swap_allowed(&new_map).await {
+            return Ok(());
+        }
         self.hex_density_map.swap(new_map).await;
+        self.write_snapshot(refresh_start).await?;
         tracing::info!(
             "density_scaler: generating hex scaling map, completed at {:?}",
             Utc::now()
@@ -96,6 +211,122 @@ impl Server {
         Ok(())
     }
 
+    /// Whether `new_map` may replace the current map. A map changing the
+    /// scale of more than the guard ratio of the hexes at once is more likely
+    /// the result of a bug or a bad metadata sync than of the network, and is
+    /// refused unless forced with [`FORCE_SCALING_MAP_SWAP_FLAG`]
+    async fn swap_allowed(&self, new_map: &HashMap<u64, Decimal>) -> bool {
+        let (changed, hexes) = self
+            .hex_density_map
+            .count_changed(new_map, self.guard_scale_delta)
+            .await;
+        let ratio = if hexes == 0 {
+            0.0
+        } else {
+            changed as f64 / hexes as f64
+        };
+        if ratio > self.guard_changed_ratio {
+            if !self.feature_flags.is_enabled(FORCE_SCALING_MAP_SWAP_FLAG) {
+                tracing::error!(
+                    changed,
+                    hexes,
+                    "density_scaler: refusing scaling map changing the scale of too many hexes, \
+                     keeping the current map"
+                );
+                telemetry::scaling_map_refused(true);
+                return false;
+            }
+            tracing::warn!(
+                changed,
+                hexes,
+                "density_scaler: swapping scaling map refused by the guard, forced by feature flag"
+            );
+        }
+        telemetry::scaling_map_refused(false);
+        true
+    }
+
+    /// Record the map just swapped in, refreshed with the activity of the
+    /// gateways before `refresh_start`, so the scales rewards were computed
+    /// with can be audited and replayed
+    async fn write_snapshot(&self, refresh_start: DateTime<Utc>) -> Result<(), TxScalerError> {
+        let (map, refreshed_at) = self.hex_density_map.snapshot().await;
+        let timestamp = refreshed_at.unwrap_or_else(Utc::now);
+        let active_since = refresh_start - Duration::minutes(HIP_17_INTERACTIVITY_LIMIT);
+        let weighted = self.weighting == Weighting::Witnesses;
+        let scales: Vec<HexDensityScale> = map
+            .into_iter()
+            .map(|(hex, scale)| HexDensityScale { hex, scale })
+            .collect();
+        for chunk in scales.chunks(SNAPSHOT_HEXES_PER_RECORD) {
+            let snapshot = HexDensitySnapshot {
+                timestamp,
+                active_since,
+                weighted,
+                scales: chunk.to_vec(),
+            };
+            self.snapshot_sink
+                .write(HexDensitySnapshotV1::from(snapshot), [])
+                .await?;
+        }
+        self.snapshot_sink.commit().await?;
+        tracing::info!(
+            hexes = scales.len(),
+            "density_scaler: wrote hex density map snapshot"
+        );
+        Ok(())
+    }
+
+    /// Weight of every gateway by its valid witnesses over the witness window,
+    /// purging the counts fallen out of it
+    async fn witness_weights(&self) -> Result<HashMap<PublicKeyBinary, u64>, TxScalerError> {
+        let since = Utc::now() - self.witness_window;
+        witness_counts::purge(&self.pool, since)
+            .await
+            .map_err(TxScalerError::WitnessCounts)?;
+        let counts = witness_counts::counts_since(&self.pool, since)
+            .await
+            .map_err(TxScalerError::WitnessCounts)?;
+        Ok(counts
+            .into_iter()
+            .map(|(pubkey, witnesses)| {
+                let weight = witnesses.min(self.witness_target) * WEIGHT_UNIT / self.witness_target;
+                (pubkey, weight)
+            })
+            .collect())
+    }
+
+    /// Persist the current scale of every interactive gateway so the rewarder
+    /// can average it across the epoch
+    pub async fn snapshot_reward_scales(&self) -> Result<(), TxScalerError> {
+        let now = Utc::now();
+        let active_gateways = self.gateways_recent_activity(now).await?;
+        let mut scales = Vec::with_capacity(active_gateways.len());
+        for k in active_gateways.into_keys() {
+            let pubkey = PublicKeyBinary::from(k);
+            let location = self
+                .gateway_cache_receiver
+                .borrow()
+                .get(&pubkey)
+                .and_then(|gateway_info| gateway_info.metadata.as_ref())
+                .map(|metadata| metadata.location);
+            if let Some(location) = location {
+                if let Some(scale) = self.hex_density_map.get(location).await {
+                    scales.push((pubkey, scale));
+                }
+            }
+        }
+        tracing::info!(
+            "density_scaler: saving reward scale snapshot of {} gateways",
+            scales.len()
+        );
+        let mut transaction = self.pool.begin().await.map_err(TxScalerError::Snapshot)?;
+        reward_scale::save_snapshot(&mut transaction, now, scales)
+            .await
+            .map_err(TxScalerError::Snapshot)?;
+        transaction.commit().await.map_err(TxScalerError::Snapshot)
+    }
+
     async fn gateways_recent_activity(
         &self,
         now: DateTime<Utc>,
@@ -110,3 +341,61 @@ impl Server {
         )
     }
 }
+
+/// Density scaling map of the gateways at `locations`, counting each by its
+/// presence or, given `weights`, by its weight. Gateways without a weight
+/// count for nothing.
+fn density_map(
+    locations: &[(PublicKeyBinary, u64)],
+    weights: Option<&HashMap<PublicKeyBinary, u64>>,
+) -> HashMap<u64, Decimal> {
+    let mut global_map = match weights {
+        Some(_) => GlobalHexMap::weighted(),
+        None => GlobalHexMap::new(),
+    };
+    for (pubkey, location) in locations {
+        match weights {
+            Some(weights) => {
+                let weight = weights.get(pubkey).copied().unwrap_or_default();
+                global_map.increment_unclipped_by(*location, weight)
+            }
+            None => global_map.increment_unclipped(*location),
+        }
+    }
+    global_map.reduce_global();
+    compute_hex_density_map(&global_map)
+}
+
+async fn write_comparison(
+    sink: &FileSinkClient,
+    weighted: bool,
+    presence_map: &HashMap<u64, Decimal>,
+    witness_map: &HashMap<u64, Decimal>,
+) -> Result<(), TxScalerError> {
+    let differing: Vec<HexScales> = presence_map
+        .iter()
+        .filter_map(|(hex, presence_scale)| {
+            let weighted_scale = witness_map.get(hex)?;
+            (presence_scale != weighted_scale).then_some(HexScales {
+                hex: *hex,
+                presence_scale: *presence_scale,
+                weighted_scale: *weighted_scale,
+            })
+        })
+        .collect();
+    tracing::info!(
+        hexes = presence_map.len(),
+        differing = differing.len(),
+        "density_scaler: compared presence and witness weighted maps"
+    );
+    let comparison = HexScaleComparison {
+        timestamp: Utc::now(),
+        weighted,
+        hexes: presence_map.len() as u64,
+        differing,
+    };
+    sink.write(HexScaleComparisonV1::from(comparison), [])
+        .await?;
+    sink.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Hourly counts of the valid witnesses of every gateway, weighting transmit
+//! scaling by witnesses.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use file_store::iot_valid_poc::IotPoc;
+use futures::stream::TryStreamExt;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::VerificationStatus;
+use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
+
+/// Count the valid witnesses of `poc`, selected for rewards or not, against
+/// the hour its beacon was received in
+pub async fn record(db: &mut Transaction<'_, Postgres>, poc: &IotPoc) -> Result<(), sqlx::Error> {
+    let received = poc.beacon_report.received_timestamp;
+    let hour = received
+        .duration_trunc(Duration::hours(1))
+        .unwrap_or(received);
+    let witnesses = poc
+        .selected_witnesses
+        .iter()
+        .chain(&poc.unselected_witnesses)
+        .filter(|witness| witness.status == VerificationStatus::Valid);
+    for witness in witnesses {
+        sqlx::query(
+            r#"
+            insert into valid_witness_counts (hotspot_key, hour, witnesses)
+            values ($1, $2, 1)
+            on conflict (hotspot_key, hour) do update set
+                witnesses = valid_witness_counts.witnesses + 1
+            "#,
+        )
+        .bind(&witness.report.pub_key)
+        .bind(hour)
+        .execute(&mut *db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Valid witnesses per gateway counted since `since`
+pub async fn counts_since(
+    db: impl sqlx::PgExecutor<'_>,
+    since: DateTime<Utc>,
+) -> Result<HashMap<PublicKeyBinary, u64>, sqlx::Error> {
+    sqlx::query_as::<_, (PublicKeyBinary, i64)>(
+        r#"
+        select hotspot_key, sum(witnesses)::bigint from valid_witness_counts
+        where hour >= $1
+        group by hotspot_key
+        "#,
+    )
+    .bind(since)
+    .fetch(db)
+    .map_ok(|(hotspot_key, witnesses)| (hotspot_key, witnesses as u64))
+    .try_collect()
+    .await
+}
+
+pub async fn purge(
+    db: impl sqlx::PgExecutor<'_>,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    Ok(
+        sqlx::query("delete from valid_witness_counts where hour < $1")
+            .bind(before)
+            .execute(db)
+            .await?
+            .rows_affected(),
+    )
+}
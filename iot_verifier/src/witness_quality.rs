@@ -0,0 +1,260 @@
+//! Rolling witness quality of every gateway, written by the rewarder to
+//! `iot_witness_quality` files and served by the witness quality api.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use file_store::{iot_valid_poc::IotPoc, iot_witness_quality::WitnessQualityReport};
+use futures::stream::TryStreamExt;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::VerificationStatus;
+use sqlx::{Postgres, Transaction};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+};
+
+/// Outcome of the witnesses verified valid, others are counted by the name
+/// of their invalid reason
+const VALID_OUTCOME: &str = "valid";
+
+/// Count the witnesses of `poc`, selected for rewards or not, by outcome
+/// against the hour its beacon was received in
+pub async fn record(db: &mut Transaction<'_, Postgres>, poc: &IotPoc) -> Result<(), sqlx::Error> {
+    let received = poc.beacon_report.received_timestamp;
+    let hour = received
+        .duration_trunc(Duration::hours(1))
+        .unwrap_or(received);
+    let mut counts: HashMap<(&PublicKeyBinary, &str), i64> = HashMap::new();
+    for witness in poc
+        .selected_witnesses
+        .iter()
+        .chain(&poc.unselected_witnesses)
+    {
+        let outcome = match witness.status {
+            VerificationStatus::Valid => VALID_OUTCOME,
+            VerificationStatus::Invalid => witness.invalid_reason.as_str_name(),
+        };
+        *counts
+            .entry((&witness.report.pub_key, outcome))
+            .or_default() += 1;
+    }
+    add_outcomes(db, hour, counts).await
+}
+
+/// Add the witness counts of each gateway and outcome to `hour` in a single
+/// statement, which can't update a row twice so the counts are summed first
+async fn add_outcomes(
+    db: &mut Transaction<'_, Postgres>,
+    hour: DateTime<Utc>,
+    counts: HashMap<(&PublicKeyBinary, &str), i64>,
+) -> Result<(), sqlx::Error> {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let mut query_builder: sqlx::QueryBuilder<Postgres> = sqlx::QueryBuilder::new(
+        " insert into witness_outcomes (hotspot_key, hour, outcome, witnesses) ",
+    );
+    query_builder.push_values(counts, |mut row, ((hotspot_key, outcome), witnesses)| {
+        row.push_bind(hotspot_key)
+            .push_bind(hour)
+            .push_bind(outcome)
+            .push_bind(witnesses);
+    });
+    query_builder.push(
+        r#"
+        on conflict (hotspot_key, hour, outcome) do update set
+            witnesses = witness_outcomes.witnesses + excluded.witnesses
+        "#,
+    );
+    query_builder.build().execute(&mut *db).await?;
+    Ok(())
+}
+
+/// The share of the witnesses of a gateway verified valid over a window, along
+/// with the mix of invalid reasons
+#[derive(Debug, Clone, PartialEq)]
+pub struct WitnessQuality {
+    pub hotspot_key: PublicKeyBinary,
+    pub valid: u64,
+    pub invalid: u64,
+    /// Invalid witnesses per invalid reason
+    pub reasons: BTreeMap<String, u64>,
+}
+
+impl WitnessQuality {
+    fn new(hotspot_key: PublicKeyBinary) -> Self {
+        Self {
+            hotspot_key,
+            valid: 0,
+            invalid: 0,
+            reasons: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, outcome: String, witnesses: u64) {
+        if outcome == VALID_OUTCOME {
+            self.valid += witnesses;
+        } else {
+            self.invalid += witnesses;
+            *self.reasons.entry(outcome).or_default() += witnesses;
+        }
+    }
+
+    /// Share of the witnesses verified valid, 0 without witnesses
+    pub fn score(&self) -> f64 {
+        match self.valid + self.invalid {
+            0 => 0.0,
+            total => self.valid as f64 / total as f64,
+        }
+    }
+
+    pub fn into_report(self, window: &Range<DateTime<Utc>>) -> WitnessQualityReport {
+        WitnessQualityReport {
+            score: self.score(),
+            pub_key: self.hotspot_key,
+            valid: self.valid,
+            invalid: self.invalid,
+            reasons: self.reasons,
+            window_start: window.start,
+            window_end: window.end,
+        }
+    }
+}
+
+/// Witness quality of every gateway with witnesses counted within `window`,
+/// or of `hotspot_key` only
+pub async fn qualities(
+    db: impl sqlx::PgExecutor<'_>,
+    window: &Range<DateTime<Utc>>,
+    hotspot_key: Option<&PublicKeyBinary>,
+) -> Result<Vec<WitnessQuality>, sqlx::Error> {
+    let mut qualities: HashMap<PublicKeyBinary, WitnessQuality> = HashMap::new();
+    let mut outcomes = sqlx::query_as::<_, (PublicKeyBinary, String, i64)>(
+        r#"
+        select hotspot_key, outcome, sum(witnesses)::bigint from witness_outcomes
+        where hour >= $1 and hour < $2 and ($3::bytea is null or hotspot_key = $3)
+        group by hotspot_key, outcome
+        "#,
+    )
+    .bind(window.start)
+    .bind(window.end)
+    .bind(hotspot_key)
+    .fetch(db);
+    while let Some((hotspot_key, outcome, witnesses)) = outcomes.try_next().await? {
+        qualities
+            .entry(hotspot_key.clone())
+            .or_insert_with(|| WitnessQuality::new(hotspot_key))
+            .add(outcome, witnesses as u64);
+    }
+    Ok(qualities.into_values().collect())
+}
+
+pub async fn purge(
+    db: impl sqlx::PgExecutor<'_>,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    Ok(sqlx::query("delete from witness_outcomes where hour < $1")
+        .bind(before)
+        .execute(db)
+        .await?
+        .rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sqlx::PgPool;
+
+    fn hotspot_key(key: &str) -> PublicKeyBinary {
+        key.parse().expect("failed gateway parse")
+    }
+
+    #[sqlx::test]
+    async fn outcomes_are_summed_per_gateway_within_the_window(pool: PgPool) {
+        let first = hotspot_key("112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6");
+        let second = hotspot_key("11sctWiP9r5wDJVuDe1Th4XSL2vaawaLLSQF8f8iokAoMAJHxqp");
+        let hour = Utc.with_ymd_and_hms(2023, 5, 1, 10, 0, 0).unwrap();
+        let next_hour = hour + Duration::hours(1);
+        let window = hour..hour + Duration::hours(2);
+
+        let mut txn = pool.begin().await.expect("begin");
+        add_outcomes(
+            &mut txn,
+            hour,
+            HashMap::from([
+                ((&first, VALID_OUTCOME), 3),
+                ((&first, "too_close"), 1),
+                ((&second, VALID_OUTCOME), 2),
+            ]),
+        )
+        .await
+        .expect("first outcomes");
+        add_outcomes(
+            &mut txn,
+            hour,
+            HashMap::from([((&first, VALID_OUTCOME), 2), ((&first, "too_close"), 1)]),
+        )
+        .await
+        .expect("outcomes added to the counted ones");
+        add_outcomes(
+            &mut txn,
+            next_hour,
+            HashMap::from([((&second, "bad_rssi"), 2)]),
+        )
+        .await
+        .expect("next hour outcomes");
+        add_outcomes(
+            &mut txn,
+            window.end,
+            HashMap::from([((&first, VALID_OUTCOME), 5)]),
+        )
+        .await
+        .expect("outcomes after the window");
+        txn.commit().await.expect("commit");
+
+        let counted = qualities(&pool, &window, None).await.expect("qualities");
+        let first_quality = counted
+            .iter()
+            .find(|quality| quality.hotspot_key == first)
+            .expect("first quality");
+        assert_eq!(
+            (5, 2, 2),
+            (
+                first_quality.valid,
+                first_quality.invalid,
+                first_quality.reasons["too_close"]
+            )
+        );
+        let second_quality = qualities(&pool, &window, Some(&second))
+            .await
+            .expect("second quality");
+        assert_eq!(1, second_quality.len());
+        assert_eq!((2, 2), (second_quality[0].valid, second_quality[0].invalid));
+        assert_eq!(2, counted.len());
+
+        assert_eq!(3, purge(&pool, next_hour).await.expect("purge"));
+        let purged = qualities(&pool, &window, None).await.expect("qualities");
+        assert_eq!(1, purged.len());
+        assert_eq!(0, purged[0].valid);
+    }
+
+    #[test]
+    fn score_is_share_of_valid_witnesses() {
+        let hotspot_key: PublicKeyBinary = "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6"
+            .parse()
+            .expect("failed gateway parse");
+        let mut quality = WitnessQuality::new(hotspot_key);
+        assert_eq!(quality.score(), 0.0);
+
+        quality.add(VALID_OUTCOME.to_string(), 6);
+        quality.add("too_close".to_string(), 1);
+        quality.add("bad_rssi".to_string(), 2);
+        quality.add("too_close".to_string(), 1);
+
+        assert_eq!(quality.valid, 6);
+        assert_eq!(quality.invalid, 4);
+        assert_eq!(quality.reasons["too_close"], 2);
+        assert_eq!(quality.reasons["bad_rssi"], 2);
+        assert_eq!(quality.score(), 0.6);
+    }
+}
@@ -0,0 +1,69 @@
+//! Api of the witness quality of gateways, served alongside the hex density
+//! api.
+
+use crate::{
+    density_service::{self, sign},
+    proto::{self, WitnessQualityReqV1, WitnessQualityRespV1},
+    witness_quality,
+};
+use chrono::{Duration, Utc};
+use file_store::{grpc::GrpcResult, traits::TimestampEncode};
+use helium_crypto::{Keypair, PublicKeyBinary};
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_witness_quality_request");
+
+/// Serves the witness quality of gateways over the `witness_quality_window`
+/// ending now, signed with the keypair of the hex density api
+pub struct WitnessQualityService {
+    pool: Pool<Postgres>,
+    window: Duration,
+    signing_key: Keypair,
+}
+
+impl WitnessQualityService {
+    pub fn new(
+        settings: &density_service::Settings,
+        pool: Pool<Postgres>,
+        window: Duration,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool,
+            window,
+            signing_key: settings.signing_keypair()?,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl proto::witness_quality_server::WitnessQuality for WitnessQualityService {
+    async fn quality(
+        &self,
+        request: Request<WitnessQualityReqV1>,
+    ) -> GrpcResult<WitnessQualityRespV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "quality");
+
+        let hotspot_key = PublicKeyBinary::from(request.hotspot_key);
+        let now = Utc::now();
+        let window = now - self.window..now;
+        let quality = witness_quality::qualities(&self.pool, &window, Some(&hotspot_key))
+            .await
+            .map_err(|err| {
+                tracing::error!(%hotspot_key, ?err, "witness quality query failed");
+                Status::internal("witness quality query failed")
+            })?
+            .pop()
+            .ok_or_else(|| Status::not_found("no witnesses of the gateway within the window"))?;
+
+        let mut resp = WitnessQualityRespV1 {
+            quality: Some(quality.into_report(&window).into()),
+            timestamp: now.encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = sign(&self.signing_key, &resp)?;
+        Ok(Response::new(resp))
+    }
+}
@@ -0,0 +1,115 @@
+//! A simulated fleet of hotspots with stable keys and locations.
+
+use helium_crypto::{KeyTag, KeyType, Keypair, Network, PublicKeyBinary};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Bounding box the simulated hotspots are placed in, roughly the
+/// continental United States.
+const LAT_RANGE: (f64, f64) = (25.0, 49.0);
+const LON_RANGE: (f64, f64) = (-124.0, -67.0);
+
+/// Hotspots within this distance in degrees of each other are considered
+/// neighbours and witness each other's beacons.
+const NEIGHBOUR_DEGREES: f64 = 0.5;
+
+pub struct Hotspot {
+    pub keypair: Keypair,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Hotspot {
+    pub fn pubkey(&self) -> PublicKeyBinary {
+        self.keypair.public_key().into()
+    }
+}
+
+pub struct Fleet {
+    pub hotspots: Vec<Hotspot>,
+    pub rng: StdRng,
+}
+
+impl Fleet {
+    /// A fleet of `size` hotspots, at least one, placed the same way for the
+    /// same `seed`
+    pub fn new(network: Network, size: usize, seed: Option<u64>) -> anyhow::Result<Self> {
+        anyhow::ensure!(size > 0, "a fleet needs at least one hotspot");
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let key_tag = KeyTag {
+            network,
+            key_type: KeyType::Ed25519,
+        };
+        let hotspots = (0..size)
+            .map(|_| Hotspot {
+                keypair: Keypair::generate(key_tag, &mut rng),
+                lat: rng.gen_range(LAT_RANGE.0..LAT_RANGE.1),
+                lon: rng.gen_range(LON_RANGE.0..LON_RANGE.1),
+            })
+            .collect();
+        Ok(Self { hotspots, rng })
+    }
+
+    pub fn random_index(&mut self) -> usize {
+        self.rng.gen_range(0..self.hotspots.len())
+    }
+
+    /// Indexes of up to `max` hotspots near the hotspot at `index`, so that
+    /// beacons are witnessed by a geographically consistent set.
+    pub fn neighbours(&self, index: usize, max: usize) -> Vec<usize> {
+        let beaconer = &self.hotspots[index];
+        self.hotspots
+            .iter()
+            .enumerate()
+            .filter(|(i, hotspot)| {
+                *i != index
+                    && (hotspot.lat - beaconer.lat).abs() < NEIGHBOUR_DEGREES
+                    && (hotspot.lon - beaconer.lon).abs() < NEIGHBOUR_DEGREES
+            })
+            .map(|(i, _)| i)
+            .take(max)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_fleets_are_repeatable() {
+        let fleet = Fleet::new(Network::TestNet, 20, Some(7)).unwrap();
+        let again = Fleet::new(Network::TestNet, 20, Some(7)).unwrap();
+        assert_eq!(20, fleet.hotspots.len());
+        for (hotspot, other) in fleet.hotspots.iter().zip(&again.hotspots) {
+            assert_eq!(hotspot.pubkey(), other.pubkey());
+            assert_eq!((hotspot.lat, hotspot.lon), (other.lat, other.lon));
+            assert!((LAT_RANGE.0..LAT_RANGE.1).contains(&hotspot.lat));
+            assert!((LON_RANGE.0..LON_RANGE.1).contains(&hotspot.lon));
+        }
+    }
+
+    #[test]
+    fn empty_fleets_are_rejected() {
+        assert!(Fleet::new(Network::TestNet, 0, Some(7)).is_err());
+    }
+
+    #[test]
+    fn neighbours_are_nearby_other_hotspots() {
+        let mut fleet = Fleet::new(Network::TestNet, 500, Some(7)).unwrap();
+        for _ in 0..50 {
+            let index = fleet.random_index();
+            let beaconer = &fleet.hotspots[index];
+            let neighbours = fleet.neighbours(index, 3);
+            assert!(neighbours.len() <= 3);
+            for neighbour in neighbours {
+                let hotspot = &fleet.hotspots[neighbour];
+                assert_ne!(index, neighbour);
+                assert!((hotspot.lat - beaconer.lat).abs() < NEIGHBOUR_DEGREES);
+                assert!((hotspot.lon - beaconer.lon).abs() < NEIGHBOUR_DEGREES);
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Builders for synthetic reports, signed by the simulated hotspot submitting
+//! them so they pass the checks done by ingest.
+
+use crate::fleet::Fleet;
+use chrono::{Duration, Utc};
+use helium_crypto::{Keypair, Sign};
+use helium_proto::{
+    services::{
+        poc_lora::{LoraBeaconReportReqV1, LoraWitnessReportReqV1},
+        poc_mobile::{
+            CellHeartbeatReqV1, DataTransferEvent, DataTransferRadioAccessTechnology,
+            DataTransferSessionReqV1,
+        },
+    },
+    DataRate, Message,
+};
+use rand::Rng;
+
+const BEACON_DATA_LEN: usize = 52;
+const FREQUENCIES: [u64; 8] = [
+    903_900_000,
+    904_100_000,
+    904_300_000,
+    904_500_000,
+    904_700_000,
+    904_900_000,
+    905_100_000,
+    905_300_000,
+];
+
+pub trait MsgSign: Message + Clone {
+    fn sign(&self, keypair: &Keypair) -> anyhow::Result<Vec<u8>>;
+}
+
+macro_rules! impl_sign {
+    ($msg_type:ty, $sig: ident) => {
+        impl MsgSign for $msg_type {
+            fn sign(&self, keypair: &Keypair) -> anyhow::Result<Vec<u8>> {
+                let mut msg = self.clone();
+                msg.$sig = vec![];
+                Ok(keypair.sign(&msg.encode_to_vec())?)
+            }
+        }
+    };
+}
+
+impl_sign!(LoraBeaconReportReqV1, signature);
+impl_sign!(LoraWitnessReportReqV1, signature);
+impl_sign!(CellHeartbeatReqV1, signature);
+impl_sign!(DataTransferSessionReqV1, signature);
+impl_sign!(DataTransferEvent, signature);
+
+fn signed<T: MsgSign>(
+    mut msg: T,
+    keypair: &Keypair,
+    set: fn(&mut T, Vec<u8>),
+) -> anyhow::Result<T> {
+    let signature = msg.sign(keypair)?;
+    set(&mut msg, signature);
+    Ok(msg)
+}
+
+/// A beacon together with the witness reports of its neighbours. They share
+/// payload, frequency and datarate like real radio traffic would, though the
+/// entropy they reference is random so verifiers reject them further down
+/// the pipeline.
+pub struct BeaconWithWitnesses {
+    pub beacon: LoraBeaconReportReqV1,
+    pub witnesses: Vec<LoraWitnessReportReqV1>,
+}
+
+pub fn beacon(fleet: &mut Fleet, max_witnesses: usize) -> anyhow::Result<BeaconWithWitnesses> {
+    let beaconer = fleet.random_index();
+    let neighbours = fleet.neighbours(beaconer, max_witnesses);
+
+    let rng = &mut fleet.rng;
+    let mut data = vec![0u8; BEACON_DATA_LEN];
+    rng.fill(&mut data[..]);
+    let mut local_entropy = vec![0u8; 4];
+    rng.fill(&mut local_entropy[..]);
+    let mut remote_entropy = vec![0u8; 32];
+    rng.fill(&mut remote_entropy[..]);
+    let frequency = FREQUENCIES[rng.gen_range(0..FREQUENCIES.len())];
+    let datarate = DataRate::Sf10bw125 as i32;
+    let now = Utc::now();
+    let tmst = rng.gen::<u32>();
+
+    let hotspot = &fleet.hotspots[beaconer];
+    let beacon = signed(
+        LoraBeaconReportReqV1 {
+            pub_key: hotspot.keypair.public_key().into(),
+            local_entropy,
+            remote_entropy,
+            data: data.clone(),
+            frequency,
+            channel: 0,
+            datarate,
+            tx_power: 27,
+            timestamp: now.timestamp_nanos() as u64,
+            signature: vec![],
+            tmst,
+        },
+        &hotspot.keypair,
+        |msg, signature| msg.signature = signature,
+    )?;
+
+    let mut witnesses = Vec::with_capacity(neighbours.len());
+    for index in neighbours {
+        let rng = &mut fleet.rng;
+        let received = now + Duration::milliseconds(rng.gen_range(1..500));
+        let signal = rng.gen_range(-1200..-400);
+        let snr = rng.gen_range(-200..100);
+        let witness_tmst = tmst.wrapping_add(rng.gen_range(0..1_000_000));
+        let hotspot = &fleet.hotspots[index];
+        witnesses.push(signed(
+            LoraWitnessReportReqV1 {
+                pub_key: hotspot.keypair.public_key().into(),
+                data: data.clone(),
+                timestamp: received.timestamp_nanos() as u64,
+                signal,
+                snr,
+                frequency,
+                datarate,
+                signature: vec![],
+                tmst: witness_tmst,
+            },
+            &hotspot.keypair,
+            |msg, signature| msg.signature = signature,
+        )?);
+    }
+
+    Ok(BeaconWithWitnesses { beacon, witnesses })
+}
+
+pub fn heartbeat(fleet: &mut Fleet) -> anyhow::Result<CellHeartbeatReqV1> {
+    let index = fleet.random_index();
+    let cell_id = fleet.rng.gen::<u32>();
+    let hotspot = &fleet.hotspots[index];
+    signed(
+        CellHeartbeatReqV1 {
+            pub_key: hotspot.keypair.public_key().into(),
+            hotspot_type: "loadgen".to_string(),
+            cell_id,
+            timestamp: Utc::now().timestamp() as u64,
+            lat: hotspot.lat,
+            lon: hotspot.lon,
+            operation_mode: true,
+            cbsd_category: "A".to_string(),
+            cbsd_id: format!("loadgen-{index}"),
+            signature: vec![],
+            coverage_object: vec![],
+        },
+        &hotspot.keypair,
+        |msg, signature| msg.signature = signature,
+    )
+}
+
+/// A data transfer session reported by `payer` for a random hotspot. The
+/// payer keypair plays the role of the mobile router.
+pub fn data_session(
+    fleet: &mut Fleet,
+    payer: &Keypair,
+) -> anyhow::Result<DataTransferSessionReqV1> {
+    let index = fleet.random_index();
+    let upload_bytes = fleet.rng.gen_range(1_000..10_000_000);
+    let download_bytes = fleet.rng.gen_range(1_000..100_000_000);
+    let event_id = format!("loadgen-{}", fleet.rng.gen::<u64>());
+    let hotspot = &fleet.hotspots[index];
+    let event = signed(
+        DataTransferEvent {
+            pub_key: hotspot.keypair.public_key().into(),
+            upload_bytes,
+            download_bytes,
+            radio_access_technology: DataTransferRadioAccessTechnology::Eutran as i32,
+            event_id,
+            payer: payer.public_key().into(),
+            timestamp: Utc::now().timestamp_millis() as u64,
+            signature: vec![],
+        },
+        payer,
+        |msg, signature| msg.signature = signature,
+    )?;
+    signed(
+        DataTransferSessionReqV1 {
+            data_transfer_usage: Some(event),
+            reward_cancelled: false,
+            pub_key: payer.public_key().into(),
+            signature: vec![],
+        },
+        payer,
+        |msg, signature| msg.signature = signature,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_store::traits::MsgVerify;
+    use helium_crypto::{KeyTag, KeyType, Network, PublicKey, PublicKeyBinary};
+
+    fn fleet() -> Fleet {
+        Fleet::new(Network::TestNet, 500, Some(7)).unwrap()
+    }
+
+    fn signer(pub_key: &[u8]) -> PublicKey {
+        PublicKey::try_from(pub_key).unwrap()
+    }
+
+    #[test]
+    fn witnesses_report_the_beacon_of_their_neighbour() {
+        let mut fleet = fleet();
+        let generated = beacon(&mut fleet, 5).unwrap();
+        let beacon = &generated.beacon;
+        assert_eq!(BEACON_DATA_LEN, beacon.data.len());
+        assert!(FREQUENCIES.contains(&beacon.frequency));
+        beacon.verify(&signer(&beacon.pub_key)).unwrap();
+
+        assert!(generated.witnesses.len() <= 5);
+        for witness in &generated.witnesses {
+            assert_ne!(beacon.pub_key, witness.pub_key);
+            assert_eq!(beacon.data, witness.data);
+            assert_eq!(beacon.frequency, witness.frequency);
+            assert_eq!(beacon.datarate, witness.datarate);
+            assert!(witness.timestamp > beacon.timestamp);
+            witness.verify(&signer(&witness.pub_key)).unwrap();
+        }
+    }
+
+    #[test]
+    fn heartbeats_are_signed_at_their_hotspot_location() {
+        let mut fleet = fleet();
+        let heartbeat = heartbeat(&mut fleet).unwrap();
+        let hotspot = fleet
+            .hotspots
+            .iter()
+            .find(|hotspot| hotspot.pubkey() == PublicKeyBinary::from(heartbeat.pub_key.clone()))
+            .expect("heartbeat of a fleet hotspot");
+        assert_eq!((hotspot.lat, hotspot.lon), (heartbeat.lat, heartbeat.lon));
+        heartbeat.verify(&signer(&heartbeat.pub_key)).unwrap();
+    }
+
+    #[test]
+    fn data_sessions_are_signed_by_the_payer() {
+        let mut fleet = fleet();
+        let payer = Keypair::generate(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::Ed25519,
+            },
+            &mut fleet.rng,
+        );
+        let session = data_session(&mut fleet, &payer).unwrap();
+        session.verify(payer.public_key()).unwrap();
+        let event = session.data_transfer_usage.expect("data transfer event");
+        let payer_key: Vec<u8> = payer.public_key().into();
+        assert_eq!(payer_key, event.payer);
+        let hotspot_key = PublicKeyBinary::from(event.pub_key);
+        assert!(fleet
+            .hotspots
+            .iter()
+            .any(|hotspot| hotspot.pubkey() == hotspot_key));
+    }
+}
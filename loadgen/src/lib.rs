@@ -0,0 +1,6 @@
+pub mod fleet;
+pub mod generator;
+pub mod settings;
+pub mod target;
+
+pub use settings::Settings;
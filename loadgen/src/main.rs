@@ -0,0 +1,203 @@
+use anyhow::Result;
+use clap::Parser;
+use helium_crypto::{KeyTag, KeyType, Keypair};
+use loadgen::{
+    fleet::Fleet,
+    generator,
+    target::{Target, TargetKind},
+    Settings,
+};
+use std::path::PathBuf;
+use tokio::{signal, time};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+#[derive(Debug, clap::Parser)]
+#[clap(version = env!("CARGO_PKG_VERSION"))]
+#[clap(about = "Helium Oracles Load Generator")]
+pub struct Cli {
+    /// Optional configuration file to use. If present the toml file at the
+    /// given path will be loaded. Environemnt variables can override the
+    /// settins in the given file.
+    #[clap(short = 'c')]
+    config: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        let settings = Settings::new(self.config)?;
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+        self.cmd.run(settings).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// Generate beacons with consistent witnesses
+    Iot(Iot),
+    /// Generate heartbeats and data transfer sessions
+    Mobile(Mobile),
+}
+
+impl Cmd {
+    async fn run(self, settings: Settings) -> Result<()> {
+        match self {
+            Self::Iot(cmd) => cmd.run(&settings).await,
+            Self::Mobile(cmd) => cmd.run(&settings).await,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Run {
+    /// Where to send the generated traffic
+    #[clap(long, value_enum, default_value = "ingest")]
+    target: TargetKind,
+    /// How long to generate traffic for, e.g. "10m". Runs until interrupted
+    /// if not given
+    #[clap(long, value_parser = humantime_duration)]
+    duration: Option<std::time::Duration>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Iot {
+    #[clap(flatten)]
+    run: Run,
+    /// Beacons generated per second
+    #[clap(long, default_value_t = 10.0, value_parser = rate)]
+    beacons_per_second: f64,
+    /// Maximum number of witnesses per beacon
+    #[clap(long, default_value_t = 10)]
+    max_witnesses: usize,
+}
+
+impl Iot {
+    async fn run(self, settings: &Settings) -> Result<()> {
+        let mut fleet = Fleet::new(settings.network, settings.hotspots, settings.seed)?;
+        let mut target = Target::from_settings(self.run.target, settings).await?;
+        let mut ticker = ticker(self.beacons_per_second);
+        let (mut beacons, mut witnesses) = (0_u64, 0_u64);
+
+        run_until(self.run.duration, async {
+            loop {
+                ticker.tick().await;
+                let generated = generator::beacon(&mut fleet, self.max_witnesses)?;
+                target.submit_beacon(generated.beacon).await?;
+                beacons += 1;
+                for witness in generated.witnesses {
+                    target.submit_witness(witness).await?;
+                    witnesses += 1;
+                }
+            }
+        })
+        .await?;
+
+        tracing::info!(beacons, witnesses, "finished generating iot traffic");
+        target.finish().await
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Mobile {
+    #[clap(flatten)]
+    run: Run,
+    /// Heartbeats generated per second
+    #[clap(long, default_value_t = 10.0, value_parser = rate)]
+    heartbeats_per_second: f64,
+    /// Data transfer sessions generated per second
+    #[clap(long, default_value_t = 10.0, value_parser = rate)]
+    data_sessions_per_second: f64,
+}
+
+impl Mobile {
+    async fn run(self, settings: &Settings) -> Result<()> {
+        let mut fleet = Fleet::new(settings.network, settings.hotspots, settings.seed)?;
+        let payer = Keypair::generate(
+            KeyTag {
+                network: settings.network,
+                key_type: KeyType::Ed25519,
+            },
+            &mut fleet.rng,
+        );
+        let mut target = Target::from_settings(self.run.target, settings).await?;
+        let mut heartbeat_ticker = ticker(self.heartbeats_per_second);
+        let mut session_ticker = ticker(self.data_sessions_per_second);
+        let (mut heartbeats, mut sessions) = (0_u64, 0_u64);
+
+        run_until(self.run.duration, async {
+            loop {
+                tokio::select! {
+                    _ = heartbeat_ticker.tick() => {
+                        target.submit_heartbeat(generator::heartbeat(&mut fleet)?).await?;
+                        heartbeats += 1;
+                    }
+                    _ = session_ticker.tick() => {
+                        let session = generator::data_session(&mut fleet, &payer)?;
+                        target.submit_data_session(session).await?;
+                        sessions += 1;
+                    }
+                }
+            }
+        })
+        .await?;
+
+        tracing::info!(heartbeats, sessions, "finished generating mobile traffic");
+        target.finish().await
+    }
+}
+
+fn ticker(per_second: f64) -> time::Interval {
+    let mut ticker = time::interval(std::time::Duration::from_secs_f64(1.0 / per_second));
+    // Generate at a steady rate even if the target falls behind rather than
+    // bursting to catch up.
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    ticker
+}
+
+/// Drive the generator until the duration elapses or the process is
+/// interrupted. Errors from the generator are returned.
+async fn run_until<F>(duration: Option<std::time::Duration>, generate: F) -> Result<()>
+where
+    F: std::future::Future<Output = Result<()>>,
+{
+    let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+    let deadline = async {
+        match duration {
+            Some(duration) => time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = generate => result,
+        _ = deadline => Ok(()),
+        _ = sigterm.recv() => Ok(()),
+        _ = signal::ctrl_c() => Ok(()),
+    }
+}
+
+fn humantime_duration(s: &str) -> Result<std::time::Duration, humantime::DurationError> {
+    humantime::parse_duration(s)
+}
+
+/// A rate per second the tickers can run at: positive, and low enough that a
+/// tick takes at least a nanosecond
+fn rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    if !(rate > 0.0 && rate <= MAX_RATE) {
+        return Err(format!("rate must be above 0 and at most {MAX_RATE}"));
+    }
+    Ok(rate)
+}
+
+const MAX_RATE: f64 = 1_000_000_000.0;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    cli.run().await
+}
@@ -0,0 +1,57 @@
+use helium_crypto::Network;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// RUST_LOG compatible settings string. Default to
+    /// "loadgen=info"
+    #[serde(default = "default_log")]
+    pub log: String,
+    /// Network the generated keys belong to: mainnet | testnet.
+    /// Default is testnet
+    #[serde(default = "default_network")]
+    pub network: Network,
+    /// Number of simulated hotspots, at least 1. Default is 1000
+    #[serde(default = "default_hotspots")]
+    pub hotspots: usize,
+    /// Optional seed for the random generator so that runs are repeatable
+    pub seed: Option<u64>,
+    /// Url of the ingest grpc service. Required when targeting ingest
+    pub ingest_url: Option<String>,
+    /// Bearer token for the mobile ingest service
+    pub ingest_token: Option<String>,
+    /// Local folder for files written when targeting S3 directly
+    #[serde(default = "default_cache")]
+    pub cache: String,
+    /// Output bucket details when targeting S3 directly
+    pub output: Option<file_store::Settings>,
+}
+
+pub fn default_log() -> String {
+    "loadgen=info".to_string()
+}
+
+pub fn default_network() -> Network {
+    Network::TestNet
+}
+
+pub fn default_hotspots() -> usize {
+    1000
+}
+
+pub fn default_cache() -> String {
+    "/var/data/loadgen".to_string()
+}
+
+impl Settings {
+    /// Load Settings from a given path. Settings are loaded from a given
+    /// optional path and can be overriden with environment variables.
+    ///
+    /// Environemnt overrides have the same name as the entries in the settings
+    /// file in uppercase and prefixed with "LOADGEN_". For example
+    /// "LOADGEN_INGEST_URL" will override the ingest url.
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "LOADGEN")
+    }
+}
@@ -0,0 +1,269 @@
+//! Destinations for generated traffic, either the ingest grpc services or
+//! ingest report files uploaded to S3.
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use file_store::{file_sink::FileSinkClient, FileSinkBuilder, FileStore, FileType};
+use helium_proto::services::{
+    poc_lora::{
+        poc_lora_client::PocLoraClient, LoraBeaconIngestReportV1, LoraBeaconReportReqV1,
+        LoraWitnessIngestReportV1, LoraWitnessReportReqV1,
+    },
+    poc_mobile::{
+        poc_mobile_client::PocMobileClient, CellHeartbeatIngestReportV1, CellHeartbeatReqV1,
+        DataTransferSessionIngestReportV1, DataTransferSessionReqV1,
+    },
+};
+use std::path::{Path, PathBuf};
+use tokio::task::JoinHandle;
+use tonic::{metadata::MetadataValue, transport::Channel, Request};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum TargetKind {
+    /// Submit reports to the ingest grpc service
+    Ingest,
+    /// Write ingest report files and upload them to the output bucket
+    S3,
+}
+
+pub enum Target {
+    Ingest(IngestTarget),
+    S3(S3Target),
+}
+
+pub struct IngestTarget {
+    lora: PocLoraClient<Channel>,
+    mobile: PocMobileClient<Channel>,
+    token: Option<MetadataValue<tonic::metadata::Ascii>>,
+}
+
+pub struct S3Target {
+    store: FileStore,
+    target_path: PathBuf,
+    shutdown_trigger: triggered::Trigger,
+    beacons: FileSinkClient,
+    witnesses: FileSinkClient,
+    heartbeats: FileSinkClient,
+    data_sessions: FileSinkClient,
+    sink_handles: Vec<JoinHandle<file_store::Result>>,
+}
+
+impl Target {
+    pub async fn from_settings(kind: TargetKind, settings: &crate::Settings) -> Result<Self> {
+        match kind {
+            TargetKind::Ingest => {
+                let Some(url) = &settings.ingest_url else {
+                    bail!("ingest_url is required to target ingest");
+                };
+                let channel = Channel::from_shared(url.clone())?.connect().await?;
+                let token = settings
+                    .ingest_token
+                    .as_ref()
+                    .map(|token| format!("Bearer {token}").parse())
+                    .transpose()?;
+                Ok(Self::Ingest(IngestTarget {
+                    lora: PocLoraClient::new(channel.clone()),
+                    mobile: PocMobileClient::new(channel),
+                    token,
+                }))
+            }
+            TargetKind::S3 => {
+                let Some(output) = &settings.output else {
+                    bail!("output settings are required to target S3");
+                };
+                let store = FileStore::from_settings(output).await?;
+                Ok(Self::S3(
+                    S3Target::new(store, Path::new(&settings.cache)).await?,
+                ))
+            }
+        }
+    }
+
+    pub async fn submit_beacon(&mut self, report: LoraBeaconReportReqV1) -> Result<()> {
+        match self {
+            Self::Ingest(target) => {
+                let request = target.request(report);
+                target.lora.submit_lora_beacon(request).await?;
+            }
+            Self::S3(target) => {
+                target
+                    .beacons
+                    .write(
+                        LoraBeaconIngestReportV1 {
+                            received_timestamp: received_timestamp(),
+                            report: Some(report),
+                        },
+                        [],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn submit_witness(&mut self, report: LoraWitnessReportReqV1) -> Result<()> {
+        match self {
+            Self::Ingest(target) => {
+                let request = target.request(report);
+                target.lora.submit_lora_witness(request).await?;
+            }
+            Self::S3(target) => {
+                target
+                    .witnesses
+                    .write(
+                        LoraWitnessIngestReportV1 {
+                            received_timestamp: received_timestamp(),
+                            report: Some(report),
+                        },
+                        [],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn submit_heartbeat(&mut self, report: CellHeartbeatReqV1) -> Result<()> {
+        match self {
+            Self::Ingest(target) => {
+                let request = target.request(report);
+                target.mobile.submit_cell_heartbeat(request).await?;
+            }
+            Self::S3(target) => {
+                target
+                    .heartbeats
+                    .write(
+                        CellHeartbeatIngestReportV1 {
+                            received_timestamp: received_timestamp(),
+                            report: Some(report),
+                        },
+                        [],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn submit_data_session(&mut self, report: DataTransferSessionReqV1) -> Result<()> {
+        match self {
+            Self::Ingest(target) => {
+                let request = target.request(report);
+                target.mobile.submit_data_transfer_session(request).await?;
+            }
+            Self::S3(target) => {
+                target
+                    .data_sessions
+                    .write(
+                        DataTransferSessionIngestReportV1 {
+                            received_timestamp: received_timestamp(),
+                            report: Some(report),
+                        },
+                        [],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush any buffered reports. For the S3 target this closes all open
+    /// files and uploads them.
+    pub async fn finish(self) -> Result<()> {
+        match self {
+            Self::Ingest(_) => Ok(()),
+            Self::S3(target) => target.finish().await,
+        }
+    }
+}
+
+impl IngestTarget {
+    fn request<T>(&self, msg: T) -> Request<T> {
+        let mut request = Request::new(msg);
+        if let Some(token) = &self.token {
+            request
+                .metadata_mut()
+                .insert("authorization", token.clone());
+        }
+        request
+    }
+}
+
+impl S3Target {
+    async fn new(store: FileStore, cache: &Path) -> Result<Self> {
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut sink_handles = Vec::new();
+        let sink = |file_type: FileType, metric: &'static str| {
+            let shutdown_listener = shutdown_listener.clone();
+            async move {
+                // Files are uploaded once the run finishes rather than as
+                // they roll, so nothing is lost if the upload lags behind.
+                FileSinkBuilder::new(file_type, cache, metric, shutdown_listener)
+                    .auto_commit(false)
+                    .create()
+                    .await
+            }
+        };
+
+        let (beacons, mut beacons_server) =
+            sink(FileType::IotBeaconIngestReport, "loadgen_beacon_report").await?;
+        let (witnesses, mut witnesses_server) =
+            sink(FileType::IotWitnessIngestReport, "loadgen_witness_report").await?;
+        let (heartbeats, mut heartbeats_server) = sink(
+            FileType::CellHeartbeatIngestReport,
+            "loadgen_heartbeat_report",
+        )
+        .await?;
+        let (data_sessions, mut data_sessions_server) = sink(
+            FileType::DataTransferSessionIngestReport,
+            "loadgen_data_transfer_session",
+        )
+        .await?;
+
+        sink_handles.push(tokio::spawn(async move { beacons_server.run().await }));
+        sink_handles.push(tokio::spawn(async move { witnesses_server.run().await }));
+        sink_handles.push(tokio::spawn(async move { heartbeats_server.run().await }));
+        sink_handles.push(tokio::spawn(
+            async move { data_sessions_server.run().await },
+        ));
+
+        Ok(Self {
+            store,
+            target_path: cache.to_path_buf(),
+            shutdown_trigger,
+            beacons,
+            witnesses,
+            heartbeats,
+            data_sessions,
+            sink_handles,
+        })
+    }
+
+    async fn finish(self) -> Result<()> {
+        let mut files = Vec::new();
+        for sink in [
+            &self.beacons,
+            &self.witnesses,
+            &self.heartbeats,
+            &self.data_sessions,
+        ] {
+            files.extend(sink.commit().await?.await??);
+        }
+        self.shutdown_trigger.trigger();
+        for handle in self.sink_handles {
+            handle.await??;
+        }
+
+        for file in files {
+            let path = self.target_path.join(&file);
+            tracing::info!("uploading {file}");
+            self.store.put(&path).await?;
+            tokio::fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+}
+
+fn received_timestamp() -> u64 {
+    Utc::now().timestamp_millis() as u64
+}
@@ -8,4 +8,6 @@ pub enum Error {
     DecodeError(#[from] std::net::AddrParseError),
     #[error("metrics build error")]
     Metrics(#[from] metrics_exporter_prometheus::BuildError),
+    #[error("status server error")]
+    Status(#[from] hyper::Error),
 }
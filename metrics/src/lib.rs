@@ -13,13 +13,24 @@ use std::{
 use tower::{Layer, Service};
 
 mod error;
+pub mod preflight;
 pub mod settings;
+pub mod status;
+pub mod ui;
 
 pub fn start_metrics(settings: &Settings) -> Result {
     let socket: SocketAddr = settings.endpoint.parse()?;
-    PrometheusBuilder::new()
-        .with_http_listener(socket)
-        .install()?;
+    if settings.ui {
+        let handle = PrometheusBuilder::new().install_recorder()?;
+        ui::start_server(socket, handle)?;
+    } else {
+        PrometheusBuilder::new()
+            .with_http_listener(socket)
+            .install()?;
+    }
+    if let Some(status_endpoint) = &settings.status_endpoint {
+        status::start_server(status_endpoint.parse()?)?;
+    }
     Ok(())
 }
 
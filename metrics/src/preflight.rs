@@ -0,0 +1,151 @@
+//! Checks of the external dependencies of a binary, run once at startup
+//! before it enters its run loop.
+
+use std::{fmt, future::Future, time::Duration};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Failure {
+    pub check: &'static str,
+    pub reason: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub struct PreflightError {
+    pub binary: &'static str,
+    pub failures: Vec<Failure>,
+}
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} preflight failed", self.binary)?;
+        for failure in &self.failures {
+            write!(f, "; {}: {}", failure.check, failure.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check, each under a timeout, and fails with all of their
+/// failures together, so a deploy with several misconfigured dependencies
+/// isn't fixed one restart at a time
+pub struct Preflight {
+    binary: &'static str,
+    timeout: Duration,
+    passed: usize,
+    failures: Vec<Failure>,
+}
+
+impl Preflight {
+    pub fn new(binary: &'static str) -> Self {
+        Self {
+            binary,
+            timeout: DEFAULT_TIMEOUT,
+            passed: 0,
+            failures: vec![],
+        }
+    }
+
+    /// Time after which a single check counts as failed. Default 30s
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Run a check, recording its failure. The value of a passing check is
+    /// returned so dependencies set up by the check can be kept.
+    pub async fn check<T, E>(
+        &mut self,
+        check: &'static str,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> Option<T>
+    where
+        E: fmt::Display,
+    {
+        match tokio::time::timeout(self.timeout, future).await {
+            Ok(result) => self.check_result(check, result),
+            Err(_) => {
+                self.fail(check, format!("timed out after {:?}", self.timeout));
+                None
+            }
+        }
+    }
+
+    /// Record the outcome of a check that has already run
+    pub fn check_result<T, E>(&mut self, check: &'static str, result: Result<T, E>) -> Option<T>
+    where
+        E: fmt::Display,
+    {
+        match result {
+            Ok(value) => {
+                tracing::info!(binary = self.binary, check, "preflight check passed");
+                self.passed += 1;
+                Some(value)
+            }
+            Err(err) => {
+                self.fail(check, err.to_string());
+                None
+            }
+        }
+    }
+
+    fn fail(&mut self, check: &'static str, reason: String) {
+        tracing::error!(
+            binary = self.binary,
+            check,
+            reason,
+            "preflight check failed"
+        );
+        self.failures.push(Failure { check, reason });
+    }
+
+    /// Fail with every failed check, if any. Once this returns `Ok` all
+    /// values returned by [`Preflight::check`] are `Some`.
+    pub fn finish(self) -> Result<(), PreflightError> {
+        if self.failures.is_empty() {
+            tracing::info!(
+                binary = self.binary,
+                checks = self.passed,
+                "preflight passed"
+            );
+            return Ok(());
+        }
+        Err(PreflightError {
+            binary: self.binary,
+            failures: self.failures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_all_failures() {
+        let mut preflight = Preflight::new("test").timeout(Duration::from_millis(10));
+        assert_eq!(
+            Some(1),
+            preflight
+                .check("passing", async { Ok::<_, String>(1) })
+                .await
+        );
+        assert_eq!(
+            None,
+            preflight.check_result::<(), _>("failing", Err("no access"))
+        );
+        assert_eq!(
+            None,
+            preflight
+                .check("hanging", std::future::pending::<Result<(), String>>())
+                .await
+        );
+        let err = preflight.finish().expect_err("preflight fails");
+        let checks: Vec<_> = err.failures.iter().map(|failure| failure.check).collect();
+        assert_eq!(vec!["failing", "hanging"], checks);
+        assert_eq!(
+            "test preflight failed; failing: no access; hanging: timed out after 10ms",
+            err.to_string()
+        );
+    }
+}
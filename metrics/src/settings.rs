@@ -5,6 +5,14 @@ pub struct Settings {
     /// Scrape endpoint for metrics
     #[serde(default = "default_metrics_endpoint")]
     pub endpoint: String,
+    /// Endpoint serving the status of the running tasks, file sink queues and
+    /// loader watermarks as json on /status. Disabled when not set
+    #[serde(default)]
+    pub status_endpoint: Option<String>,
+    /// Serve the read-only html pages of the binary from /ui on the metrics
+    /// endpoint, next to the scrape. Default false
+    #[serde(default)]
+    pub ui: bool,
 }
 
 pub fn default_metrics_endpoint() -> String {
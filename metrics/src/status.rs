@@ -0,0 +1,297 @@
+//! Self describing status of a running binary, served as json from `/status`
+//! on the `status_endpoint` of the metrics settings.
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Registry {
+    tasks: BTreeMap<(&'static str, &'static str), TaskStatus>,
+    queues: BTreeMap<String, QueueDepth>,
+    watermarks: BTreeMap<String, u64>,
+    ingest: BTreeMap<String, Ingest>,
+}
+
+#[derive(Default)]
+struct Ingest {
+    processed_up_to: Option<u64>,
+    pending: QueueDepth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    Stopped,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    /// Unix timestamp in seconds the task started at
+    pub started: u64,
+    /// Unix timestamp in seconds of the last heartbeat, if any
+    pub last_heartbeat: Option<u64>,
+}
+
+/// Number of messages waiting in a queue, shared between its producers and
+/// its consumer
+#[derive(Clone, Debug, Default)]
+pub struct QueueDepth(Arc<AtomicUsize>);
+
+impl QueueDepth {
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decrement(&self) {
+        // saturating so a consumer draining messages sent before the queue
+        // was registered can't wrap the depth around
+        _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+                depth.checked_sub(1)
+            });
+    }
+
+    pub fn set(&self, depth: usize) {
+        self.0.store(depth, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct IngestStatus {
+    /// Unix timestamp in seconds up to which files have been fully processed
+    pub processed_up_to: Option<u64>,
+    /// Reports, or files where reports aren't tracked, waiting to be processed
+    pub pending: usize,
+}
+
+/// What a binary is doing, or not doing, during an incident: its supervised
+/// tasks, the messages queued in every file sink, the watermark of every
+/// loader and the ingest progress per input file type
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub tasks: BTreeMap<String, BTreeMap<String, TaskStatus>>,
+    pub queues: BTreeMap<String, usize>,
+    pub watermarks: BTreeMap<String, u64>,
+    pub ingest: BTreeMap<String, IngestStatus>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    // the registry is only ever updated in place, a panic while holding the
+    // lock can't leave it inconsistent
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn set_state(subsystem: &'static str, name: &'static str, state: TaskState) {
+    let mut registry = registry();
+    let task = registry
+        .tasks
+        .entry((subsystem, name))
+        .or_insert_with(|| TaskStatus {
+            state,
+            started: now(),
+            last_heartbeat: None,
+        });
+    task.state = state;
+}
+
+/// Run `task`, recording it as running under `subsystem` until it returns,
+/// and as stopped or failed after
+pub async fn supervise<T, E>(
+    subsystem: &'static str,
+    name: &'static str,
+    task: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    set_state(subsystem, name, TaskState::Running);
+    let result = task.await;
+    let state = if result.is_ok() {
+        TaskState::Stopped
+    } else {
+        TaskState::Failed
+    };
+    set_state(subsystem, name, state);
+    result
+}
+
+/// Record that a task is making progress, usually once per tick of its loop
+pub fn heartbeat(subsystem: &'static str, name: &'static str) {
+    let now = now();
+    registry()
+        .tasks
+        .entry((subsystem, name))
+        .or_insert_with(|| TaskStatus {
+            state: TaskState::Running,
+            started: now,
+            last_heartbeat: None,
+        })
+        .last_heartbeat = Some(now);
+}
+
+/// The depth of the named queue, registering it on first use
+pub fn queue(name: &str) -> QueueDepth {
+    registry()
+        .queues
+        .entry(name.to_string())
+        .or_default()
+        .clone()
+}
+
+/// Record the timestamp, in seconds, a loader has processed up to
+pub fn watermark(name: &str, timestamp: u64) {
+    registry().watermarks.insert(name.to_string(), timestamp);
+}
+
+/// Record the timestamp, in seconds, up to which files of `file_type` have
+/// been fully processed
+pub fn processed_up_to(file_type: &str, timestamp: u64) {
+    registry()
+        .ingest
+        .entry(file_type.to_string())
+        .or_default()
+        .processed_up_to = Some(timestamp);
+}
+
+/// The number of reports of `file_type` pending processing, registering the
+/// file type on first use
+pub fn pending(file_type: &str) -> QueueDepth {
+    registry()
+        .ingest
+        .entry(file_type.to_string())
+        .or_default()
+        .pending
+        .clone()
+}
+
+fn ingest_report(registry: &Registry) -> BTreeMap<String, IngestStatus> {
+    registry
+        .ingest
+        .iter()
+        .map(|(file_type, ingest)| {
+            let status = IngestStatus {
+                processed_up_to: ingest.processed_up_to,
+                pending: ingest.pending.get(),
+            };
+            (file_type.clone(), status)
+        })
+        .collect()
+}
+
+pub fn report() -> StatusReport {
+    let registry = registry();
+    let mut tasks: BTreeMap<String, BTreeMap<String, TaskStatus>> = BTreeMap::new();
+    for ((subsystem, name), status) in &registry.tasks {
+        tasks
+            .entry(subsystem.to_string())
+            .or_default()
+            .insert(name.to_string(), status.clone());
+    }
+    StatusReport {
+        tasks,
+        queues: registry
+            .queues
+            .iter()
+            .map(|(name, depth)| (name.clone(), depth.get()))
+            .collect(),
+        watermarks: registry.watermarks.clone(),
+        ingest: ingest_report(&registry),
+    }
+}
+
+fn json_response<T: Serialize>(body: &T) -> hyper::http::Result<Response<Body>> {
+    match serde_json::to_vec_pretty(body) {
+        Ok(body) => Response::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(body)),
+        Err(_) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty()),
+    }
+}
+
+async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/status") => json_response(&report()),
+        (&Method::GET, "/ingest") => json_response(&report().ingest),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    Ok(response.unwrap_or_else(|_| Response::new(Body::empty())))
+}
+
+/// Serve the status report on `addr` until the process exits. The ingest
+/// progress is also served on its own from `/ingest` for external lag
+/// monitors
+pub fn start_server(addr: SocketAddr) -> crate::Result {
+    let server = Server::try_bind(&addr)?.serve(make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(handle))
+    }));
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!("status server error: {err:?}");
+        }
+    });
+    tracing::info!("status endpoint listening on {addr}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_tasks_queues_and_watermarks() {
+        let result: Result<(), ()> = supervise("test", "failing", async { Err(()) }).await;
+        assert!(result.is_err());
+        heartbeat("test", "beating");
+        let depth = queue("test_sink");
+        depth.increment();
+        depth.increment();
+        depth.decrement();
+        watermark("test_loader", 1_690_000_000);
+        processed_up_to("test_ingest", 1_690_000_000);
+        pending("test_ingest").set(3);
+
+        let report = report();
+        let tasks = &report.tasks["test"];
+        assert_eq!(TaskState::Failed, tasks["failing"].state);
+        assert!(tasks["beating"].last_heartbeat.is_some());
+        assert_eq!(1, report.queues["test_sink"]);
+        assert_eq!(1_690_000_000, report.watermarks["test_loader"]);
+        let ingest = &report.ingest["test_ingest"];
+        assert_eq!(Some(1_690_000_000), ingest.processed_up_to);
+        assert_eq!(3, ingest.pending);
+    }
+}
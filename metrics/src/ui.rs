@@ -0,0 +1,193 @@
+//! Read-only html pages served on the metrics endpoint, so on-call can
+//! inspect a running binary without database credentials.
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use once_cell::sync::Lazy;
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+pub type RenderResult = Result<String, Box<dyn std::error::Error + Send + Sync>>;
+pub type Render = Pin<Box<dyn Future<Output = RenderResult> + Send>>;
+
+static PAGES: Lazy<Mutex<BTreeMap<&'static str, Arc<dyn Page>>>> = Lazy::new(Default::default);
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }
+th { background: #eee; }
+"#;
+
+/// A page of the ui
+pub trait Page: Send + Sync + 'static {
+    /// Title of the page, its heading and its link in the index
+    fn title(&self) -> &'static str;
+
+    /// The html of the body of the page, below its heading
+    fn render(&self) -> Render;
+}
+
+/// A page rendered up front, for pages not waiting on anything
+pub fn ready(html: String) -> Render {
+    let result: RenderResult = Ok(html);
+    Box::pin(std::future::ready(result))
+}
+
+/// Serve `page` from `/ui/<path>`, replacing any page registered before at
+/// the same path, and link it from the index at `/ui`. Pages are not
+/// authenticated, only enable the ui on an endpoint reachable by operators
+/// alone
+pub fn register(path: &'static str, page: impl Page) {
+    pages().insert(path, Arc::new(page));
+}
+
+fn pages() -> std::sync::MutexGuard<'static, BTreeMap<&'static str, Arc<dyn Page>>> {
+    // pages are only ever inserted, a panic while holding the lock can't
+    // leave the map inconsistent
+    PAGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Escape `text` for html
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A table of `rows` under `headers`, with every cell escaped
+pub fn table<R>(headers: &[&str], rows: impl IntoIterator<Item = R>) -> String
+where
+    R: IntoIterator<Item = String>,
+{
+    let mut html = String::from("<table><tr>");
+    for header in headers {
+        html.push_str(&format!("<th>{}</th>", escape(header)));
+    }
+    html.push_str("</tr>");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            html.push_str(&format!("<td>{}</td>", escape(&cell)));
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn layout(title: &str, body: &str) -> String {
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>{STYLE}</style></head><body><p><a href=\"/ui\">index</a></p>\
+         <h1>{title}</h1>{body}</body></html>",
+        title = escape(title)
+    )
+}
+
+fn index() -> String {
+    let links: String = pages()
+        .iter()
+        .map(|(path, page)| {
+            let title = escape(page.title());
+            format!("<li><a href=\"/ui/{path}\">{title}</a></li>")
+        })
+        .collect();
+    layout("status", &format!("<ul>{links}</ul>"))
+}
+
+fn html_response(status: StatusCode, html: String) -> hyper::http::Result<Response<Body>> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "text/html; charset=utf-8")
+        .body(Body::from(html))
+}
+
+async fn render_page(path: &str) -> hyper::http::Result<Response<Body>> {
+    let page = pages().get(path).cloned();
+    let Some(page) = page else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty());
+    };
+    match page.render().await {
+        Ok(body) => html_response(StatusCode::OK, layout(page.title(), &body)),
+        Err(err) => {
+            tracing::warn!(path, ?err, "error rendering ui page");
+            let error = escape(&err.to_string());
+            let body = format!("<p>error rendering page: {error}</p>");
+            html_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                layout(page.title(), &body),
+            )
+        }
+    }
+}
+
+async fn handle(
+    request: Request<Body>,
+    metrics: PrometheusHandle,
+) -> Result<Response<Body>, Infallible> {
+    let path = request.uri().path();
+    let response = match (request.method(), path.strip_prefix("/ui")) {
+        (&Method::GET, Some("" | "/")) => html_response(StatusCode::OK, index()),
+        (&Method::GET, Some(page)) => render_page(page.trim_start_matches('/')).await,
+        _ => Response::builder()
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(Body::from(metrics.render())),
+    };
+    Ok(response.unwrap_or_else(|_| Response::new(Body::empty())))
+}
+
+/// Serve the prometheus scrape of `metrics` and the ui on `addr` until the
+/// process exits
+pub(crate) fn start_server(addr: SocketAddr, metrics: PrometheusHandle) -> crate::Result {
+    let server =
+        Server::try_bind(&addr)?.serve(make_service_fn(move |_| {
+            let metrics = metrics.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| handle(request, metrics.clone())))
+            }
+        }));
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!("metrics server error: {err:?}");
+        }
+    });
+    tracing::info!("metrics endpoint with ui listening on {addr}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_table_cells() {
+        let html = table(&["name"], [vec!["<b>&co</b>".to_string()]]);
+        assert_eq!(
+            "<table><tr><th>name</th></tr><tr><td>&lt;b&gt;&amp;co&lt;/b&gt;</td></tr></table>",
+            html
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// Services in `src/ext.rs` are not part of helium-proto. Their server and
+// client stubs are generated here from the prost messages defined there.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::ext::{input}"))
+        .output_type(format!("crate::ext::{output}"))
+        .codec_path("tonic::codec::ProstCodec")
+        .build()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let admin_keys = Service::builder()
+        .name("AdminKeys")
+        .package("helium.mobile_config.ext")
+        .method(method(
+            "list_keys",
+            "ListKeys",
+            "AdminListKeysReqV1",
+            "AdminListKeysResV1",
+        ))
+        .build();
+
+    let carrier_payers = Service::builder()
+        .name("CarrierPayers")
+        .package("helium.mobile_config.ext")
+        .method(method(
+            "payer",
+            "Payer",
+            "CarrierPayerReqV1",
+            "CarrierPayerResV1",
+        ))
+        .build();
+
+    let gateway_batch = Service::builder()
+        .name("GatewayBatch")
+        .package("helium.mobile_config.ext")
+        .method(method(
+            "info_batch",
+            "InfoBatch",
+            "GatewayInfoBatchReqV1",
+            "GatewayInfoBatchResV1",
+        ))
+        .build();
+
+    let radio_onboarding = Service::builder()
+        .name("RadioOnboarding")
+        .package("helium.mobile_config.ext")
+        .method(method(
+            "onboarding",
+            "Onboarding",
+            "RadioOnboardingReqV1",
+            "RadioOnboardingResV1",
+        ))
+        .build();
+
+    Builder::new().compile(&[admin_keys, carrier_payers, gateway_batch, radio_onboarding]);
+}
@@ -0,0 +1,9 @@
+create table carrier_payers (
+    carrier_key text primary key not null,
+    payer text not null,
+
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+
+select trigger_updated_at('carrier_payers');
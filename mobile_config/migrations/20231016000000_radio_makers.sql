@@ -0,0 +1,10 @@
+create table radio_makers (
+    address text primary key not null,
+    name text not null,
+    approved boolean not null default false,
+
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+
+select trigger_updated_at('radio_makers');
@@ -1,4 +1,5 @@
 use crate::{
+    ext::{self, AdminKeyV1, AdminListKeysReqV1, AdminListKeysResV1},
     key_cache::{self, CacheKeys, KeyCache},
     settings::Settings,
     telemetry, verify_public_key, GrpcResult, KeyRole,
@@ -13,12 +14,13 @@ use helium_proto::{
     Message,
 };
 use sqlx::{Pool, Postgres};
+use std::sync::Arc;
 use tokio::sync::watch;
 use tonic::{Request, Response, Status};
 
 pub struct AdminService {
     key_cache: KeyCache,
-    key_cache_updater: watch::Sender<CacheKeys>,
+    key_cache_updater: Arc<watch::Sender<CacheKeys>>,
     pool: Pool<Postgres>,
     signing_key: Keypair,
 }
@@ -27,7 +29,7 @@ impl AdminService {
     pub fn new(
         settings: &Settings,
         key_cache: KeyCache,
-        key_cache_updater: watch::Sender<CacheKeys>,
+        key_cache_updater: Arc<watch::Sender<CacheKeys>>,
         pool: Pool<Postgres>,
     ) -> Result<Self> {
         Ok(Self {
@@ -135,3 +137,35 @@ impl mobile_config::Admin for AdminService {
         Ok(Response::new(resp))
     }
 }
+
+#[tonic::async_trait]
+impl ext::admin_keys_server::AdminKeys for AdminService {
+    async fn list_keys(
+        &self,
+        request: Request<AdminListKeysReqV1>,
+    ) -> GrpcResult<AdminListKeysResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("admin", "list-keys");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_admin_request_signature(&signer, &request)?;
+
+        let mut keys = self.key_cache.get_keys();
+        keys.sort_by_key(|(pubkey, key_role)| (key_role.to_string(), pubkey.to_string()));
+
+        let mut resp = AdminListKeysResV1 {
+            keys: keys
+                .into_iter()
+                .map(|(pubkey, key_role)| AdminKeyV1 {
+                    pubkey: pubkey.into(),
+                    role: key_role.into(),
+                })
+                .collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        resp.signature = self.sign_response(&resp.encode_to_vec())?;
+        Ok(Response::new(resp))
+    }
+}
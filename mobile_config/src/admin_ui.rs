@@ -0,0 +1,45 @@
+//! Pages of the read-only admin ui: the key registry and the gateway info
+//! streams in progress.
+
+use crate::{key_cache::KeyCache, telemetry};
+use poc_metrics::ui::{self, Page, Render};
+
+pub fn register(key_cache: KeyCache) {
+    ui::register("keys", KeysPage { key_cache });
+    ui::register("streams", StreamsPage);
+}
+
+struct KeysPage {
+    key_cache: KeyCache,
+}
+
+impl Page for KeysPage {
+    fn title(&self) -> &'static str {
+        "key registry"
+    }
+
+    fn render(&self) -> Render {
+        let mut keys = self.key_cache.get_keys();
+        keys.sort_by_key(|(pubkey, key_role)| (key_role.to_string(), pubkey.to_string()));
+        let rows = keys
+            .into_iter()
+            .map(|(pubkey, key_role)| vec![key_role.to_string(), pubkey.to_string()]);
+        ui::ready(ui::table(&["role", "pubkey"], rows))
+    }
+}
+
+struct StreamsPage;
+
+impl Page for StreamsPage {
+    fn title(&self) -> &'static str {
+        "streams"
+    }
+
+    fn render(&self) -> Render {
+        let rows = [vec![
+            "gateway info".to_string(),
+            telemetry::info_streams().to_string(),
+        ]];
+        ui::ready(ui::table(&["stream", "subscribers"], rows))
+    }
+}
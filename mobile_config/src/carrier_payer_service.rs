@@ -0,0 +1,169 @@
+//! Mapping of carriers, identified by the key signing their sessions, to the
+//! payer whose DC are burned for their data transfer sessions.
+
+use crate::{
+    ext::{self, CarrierPayerReqV1, CarrierPayerResV1},
+    key_cache::KeyCache,
+    telemetry, verify_public_key, GrpcResult,
+};
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, PublicKeyBinary, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use tonic::{Request, Response, Status};
+
+pub struct CarrierPayerService {
+    key_cache: KeyCache,
+    pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl CarrierPayerService {
+    pub fn new(key_cache: KeyCache, pool: Pool<Postgres>, signing_key: Keypair) -> Self {
+        Self {
+            key_cache,
+            pool,
+            signing_key,
+        }
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::carrier_payers_server::CarrierPayers for CarrierPayerService {
+    async fn payer(&self, request: Request<CarrierPayerReqV1>) -> GrpcResult<CarrierPayerResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("carrier_payers", "payer");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.key_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+
+        let carrier_key = PublicKeyBinary::from(request.carrier_key);
+        let payer = db::fetch_payer(&self.pool, &carrier_key)
+            .await
+            .map_err(|err| {
+                tracing::error!(%carrier_key, ?err, "carrier payer lookup failed");
+                Status::internal("carrier payer lookup failed")
+            })?
+            .ok_or_else(|| Status::not_found(format!("carrier: {carrier_key}")))?;
+
+        let mut response = CarrierPayerResV1 {
+            carrier_key: carrier_key.into(),
+            payer: payer.into(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        response.signature = self.sign_response(&response.encode_to_vec())?;
+        Ok(Response::new(response))
+    }
+}
+
+pub mod db {
+    use helium_crypto::PublicKeyBinary;
+    use sqlx::Row;
+    use std::str::FromStr;
+
+    pub async fn fetch_payer(
+        db: impl sqlx::PgExecutor<'_>,
+        carrier_key: &PublicKeyBinary,
+    ) -> Result<Option<PublicKeyBinary>, sqlx::Error> {
+        let payer = sqlx::query_scalar::<_, String>(
+            r#" select payer from carrier_payers where carrier_key = $1 "#,
+        )
+        .bind(carrier_key.to_string())
+        .fetch_optional(db)
+        .await?;
+        Ok(payer.and_then(|payer| PublicKeyBinary::from_str(&payer).ok()))
+    }
+
+    pub async fn fetch_all(
+        db: impl sqlx::PgExecutor<'_>,
+    ) -> Result<Vec<(String, String)>, sqlx::Error> {
+        Ok(
+            sqlx::query(r#" select carrier_key, payer from carrier_payers order by carrier_key "#)
+                .fetch_all(db)
+                .await?
+                .into_iter()
+                .map(|row| (row.get("carrier_key"), row.get("payer")))
+                .collect(),
+        )
+    }
+
+    pub async fn set_payer(
+        db: impl sqlx::PgExecutor<'_>,
+        carrier_key: &PublicKeyBinary,
+        payer: &PublicKeyBinary,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            insert into carrier_payers (carrier_key, payer) values ($1, $2)
+            on conflict (carrier_key) do update set payer = EXCLUDED.payer
+            "#,
+        )
+        .bind(carrier_key.to_string())
+        .bind(payer.to_string())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_payer(
+        db: impl sqlx::PgExecutor<'_>,
+        carrier_key: &PublicKeyBinary,
+    ) -> Result<bool, sqlx::Error> {
+        let removed = sqlx::query(r#" delete from carrier_payers where carrier_key = $1 "#)
+            .bind(carrier_key.to_string())
+            .execute(db)
+            .await?
+            .rows_affected();
+        Ok(removed > 0)
+    }
+}
+
+/// Command line access to the carrier payer mappings
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List the payer of every carrier
+    List,
+    /// Set the payer debited for the sessions of a carrier
+    Set {
+        carrier_key: PublicKeyBinary,
+        payer: PublicKeyBinary,
+    },
+    /// Remove the payer of a carrier, debiting the payer named in its
+    /// sessions again
+    Remove { carrier_key: PublicKeyBinary },
+}
+
+impl Cmd {
+    pub async fn run(&self, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                for (carrier_key, payer) in db::fetch_all(pool).await? {
+                    println!("{carrier_key}: {payer}");
+                }
+            }
+            Self::Set { carrier_key, payer } => {
+                db::set_payer(pool, carrier_key, payer).await?;
+                println!("{carrier_key}: {payer}");
+            }
+            Self::Remove { carrier_key } => {
+                if db::remove_payer(pool, carrier_key).await? {
+                    println!("{carrier_key}: removed");
+                } else {
+                    println!("{carrier_key}: no payer set");
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use super::{ClientError, Settings, CACHE_EVICTION_FREQUENCY};
+use crate::ext::{carrier_payers_client::CarrierPayersClient, CarrierPayerReqV1};
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
+use helium_proto::{services::Channel, Message};
+use retainer::Cache;
+use std::{sync::Arc, time::Duration};
+
+#[derive(Clone)]
+pub struct CarrierPayerClient {
+    client: CarrierPayersClient<Channel>,
+    signing_key: Arc<Keypair>,
+    config_pubkey: PublicKey,
+    cache: Arc<Cache<PublicKeyBinary, Option<PublicKeyBinary>>>,
+    cache_ttl: Duration,
+}
+
+impl CarrierPayerClient {
+    pub fn from_settings(settings: &Settings) -> Result<Self, Box<helium_crypto::Error>> {
+        let cache = Arc::new(Cache::new());
+        let cloned_cache = cache.clone();
+        tokio::spawn(async move {
+            cloned_cache
+                .monitor(4, 0.25, CACHE_EVICTION_FREQUENCY)
+                .await
+        });
+
+        Ok(Self {
+            client: settings.connect_carrier_payers_client(),
+            signing_key: settings.signing_keypair()?,
+            config_pubkey: settings.config_pubkey()?,
+            cache_ttl: settings.cache_ttl(),
+            cache,
+        })
+    }
+
+    /// The payer mapped to the carrier signing sessions with `carrier_key`,
+    /// if any
+    pub async fn payer(
+        &self,
+        carrier_key: &PublicKeyBinary,
+    ) -> Result<Option<PublicKeyBinary>, ClientError> {
+        if let Some(cached_response) = self.cache.get(carrier_key).await {
+            return Ok(cached_response.value().clone());
+        }
+
+        let mut request = CarrierPayerReqV1 {
+            carrier_key: carrier_key.clone().into(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        tracing::debug!(
+            carrier_key = carrier_key.to_string(),
+            "fetching carrier payer"
+        );
+        let response = match self.client.clone().payer(request).await {
+            Ok(payer_res) => {
+                let response = payer_res.into_inner();
+                response.verify(&self.config_pubkey)?;
+                Some(response.payer.into())
+            }
+            Err(status) if status.code() == tonic::Code::NotFound => None,
+            Err(status) => Err(status)?,
+        };
+
+        self.cache
+            .insert(carrier_key.clone(), response.clone(), self.cache_ttl)
+            .await;
+
+        Ok(response)
+    }
+}
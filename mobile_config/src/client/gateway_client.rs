@@ -1,6 +1,10 @@
-use super::{ClientError, Settings, CACHE_EVICTION_FREQUENCY};
-use crate::gateway_info;
-use file_store::traits::MsgVerify;
+use super::{telemetry, ClientError, Settings, CACHE_EVICTION_FREQUENCY};
+use crate::{
+    ext::{gateway_batch_client, GatewayInfoBatchReqV1},
+    gateway_info::{self, GatewayInfoResolver},
+};
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
 use futures::stream::{self, StreamExt};
 use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
 use helium_proto::{
@@ -8,20 +12,55 @@ use helium_proto::{
     Message,
 };
 use retainer::Cache;
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Info of every gateway resolved, None for those not found
+pub type ResolvedGateways = HashMap<PublicKeyBinary, Option<gateway_info::GatewayInfo>>;
 
 #[derive(Clone)]
 pub struct GatewayClient {
     pub client: mobile_config::GatewayClient<Channel>,
+    batch_client: gateway_batch_client::GatewayBatchClient<Channel>,
+    /// The service answered a batch request as unimplemented, every gateway
+    /// is resolved on its own from then on
+    batch_unsupported: Arc<AtomicBool>,
     signing_key: Arc<Keypair>,
     config_pubkey: PublicKey,
     batch_size: u32,
     cache: Arc<Cache<PublicKeyBinary, Option<gateway_info::GatewayInfo>>>,
     cache_ttl: Duration,
+    /// Max number of gateways cached by the prewarm, None when disabled
+    prewarm_max_entries: Option<usize>,
+    prewarm_timeout: Duration,
+    #[cfg(feature = "fault-injection")]
+    faults: file_store::fault_injection::RpcSettings,
 }
 
 impl GatewayClient {
     pub fn from_settings(settings: &Settings) -> Result<Self, Box<helium_crypto::Error>> {
+        Ok(Self::new(
+            settings,
+            settings.connect_gateway_client(),
+            settings.connect_gateway_batch_client(),
+            settings.signing_keypair()?,
+            settings.config_pubkey()?,
+        ))
+    }
+
+    fn new(
+        settings: &Settings,
+        client: mobile_config::GatewayClient<Channel>,
+        batch_client: gateway_batch_client::GatewayBatchClient<Channel>,
+        signing_key: Arc<Keypair>,
+        config_pubkey: PublicKey,
+    ) -> Self {
         let cache = Arc::new(Cache::new());
         let cloned_cache = cache.clone();
         tokio::spawn(async move {
@@ -30,14 +69,178 @@ impl GatewayClient {
                 .await
         });
 
-        Ok(Self {
-            client: settings.connect_gateway_client(),
-            signing_key: settings.signing_keypair()?,
-            config_pubkey: settings.config_pubkey()?,
+        Self {
+            client,
+            batch_client,
+            batch_unsupported: Arc::new(AtomicBool::new(false)),
+            signing_key,
+            config_pubkey,
             batch_size: settings.batch_size,
             cache_ttl: settings.cache_ttl(),
             cache,
-        })
+            prewarm_max_entries: settings
+                .prewarm_cache
+                .then_some(settings.prewarm_max_entries),
+            prewarm_timeout: settings.prewarm_timeout,
+            #[cfg(feature = "fault-injection")]
+            faults: settings.faults.clone(),
+        }
+    }
+
+    /// Fill the cache with the info of gateways streamed from the config
+    /// service, up to the configured max, so consumers starting with a cold
+    /// cache don't send an info request for every gateway they come across.
+    /// Returns the number of gateways cached, none unless `prewarm_cache` is
+    /// set. Stops early, keeping what was cached, on shutdown or once
+    /// `prewarm_timeout` has passed
+    pub async fn prewarm(&self, shutdown: &triggered::Listener) -> Result<usize, ClientError> {
+        let Some(max_entries) = self.prewarm_max_entries else {
+            return Ok(0);
+        };
+        tracing::info!(max_entries, "prewarming gateway info cache");
+        let deadline = tokio::time::sleep(self.prewarm_timeout);
+        tokio::pin!(deadline);
+        let mut client = self.clone();
+        let mut gateways = tokio::select! {
+            biased;
+            _ = shutdown.clone() => return Ok(0),
+            _ = &mut deadline => {
+                tracing::warn!("gateway info cache prewarm timed out");
+                return Ok(0);
+            }
+            gateways = client.stream_gateways_info() => gateways?.take(max_entries),
+        };
+        let mut cached = 0;
+        loop {
+            let info = tokio::select! {
+                biased;
+                _ = shutdown.clone() => {
+                    tracing::info!(cached, "gateway info cache prewarm stopped by shutdown");
+                    return Ok(cached);
+                }
+                _ = &mut deadline => {
+                    tracing::warn!(cached, "gateway info cache prewarm timed out");
+                    return Ok(cached);
+                }
+                info = gateways.next() => match info {
+                    Some(info) => info,
+                    None => break,
+                },
+            };
+            // Spread the expiry of the prewarmed gateways over the second half
+            // of the ttl, so they aren't all requested again at once
+            let spread = 0.5 + 0.5 * cached as f64 / max_entries.max(1) as f64;
+            self.cache
+                .insert(
+                    info.address.clone(),
+                    Some(info),
+                    self.cache_ttl.mul_f64(spread),
+                )
+                .await;
+            cached += 1;
+        }
+        tracing::info!(cached, "prewarmed gateway info cache");
+        Ok(cached)
+    }
+
+    /// Resolve the info of every gateway in `addresses`, None for those not
+    /// found, with a single request per `batch_size` gateways not cached yet.
+    /// Every gateway resolved is cached. Falls back to resolving gateways one
+    /// at a time from services without batch support
+    pub async fn resolve_gateway_info_batch(
+        &self,
+        addresses: &[PublicKeyBinary],
+    ) -> Result<ResolvedGateways, ClientError> {
+        let mut resolved = ResolvedGateways::new();
+        let mut missing = HashSet::new();
+        for address in addresses {
+            let cached = self.cache.get(address).await;
+            telemetry::count_cache_lookup("info_batch", cached.is_some());
+            match cached {
+                Some(cached) => {
+                    resolved.insert(address.clone(), cached.value().clone());
+                }
+                None => {
+                    missing.insert(address.clone());
+                }
+            }
+        }
+        let missing: Vec<PublicKeyBinary> = missing.into_iter().collect();
+
+        for batch in missing.chunks(self.batch_size.max(1) as usize) {
+            let batch_resolved = match self.fetch_batch(batch).await? {
+                Some(batch_resolved) => batch_resolved,
+                None => {
+                    let mut batch_resolved = ResolvedGateways::new();
+                    for address in batch {
+                        let info = self.resolve_gateway_info(address).await?;
+                        batch_resolved.insert(address.clone(), info);
+                    }
+                    batch_resolved
+                }
+            };
+            resolved.extend(batch_resolved);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Fetch and cache the info of the gateways in `batch` with a single
+    /// request, None when the service does not support it
+    async fn fetch_batch(
+        &self,
+        batch: &[PublicKeyBinary],
+    ) -> Result<Option<ResolvedGateways>, ClientError> {
+        if self.batch_unsupported.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let mut request = GatewayInfoBatchReqV1 {
+            addresses: batch.iter().cloned().map(Into::into).collect(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        tracing::debug!(count = batch.len(), "fetching gateway info batch");
+        #[cfg(feature = "fault-injection")]
+        self.faults.inject("info_batch")?;
+        let start = Instant::now();
+        let response = match self.batch_client.clone().info_batch(request).await {
+            Ok(response) => {
+                telemetry::record_rpc_duration("info_batch", start, "ok");
+                response.into_inner()
+            }
+            Err(status) if status.code() == tonic::Code::Unimplemented => {
+                tracing::info!("gateway info batches unsupported, resolving gateways one by one");
+                self.batch_unsupported.store(true, Ordering::Relaxed);
+                return Ok(None);
+            }
+            Err(status) => {
+                telemetry::record_rpc_duration("info_batch", start, "error");
+                Err(status)?
+            }
+        };
+        response.verify(&self.config_pubkey).map_err(|err| {
+            telemetry::count_verification_failure("info_batch");
+            err
+        })?;
+
+        let mut resolved: ResolvedGateways = batch
+            .iter()
+            .map(|address| (address.clone(), None))
+            .collect();
+        for info in response.gateways {
+            let info = gateway_info::GatewayInfo::from(info);
+            resolved.insert(info.address.clone(), Some(info));
+        }
+        for (address, info) in &resolved {
+            self.cache
+                .insert(address.clone(), info.clone(), self.cache_ttl)
+                .await;
+        }
+
+        Ok(Some(resolved))
     }
 }
 
@@ -49,7 +252,9 @@ impl gateway_info::GatewayInfoResolver for GatewayClient {
         &self,
         address: &PublicKeyBinary,
     ) -> Result<Option<gateway_info::GatewayInfo>, Self::Error> {
-        if let Some(cached_response) = self.cache.get(address).await {
+        let cached = self.cache.get(address).await;
+        telemetry::count_cache_lookup("info", cached.is_some());
+        if let Some(cached_response) = cached {
             return Ok(cached_response.value().clone());
         }
 
@@ -60,14 +265,28 @@ impl gateway_info::GatewayInfoResolver for GatewayClient {
         };
         request.signature = self.signing_key.sign(&request.encode_to_vec())?;
         tracing::debug!(pubkey = address.to_string(), "fetching gateway info");
+        #[cfg(feature = "fault-injection")]
+        self.faults.inject("info")?;
+        let start = Instant::now();
         let response = match self.client.clone().info(request).await {
             Ok(info_res) => {
+                telemetry::record_rpc_duration("info", start, "ok");
                 let response = info_res.into_inner();
-                response.verify(&self.config_pubkey)?;
+                response.verify(&self.config_pubkey).map_err(|err| {
+                    telemetry::count_verification_failure("info");
+                    err
+                })?;
                 response.info.map(gateway_info::GatewayInfo::from)
             }
-            Err(status) if status.code() == tonic::Code::NotFound => None,
-            Err(status) => Err(status)?,
+            Err(status) if status.code() == tonic::Code::NotFound => {
+                telemetry::record_rpc_duration("info", start, "not_found");
+                telemetry::count_not_found("info");
+                None
+            }
+            Err(status) => {
+                telemetry::record_rpc_duration("info", start, "error");
+                Err(status)?
+            }
         };
 
         self.cache
@@ -87,18 +306,31 @@ impl gateway_info::GatewayInfoResolver for GatewayClient {
         };
         req.signature = self.signing_key.sign(&req.encode_to_vec())?;
         tracing::debug!("fetching gateway info stream");
+        #[cfg(feature = "fault-injection")]
+        self.faults.inject("info_stream")?;
         let pubkey = Arc::new(self.config_pubkey.clone());
-        let res_stream = self
-            .client
-            .info_stream(req)
-            .await?
+        let start = Instant::now();
+        let res_stream = match self.client.info_stream(req).await {
+            Ok(res_stream) => {
+                telemetry::record_rpc_duration("info_stream", start, "ok");
+                res_stream
+            }
+            Err(status) => {
+                telemetry::record_rpc_duration("info_stream", start, "error");
+                Err(status)?
+            }
+        };
+        let res_stream = res_stream
             .into_inner()
             .filter_map(|res| async move { res.ok() })
             .map(move |res| (res, pubkey.clone()))
             .filter_map(|(res, pubkey)| async move {
                 match res.verify(&pubkey) {
                     Ok(()) => Some(res),
-                    Err(_) => None,
+                    Err(_) => {
+                        telemetry::count_verification_failure("info_stream");
+                        None
+                    }
                 }
             })
             .flat_map(|res| stream::iter(res.gateways.into_iter()))
@@ -108,3 +340,302 @@ impl gateway_info::GatewayInfoResolver for GatewayClient {
         Ok(res_stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ext::{gateway_batch_server, GatewayInfoBatchResV1},
+        GrpcResult, GrpcStreamResult,
+    };
+    use helium_crypto::{KeyTag, KeyType, Network};
+    use helium_proto::services::{
+        mobile_config::{
+            GatewayInfo as GatewayInfoProto, GatewayInfoReqV1, GatewayInfoResV1,
+            GatewayInfoStreamReqV1, GatewayInfoStreamResV1, GatewayServer,
+        },
+        Endpoint,
+    };
+    use rand::rngs::OsRng;
+    use std::sync::atomic::AtomicUsize;
+    use tonic::{transport::Server, Request, Response, Status};
+
+    fn keypair() -> Keypair {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed25519,
+        };
+        Keypair::generate(key_tag, &mut OsRng)
+    }
+
+    fn gateway(byte: u8) -> PublicKeyBinary {
+        PublicKeyBinary::from(vec![byte; 33])
+    }
+
+    /// Config service knowing a fixed set of gateways, counting the requests
+    /// it is sent
+    #[derive(Clone)]
+    struct MockConfig {
+        signing_key: Arc<Keypair>,
+        known: Arc<Vec<PublicKeyBinary>>,
+        info_requests: Arc<AtomicUsize>,
+        batch_requests: Arc<AtomicUsize>,
+    }
+
+    impl MockConfig {
+        fn new(known: &[PublicKeyBinary]) -> Self {
+            Self {
+                signing_key: Arc::new(keypair()),
+                known: Arc::new(known.to_vec()),
+                info_requests: Arc::default(),
+                batch_requests: Arc::default(),
+            }
+        }
+
+        fn info(&self, address: &PublicKeyBinary) -> Option<GatewayInfoProto> {
+            self.known.contains(address).then(|| GatewayInfoProto {
+                address: address.clone().into(),
+                ..Default::default()
+            })
+        }
+
+        /// Serve the gateway service, along with the gateway batch service
+        /// unless `batches` is false, returning a client of it
+        async fn serve(self, batches: bool, prewarm: bool) -> GatewayClient {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("listener");
+            let addr = listener.local_addr().expect("listener address");
+            let incoming = stream::unfold(listener, |listener| async move {
+                let stream = listener.accept().await.map(|(stream, _)| stream);
+                Some((stream, listener))
+            });
+            let config_pubkey = self.signing_key.public_key().clone();
+            let router = Server::builder()
+                .add_service(GatewayServer::new(self.clone()))
+                .add_optional_service(
+                    batches.then(|| gateway_batch_server::GatewayBatchServer::new(self)),
+                );
+            tokio::spawn(router.serve_with_incoming(incoming));
+
+            let channel = Endpoint::from_shared(format!("http://{addr}"))
+                .expect("endpoint")
+                .connect_lazy();
+            let settings = Settings {
+                url: http::Uri::from_static("http://127.0.0.1"),
+                signing_keypair: String::new(),
+                config_pubkey: String::new(),
+                connect_timeout: Duration::from_secs(5),
+                rpc_timeout: Duration::from_secs(5),
+                batch_size: 2,
+                cache_ttl_in_secs: 60,
+                prewarm_cache: prewarm,
+                prewarm_max_entries: 10,
+                prewarm_timeout: Duration::from_secs(5),
+                #[cfg(feature = "fault-injection")]
+                faults: Default::default(),
+            };
+            GatewayClient::new(
+                &settings,
+                mobile_config::GatewayClient::new(channel.clone()),
+                gateway_batch_client::GatewayBatchClient::new(channel),
+                Arc::new(keypair()),
+                config_pubkey,
+            )
+        }
+
+        fn sign<M: Message>(&self, response: &M) -> Result<Vec<u8>, Status> {
+            self.signing_key
+                .sign(&response.encode_to_vec())
+                .map_err(|_| Status::internal("response signing error"))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl mobile_config::Gateway for MockConfig {
+        async fn info(&self, request: Request<GatewayInfoReqV1>) -> GrpcResult<GatewayInfoResV1> {
+            self.info_requests.fetch_add(1, Ordering::SeqCst);
+            let address = PublicKeyBinary::from(request.into_inner().address);
+            let info = self
+                .info(&address)
+                .ok_or_else(|| Status::not_found(address.to_string()))?;
+            let mut res = GatewayInfoResV1 {
+                info: Some(info),
+                timestamp: Utc::now().encode_timestamp(),
+                signer: self.signing_key.public_key().into(),
+                signature: vec![],
+            };
+            res.signature = self.sign(&res)?;
+            Ok(Response::new(res))
+        }
+
+        type info_streamStream = GrpcStreamResult<GatewayInfoStreamResV1>;
+        async fn info_stream(
+            &self,
+            _request: Request<GatewayInfoStreamReqV1>,
+        ) -> GrpcResult<Self::info_streamStream> {
+            let mut res = GatewayInfoStreamResV1 {
+                gateways: self.known.iter().filter_map(|a| self.info(a)).collect(),
+                timestamp: Utc::now().encode_timestamp(),
+                signer: self.signing_key.public_key().into(),
+                signature: vec![],
+            };
+            res.signature = self.sign(&res)?;
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            tx.send(Ok(res)).await.expect("stream response");
+            Ok(Response::new(GrpcStreamResult::new(rx)))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl gateway_batch_server::GatewayBatch for MockConfig {
+        async fn info_batch(
+            &self,
+            request: Request<GatewayInfoBatchReqV1>,
+        ) -> GrpcResult<GatewayInfoBatchResV1> {
+            self.batch_requests.fetch_add(1, Ordering::SeqCst);
+            let mut res = GatewayInfoBatchResV1 {
+                gateways: request
+                    .into_inner()
+                    .addresses
+                    .into_iter()
+                    .filter_map(|address| self.info(&address.into()))
+                    .collect(),
+                timestamp: Utc::now().encode_timestamp(),
+                signer: self.signing_key.public_key().into(),
+                signature: vec![],
+            };
+            res.signature = self.sign(&res)?;
+            Ok(Response::new(res))
+        }
+    }
+
+    fn found(resolved: &ResolvedGateways) -> HashSet<PublicKeyBinary> {
+        resolved
+            .iter()
+            .filter(|(_, info)| info.is_some())
+            .map(|(address, _)| address.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn batches_cache_gateways_not_found() {
+        let config = MockConfig::new(&[gateway(1), gateway(2)]);
+        let client = config.clone().serve(true, false).await;
+
+        let addresses = [gateway(1), gateway(2), gateway(3)];
+        let resolved = client.resolve_gateway_info_batch(&addresses).await.unwrap();
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(found(&resolved), HashSet::from([gateway(1), gateway(2)]));
+        // two batches of at most 2 gateways
+        assert_eq!(config.batch_requests.load(Ordering::SeqCst), 2);
+
+        // every gateway is cached, those not found too
+        let resolved = client.resolve_gateway_info_batch(&addresses).await.unwrap();
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(found(&resolved), HashSet::from([gateway(1), gateway(2)]));
+        assert!(client
+            .resolve_gateway_info(&gateway(3))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(config.batch_requests.load(Ordering::SeqCst), 2);
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn unimplemented_batches_fall_back_to_single_requests() {
+        let config = MockConfig::new(&[gateway(1)]);
+        let client = config.clone().serve(false, false).await;
+
+        let resolved = client
+            .resolve_gateway_info_batch(&[gateway(1), gateway(2)])
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(found(&resolved), HashSet::from([gateway(1)]));
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 2);
+        assert!(client.batch_unsupported.load(Ordering::Relaxed));
+
+        // gateways resolved one by one are cached too, found or not
+        let resolved = client
+            .resolve_gateway_info_batch(&[gateway(1), gateway(2), gateway(3)])
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(found(&resolved), HashSet::from([gateway(1)]));
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn prewarm_caches_streamed_gateways() {
+        let config = MockConfig::new(&[gateway(1), gateway(2)]);
+        let (_trigger, shutdown) = triggered::trigger();
+
+        let client = config.clone().serve(true, false).await;
+        assert_eq!(client.prewarm(&shutdown).await.unwrap(), 0);
+
+        let client = config.clone().serve(true, true).await;
+        assert_eq!(client.prewarm(&shutdown).await.unwrap(), 2);
+        assert!(client
+            .resolve_gateway_info(&gateway(1))
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn prewarm_stops_on_shutdown_and_timeout() {
+        let config = MockConfig::new(&[gateway(1), gateway(2)]);
+        let mut client = config.clone().serve(true, true).await;
+
+        let (trigger, shutdown) = triggered::trigger();
+        trigger.trigger();
+        assert_eq!(client.prewarm(&shutdown).await.unwrap(), 0);
+
+        let (_trigger, shutdown) = triggered::trigger();
+        client.prewarm_timeout = Duration::ZERO;
+        assert_eq!(client.prewarm(&shutdown).await.unwrap(), 0);
+
+        // nothing was cached
+        assert!(client
+            .resolve_gateway_info(&gateway(1))
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[tokio::test]
+    async fn injected_faults_fail_requests_unsent() {
+        let config = MockConfig::new(&[gateway(1)]);
+        let mut client = config.clone().serve(true, false).await;
+        client.faults = file_store::fault_injection::RpcSettings {
+            error_rates: HashMap::from([
+                ("info".to_string(), 1.0),
+                ("info_batch".to_string(), 1.0),
+            ]),
+        };
+
+        assert!(matches!(
+            client.resolve_gateway_info(&gateway(1)).await,
+            Err(ClientError::GrpcError(status)) if status.code() == tonic::Code::Unavailable
+        ));
+        assert!(client
+            .resolve_gateway_info_batch(&[gateway(1)])
+            .await
+            .is_err());
+        assert_eq!(config.info_requests.load(Ordering::SeqCst), 0);
+        assert_eq!(config.batch_requests.load(Ordering::SeqCst), 0);
+
+        // failures aren't cached, the gateway resolves once the faults stop
+        client.faults = Default::default();
+        assert!(client
+            .resolve_gateway_info(&gateway(1))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
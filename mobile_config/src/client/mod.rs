@@ -1,13 +1,18 @@
 pub mod authorization_client;
+pub mod carrier_payer_client;
 pub mod entity_client;
 pub mod gateway_client;
+pub mod radio_onboarding_client;
 mod settings;
+mod telemetry;
 
 use std::time::Duration;
 
 pub use authorization_client::AuthorizationClient;
+pub use carrier_payer_client::CarrierPayerClient;
 pub use entity_client::EntityClient;
 pub use gateway_client::GatewayClient;
+pub use radio_onboarding_client::RadioOnboardingClient;
 pub use settings::Settings;
 
 const CACHE_EVICTION_FREQUENCY: Duration = Duration::from_secs(60 * 60);
@@ -0,0 +1,98 @@
+use super::{ClientError, Settings, CACHE_EVICTION_FREQUENCY};
+use crate::ext::{radio_onboarding_client, RadioOnboardingReqV1};
+use chrono::{DateTime, TimeZone, Utc};
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign};
+use helium_proto::{services::Channel, Message};
+use retainer::Cache;
+use std::{sync::Arc, time::Duration};
+
+/// The onboarding record of a radio's hotspot
+#[derive(Clone, Debug)]
+pub struct RadioOnboarding {
+    pub onboarded_at: Option<DateTime<Utc>>,
+    /// Address of the maker account, None when not known
+    pub maker: Option<String>,
+    /// Name of the maker in the registry, None when not registered
+    pub maker_name: Option<String>,
+    pub maker_approved: bool,
+}
+
+#[derive(Clone)]
+pub struct RadioOnboardingClient {
+    client: radio_onboarding_client::RadioOnboardingClient<Channel>,
+    signing_key: Arc<Keypair>,
+    config_pubkey: PublicKey,
+    cache: Arc<Cache<PublicKeyBinary, Option<RadioOnboarding>>>,
+    cache_ttl: Duration,
+}
+
+impl RadioOnboardingClient {
+    pub fn from_settings(settings: &Settings) -> Result<Self, Box<helium_crypto::Error>> {
+        let cache = Arc::new(Cache::new());
+        let cloned_cache = cache.clone();
+        tokio::spawn(async move {
+            cloned_cache
+                .monitor(4, 0.25, CACHE_EVICTION_FREQUENCY)
+                .await
+        });
+
+        Ok(Self {
+            client: settings.connect_radio_onboarding_client(),
+            signing_key: settings.signing_keypair()?,
+            config_pubkey: settings.config_pubkey()?,
+            cache_ttl: settings.cache_ttl(),
+            cache,
+        })
+    }
+
+    /// The onboarding record of the hotspot `hotspot_key`, None when it
+    /// isn't onboarded
+    pub async fn resolve_onboarding(
+        &self,
+        hotspot_key: &PublicKeyBinary,
+    ) -> Result<Option<RadioOnboarding>, ClientError> {
+        if let Some(cached_response) = self.cache.get(hotspot_key).await {
+            return Ok(cached_response.value().clone());
+        }
+
+        let mut request = RadioOnboardingReqV1 {
+            hotspot_key: hotspot_key.clone().into(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        tracing::debug!(
+            hotspot_key = hotspot_key.to_string(),
+            "fetching radio onboarding"
+        );
+        let response = match self.client.clone().onboarding(request).await {
+            Ok(onboarding_res) => {
+                let response = onboarding_res.into_inner();
+                response.verify(&self.config_pubkey)?;
+                Some(RadioOnboarding {
+                    onboarded_at: match response.onboarded_at {
+                        0 => None,
+                        timestamp => Utc.timestamp_opt(timestamp as i64, 0).single(),
+                    },
+                    maker: non_empty(response.maker),
+                    maker_name: non_empty(response.maker_name),
+                    maker_approved: response.maker_approved,
+                })
+            }
+            Err(status) if status.code() == tonic::Code::NotFound => None,
+            Err(status) => Err(status)?,
+        };
+
+        self.cache
+            .insert(hotspot_key.clone(), response.clone(), self.cache_ttl)
+            .await;
+
+        Ok(response)
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
@@ -11,25 +11,46 @@ pub struct Settings {
     pub signing_keypair: String,
     /// B58 encoded public key of the mobile config server for verification
     pub config_pubkey: String,
-    /// Connect timeout for the mobile config client in seconds. Default 5
-    #[serde(default = "default_connect_timeout")]
-    pub connect_timeout: u64,
-    /// RPC timeout for mobile config client in seconds. Default 5
-    #[serde(default = "default_rpc_timeout")]
-    pub rpc_timeout: u64,
+    /// Connect timeout for the mobile config client. Default 5s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_connect_timeout"
+    )]
+    pub connect_timeout: Duration,
+    /// RPC timeout for mobile config client. Default 5s
+    #[serde(with = "settings_loader::duration", default = "default_rpc_timeout")]
+    pub rpc_timeout: Duration,
     /// Batch size for hotspot metadata stream results. Default 100
     #[serde(default = "default_batch_size")]
     pub batch_size: u32,
     #[serde(default = "default_cache_ttl_in_secs")]
     pub cache_ttl_in_secs: u64,
+    /// Fill the gateway info cache from the gateway info stream at startup,
+    /// before verifying anything. Default false
+    #[serde(default)]
+    pub prewarm_cache: bool,
+    /// Max number of gateways cached by the prewarm. Default 500_000
+    #[serde(default = "default_prewarm_max_entries")]
+    pub prewarm_max_entries: usize,
+    /// How long until the prewarm stops, keeping the gateways cached so far.
+    /// Default 5m
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_prewarm_timeout"
+    )]
+    pub prewarm_timeout: Duration,
+    /// Rpc failures injected for testing. Default none
+    #[cfg(feature = "fault-injection")]
+    #[serde(default)]
+    pub faults: file_store::fault_injection::RpcSettings,
 }
 
-pub fn default_connect_timeout() -> u64 {
-    5
+pub fn default_connect_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
-pub fn default_rpc_timeout() -> u64 {
-    5
+pub fn default_rpc_timeout() -> Duration {
+    Duration::from_secs(5)
 }
 
 pub fn default_batch_size() -> u32 {
@@ -40,6 +61,14 @@ pub fn default_cache_ttl_in_secs() -> u64 {
     60 * 60
 }
 
+pub fn default_prewarm_max_entries() -> usize {
+    500_000
+}
+
+pub fn default_prewarm_timeout() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
 impl Settings {
     pub fn connect_gateway_client(&self) -> mobile_config::GatewayClient<Channel> {
         let channel = connect_channel(self);
@@ -56,11 +85,31 @@ impl Settings {
         mobile_config::EntityClient::new(channel)
     }
 
+    pub fn connect_carrier_payers_client(
+        &self,
+    ) -> crate::ext::carrier_payers_client::CarrierPayersClient<Channel> {
+        let channel = connect_channel(self);
+        crate::ext::carrier_payers_client::CarrierPayersClient::new(channel)
+    }
+
+    pub fn connect_gateway_batch_client(
+        &self,
+    ) -> crate::ext::gateway_batch_client::GatewayBatchClient<Channel> {
+        let channel = connect_channel(self);
+        crate::ext::gateway_batch_client::GatewayBatchClient::new(channel)
+    }
+
+    pub fn connect_radio_onboarding_client(
+        &self,
+    ) -> crate::ext::radio_onboarding_client::RadioOnboardingClient<Channel> {
+        let channel = connect_channel(self);
+        crate::ext::radio_onboarding_client::RadioOnboardingClient::new(channel)
+    }
+
     pub fn signing_keypair(
         &self,
     ) -> Result<Arc<helium_crypto::Keypair>, Box<helium_crypto::Error>> {
-        let data = std::fs::read(&self.signing_keypair).map_err(helium_crypto::Error::from)?;
-        Ok(Arc::new(helium_crypto::Keypair::try_from(&data[..])?))
+        Ok(Arc::new(settings_loader::keypair(&self.signing_keypair)?))
     }
 
     pub fn config_pubkey(&self) -> Result<helium_crypto::PublicKey, helium_crypto::Error> {
@@ -70,11 +119,20 @@ impl Settings {
     pub fn cache_ttl(&self) -> std::time::Duration {
         std::time::Duration::from_secs(self.cache_ttl_in_secs)
     }
+
+    /// Check the config server accepts connections, for preflight checks
+    pub async fn check_reachable(&self) -> Result<(), tonic::transport::Error> {
+        Endpoint::from(self.url.clone())
+            .connect_timeout(self.connect_timeout)
+            .connect()
+            .await
+            .map(|_| ())
+    }
 }
 
 fn connect_channel(settings: &Settings) -> Channel {
     Endpoint::from(settings.url.clone())
-        .connect_timeout(Duration::from_secs(settings.connect_timeout))
-        .timeout(Duration::from_secs(settings.rpc_timeout))
+        .connect_timeout(settings.connect_timeout)
+        .timeout(settings.rpc_timeout)
         .connect_lazy()
 }
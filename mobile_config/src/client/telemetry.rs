@@ -0,0 +1,33 @@
+//! Metrics of the mobile config clients labelled with the rpc, so operators
+//! can alert on a degrading config service.
+
+use std::time::Instant;
+
+const CACHE_METRIC: &str = "mobile_config_client_cache";
+const NOT_FOUND_METRIC: &str = "mobile_config_client_not_found";
+const VERIFICATION_FAILURE_METRIC: &str = "mobile_config_client_verification_failure";
+const RPC_DURATION_METRIC: &str = "mobile_config_client_rpc_duration";
+
+pub fn count_cache_lookup(rpc: &'static str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    metrics::increment_counter!(CACHE_METRIC, "rpc" => rpc, "result" => result);
+}
+
+pub fn count_not_found(rpc: &'static str) {
+    metrics::increment_counter!(NOT_FOUND_METRIC, "rpc" => rpc);
+}
+
+pub fn count_verification_failure(rpc: &'static str) {
+    metrics::increment_counter!(VERIFICATION_FAILURE_METRIC, "rpc" => rpc);
+}
+
+/// Record the latency of an rpc started at `start`, labelled with its
+/// `result`: `ok`, `not_found` or `error`
+pub fn record_rpc_duration(rpc: &'static str, start: Instant, result: &'static str) {
+    metrics::histogram!(
+        RPC_DURATION_METRIC,
+        start.elapsed(),
+        "rpc" => rpc,
+        "result" => result
+    );
+}
@@ -0,0 +1,153 @@
+//! Protocol messages and services of the config service that are not part of
+//! helium-proto, with stubs generated by the build script.
+
+use file_store::impl_msg_verify;
+use helium_proto::services::mobile_config::{AdminKeyRole, GatewayInfo};
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.mobile_config.ext.AdminKeys.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.mobile_config.ext.CarrierPayers.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.mobile_config.ext.GatewayBatch.rs"
+));
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.mobile_config.ext.RadioOnboarding.rs"
+));
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AdminListKeysReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AdminKeyV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pubkey: Vec<u8>,
+    #[prost(enumeration = "AdminKeyRole", tag = "2")]
+    pub role: i32,
+}
+
+/// Every registered key along with the admin key from the service settings
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AdminListKeysResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub keys: Vec<AdminKeyV1>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CarrierPayerReqV1 {
+    /// Key the carrier signs its data transfer sessions with
+    #[prost(bytes = "vec", tag = "1")]
+    pub carrier_key: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// The payer whose DC are burned for the data transfer sessions of a carrier
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CarrierPayerResV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub carrier_key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub payer: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RadioOnboardingReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot_key: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// The onboarding record of a hotspot and the maker which onboarded it
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RadioOnboardingResV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hotspot_key: Vec<u8>,
+    /// Unix timestamp in seconds, zero when not known
+    #[prost(uint64, tag = "2")]
+    pub onboarded_at: u64,
+    /// B58 address of the maker account, empty when not known
+    #[prost(string, tag = "3")]
+    pub maker: String,
+    /// Name of the maker in the maker registry, empty when not registered
+    #[prost(string, tag = "4")]
+    pub maker_name: String,
+    #[prost(bool, tag = "5")]
+    pub maker_approved: bool,
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub signature: Vec<u8>,
+}
+
+/// Info of the gateways at `addresses`, up to the service batch limit
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayInfoBatchReqV1 {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub addresses: Vec<Vec<u8>>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Info of every requested gateway that was found, gateways not found are
+/// left out
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayInfoBatchResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub gateways: Vec<GatewayInfo>,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+impl_msg_verify!(AdminListKeysReqV1, signature);
+impl_msg_verify!(AdminListKeysResV1, signature);
+impl_msg_verify!(CarrierPayerReqV1, signature);
+impl_msg_verify!(CarrierPayerResV1, signature);
+impl_msg_verify!(GatewayInfoBatchReqV1, signature);
+impl_msg_verify!(GatewayInfoBatchResV1, signature);
+impl_msg_verify!(RadioOnboardingReqV1, signature);
+impl_msg_verify!(RadioOnboardingResV1, signature);
@@ -91,6 +91,24 @@ pub(crate) mod db {
             .await?)
     }
 
+    pub async fn get_info_batch(
+        db: impl PgExecutor<'_>,
+        addresses: &[PublicKeyBinary],
+    ) -> anyhow::Result<Vec<GatewayInfo>> {
+        let entity_keys = addresses
+            .iter()
+            .map(|address| bs58::decode(address.to_string()).into_vec())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut query: sqlx::QueryBuilder<sqlx::Postgres> =
+            sqlx::QueryBuilder::new(GET_METADATA_SQL);
+        query.push(" where kta.entity_key = any($1) ");
+        Ok(query
+            .build_query_as::<GatewayInfo>()
+            .bind(entity_keys)
+            .fetch_all(db)
+            .await?)
+    }
+
     pub fn all_info_stream<'a>(
         db: impl PgExecutor<'a> + 'a,
     ) -> impl Stream<Item = GatewayInfo> + 'a {
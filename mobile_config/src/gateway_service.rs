@@ -1,4 +1,5 @@
 use crate::{
+    ext::{self, GatewayInfoBatchReqV1, GatewayInfoBatchResV1},
     gateway_info::{self, GatewayInfo},
     key_cache::KeyCache,
     telemetry, verify_public_key, GrpcResult, GrpcStreamResult,
@@ -20,6 +21,9 @@ use sqlx::{Pool, Postgres};
 use std::sync::Arc;
 use tonic::{Request, Response, Status};
 
+/// Max number of gateways resolved by a single `info_batch` request
+const MAX_INFO_BATCH: usize = 1000;
+
 pub struct GatewayService {
     key_cache: KeyCache,
     metadata_pool: Pool<Postgres>,
@@ -114,13 +118,60 @@ impl mobile_config::Gateway for GatewayService {
         let (tx, rx) = tokio::sync::mpsc::channel(100);
 
         tokio::spawn(async move {
-            stream_all_gateways_info(&pool, tx.clone(), signing_key.clone(), batch_size).await
+            telemetry::info_stream_start();
+            let result =
+                stream_all_gateways_info(&pool, tx.clone(), signing_key.clone(), batch_size).await;
+            telemetry::info_stream_end();
+            result
         });
 
         Ok(Response::new(GrpcStreamResult::new(rx)))
     }
 }
 
+#[tonic::async_trait]
+impl ext::gateway_batch_server::GatewayBatch for GatewayService {
+    async fn info_batch(
+        &self,
+        request: Request<GatewayInfoBatchReqV1>,
+    ) -> GrpcResult<GatewayInfoBatchResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway", "info-batch");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.verify_request_signature(&signer, &request)?;
+
+        if request.addresses.len() > MAX_INFO_BATCH {
+            return Err(Status::invalid_argument(format!(
+                "batch exceeds {MAX_INFO_BATCH} addresses"
+            )));
+        }
+        let addresses: Vec<PublicKeyBinary> = request
+            .addresses
+            .into_iter()
+            .map(PublicKeyBinary::from)
+            .collect();
+        tracing::debug!(count = addresses.len(), "fetching gateway info batch");
+
+        let gateways = gateway_info::db::get_info_batch(&self.metadata_pool, &addresses)
+            .await
+            .map_err(|_| Status::internal("error fetching gateway info"))?
+            .into_iter()
+            .map(|info| info.try_into())
+            .collect::<Result<Vec<mobile_config::GatewayInfo>, _>>()
+            .map_err(|_| Status::internal("error serializing gateway info"))?;
+
+        let mut res = GatewayInfoBatchResV1 {
+            gateways,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        res.signature = self.sign_response(&res.encode_to_vec())?;
+        Ok(Response::new(res))
+    }
+}
+
 async fn stream_all_gateways_info(
     pool: &Pool<Postgres>,
     tx: tokio::sync::mpsc::Sender<Result<GatewayInfoStreamResV1, Status>>,
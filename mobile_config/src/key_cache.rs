@@ -2,7 +2,8 @@ use crate::{settings::Settings, KeyRole};
 use anyhow::anyhow;
 use file_store::traits::MsgVerify;
 use helium_crypto::{PublicKey, PublicKeyBinary};
-use std::collections::HashSet;
+use sqlx::{Pool, Postgres};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 use tokio::sync::watch;
 
 pub type CacheKeys = HashSet<(PublicKey, KeyRole)>;
@@ -95,6 +96,61 @@ impl KeyCache {
     }
 }
 
+/// Reloads the registered keys on an interval so that keys added or removed
+/// by other instances of the service, or directly in the database, take
+/// effect without a restart
+pub struct KeyCacheRefresher {
+    pool: Pool<Postgres>,
+    config_admin: PublicKey,
+    cache_sender: Arc<watch::Sender<CacheKeys>>,
+    refresh_interval: Duration,
+}
+
+impl KeyCacheRefresher {
+    pub fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        cache_sender: Arc<watch::Sender<CacheKeys>>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool,
+            config_admin: settings.admin_pubkey()?,
+            cache_sender,
+            refresh_interval: settings.key_cache_refresh_interval,
+        })
+    }
+
+    pub async fn run(self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting key cache refresher");
+        let mut trigger = tokio::time::interval(self.refresh_interval);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = trigger.tick() => match self.refresh().await {
+                    Ok(()) => (),
+                    Err(err) => tracing::warn!(?err, "failed to refresh key cache"),
+                }
+            }
+        }
+        tracing::info!("stopping key cache refresher");
+        Ok(())
+    }
+
+    async fn refresh(&self) -> anyhow::Result<()> {
+        let mut stored_keys = db::fetch_stored_keys(&self.pool).await?;
+        stored_keys.insert((self.config_admin.clone(), KeyRole::Administrator));
+        self.cache_sender.send_if_modified(|cache| {
+            if *cache == stored_keys {
+                return false;
+            }
+            tracing::info!(keys = stored_keys.len(), "registered keys changed");
+            *cache = stored_keys;
+            true
+        });
+        Ok(())
+    }
+}
+
 pub(crate) mod db {
     use super::{CacheKeys, KeyRole, PublicKey, PublicKeyBinary};
     use sqlx::Row;
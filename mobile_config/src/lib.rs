@@ -1,23 +1,26 @@
 use helium_crypto::PublicKey;
 use helium_proto::services::mobile_config::AdminKeyRole as ProtoKeyRole;
 use serde::Serialize;
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
+use tonic::Status;
 
 pub mod admin_service;
+pub mod admin_ui;
 pub mod authorization_service;
+pub mod carrier_payer_service;
 pub mod client;
 pub mod entity_service;
+pub mod ext;
 pub mod gateway_info;
 pub mod gateway_service;
 pub mod key_cache;
+pub mod proxy;
+pub mod radio_onboarding_service;
 pub mod settings;
 pub mod telemetry;
 
 pub use client::{GatewayClient, Settings as ClientSettings};
 
-pub type GrpcResult<T> = Result<Response<T>, Status>;
-pub type GrpcStreamResult<T> = ReceiverStream<Result<T, Status>>;
+pub use file_store::grpc::{GrpcResult, GrpcStreamResult};
 
 pub fn verify_public_key(bytes: &[u8]) -> Result<PublicKey, Status> {
     PublicKey::try_from(bytes).map_err(|_| Status::invalid_argument("invalid public key"))
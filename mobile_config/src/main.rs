@@ -5,11 +5,23 @@ use helium_proto::services::mobile_config::{
     AdminServer, AuthorizationServer, EntityServer, GatewayServer,
 };
 use mobile_config::{
-    admin_service::AdminService, authorization_service::AuthorizationService,
-    entity_service::EntityService, gateway_service::GatewayService, key_cache::KeyCache,
+    admin_service::AdminService,
+    admin_ui,
+    authorization_service::AuthorizationService,
+    carrier_payer_service::{self, CarrierPayerService},
+    entity_service::EntityService,
+    ext::{
+        admin_keys_server::AdminKeysServer, carrier_payers_server::CarrierPayersServer,
+        gateway_batch_server::GatewayBatchServer, radio_onboarding_server::RadioOnboardingServer,
+    },
+    gateway_service::GatewayService,
+    key_cache::{KeyCache, KeyCacheRefresher},
+    proxy::{self, GatewayProxy},
+    radio_onboarding_service::{self, MakerSync, RadioOnboardingService},
     settings::Settings,
 };
-use std::{path::PathBuf, time::Duration};
+use poc_metrics::preflight::Preflight;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio::signal;
 use tonic::transport;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -30,20 +42,49 @@ pub struct Cli {
 
 impl Cli {
     pub async fn run(self) -> Result<()> {
-        let settings = Settings::new(self.config)?;
-        self.cmd.run(settings).await
+        self.cmd.run(self.config).await
     }
 }
 
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Daemon),
+    /// Manage the payers debited for the data transfer sessions of carriers
+    #[clap(subcommand)]
+    CarrierPayers(carrier_payer_service::Cmd),
+    /// Manage the registry of the makers onboarding radios
+    #[clap(subcommand)]
+    RadioMakers(radio_onboarding_service::Cmd),
+    Proxy(Proxy),
 }
 
 impl Cmd {
-    pub async fn run(&self, settings: Settings) -> Result<()> {
+    pub async fn run(&self, config: Option<PathBuf>) -> Result<()> {
         match self {
-            Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Server(cmd) => cmd.run(&Settings::new(config)?).await,
+            Self::CarrierPayers(cmd) => {
+                let settings = Settings::new(config)?;
+                let (_shutdown_trigger, shutdown) = triggered::trigger();
+                let (pool, _db_join_handle) = settings
+                    .database
+                    .connect("mobile-config-store", shutdown)
+                    .await?;
+                cmd.run(&pool).await
+            }
+            Self::RadioMakers(cmd) => {
+                let settings = Settings::new(config)?;
+                let (_shutdown_trigger, shutdown) = triggered::trigger();
+                let (pool, _db_join_handle) = settings
+                    .database
+                    .connect("mobile-config-store", shutdown.clone())
+                    .await?;
+                let (metadata_pool, _md_join_handle) = settings
+                    .metadata
+                    .connect("mobile-config-metadata", shutdown)
+                    .await?;
+                cmd.run(&pool, &metadata_pool).await
+            }
+            Self::Proxy(cmd) => cmd.run(&proxy::Settings::new(config)?).await,
         }
     }
 }
@@ -76,7 +117,6 @@ impl Daemon {
             .database
             .connect("mobile-config-store", shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
 
         // Create on-chain metadata pool
         let (metadata_pool, md_pool_handle) = settings
@@ -84,37 +124,85 @@ impl Daemon {
             .connect("mobile-config-metadata", shutdown_listener.clone())
             .await?;
 
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check(
+                "metadata database",
+                db_store::preflight::check_connection(&metadata_pool),
+            )
+            .await;
+        preflight.check_result("signing keypair", settings.signing_keypair());
+        preflight.finish()?;
+
+        sqlx::migrate!().run(&pool).await?;
+
         let listen_addr = settings.listen_addr()?;
 
         let (key_cache_updater, key_cache) = KeyCache::new(settings, &pool).await?;
+        let key_cache_updater = Arc::new(key_cache_updater);
+        let key_cache_refresher =
+            KeyCacheRefresher::new(settings, pool.clone(), key_cache_updater.clone())?;
+        if settings.metrics.ui {
+            admin_ui::register(key_cache.clone());
+        }
 
-        let admin_svc =
-            AdminService::new(settings, key_cache.clone(), key_cache_updater, pool.clone())?;
-        let gateway_svc = GatewayService::new(
+        let admin_svc = Arc::new(AdminService::new(
+            settings,
+            key_cache.clone(),
+            key_cache_updater,
+            pool.clone(),
+        )?);
+        let gateway_svc = Arc::new(GatewayService::new(
             key_cache.clone(),
             metadata_pool.clone(),
             settings.signing_keypair()?,
-        );
+        ));
         let auth_svc = AuthorizationService::new(key_cache.clone(), settings.signing_keypair()?);
         let entity_svc = EntityService::new(
             key_cache.clone(),
             metadata_pool.clone(),
             settings.signing_keypair()?,
         );
+        let carrier_payer_svc =
+            CarrierPayerService::new(key_cache.clone(), pool.clone(), settings.signing_keypair()?);
+        let radio_onboarding_svc = RadioOnboardingService::new(
+            key_cache.clone(),
+            pool.clone(),
+            metadata_pool.clone(),
+            settings.signing_keypair()?,
+        );
+        let maker_sync = MakerSync::new(
+            pool.clone(),
+            metadata_pool.clone(),
+            settings.maker_sync_interval,
+        );
 
         let server = transport::Server::builder()
             .http2_keepalive_interval(Some(Duration::from_secs(250)))
             .http2_keepalive_timeout(Some(Duration::from_secs(60)))
-            .add_service(AdminServer::new(admin_svc))
-            .add_service(GatewayServer::new(gateway_svc))
+            .add_service(AdminServer::from_arc(admin_svc.clone()))
+            .add_service(AdminKeysServer::from_arc(admin_svc))
+            .add_service(GatewayServer::from_arc(gateway_svc.clone()))
+            .add_service(GatewayBatchServer::from_arc(gateway_svc))
             .add_service(AuthorizationServer::new(auth_svc))
             .add_service(EntityServer::new(entity_svc))
-            .serve_with_shutdown(listen_addr, shutdown_listener)
+            .add_service(CarrierPayersServer::new(carrier_payer_svc))
+            .add_service(RadioOnboardingServer::new(radio_onboarding_svc))
+            .serve_with_shutdown(listen_addr, shutdown_listener.clone())
             .map_err(Error::from);
 
         tokio::try_join!(
             pool_handle.map_err(Error::from),
             md_pool_handle.map_err(Error::from),
+            key_cache_refresher.run(shutdown_listener.clone()),
+            maker_sync.run(shutdown_listener),
             server,
         )?;
 
@@ -122,6 +210,55 @@ impl Daemon {
     }
 }
 
+/// Serve the gateway info apis from a cache in front of an upstream config
+/// service, without database access
+#[derive(Debug, clap::Args)]
+pub struct Proxy;
+
+impl Proxy {
+    pub async fn run(&self, settings: &proxy::Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        // Configure shutdown trigger
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigterm.recv() => shutdown_trigger.trigger(),
+                _ = signal::ctrl_c() => shutdown_trigger.trigger(),
+            }
+        });
+
+        // Install prometheus metrics exporter
+        poc_metrics::start_metrics(&settings.metrics)?;
+
+        // Check the upstream before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check("upstream config service", settings.check_upstream())
+            .await;
+        preflight.check_result("upstream pubkey", settings.upstream_pubkey());
+        preflight.finish()?;
+
+        let listen_addr = settings.listen_addr()?;
+        let gateway_proxy = GatewayProxy::new(settings)?;
+        tracing::debug!("listening on {listen_addr}");
+        tracing::debug!("proxying {}", settings.upstream);
+
+        transport::Server::builder()
+            .http2_keepalive_interval(Some(Duration::from_secs(250)))
+            .http2_keepalive_timeout(Some(Duration::from_secs(60)))
+            .add_service(GatewayServer::new(gateway_proxy))
+            .serve_with_shutdown(listen_addr, shutdown_listener)
+            .await?;
+
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -0,0 +1,179 @@
+//! Read-only caching proxy of the `gateway` apis, needing no database access
+//! so it can be deployed close to the oracles hammering it.
+
+use crate::{telemetry, GrpcResult, GrpcStreamResult};
+use config_proxy::{forward_stream, verify_request_signer, AcceptedSigners, ResponseCache};
+use helium_crypto::{PublicKey, PublicKeyBinary};
+use helium_proto::services::{
+    mobile_config::{
+        self, GatewayClient, GatewayInfoReqV1, GatewayInfoResV1, GatewayInfoStreamReqV1,
+        GatewayInfoStreamResV1,
+    },
+    Channel, Endpoint,
+};
+use serde::Deserialize;
+use std::{
+    net::{AddrParseError, SocketAddr},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tonic::{Request, Response};
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// RUST_LOG compatible settings string. Default to
+    /// "mobile_config=info"
+    #[serde(default = "default_log")]
+    pub log: String,
+    /// Listen address. Default to 0.0.0.0::8080
+    #[serde(default = "crate::settings::default_listen_addr")]
+    pub listen: String,
+    /// grpc url of the upstream config service
+    #[serde(with = "http_serde::uri")]
+    pub upstream: http::Uri,
+    /// B58 encoded public key of the upstream config service, verifying the
+    /// responses it signs
+    pub upstream_pubkey: String,
+    /// Connect timeout to the upstream. Default 5s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_connect_timeout"
+    )]
+    pub connect_timeout: Duration,
+    /// RPC timeout to the upstream. Default 5s
+    #[serde(with = "settings_loader::duration", default = "default_rpc_timeout")]
+    pub rpc_timeout: Duration,
+    /// How long a response is served from the cache before it is revalidated
+    /// with the upstream. Default is 5m
+    #[serde(with = "settings_loader::duration", default = "default_ttl")]
+    pub ttl: Duration,
+    /// Max age of the cached responses served while the upstream fails.
+    /// Default is 1h
+    #[serde(with = "settings_loader::duration", default = "default_max_stale")]
+    pub max_stale: Duration,
+    pub metrics: poc_metrics::Settings,
+}
+
+pub fn default_log() -> String {
+    "mobile_config=info".to_string()
+}
+
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_rpc_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_ttl() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_max_stale() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+impl Settings {
+    /// Settings can be loaded from a given optional path and
+    /// can be overridden with environment variables.
+    ///
+    /// Environment overrides have the same name as the entries
+    /// in the settings file in uppercase and prefixed with "CFG_".
+    /// Example: "CFG_UPSTREAM" will override the upstream url.
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::Loader::new("CFG")
+            .env_separator("__")
+            .file(path)
+            .load()
+    }
+
+    pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
+        SocketAddr::from_str(&self.listen)
+    }
+
+    pub fn upstream_pubkey(&self) -> Result<PublicKey, helium_crypto::Error> {
+        PublicKey::from_str(&self.upstream_pubkey)
+    }
+
+    fn upstream_endpoint(&self) -> Endpoint {
+        Endpoint::from(self.upstream.clone())
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.rpc_timeout)
+    }
+
+    /// Check the upstream accepts connections, for preflight checks
+    pub async fn check_upstream(&self) -> Result<(), tonic::transport::Error> {
+        self.upstream_endpoint().connect().await.map(|_| ())
+    }
+}
+
+/// Serves gateway info from a cache in front of the upstream, see
+/// [`config_proxy`], to the signers in [`AcceptedSigners`]. `info_stream` is
+/// passed through uncached
+pub struct GatewayProxy {
+    upstream: GatewayClient<Channel>,
+    infos: ResponseCache<PublicKeyBinary, GatewayInfoResV1>,
+    /// Signers of info requests the upstream accepted
+    signers: AcceptedSigners,
+}
+
+impl GatewayProxy {
+    pub fn new(settings: &Settings) -> anyhow::Result<Self> {
+        Ok(Self {
+            upstream: GatewayClient::new(settings.upstream_endpoint().connect_lazy()),
+            infos: ResponseCache::new(
+                Arc::new(settings.upstream_pubkey()?),
+                settings.ttl,
+                settings.max_stale,
+                telemetry::count_proxy_lookup,
+            ),
+            signers: AcceptedSigners::new(settings.ttl),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl mobile_config::Gateway for GatewayProxy {
+    async fn info(&self, request: Request<GatewayInfoReqV1>) -> GrpcResult<GatewayInfoResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "info");
+
+        let signer = verify_request_signer(&request.signer, &request)?;
+        let authorized = self.signers.contains(&signer).await;
+
+        let address = request.address.clone().into();
+        let mut upstream = self.upstream.clone();
+        let response = self
+            .infos
+            .get_or_fetch("info", address, authorized, async move {
+                upstream.info(request).await
+            })
+            .await?;
+        if !authorized {
+            self.signers.insert(signer).await;
+        }
+
+        Ok(Response::new(response))
+    }
+
+    type info_streamStream = GrpcStreamResult<GatewayInfoStreamResV1>;
+    async fn info_stream(
+        &self,
+        request: Request<GatewayInfoStreamReqV1>,
+    ) -> GrpcResult<Self::info_streamStream> {
+        let request = request.into_inner();
+        telemetry::count_request("gateway-proxy", "info-stream");
+
+        let upstream = self
+            .upstream
+            .clone()
+            .info_stream(request)
+            .await?
+            .into_inner();
+
+        Ok(Response::new(forward_stream(upstream, 100)))
+    }
+}
@@ -0,0 +1,371 @@
+//! Onboarding records of radios and the registry of the makers onboarding
+//! them.
+
+use crate::{
+    ext::{self, RadioOnboardingReqV1, RadioOnboardingResV1},
+    key_cache::KeyCache,
+    telemetry, verify_public_key, GrpcResult,
+};
+use chrono::Utc;
+use file_store::traits::TimestampEncode;
+use helium_crypto::{Keypair, PublicKeyBinary, Sign};
+use helium_proto::Message;
+use sqlx::{Pool, Postgres};
+use std::time::Duration;
+use tonic::{Request, Response, Status};
+
+/// Serves the onboarding record of a hotspot, its `mobile_hotspot_infos` row
+/// in the metadata db, along with its maker in the registry
+pub struct RadioOnboardingService {
+    key_cache: KeyCache,
+    pool: Pool<Postgres>,
+    metadata_pool: Pool<Postgres>,
+    signing_key: Keypair,
+}
+
+impl RadioOnboardingService {
+    pub fn new(
+        key_cache: KeyCache,
+        pool: Pool<Postgres>,
+        metadata_pool: Pool<Postgres>,
+        signing_key: Keypair,
+    ) -> Self {
+        Self {
+            key_cache,
+            pool,
+            metadata_pool,
+            signing_key,
+        }
+    }
+
+    fn sign_response(&self, response: &[u8]) -> Result<Vec<u8>, Status> {
+        self.signing_key
+            .sign(response)
+            .map_err(|_| Status::internal("response signing error"))
+    }
+}
+
+#[tonic::async_trait]
+impl ext::radio_onboarding_server::RadioOnboarding for RadioOnboardingService {
+    async fn onboarding(
+        &self,
+        request: Request<RadioOnboardingReqV1>,
+    ) -> GrpcResult<RadioOnboardingResV1> {
+        let request = request.into_inner();
+        telemetry::count_request("radio_onboarding", "onboarding");
+
+        let signer = verify_public_key(&request.signer)?;
+        self.key_cache
+            .verify_signature(&signer, &request)
+            .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+
+        let hotspot_key = PublicKeyBinary::from(request.hotspot_key);
+        let onboarding = db::fetch_onboarding(&self.metadata_pool, &hotspot_key)
+            .await
+            .map_err(|err| {
+                tracing::error!(%hotspot_key, ?err, "radio onboarding lookup failed");
+                Status::internal("radio onboarding lookup failed")
+            })?
+            .ok_or_else(|| Status::not_found(format!("hotspot: {hotspot_key}")))?;
+        let maker = match &onboarding.maker {
+            Some(maker) => db::fetch_maker(&self.pool, maker).await.map_err(|err| {
+                tracing::error!(maker, ?err, "radio maker lookup failed");
+                Status::internal("radio maker lookup failed")
+            })?,
+            None => None,
+        };
+
+        let mut response = RadioOnboardingResV1 {
+            hotspot_key: hotspot_key.into(),
+            onboarded_at: onboarding
+                .onboarded_at
+                .map_or(0, |onboarded_at| onboarded_at.encode_timestamp()),
+            maker: onboarding.maker.unwrap_or_default(),
+            maker_name: maker.as_ref().map_or("", |maker| &maker.name).to_string(),
+            maker_approved: maker.is_some_and(|maker| maker.approved),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        response.signature = self.sign_response(&response.encode_to_vec())?;
+        Ok(Response::new(response))
+    }
+}
+
+/// Syncs the makers of the metadata db into the maker registry on an
+/// interval. New makers are unapproved, and are approved or revoked with the
+/// [`Cmd`] subcommand
+pub struct MakerSync {
+    pool: Pool<Postgres>,
+    metadata_pool: Pool<Postgres>,
+    interval: Duration,
+}
+
+impl MakerSync {
+    pub fn new(pool: Pool<Postgres>, metadata_pool: Pool<Postgres>, interval: Duration) -> Self {
+        Self {
+            pool,
+            metadata_pool,
+            interval,
+        }
+    }
+
+    pub async fn run(self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting radio maker sync");
+        let mut trigger = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = trigger.tick() => match db::sync_makers(&self.metadata_pool, &self.pool).await {
+                    Ok(added) if added > 0 => tracing::info!(added, "new radio makers synced"),
+                    Ok(_) => (),
+                    Err(err) => tracing::warn!(?err, "failed to sync radio makers"),
+                }
+            }
+        }
+        tracing::info!("stopping radio maker sync");
+        Ok(())
+    }
+}
+
+pub mod db {
+    use chrono::{DateTime, Utc};
+    use helium_crypto::PublicKeyBinary;
+    use sqlx::{Pool, Postgres, Row};
+
+    /// The onboarding record of a hotspot in the metadata db
+    pub struct Onboarding {
+        pub onboarded_at: Option<DateTime<Utc>>,
+        pub maker: Option<String>,
+    }
+
+    /// A maker of the registry
+    pub struct Maker {
+        pub address: String,
+        pub name: String,
+        pub approved: bool,
+    }
+
+    pub async fn fetch_onboarding(
+        metadata: impl sqlx::PgExecutor<'_>,
+        hotspot_key: &PublicKeyBinary,
+    ) -> anyhow::Result<Option<Onboarding>> {
+        let entity_key = bs58::decode(hotspot_key.to_string()).into_vec()?;
+        let row = sqlx::query(
+            r#"
+            select infos.created_at, infos.maker
+            from mobile_hotspot_infos infos
+            join key_to_assets kta on infos.asset = kta.asset
+            where kta.entity_key = $1
+            "#,
+        )
+        .bind(entity_key)
+        .fetch_optional(metadata)
+        .await?;
+        Ok(row.map(|row| Onboarding {
+            onboarded_at: row.get("created_at"),
+            maker: row.get("maker"),
+        }))
+    }
+
+    pub async fn fetch_maker(
+        db: impl sqlx::PgExecutor<'_>,
+        address: &str,
+    ) -> Result<Option<Maker>, sqlx::Error> {
+        let row =
+            sqlx::query(r#" select address, name, approved from radio_makers where address = $1 "#)
+                .bind(address)
+                .fetch_optional(db)
+                .await?;
+        Ok(row.map(|row| Maker {
+            address: row.get("address"),
+            name: row.get("name"),
+            approved: row.get("approved"),
+        }))
+    }
+
+    pub async fn fetch_all(db: impl sqlx::PgExecutor<'_>) -> Result<Vec<Maker>, sqlx::Error> {
+        Ok(
+            sqlx::query(r#" select address, name, approved from radio_makers order by name "#)
+                .fetch_all(db)
+                .await?
+                .into_iter()
+                .map(|row| Maker {
+                    address: row.get("address"),
+                    name: row.get("name"),
+                    approved: row.get("approved"),
+                })
+                .collect(),
+        )
+    }
+
+    /// Add the makers of the metadata db missing from the registry and
+    /// refresh the names of the others. Returns the number of makers added.
+    ///
+    /// The first sync into an empty registry seeds it with every maker
+    /// approved, as their radios were onboarded before the registry existed
+    /// and rejecting them all would halt their rewards. Makers added later
+    /// are not approved.
+    pub async fn sync_makers(
+        metadata: &Pool<Postgres>,
+        pool: &Pool<Postgres>,
+    ) -> Result<u64, sqlx::Error> {
+        let makers: Vec<(String, String)> = sqlx::query_as(r#" select address, name from makers "#)
+            .fetch_all(metadata)
+            .await?;
+        let mut added = 0;
+        let mut transaction = pool.begin().await?;
+        let seeding =
+            sqlx::query_scalar::<_, bool>(r#" select not exists(select 1 from radio_makers) "#)
+                .fetch_one(&mut transaction)
+                .await?;
+        for (address, name) in makers {
+            let inserted = sqlx::query_scalar::<_, bool>(
+                r#"
+                insert into radio_makers (address, name, approved) values ($1, $2, $3)
+                on conflict (address) do update set name = EXCLUDED.name
+                returning (xmax = 0)
+                "#,
+            )
+            .bind(address)
+            .bind(name)
+            .bind(seeding)
+            .fetch_one(&mut transaction)
+            .await?;
+            if inserted {
+                added += 1;
+            }
+        }
+        transaction.commit().await?;
+        Ok(added)
+    }
+
+    pub async fn set_approved(
+        db: impl sqlx::PgExecutor<'_>,
+        address: &str,
+        approved: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query(r#" update radio_makers set approved = $2 where address = $1 "#)
+            .bind(address)
+            .bind(approved)
+            .execute(db)
+            .await?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+}
+
+/// Command line access to the radio maker registry
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    /// List every maker of the registry
+    List,
+    /// Sync the makers of the metadata db into the registry now
+    Sync,
+    /// Approve the radios onboarded by a maker
+    Approve { address: String },
+    /// Revoke the approval of a maker, invalidating the heartbeats of the
+    /// radios it onboarded
+    Revoke { address: String },
+}
+
+impl Cmd {
+    pub async fn run(
+        &self,
+        pool: &Pool<Postgres>,
+        metadata_pool: &Pool<Postgres>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                for maker in db::fetch_all(pool).await? {
+                    let approval = if maker.approved {
+                        "approved"
+                    } else {
+                        "not approved"
+                    };
+                    println!("{}: {} ({approval})", maker.address, maker.name);
+                }
+            }
+            Self::Sync => {
+                let added = db::sync_makers(metadata_pool, pool).await?;
+                println!("{added} makers added");
+            }
+            Self::Approve { address } => set_approved(pool, address, true).await?,
+            Self::Revoke { address } => set_approved(pool, address, false).await?,
+        }
+        Ok(())
+    }
+}
+
+async fn set_approved(pool: &Pool<Postgres>, address: &str, approved: bool) -> anyhow::Result<()> {
+    if db::set_approved(pool, address, approved).await? {
+        let approval = if approved { "approved" } else { "revoked" };
+        println!("{address}: {approval}");
+    } else {
+        println!("{address}: not in the registry, sync it first");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::db;
+    use sqlx::PgPool;
+
+    async fn add_metadata_maker(pool: &PgPool, address: &str, name: &str) {
+        sqlx::query("insert into makers (address, name) values ($1, $2)")
+            .bind(address)
+            .bind(name)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn makers(pool: &PgPool) -> Vec<(String, String, bool)> {
+        db::fetch_all(pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|maker| (maker.address, maker.name, maker.approved))
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn sync_seeds_the_empty_registry_approved(pool: PgPool) {
+        // The metadata db is stood in for by a makers table of the same pool
+        sqlx::query("create table makers (address text primary key, name text not null)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        add_metadata_maker(&pool, "a", "maker a").await;
+        add_metadata_maker(&pool, "b", "maker b").await;
+
+        assert_eq!(db::sync_makers(&pool, &pool).await.unwrap(), 2);
+        assert_eq!(
+            makers(&pool).await,
+            vec![
+                ("a".to_string(), "maker a".to_string(), true),
+                ("b".to_string(), "maker b".to_string(), true),
+            ]
+        );
+
+        // Later makers are not approved, and names are refreshed without
+        // touching approvals
+        db::set_approved(&pool, "b", false).await.unwrap();
+        add_metadata_maker(&pool, "c", "maker c").await;
+        sqlx::query("update makers set name = 'renamed a' where address = 'a'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(db::sync_makers(&pool, &pool).await.unwrap(), 1);
+        assert_eq!(
+            makers(&pool).await,
+            vec![
+                ("b".to_string(), "maker b".to_string(), false),
+                ("c".to_string(), "maker c".to_string(), false),
+                ("a".to_string(), "renamed a".to_string(), true),
+            ]
+        );
+    }
+}
@@ -1,9 +1,9 @@
-use config::{Config, Environment, File};
 use serde::Deserialize;
 use std::{
     net::{AddrParseError, SocketAddr},
     path::Path,
     str::FromStr,
+    time::Duration,
 };
 
 #[derive(Debug, Deserialize)]
@@ -19,6 +19,20 @@ pub struct Settings {
     pub signing_keypair: String,
     /// B58 encoded public key of the default admin keypair
     pub admin_pubkey: String,
+    /// Interval at which registered keys are reloaded from the database.
+    /// Default 1m
+    #[serde(
+        with = "settings_loader::duration::positive",
+        default = "default_key_cache_refresh_interval"
+    )]
+    pub key_cache_refresh_interval: Duration,
+    /// Interval at which the makers of the metadata db are synced into the
+    /// radio maker registry. Default 1h
+    #[serde(
+        with = "settings_loader::duration::positive",
+        default = "default_maker_sync_interval"
+    )]
+    pub maker_sync_interval: Duration,
     /// Settings passed to the db_store crate for connecting to
     /// the config service's own persistence store
     pub database: db_store::Settings,
@@ -36,6 +50,14 @@ pub fn default_listen_addr() -> String {
     "0.0.0.0:8080".to_string()
 }
 
+fn default_key_cache_refresh_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_maker_sync_interval() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
 impl Settings {
     /// Settings can be loaded from a given optional path and
     /// can be overridden with environment variables.
@@ -43,21 +65,11 @@ impl Settings {
     /// Environment overrides have the same name as the entries
     /// in the settings file in uppercase and prefixed with "CFG_".
     /// Example: "CFG_DATABASE_URL" will override the database url.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-
-        // Add in settings from the environment (with prefix of APP)
-        // E.g. `CFG_DEBUG=1 .target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("CFG").separator("__"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::Loader::new("CFG")
+            .env_separator("__")
+            .file(path)
+            .load()
     }
 
     pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
@@ -65,8 +77,7 @@ impl Settings {
     }
 
     pub fn signing_keypair(&self) -> anyhow::Result<helium_crypto::Keypair> {
-        let data = std::fs::read(&self.signing_keypair).map_err(helium_crypto::Error::from)?;
-        Ok(helium_crypto::Keypair::try_from(&data[..])?)
+        Ok(settings_loader::keypair(&self.signing_keypair)?)
     }
 
     pub fn admin_pubkey(&self) -> anyhow::Result<helium_crypto::PublicKey> {
@@ -1,6 +1,14 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 const RPC_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "grpc-request");
 const GATEWAY_CHAIN_LOOKUP_METRIC: &str =
     concat!(env!("CARGO_PKG_NAME"), "-", "gateway-chain-lookup");
+const PROXY_LOOKUP_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "proxy-lookup");
+const STREAM_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "grpc-stream");
+
+/// Gateway info streams in progress, as the stream metric only lives in the
+/// recorder
+static INFO_STREAMS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn count_request(service: &'static str, rpc: &'static str) {
     metrics::increment_counter!(RPC_METRIC, "service" => service, "rpc" => rpc);
@@ -9,3 +17,21 @@ pub fn count_request(service: &'static str, rpc: &'static str) {
 pub fn count_gateway_chain_lookup(result: &'static str) {
     metrics::increment_counter!(GATEWAY_CHAIN_LOOKUP_METRIC, "result" => result);
 }
+
+pub fn count_proxy_lookup(rpc: &'static str, result: &'static str) {
+    metrics::increment_counter!(PROXY_LOOKUP_METRIC, "rpc" => rpc, "result" => result);
+}
+
+pub fn info_stream_start() {
+    INFO_STREAMS.fetch_add(1, Ordering::Relaxed);
+    metrics::increment_gauge!(STREAM_METRIC, 1.0);
+}
+
+pub fn info_stream_end() {
+    INFO_STREAMS.fetch_sub(1, Ordering::Relaxed);
+    metrics::decrement_gauge!(STREAM_METRIC, 1.0);
+}
+
+pub fn info_streams() -> usize {
+    INFO_STREAMS.load(Ordering::Relaxed)
+}
@@ -11,11 +11,15 @@ use helium_proto::{
     },
     Message,
 };
-use mobile_config::KeyRole;
+use mobile_config::{
+    ext::{admin_keys_client, AdminListKeysReqV1, AdminListKeysResV1},
+    KeyRole,
+};
 use std::str::FromStr;
 
 pub struct AdminClient {
     client: admin_client::AdminClient<helium_proto::services::Channel>,
+    keys_client: admin_keys_client::AdminKeysClient<helium_proto::services::Channel>,
     server_pubkey: PublicKey,
 }
 
@@ -38,6 +42,7 @@ impl AdminClient {
     pub async fn new(host: &str, server_pubkey: &str) -> Result<Self> {
         Ok(Self {
             client: admin_client::AdminClient::connect(host.to_owned()).await?,
+            keys_client: admin_keys_client::AdminKeysClient::connect(host.to_owned()).await?,
             server_pubkey: PublicKey::from_str(server_pubkey)?,
         })
     }
@@ -83,6 +88,25 @@ impl AdminClient {
             .into_inner()
             .verify(&self.server_pubkey)
     }
+
+    pub async fn list_keys(&mut self, keypair: &Keypair) -> Result<Vec<(PublicKey, KeyRole)>> {
+        let mut request = AdminListKeysReqV1 {
+            timestamp: current_timestamp()?,
+            signer: keypair.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = request.sign(keypair)?;
+        let response = self.keys_client.list_keys(request).await?.into_inner();
+        response.verify(&self.server_pubkey)?;
+        response
+            .keys
+            .into_iter()
+            .map(|key| {
+                let pubkey = PublicKey::try_from(key.pubkey)?;
+                Ok::<_, anyhow::Error>((pubkey, KeyRole::from_i32(key.role)?))
+            })
+            .collect()
+    }
 }
 
 impl AuthClient {
@@ -208,6 +232,7 @@ macro_rules! impl_sign {
 
 impl_sign!(AdminAddKeyReqV1, signature);
 impl_sign!(AdminRemoveKeyReqV1, signature);
+impl_sign!(AdminListKeysReqV1, signature);
 impl_sign!(AuthorizationVerifyReqV1, signature);
 impl_sign!(AuthorizationListReqV1, signature);
 impl_sign!(EntityVerifyReqV1, signature);
@@ -236,6 +261,7 @@ macro_rules! impl_verify {
 }
 
 impl_verify!(AdminKeyResV1, signature);
+impl_verify!(AdminListKeysResV1, signature);
 impl_verify!(AuthorizationVerifyResV1, signature);
 impl_verify!(AuthorizationListResV1, signature);
 impl_verify!(EntityVerifyResV1, signature);
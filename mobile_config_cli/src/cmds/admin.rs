@@ -1,6 +1,7 @@
-use crate::{client, cmds::PathBufKeypair, Msg, Result};
+use crate::{client, cmds::PathBufKeypair, Msg, PrettyJson, Result};
 
-use super::AdminKeyArgs;
+use super::{AdminKeyArgs, ListAdminKeys};
+use serde_json::json;
 
 pub async fn add_key(args: AdminKeyArgs) -> Result<Msg> {
     let output = format!("Added {} as {} key", args.pubkey, args.key_role);
@@ -27,3 +28,13 @@ pub async fn remove_key(args: AdminKeyArgs) -> Result<Msg> {
     }
     Msg::dry_run(output)
 }
+
+pub async fn list_keys(args: ListAdminKeys) -> Result<Msg> {
+    let mut client = client::AdminClient::new(&args.config_host, &args.config_pubkey).await?;
+    let keys = client.list_keys(&args.keypair.to_keypair()?).await?;
+    let output = keys
+        .into_iter()
+        .map(|(pubkey, role)| json!({ "pubkey": pubkey, "role": role }))
+        .collect::<Vec<_>>();
+    Msg::ok(output.pretty_json()?)
+}
@@ -5,8 +5,8 @@ use helium_crypto::PublicKey;
 use helium_proto::services::mobile_config::{
     GatewayInfo as GatewayInfoProto, GatewayMetadata as GatewayMetadataProto,
 };
+use hex_utils::h3o::LatLng;
 use serde::Serialize;
-use std::str::FromStr;
 
 pub async fn info(args: GetHotspot) -> Result<Msg> {
     let mut client = client::GatewayClient::new(&args.config_host, &args.config_pubkey).await?;
@@ -56,11 +56,11 @@ impl TryFrom<GatewayInfoProto> for GatewayInfo {
 }
 
 impl TryFrom<GatewayMetadataProto> for GatewayMetadata {
-    type Error = h3o::error::InvalidCellIndex;
+    type Error = hex_utils::Error;
 
     fn try_from(md: GatewayMetadataProto) -> Result<Self, Self::Error> {
         let location = md.clone().location;
-        let latlng: h3o::LatLng = h3o::CellIndex::from_str(&md.location)?.into();
+        let latlng: LatLng = hex_utils::parse(&md.location)?.into();
         Ok(Self {
             location,
             lat: latlng.lat(),
@@ -187,6 +187,8 @@ pub enum AdminCommands {
     AddKey(AdminKeyArgs),
     /// Remove a pubkey/role
     RemoveKey(AdminKeyArgs),
+    /// List all registered pubkeys and their roles
+    ListKeys(ListAdminKeys),
 }
 
 #[derive(Debug, Args)]
@@ -205,6 +207,16 @@ pub struct AdminKeyArgs {
     pub commit: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct ListAdminKeys {
+    #[arg(from_global)]
+    pub keypair: PathBuf,
+    #[arg(from_global)]
+    pub config_host: String,
+    #[arg(from_global)]
+    pub config_pubkey: String,
+}
+
 pub trait PathBufKeypair {
     fn to_keypair(&self) -> Result<helium_crypto::Keypair>;
 }
@@ -28,6 +28,7 @@ pub async fn handle_cli(cli: Cli) -> Result<Msg> {
         Commands::Admin { command } => match command {
             cmds::AdminCommands::AddKey(args) => admin::add_key(args).await,
             cmds::AdminCommands::RemoveKey(args) => admin::remove_key(args).await,
+            cmds::AdminCommands::ListKeys(args) => admin::list_keys(args).await,
         },
         Commands::Authorization { command } => match command {
             cmds::AuthCommands::VerifyKey(args) => authorization::verify_key_role(args).await,
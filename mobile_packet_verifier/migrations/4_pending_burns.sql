@@ -0,0 +1,38 @@
+-- Debits are burned through the pending burns and balance cache of the iot
+-- packet verifier, which record confirmed burns in the payer ledger.
+CREATE TYPE payer_ledger_kind AS ENUM ('debit', 'credit', 'balance_refresh');
+
+CREATE TABLE payer_ledger (
+       id BIGSERIAL PRIMARY KEY,
+       payer TEXT NOT NULL,
+       kind payer_ledger_kind NOT NULL,
+       amount BIGINT NOT NULL,
+       balance BIGINT,
+       oui BIGINT,
+       reference TEXT,
+       recorded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX payer_ledger_payer_recorded_at_idx ON payer_ledger (payer, recorded_at);
+
+CREATE TYPE token_use AS ENUM ('iot_packets', 'mobile_data');
+
+CREATE TABLE pending_burns (
+       payer TEXT NOT NULL,
+       token_use token_use NOT NULL,
+       amount BIGINT NOT NULL,
+       last_burn TIMESTAMP NOT NULL,
+       PRIMARY KEY(payer, token_use)
+);
+
+-- DC debited for each session, so the sessions written out after a burn
+-- account for exactly what was debited
+ALTER TABLE data_transfer_sessions ADD COLUMN num_dcs BIGINT NOT NULL DEFAULT 0;
+
+UPDATE data_transfer_sessions
+SET num_dcs = GREATEST(1, CEIL((uploaded_bytes + downloaded_bytes) / 20000.0));
+
+INSERT INTO pending_burns (payer, token_use, amount, last_burn)
+SELECT payer, 'mobile_data', SUM(num_dcs), now()
+FROM data_transfer_sessions
+GROUP BY payer;
@@ -0,0 +1,7 @@
+-- Sessions are debited to the pending burns and payer ledger of the iot
+-- packet verifier database. Pending amounts of the sessions left are added
+-- there on start, from the num_dcs of each session.
+DROP TABLE pending_burns;
+DROP TYPE token_use;
+DROP TABLE payer_ledger;
+DROP TYPE payer_ledger_kind;
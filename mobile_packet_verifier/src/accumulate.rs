@@ -1,21 +1,31 @@
+use crate::burner::bytes_to_dc;
 use chrono::{DateTime, Utc};
+use dc_ledger::{
+    balances::Debiter,
+    pending_burns::{PendingBurns, TokenUse},
+};
 use file_store::file_sink::FileSinkClient;
 use file_store::mobile_session::{
     DataTransferSessionIngestReport, DataTransferSessionReq, InvalidDataTransferIngestReport,
 };
+use file_store::traits::TimestampEncode;
 use futures::{Stream, StreamExt};
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::mobile_config::NetworkKeyRole;
+use helium_proto::services::packet_verifier::ValidDataTransferSession;
 use helium_proto::services::poc_mobile::{
     invalid_data_transfer_ingest_report_v1::DataTransferIngestReportStatus,
     InvalidDataTransferIngestReportV1,
 };
 use mobile_config::{
-    client::{AuthorizationClient, ClientError, GatewayClient},
+    client::{AuthorizationClient, CarrierPayerClient, ClientError, GatewayClient},
     gateway_info::GatewayInfoResolver,
 };
 use sqlx::{Postgres, Transaction};
 
+const INSUFFICIENT_BALANCE_COUNTER: &str =
+    concat!(env!("CARGO_PKG_NAME"), "_insufficient_balance_session");
+
 #[derive(thiserror::Error, Debug)]
 pub enum AccumulationError {
     #[error("file store error: {0}")]
@@ -26,16 +36,31 @@ pub enum AccumulationError {
     ReportsStreamDropped,
     #[error("config client error: {0}")]
     ConfigClientError(#[from] ClientError),
+    #[error("debit error: {0}")]
+    DebitError(Box<dyn std::error::Error + Send + Sync>),
 }
 
-pub async fn accumulate_sessions(
+/// Accumulate the valid sessions of a report file, debiting the DC they cost
+/// from the payer of the carrier and adding them to its pending mobile data
+/// burn in the `ledger` transaction, which holds the ledger lock. Sessions
+/// the payer can't afford are written out to `unpaid_sessions` instead.
+#[allow(clippy::too_many_arguments)]
+pub async fn accumulate_sessions<D>(
     gateway_client: &GatewayClient,
     auth_client: &AuthorizationClient,
+    carrier_payers: &CarrierPayerClient,
+    debiter: &D,
+    ledger: &mut Transaction<'_, Postgres>,
     conn: &mut Transaction<'_, Postgres>,
     invalid_data_session_report_sink: &FileSinkClient,
+    unpaid_sessions: &FileSinkClient,
     curr_file_ts: DateTime<Utc>,
     reports: impl Stream<Item = DataTransferSessionIngestReport>,
-) -> Result<(), AccumulationError> {
+) -> Result<(), AccumulationError>
+where
+    D: Debiter,
+    D::Error: std::error::Error + Send + Sync + 'static,
+{
     tokio::pin!(reports);
 
     while let Some(report) = reports.next().await {
@@ -56,22 +81,55 @@ pub async fn accumulate_sessions(
             write_invalid_report(invalid_data_session_report_sink, report_validity, report).await?;
             continue;
         }
+        let payer = carrier_payers
+            .payer(&report.report.pub_key)
+            .await?
+            .unwrap_or_else(|| report.report.data_transfer_usage.payer.clone());
         let event = report.report.data_transfer_usage;
+        let num_dcs = bytes_to_dc(event.upload_bytes + event.download_bytes);
+        let remaining_balance = debiter
+            .debit_if_sufficient(&payer, num_dcs, 0)
+            .await
+            .map_err(|err| AccumulationError::DebitError(Box::new(err)))?;
+        if remaining_balance.is_none() {
+            metrics::increment_counter!(INSUFFICIENT_BALANCE_COUNTER, "payer" => payer.to_string());
+            let timestamp = curr_file_ts.encode_timestamp_millis();
+            unpaid_sessions
+                .write(
+                    ValidDataTransferSession {
+                        pub_key: event.pub_key.into(),
+                        payer: payer.into(),
+                        upload_bytes: event.upload_bytes,
+                        download_bytes: event.download_bytes,
+                        num_dcs,
+                        first_timestamp: timestamp,
+                        last_timestamp: timestamp,
+                    },
+                    &[],
+                )
+                .await?;
+            continue;
+        }
+        (&mut *ledger)
+            .add_burned_amount(&payer, TokenUse::MobileData, num_dcs)
+            .await?;
         sqlx::query(
             r#"
-            INSERT INTO data_transfer_sessions (pub_key, payer, uploaded_bytes, downloaded_bytes, first_timestamp, last_timestamp)
-            VALUES ($1, $2, $3, $4, $5, $5)
+            INSERT INTO data_transfer_sessions (pub_key, payer, uploaded_bytes, downloaded_bytes, first_timestamp, last_timestamp, num_dcs)
+            VALUES ($1, $2, $3, $4, $5, $5, $6)
             ON CONFLICT (pub_key, payer) DO UPDATE SET
             uploaded_bytes = data_transfer_sessions.uploaded_bytes + EXCLUDED.uploaded_bytes,
             downloaded_bytes = data_transfer_sessions.downloaded_bytes + EXCLUDED.downloaded_bytes,
-            last_timestamp = GREATEST(data_transfer_sessions.last_timestamp, EXCLUDED.last_timestamp)
+            last_timestamp = GREATEST(data_transfer_sessions.last_timestamp, EXCLUDED.last_timestamp),
+            num_dcs = data_transfer_sessions.num_dcs + EXCLUDED.num_dcs
             "#
         )
             .bind(event.pub_key)
-            .bind(event.payer)
+            .bind(payer)
             .bind(event.upload_bytes as i64)
             .bind(event.download_bytes as i64)
             .bind(curr_file_ts)
+            .bind(num_dcs as i64)
             .execute(&mut *conn)
             .await?;
     }
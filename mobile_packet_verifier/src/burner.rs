@@ -1,5 +1,11 @@
 use chrono::{DateTime, Utc};
+use dc_ledger::{
+    balances::BalanceCache,
+    burner::{self, Settlement},
+    pending_burns::TokenUse,
+};
 use file_store::{file_sink::FileSinkClient, traits::TimestampEncode};
+use futures::TryStreamExt;
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::packet_verifier::ValidDataTransferSession;
 use solana::SolanaNetwork;
@@ -14,31 +20,27 @@ pub struct DataTransferSession {
     downloaded_bytes: i64,
     first_timestamp: DateTime<Utc>,
     last_timestamp: DateTime<Utc>,
+    num_dcs: i64,
 }
 
-#[derive(Default)]
-pub struct PayerTotals {
-    total_dcs: u64,
-    sessions: Vec<DataTransferSession>,
-}
-
-impl PayerTotals {
-    fn push_sess(&mut self, sess: DataTransferSession) {
-        self.total_dcs += bytes_to_dc(sess.downloaded_bytes as u64 + sess.uploaded_bytes as u64);
-        self.sessions.push(sess);
-    }
-}
-
+/// Burns the pending mobile data burns in the shared ledger, writing out
+/// the sessions of a payer once they are paid for
 pub struct Burner<S> {
     valid_sessions: FileSinkClient,
-    solana: S,
+    burner: burner::Burner<Pool<Postgres>, S>,
 }
 
 impl<S> Burner<S> {
-    pub fn new(valid_sessions: FileSinkClient, solana: S) -> Self {
+    pub fn new(
+        ledger: Pool<Postgres>,
+        valid_sessions: FileSinkClient,
+        balances: &BalanceCache<S>,
+        solana: S,
+    ) -> Self {
         Self {
             valid_sessions,
-            solana,
+            // Burns are made by the daemon every burn period
+            burner: burner::Burner::new(ledger, balances, TokenUse::MobileData, 0, solana),
         }
     }
 }
@@ -49,65 +51,39 @@ pub enum BurnError<E> {
     FileStoreError(#[from] file_store::Error),
     #[error("sql error: {0}")]
     SqlError(#[from] sqlx::Error),
-    #[error("solana error: {0}")]
-    SolanaError(E),
+    #[error("burn error: {0}")]
+    BurnError(#[from] burner::BurnError<sqlx::Error, E>),
 }
 
 impl<S> Burner<S>
 where
     S: SolanaNetwork,
 {
-    pub async fn burn(&self, pool: &Pool<Postgres>) -> Result<(), BurnError<S::Error>> {
-        // Fetch all of the sessions
-        let sessions: Vec<DataTransferSession> =
-            sqlx::query_as("SELECT * FROM data_transfer_sessions")
-                .fetch_all(pool)
-                .await?;
-
-        // Fetch all of the sessions and group by the payer
-        let mut payer_totals = HashMap::<PublicKeyBinary, PayerTotals>::new();
-        for session in sessions.into_iter() {
-            payer_totals
-                .entry(session.payer.clone())
-                .or_default()
-                .push_sess(session);
-        }
-
-        for (
-            payer,
-            PayerTotals {
-                total_dcs,
-                sessions,
-            },
-        ) in payer_totals.into_iter()
+    /// Burn the pending mobile data burn of every payer. The sessions of a
+    /// payer are written out and deleted once nothing is left to burn for
+    /// them; a burn tracked since a restart may cover only part of them.
+    pub async fn burn(&mut self, pool: &Pool<Postgres>) -> Result<(), BurnError<S::Error>> {
+        while let Some(Settlement {
+            payer, remaining, ..
+        }) = self.burner.burn().await?
         {
-            tracing::info!(%total_dcs, %payer, "Burning DC");
-
-            if self
-                .solana
-                .burn_data_credits(&payer, total_dcs)
-                .await
-                .is_err()
-            {
-                // We have failed to burn data credits:
-                metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "false");
+            if remaining > 0 {
+                tracing::info!(%payer, %remaining, "Sessions burned in part");
                 continue;
             }
 
-            // We succesfully managed to burn data credits:
-
-            metrics::counter!("burned", total_dcs, "payer" => payer.to_string(), "success" => "true");
-
-            // Delete from the data transfer session and write out to S3
+            let mut transaction = pool.begin().await?;
+            let sessions: Vec<DataTransferSession> =
+                sqlx::query_as("DELETE FROM data_transfer_sessions WHERE payer = $1 RETURNING *")
+                    .bind(&payer)
+                    .fetch_all(&mut transaction)
+                    .await?;
+            transaction.commit().await?;
 
-            sqlx::query("DELETE FROM data_transfer_sessions WHERE payer = $1")
-                .bind(&payer)
-                .execute(pool)
-                .await?;
+            // Write out to S3
 
             for session in sessions {
-                let num_dcs =
-                    bytes_to_dc(session.uploaded_bytes as u64 + session.downloaded_bytes as u64);
+                let num_dcs = session.num_dcs as u64;
                 self.valid_sessions
                     .write(
                         ValidDataTransferSession {
@@ -123,27 +99,27 @@ where
                     )
                     .await?;
             }
-
-            // Fetch the balance after
-
-            metrics::gauge!(
-                "balance",
-                self
-                    .solana
-                    .payer_balance(&payer)
-                    .await
-                    .map_err(BurnError::SolanaError)? as f64,
-                "payer" => payer.to_string()
-            );
         }
 
         Ok(())
     }
 }
 
+/// DC debited for the sessions of every payer not written out yet, the
+/// mobile data burns pending in the ledger are reconciled with on start
+pub async fn owed(pool: &Pool<Postgres>) -> Result<HashMap<PublicKeyBinary, u64>, sqlx::Error> {
+    sqlx::query_as::<_, (PublicKeyBinary, i64)>(
+        "SELECT payer, SUM(num_dcs)::BIGINT FROM data_transfer_sessions GROUP BY payer",
+    )
+    .fetch(pool)
+    .map_ok(|(payer, amount)| (payer, amount as u64))
+    .try_collect()
+    .await
+}
+
 const BYTES_PER_DC: u64 = 20_000;
 
-fn bytes_to_dc(bytes: u64) -> u64 {
+pub fn bytes_to_dc(bytes: u64) -> u64 {
     let bytes = bytes.max(BYTES_PER_DC);
     // Integer div/ceil from: https://stackoverflow.com/a/2745086
     (bytes + BYTES_PER_DC - 1) / BYTES_PER_DC
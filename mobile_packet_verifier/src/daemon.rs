@@ -1,6 +1,13 @@
-use crate::{burner::Burner, settings::Settings};
-use anyhow::{bail, Error, Result};
+use crate::{
+    burner::{self, Burner},
+    settings::Settings,
+};
+use anyhow::{Error, Result};
 use chrono::{TimeZone, Utc};
+use dc_ledger::{
+    balances::BalanceCache,
+    pending_burns::{self, TokenUse},
+};
 use file_store::{
     file_info_poller::{FileInfoStream, LookbackBehavior},
     file_sink::FileSinkClient,
@@ -9,7 +16,11 @@ use file_store::{
     FileSinkBuilder, FileStore, FileType,
 };
 use futures_util::TryFutureExt;
-use mobile_config::{client::AuthorizationClient, GatewayClient};
+use mobile_config::{
+    client::{AuthorizationClient, CarrierPayerClient},
+    GatewayClient,
+};
+use poc_metrics::preflight::Preflight;
 use solana::{SolanaNetwork, SolanaRpc};
 use sqlx::{Pool, Postgres};
 use tokio::{
@@ -20,32 +31,45 @@ use tokio::{
 
 pub struct Daemon<S> {
     pool: Pool<Postgres>,
+    ledger: Pool<Postgres>,
     burner: Burner<S>,
+    balances: BalanceCache<S>,
     reports: Receiver<FileInfoStream<DataTransferSessionIngestReport>>,
     burn_period: Duration,
     gateway_client: GatewayClient,
     auth_client: AuthorizationClient,
+    carrier_payers: CarrierPayerClient,
     invalid_data_session_report_sink: FileSinkClient,
+    unpaid_sessions: FileSinkClient,
 }
 
 impl<S> Daemon<S> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         settings: &Settings,
         pool: Pool<Postgres>,
+        ledger: Pool<Postgres>,
         reports: Receiver<FileInfoStream<DataTransferSessionIngestReport>>,
         burner: Burner<S>,
+        balances: BalanceCache<S>,
         gateway_client: GatewayClient,
         auth_client: AuthorizationClient,
+        carrier_payers: CarrierPayerClient,
         invalid_data_session_report_sink: FileSinkClient,
+        unpaid_sessions: FileSinkClient,
     ) -> Self {
         Self {
             pool,
+            ledger,
             burner,
+            balances,
             reports,
             burn_period: Duration::from_secs(60 * 60 * settings.burn_period as u64),
             gateway_client,
             auth_client,
+            carrier_payers,
             invalid_data_session_report_sink,
+            unpaid_sessions,
         }
     }
 }
@@ -65,11 +89,30 @@ where
                     };
                     tracing::info!("Verifying file: {}", file.file_info);
                     let ts = file.file_info.timestamp;
+                    let mut ledger = self.ledger.begin().await?;
+                    pending_burns::lock(&mut ledger).await?;
+                    self.balances.sync(&mut ledger).await?;
                     let mut transaction = self.pool.begin().await?;
                     let reports = file.into_stream(&mut transaction).await?;
-                    crate::accumulate::accumulate_sessions(&self.gateway_client, &self.auth_client, &mut transaction, &self.invalid_data_session_report_sink, ts, reports).await?;
+                    crate::accumulate::accumulate_sessions(
+                        &self.gateway_client,
+                        &self.auth_client,
+                        &self.carrier_payers,
+                        &self.balances,
+                        &mut ledger,
+                        &mut transaction,
+                        &self.invalid_data_session_report_sink,
+                        &self.unpaid_sessions,
+                        ts,
+                        reports,
+                    )
+                    .await?;
+                    // Pending burns committed without their sessions are
+                    // lowered back on start, see `pending_burns::reconcile`
+                    ledger.commit().await?;
                     transaction.commit().await?;
                     self.invalid_data_session_report_sink.commit().await?;
+                    self.unpaid_sessions.commit().await?;
                 },
                 _ = sleep_until(burn_time) => {
                     // It's time to burn
@@ -98,23 +141,51 @@ impl Cmd {
             }
         });
 
-        // Set up the postgres pool:
+        // Set up the postgres pools:
         let (pool, conn_handler) = settings
             .database
             .connect("mobile-packet-verifier", shutdown_listener.clone())
             .await?;
-        sqlx::migrate!().run(&pool).await?;
+        let (mut ledger, ledger_handler) = settings
+            .ledger
+            .connect("mobile-packet-verifier-ledger", shutdown_listener.clone())
+            .await?;
 
-        // Set up the solana network:
-        let solana = if settings.enable_solana_integration {
-            let Some(ref solana_settings) = settings.solana else {
-                bail!("Missing solana section in settings");
-            };
-            // Set up the solana RpcClient:
-            Some(SolanaRpc::new(solana_settings).await?)
-        } else {
-            None
-        };
+        // Check all external dependencies before starting, setting up the
+        // solana RpcClient on the way, none in shadow mode:
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        let solana = preflight
+            .check(
+                "solana burn keypair",
+                SolanaRpc::shadowable(settings.enable_solana_integration, settings.solana.as_ref()),
+            )
+            .await
+            .flatten();
+        if let Some(solana) = &solana {
+            preflight.check("solana rpc", solana.check_health()).await;
+        }
+        preflight
+            .check("ingest bucket", settings.ingest.check_read())
+            .await;
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        preflight
+            .check("mobile config", settings.config_client.check_reachable())
+            .await;
+        preflight.check_result(
+            "mobile config keypair",
+            settings.config_client.signing_keypair(),
+        );
+        preflight.finish()?;
+
+        sqlx::migrate!().run(&pool).await?;
 
         let sol_balance_monitor = solana::balance_monitor::start(
             env!("CARGO_PKG_NAME"),
@@ -151,7 +222,36 @@ impl Cmd {
         .create()
         .await?;
 
-        let burner = Burner::new(valid_sessions, solana);
+        let (unpaid_sessions, mut unpaid_sessions_server) = FileSinkBuilder::new(
+            FileType::UnpaidDataTransferSession,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_unpaid_data_transfer_session"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        // Settle the pending mobile data burns in the ledger with the
+        // sessions left:
+        let mut transaction = ledger.begin().await?;
+        pending_burns::lock(&mut transaction).await?;
+        pending_burns::reconcile(
+            &mut transaction,
+            TokenUse::MobileData,
+            &burner::owed(&pool).await?,
+        )
+        .await?;
+        transaction.commit().await?;
+
+        // Set up the balance cache, sharing its balances with the iot packet
+        // verifier through the ledger:
+        let balances = BalanceCache::new(&mut ledger, solana.clone())
+            .await?
+            .with_ledger(ledger.clone());
+
+        let burner = Burner::new(ledger.clone(), valid_sessions, &balances, solana);
 
         let file_store = FileStore::from_settings(&settings.ingest).await?;
 
@@ -169,25 +269,35 @@ impl Cmd {
                 .await?;
 
         let gateway_client = GatewayClient::from_settings(&settings.config_client)?;
+        if let Err(err) = gateway_client.prewarm(&shutdown_listener).await {
+            tracing::warn!(?err, "failed to prewarm gateway info cache");
+        }
         let auth_client = AuthorizationClient::from_settings(&settings.config_client)?;
+        let carrier_payers = CarrierPayerClient::from_settings(&settings.config_client)?;
 
         let daemon = Daemon::new(
             settings,
             pool,
+            ledger,
             reports,
             burner,
+            balances,
             gateway_client,
             auth_client,
+            carrier_payers,
             invalid_sessions,
+            unpaid_sessions,
         );
 
         tokio::try_join!(
             source_join_handle.map_err(Error::from),
             valid_sessions_server.run().map_err(Error::from),
             invalid_sessions_server.run().map_err(Error::from),
+            unpaid_sessions_server.run().map_err(Error::from),
             file_upload.run(&shutdown_listener).map_err(Error::from),
             daemon.run(&shutdown_listener).map_err(Error::from),
             conn_handler.map_err(Error::from),
+            ledger_handler.map_err(Error::from),
             sol_balance_monitor.map_err(Error::from),
         )?;
 
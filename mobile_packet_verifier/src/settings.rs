@@ -1,5 +1,4 @@
 use chrono::{DateTime, TimeZone, Utc};
-use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 use std::path::Path;
 
@@ -15,6 +14,10 @@ pub struct Settings {
     #[serde(default = "default_burn_period")]
     pub burn_period: i64,
     pub database: db_store::Settings,
+    /// Database of the iot packet verifier, whose pending burns and payer
+    /// ledger the sessions are debited to, so a payer shared with iot orgs
+    /// has a single balance
+    pub ledger: db_store::Settings,
     pub ingest: file_store::Settings,
     pub output: file_store::Settings,
     pub metrics: poc_metrics::Settings,
@@ -49,20 +52,8 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "VERIFY_". For example
     /// "VERIFY_DATABASE_URL" will override the data base url.
-    pub fn new(path: Option<impl AsRef<Path>>) -> Result<Self, ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of VERIFY)
-        // Eg.. `INJECT_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("MOBILE_PACKET_VERIFY").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new(path: Option<impl AsRef<Path>>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "MOBILE_PACKET_VERIFY")
     }
 
     pub fn start_after(&self) -> DateTime<Utc> {
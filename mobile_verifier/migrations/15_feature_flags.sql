@@ -0,0 +1,5 @@
+create table feature_flags (
+    name text primary key not null,
+    enabled boolean not null default false,
+    updated_at timestamptz not null default now()
+);
@@ -0,0 +1,6 @@
+create table reward_holds (
+    epoch_start timestamptz primary key not null,
+    reason text not null,
+    held_at timestamptz not null default now(),
+    released_at timestamptz
+);
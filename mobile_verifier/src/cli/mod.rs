@@ -1,2 +1,5 @@
+pub mod feature_flag;
+pub mod online_migration;
 pub mod reward_from_db;
+pub mod reward_hold;
 pub mod server;
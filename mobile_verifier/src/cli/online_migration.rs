@@ -0,0 +1,22 @@
+use crate::{online_migrations, Settings};
+use anyhow::Result;
+use db_store::online_migration;
+
+/// Drive the online migrations of the verifier database through their phases
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    cmd: online_migration::Cmd,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (pool, _join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown_listener)
+            .await?;
+        self.cmd.run(&pool, &online_migrations::all()).await?;
+        Ok(())
+    }
+}
@@ -44,7 +44,8 @@ impl Cmd {
 
         let mut total_rewards = 0_u64;
         let mut owner_rewards = HashMap::<_, u64>::new();
-        for reward in reward_shares.into_rewards(Decimal::ZERO, &epoch) {
+        let (rewards, _dust) = reward_shares.into_rewards(Decimal::ZERO, &epoch);
+        for reward in rewards {
             if let Some(proto::mobile_reward_share::Reward::RadioReward(proto::RadioReward {
                 hotspot_key,
                 poc_reward,
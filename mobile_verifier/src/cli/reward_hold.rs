@@ -0,0 +1,22 @@
+use crate::Settings;
+use anyhow::Result;
+use db_store::reward_holds;
+
+/// Hold or release the rewards of an epoch while it is investigated
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    cmd: reward_holds::Cmd,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (pool, _join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown_listener)
+            .await?;
+        self.cmd.run(&pool).await?;
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
-    data_session::DataSessionIngestor, heartbeats::HeartbeatDaemon, rewarder::Rewarder,
-    speedtests::SpeedtestDaemon, subscriber_location::SubscriberLocationIngestor, telemetry,
-    Settings,
+    data_session::DataSessionIngestor, heartbeats::HeartbeatDaemon, reward_quorum::RewardQuorum,
+    rewarder::Rewarder, speedtests::SpeedtestDaemon,
+    subscriber_location::SubscriberLocationIngestor, telemetry, Settings,
 };
 use anyhow::{Error, Result};
 use chrono::Duration;
+use db_store::feature_flags;
 use file_store::{
     file_info_poller::LookbackBehavior, file_sink, file_source, file_upload,
     heartbeat::CellHeartbeatIngestReport, mobile_subscriber::SubscriberLocationIngestReport,
@@ -13,7 +14,10 @@ use file_store::{
 };
 
 use futures_util::TryFutureExt;
-use mobile_config::client::{AuthorizationClient, EntityClient, GatewayClient};
+use mobile_config::client::{
+    AuthorizationClient, EntityClient, GatewayClient, RadioOnboardingClient,
+};
+use poc_metrics::preflight::Preflight;
 use price::PriceTracker;
 use tokio::signal;
 
@@ -37,10 +41,50 @@ impl Cmd {
             .database
             .connect(env!("CARGO_PKG_NAME"), shutdown_listener.clone())
             .await?;
+
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check("ingest bucket", settings.ingest.check_read())
+            .await;
+        preflight
+            .check(
+                "data transfer ingest bucket",
+                settings.data_transfer_ingest.check_read(),
+            )
+            .await;
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        preflight
+            .check("price bucket", settings.price_tracker.check_store())
+            .await;
+        preflight
+            .check("mobile config", settings.config_client.check_reachable())
+            .await;
+        preflight.check_result(
+            "mobile config keypair",
+            settings.config_client.signing_keypair(),
+        );
+        preflight.finish()?;
+
         sqlx::migrate!().run(&pool).await?;
 
         telemetry::initialize(&pool).await?;
 
+        let (feature_flags, feature_flags_join_handle) = feature_flags::start(
+            pool.clone(),
+            feature_flags::DEFAULT_REFRESH_INTERVAL,
+            shutdown_listener.clone(),
+        )
+        .await?;
+
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
             file_upload::FileUpload::from_settings(&settings.output, file_upload_rx).await?;
@@ -52,8 +96,13 @@ impl Cmd {
 
         // mobile config clients
         let gateway_client = GatewayClient::from_settings(&settings.config_client)?;
+        if let Err(err) = gateway_client.prewarm(&shutdown_listener).await {
+            tracing::warn!(?err, "failed to prewarm gateway info cache");
+        }
         let auth_client = AuthorizationClient::from_settings(&settings.config_client)?;
         let entity_client = EntityClient::from_settings(&settings.config_client)?;
+        let radio_onboarding_client =
+            RadioOnboardingClient::from_settings(&settings.config_client)?;
 
         // price tracker
         let (price_tracker, tracker_process) =
@@ -82,11 +131,27 @@ impl Cmd {
         .create()
         .await?;
 
+        // Heartbeats of radios failing the onboarding checks
+        let (onboarding_rejections, mut onboarding_rejections_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::MobileOnboardingRejection,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_onboarding_rejection"),
+                shutdown_listener.clone(),
+            )
+            .deposits(Some(file_upload_tx.clone()))
+            .auto_commit(false)
+            .roll_time(Duration::minutes(15))
+            .create()
+            .await?;
+
         let heartbeat_daemon = HeartbeatDaemon::new(
             pool.clone(),
             gateway_client.clone(),
+            radio_onboarding_client,
             heartbeats,
-            valid_heartbeats,
+            valid_heartbeats.clone(),
+            onboarding_rejections.clone(),
         );
 
         // Speedtests
@@ -116,7 +181,7 @@ impl Cmd {
             pool.clone(),
             gateway_client.clone(),
             speedtests,
-            valid_speedtests,
+            valid_speedtests.clone(),
         );
 
         // Mobile rewards
@@ -143,14 +208,34 @@ impl Cmd {
         .create()
         .await?;
 
+        // Dust of the reward pools, written with the manifests
+        let (reward_dust, mut reward_dust_server) = file_sink::FileSinkBuilder::new(
+            FileType::MobileRewardDust,
+            store_base_path,
+            concat!(env!("CARGO_PKG_NAME"), "_reward_dust"),
+            shutdown_listener.clone(),
+        )
+        .deposits(Some(file_upload_tx.clone()))
+        .auto_commit(false)
+        .create()
+        .await?;
+
+        let reward_quorum = match &settings.reward_quorum {
+            Some(quorum_settings) => Some(RewardQuorum::from_settings(quorum_settings).await?),
+            None => None,
+        };
+
         let rewarder = Rewarder::new(
             pool.clone(),
             Duration::hours(reward_period_hours),
             Duration::minutes(settings.reward_offset_minutes),
-            mobile_rewards,
-            reward_manifests,
+            mobile_rewards.clone(),
+            reward_manifests.clone(),
+            reward_dust.clone(),
             price_tracker,
             settings.disable_discovery_loc_rewards_to_s3,
+            feature_flags,
+            reward_quorum,
         );
 
         // subscriber location
@@ -181,7 +266,21 @@ impl Cmd {
             auth_client.clone(),
             entity_client.clone(),
             subscriber_location_ingest,
-            verified_subscriber_location,
+            verified_subscriber_location.clone(),
+        );
+
+        // the verifier halts on any of its sinks failing to store a write
+        let sink_failure = file_sink::halt_on_failure(
+            vec![
+                valid_heartbeats,
+                onboarding_rejections,
+                valid_speedtests,
+                mobile_rewards,
+                reward_manifests,
+                reward_dust,
+                verified_subscriber_location,
+            ],
+            shutdown_listener.clone(),
         );
 
         // data transfers
@@ -199,11 +298,15 @@ impl Cmd {
 
         tokio::try_join!(
             db_join_handle.map_err(Error::from),
+            feature_flags_join_handle.map_err(Error::from),
             valid_heartbeats_server.run().map_err(Error::from),
+            onboarding_rejections_server.run().map_err(Error::from),
             valid_speedtests_server.run().map_err(Error::from),
             mobile_rewards_server.run().map_err(Error::from),
             file_upload.run(&shutdown_listener).map_err(Error::from),
             reward_manifests_server.run().map_err(Error::from),
+            reward_dust_server.run().map_err(Error::from),
+            sink_failure.map_err(Error::from),
             verified_subscriber_location_server
                 .run()
                 .map_err(Error::from),
@@ -1,18 +1,24 @@
 //! Heartbeat storage
 
-use crate::cell_type::CellType;
+use crate::{cell_type::CellType, telemetry};
 use chrono::{DateTime, Duration, DurationRound, RoundingError, Utc};
 use file_store::{
-    file_info_poller::FileInfoStream, file_sink::FileSinkClient,
+    file_info_poller::FileInfoStream,
+    file_sink::FileSinkClient,
     heartbeat::CellHeartbeatIngestReport,
+    mobile_onboarding_rejection::{OnboardingRejectionReasonV1, OnboardingRejectionV1},
 };
 use futures::{
-    stream::{Stream, StreamExt, TryStreamExt},
+    stream::{self, Stream, StreamExt, TryStreamExt},
     TryFutureExt,
 };
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_mobile as proto;
-use mobile_config::{client::ClientError, gateway_info::GatewayInfoResolver, GatewayClient};
+use mobile_config::{
+    client::{radio_onboarding_client::RadioOnboarding, ClientError, RadioOnboardingClient},
+    gateway_info::GatewayInfoResolver,
+    GatewayClient,
+};
 use retainer::Cache;
 use rust_decimal::{prelude::ToPrimitive, Decimal};
 use sqlx::{Postgres, Transaction};
@@ -45,22 +51,28 @@ impl From<HeartbeatKey> for HeartbeatReward {
 pub struct HeartbeatDaemon {
     pool: sqlx::Pool<sqlx::Postgres>,
     gateway_client: GatewayClient,
+    radio_onboarding_client: RadioOnboardingClient,
     heartbeats: Receiver<FileInfoStream<CellHeartbeatIngestReport>>,
     file_sink: FileSinkClient,
+    onboarding_rejections: FileSinkClient,
 }
 
 impl HeartbeatDaemon {
     pub fn new(
         pool: sqlx::Pool<sqlx::Postgres>,
         gateway_client: GatewayClient,
+        radio_onboarding_client: RadioOnboardingClient,
         heartbeats: Receiver<FileInfoStream<CellHeartbeatIngestReport>>,
         file_sink: FileSinkClient,
+        onboarding_rejections: FileSinkClient,
     ) -> Self {
         Self {
             pool,
             gateway_client,
+            radio_onboarding_client,
             heartbeats,
             file_sink,
+            onboarding_rejections,
         }
     }
 
@@ -104,11 +116,20 @@ impl HeartbeatDaemon {
         let mut transaction = self.pool.begin().await?;
         let reports = file.into_stream(&mut transaction).await?;
 
-        let mut validated_heartbeats =
-            pin!(Heartbeat::validate_heartbeats(&self.gateway_client, reports, &epoch).await);
+        let mut validated_heartbeats = pin!(
+            Heartbeat::validate_heartbeats(
+                &self.gateway_client,
+                &self.radio_onboarding_client,
+                reports,
+                &epoch
+            )
+            .await
+        );
 
         while let Some(heartbeat) = validated_heartbeats.next().await.transpose()? {
-            heartbeat.write(&self.file_sink).await?;
+            heartbeat
+                .write(&self.file_sink, &self.onboarding_rejections)
+                .await?;
             let key = (heartbeat.cbsd_id.clone(), heartbeat.truncated_timestamp()?);
 
             if cache.get(&key).await.is_none() {
@@ -120,6 +141,7 @@ impl HeartbeatDaemon {
         }
 
         self.file_sink.commit().await?;
+        self.onboarding_rejections.commit().await?;
         transaction.commit().await?;
 
         Ok(())
@@ -129,6 +151,10 @@ impl HeartbeatDaemon {
 /// Minimum number of heartbeats required to give a reward to the hotspot.
 pub const MINIMUM_HEARTBEAT_COUNT: i64 = 12;
 
+/// Number of heartbeats whose hotspots are resolved with a single gateway info
+/// request before validating them
+const GATEWAY_BATCH_SIZE: usize = 500;
+
 impl HeartbeatReward {
     pub fn validated<'a>(
         exec: impl sqlx::PgExecutor<'a> + Copy + 'a,
@@ -159,6 +185,46 @@ pub struct Heartbeat {
     pub hotspot_key: PublicKeyBinary,
     pub timestamp: DateTime<Utc>,
     pub validity: proto::HeartbeatValidity,
+    /// Why the radio failed the onboarding checks, if it did
+    pub onboarding_failure: Option<OnboardingFailure>,
+}
+
+/// Why a radio failed the onboarding checks. helium-proto has no heartbeat
+/// validity for these, so the heartbeats keep the validity of the other
+/// checks and are written with this reason to the onboarding rejection sink
+/// instead of among the validated heartbeats.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OnboardingFailure {
+    /// The hotspot of the radio has no onboarding record
+    NotOnboarded,
+    /// The hotspot of the radio was onboarded by a maker not approved, with
+    /// the address of the maker if known
+    MakerNotApproved(Option<String>),
+}
+
+impl OnboardingFailure {
+    /// The onboarding check failed by a radio whose hotspot has the
+    /// onboarding record `onboarding`, if any
+    pub fn check(onboarding: Option<&RadioOnboarding>) -> Option<Self> {
+        match onboarding {
+            None => Some(Self::NotOnboarded),
+            Some(onboarding) if !onboarding.maker_approved => {
+                Some(Self::MakerNotApproved(onboarding.maker.clone()))
+            }
+            Some(_) => None,
+        }
+    }
+
+    pub fn reason(&self) -> OnboardingRejectionReasonV1 {
+        match self {
+            Self::NotOnboarded => OnboardingRejectionReasonV1::RadioNotOnboarded,
+            Self::MakerNotApproved(_) => OnboardingRejectionReasonV1::MakerNotApproved,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.reason().as_str()
+    }
 }
 
 #[derive(sqlx::FromRow)]
@@ -181,26 +247,77 @@ impl Heartbeat {
 
     pub async fn validate_heartbeats<'a>(
         gateway_client: &'a GatewayClient,
+        radio_onboarding_client: &'a RadioOnboardingClient,
         heartbeats: impl Stream<Item = CellHeartbeatIngestReport> + 'a,
         epoch: &'a Range<DateTime<Utc>>,
     ) -> impl Stream<Item = Result<Self, ClientError>> + 'a {
-        heartbeats.then(move |heartbeat_report| {
-            let mut gateway_client = gateway_client.clone();
-            async move {
-                let (cell_type, validity) =
-                    validate_heartbeat(&heartbeat_report, &mut gateway_client, epoch).await?;
-                Ok(Heartbeat {
-                    hotspot_key: heartbeat_report.report.pubkey,
-                    cbsd_id: heartbeat_report.report.cbsd_id,
-                    timestamp: heartbeat_report.received_timestamp,
-                    cell_type,
-                    validity,
-                })
-            }
-        })
+        heartbeats
+            .chunks(GATEWAY_BATCH_SIZE)
+            .then(move |batch| async move {
+                // Fills the gateway cache the validation of each heartbeat
+                // hits, which resolves any gateway left out on its own
+                let addresses: Vec<PublicKeyBinary> = batch
+                    .iter()
+                    .map(|heartbeat| heartbeat.report.pubkey.clone())
+                    .collect();
+                if let Err(err) = gateway_client.resolve_gateway_info_batch(&addresses).await {
+                    tracing::warn!(?err, "failed to resolve gateway info batch");
+                }
+                stream::iter(batch)
+            })
+            .flatten()
+            .then(move |heartbeat_report| {
+                let mut gateway_client = gateway_client.clone();
+                async move {
+                    let (cell_type, validity) =
+                        validate_heartbeat(&heartbeat_report, &mut gateway_client, epoch).await?;
+                    let onboarding_failure = if validity == proto::HeartbeatValidity::Valid {
+                        validate_onboarding(&heartbeat_report, radio_onboarding_client).await?
+                    } else {
+                        None
+                    };
+                    Ok(Heartbeat {
+                        hotspot_key: heartbeat_report.report.pubkey,
+                        cbsd_id: heartbeat_report.report.cbsd_id,
+                        timestamp: heartbeat_report.received_timestamp,
+                        cell_type,
+                        validity,
+                        onboarding_failure,
+                    })
+                }
+            })
     }
 
-    pub async fn write(&self, heartbeats: &FileSinkClient) -> file_store::Result {
+    /// Whether the heartbeat passed every check, onboarding included
+    pub fn is_valid(&self) -> bool {
+        self.validity == proto::HeartbeatValidity::Valid && self.onboarding_failure.is_none()
+    }
+
+    /// Write the heartbeat to `heartbeats`, or to `onboarding_rejections`
+    /// when it failed the onboarding checks
+    pub async fn write(
+        &self,
+        heartbeats: &FileSinkClient,
+        onboarding_rejections: &FileSinkClient,
+    ) -> file_store::Result {
+        if let Some(failure) = &self.onboarding_failure {
+            onboarding_rejections
+                .write(
+                    OnboardingRejectionV1 {
+                        cbsd_id: self.cbsd_id.clone(),
+                        pub_key: self.hotspot_key.clone().into(),
+                        timestamp: self.timestamp.timestamp() as u64,
+                        reason: failure.reason() as i32,
+                        maker: match failure {
+                            OnboardingFailure::MakerNotApproved(Some(maker)) => maker.clone(),
+                            _ => String::new(),
+                        },
+                    },
+                    &[("reason", failure.as_str())],
+                )
+                .await?;
+            return Ok(());
+        }
         heartbeats
             .write(
                 proto::Heartbeat {
@@ -225,7 +342,7 @@ impl Heartbeat {
         exec: &mut Transaction<'_, Postgres>,
     ) -> Result<bool, SaveHeartbeatError> {
         // If the heartbeat is not valid, do not save it
-        if self.validity != proto::HeartbeatValidity::Valid {
+        if !self.is_valid() {
             return Ok(false);
         }
 
@@ -287,3 +404,89 @@ async fn validate_heartbeat(
 
     Ok((cell_type, proto::HeartbeatValidity::Valid))
 }
+
+/// Check the hotspot of the radio sending `heartbeat` was onboarded by an
+/// approved maker, counting the heartbeats of every maker
+async fn validate_onboarding(
+    heartbeat: &CellHeartbeatIngestReport,
+    radio_onboarding_client: &RadioOnboardingClient,
+) -> Result<Option<OnboardingFailure>, ClientError> {
+    let onboarding = radio_onboarding_client
+        .resolve_onboarding(&heartbeat.report.pubkey)
+        .await?;
+    let failure = OnboardingFailure::check(onboarding.as_ref());
+    let maker = match &onboarding {
+        None => "none",
+        Some(onboarding) => onboarding
+            .maker_name
+            .as_deref()
+            .or(onboarding.maker.as_deref())
+            .unwrap_or("unknown"),
+    };
+    let validity = failure
+        .as_ref()
+        .map_or(proto::HeartbeatValidity::Valid.as_str_name(), |failure| {
+            failure.as_str()
+        });
+    telemetry::count_maker_heartbeat(maker, validity);
+    Ok(failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn onboarding(maker: Option<&str>, maker_approved: bool) -> RadioOnboarding {
+        RadioOnboarding {
+            onboarded_at: None,
+            maker: maker.map(str::to_string),
+            maker_name: None,
+            maker_approved,
+        }
+    }
+
+    fn heartbeat(onboarding_failure: Option<OnboardingFailure>) -> Heartbeat {
+        Heartbeat {
+            cbsd_id: "P27-SCE4255W2107CW5000014".to_string(),
+            cell_type: Some(CellType::Nova436H),
+            hotspot_key: PublicKeyBinary::from(vec![1]),
+            timestamp: Utc::now(),
+            validity: proto::HeartbeatValidity::Valid,
+            onboarding_failure,
+        }
+    }
+
+    #[test]
+    fn onboarding_failures() {
+        assert_eq!(
+            OnboardingFailure::check(None),
+            Some(OnboardingFailure::NotOnboarded)
+        );
+        assert_eq!(
+            OnboardingFailure::check(Some(&onboarding(Some("maker"), false))),
+            Some(OnboardingFailure::MakerNotApproved(Some(
+                "maker".to_string()
+            )))
+        );
+        assert_eq!(
+            OnboardingFailure::check(Some(&onboarding(None, false))),
+            Some(OnboardingFailure::MakerNotApproved(None))
+        );
+        assert_eq!(
+            OnboardingFailure::check(Some(&onboarding(Some("maker"), true))),
+            None
+        );
+        assert_eq!(
+            OnboardingFailure::MakerNotApproved(None).reason(),
+            OnboardingRejectionReasonV1::MakerNotApproved
+        );
+    }
+
+    #[test]
+    fn onboarding_failure_keeps_the_validity_but_is_not_valid() {
+        assert!(heartbeat(None).is_valid());
+        let rejected = heartbeat(Some(OnboardingFailure::NotOnboarded));
+        assert_eq!(rejected.validity, proto::HeartbeatValidity::Valid);
+        assert!(!rejected.is_valid());
+    }
+}
@@ -1,6 +1,7 @@
 mod cell_type;
 mod data_session;
 mod heartbeats;
+mod online_migrations;
 mod reward_shares;
 mod settings;
 mod speedtests;
@@ -8,6 +9,7 @@ mod subscriber_location;
 mod telemetry;
 
 pub mod cli;
+pub mod reward_quorum;
 pub mod rewarder;
 
 pub use settings::Settings;
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 use mobile_verifier::{
-    cli::{reward_from_db, server},
+    cli::{feature_flag, online_migration, reward_from_db, reward_hold, server},
     Settings,
 };
 use std::path;
@@ -36,6 +36,9 @@ impl Cli {
 pub enum Cmd {
     Server(server::Cmd),
     RewardFromDb(reward_from_db::Cmd),
+    FeatureFlag(feature_flag::Cmd),
+    OnlineMigration(online_migration::Cmd),
+    RewardHold(reward_hold::Cmd),
 }
 
 impl Cmd {
@@ -43,6 +46,9 @@ impl Cmd {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
             Self::RewardFromDb(cmd) => cmd.run(&settings).await,
+            Self::FeatureFlag(cmd) => cmd.run(&settings).await,
+            Self::OnlineMigration(cmd) => cmd.run(&settings).await,
+            Self::RewardHold(cmd) => cmd.run(&settings).await,
         }
     }
 }
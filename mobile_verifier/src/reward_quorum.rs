@@ -0,0 +1,428 @@
+//! Agreement of several reward oracles on an epoch before its manifest is
+//! published.
+
+use chrono::{DateTime, Utc};
+use file_store::FileStore;
+use helium_crypto::{Keypair, PublicKey, PublicKeyBinary, Sign, Verify};
+use helium_proto::services::poc_mobile::MobileRewardShare;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+const ATTESTATION_PREFIX: &str = "reward_attestation";
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// File from which to load the keypair signing the attestations of this
+    /// oracle
+    pub keypair: String,
+    /// Bucket the attestations of this oracle are written to, read by its
+    /// peers
+    pub attestations: file_store::Settings,
+    /// The other oracles rewarding the same epochs
+    #[serde(default)]
+    pub peers: Vec<PeerSettings>,
+    /// Number of oracles, this one included, that must attest to the same
+    /// rewards before the manifest is published
+    pub quorum: usize,
+    /// How long to wait for the attestations of the peers. Default is 30
+    /// minutes
+    #[serde(with = "settings_loader::duration", default = "default_timeout")]
+    pub timeout: Duration,
+    /// How often the buckets of the peers are checked for their attestations.
+    /// Default is 1 minute
+    #[serde(with = "settings_loader::duration", default = "default_poll_interval")]
+    pub poll_interval: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PeerSettings {
+    /// Public key the peer signs its attestations with
+    pub pubkey: PublicKeyBinary,
+    /// Bucket the peer writes its attestations to
+    pub attestations: file_store::Settings,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(30 * 60)
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// Digest of the reward shares of an epoch, whatever the order they were
+/// written in
+#[derive(Default)]
+pub struct EpochDigest {
+    shares: Vec<[u8; 32]>,
+}
+
+impl EpochDigest {
+    pub fn add(&mut self, share: &MobileRewardShare) {
+        self.shares
+            .push(Sha256::digest(share.encode_to_vec()).into());
+    }
+
+    pub fn finalize(mut self) -> String {
+        self.shares.sort_unstable();
+        let mut hasher = Sha256::new();
+        for share in &self.shares {
+            hasher.update(share);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// What the rewards of an epoch were computed from, logged when oracles
+/// diverge
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpochInputs {
+    pub mobile_price: u64,
+    pub data_transfer_scale: f64,
+    pub poc_shares: u64,
+    pub transfer_shares: u64,
+    pub mapping_shares: u64,
+    pub poc_dust: u64,
+    pub transfer_dust: u64,
+    pub mapping_dust: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attestation {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub digest: String,
+    pub inputs: EpochInputs,
+    pub signer: PublicKeyBinary,
+    pub signature: Vec<u8>,
+}
+
+impl Attestation {
+    fn signed_bytes(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(&Attestation {
+            signature: vec![],
+            ..self.clone()
+        })
+    }
+
+    fn verify(&self) -> anyhow::Result<()> {
+        let signer = PublicKey::try_from(self.signer.clone())?;
+        signer.verify(&self.signed_bytes()?, &self.signature)?;
+        Ok(())
+    }
+
+    fn agrees_with(&self, other: &Attestation) -> bool {
+        self.digest == other.digest && self.start == other.start
+    }
+}
+
+fn attestation_key(end: DateTime<Utc>) -> String {
+    format!("{ATTESTATION_PREFIX}.{}.json", end.timestamp_millis())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Agreement {
+    /// The quorum attested to the same rewards
+    Reached,
+    /// Too many oracles attested to other rewards for the quorum to be reached
+    Diverged,
+    /// The peers didn't attest within the timeout
+    TimedOut,
+}
+
+impl Agreement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reached => "reached",
+            Self::Diverged => "diverged",
+            Self::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// Attestations of the peers read in one poll
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Tally {
+    agreeing: usize,
+    diverging: usize,
+    pending: usize,
+}
+
+impl Tally {
+    fn count<'a>(
+        ours: &Attestation,
+        theirs: impl IntoIterator<Item = Option<&'a Attestation>>,
+    ) -> Self {
+        theirs
+            .into_iter()
+            .fold(Self::default(), |mut tally, theirs| {
+                match theirs {
+                    Some(theirs) if ours.agrees_with(theirs) => tally.agreeing += 1,
+                    Some(_) => tally.diverging += 1,
+                    None => tally.pending += 1,
+                }
+                tally
+            })
+    }
+
+    /// The agreement of `quorum` oracles, this one included, if settled. A
+    /// diverging peer may still compute the epoch again and agree, so the
+    /// quorum only diverges once `timed_out`
+    fn agreement(&self, quorum: usize, timed_out: bool) -> Option<Agreement> {
+        if self.agreeing + 1 >= quorum {
+            Some(Agreement::Reached)
+        } else if !timed_out {
+            None
+        } else if self.agreeing + 1 + self.pending < quorum {
+            Some(Agreement::Diverged)
+        } else {
+            Some(Agreement::TimedOut)
+        }
+    }
+}
+
+struct Peer {
+    pubkey: PublicKeyBinary,
+    attestations: FileStore,
+}
+
+/// Collects the attestations of the oracles rewarding an epoch. Every oracle
+/// digests the reward shares it wrote and writes a signed attestation of the
+/// digest to its attestation bucket, read by its peers
+pub struct RewardQuorum {
+    keypair: Keypair,
+    attestations: FileStore,
+    peers: Vec<Peer>,
+    quorum: usize,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl RewardQuorum {
+    pub async fn from_settings(settings: &Settings) -> anyhow::Result<Self> {
+        let data = std::fs::read(&settings.keypair)?;
+        let keypair = Keypair::try_from(&data[..])?;
+        let mut peers = Vec::with_capacity(settings.peers.len());
+        for peer in &settings.peers {
+            peers.push(Peer {
+                pubkey: peer.pubkey.clone(),
+                attestations: FileStore::from_settings(&peer.attestations).await?,
+            });
+        }
+        anyhow::ensure!(
+            settings.quorum > 0 && settings.quorum <= peers.len() + 1,
+            "reward quorum of {} out of {} oracles",
+            settings.quorum,
+            peers.len() + 1
+        );
+        Ok(Self {
+            keypair,
+            attestations: FileStore::from_settings(&settings.attestations).await?,
+            peers,
+            quorum: settings.quorum,
+            timeout: settings.timeout,
+            poll_interval: settings.poll_interval,
+        })
+    }
+
+    /// Attest to the rewards of `reward_period` and wait for the quorum to
+    /// attest to the same digest. The attestations of the peers are read
+    /// again every `poll_interval`, as a peer computing the epoch again
+    /// replaces its attestation. A peer attesting to another digest is logged
+    /// with the inputs of both oracles. Without a quorum within `timeout` the
+    /// manifest is refused, and the rewarder computes the epoch again after
+    /// its usual delay
+    pub async fn agree(
+        &self,
+        reward_period: &Range<DateTime<Utc>>,
+        digest: String,
+        inputs: EpochInputs,
+    ) -> anyhow::Result<Agreement> {
+        let mut attestation = Attestation {
+            start: reward_period.start,
+            end: reward_period.end,
+            digest,
+            inputs,
+            signer: self.keypair.public_key().clone().into(),
+            signature: vec![],
+        };
+        attestation.signature = self.keypair.sign(&attestation.signed_bytes()?)?;
+        self.attestations
+            .put_bytes(
+                &attestation_key(reward_period.end),
+                serde_json::to_vec(&attestation)?,
+            )
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        // Digests each diverging peer was last logged with
+        let mut diverged: HashMap<PublicKeyBinary, String> = HashMap::new();
+        loop {
+            let mut theirs = Vec::with_capacity(self.peers.len());
+            for peer in &self.peers {
+                let attestation_of_peer = self.fetch_attestation(peer, reward_period).await;
+                if let Some(peer_attestation) = &attestation_of_peer {
+                    if attestation.agrees_with(peer_attestation) {
+                        diverged.remove(&peer.pubkey);
+                    } else if diverged.get(&peer.pubkey) != Some(&peer_attestation.digest) {
+                        tracing::error!(
+                            peer = %peer.pubkey,
+                            ours = ?attestation.inputs,
+                            theirs = ?peer_attestation.inputs,
+                            "reward attestation of peer diverges"
+                        );
+                        diverged.insert(peer.pubkey.clone(), peer_attestation.digest.clone());
+                    }
+                }
+                theirs.push(attestation_of_peer);
+            }
+
+            let tally = Tally::count(&attestation, theirs.iter().map(Option::as_ref));
+            tracing::info!(
+                agreeing = tally.agreeing + 1,
+                diverging = tally.diverging,
+                pending = tally.pending,
+                quorum = self.quorum,
+                "collected reward attestations"
+            );
+            let timed_out = tokio::time::Instant::now() + self.poll_interval > deadline;
+            if let Some(agreement) = tally.agreement(self.quorum, timed_out) {
+                return Ok(agreement);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// The verified attestation of `peer` for the epoch ending with
+    /// `reward_period`, if it has written one yet
+    async fn fetch_attestation(
+        &self,
+        peer: &Peer,
+        reward_period: &Range<DateTime<Utc>>,
+    ) -> Option<Attestation> {
+        let key = attestation_key(reward_period.end);
+        let attestation = match peer.attestations.get_bytes(key).await {
+            Ok(bytes) => serde_json::from_slice::<Attestation>(&bytes),
+            Err(err) => {
+                tracing::debug!(peer = %peer.pubkey, ?err, "no reward attestation of peer yet");
+                return None;
+            }
+        };
+        match attestation {
+            Ok(attestation) if attestation.signer != peer.pubkey => {
+                tracing::warn!(peer = %peer.pubkey, "reward attestation signed by another key");
+                None
+            }
+            Ok(attestation) => match attestation.verify() {
+                Ok(()) => Some(attestation),
+                Err(err) => {
+                    tracing::warn!(peer = %peer.pubkey, ?err, "invalid reward attestation");
+                    None
+                }
+            },
+            Err(err) => {
+                tracing::warn!(peer = %peer.pubkey, ?err, "undecodable reward attestation");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_proto::services::poc_mobile::{mobile_reward_share::Reward, GatewayReward};
+
+    fn share(dc_transfer_reward: u64) -> MobileRewardShare {
+        MobileRewardShare {
+            start_period: 0,
+            end_period: 86400,
+            reward: Some(Reward::GatewayReward(GatewayReward {
+                hotspot_key: vec![1, 2, 3],
+                dc_transfer_reward,
+            })),
+        }
+    }
+
+    #[test]
+    fn digest_ignores_share_order() {
+        let mut first = EpochDigest::default();
+        first.add(&share(1));
+        first.add(&share(2));
+        let mut second = EpochDigest::default();
+        second.add(&share(2));
+        second.add(&share(1));
+        let mut third = EpochDigest::default();
+        third.add(&share(1));
+        third.add(&share(3));
+
+        let digest = first.finalize();
+        assert_eq!(digest, second.finalize());
+        assert_ne!(digest, third.finalize());
+    }
+
+    fn attestation(digest: &str) -> Attestation {
+        Attestation {
+            start: DateTime::<Utc>::MIN_UTC,
+            end: DateTime::<Utc>::MAX_UTC,
+            digest: digest.to_string(),
+            inputs: EpochInputs::default(),
+            signer: PublicKeyBinary::from(vec![0]),
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn diverging_peers_are_tallied_again_every_poll() {
+        let ours = attestation("a");
+        let agreeing = attestation("a");
+        let diverging = attestation("b");
+
+        // Two of four peers diverge, one hasn't attested yet:
+        let first = Tally::count(
+            &ours,
+            [Some(&agreeing), Some(&diverging), Some(&diverging), None],
+        );
+        assert_eq!(
+            first,
+            Tally {
+                agreeing: 1,
+                diverging: 2,
+                pending: 1,
+            }
+        );
+        // The quorum can't be reached without a diverging peer, which may
+        // still compute the epoch again:
+        assert_eq!(first.agreement(4, false), None);
+        assert_eq!(first.agreement(4, true), Some(Agreement::Diverged));
+        assert_eq!(first.agreement(3, true), Some(Agreement::TimedOut));
+        assert_eq!(first.agreement(2, false), Some(Agreement::Reached));
+
+        // Until one does and agrees:
+        let second = Tally::count(
+            &ours,
+            [Some(&agreeing), Some(&agreeing), Some(&diverging), None],
+        );
+        assert_eq!(second.agreement(4, false), None);
+        assert_eq!(second.agreement(3, false), Some(Agreement::Reached));
+    }
+
+    #[test]
+    fn attestations_of_other_epochs_diverge() {
+        let ours = attestation("a");
+        let other_epoch = Attestation {
+            start: DateTime::<Utc>::MAX_UTC,
+            ..attestation("a")
+        };
+
+        let tally = Tally::count(&ours, [Some(&other_epoch)]);
+        assert_eq!(tally.diverging, 1);
+        assert_eq!(tally.agreement(2, true), Some(Agreement::Diverged));
+        assert_eq!(
+            Tally::default().agreement(1, false),
+            Some(Agreement::Reached)
+        );
+    }
+}
@@ -11,6 +11,7 @@ use futures::{Stream, StreamExt};
 use helium_crypto::PublicKeyBinary;
 use helium_proto::services::poc_mobile as proto;
 use helium_proto::services::poc_mobile::mobile_reward_share::Reward as ProtoReward;
+use reward_scheduler::reward_share::RewardPool;
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
 use std::collections::HashMap;
@@ -104,32 +105,40 @@ impl TransferRewards {
         }
     }
 
+    /// The data transfer rewards of every hotspot, along with the dust left
+    /// over from rounding them down to whole bones
     pub fn into_rewards(
         self,
         epoch: &'_ Range<DateTime<Utc>>,
-    ) -> impl Iterator<Item = proto::MobileRewardShare> + '_ {
+    ) -> (impl Iterator<Item = proto::MobileRewardShare> + '_, u64) {
         let Self {
             reward_scale,
             rewards,
-            ..
+            reward_sum,
         } = self;
+        let mut pool = RewardPool::with_rewards_per_share(reward_sum, reward_scale);
+        let rewards = pool.allocate_all(
+            rewards
+                .into_iter()
+                .map(|(hotspot_key, reward)| (Vec::<u8>::from(hotspot_key), reward)),
+        );
         let start_period = epoch.start.encode_timestamp();
         let end_period = epoch.end.encode_timestamp();
-        rewards
+        let rewards = rewards
             .into_iter()
-            .map(move |(hotspot_key, reward)| proto::MobileRewardShare {
-                start_period,
-                end_period,
-                reward: Some(proto::mobile_reward_share::Reward::GatewayReward(
-                    proto::GatewayReward {
-                        hotspot_key: hotspot_key.into(),
-                        dc_transfer_reward: (reward * reward_scale)
-                            .round_dp_with_strategy(0, RoundingStrategy::ToZero)
-                            .to_u64()
-                            .unwrap_or(0),
-                    },
-                )),
-            })
+            .map(
+                move |(hotspot_key, dc_transfer_reward)| proto::MobileRewardShare {
+                    start_period,
+                    end_period,
+                    reward: Some(proto::mobile_reward_share::Reward::GatewayReward(
+                        proto::GatewayReward {
+                            hotspot_key,
+                            dc_transfer_reward,
+                        },
+                    )),
+                },
+            );
+        (rewards, pool.dust())
     }
 }
 
@@ -145,10 +154,7 @@ impl MapperShares {
         }
     }
 
-    pub fn rewards_per_share(
-        &self,
-        reward_period: &'_ Range<DateTime<Utc>>,
-    ) -> anyhow::Result<Decimal> {
+    fn reward_pool(&self, reward_period: &'_ Range<DateTime<Utc>>) -> RewardPool {
         // note: currently rewards_per_share calculation only takes into
         // consideration discovery mapping shares
         // in the future it will also need to take into account
@@ -164,32 +170,36 @@ impl MapperShares {
         // however the fuction is setup to allow the verification mapper shares to be easily
         // added without impacting code structure ( the per share value for those will be different )
         let total_mapper_shares = discovery_mappers_count * DISCOVERY_MAPPING_SHARES;
-        let res = total_mappers_pool
-            .checked_div(total_mapper_shares)
-            .unwrap_or(Decimal::ZERO);
-        Ok(res)
+        RewardPool::new(total_mappers_pool, total_mapper_shares)
     }
 
+    /// The discovery mapping rewards of every subscriber, along with the dust
+    /// left over from rounding them down to whole bones
     pub fn into_subscriber_rewards(
         self,
         reward_period: &'_ Range<DateTime<Utc>>,
-        reward_per_share: Decimal,
-    ) -> impl Iterator<Item = proto::MobileRewardShare> + '_ {
-        self.discovery_mapping_shares
+    ) -> (impl Iterator<Item = proto::MobileRewardShare> + '_, u64) {
+        let mut pool = self.reward_pool(reward_period);
+        let rewards = pool.allocate_all(
+            self.discovery_mapping_shares
+                .into_iter()
+                .map(|subscriber_id| (subscriber_id, DISCOVERY_MAPPING_SHARES)),
+        );
+        let rewards = rewards
             .into_iter()
-            .map(move |subscriber_id| proto::SubscriberReward {
-                subscriber_id,
-                discovery_location_amount: (DISCOVERY_MAPPING_SHARES * reward_per_share)
-                    .round_dp_with_strategy(0, RoundingStrategy::ToZero)
-                    .to_u64()
-                    .unwrap_or(0),
-            })
+            .map(
+                |(subscriber_id, discovery_location_amount)| proto::SubscriberReward {
+                    subscriber_id,
+                    discovery_location_amount,
+                },
+            )
             .filter(|subscriber_reward| subscriber_reward.discovery_location_amount > 0)
             .map(|subscriber_reward| proto::MobileRewardShare {
                 start_period: reward_period.start.encode_timestamp(),
                 end_period: reward_period.end.encode_timestamp(),
                 reward: Some(ProtoReward::SubscriberReward(subscriber_reward)),
-            })
+            });
+        (rewards, pool.dust())
     }
 }
 
@@ -257,46 +267,47 @@ impl PocShares {
             })
     }
 
+    /// The poc rewards of every radio, along with the dust left over from
+    /// rounding them down to whole bones
     pub fn into_rewards(
         self,
         transfer_rewards_sum: Decimal,
         epoch: &'_ Range<DateTime<Utc>>,
-    ) -> impl Iterator<Item = proto::MobileRewardShare> + '_ {
+    ) -> (impl Iterator<Item = proto::MobileRewardShare> + '_, u64) {
         let total_shares = self.total_shares();
         let available_poc_rewards =
             get_scheduled_tokens_for_poc_and_dc(epoch.end - epoch.start) - transfer_rewards_sum;
-        let poc_rewards_per_share = available_poc_rewards / total_shares;
+        let radio_shares = self.hotspot_shares.into_iter().flat_map(
+            |(hotspot_key, RadioShares { radio_shares })| {
+                let hotspot_key: Vec<u8> = hotspot_key.into();
+                radio_shares
+                    .into_iter()
+                    .map(move |(cbsd_id, amount)| ((hotspot_key.clone(), cbsd_id), amount))
+            },
+        );
+        // radios are allocated in hotspot key and cbsd id order
+        let mut pool = RewardPool::new(available_poc_rewards, total_shares);
+        let rewards = pool.allocate_all(radio_shares);
         let start_period = epoch.start.encode_timestamp();
         let end_period = epoch.end.encode_timestamp();
-        self.hotspot_shares
+        let rewards = rewards
             .into_iter()
-            .flat_map(move |(hotspot_key, RadioShares { radio_shares })| {
-                radio_shares.into_iter().map(move |(cbsd_id, amount)| {
-                    let poc_reward = poc_rewards_per_share * amount;
-                    let hotspot_key: Vec<u8> = hotspot_key.clone().into();
-                    proto::MobileRewardShare {
-                        start_period,
-                        end_period,
-                        reward: Some(proto::mobile_reward_share::Reward::RadioReward(
-                            proto::RadioReward {
-                                hotspot_key,
-                                cbsd_id,
-                                poc_reward: poc_reward
-                                    .round_dp_with_strategy(0, RoundingStrategy::ToZero)
-                                    .to_u64()
-                                    .unwrap_or(0),
-                                ..Default::default()
-                            },
-                        )),
-                    }
-                })
-            })
-            .filter(|mobile_reward| match mobile_reward.reward {
-                Some(proto::mobile_reward_share::Reward::RadioReward(ref radio_reward)) => {
-                    radio_reward.poc_reward > 0
-                }
-                _ => false,
-            })
+            .filter(|(_, poc_reward)| *poc_reward > 0)
+            .map(
+                move |((hotspot_key, cbsd_id), poc_reward)| proto::MobileRewardShare {
+                    start_period,
+                    end_period,
+                    reward: Some(proto::mobile_reward_share::Reward::RadioReward(
+                        proto::RadioReward {
+                            hotspot_key,
+                            cbsd_id,
+                            poc_reward,
+                            ..Default::default()
+                        },
+                    )),
+                },
+            );
+        (rewards, pool.dust())
     }
 }
 
@@ -368,7 +379,6 @@ mod test {
 
         // translate location shares into discovery mapping shares
         let mapping_shares = MapperShares::new(location_shares);
-        let rewards_per_share = mapping_shares.rewards_per_share(&epoch).unwrap();
 
         // verify total rewards for the epoch
         let total_epoch_rewards = get_total_scheduled_tokens(epoch.end - epoch.start)
@@ -388,7 +398,8 @@ mod test {
 
         // get the summed rewards allocated to subscribers for discovery location
         let mut total_discovery_mapping_rewards = 0_u64;
-        for subscriber_share in mapping_shares.into_subscriber_rewards(&epoch, rewards_per_share) {
+        let (subscriber_shares, dust) = mapping_shares.into_subscriber_rewards(&epoch);
+        for subscriber_share in subscriber_shares {
             if let Some(MobileReward::SubscriberReward(r)) = subscriber_share.reward {
                 total_discovery_mapping_rewards += r.discovery_location_amount;
                 assert_eq!(expected_reward_per_subscriber, r.discovery_location_amount);
@@ -404,6 +415,8 @@ mod test {
         // the difference in bones cannot be more than the total number of subscribers ( 10 k)
         let diff = total_mapper_rewards - total_discovery_mapping_rewards;
         assert!(diff < NUM_SUBSCRIBERS);
+        // and that difference is accounted for as dust
+        assert_eq!(diff, dust);
     }
 
     #[tokio::test]
@@ -815,6 +828,7 @@ mod test {
             .await
             .unwrap()
             .into_rewards(Decimal::ZERO, &epoch)
+            .0
         {
             let radio_reward = match mobile_reward.reward {
                 Some(proto::mobile_reward_share::Reward::RadioReward(radio_reward)) => radio_reward,
@@ -895,7 +909,8 @@ mod test {
         let owner_shares = PocShares { hotspot_shares };
         let epoch = now - Duration::hours(1)..now;
         let expected_hotspot = gw1;
-        for mobile_reward in owner_shares.into_rewards(Decimal::ZERO, &epoch) {
+        let (mobile_rewards, _dust) = owner_shares.into_rewards(Decimal::ZERO, &epoch);
+        for mobile_reward in mobile_rewards {
             let radio_reward = match mobile_reward.reward {
                 Some(proto::mobile_reward_share::Reward::RadioReward(radio_reward)) => radio_reward,
                 _ => unreachable!(),
@@ -1,14 +1,19 @@
 use crate::{
     data_session,
     heartbeats::HeartbeatReward,
+    reward_quorum::{Agreement, EpochDigest, EpochInputs, RewardQuorum},
     reward_shares::{MapperShares, PocShares, TransferRewards},
     speedtests::SpeedtestAverages,
     subscriber_location, telemetry,
 };
 use anyhow::bail;
 use chrono::{DateTime, Duration, TimeZone, Utc};
-use db_store::meta;
-use file_store::{file_sink::FileSinkClient, traits::TimestampEncode};
+use db_store::{meta, reward_holds, FeatureFlags};
+use file_store::{
+    file_sink::FileSinkClient,
+    mobile_reward_dust::{RewardDust, RewardDustV1},
+    traits::TimestampEncode,
+};
 use helium_proto::services::poc_mobile::mobile_reward_share::Reward as ProtoReward;
 use helium_proto::RewardManifest;
 use price::PriceTracker;
@@ -21,25 +26,36 @@ use tokio::time::sleep;
 
 const REWARDS_NOT_CURRENT_DELAY_PERIOD: i64 = 5;
 
+/// Feature flag writing discovery location rewards to s3 regardless of the
+/// `disable_discovery_loc_rewards_to_s3` setting
+pub const DISCOVERY_LOC_REWARDS_TO_S3_FLAG: &str = "mobile_verifier.discovery_loc_rewards_to_s3";
+
 pub struct Rewarder {
     pool: Pool<Postgres>,
     reward_period_duration: Duration,
     reward_offset: Duration,
     mobile_rewards: FileSinkClient,
     reward_manifests: FileSinkClient,
+    reward_dust: FileSinkClient,
     price_tracker: PriceTracker,
     disable_discovery_loc_rewards_to_s3: bool,
+    feature_flags: FeatureFlags,
+    quorum: Option<RewardQuorum>,
 }
 
 impl Rewarder {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool: Pool<Postgres>,
         reward_period_duration: Duration,
         reward_offset: Duration,
         mobile_rewards: FileSinkClient,
         reward_manifests: FileSinkClient,
+        reward_dust: FileSinkClient,
         price_tracker: PriceTracker,
         disable_discovery_loc_rewards_to_s3: bool,
+        feature_flags: FeatureFlags,
+        quorum: Option<RewardQuorum>,
     ) -> Self {
         Self {
             pool,
@@ -47,8 +63,11 @@ impl Rewarder {
             reward_offset,
             mobile_rewards,
             reward_manifests,
+            reward_dust,
             price_tracker,
             disable_discovery_loc_rewards_to_s3,
+            feature_flags,
+            quorum,
         }
     }
 
@@ -64,8 +83,10 @@ impl Rewarder {
             );
             let now = Utc::now();
             let sleep_duration = if scheduler.should_reward(now) {
-                if self.is_data_current(&scheduler.reward_period).await? {
-                    self.reward(&scheduler).await?;
+                if !self.is_held(&scheduler.reward_period).await?
+                    && self.is_data_current(&scheduler.reward_period).await?
+                    && self.reward(&scheduler).await?
+                {
                     continue;
                 } else {
                     Duration::minutes(REWARDS_NOT_CURRENT_DELAY_PERIOD).to_std()?
@@ -88,6 +109,20 @@ impl Rewarder {
         Ok(())
     }
 
+    /// Whether an admin holds the rewards of the epoch, leaving it to be
+    /// rewarded once released
+    async fn is_held(&self, reward_period: &Range<DateTime<Utc>>) -> anyhow::Result<bool> {
+        let held = reward_holds::is_held(&self.pool, reward_period.start).await?;
+        if held {
+            tracing::info!(
+                "rewards for epoch starting {} are held",
+                reward_period.start
+            );
+        }
+        telemetry::reward_held(held);
+        Ok(held)
+    }
+
     async fn disable_complete_data_checks_until(&self) -> db_store::Result<DateTime<Utc>> {
         Utc.timestamp_opt(
             meta::fetch(&self.pool, "disable_complete_data_checks_until").await?,
@@ -133,7 +168,11 @@ impl Rewarder {
         Ok(true)
     }
 
-    pub async fn reward(&self, scheduler: &Scheduler) -> anyhow::Result<()> {
+    /// Reward the epoch of `scheduler`, returning whether its manifest was
+    /// published. Without the agreement of the reward quorum, the reward
+    /// shares written are left out of any manifest and the epoch is rewarded
+    /// again later on
+    pub async fn reward(&self, scheduler: &Scheduler) -> anyhow::Result<bool> {
         let reward_period = &scheduler.reward_period;
 
         tracing::info!(
@@ -146,9 +185,14 @@ impl Rewarder {
         let speedtests = SpeedtestAverages::validated(&self.pool, reward_period.end).await?;
 
         let poc_rewards = PocShares::aggregate(heartbeats, speedtests).await?;
+        // The price is pinned to the end of the epoch so every oracle of the
+        // reward quorum prices it the same, whenever it rewards it
         let mobile_price = self
             .price_tracker
-            .price(&helium_proto::BlockchainTokenTypeV1::Mobile)
+            .price_at(
+                &helium_proto::BlockchainTokenTypeV1::Mobile,
+                reward_period.end,
+            )
             .await?;
 
         // Mobile prices are supplied in 10^6, so we must convert them to Decimal
@@ -170,9 +214,18 @@ impl Rewarder {
         };
         telemetry::data_transfer_rewards_scale(scale);
 
-        for mobile_reward_share in
-            poc_rewards.into_rewards(transfer_rewards.reward_sum(), reward_period)
-        {
+        let mut digest = EpochDigest::default();
+        let mut inputs = EpochInputs {
+            mobile_price,
+            data_transfer_scale: scale,
+            ..Default::default()
+        };
+
+        let (poc_reward_shares, poc_dust) =
+            poc_rewards.into_rewards(transfer_rewards.reward_sum(), reward_period);
+        for mobile_reward_share in poc_reward_shares {
+            digest.add(&mobile_reward_share);
+            inputs.poc_shares += 1;
             self.mobile_rewards
                 .write(mobile_reward_share, [])
                 .await?
@@ -180,7 +233,10 @@ impl Rewarder {
                 .await??;
         }
 
-        for mobile_reward_share in transfer_rewards.into_rewards(reward_period) {
+        let (transfer_reward_shares, transfer_dust) = transfer_rewards.into_rewards(reward_period);
+        for mobile_reward_share in transfer_reward_shares {
+            digest.add(&mobile_reward_share);
+            inputs.transfer_shares += 1;
             self.mobile_rewards
                 .write(mobile_reward_share, [])
                 .await?
@@ -199,13 +255,17 @@ impl Rewarder {
 
         // determine mapping shares based on location shares and data transferred
         let mapping_shares = MapperShares::new(location_shares);
-        let rewards_per_share = mapping_shares.rewards_per_share(reward_period)?;
+
+        let disable_discovery_loc_rewards_to_s3 = self.disable_discovery_loc_rewards_to_s3
+            && !self
+                .feature_flags
+                .is_enabled(DISCOVERY_LOC_REWARDS_TO_S3_FLAG);
 
         // translate discovery mapping shares into subscriber rewards
-        for mapping_share in
-            mapping_shares.into_subscriber_rewards(reward_period, rewards_per_share)
-        {
-            if self.disable_discovery_loc_rewards_to_s3 {
+        let (mapping_reward_shares, mapping_dust) =
+            mapping_shares.into_subscriber_rewards(reward_period);
+        for mapping_share in mapping_reward_shares {
+            if disable_discovery_loc_rewards_to_s3 {
                 tracing::info!(
                     "discovery location rewards output to s3 is disabled, outputting to logs only"
                 );
@@ -217,6 +277,8 @@ impl Rewarder {
                     )
                 }
             } else {
+                digest.add(&mapping_share);
+                inputs.mapping_shares += 1;
                 self.mobile_rewards
                     .write(mapping_share.clone(), [])
                     .await?
@@ -225,8 +287,32 @@ impl Rewarder {
             }
         }
 
+        // no mobile reward share can hold the dust left from rounding down
+        // rewards, it is written to its own record with the manifest below
+        tracing::info!(poc_dust, transfer_dust, mapping_dust, "reward dust");
+        telemetry::reward_dust("poc", poc_dust);
+        telemetry::reward_dust("data_transfer", transfer_dust);
+        telemetry::reward_dust("mapping", mapping_dust);
+
         let written_files = self.mobile_rewards.commit().await?.await??;
 
+        if let Some(quorum) = &self.quorum {
+            inputs.poc_dust = poc_dust;
+            inputs.transfer_dust = transfer_dust;
+            inputs.mapping_dust = mapping_dust;
+            let agreement = quorum
+                .agree(reward_period, digest.finalize(), inputs)
+                .await?;
+            telemetry::reward_quorum(agreement.as_str());
+            if agreement != Agreement::Reached {
+                tracing::error!(
+                    agreement = agreement.as_str(),
+                    "reward quorum not reached, refusing to publish manifest"
+                );
+                return Ok(false);
+            }
+        }
+
         let mut transaction = self.pool.begin().await?;
 
         // Clear the heartbeats table of old heartbeats:
@@ -244,6 +330,23 @@ impl Rewarder {
         save_next_rewarded_end_time(&mut transaction, &next_reward_period.end).await?;
         transaction.commit().await?;
 
+        // the dust of the epoch goes out ahead of its manifest, a failed
+        // write of the manifest then at worst repeats the dust record
+        self.reward_dust
+            .write(
+                RewardDustV1::from(RewardDust {
+                    start: reward_period.start,
+                    end: reward_period.end,
+                    poc: poc_dust,
+                    data_transfer: transfer_dust,
+                    mapping: mapping_dust,
+                }),
+                [],
+            )
+            .await?
+            .await??;
+        self.reward_dust.commit().await?.await??;
+
         // now that the db has been purged, safe to write out the manifest
         self.reward_manifests
             .write(
@@ -259,7 +362,7 @@ impl Rewarder {
 
         self.reward_manifests.commit().await?;
         telemetry::last_rewarded_end_time(next_reward_period.start);
-        Ok(())
+        Ok(true)
     }
 }
 
@@ -1,5 +1,4 @@
 use chrono::{DateTime, TimeZone, Utc};
-use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 use std::path::Path;
 
@@ -27,6 +26,10 @@ pub struct Settings {
     pub start_after: u64,
     #[serde(default = "default_disable_discovery_loc_rewards_to_s3")]
     pub disable_discovery_loc_rewards_to_s3: bool,
+    /// Settings for publishing reward manifests only once a quorum of reward
+    /// oracles agrees on the rewards. Default none, publishing right away
+    #[serde(default)]
+    pub reward_quorum: Option<crate::reward_quorum::Settings>,
 }
 
 pub fn default_disable_discovery_loc_rewards_to_s3() -> bool {
@@ -56,20 +59,8 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "VERIFY_". For example
     /// "VERIFY_DATABASE_URL" will override the data base url.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of VERIFY)
-        // Eg.. `INJECT_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("VERIFY").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "VERIFY")
     }
 
     pub fn start_after(&self) -> DateTime<Utc> {
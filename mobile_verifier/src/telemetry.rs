@@ -5,6 +5,10 @@ use crate::rewarder;
 
 const LAST_REWARDED_END_TIME: &str = "last_rewarded_end_time";
 const DATA_TRANSFER_REWARDS_SCALE: &str = "data_transfer_rewards_scale";
+const REWARD_DUST: &str = "reward_dust";
+const REWARD_HELD: &str = "reward_held";
+const MAKER_HEARTBEATS: &str = "maker_heartbeats";
+const REWARD_QUORUM: &str = "reward_quorum";
 
 pub async fn initialize(db: &Pool<Postgres>) -> anyhow::Result<()> {
     last_rewarded_end_time(rewarder::last_rewarded_end_time(db).await?);
@@ -19,3 +23,23 @@ pub fn last_rewarded_end_time(timestamp: DateTime<Utc>) {
 pub fn data_transfer_rewards_scale(scale: f64) {
     metrics::gauge!(DATA_TRANSFER_REWARDS_SCALE, scale);
 }
+
+pub fn reward_held(held: bool) {
+    metrics::gauge!(REWARD_HELD, if held { 1.0 } else { 0.0 });
+}
+
+pub fn reward_quorum(agreement: &'static str) {
+    metrics::increment_counter!(REWARD_QUORUM, "agreement" => agreement);
+}
+
+pub fn count_maker_heartbeat(maker: &str, validity: &'static str) {
+    metrics::increment_counter!(
+        MAKER_HEARTBEATS,
+        "maker" => maker.to_string(),
+        "validity" => validity
+    );
+}
+
+pub fn reward_dust(pool: &'static str, dust: u64) {
+    metrics::gauge!(REWARD_DUST, dust as f64, "pool" => pool);
+}
@@ -4,6 +4,7 @@ use clap::Parser;
 use file_store::{file_sink, file_upload, FileType};
 use futures_util::TryFutureExt;
 use poc_entropy::{entropy_generator::EntropyGenerator, server::ApiServer, Settings};
+use poc_metrics::preflight::Preflight;
 use std::{net::SocketAddr, path};
 use tokio::{self, signal};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -67,6 +68,17 @@ impl Server {
             }
         });
 
+        // Check all external dependencies before starting, setting up the
+        // entropy generator on the way
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        let entropy_generator = preflight
+            .check("entropy source", EntropyGenerator::new(&settings.source))
+            .await;
+        preflight.finish()?;
+
         // Initialize uploader
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
@@ -75,7 +87,7 @@ impl Server {
         let store_base_path = path::Path::new(&settings.cache);
 
         // entropy
-        let mut entropy_generator = EntropyGenerator::new(&settings.source).await?;
+        let mut entropy_generator = entropy_generator.expect("entropy source passed preflight");
         let entropy_watch = entropy_generator.receiver();
 
         let (entropy_sink, mut entropy_sink_server) = file_sink::FileSinkBuilder::new(
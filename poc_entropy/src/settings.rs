@@ -1,4 +1,3 @@
-use config::{Config, Environment, File};
 use serde::Deserialize;
 use std::path::Path;
 
@@ -41,19 +40,7 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "ENTROPY_". For example
     /// "ENTROPY_LOG" will override the log setting.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `MI_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("ENTROPY").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "ENTROPY")
     }
 }
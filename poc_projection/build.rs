@@ -0,0 +1,32 @@
+// The query service is not part of helium-proto. Its server and client stubs
+// are generated here from the prost messages defined in `src/proto.rs`.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn method(name: &str, route_name: &str, input: &str, output: &str) -> Method {
+    Method::builder()
+        .name(name)
+        .route_name(route_name)
+        .input_type(format!("crate::proto::{input}"))
+        .output_type(format!("crate::proto::{output}"))
+        .codec_path("tonic::codec::ProstCodec")
+        .build()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let poc_query = Service::builder()
+        .name("PocQuery")
+        .package("helium.poc_projection")
+        .method(method("get", "Get", "PocReqV1", "PocV1"))
+        .method(method(
+            "gateway_pocs",
+            "GatewayPocs",
+            "GatewayPocsReqV1",
+            "PocsResV1",
+        ))
+        .method(method("hex_pocs", "HexPocs", "HexPocsReqV1", "PocsResV1"))
+        .build();
+
+    Builder::new().compile(&[poc_query]);
+}
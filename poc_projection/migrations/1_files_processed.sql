@@ -0,0 +1,6 @@
+create table files_processed (
+	file_name varchar primary key,
+	file_type varchar not null,
+	file_timestamp timestamptz not null,
+	processed_at timestamptz not null
+);
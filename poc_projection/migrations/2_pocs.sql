@@ -0,0 +1,28 @@
+create table pocs (
+    poc_id bytea primary key not null,
+    beaconer text not null,
+    location bigint,
+    received_timestamp timestamptz not null,
+    hex_scale decimal not null,
+    reward_unit decimal not null
+);
+
+create index pocs_beaconer_idx on pocs (beaconer, received_timestamp);
+create index pocs_location_idx on pocs (location, received_timestamp);
+create index pocs_received_timestamp_idx on pocs (received_timestamp);
+
+create table poc_witnesses (
+    poc_id bytea not null references pocs (poc_id) on delete cascade,
+    witness text not null,
+    location bigint,
+    received_timestamp timestamptz not null,
+    selected boolean not null,
+    valid boolean not null,
+    invalid_reason text not null,
+    signal integer not null,
+    snr integer not null,
+    reward_unit decimal not null,
+    primary key (poc_id, witness)
+);
+
+create index poc_witnesses_witness_idx on poc_witnesses (witness, received_timestamp);
@@ -0,0 +1,9 @@
+pub mod poc_store;
+pub mod projector;
+pub mod proto;
+pub mod query_service;
+pub mod settings;
+
+pub use projector::Projector;
+pub use query_service::QueryService;
+pub use settings::Settings;
@@ -0,0 +1,136 @@
+use anyhow::{Error, Result};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use file_store::{
+    file_info_poller::LookbackBehavior, file_source, iot_valid_poc::IotPoc, FileStore, FileType,
+};
+use futures_util::TryFutureExt;
+use poc_metrics::preflight::Preflight;
+use poc_projection::{proto::poc_query_server::PocQueryServer, Projector, QueryService, Settings};
+use std::path::PathBuf;
+use tokio::signal;
+use tonic::transport;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+#[derive(Debug, clap::Parser)]
+#[clap(version = env!("CARGO_PKG_VERSION"))]
+#[clap(about = "Helium IoT PoC Projection")]
+pub struct Cli {
+    /// Optional configuration file to use. If present the toml file at the
+    /// given path will be loaded. Environemnt variables can override the
+    /// settins in the given file.
+    #[clap(short = 'c')]
+    config: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        let settings = Settings::new(self.config)?;
+        self.cmd.run(settings).await
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Cmd {
+    Server(Server),
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: Settings) -> Result<()> {
+        match self {
+            Self::Server(cmd) => cmd.run(&settings).await,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Server {}
+
+impl Server {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(&settings.log))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+
+        // Install the prometheus metrics exporter
+        poc_metrics::start_metrics(&settings.metrics)?;
+
+        // Configure shutdown trigger
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut sigterm = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigterm.recv() => shutdown_trigger.trigger(),
+                _ = signal::ctrl_c() => shutdown_trigger.trigger(),
+            }
+        });
+
+        // Create database pool
+        let (pool, db_join_handle) = settings
+            .database
+            .connect(env!("CARGO_PKG_NAME"), shutdown_listener.clone())
+            .await?;
+
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check("verifier bucket", settings.verifier.check_read())
+            .await;
+        preflight.finish()?;
+
+        sqlx::migrate!().run(&pool).await?;
+
+        let file_store = FileStore::from_settings(&settings.verifier).await?;
+
+        let (receiver, source_join_handle) = file_source::continuous_source::<IotPoc>()
+            .db(pool.clone())
+            .store(file_store)
+            .file_type(FileType::IotPoc)
+            .lookback(LookbackBehavior::StartAfter(
+                Utc.timestamp_opt(settings.start_after as i64, 0)
+                    .single()
+                    .unwrap(),
+            ))
+            .poll_duration(settings.interval())
+            .offset(settings.interval() * 2)
+            .build()?
+            .start(shutdown_listener.clone())
+            .await?;
+
+        let projector = Projector::new(settings, pool.clone());
+
+        let listen_addr = settings.listen_addr()?;
+        tracing::info!("query api listening on {listen_addr}");
+        let query_service = QueryService::new(settings, pool);
+        let server = transport::Server::builder()
+            .layer(poc_metrics::request_layer!("poc_projection_connection"))
+            .add_service(PocQueryServer::new(query_service))
+            .serve_with_shutdown(listen_addr, shutdown_listener.clone())
+            .map_err(Error::from);
+
+        tokio::try_join!(
+            db_join_handle.map_err(Error::from),
+            source_join_handle.map_err(Error::from),
+            projector.run(shutdown_listener, receiver),
+            server,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    cli.run().await
+}
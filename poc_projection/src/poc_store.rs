@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use file_store::iot_valid_poc::{IotPoc, IotVerifiedWitnessReport};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::VerificationStatus;
+use rust_decimal::Decimal;
+use sqlx::{Pool, Postgres, Transaction};
+use std::{collections::HashMap, ops::Range};
+
+#[derive(sqlx::FromRow)]
+pub struct PocRecord {
+    pub poc_id: Vec<u8>,
+    pub beaconer: PublicKeyBinary,
+    pub location: Option<i64>,
+    pub received_timestamp: DateTime<Utc>,
+    pub hex_scale: Decimal,
+    pub reward_unit: Decimal,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct WitnessRecord {
+    pub poc_id: Vec<u8>,
+    pub witness: PublicKeyBinary,
+    pub location: Option<i64>,
+    pub received_timestamp: DateTime<Utc>,
+    pub selected: bool,
+    pub valid: bool,
+    pub invalid_reason: String,
+    pub signal: i32,
+    pub snr: i32,
+    pub reward_unit: Decimal,
+}
+
+pub struct Poc {
+    pub poc: PocRecord,
+    pub witnesses: Vec<WitnessRecord>,
+}
+
+pub async fn insert(txn: &mut Transaction<'_, Postgres>, poc: &IotPoc) -> Result<(), sqlx::Error> {
+    let beacon = &poc.beacon_report;
+    sqlx::query(
+        r#"
+        insert into pocs (poc_id, beaconer, location, received_timestamp, hex_scale, reward_unit)
+        values ($1, $2, $3, $4, $5, $6)
+        on conflict (poc_id) do nothing
+        "#,
+    )
+    .bind(&poc.poc_id)
+    .bind(&beacon.report.pub_key)
+    .bind(beacon.location.map(|location| location as i64))
+    .bind(beacon.received_timestamp)
+    .bind(beacon.hex_scale)
+    .bind(beacon.reward_unit)
+    .execute(&mut *txn)
+    .await?;
+
+    let witnesses = poc
+        .selected_witnesses
+        .iter()
+        .map(|witness| (witness, true))
+        .chain(
+            poc.unselected_witnesses
+                .iter()
+                .map(|witness| (witness, false)),
+        );
+    for (witness, selected) in witnesses {
+        insert_witness(txn, &poc.poc_id, witness, selected).await?;
+    }
+    Ok(())
+}
+
+async fn insert_witness(
+    txn: &mut Transaction<'_, Postgres>,
+    poc_id: &[u8],
+    witness: &IotVerifiedWitnessReport,
+    selected: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        insert into poc_witnesses (
+            poc_id, witness, location, received_timestamp, selected, valid,
+            invalid_reason, signal, snr, reward_unit
+        )
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        on conflict (poc_id, witness) do nothing
+        "#,
+    )
+    .bind(poc_id)
+    .bind(&witness.report.pub_key)
+    .bind(witness.location.map(|location| location as i64))
+    .bind(witness.received_timestamp)
+    .bind(selected)
+    .bind(witness.status == VerificationStatus::Valid)
+    .bind(witness.invalid_reason.as_str_name())
+    .bind(witness.report.signal)
+    .bind(witness.report.snr)
+    .bind(witness.reward_unit)
+    .execute(&mut *txn)
+    .await?;
+    Ok(())
+}
+
+/// Remove PoCs, and their witnesses, received before `before`
+pub async fn delete_before(
+    db: impl sqlx::PgExecutor<'_>,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    sqlx::query("delete from pocs where received_timestamp < $1")
+        .bind(before)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+}
+
+pub async fn get(db: &Pool<Postgres>, poc_id: &[u8]) -> Result<Option<Poc>, sqlx::Error> {
+    let poc = sqlx::query_as::<_, PocRecord>("select * from pocs where poc_id = $1")
+        .bind(poc_id)
+        .fetch_optional(db)
+        .await?;
+    match poc {
+        Some(poc) => Ok(with_witnesses(db, vec![poc]).await?.pop()),
+        None => Ok(None),
+    }
+}
+
+/// PoCs `gateway` beaconed, or was a valid witness of, most recent first.
+/// Witnessed PoCs are matched on the time the witness report was received
+pub async fn for_gateway(
+    db: &Pool<Postgres>,
+    gateway: &PublicKeyBinary,
+    period: &Range<DateTime<Utc>>,
+    limit: u32,
+) -> Result<Vec<Poc>, sqlx::Error> {
+    let pocs = sqlx::query_as::<_, PocRecord>(
+        r#"
+        select * from pocs
+        where beaconer = $1 and received_timestamp >= $2 and received_timestamp < $3
+        union
+        select p.* from pocs p
+        join poc_witnesses w on w.poc_id = p.poc_id
+        where w.witness = $1 and w.valid
+            and w.received_timestamp >= $2 and w.received_timestamp < $3
+        order by received_timestamp desc
+        limit $4
+        "#,
+    )
+    .bind(gateway)
+    .bind(period.start)
+    .bind(period.end)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await?;
+    with_witnesses(db, pocs).await
+}
+
+/// PoCs beaconed from `location`, most recent first
+pub async fn for_hex(
+    db: &Pool<Postgres>,
+    location: u64,
+    period: &Range<DateTime<Utc>>,
+    limit: u32,
+) -> Result<Vec<Poc>, sqlx::Error> {
+    let pocs = sqlx::query_as::<_, PocRecord>(
+        r#"
+        select * from pocs
+        where location = $1 and received_timestamp >= $2 and received_timestamp < $3
+        order by received_timestamp desc
+        limit $4
+        "#,
+    )
+    .bind(location as i64)
+    .bind(period.start)
+    .bind(period.end)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await?;
+    with_witnesses(db, pocs).await
+}
+
+async fn with_witnesses(
+    db: &Pool<Postgres>,
+    pocs: Vec<PocRecord>,
+) -> Result<Vec<Poc>, sqlx::Error> {
+    let poc_ids: Vec<Vec<u8>> = pocs.iter().map(|poc| poc.poc_id.clone()).collect();
+    let records = sqlx::query_as::<_, WitnessRecord>(
+        "select * from poc_witnesses where poc_id = any($1) order by received_timestamp",
+    )
+    .bind(&poc_ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut witnesses: HashMap<Vec<u8>, Vec<WitnessRecord>> = HashMap::new();
+    for witness in records {
+        witnesses
+            .entry(witness.poc_id.clone())
+            .or_default()
+            .push(witness);
+    }
+
+    Ok(pocs
+        .into_iter()
+        .map(|poc| Poc {
+            witnesses: witnesses.remove(&poc.poc_id).unwrap_or_default(),
+            poc,
+        })
+        .collect())
+}
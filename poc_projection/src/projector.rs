@@ -0,0 +1,73 @@
+use crate::{poc_store, Settings};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use file_store::{file_info_poller::FileInfoStream, iot_valid_poc::IotPoc};
+use futures::StreamExt;
+use sqlx::{Pool, Postgres};
+use task_scheduler::{Schedule, Scheduler};
+use tokio::sync::mpsc::Receiver;
+
+const POCS_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_pocs");
+
+pub struct Projector {
+    pool: Pool<Postgres>,
+    retention: Duration,
+    prune_schedule: Schedule,
+    prune_jitter: std::time::Duration,
+}
+
+impl Projector {
+    pub fn new(settings: &Settings, pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            retention: settings.retention(),
+            prune_schedule: settings.prune_schedule.clone(),
+            prune_jitter: settings.prune_jitter(),
+        }
+    }
+
+    pub async fn run(
+        &self,
+        shutdown: triggered::Listener,
+        mut receiver: Receiver<FileInfoStream<IotPoc>>,
+    ) -> Result<()> {
+        tracing::info!("starting projector");
+        let mut prune_timer =
+            Scheduler::new("pruner", self.prune_schedule.clone(), self.prune_jitter).immediately();
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => break,
+                _ = prune_timer.tick() => self.prune().await?,
+                msg = receiver.recv() => match msg {
+                    Some(file_info_stream) => self.handle_file(file_info_stream).await?,
+                    None => break,
+                }
+            }
+        }
+        tracing::info!("stopping projector");
+        Ok(())
+    }
+
+    async fn handle_file(&self, file_info_stream: FileInfoStream<IotPoc>) -> Result<()> {
+        let key = file_info_stream.file_info.key.clone();
+        tracing::info!(file = %key, "projecting poc file");
+        let mut txn = self.pool.begin().await?;
+        let mut stream = file_info_stream.into_stream(&mut txn).await?;
+        let mut count: u64 = 0;
+        while let Some(poc) = stream.next().await {
+            poc_store::insert(&mut txn, &poc).await?;
+            count += 1;
+        }
+        txn.commit().await?;
+        metrics::counter!(POCS_COUNTER, count);
+        tracing::info!(file = %key, count, "completed projecting poc file");
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<()> {
+        let before = Utc::now() - self.retention;
+        let deleted = poc_store::delete_before(&self.pool, before).await?;
+        tracing::info!(%before, deleted, "pruned pocs");
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+//! Messages of the read only PoC query service. Timestamps are in
+//! milliseconds, except for request time bounds which are in seconds.
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.poc_projection.PocQuery.rs"
+));
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PocReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub poc_id: Vec<u8>,
+}
+
+/// Valid PoCs a gateway beaconed, or was a valid witness of, within
+/// `start..end`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GatewayPocsReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub gateway: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(uint64, tag = "3")]
+    pub end: u64,
+    /// Max number of PoCs returned, most recent first. Zero or anything
+    /// above the service limit is capped to the service limit
+    #[prost(uint32, tag = "4")]
+    pub limit: u32,
+}
+
+/// Valid PoCs beaconed from the h3 cell `location` within `start..end`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HexPocsReqV1 {
+    #[prost(uint64, tag = "1")]
+    pub location: u64,
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(uint64, tag = "3")]
+    pub end: u64,
+    #[prost(uint32, tag = "4")]
+    pub limit: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PocsResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub pocs: Vec<PocV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PocV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub poc_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub beaconer: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub location: u64,
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    #[prost(string, tag = "5")]
+    pub hex_scale: String,
+    #[prost(string, tag = "6")]
+    pub reward_unit: String,
+    #[prost(message, repeated, tag = "7")]
+    pub witnesses: Vec<PocWitnessV1>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PocWitnessV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub pub_key: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub location: u64,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bool, tag = "4")]
+    pub selected: bool,
+    #[prost(bool, tag = "5")]
+    pub valid: bool,
+    #[prost(string, tag = "6")]
+    pub invalid_reason: String,
+    #[prost(sint32, tag = "7")]
+    pub signal: i32,
+    #[prost(sint32, tag = "8")]
+    pub snr: i32,
+    #[prost(string, tag = "9")]
+    pub reward_unit: String,
+}
@@ -0,0 +1,138 @@
+use crate::{
+    poc_store::{self, Poc},
+    proto::{self, GatewayPocsReqV1, HexPocsReqV1, PocReqV1, PocV1, PocWitnessV1, PocsResV1},
+    Settings,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use file_store::{grpc::GrpcResult, traits::TimestampEncode};
+use helium_crypto::PublicKey;
+use sqlx::{Pool, Postgres};
+use std::ops::Range;
+use tonic::{Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_request");
+
+/// Read only queries against the projection. Nothing here is authenticated,
+/// everything served is derived from public verifier output
+pub struct QueryService {
+    pool: Pool<Postgres>,
+    max_limit: u32,
+}
+
+impl QueryService {
+    pub fn new(settings: &Settings, pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            max_limit: settings.max_query_limit,
+        }
+    }
+
+    fn limit(&self, requested: u32) -> u32 {
+        match requested {
+            0 => self.max_limit,
+            limit => limit.min(self.max_limit),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl proto::poc_query_server::PocQuery for QueryService {
+    async fn get(&self, request: Request<PocReqV1>) -> GrpcResult<PocV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "get");
+
+        poc_store::get(&self.pool, &request.poc_id)
+            .await
+            .map_err(internal)?
+            .map(|poc| Response::new(poc.into()))
+            .ok_or_else(|| Status::not_found("poc not found"))
+    }
+
+    async fn gateway_pocs(&self, request: Request<GatewayPocsReqV1>) -> GrpcResult<PocsResV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "gateway_pocs");
+
+        let gateway = PublicKey::try_from(request.gateway.as_slice())
+            .map_err(|_| Status::invalid_argument("invalid gateway public key"))?;
+        let period = period(request.start, request.end)?;
+        let pocs = poc_store::for_gateway(
+            &self.pool,
+            &gateway.into(),
+            &period,
+            self.limit(request.limit),
+        )
+        .await
+        .map_err(internal)?;
+
+        Ok(Response::new(PocsResV1 {
+            pocs: pocs.into_iter().map(PocV1::from).collect(),
+        }))
+    }
+
+    async fn hex_pocs(&self, request: Request<HexPocsReqV1>) -> GrpcResult<PocsResV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "hex_pocs");
+
+        let period = period(request.start, request.end)?;
+        let pocs = poc_store::for_hex(
+            &self.pool,
+            request.location,
+            &period,
+            self.limit(request.limit),
+        )
+        .await
+        .map_err(internal)?;
+
+        Ok(Response::new(PocsResV1 {
+            pocs: pocs.into_iter().map(PocV1::from).collect(),
+        }))
+    }
+}
+
+fn period(start: u64, end: u64) -> Result<Range<DateTime<Utc>>, Status> {
+    let to_datetime = |secs: u64| {
+        Utc.timestamp_opt(secs as i64, 0)
+            .single()
+            .ok_or_else(|| Status::invalid_argument("invalid timestamp"))
+    };
+    let period = to_datetime(start)?..to_datetime(end)?;
+    if period.is_empty() {
+        return Err(Status::invalid_argument("start must be before end"));
+    }
+    Ok(period)
+}
+
+fn internal(err: sqlx::Error) -> Status {
+    tracing::error!(?err, "poc query failed");
+    Status::internal("poc query failed")
+}
+
+impl From<Poc> for PocV1 {
+    fn from(poc: Poc) -> Self {
+        Self {
+            poc_id: poc.poc.poc_id,
+            beaconer: poc.poc.beaconer.into(),
+            location: poc.poc.location.unwrap_or_default() as u64,
+            timestamp: poc.poc.received_timestamp.encode_timestamp_millis(),
+            hex_scale: poc.poc.hex_scale.to_string(),
+            reward_unit: poc.poc.reward_unit.to_string(),
+            witnesses: poc.witnesses.into_iter().map(PocWitnessV1::from).collect(),
+        }
+    }
+}
+
+impl From<poc_store::WitnessRecord> for PocWitnessV1 {
+    fn from(witness: poc_store::WitnessRecord) -> Self {
+        Self {
+            pub_key: witness.witness.into(),
+            location: witness.location.unwrap_or_default() as u64,
+            timestamp: witness.received_timestamp.encode_timestamp_millis(),
+            selected: witness.selected,
+            valid: witness.valid,
+            invalid_reason: witness.invalid_reason,
+            signal: witness.signal,
+            snr: witness.snr,
+            reward_unit: witness.reward_unit.to_string(),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::Duration;
+use serde::Deserialize;
+use std::{
+    net::{AddrParseError, SocketAddr},
+    path::Path,
+    str::FromStr,
+    time,
+};
+use task_scheduler::Schedule;
+
+#[derive(Debug, Deserialize)]
+pub struct Settings {
+    /// RUST_LOG compatible settings string. Default to
+    /// "poc_projection=debug,poc_store=info"
+    #[serde(default = "default_log")]
+    pub log: String,
+    /// Listen address for the query api. Default "0.0.0.0:8080"
+    #[serde(default = "default_listen_addr")]
+    pub listen: String,
+    /// Check interval for new verified poc files in seconds. (Default is 300;
+    /// 5 minutes)
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Days PoCs are kept in the projection for. (Default is 30)
+    #[serde(default = "default_retention_days")]
+    pub retention_days: i64,
+    /// When PoCs past retention are pruned, every number of seconds or
+    /// duration like "1h", or on a cron expression like "0 0 3 * * *".
+    /// (Default is 3600; 1 hour)
+    #[serde(default = "default_prune_schedule")]
+    pub prune_schedule: Schedule,
+    /// Up to how long each prune is delayed at random past its schedule (in
+    /// seconds). (Default is 0)
+    #[serde(default)]
+    pub prune_jitter: u64,
+    /// Max number of PoCs returned by a single query. (Default is 500)
+    #[serde(default = "default_max_query_limit")]
+    pub max_query_limit: u32,
+    /// Unix timestamp of the first verified poc file to project. Default 0
+    #[serde(default = "default_start_after")]
+    pub start_after: u64,
+    pub database: db_store::Settings,
+    pub verifier: file_store::Settings,
+    pub metrics: poc_metrics::Settings,
+}
+
+pub fn default_log() -> String {
+    "poc_projection=debug,poc_store=info".to_string()
+}
+
+pub fn default_listen_addr() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn default_interval() -> i64 {
+    300
+}
+
+fn default_retention_days() -> i64 {
+    30
+}
+
+fn default_prune_schedule() -> Schedule {
+    Schedule::Every(time::Duration::from_secs(60 * 60))
+}
+
+fn default_max_query_limit() -> u32 {
+    500
+}
+
+fn default_start_after() -> u64 {
+    0
+}
+
+impl Settings {
+    /// Load Settings from a given path. Settings are loaded from a given
+    /// optional path and can be overriden with environment variables.
+    ///
+    /// Environemnt overrides have the same name as the entries in the settings
+    /// file in uppercase and prefixed with "PROJECTION_". For example
+    /// "PROJECTION_DATABASE_URL" will override the data base url.
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "PROJECTION")
+    }
+
+    pub fn listen_addr(&self) -> Result<SocketAddr, AddrParseError> {
+        SocketAddr::from_str(&self.listen)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::seconds(self.interval)
+    }
+
+    pub fn retention(&self) -> Duration {
+        Duration::days(self.retention_days)
+    }
+
+    pub fn prune_jitter(&self) -> time::Duration {
+        time::Duration::from_secs(self.prune_jitter)
+    }
+}
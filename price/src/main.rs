@@ -4,7 +4,9 @@ use clap::Parser;
 use file_store::{file_sink, file_upload, FileType};
 use futures_util::TryFutureExt;
 use helium_proto::BlockchainTokenTypeV1;
+use poc_metrics::preflight::Preflight;
 use price::{cli::check, PriceGenerator, Settings};
+use solana_client::nonblocking::rpc_client::RpcClient;
 use std::path::{self, PathBuf};
 use tokio::{self, signal};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -91,6 +93,19 @@ impl Server {
             }
         });
 
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check("output bucket", settings.output.check_write())
+            .await;
+        preflight
+            .check(
+                "price source",
+                RpcClient::new(settings.source.clone()).get_health(),
+            )
+            .await;
+        preflight.finish()?;
+
         // Initialize uploader
         let (file_upload_tx, file_upload_rx) = file_upload::message_channel();
         let file_upload =
@@ -58,6 +58,12 @@ impl Settings {
     fn price_duration(&self) -> Duration {
         Duration::minutes(self.price_duration_minutes as i64)
     }
+
+    /// Check the bucket prices are read from can be listed, for preflight
+    /// checks
+    pub async fn check_store(&self) -> file_store::Result {
+        self.file_store.check_read().await
+    }
 }
 
 #[derive(Clone)]
@@ -65,6 +71,7 @@ pub struct PriceTracker {
     price_duration: Duration,
     task_killer: mpsc::Sender<String>,
     price_receiver: watch::Receiver<Prices>,
+    file_store: FileStore,
 }
 
 impl PriceTracker {
@@ -85,9 +92,10 @@ impl PriceTracker {
             calculate_initial_prices(&file_store, settings.price_duration(), &price_sender).await?;
 
         let shutdown_clone = shutdown.clone();
+        let run_store = file_store.clone();
         let handle = tokio::spawn(async move {
             run(
-                file_store,
+                run_store,
                 task_kill_receiver,
                 price_sender,
                 initial_timestamp,
@@ -100,6 +108,7 @@ impl PriceTracker {
             price_duration: settings.price_duration(),
             task_killer: task_kill_sender,
             price_receiver,
+            file_store,
         };
 
         Ok((tracker, async move {
@@ -134,6 +143,41 @@ impl PriceTracker {
 
         result
     }
+
+    /// The last price of `token_type` reported at or before `at`, read back
+    /// from the price reports rather than taken from the latest price. Every
+    /// caller asking for the same `at` gets the same price whenever it asks,
+    /// and an error here leaves the tracker running.
+    pub async fn price_at(
+        &self,
+        token_type: &BlockchainTokenTypeV1,
+        at: DateTime<Utc>,
+    ) -> Result<u64, PriceTrackerError> {
+        let oldest = at - self.price_duration;
+        let files = self.file_store.list(FileType::PriceReport, oldest, at);
+        let mut reports = self.file_store.source(files);
+        let mut latest: Option<Price> = None;
+        while let Some(buf) = reports.try_next().await? {
+            let report = PriceReportV1::decode(buf)?;
+            if report.token_type() != *token_type {
+                continue;
+            }
+            let price = Price::try_from(&report)?;
+            if price.timestamp <= at
+                && latest
+                    .as_ref()
+                    .map_or(true, |latest| price.timestamp >= latest.timestamp)
+            {
+                latest = Some(price);
+            }
+        }
+
+        match latest {
+            Some(price) if price.timestamp > oldest => Ok(price.price),
+            Some(price) => Err(PriceTrackerError::PriceTooOld(price.timestamp)),
+            None => Err(PriceTrackerError::PriceNotAvailable),
+        }
+    }
 }
 
 async fn run(
@@ -1,6 +1,5 @@
 use anyhow::{anyhow, Result};
 use chrono::Duration;
-use config::{Config, Environment, File};
 use helium_proto::BlockchainTokenTypeV1;
 use serde::Deserialize;
 use solana_sdk::pubkey::Pubkey as SolPubkey;
@@ -93,20 +92,10 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "price_". For example
     /// "price_LOG_" will override the log setting.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `MI_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("price").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(
+        path: Option<P>,
+    ) -> std::result::Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "price")
     }
 
     pub fn interval(&self) -> Duration {
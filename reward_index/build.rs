@@ -0,0 +1,34 @@
+// The reward accruals service is not part of helium-proto. Its server and
+// client stubs are generated here from the prost messages defined in
+// `src/proto.rs`.
+use tonic_build::manual::{Builder, Method, Service};
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let reward_accruals = Service::builder()
+        .name("RewardAccruals")
+        .package("helium.reward_index")
+        .method(
+            Method::builder()
+                .name("subscribe")
+                .route_name("Subscribe")
+                .input_type("crate::proto::RewardAccrualsReqV1")
+                .output_type("crate::proto::RewardAccrualV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("earnings")
+                .route_name("Earnings")
+                .input_type("crate::proto::HotspotEarningsReqV1")
+                .output_type("crate::proto::HotspotEarningsResV1")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .build();
+
+    Builder::new().compile(&[reward_accruals]);
+}
@@ -0,0 +1,11 @@
+create table reward_history (
+    address text not null,
+    reward_type reward_type not null,
+    owner text not null,
+    amount bigint not null,
+    epoch_start timestamptz not null,
+    epoch_end timestamptz not null,
+    primary key (address, reward_type, epoch_end)
+);
+
+create index reward_history_owner_idx on reward_history (owner, epoch_end);
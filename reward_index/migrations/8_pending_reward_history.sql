@@ -0,0 +1,8 @@
+create table pending_reward_history (
+    address text not null,
+    reward_type reward_type not null,
+    amount bigint not null,
+    epoch_start timestamptz not null,
+    epoch_end timestamptz not null,
+    primary key (address, reward_type, epoch_end)
+);
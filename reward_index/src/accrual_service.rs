@@ -0,0 +1,511 @@
+//! Api of reward accruals, streamed to the wallet backends subscribing to
+//! the reward keys they follow, and of the reward history of hotspots.
+
+use crate::{
+    indexer::RewardType,
+    proto::{
+        self, reward_accruals_server::RewardAccrualsServer, HotspotEarningV1, HotspotEarningsReqV1,
+        HotspotEarningsResV1, RewardAccrualV1, RewardAccrualsReqV1,
+    },
+    reward_index::{self, HistoryCursor},
+};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    grpc::{GrpcResult, GrpcStreamResult},
+    traits::{MsgVerify, TimestampDecode, TimestampEncode},
+};
+use helium_crypto::{KeyType, Keypair, PublicKey, Sign};
+use prost::Message;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::sync::{broadcast, mpsc};
+use tonic::{transport, Request, Response, Status};
+
+const REQUEST_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_accruals_request");
+const SUBSCRIBERS_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_accruals_subscribers");
+/// Largest difference between the timestamp of a request and its receipt
+const MAX_REQUEST_SKEW_MINUTES: i64 = 5;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    /// Listen address of the reward accruals api. Default "0.0.0.0:8080"
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// File from which to load the keypair signing accruals
+    pub keypair: String,
+    /// Max number of reward keys a single subscription follows. Default is
+    /// 1000
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+    /// Max number of earnings in a page of the reward history. Default is
+    /// 1000
+    #[serde(default = "default_max_page_size")]
+    pub max_page_size: usize,
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn default_max_keys() -> usize {
+    1000
+}
+
+fn default_max_page_size() -> usize {
+    1000
+}
+
+impl Settings {
+    pub fn signing_keypair(&self) -> anyhow::Result<Keypair> {
+        Ok(settings_loader::keypair(&self.keypair)?)
+    }
+}
+
+/// Rewards accrued by a key in an epoch
+#[derive(Debug, Clone)]
+pub struct Accrual {
+    pub reward_type: RewardType,
+    pub amount: u64,
+    /// Total rewards of the key, the epoch included
+    pub total: u64,
+    /// Wallet owning the hotspot when the epoch was indexed, if known
+    pub owner: Option<String>,
+}
+
+/// Rewards accrued in a committed epoch, by reward key
+#[derive(Debug)]
+pub struct EpochAccruals {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub accruals: HashMap<String, Accrual>,
+}
+
+/// Streams the rewards accrued by reward keys to their subscribers
+pub struct AccrualService {
+    pool: Pool<Postgres>,
+    accruals: broadcast::Sender<Arc<EpochAccruals>>,
+    signing_key: Arc<Keypair>,
+    max_keys: usize,
+    max_page_size: usize,
+}
+
+impl AccrualService {
+    pub fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        accruals: broadcast::Sender<Arc<EpochAccruals>>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            pool,
+            accruals,
+            signing_key: Arc::new(settings.signing_keypair()?),
+            max_keys: settings.max_keys,
+            max_page_size: settings.max_page_size.max(1),
+        })
+    }
+
+    /// Serve the accruals api until shutdown
+    pub async fn serve(
+        self,
+        settings: &Settings,
+        shutdown: triggered::Listener,
+    ) -> anyhow::Result<()> {
+        let listen_addr = SocketAddr::from_str(&settings.listen)?;
+        tracing::info!("reward accruals api listening on {listen_addr}");
+        transport::Server::builder()
+            .layer(poc_metrics::request_layer!(
+                "reward_index_accruals_connection"
+            ))
+            .add_service(RewardAccrualsServer::new(self))
+            .serve_with_shutdown(listen_addr, shutdown)
+            .await?;
+        Ok(())
+    }
+
+    /// The wallet of the owner signing `request`, once the request is checked
+    fn verify_request(
+        &self,
+        request: &RewardAccrualsReqV1,
+        now: DateTime<Utc>,
+    ) -> Result<String, Status> {
+        let wallet = verify_owner(&request.owner, request.timestamp, request, now)?;
+        if request.keys.len() > self.max_keys {
+            return Err(Status::invalid_argument(format!(
+                "more than {} reward keys",
+                self.max_keys
+            )));
+        }
+        Ok(wallet)
+    }
+
+    /// The wallet of the owner signing `request`, once the request is checked
+    fn verify_earnings_request(
+        &self,
+        request: &HotspotEarningsReqV1,
+        now: DateTime<Utc>,
+    ) -> Result<String, Status> {
+        let wallet = verify_owner(&request.owner, request.timestamp, request, now)?;
+        if request.hotspot_keys.len() > self.max_keys {
+            return Err(Status::invalid_argument(format!(
+                "more than {} hotspot keys",
+                self.max_keys
+            )));
+        }
+        Ok(wallet)
+    }
+}
+
+/// The wallet of `owner`, once it is checked to have signed `request` at
+/// `timestamp`, close enough to `now` for requests not to be replayed. Only
+/// the rewards of the hotspots a wallet owned when they were indexed, as
+/// recorded in the reward history, are served to it
+fn verify_owner<R: MsgVerify>(
+    owner: &[u8],
+    timestamp: u64,
+    request: &R,
+    now: DateTime<Utc>,
+) -> Result<String, Status> {
+    let owner = PublicKey::try_from(owner)
+        .map_err(|_| Status::invalid_argument("invalid owner public key"))?;
+    request
+        .verify(&owner)
+        .map_err(|_| Status::permission_denied("unauthorized request signature"))?;
+    let timestamp = timestamp
+        .to_timestamp()
+        .map_err(|_| Status::invalid_argument("invalid timestamp"))?;
+    let skew = Duration::minutes(MAX_REQUEST_SKEW_MINUTES);
+    if timestamp < now - skew || timestamp > now + skew {
+        return Err(Status::invalid_argument(
+            "request timestamp too far from now",
+        ));
+    }
+    wallet_address(&owner).ok_or_else(|| Status::invalid_argument("owner is not a wallet key"))
+}
+
+/// The solana wallet address of `owner`, as hotspot owners are recorded in
+/// the reward history. Only ed25519 keys are wallet keys
+fn wallet_address(owner: &PublicKey) -> Option<String> {
+    if !matches!(owner.key_type(), KeyType::Ed25519) {
+        return None;
+    }
+    Some(bs58::encode(&owner.to_vec()[1..]).into_string())
+}
+
+#[tonic::async_trait]
+impl proto::reward_accruals_server::RewardAccruals for AccrualService {
+    type subscribeStream = GrpcStreamResult<RewardAccrualV1>;
+    /// Stream the current total of each key of the subscription, or of every
+    /// hotspot of the wallet when it has none, then their rewards in every
+    /// epoch committed from then on. Subscribers falling more than a few
+    /// epochs behind have their stream ended with `DATA_LOSS`, and resubscribe
+    /// to catch up on totals
+    async fn subscribe(
+        &self,
+        request: Request<RewardAccrualsReqV1>,
+    ) -> GrpcResult<Self::subscribeStream> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "subscribe");
+        let wallet = self.verify_request(&request, Utc::now())?;
+
+        // subscribe before reading the totals, so no epoch committed in
+        // between is missed
+        let accruals = self.accruals.subscribe();
+        let owned = reward_index::owned_keys(&self.pool, &wallet, &request.keys).await;
+        let totals = match owned {
+            Ok(owned) => reward_index::fetch(&self.pool, &owned).await,
+            Err(err) => Err(err),
+        }
+        .map_err(|err| {
+            tracing::error!(?err, "reward index query failed");
+            Status::internal("reward index query failed")
+        })?;
+
+        let (tx, rx) = mpsc::channel(100);
+        let subscriber = Subscriber {
+            wallet,
+            keys: (!request.keys.is_empty()).then(|| request.keys.into_iter().collect()),
+            tx,
+            signing_key: self.signing_key.clone(),
+        };
+        tokio::spawn(async move {
+            metrics::increment_gauge!(SUBSCRIBERS_GAUGE, 1.0);
+            subscriber.run(totals, accruals).await;
+            metrics::decrement_gauge!(SUBSCRIBERS_GAUGE, 1.0);
+        });
+
+        Ok(Response::new(GrpcStreamResult::new(rx)))
+    }
+
+    /// A page of the rewards of the hotspots of the wallet per epoch and class
+    async fn earnings(
+        &self,
+        request: Request<HotspotEarningsReqV1>,
+    ) -> GrpcResult<HotspotEarningsResV1> {
+        let request = request.into_inner();
+        metrics::increment_counter!(REQUEST_COUNTER, "rpc" => "earnings");
+        let wallet = self.verify_earnings_request(&request, Utc::now())?;
+
+        let after = match request.cursor.as_str() {
+            "" => HistoryCursor::default(),
+            cursor => cursor.parse().map_err(Status::invalid_argument)?,
+        };
+        let limit = match request.limit as usize {
+            0 => self.max_page_size,
+            limit => limit.min(self.max_page_size),
+        };
+        let history =
+            reward_index::fetch_history(&self.pool, &wallet, &request.hotspot_keys, &after, limit)
+                .await
+                .map_err(|err| {
+                    tracing::error!(?err, "reward history query failed");
+                    Status::internal("reward history query failed")
+                })?;
+
+        let cursor = match history.last() {
+            Some(last) if history.len() == limit => HistoryCursor::from(last).to_string(),
+            _ => String::new(),
+        };
+        let mut response = HotspotEarningsResV1 {
+            earnings: history
+                .into_iter()
+                .map(|reward| HotspotEarningV1 {
+                    hotspot_key: reward.address,
+                    reward_type: reward.reward_type.as_str().to_string(),
+                    amount: reward.amount as u64,
+                    epoch_start: reward.epoch_start.encode_timestamp(),
+                    epoch_end: reward.epoch_end.encode_timestamp(),
+                })
+                .collect(),
+            cursor,
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        response.signature = self
+            .signing_key
+            .sign(&response.encode_to_vec())
+            .map_err(|_| Status::internal("response signing error"))?;
+        Ok(Response::new(response))
+    }
+}
+
+struct Subscriber {
+    wallet: String,
+    /// Keys followed, or every hotspot of the wallet
+    keys: Option<HashSet<String>>,
+    tx: mpsc::Sender<Result<RewardAccrualV1, Status>>,
+    signing_key: Arc<Keypair>,
+}
+
+impl Subscriber {
+    /// Send the current totals, then the accruals of the subscribed keys in
+    /// each committed epoch until the subscriber goes away or lags behind
+    async fn run(
+        self,
+        totals: Vec<reward_index::IndexedReward>,
+        mut accruals: broadcast::Receiver<Arc<EpochAccruals>>,
+    ) {
+        for total in totals {
+            let accrual = RewardAccrualV1 {
+                key: total.address,
+                reward_type: total
+                    .reward_type
+                    .map_or("", |reward_type| reward_type.as_str())
+                    .to_string(),
+                amount: 0,
+                total: total.rewards as u64,
+                epoch_start: 0,
+                epoch_end: total
+                    .last_reward
+                    .map_or(0, |last_reward| last_reward.encode_timestamp()),
+                ..Default::default()
+            };
+            if !self.send(accrual).await {
+                return;
+            }
+        }
+
+        loop {
+            let epoch = match accruals.recv().await {
+                Ok(epoch) => epoch,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let status = Status::data_loss(format!(
+                        "{skipped} epochs behind, resubscribe for current totals"
+                    ));
+                    let _ = self.tx.send(Err(status)).await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            for (key, accrual) in &epoch.accruals {
+                if !self.follows(key, accrual) {
+                    continue;
+                }
+                let accrual = RewardAccrualV1 {
+                    key: key.clone(),
+                    reward_type: accrual.reward_type.as_str().to_string(),
+                    amount: accrual.amount,
+                    total: accrual.total,
+                    epoch_start: epoch.start.encode_timestamp(),
+                    epoch_end: epoch.end.encode_timestamp(),
+                    ..Default::default()
+                };
+                if !self.send(accrual).await {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Whether the accrual of `key` is of a hotspot the wallet owned and the
+    /// subscriber follows
+    fn follows(&self, key: &str, accrual: &Accrual) -> bool {
+        accrual.owner.as_ref() == Some(&self.wallet)
+            && self.keys.as_ref().map_or(true, |keys| keys.contains(key))
+    }
+
+    /// Sign and send `accrual`. Returns whether the subscriber is still there
+    async fn send(&self, mut accrual: RewardAccrualV1) -> bool {
+        accrual.timestamp = Utc::now().encode_timestamp();
+        accrual.signer = self.signing_key.public_key().into();
+        match self.signing_key.sign(&accrual.encode_to_vec()) {
+            Ok(signature) => accrual.signature = signature,
+            Err(err) => {
+                tracing::error!(?err, "accrual signing error");
+                let status = Status::internal("accrual signing error");
+                let _ = self.tx.send(Err(status)).await;
+                return false;
+            }
+        }
+        self.tx.send(Ok(accrual)).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_crypto::{KeyTag, Network};
+    use rand::rngs::OsRng;
+
+    fn keypair(key_type: KeyType) -> Keypair {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type,
+        };
+        Keypair::generate(key_tag, &mut OsRng)
+    }
+
+    fn service() -> AccrualService {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/reward_index")
+            .expect("lazy pool");
+        AccrualService {
+            pool,
+            accruals: broadcast::channel(1).0,
+            signing_key: Arc::new(keypair(KeyType::Ed25519)),
+            max_keys: 2,
+            max_page_size: 10,
+        }
+    }
+
+    fn subscription(
+        owner: &Keypair,
+        keys: &[&str],
+        timestamp: DateTime<Utc>,
+    ) -> RewardAccrualsReqV1 {
+        let mut request = RewardAccrualsReqV1 {
+            owner: owner.public_key().into(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            timestamp: timestamp.encode_timestamp(),
+            signature: vec![],
+        };
+        request.signature = owner.sign(&request.encode_to_vec()).expect("signature");
+        request
+    }
+
+    #[tokio::test]
+    async fn subscriptions_are_signed_recently_by_a_wallet() {
+        let service = service();
+        let owner = keypair(KeyType::Ed25519);
+        let now = Utc::now();
+
+        let wallet = service
+            .verify_request(&subscription(&owner, &[], now), now)
+            .expect("subscription to every hotspot");
+        assert_eq!(Some(wallet), wallet_address(&owner.public_key()));
+        assert!(service
+            .verify_request(
+                &subscription(&owner, &["a", "b"], now),
+                now + Duration::minutes(4)
+            )
+            .is_ok());
+
+        let stale = subscription(&owner, &["a"], now - Duration::minutes(6));
+        assert_eq!(
+            service.verify_request(&stale, now).unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        let ahead = subscription(&owner, &["a"], now + Duration::minutes(6));
+        assert!(service.verify_request(&ahead, now).is_err());
+
+        let mut forged = subscription(&owner, &["a"], now);
+        forged.keys.push("b".to_string());
+        assert_eq!(
+            service.verify_request(&forged, now).unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+
+        let too_many = subscription(&owner, &["a", "b", "c"], now);
+        assert!(service.verify_request(&too_many, now).is_err());
+
+        let not_a_wallet = keypair(KeyType::EccCompact);
+        assert!(service
+            .verify_request(&subscription(&not_a_wallet, &["a"], now), now)
+            .is_err());
+    }
+
+    fn accrual(amount: u64, owner: &str) -> Accrual {
+        Accrual {
+            reward_type: RewardType::IotGateway,
+            amount,
+            total: amount,
+            owner: Some(owner.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_only_receive_the_accruals_of_their_hotspots() {
+        let (accruals_tx, accruals) = broadcast::channel(4);
+        let (tx, mut rx) = mpsc::channel(10);
+        let subscriber = Subscriber {
+            wallet: "alice".to_string(),
+            keys: Some(HashSet::from(["kept".to_string(), "sold".to_string()])),
+            tx,
+            signing_key: Arc::new(keypair(KeyType::Ed25519)),
+        };
+        let now = Utc::now();
+        accruals_tx
+            .send(Arc::new(EpochAccruals {
+                start: now - Duration::hours(24),
+                end: now,
+                accruals: HashMap::from([
+                    ("kept".to_string(), accrual(1, "alice")),
+                    ("sold".to_string(), accrual(2, "bob")),
+                    ("unfollowed".to_string(), accrual(3, "alice")),
+                ]),
+            }))
+            .expect("subscribed");
+        drop(accruals_tx);
+
+        subscriber.run(vec![], accruals).await;
+        let received = rx.recv().await.expect("accrual").expect("ok");
+        assert_eq!((received.key.as_str(), received.amount), ("kept", 1));
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,178 @@
+//! CSV export of the rewards of an epoch for finance, read from the reward
+//! shares listed by its reward manifest rather than from the index.
+
+use crate::{indexer, settings::Mode};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    reward_manifest::RewardManifest, traits::MsgDecode, FileInfo, FileStore, FileType,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Postgres, Row};
+use std::{collections::BTreeMap, io::Write, str::FromStr};
+
+/// How long after the end of its epoch a reward manifest is looked for
+const MANIFEST_LOOKAHEAD_HOURS: i64 = 48;
+
+pub const CSV_HEADER: &str = "owner,gateway,class,amount,epoch";
+
+/// The aggregated rewards of an epoch, by reward key and class
+pub struct EpochRewards {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub rewards: BTreeMap<(String, &'static str), u64>,
+}
+
+/// The reward manifest of the epoch ending at `epoch_end`
+pub async fn find_manifest(
+    verifier_store: &FileStore,
+    epoch_end: DateTime<Utc>,
+) -> Result<RewardManifest> {
+    let infos = verifier_store
+        .list_all(
+            FileType::RewardManifest,
+            epoch_end - Duration::seconds(1),
+            epoch_end + Duration::hours(MANIFEST_LOOKAHEAD_HOURS),
+        )
+        .await?;
+    for info in infos {
+        let mut manifests = verifier_store.stream_file(info).await?;
+        while let Some(msg) = manifests.try_next().await? {
+            let manifest = RewardManifest::decode(msg)?;
+            if manifest.end_timestamp == epoch_end {
+                return Ok(manifest);
+            }
+        }
+    }
+    Err(anyhow!(
+        "no reward manifest for the epoch ending at {epoch_end}"
+    ))
+}
+
+/// Aggregate the reward shares listed by `manifest` per reward key and class,
+/// like the indexer does
+pub async fn epoch_rewards(
+    verifier_store: &FileStore,
+    manifest: &RewardManifest,
+    mode: Mode,
+    op_fund_key: &str,
+) -> Result<EpochRewards> {
+    let reward_files = stream::iter(
+        manifest
+            .written_files
+            .iter()
+            .map(|file_name| FileInfo::from_str(file_name)),
+    )
+    .boxed();
+    let mut reward_shares = verifier_store.source_unordered(5, reward_files);
+
+    let mut rewards = BTreeMap::new();
+    while let Some(msg) = reward_shares.try_next().await? {
+        let (reward_key, amount) = indexer::extract_reward_share(mode, op_fund_key, &msg)?;
+        *rewards
+            .entry((reward_key.key, reward_key.reward_type.as_str()))
+            .or_default() += amount;
+    }
+    Ok(EpochRewards {
+        start: manifest.start_timestamp,
+        end: manifest.end_timestamp,
+        rewards,
+    })
+}
+
+/// The owners of the hotspots among `keys`, by b58 hotspot key. Keys that
+/// aren't hotspots, and hotspots the metadata doesn't know, have no owner
+pub async fn hotspot_owners<'a>(
+    metadata: &Pool<Postgres>,
+    keys: impl Iterator<Item = &'a String>,
+) -> Result<BTreeMap<String, String>> {
+    let entity_keys: Vec<Vec<u8>> = keys
+        .filter_map(|key| bs58::decode(key).into_vec().ok())
+        .collect();
+    let mut owners = BTreeMap::new();
+    for chunk in entity_keys.chunks(10_000) {
+        let rows = sqlx::query(
+            r#"
+            select kta.entity_key, ao.owner
+            from key_to_assets kta
+            join asset_owners ao on kta.asset = ao.asset
+            where kta.entity_key = any($1)
+            "#,
+        )
+        .bind(chunk)
+        .fetch_all(metadata)
+        .await?;
+        for row in rows {
+            let entity_key: Vec<u8> = row.get("entity_key");
+            let owner: String = row.get("owner");
+            owners.insert(PublicKeyBinary::from(entity_key).to_string(), owner);
+        }
+    }
+    Ok(owners)
+}
+
+/// Write `rewards` as csv, one row per key and class ordered by key then
+/// class so exporting an epoch twice produces the same file. A comment footer
+/// holds the row count, the total amount and the sha256 of everything above
+pub fn write_csv(
+    out: &mut impl Write,
+    rewards: &EpochRewards,
+    owners: &BTreeMap<String, String>,
+) -> Result<()> {
+    let epoch = rewards.end.to_rfc3339();
+    let mut csv = format!("{CSV_HEADER}\n");
+    let mut total: u128 = 0;
+    for ((key, class), amount) in &rewards.rewards {
+        let owner = owners.get(key).map_or("", String::as_str);
+        csv.push_str(&format!("{owner},{key},{class},{amount},{epoch}\n"));
+        total += u128::from(*amount);
+    }
+
+    let checksum: String = Sha256::digest(csv.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    out.write_all(csv.as_bytes())?;
+    writeln!(out, "# rows: {}, total: {total}", rewards.rewards.len())?;
+    writeln!(out, "# sha256: {checksum}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn csv_rows_are_ordered_and_checksummed() {
+        let end = Utc.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap();
+        let mut rewards = BTreeMap::new();
+        rewards.insert(("b".to_string(), "iot_gateway"), 20);
+        rewards.insert(("a".to_string(), "iot_operational"), 5);
+        rewards.insert(("a".to_string(), "iot_gateway"), 10);
+        let rewards = EpochRewards {
+            start: end - Duration::hours(24),
+            end,
+            rewards,
+        };
+        let owners = BTreeMap::from([("b".to_string(), "owner".to_string())]);
+
+        let mut out = vec![];
+        write_csv(&mut out, &rewards, &owners).expect("csv");
+        let csv = String::from_utf8(out).expect("utf8");
+        let body = "owner,gateway,class,amount,epoch\n\
+            ,a,iot_gateway,10,2023-09-01T00:00:00+00:00\n\
+            ,a,iot_operational,5,2023-09-01T00:00:00+00:00\n\
+            owner,b,iot_gateway,20,2023-09-01T00:00:00+00:00\n";
+        let checksum: String = Sha256::digest(body.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        assert_eq!(
+            csv,
+            format!("{body}# rows: 3, total: 35\n# sha256: {checksum}\n")
+        );
+    }
+}
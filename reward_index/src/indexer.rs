@@ -1,6 +1,9 @@
-use crate::{reward_index, settings, telemetry, Settings};
+use crate::{
+    accrual_service::{Accrual, EpochAccruals},
+    export, reward_index, settings, telemetry, Settings,
+};
 use anyhow::{anyhow, bail, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use file_store::{
     file_info_poller::FileInfoStream, reward_manifest::RewardManifest, FileInfo, FileStore,
 };
@@ -13,14 +16,31 @@ use helium_proto::{
 };
 use poc_metrics::record_duration;
 use sqlx::{Pool, Postgres, Transaction};
-use std::{collections::HashMap, str::FromStr};
-use tokio::sync::mpsc::Receiver;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc::Receiver};
+
+/// Committed epochs buffered for subscribers of the accruals api lagging
+/// behind
+const ACCRUALS_CAPACITY: usize = 16;
+/// Time between backfills of the history of epochs indexed while hotspot
+/// owners could not be resolved
+const HISTORY_BACKFILL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Max number of rewards backfilled in a single transaction
+const HISTORY_BACKFILL_BATCH: usize = 1_000;
 
 pub struct Indexer {
     pool: Pool<Postgres>,
+    metadata: Option<Pool<Postgres>>,
     verifier_store: FileStore,
     mode: settings::Mode,
     op_fund_key: String,
+    max_concurrent_epochs: usize,
+    accruals: broadcast::Sender<Arc<EpochAccruals>>,
 }
 
 #[derive(sqlx::Type, Debug, Clone, PartialEq, Eq, Hash)]
@@ -32,72 +52,175 @@ pub enum RewardType {
     MobileSubscriber,
 }
 
+impl RewardType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MobileGateway => "mobile_gateway",
+            Self::IotGateway => "iot_gateway",
+            Self::IotOperational => "iot_operational",
+            Self::MobileSubscriber => "mobile_subscriber",
+        }
+    }
+
+    /// Whether the rewards are those of a hotspot
+    pub fn is_hotspot(&self) -> bool {
+        matches!(self, Self::MobileGateway | Self::IotGateway)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RewardKey {
+    pub key: String,
+    pub reward_type: RewardType,
+}
+
+/// The rewards of a reward manifest file, aggregated and waiting to be
+/// committed in the transaction that recorded the file as processed
+struct Epoch {
     key: String,
-    reward_type: RewardType,
+    txn: Transaction<'static, Postgres>,
+    manifests: Vec<ManifestRewards>,
+}
+
+/// The aggregated rewards of a reward manifest, with the owners of the
+/// hotspots rewarded by b58 hotspot key. Owners are None when they could not
+/// be resolved, the history of the hotspots is then backfilled later
+struct ManifestRewards {
+    manifest: RewardManifest,
+    rewards: HashMap<RewardKey, u64>,
+    owners: Option<BTreeMap<String, String>>,
 }
 
 impl Indexer {
-    pub async fn new(settings: &Settings, pool: Pool<Postgres>) -> Result<Self> {
+    /// With a `metadata` database, the rewards of each hotspot are recorded
+    /// in the reward history with the owner of the hotspot as it is indexed
+    pub async fn new(
+        settings: &Settings,
+        pool: Pool<Postgres>,
+        metadata: Option<Pool<Postgres>>,
+    ) -> Result<Self> {
         Ok(Self {
             mode: settings.mode,
             verifier_store: FileStore::from_settings(&settings.verifier).await?,
             pool,
+            metadata,
             op_fund_key: match settings.mode {
                 settings::Mode::Iot => settings
                     .operation_fund_key()
                     .ok_or_else(|| anyhow!("operation fund key is required for IOT mode"))?,
                 settings::Mode::Mobile => String::new(),
             },
+            max_concurrent_epochs: settings.max_concurrent_epochs.max(1),
+            accruals: broadcast::channel(ACCRUALS_CAPACITY).0,
         })
     }
 
+    /// The rewards accrued in each epoch are sent here once committed
+    pub fn accruals(&self) -> broadcast::Sender<Arc<EpochAccruals>> {
+        self.accruals.clone()
+    }
+
+    /// Index the reward manifests received. Up to `max_concurrent_epochs`
+    /// manifests have their reward shares fetched and aggregated at once,
+    /// which is what makes backfills slow, while the aggregated rewards are
+    /// committed one manifest at a time in the order they were received.
     pub async fn run(
-        &mut self,
+        &self,
         shutdown: triggered::Listener,
-        mut receiver: Receiver<FileInfoStream<RewardManifest>>,
+        receiver: Receiver<FileInfoStream<RewardManifest>>,
     ) -> Result<()> {
-        tracing::info!(mode = self.mode.to_string(), "starting index");
+        tracing::info!(
+            mode = self.mode.to_string(),
+            max_concurrent_epochs = self.max_concurrent_epochs,
+            "starting index"
+        );
+
+        let mut epochs = load_in_order(receiver, self.max_concurrent_epochs, |file_info_stream| {
+            self.load_epoch(file_info_stream)
+        });
+        let mut last_epoch_end = None;
+        let mut history_backfill = tokio::time::interval(HISTORY_BACKFILL_INTERVAL);
 
         loop {
             tokio::select! {
-            _ = shutdown.clone() => {
+                _ = shutdown.clone() => {
                     tracing::info!("Indexer shutting down");
                     return Ok(());
-            }
-            msg = receiver.recv() => if let Some(file_info_stream) = msg {
-                    let key = &file_info_stream.file_info.key.clone();
-                    tracing::info!(file = %key, "Processing reward file");
-                    let mut txn = self.pool.begin().await?;
-                    let mut stream = file_info_stream.into_stream(&mut txn).await?;
-
-                    while let Some(reward_manifest) = stream.next().await {
-                        record_duration!(
-                            "reward_index_duration",
-                            self.handle_rewards(&mut txn, reward_manifest).await?
-                        )
+                }
+                epoch = epochs.next() => match epoch {
+                    Some(epoch) => self.commit_epoch(epoch?, &mut last_epoch_end).await?,
+                    None => return Ok(()),
+                },
+                _ = history_backfill.tick(), if self.metadata.is_some() => {
+                    match self.backfill_history().await {
+                        Ok(0) => (),
+                        Ok(backfilled) => tracing::info!(backfilled, "backfilled reward history"),
+                        Err(err) => tracing::warn!(?err, "failed to backfill reward history"),
                     }
-                    txn.commit().await?;
-                    tracing::info!(file = %key, "Completed processing reward file");
-                    telemetry::last_reward_processed_time(&self.pool, Utc::now()).await?;
                 }
             }
         }
     }
 
-    async fn handle_rewards(
-        &mut self,
-        txn: &mut Transaction<'_, Postgres>,
-        manifest: RewardManifest,
-    ) -> Result<()> {
-        let manifest_time = manifest.end_timestamp;
+    async fn load_epoch(&self, file_info_stream: FileInfoStream<RewardManifest>) -> Result<Epoch> {
+        let key = file_info_stream.file_info.key.clone();
+        tracing::info!(file = %key, "Processing reward file");
+        let mut txn = self.pool.begin().await?;
+        let reward_manifests: Vec<RewardManifest> = file_info_stream
+            .into_stream(&mut txn)
+            .await?
+            .collect()
+            .await;
+
+        let mut manifests = Vec::with_capacity(reward_manifests.len());
+        for manifest in reward_manifests {
+            let rewards = record_duration!(
+                "reward_index_duration",
+                self.aggregate_rewards(&manifest).await?
+            );
+            let owners = match &self.metadata {
+                Some(metadata) => {
+                    let hotspots = rewards
+                        .keys()
+                        .filter(|reward_key| reward_key.reward_type.is_hotspot())
+                        .map(|reward_key| &reward_key.key);
+                    // The rewards are indexed all the same, rather than
+                    // holding up indexing while the metadata db is down
+                    export::hotspot_owners(metadata, hotspots)
+                        .await
+                        .map_err(|err| {
+                            tracing::warn!(
+                                file = %key,
+                                ?err,
+                                "failed to resolve hotspot owners, reward history to be backfilled"
+                            );
+                        })
+                        .ok()
+                }
+                None => Some(BTreeMap::new()),
+            };
+            manifests.push(ManifestRewards {
+                manifest,
+                rewards,
+                owners,
+            });
+        }
+        Ok(Epoch {
+            key,
+            txn,
+            manifests,
+        })
+    }
 
+    async fn aggregate_rewards(
+        &self,
+        manifest: &RewardManifest,
+    ) -> Result<HashMap<RewardKey, u64>> {
         let reward_files = stream::iter(
             manifest
                 .written_files
-                .into_iter()
-                .map(|file_name| FileInfo::from_str(&file_name)),
+                .iter()
+                .map(|file_name| FileInfo::from_str(file_name)),
         )
         .boxed();
 
@@ -109,69 +232,259 @@ impl Indexer {
             *hotspot_rewards.entry(key).or_default() += amount;
         }
 
-        for (reward_key, amount) in hotspot_rewards {
-            reward_index::insert(
-                &mut *txn,
-                reward_key.key,
-                amount,
-                reward_key.reward_type,
-                &manifest_time,
-            )
-            .await?;
-        }
+        Ok(hotspot_rewards)
+    }
+
+    async fn commit_epoch(
+        &self,
+        epoch: Epoch,
+        last_epoch_end: &mut Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let Epoch {
+            key,
+            mut txn,
+            manifests,
+        } = epoch;
+        let mut committed = Vec::with_capacity(manifests.len());
+        for ManifestRewards {
+            manifest,
+            rewards: hotspot_rewards,
+            owners,
+        } in manifests
+        {
+            if let Some(last_end) = *last_epoch_end {
+                if manifest.start_timestamp != last_end {
+                    tracing::warn!(
+                        file = %key,
+                        %last_end,
+                        start = %manifest.start_timestamp,
+                        "reward manifest does not start where the last one ended"
+                    );
+                }
+            }
+            *last_epoch_end = Some(manifest.end_timestamp);
 
+            let mut accruals = HashMap::with_capacity(hotspot_rewards.len());
+            for (reward_key, amount) in hotspot_rewards {
+                let total = reward_index::insert(
+                    &mut txn,
+                    reward_key.key.clone(),
+                    amount,
+                    reward_key.reward_type.clone(),
+                    &manifest.end_timestamp,
+                )
+                .await?;
+                if let Some(total) = total {
+                    let owner = owners
+                        .as_ref()
+                        .and_then(|owners| owners.get(&reward_key.key));
+                    if let Some(owner) = owner {
+                        reward_index::insert_history(
+                            &mut txn,
+                            &reward_key.key,
+                            &reward_key.reward_type,
+                            owner,
+                            amount,
+                            &manifest.start_timestamp,
+                            &manifest.end_timestamp,
+                        )
+                        .await?;
+                    } else if owners.is_none() && reward_key.reward_type.is_hotspot() {
+                        reward_index::insert_pending_history(
+                            &mut txn,
+                            &reward_key.key,
+                            &reward_key.reward_type,
+                            amount,
+                            &manifest.start_timestamp,
+                            &manifest.end_timestamp,
+                        )
+                        .await?;
+                    }
+                    let accrual = Accrual {
+                        reward_type: reward_key.reward_type,
+                        amount,
+                        total,
+                        owner: owner.cloned(),
+                    };
+                    accruals.insert(reward_key.key, accrual);
+                }
+            }
+            committed.push(EpochAccruals {
+                start: manifest.start_timestamp,
+                end: manifest.end_timestamp,
+                accruals,
+            });
+        }
+        txn.commit().await?;
+        for epoch_accruals in committed {
+            // sending only fails without subscribers
+            let _ = self.accruals.send(Arc::new(epoch_accruals));
+        }
+        tracing::info!(file = %key, "Completed processing reward file");
+        telemetry::last_reward_processed_time(&self.pool, Utc::now()).await?;
         Ok(())
     }
 
-    fn extract_reward_share(&self, msg: &[u8]) -> Result<(RewardKey, u64)> {
-        match self.mode {
-            settings::Mode::Mobile => {
-                let share = MobileRewardShare::decode(msg)?;
-                match share.reward {
-                    Some(MobileReward::RadioReward(r)) => Ok((
-                        RewardKey {
-                            key: PublicKeyBinary::from(r.hotspot_key).to_string(),
-                            reward_type: RewardType::MobileGateway,
-                        },
-                        r.poc_reward,
-                    )),
-                    Some(MobileReward::GatewayReward(r)) => Ok((
-                        RewardKey {
-                            key: PublicKeyBinary::from(r.hotspot_key).to_string(),
-                            reward_type: RewardType::MobileGateway,
-                        },
-                        r.dc_transfer_reward,
-                    )),
-                    Some(MobileReward::SubscriberReward(r)) => Ok((
-                        RewardKey {
-                            key: bs58::encode(&r.subscriber_id).into_string(),
-                            reward_type: RewardType::MobileSubscriber,
-                        },
-                        r.discovery_location_amount,
-                    )),
-                    _ => bail!("got an invalid reward share"),
-                }
+    /// Record the history of the hotspots rewarded in epochs indexed while
+    /// their owners could not be resolved, with the owners resolved now.
+    /// Returns the number of rewards recorded
+    async fn backfill_history(&self) -> Result<usize> {
+        let Some(metadata) = &self.metadata else {
+            return Ok(0);
+        };
+        let mut backfilled = 0;
+        loop {
+            let pending =
+                reward_index::fetch_pending_history(&self.pool, HISTORY_BACKFILL_BATCH).await?;
+            if pending.is_empty() {
+                return Ok(backfilled);
             }
-            settings::Mode::Iot => {
-                let share = IotRewardShare::decode(msg)?;
-                match share.reward {
-                    Some(IotReward::GatewayReward(r)) => Ok((
-                        RewardKey {
-                            key: PublicKeyBinary::from(r.hotspot_key).to_string(),
-                            reward_type: RewardType::IotGateway,
-                        },
-                        r.witness_amount + r.beacon_amount + r.dc_transfer_amount,
-                    )),
-                    Some(IotReward::OperationalReward(r)) => Ok((
-                        RewardKey {
-                            key: self.op_fund_key.clone(),
-                            reward_type: RewardType::IotOperational,
-                        },
-                        r.amount,
-                    )),
-                    _ => bail!("got an invalid iot reward share"),
+            let owners =
+                export::hotspot_owners(metadata, pending.iter().map(|reward| &reward.address))
+                    .await?;
+            let mut txn = self.pool.begin().await?;
+            for reward in &pending {
+                // Hotspots without an owner have no history, as when indexed
+                if let Some(owner) = owners.get(&reward.address) {
+                    reward_index::insert_history(
+                        &mut txn,
+                        &reward.address,
+                        &reward.reward_type,
+                        owner,
+                        reward.amount as u64,
+                        &reward.epoch_start,
+                        &reward.epoch_end,
+                    )
+                    .await?;
+                    backfilled += 1;
                 }
             }
+            reward_index::delete_pending_history(&mut txn, &pending).await?;
+            txn.commit().await?;
+            if pending.len() < HISTORY_BACKFILL_BATCH {
+                return Ok(backfilled);
+            }
+        }
+    }
+
+    fn extract_reward_share(&self, msg: &[u8]) -> Result<(RewardKey, u64)> {
+        extract_reward_share(self.mode, &self.op_fund_key, msg)
+    }
+}
+
+/// The items received, loaded up to `concurrency` at a time and yielded in
+/// the order they were received
+fn load_in_order<'a, T, F, Fut>(
+    receiver: Receiver<T>,
+    concurrency: usize,
+    load: F,
+) -> stream::BoxStream<'a, Fut::Output>
+where
+    T: Send + 'a,
+    F: FnMut(T) -> Fut + Send + 'a,
+    Fut: std::future::Future + Send + 'a,
+    Fut::Output: Send,
+{
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+    .map(load)
+    .buffered(concurrency)
+    .boxed()
+}
+
+/// The reward key and amount of an encoded reward share of `mode`
+pub fn extract_reward_share(
+    mode: settings::Mode,
+    op_fund_key: &str,
+    msg: &[u8],
+) -> Result<(RewardKey, u64)> {
+    match mode {
+        settings::Mode::Mobile => {
+            let share = MobileRewardShare::decode(msg)?;
+            match share.reward {
+                Some(MobileReward::RadioReward(r)) => Ok((
+                    RewardKey {
+                        key: PublicKeyBinary::from(r.hotspot_key).to_string(),
+                        reward_type: RewardType::MobileGateway,
+                    },
+                    r.poc_reward,
+                )),
+                Some(MobileReward::GatewayReward(r)) => Ok((
+                    RewardKey {
+                        key: PublicKeyBinary::from(r.hotspot_key).to_string(),
+                        reward_type: RewardType::MobileGateway,
+                    },
+                    r.dc_transfer_reward,
+                )),
+                Some(MobileReward::SubscriberReward(r)) => Ok((
+                    RewardKey {
+                        key: bs58::encode(&r.subscriber_id).into_string(),
+                        reward_type: RewardType::MobileSubscriber,
+                    },
+                    r.discovery_location_amount,
+                )),
+                _ => bail!("got an invalid reward share"),
+            }
+        }
+        settings::Mode::Iot => {
+            let share = IotRewardShare::decode(msg)?;
+            match share.reward {
+                Some(IotReward::GatewayReward(r)) => Ok((
+                    RewardKey {
+                        key: PublicKeyBinary::from(r.hotspot_key).to_string(),
+                        reward_type: RewardType::IotGateway,
+                    },
+                    r.witness_amount + r.beacon_amount + r.dc_transfer_amount,
+                )),
+                Some(IotReward::OperationalReward(r)) => Ok((
+                    RewardKey {
+                        key: op_fund_key.to_string(),
+                        reward_type: RewardType::IotOperational,
+                    },
+                    r.amount,
+                )),
+                _ => bail!("got an invalid iot reward share"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn loads_concurrently_in_order() {
+        let (tx, rx) = mpsc::channel(8);
+        for epoch in 0..6u64 {
+            tx.send(epoch).await.unwrap();
         }
+        drop(tx);
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let loaded: Vec<u64> = load_in_order(rx, 3, |epoch| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            async move {
+                let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(running, Ordering::SeqCst);
+                // later epochs load faster, and are still yielded last
+                tokio::time::sleep(Duration::from_millis(30 - 5 * epoch)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                epoch
+            }
+        })
+        .collect()
+        .await;
+
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], loaded);
+        assert_eq!(3, max_in_flight.load(Ordering::SeqCst));
     }
 }
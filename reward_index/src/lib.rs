@@ -1,4 +1,8 @@
+pub mod accrual_service;
+pub mod export;
 pub mod indexer;
+pub mod progress;
+pub mod proto;
 mod reward_index;
 pub mod settings;
 pub mod telemetry;
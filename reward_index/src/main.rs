@@ -1,13 +1,17 @@
 use anyhow::Result;
-use chrono::{TimeZone, Utc};
+use chrono::{NaiveDateTime, TimeZone, Utc};
 use clap::Parser;
 use file_store::{
     file_info_poller::LookbackBehavior, file_source, reward_manifest::RewardManifest, FileStore,
     FileType,
 };
 use futures_util::TryFutureExt;
-use reward_index::{settings::Settings, telemetry, Indexer};
-use std::path::PathBuf;
+use poc_metrics::preflight::Preflight;
+use reward_index::{
+    accrual_service::AccrualService, export, progress::Progress, settings::Settings, telemetry,
+    Indexer,
+};
+use std::{collections::BTreeMap, fs::File, io, path::PathBuf};
 use tokio::signal;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -35,12 +39,16 @@ impl Cli {
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Server),
+    Progress(ProgressCmd),
+    Export(ExportCmd),
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::Progress(cmd) => cmd.run(&settings).await,
+            Self::Export(cmd) => cmd.run(&settings).await,
         }
     }
 }
@@ -74,6 +82,23 @@ impl Server {
             .database
             .connect(&app_name, shutdown_listener.clone())
             .await?;
+
+        // Check all external dependencies before starting
+        let mut preflight = Preflight::new(env!("CARGO_PKG_NAME"));
+        preflight
+            .check(
+                "database schema",
+                db_store::preflight::check_schema(&pool, &sqlx::migrate!()),
+            )
+            .await;
+        preflight
+            .check("verifier bucket", settings.verifier.check_read())
+            .await;
+        if let Some(accrual_api) = &settings.accrual_api {
+            preflight.check_result("accrual api keypair", accrual_api.signing_keypair());
+        }
+        preflight.finish()?;
+
         sqlx::migrate!().run(&pool).await?;
 
         telemetry::initialize(&pool).await?;
@@ -84,30 +109,117 @@ impl Server {
             .db(pool.clone())
             .store(file_store)
             .file_type(FileType::RewardManifest)
-            .lookback(LookbackBehavior::StartAfter(
-                Utc.timestamp_opt(settings.start_after as i64, 0)
-                    .single()
-                    .unwrap(),
-            ))
+            .lookback(LookbackBehavior::StartAfter(settings.start_after()))
             .poll_duration(settings.interval())
             .offset(settings.interval() * 2)
             .build()?
             .start(shutdown_listener.clone())
             .await?;
 
+        // Hotspot owners recorded in the reward history
+        let (metadata, metadata_join_handle) = match &settings.metadata {
+            Some(metadata) => {
+                let (metadata_pool, join_handle) = metadata
+                    .connect("reward-index-metadata", shutdown_listener.clone())
+                    .await?;
+                (Some(metadata_pool), Some(join_handle))
+            }
+            None => (None, None),
+        };
+
         // Reward server
-        let mut indexer = Indexer::new(settings, pool).await?;
+        let indexer = Indexer::new(settings, pool.clone(), metadata).await?;
+
+        // Reward accruals api
+        let accrual_api = async {
+            match &settings.accrual_api {
+                Some(accrual_settings) => {
+                    AccrualService::new(accrual_settings, pool, indexer.accruals())?
+                        .serve(accrual_settings, shutdown_listener.clone())
+                        .await
+                }
+                None => Ok(()),
+            }
+        };
+
+        let metadata_tracker = async {
+            match metadata_join_handle {
+                Some(join_handle) => join_handle.await.map_err(anyhow::Error::from),
+                None => Ok(()),
+            }
+        };
 
         tokio::try_join!(
             db_join_handle.map_err(anyhow::Error::from),
+            metadata_tracker,
             source_join_handle.map_err(anyhow::Error::from),
-            indexer.run(shutdown_listener, receiver),
+            indexer.run(shutdown_listener.clone(), receiver),
+            accrual_api,
         )?;
 
         Ok(())
     }
 }
 
+/// Report how many reward manifests in the verifier bucket have been indexed
+#[derive(Debug, clap::Args)]
+pub struct ProgressCmd {}
+
+impl ProgressCmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let (_shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let app_name = format!("{}_{}", settings.mode, env!("CARGO_PKG_NAME"));
+        let (pool, _db_join_handle) = settings
+            .database
+            .connect(&app_name, shutdown_listener)
+            .await?;
+        let verifier_store = FileStore::from_settings(&settings.verifier).await?;
+        let progress = Progress::fetch(&pool, &verifier_store, settings.start_after()).await?;
+        println!("{progress}");
+        Ok(())
+    }
+}
+
+/// Export the rewards of an epoch as csv, from the reward shares in the
+/// verifier bucket
+#[derive(Debug, clap::Args)]
+pub struct ExportCmd {
+    /// End of the epoch to export, in UTC, e.g. 2023-09-01T00:00:00
+    #[clap(long)]
+    epoch_end: NaiveDateTime,
+    /// File to write the csv to, stdout when not given
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl ExportCmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let verifier_store = FileStore::from_settings(&settings.verifier).await?;
+        let epoch_end = Utc.from_utc_datetime(&self.epoch_end);
+        let manifest = export::find_manifest(&verifier_store, epoch_end).await?;
+        let op_fund_key = settings.operation_fund_key().unwrap_or_default();
+        let rewards =
+            export::epoch_rewards(&verifier_store, &manifest, settings.mode, &op_fund_key).await?;
+
+        let owners = match &settings.metadata {
+            Some(metadata) => {
+                let (_shutdown_trigger, shutdown_listener) = triggered::trigger();
+                let (metadata_pool, _join_handle) = metadata
+                    .connect("reward-index-metadata", shutdown_listener)
+                    .await?;
+                let keys = rewards.rewards.keys().map(|(key, _)| key);
+                export::hotspot_owners(&metadata_pool, keys).await?
+            }
+            None => BTreeMap::new(),
+        };
+
+        match &self.output {
+            Some(path) => export::write_csv(&mut File::create(path)?, &rewards, &owners),
+            None => export::write_csv(&mut io::stdout().lock(), &rewards, &owners),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -0,0 +1,123 @@
+//! Progress of the index through the reward manifests in the verifier
+//! bucket, mostly useful to follow a backfill.
+
+use chrono::{DateTime, Utc};
+use file_store::{FileInfo, FileStore, FileType};
+use sqlx::{Pool, Postgres};
+use std::fmt;
+
+pub struct Progress {
+    /// Reward manifests in the bucket since `start_after`
+    pub manifests: usize,
+    /// Reward manifests in the bucket newer than the latest one indexed
+    pub pending: usize,
+    /// Timestamp of the latest reward manifest indexed
+    pub indexed_up_to: Option<DateTime<Utc>>,
+    /// Latest reward recorded in the index
+    pub last_reward: Option<DateTime<Utc>>,
+    pub addresses: i64,
+    pub rewards: i64,
+}
+
+impl Progress {
+    pub async fn fetch(
+        pool: &Pool<Postgres>,
+        verifier_store: &FileStore,
+        start_after: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        let indexed_up_to = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "select max(file_timestamp) from files_processed where file_type = $1",
+        )
+        .bind(FileType::RewardManifest.to_str())
+        .fetch_one(pool)
+        .await?;
+        let (addresses, rewards, last_reward) =
+            sqlx::query_as::<_, (i64, i64, Option<DateTime<Utc>>)>(
+                r#"
+                select count(*), coalesce(sum(rewards), 0)::bigint, max(last_reward)
+                from reward_index
+                "#,
+            )
+            .fetch_one(pool)
+            .await?;
+
+        let manifests = verifier_store
+            .list_all(FileType::RewardManifest, start_after, Utc::now())
+            .await?;
+        let pending = pending(&manifests, indexed_up_to);
+
+        Ok(Self {
+            manifests: manifests.len(),
+            pending,
+            indexed_up_to,
+            last_reward,
+            addresses,
+            rewards,
+        })
+    }
+}
+
+/// The manifests newer than the latest one indexed, all of them when none is
+fn pending(manifests: &[FileInfo], indexed_up_to: Option<DateTime<Utc>>) -> usize {
+    manifests
+        .iter()
+        .filter(|manifest| indexed_up_to.map_or(true, |ts| manifest.timestamp > ts))
+        .count()
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indexed = self.manifests - self.pending;
+        writeln!(
+            f,
+            "reward manifests: {indexed} of {} indexed, {} pending",
+            self.manifests, self.pending
+        )?;
+        match self.indexed_up_to {
+            Some(ts) => writeln!(f, "indexed up to: {ts}")?,
+            None => writeln!(f, "indexed up to: none")?,
+        }
+        match self.last_reward {
+            Some(ts) => writeln!(f, "last reward: {ts}")?,
+            None => writeln!(f, "last reward: none")?,
+        }
+        write!(
+            f,
+            "addresses: {}, total rewards: {}",
+            self.addresses, self.rewards
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn counts_manifests_after_the_last_indexed() {
+        let at = |hour| Utc.with_ymd_and_hms(2023, 5, 1, hour, 0, 0).unwrap();
+        let manifests: Vec<FileInfo> = (0..4)
+            .map(|hour| FileInfo::from((FileType::RewardManifest, at(hour))))
+            .collect();
+        assert_eq!(4, pending(&manifests, None));
+        assert_eq!(2, pending(&manifests, Some(at(1))));
+        assert_eq!(0, pending(&manifests, Some(at(3))));
+
+        let progress = Progress {
+            manifests: manifests.len(),
+            pending: pending(&manifests, Some(at(1))),
+            indexed_up_to: Some(at(1)),
+            last_reward: None,
+            addresses: 3,
+            rewards: 42,
+        };
+        assert_eq!(
+            "reward manifests: 2 of 4 indexed, 2 pending\n\
+             indexed up to: 2023-05-01 01:00:00 UTC\n\
+             last reward: none\n\
+             addresses: 3, total rewards: 42",
+            progress.to_string()
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Messages of the reward accruals service, which are not part of
+//! helium-proto. Timestamps are in seconds.
+
+use file_store::impl_msg_verify;
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/helium.reward_index.RewardAccruals.rs"
+));
+
+/// Subscription to the rewards accrued by `keys`, the reward keys as
+/// indexed: b58 hotspot keys, subscriber ids or the operation fund key.
+/// Signed by `owner`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RewardAccrualsReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub owner: Vec<u8>,
+    #[prost(string, repeated, tag = "2")]
+    pub keys: Vec<String>,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+}
+
+/// Rewards accrued by a key in the epoch `epoch_start..epoch_end`, and its
+/// total rewards with them. The current total of each key, sent first on
+/// subscribing, has no `amount` and no `epoch_start`, with `epoch_end` the
+/// end of the last epoch the key was rewarded in.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RewardAccrualV1 {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    /// One of `mobile_gateway`, `mobile_subscriber`, `iot_gateway` or
+    /// `iot_operational`
+    #[prost(string, tag = "2")]
+    pub reward_type: String,
+    #[prost(uint64, tag = "3")]
+    pub amount: u64,
+    #[prost(uint64, tag = "4")]
+    pub total: u64,
+    #[prost(uint64, tag = "5")]
+    pub epoch_start: u64,
+    #[prost(uint64, tag = "6")]
+    pub epoch_end: u64,
+    #[prost(uint64, tag = "7")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "8")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "9")]
+    pub signature: Vec<u8>,
+}
+
+/// Query of the reward history of the hotspots owned by the wallet of
+/// `owner`, an ed25519 key, when they were rewarded. Signed by `owner`.
+/// Only the rewards of `hotspot_keys` are returned unless empty. Pages of up
+/// to `limit` earnings start after `cursor`, the cursor of the previous page,
+/// or at the first epoch when empty
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotEarningsReqV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub owner: Vec<u8>,
+    #[prost(string, repeated, tag = "2")]
+    pub hotspot_keys: Vec<String>,
+    #[prost(string, tag = "3")]
+    pub cursor: String,
+    #[prost(uint32, tag = "4")]
+    pub limit: u32,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: Vec<u8>,
+}
+
+/// Rewards of a class accrued by a hotspot in the epoch
+/// `epoch_start..epoch_end`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotEarningV1 {
+    #[prost(string, tag = "1")]
+    pub hotspot_key: String,
+    /// One of `mobile_gateway` or `iot_gateway`
+    #[prost(string, tag = "2")]
+    pub reward_type: String,
+    #[prost(uint64, tag = "3")]
+    pub amount: u64,
+    #[prost(uint64, tag = "4")]
+    pub epoch_start: u64,
+    #[prost(uint64, tag = "5")]
+    pub epoch_end: u64,
+}
+
+/// A page of earnings ordered by epoch. `cursor` queries the next page and is
+/// empty after the last one
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HotspotEarningsResV1 {
+    #[prost(message, repeated, tag = "1")]
+    pub earnings: Vec<HotspotEarningV1>,
+    #[prost(string, tag = "2")]
+    pub cursor: String,
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+}
+
+impl_msg_verify!(RewardAccrualsReqV1, signature);
+impl_msg_verify!(RewardAccrualV1, signature);
+impl_msg_verify!(HotspotEarningsReqV1, signature);
+impl_msg_verify!(HotspotEarningsResV1, signature);
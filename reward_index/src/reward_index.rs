@@ -1,22 +1,33 @@
 use crate::indexer::RewardType;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+use std::{fmt, str::FromStr};
 
+#[derive(sqlx::FromRow)]
+pub struct IndexedReward {
+    pub address: String,
+    pub rewards: i64,
+    pub last_reward: Option<DateTime<Utc>>,
+    pub reward_type: Option<RewardType>,
+}
+
+/// Add `amount` to the rewards of `address`. Returns the total rewards of
+/// `address` with it, or `None` when there was nothing to add.
 pub async fn insert<'c, E>(
     executor: E,
     address: String,
     amount: u64,
     reward_type: RewardType,
     timestamp: &DateTime<Utc>,
-) -> Result<(), sqlx::Error>
+) -> Result<Option<u64>, sqlx::Error>
 where
     E: sqlx::Executor<'c, Database = sqlx::Postgres>,
 {
     // Safeguard against 0 amount shares updating the last rewarded timestamp
     if amount == 0 {
-        return Ok(());
+        return Ok(None);
     }
 
-    sqlx::query(
+    let total = sqlx::query_scalar::<_, i64>(
         r#"
         insert into reward_index (
                 address,
@@ -27,14 +38,457 @@ where
             on conflict(address) do update set
                 rewards = reward_index.rewards + EXCLUDED.rewards,
                 last_reward = EXCLUDED.last_reward
+            returning rewards
         "#,
     )
     .bind(address)
     .bind(amount as i64)
     .bind(timestamp)
     .bind(reward_type)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(Some(total as u64))
+}
+
+pub async fn fetch<'c, E>(
+    executor: E,
+    addresses: &[String],
+) -> Result<Vec<IndexedReward>, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query_as::<_, IndexedReward>(
+        r#"
+        select address, rewards, last_reward, reward_type from reward_index
+        where address = any($1)
+        "#,
+    )
+    .bind(addresses)
+    .fetch_all(executor)
+    .await
+}
+
+/// Rewards of a class accrued by a hotspot in an epoch, recorded with the
+/// owner of the hotspot when the epoch was indexed
+#[derive(sqlx::FromRow)]
+pub struct HistoricalReward {
+    pub address: String,
+    pub reward_type: RewardType,
+    pub amount: i64,
+    pub epoch_start: DateTime<Utc>,
+    pub epoch_end: DateTime<Utc>,
+}
+
+/// Position in the reward history of an owner, ordered by epoch end, hotspot
+/// key then reward type. Encoded as `<epoch end millis>:<reward type>:<key>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub epoch_end: DateTime<Utc>,
+    pub reward_type: String,
+    pub address: String,
+}
+
+impl Default for HistoryCursor {
+    fn default() -> Self {
+        Self {
+            epoch_end: DateTime::<Utc>::from(std::time::UNIX_EPOCH),
+            reward_type: String::new(),
+            address: String::new(),
+        }
+    }
+}
+
+impl From<&HistoricalReward> for HistoryCursor {
+    fn from(reward: &HistoricalReward) -> Self {
+        Self {
+            epoch_end: reward.epoch_end,
+            reward_type: reward.reward_type.as_str().to_string(),
+            address: reward.address.clone(),
+        }
+    }
+}
+
+impl fmt::Display for HistoryCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.epoch_end.timestamp_millis(),
+            self.reward_type,
+            self.address
+        )
+    }
+}
+
+impl FromStr for HistoryCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(epoch_end), Some(reward_type), Some(address)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("invalid history cursor {s}"));
+        };
+        let epoch_end = epoch_end
+            .parse()
+            .ok()
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+            .ok_or_else(|| format!("invalid history cursor epoch {epoch_end}"))?;
+        Ok(Self {
+            epoch_end,
+            reward_type: reward_type.to_string(),
+            address: address.to_string(),
+        })
+    }
+}
+
+/// Record the rewards `address`, owned by `owner`, accrued in an epoch
+pub async fn insert_history<'c, E>(
+    executor: E,
+    address: &str,
+    reward_type: &RewardType,
+    owner: &str,
+    amount: u64,
+    epoch_start: &DateTime<Utc>,
+    epoch_end: &DateTime<Utc>,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query(
+        r#"
+        insert into reward_history (
+                address,
+                reward_type,
+                owner,
+                amount,
+                epoch_start,
+                epoch_end
+            ) values ($1, $2, $3, $4, $5, $6)
+            on conflict do nothing
+        "#,
+    )
+    .bind(address)
+    .bind(reward_type)
+    .bind(owner)
+    .bind(amount as i64)
+    .bind(epoch_start)
+    .bind(epoch_end)
     .execute(executor)
     .await?;
+    Ok(())
+}
 
+/// Record the rewards `address` accrued in an epoch whose hotspot owners
+/// could not be resolved, for its history to be backfilled once they can be
+pub async fn insert_pending_history<'c, E>(
+    executor: E,
+    address: &str,
+    reward_type: &RewardType,
+    amount: u64,
+    epoch_start: &DateTime<Utc>,
+    epoch_end: &DateTime<Utc>,
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query(
+        r#"
+        insert into pending_reward_history (
+                address,
+                reward_type,
+                amount,
+                epoch_start,
+                epoch_end
+            ) values ($1, $2, $3, $4, $5)
+            on conflict do nothing
+        "#,
+    )
+    .bind(address)
+    .bind(reward_type)
+    .bind(amount as i64)
+    .bind(epoch_start)
+    .bind(epoch_end)
+    .execute(executor)
+    .await?;
     Ok(())
 }
+
+/// Up to `limit` of the rewards waiting for their history to be backfilled,
+/// oldest epochs first
+pub async fn fetch_pending_history<'c, E>(
+    executor: E,
+    limit: usize,
+) -> Result<Vec<HistoricalReward>, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query_as::<_, HistoricalReward>(
+        r#"
+        select address, reward_type, amount, epoch_start, epoch_end from pending_reward_history
+        order by epoch_end, address, reward_type::text
+        limit $1
+        "#,
+    )
+    .bind(limit as i64)
+    .fetch_all(executor)
+    .await
+}
+
+/// Remove `rewards` from those waiting for their history to be backfilled
+pub async fn delete_pending_history<'c, E>(
+    executor: E,
+    rewards: &[HistoricalReward],
+) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    let addresses: Vec<&str> = rewards.iter().map(|r| r.address.as_str()).collect();
+    let reward_types: Vec<&str> = rewards.iter().map(|r| r.reward_type.as_str()).collect();
+    let epoch_ends: Vec<DateTime<Utc>> = rewards.iter().map(|r| r.epoch_end).collect();
+    sqlx::query(
+        r#"
+        delete from pending_reward_history p
+        using unnest($1::text[], $2::text[], $3::timestamptz[]) as d(address, reward_type, epoch_end)
+        where p.address = d.address
+            and p.reward_type::text = d.reward_type
+            and p.epoch_end = d.epoch_end
+        "#,
+    )
+    .bind(addresses)
+    .bind(reward_types)
+    .bind(epoch_ends)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Up to `limit` rewards of the hotspots owned by `owner` when rewarded,
+/// following `after`. Only rewards of `addresses` are returned unless empty
+pub async fn fetch_history<'c, E>(
+    executor: E,
+    owner: &str,
+    addresses: &[String],
+    after: &HistoryCursor,
+    limit: usize,
+) -> Result<Vec<HistoricalReward>, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query_as::<_, HistoricalReward>(
+        r#"
+        select address, reward_type, amount, epoch_start, epoch_end from reward_history
+        where owner = $1
+            and (cardinality($2::text[]) = 0 or address = any($2))
+            and (epoch_end, address, reward_type::text) > ($3, $4, $5)
+        order by epoch_end, address, reward_type::text
+        limit $6
+        "#,
+    )
+    .bind(owner)
+    .bind(addresses)
+    .bind(after.epoch_end)
+    .bind(&after.address)
+    .bind(&after.reward_type)
+    .bind(limit as i64)
+    .fetch_all(executor)
+    .await
+}
+
+/// The keys among `addresses`, or among all hotspots unless empty, whose
+/// latest rewards in the history were earned while owned by `owner`
+pub async fn owned_keys<'c, E>(
+    executor: E,
+    owner: &str,
+    addresses: &[String],
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar(
+        r#"
+        select address from (
+            select distinct on (address) address, owner from reward_history
+            where address in (select address from reward_history where owner = $1)
+                and (cardinality($2::text[]) = 0 or address = any($2))
+            order by address, epoch_end desc
+        ) latest
+        where owner = $1
+        "#,
+    )
+    .bind(owner)
+    .bind(addresses)
+    .fetch_all(executor)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_cursor_round_trips() {
+        let cursor = HistoryCursor {
+            epoch_end: Utc.timestamp_millis_opt(1_693_526_400_000).unwrap(),
+            reward_type: "iot_gateway".to_string(),
+            address: "112NqN2WWMwtK29PMzRby62fDydBJfsCLkCAf392stdok48ovNT6".to_string(),
+        };
+        assert_eq!(Ok(cursor.clone()), cursor.to_string().parse());
+        assert!("1693526400000:iot_gateway"
+            .parse::<HistoryCursor>()
+            .is_err());
+    }
+
+    #[sqlx::test]
+    async fn history_is_scoped_to_owner_and_paged_by_cursor(
+        pool: sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        let epoch = |day: u32| Utc.with_ymd_and_hms(2023, 9, day, 0, 0, 0).unwrap();
+        let history = [
+            ("a", RewardType::IotGateway, "alice", 1),
+            ("b", RewardType::IotGateway, "alice", 1),
+            ("a", RewardType::MobileGateway, "alice", 1),
+            ("a", RewardType::IotGateway, "alice", 2),
+            ("a", RewardType::IotGateway, "bob", 3),
+            ("c", RewardType::IotGateway, "bob", 1),
+        ];
+        for (address, reward_type, owner, day) in &history {
+            insert_history(
+                &pool,
+                address,
+                reward_type,
+                owner,
+                *day as u64,
+                &epoch(day - 1),
+                &epoch(*day),
+            )
+            .await?;
+        }
+        let keys = |rewards: &[HistoricalReward]| {
+            rewards
+                .iter()
+                .map(|r| (r.address.clone(), r.reward_type.as_str(), r.epoch_end))
+                .collect::<Vec<_>>()
+        };
+
+        // pages of alice's rewards only, ordered by epoch, key then class
+        let page = fetch_history(&pool, "alice", &[], &HistoryCursor::default(), 2).await?;
+        assert_eq!(
+            keys(&page),
+            vec![
+                ("a".to_string(), "iot_gateway", epoch(1)),
+                ("a".to_string(), "mobile_gateway", epoch(1)),
+            ]
+        );
+        let cursor: HistoryCursor = HistoryCursor::from(page.last().unwrap())
+            .to_string()
+            .parse()
+            .unwrap();
+        let page = fetch_history(&pool, "alice", &[], &cursor, 2).await?;
+        assert_eq!(
+            keys(&page),
+            vec![
+                ("b".to_string(), "iot_gateway", epoch(1)),
+                ("a".to_string(), "iot_gateway", epoch(2)),
+            ]
+        );
+        assert_eq!(page[1].amount, 2);
+        let cursor = HistoryCursor::from(page.last().unwrap());
+        assert!(fetch_history(&pool, "alice", &[], &cursor, 2)
+            .await?
+            .is_empty());
+
+        // restricted to some keys, and never those rewarded to another owner
+        let page = fetch_history(
+            &pool,
+            "alice",
+            &["b".to_string(), "c".to_string()],
+            &HistoryCursor::default(),
+            10,
+        )
+        .await?;
+        assert_eq!(
+            keys(&page),
+            vec![("b".to_string(), "iot_gateway", epoch(1))]
+        );
+        let page = fetch_history(&pool, "bob", &[], &HistoryCursor::default(), 10).await?;
+        assert_eq!(
+            keys(&page),
+            vec![
+                ("c".to_string(), "iot_gateway", epoch(1)),
+                ("a".to_string(), "iot_gateway", epoch(3)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn pending_history_is_fetched_until_deleted(pool: sqlx::PgPool) -> anyhow::Result<()> {
+        let epoch = |day: u32| Utc.with_ymd_and_hms(2023, 9, day, 0, 0, 0).unwrap();
+        for (address, day) in [("a", 2), ("b", 1), ("a", 1)] {
+            insert_pending_history(
+                &pool,
+                address,
+                &RewardType::IotGateway,
+                1,
+                &epoch(day - 1),
+                &epoch(day),
+            )
+            .await?;
+        }
+
+        let pending = fetch_pending_history(&pool, 2).await?;
+        let pending_keys: Vec<_> = pending
+            .iter()
+            .map(|r| (r.address.as_str(), r.epoch_end))
+            .collect();
+        assert_eq!(pending_keys, vec![("a", epoch(1)), ("b", epoch(1))]);
+
+        delete_pending_history(&pool, &pending).await?;
+        let pending = fetch_pending_history(&pool, 10).await?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            (pending[0].address.as_str(), pending[0].epoch_end),
+            ("a", epoch(2))
+        );
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn only_hotspots_still_owned_are_owned_keys(pool: sqlx::PgPool) -> anyhow::Result<()> {
+        let epoch = |day: u32| Utc.with_ymd_and_hms(2023, 9, day, 0, 0, 0).unwrap();
+        let history = [
+            ("kept", "alice", 1),
+            ("kept", "alice", 2),
+            ("sold", "alice", 1),
+            ("sold", "bob", 2),
+            ("bought", "bob", 1),
+            ("bought", "alice", 2),
+        ];
+        for (address, owner, day) in history {
+            insert_history(
+                &pool,
+                address,
+                &RewardType::IotGateway,
+                owner,
+                1,
+                &epoch(day - 1),
+                &epoch(day),
+            )
+            .await?;
+        }
+
+        let mut owned = owned_keys(&pool, "alice", &[]).await?;
+        owned.sort();
+        assert_eq!(owned, vec!["bought", "kept"]);
+        assert_eq!(
+            owned_keys(&pool, "alice", &["sold".to_string(), "kept".to_string()]).await?,
+            vec!["kept"]
+        );
+        assert_eq!(owned_keys(&pool, "bob", &[]).await?, vec!["sold"]);
+        assert!(owned_keys(&pool, "carol", &[]).await?.is_empty());
+        Ok(())
+    }
+}
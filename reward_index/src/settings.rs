@@ -1,5 +1,4 @@
-use chrono::Duration;
-use config::{Config, Environment, File};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use serde::Deserialize;
 use std::{fmt, path::Path};
 
@@ -38,12 +37,28 @@ pub struct Settings {
     pub operation_fund_key: Option<String>,
     #[serde(default = "default_start_after")]
     pub start_after: u64,
+    /// Reward manifests fetched and aggregated at once, each holding a
+    /// database connection until it is committed. Keep below the database
+    /// max_connections. (Default is 4)
+    #[serde(default = "default_max_concurrent_epochs")]
+    pub max_concurrent_epochs: usize,
+    /// Signed api streaming the rewards accrued by reward keys as epochs are
+    /// committed, disabled when not set
+    pub accrual_api: Option<crate::accrual_service::Settings>,
+    /// Optional on-chain metadata database resolving the owners of the
+    /// hotspots in reward exports and in the reward history. Owners are left
+    /// empty in exports and no history is recorded when not set
+    pub metadata: Option<db_store::Settings>,
 }
 
 pub fn default_start_after() -> u64 {
     0
 }
 
+pub fn default_max_concurrent_epochs() -> usize {
+    4
+}
+
 pub fn default_log() -> String {
     "reward_index=debug,poc_store=info".to_string()
 }
@@ -55,26 +70,20 @@ impl Settings {
     /// Environemnt overrides have the same name as the entries in the settings
     /// file in uppercase and prefixed with "MI_". For example "MI_DATABASE_URL"
     /// will override the data base url.
-    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, config::ConfigError> {
-        let mut builder = Config::builder();
-
-        if let Some(file) = path {
-            // Add optional settings file
-            builder = builder
-                .add_source(File::with_name(&file.as_ref().to_string_lossy()).required(false));
-        }
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `MI_DEBUG=1 ./target/app` would set the `debug` key
-        builder
-            .add_source(Environment::with_prefix("MI").separator("_"))
-            .build()
-            .and_then(|config| config.try_deserialize())
+    pub fn new<P: AsRef<Path>>(path: Option<P>) -> Result<Self, settings_loader::Error> {
+        settings_loader::load(path, "MI")
     }
 
     pub fn interval(&self) -> Duration {
         Duration::seconds(self.interval)
     }
 
+    pub fn start_after(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.start_after as i64, 0)
+            .single()
+            .unwrap()
+    }
+
     pub fn operation_fund_key(&self) -> Option<String> {
         self.operation_fund_key.clone()
     }
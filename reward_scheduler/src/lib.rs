@@ -1,3 +1,5 @@
+pub mod reward_share;
+
 use chrono::{DateTime, Duration, Utc};
 use std::ops::Range;
 
@@ -0,0 +1,206 @@
+//! Rounding policy for splitting a reward pool over shares.
+
+use rust_decimal::prelude::*;
+
+/// Precision of the rewards per share
+pub const REWARD_PREC: u32 = 15;
+
+/// A reward pool paid out in whole bones. Every share is floored to whole
+/// bones, so nothing more than the pool is ever handed out, and whatever is
+/// not allocated is left as [`RewardPool::dust`] for the rewarder to assign
+#[derive(Debug)]
+pub struct RewardPool {
+    total: u64,
+    rewards_per_share: Decimal,
+    allocated: u64,
+}
+
+impl RewardPool {
+    /// A pool of `total_rewards` split evenly over `total_shares`, with the
+    /// rewards per share rounded to [`REWARD_PREC`]
+    pub fn new(total_rewards: Decimal, total_shares: Decimal) -> Self {
+        let rewards_per_share = if total_shares > Decimal::ZERO {
+            (total_rewards / total_shares)
+                .round_dp_with_strategy(REWARD_PREC, RoundingStrategy::MidpointNearestEven)
+        } else {
+            Decimal::ZERO
+        };
+        Self::with_rewards_per_share(total_rewards, rewards_per_share)
+    }
+
+    /// A pool of `total_rewards` paying out a fixed `rewards_per_share`
+    pub fn with_rewards_per_share(total_rewards: Decimal, rewards_per_share: Decimal) -> Self {
+        Self {
+            total: floor(total_rewards),
+            rewards_per_share,
+            allocated: 0,
+        }
+    }
+
+    pub fn rewards_per_share(&self) -> Decimal {
+        self.rewards_per_share
+    }
+
+    /// Whole bones of the pool
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    /// Bones of the pool left after all allocations, none when the pool has
+    /// no shares to pay out
+    pub fn dust(&self) -> u64 {
+        if self.rewards_per_share > Decimal::ZERO {
+            self.remaining()
+        } else {
+            0
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        self.total - self.allocated
+    }
+
+    /// Allocate the rewards for `shares`, floored to whole bones and capped at
+    /// what is left of the pool. Non positive shares receive nothing.
+    pub fn allocate(&mut self, shares: Decimal) -> u64 {
+        let reward = floor(self.rewards_per_share * shares).min(self.remaining());
+        self.allocated += reward;
+        reward
+    }
+
+    /// Allocate the rewards for every key's shares in key order, so the
+    /// amounts don't depend on the order the shares were gathered in
+    pub fn allocate_all<K: Ord>(
+        &mut self,
+        shares: impl IntoIterator<Item = (K, Decimal)>,
+    ) -> Vec<(K, u64)> {
+        let mut shares: Vec<_> = shares.into_iter().collect();
+        shares.sort_by(|(a, _), (b, _)| a.cmp(b));
+        shares
+            .into_iter()
+            .map(|(key, shares)| (key, self.allocate(shares)))
+            .collect()
+    }
+}
+
+fn floor(amount: Decimal) -> u64 {
+    amount
+        .round_dp_with_strategy(0, RoundingStrategy::ToZero)
+        .to_u64()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn floors_and_accounts_for_dust() {
+        let mut pool = RewardPool::new(dec!(100), dec!(3));
+        assert_eq!(dec!(33.333333333333333), pool.rewards_per_share());
+        let rewards = pool.allocate_all([("b", dec!(1)), ("a", dec!(1)), ("c", dec!(1))]);
+        assert_eq!(vec![("a", 33), ("b", 33), ("c", 33)], rewards);
+        assert_eq!(99, pool.allocated());
+        assert_eq!(1, pool.dust());
+
+        // fractional bones of the pool itself are never paid out
+        let mut pool = RewardPool::new(dec!(10.9), dec!(1));
+        assert_eq!(10, pool.allocate(dec!(1)));
+        assert_eq!(0, pool.dust());
+    }
+
+    #[test]
+    fn empty_and_non_positive_shares_receive_nothing() {
+        // a pool without shares is left unemitted rather than all dust
+        let mut pool = RewardPool::new(dec!(100), Decimal::ZERO);
+        assert_eq!(0, pool.allocate(dec!(5)));
+        assert_eq!(0, pool.dust());
+        assert_eq!(
+            0,
+            RewardPool::with_rewards_per_share(dec!(100), Decimal::ZERO).dust()
+        );
+
+        let mut pool = RewardPool::new(dec!(100), dec!(10));
+        assert_eq!(0, pool.allocate(dec!(-1)));
+        assert_eq!(0, pool.allocate(Decimal::ZERO));
+        assert_eq!(100, pool.dust());
+    }
+
+    #[test]
+    fn never_allocates_more_than_the_pool() {
+        // rewards per share over what the pool can pay out, the last share
+        // in key order gets what is left
+        let mut pool = RewardPool::with_rewards_per_share(dec!(10), dec!(4));
+        let rewards = pool.allocate_all([(3, dec!(1)), (1, dec!(1)), (2, dec!(1))]);
+        assert_eq!(vec![(1, 4), (2, 4), (3, 2)], rewards);
+        assert_eq!(0, pool.dust());
+    }
+
+    #[test]
+    fn allocations_and_dust_add_up_to_the_pool() {
+        let share_sets: [&[Decimal]; 6] = [
+            &[dec!(1)],
+            &[dec!(1), dec!(1), dec!(1)],
+            &[dec!(1), dec!(2), dec!(3), dec!(4)],
+            &[dec!(0.1), dec!(0.2), dec!(0.7)],
+            &[dec!(7), dec!(0), dec!(13), dec!(0.5), dec!(11)],
+            &[
+                dec!(1),
+                dec!(1),
+                dec!(1),
+                dec!(1),
+                dec!(1),
+                dec!(1),
+                dec!(1),
+            ],
+        ];
+        for total in 0..=250 {
+            for shares in share_sets {
+                let total_rewards = Decimal::from(total) + dec!(0.75);
+                let total_shares: Decimal = shares.iter().sum();
+                let mut pool = RewardPool::new(total_rewards, total_shares);
+                let rewards = pool.allocate_all(shares.iter().copied().enumerate());
+
+                let allocated: u64 = rewards.iter().map(|(_, reward)| reward).sum();
+                assert_eq!(allocated, pool.allocated());
+                assert_eq!(total as u64, allocated + pool.dust());
+                // flooring every share loses less than a bone per share
+                assert!(pool.dust() <= shares.len() as u64);
+                for (n, reward) in rewards {
+                    let exact = total_rewards * shares[n] / total_shares;
+                    assert!(Decimal::from(reward) <= exact);
+                    assert!(exact - Decimal::from(reward) < Decimal::ONE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn allocation_does_not_depend_on_share_order() {
+        let shares = [
+            ("d", dec!(3.3)),
+            ("a", dec!(1.1)),
+            ("c", dec!(5.5)),
+            ("b", dec!(2.2)),
+        ];
+        let total_shares: Decimal = shares.iter().map(|(_, shares)| shares).sum();
+        let mut expected = None;
+        for rotation in 0..shares.len() {
+            for reversed in [false, true] {
+                let mut ordered = shares.to_vec();
+                ordered.rotate_left(rotation);
+                if reversed {
+                    ordered.reverse();
+                }
+                let mut pool = RewardPool::new(dec!(1_000_003), total_shares);
+                let result = (pool.allocate_all(ordered), pool.dust());
+                assert_eq!(&result, expected.get_or_insert_with(|| result.clone()));
+            }
+        }
+    }
+}
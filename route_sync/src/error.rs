@@ -0,0 +1,17 @@
+pub type Result<T = ()> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("error signing request: {0}")]
+    Signing(#[from] helium_crypto::Error),
+    #[error("grpc error response: {0}")]
+    Rpc(#[from] tonic::Status),
+    #[error("error verifying response signature: {0}")]
+    Verification(#[from] file_store::Error),
+    #[error("error decoding persisted routes: {0}")]
+    Decode(#[from] prost::DecodeError),
+    #[error("route stream closed by server")]
+    StreamClosed,
+}
@@ -0,0 +1,9 @@
+mod error;
+mod settings;
+pub mod store;
+pub mod sync;
+
+pub use error::{Error, Result};
+pub use settings::Settings;
+pub use store::RouteStore;
+pub use sync::{RouteReader, RouteSync};
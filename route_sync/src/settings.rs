@@ -0,0 +1,62 @@
+use serde::Deserialize;
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Settings {
+    /// Connection and signing settings for the iot config service. The
+    /// signing keypair must be registered as a packet router key
+    pub config_client: iot_config::client::Settings,
+    /// File the local copy of the routes is persisted to and restored from on
+    /// startup
+    pub cache: PathBuf,
+    /// The initial snapshot sent on every new route stream is considered
+    /// complete once no message arrives for this long. Default 5s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_snapshot_settle"
+    )]
+    pub snapshot_settle: Duration,
+    /// A snapshot without any message is considered complete, and empty,
+    /// after this long. Default 30s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_empty_snapshot_timeout"
+    )]
+    pub empty_snapshot_timeout: Duration,
+    /// Interval at which the stream is reopened to reconcile the local copy
+    /// against a fresh snapshot. Default 1h
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_reconcile_interval"
+    )]
+    pub reconcile_interval: Duration,
+    /// Interval at which changes are persisted to `cache`. Default 30s
+    #[serde(
+        with = "settings_loader::duration",
+        default = "default_persist_interval"
+    )]
+    pub persist_interval: Duration,
+    /// Upper bound on the delay between reconnect attempts. Default 5m
+    #[serde(with = "settings_loader::duration", default = "default_max_backoff")]
+    pub max_backoff: Duration,
+}
+
+fn default_snapshot_settle() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_empty_snapshot_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_reconcile_interval() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn default_persist_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_max_backoff() -> Duration {
+    Duration::from_secs(5 * 60)
+}
@@ -0,0 +1,343 @@
+use crate::Result;
+use helium_proto::{
+    services::iot_config::{
+        route_stream_res_v1::Data, ActionV1, DevaddrRangeV1, EuiPairV1, RouteStreamResV1, RouteV1,
+        SkfV1,
+    },
+    Message,
+};
+use prost::bytes::{Buf, BufMut};
+use std::collections::{HashMap, HashSet};
+
+/// In memory copy of the routes, eui pairs and devaddr ranges published on
+/// the iot config route stream, and the session key filters published on its
+/// versioned skf stream.
+#[derive(Debug, Default, Clone)]
+pub struct RouteStore {
+    cursor: u64,
+    skf_version: u64,
+    routes: HashMap<String, RouteV1>,
+    euis: HashMap<(u64, u64), HashSet<String>>,
+    devaddr_ranges: HashMap<String, HashSet<(u32, u32)>>,
+    skfs: HashMap<(String, u32), HashMap<String, u32>>,
+}
+
+impl RouteStore {
+    /// Timestamp of the most recent update applied to the store
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Version of the session key filters, the skf stream resumes after it
+    pub fn skf_version(&self) -> u64 {
+        self.skf_version
+    }
+
+    pub fn route_count(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn skf_count(&self) -> usize {
+        self.skfs.values().map(HashMap::len).sum()
+    }
+
+    pub fn apply(&mut self, update: &RouteStreamResV1) {
+        self.cursor = self.cursor.max(update.timestamp);
+        let add = update.action() == ActionV1::Add;
+        match &update.data {
+            Some(Data::Route(route)) if add => {
+                self.routes.insert(route.id.clone(), route.clone());
+            }
+            Some(Data::Route(route)) => self.remove_route(&route.id),
+            Some(Data::EuiPair(pair)) => {
+                let key = (pair.app_eui, pair.dev_eui);
+                if add {
+                    self.euis
+                        .entry(key)
+                        .or_default()
+                        .insert(pair.route_id.clone());
+                } else if let Some(route_ids) = self.euis.get_mut(&key) {
+                    route_ids.remove(&pair.route_id);
+                    if route_ids.is_empty() {
+                        self.euis.remove(&key);
+                    }
+                }
+            }
+            Some(Data::DevaddrRange(range)) => {
+                let key = (range.start_addr, range.end_addr);
+                let ranges = self
+                    .devaddr_ranges
+                    .entry(range.route_id.clone())
+                    .or_default();
+                if add {
+                    ranges.insert(key);
+                } else {
+                    ranges.remove(&key);
+                }
+            }
+            Some(Data::Skf(skf)) => self.apply_skf(add, skf),
+            None => tracing::warn!("ignoring route stream update without data"),
+        }
+    }
+
+    pub fn apply_skf(&mut self, add: bool, skf: &SkfV1) {
+        let key = (skf.route_id.clone(), skf.devaddr);
+        let session_keys = self.skfs.entry(key.clone()).or_default();
+        if add {
+            session_keys.insert(skf.session_key.clone(), skf.max_copies);
+        } else {
+            session_keys.remove(&skf.session_key);
+            if session_keys.is_empty() {
+                self.skfs.remove(&key);
+            }
+        }
+    }
+
+    pub fn set_skf_version(&mut self, version: u64) {
+        self.skf_version = version;
+    }
+
+    /// Replace the session key filters and their version with those of
+    /// `other`, leaving it without any
+    pub fn take_skfs(&mut self, other: &mut RouteStore) {
+        self.skfs = std::mem::take(&mut other.skfs);
+        self.skf_version = std::mem::take(&mut other.skf_version);
+    }
+
+    fn remove_route(&mut self, route_id: &str) {
+        self.routes.remove(route_id);
+        self.devaddr_ranges.remove(route_id);
+        self.skfs.retain(|(id, _), _| id != route_id);
+        self.euis.retain(|_, route_ids| {
+            route_ids.remove(route_id);
+            !route_ids.is_empty()
+        });
+    }
+
+    pub fn route(&self, route_id: &str) -> Option<&RouteV1> {
+        self.routes.get(route_id)
+    }
+
+    /// Routes with a devaddr range containing the given devaddr
+    pub fn routes_for_devaddr(&self, devaddr: u32) -> Vec<&RouteV1> {
+        self.devaddr_ranges
+            .iter()
+            .filter(|(_, ranges)| {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&devaddr))
+            })
+            .filter_map(|(route_id, _)| self.routes.get(route_id))
+            .collect()
+    }
+
+    /// Routes the given app and dev eui pair is registered to
+    pub fn routes_for_eui(&self, app_eui: u64, dev_eui: u64) -> Vec<&RouteV1> {
+        self.euis
+            .get(&(app_eui, dev_eui))
+            .into_iter()
+            .flatten()
+            .filter_map(|route_id| self.routes.get(route_id))
+            .collect()
+    }
+
+    /// Session keys and their max copies for the devaddr on the given route
+    pub fn session_keys(&self, route_id: &str, devaddr: u32) -> Vec<(String, u32)> {
+        self.skfs
+            .get(&(route_id.to_string(), devaddr))
+            .map(|keys| {
+                keys.iter()
+                    .map(|(key, max_copies)| (key.clone(), *max_copies))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Encode the store as the cursor and the skf version followed by a
+    /// length delimited add update for every entry
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.put_u64(self.cursor);
+        buf.put_u64(self.skf_version);
+        for data in self.entries() {
+            let update = RouteStreamResV1 {
+                action: ActionV1::Add.into(),
+                data: Some(data),
+                ..Default::default()
+            };
+            // encoding into a vec can only fail on lack of capacity, which
+            // a vec grows on demand
+            let _ = update.encode_length_delimited(&mut buf);
+        }
+        buf
+    }
+
+    pub fn decode(mut buf: &[u8]) -> Result<Self> {
+        let mut store = Self::default();
+        if buf.remaining() < 16 {
+            return Ok(store);
+        }
+        let cursor = buf.get_u64();
+        let skf_version = buf.get_u64();
+        while buf.has_remaining() {
+            store.apply(&RouteStreamResV1::decode_length_delimited(&mut buf)?);
+        }
+        store.cursor = cursor;
+        store.skf_version = skf_version;
+        Ok(store)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = Data> + '_ {
+        let routes = self.routes.values().cloned().map(Data::Route);
+        let euis = self
+            .euis
+            .iter()
+            .flat_map(|((app_eui, dev_eui), route_ids)| {
+                route_ids.iter().map(|route_id| {
+                    Data::EuiPair(EuiPairV1 {
+                        route_id: route_id.clone(),
+                        app_eui: *app_eui,
+                        dev_eui: *dev_eui,
+                    })
+                })
+            });
+        let devaddr_ranges = self.devaddr_ranges.iter().flat_map(|(route_id, ranges)| {
+            ranges.iter().map(|(start_addr, end_addr)| {
+                Data::DevaddrRange(DevaddrRangeV1 {
+                    route_id: route_id.clone(),
+                    start_addr: *start_addr,
+                    end_addr: *end_addr,
+                })
+            })
+        });
+        let skfs = self.skfs.iter().flat_map(|((route_id, devaddr), keys)| {
+            keys.iter().map(|(session_key, max_copies)| {
+                Data::Skf(SkfV1 {
+                    route_id: route_id.clone(),
+                    devaddr: *devaddr,
+                    session_key: session_key.clone(),
+                    max_copies: *max_copies,
+                })
+            })
+        });
+        routes.chain(euis).chain(devaddr_ranges).chain(skfs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(action: ActionV1, data: Data, timestamp: u64) -> RouteStreamResV1 {
+        RouteStreamResV1 {
+            action: action.into(),
+            data: Some(data),
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    fn route(id: &str) -> Data {
+        Data::Route(RouteV1 {
+            id: id.to_string(),
+            oui: 1,
+            active: true,
+            ..Default::default()
+        })
+    }
+
+    fn devaddr_range(route_id: &str, start_addr: u32, end_addr: u32) -> Data {
+        Data::DevaddrRange(DevaddrRangeV1 {
+            route_id: route_id.to_string(),
+            start_addr,
+            end_addr,
+        })
+    }
+
+    fn eui_pair(route_id: &str) -> Data {
+        Data::EuiPair(EuiPairV1 {
+            route_id: route_id.to_string(),
+            app_eui: 1,
+            dev_eui: 2,
+        })
+    }
+
+    fn skf(route_id: &str, session_key: &str) -> Data {
+        Data::Skf(SkfV1 {
+            route_id: route_id.to_string(),
+            devaddr: 10,
+            session_key: session_key.to_string(),
+            max_copies: 3,
+        })
+    }
+
+    fn populated() -> RouteStore {
+        let mut store = RouteStore::default();
+        store.set_skf_version(42);
+        for (timestamp, data) in [
+            route("a"),
+            route("b"),
+            devaddr_range("a", 0, 15),
+            devaddr_range("b", 10, 20),
+            eui_pair("a"),
+            eui_pair("b"),
+            skf("a", "key1"),
+            skf("a", "key2"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            store.apply(&update(ActionV1::Add, data, timestamp as u64));
+        }
+        store
+    }
+
+    #[test]
+    fn queries_follow_updates() {
+        let mut store = populated();
+        assert_eq!(2, store.routes_for_devaddr(12).len());
+        assert_eq!(1, store.routes_for_devaddr(5).len());
+        assert_eq!(2, store.routes_for_eui(1, 2).len());
+        assert_eq!(2, store.session_keys("a", 10).len());
+
+        store.apply(&update(ActionV1::Remove, skf("a", "key1"), 8));
+        assert_eq!(vec![("key2".to_string(), 3)], store.session_keys("a", 10));
+
+        store.apply(&update(ActionV1::Remove, route("a"), 9));
+        assert!(store.route("a").is_none());
+        assert!(store.routes_for_devaddr(5).is_empty());
+        assert_eq!(1, store.routes_for_eui(1, 2).len());
+        assert!(store.session_keys("a", 10).is_empty());
+        assert_eq!(9, store.cursor());
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        let store = populated();
+        let decoded = RouteStore::decode(&store.encode()).expect("decode");
+        assert_eq!(store.cursor(), decoded.cursor());
+        assert_eq!(42, decoded.skf_version());
+        assert_eq!(store.routes, decoded.routes);
+        assert_eq!(store.euis, decoded.euis);
+        assert_eq!(store.devaddr_ranges, decoded.devaddr_ranges);
+        assert_eq!(store.skfs, decoded.skfs);
+    }
+
+    #[test]
+    fn take_skfs_keeps_skfs_across_route_snapshots() {
+        let mut store = populated();
+        let mut snapshot = RouteStore::default();
+        snapshot.apply(&update(ActionV1::Add, route("a"), 20));
+        snapshot.apply(&update(ActionV1::Add, skf("a", "stale"), 21));
+
+        snapshot.take_skfs(&mut store);
+        assert_eq!(42, snapshot.skf_version());
+        let mut session_keys = snapshot.session_keys("a", 10);
+        session_keys.sort();
+        assert_eq!(
+            vec![("key1".to_string(), 3), ("key2".to_string(), 3)],
+            session_keys
+        );
+        assert_eq!(0, store.skf_count());
+    }
+}
@@ -0,0 +1,373 @@
+//! Keeps a [`RouteStore`] in sync with the iot config route and skf streams.
+
+use crate::{Error, Result, RouteStore, Settings};
+use chrono::Utc;
+use file_store::traits::{MsgVerify, TimestampEncode};
+use helium_crypto::{Keypair, PublicKey, Sign};
+use helium_proto::{
+    services::{
+        iot_config::{
+            route_client::RouteClient, route_stream_res_v1::Data, ActionV1, RouteStreamReqV1,
+            RouteStreamResV1, RouteV1,
+        },
+        Channel, Endpoint,
+    },
+    Message,
+};
+use iot_config::ext::{
+    skf_stream_client::SkfStreamClient, SkfStreamKindV1, SkfStreamReqV1, SkfStreamResV1,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{watch, RwLock},
+    time,
+};
+use tonic::Streaming;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const ROUTES_GAUGE: &str = "route_sync_routes";
+const SKFS_GAUGE: &str = "route_sync_skfs";
+const CURSOR_GAUGE: &str = "route_sync_cursor";
+const SKF_VERSION_GAUGE: &str = "route_sync_skf_version";
+
+/// Cheaply cloneable query access to the synced routes
+#[derive(Clone, Debug)]
+pub struct RouteReader {
+    store: Arc<RwLock<RouteStore>>,
+    cursor: watch::Receiver<u64>,
+}
+
+impl RouteReader {
+    pub async fn route(&self, route_id: &str) -> Option<RouteV1> {
+        self.store.read().await.route(route_id).cloned()
+    }
+
+    pub async fn routes_for_devaddr(&self, devaddr: u32) -> Vec<RouteV1> {
+        let store = self.store.read().await;
+        store
+            .routes_for_devaddr(devaddr)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn routes_for_eui(&self, app_eui: u64, dev_eui: u64) -> Vec<RouteV1> {
+        let store = self.store.read().await;
+        store
+            .routes_for_eui(app_eui, dev_eui)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn session_keys(&self, route_id: &str, devaddr: u32) -> Vec<(String, u32)> {
+        self.store.read().await.session_keys(route_id, devaddr)
+    }
+
+    /// Timestamp of the most recent update applied to the local copy
+    pub fn cursor(&self) -> u64 {
+        *self.cursor.borrow()
+    }
+
+    /// Subscribe to changes of the local copy
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.cursor.clone()
+    }
+}
+
+/// Syncs the local copy of the routes, served by the [`RouteReader`] from
+/// the previous copy, restored from disk on startup, until the first
+/// snapshot settles
+pub struct RouteSync {
+    client: RouteClient<Channel>,
+    skf_client: SkfStreamClient<Channel>,
+    signing_key: Arc<Keypair>,
+    config_pubkey: PublicKey,
+    settings: Settings,
+    store: Arc<RwLock<RouteStore>>,
+    cursor: watch::Sender<u64>,
+}
+
+enum Exit {
+    Shutdown,
+    Reconcile,
+}
+
+enum StreamEvent {
+    Update(RouteStreamResV1),
+    Settled,
+    Closed,
+}
+
+enum SkfEvent {
+    Update(SkfStreamResV1),
+    Closed,
+}
+
+impl RouteSync {
+    pub async fn from_settings(settings: &Settings) -> Result<(RouteReader, Self)> {
+        let config = &settings.config_client;
+        let channel = Endpoint::from(config.url.clone())
+            .connect_timeout(config.connect_timeout)
+            .connect_lazy();
+        let store = load(settings).await;
+        let (cursor_tx, cursor_rx) = watch::channel(store.cursor());
+        let store = Arc::new(RwLock::new(store));
+        Ok((
+            RouteReader {
+                store: store.clone(),
+                cursor: cursor_rx,
+            },
+            Self {
+                client: RouteClient::new(channel.clone()),
+                skf_client: SkfStreamClient::new(channel),
+                signing_key: config.signing_keypair().map_err(|err| *err)?,
+                config_pubkey: config.config_pubkey()?,
+                settings: settings.clone(),
+                store,
+                cursor: cursor_tx,
+            },
+        ))
+    }
+
+    pub async fn run(mut self, shutdown: &triggered::Listener) -> Result {
+        tracing::info!("starting route sync");
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match self.sync(shutdown).await {
+                Ok(Exit::Shutdown) => break,
+                Ok(Exit::Reconcile) => {
+                    tracing::info!("reopening route stream to reconcile");
+                    backoff = INITIAL_BACKOFF;
+                }
+                Err(err) => {
+                    tracing::warn!(?err, "route stream failed, reconnecting in {backoff:?}");
+                    tokio::select! {
+                        _ = shutdown.clone() => break,
+                        _ = time::sleep(backoff) => (),
+                    }
+                    backoff = (backoff * 2).min(self.settings.max_backoff);
+                }
+            }
+        }
+        tracing::info!("stopping route sync");
+        self.persist().await
+    }
+
+    /// Follow the streams until they end. A route stream starts with a
+    /// snapshot of all routes, eui pairs and devaddr ranges, collected into a
+    /// fresh store swapped in once the stream settles, so removals missed
+    /// while disconnected are reconciled without a partially populated copy.
+    /// A snapshot without any message settles after the longer
+    /// `empty_snapshot_timeout`. The skf stream resumes after the version of
+    /// the local copy, only sending a snapshot when it can't. Updates are
+    /// applied before the next one is requested, so a slow consumer holds
+    /// back the server, which closes its stream once too far behind
+    async fn sync(&mut self, shutdown: &triggered::Listener) -> Result<Exit> {
+        let mut stream = self.subscribe().await?;
+        let mut skf_stream = self.subscribe_skfs().await?;
+        let mut snapshot = Some(RouteStore::default());
+        let mut skf_snapshot = None;
+        let mut last_message = time::Instant::now();
+        let mut dirty = false;
+
+        let reconcile = time::sleep(self.settings.reconcile_interval);
+        tokio::pin!(reconcile);
+        let mut persist = time::interval(self.settings.persist_interval);
+        persist.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+        loop {
+            // a snapshot settles after a gap between its messages, or a
+            // longer one before its first so a slow server doesn't swap in
+            // an empty copy
+            let settle = snapshot.as_ref().map(|snapshot| {
+                let gap = if snapshot.cursor() > 0 {
+                    self.settings.snapshot_settle
+                } else {
+                    self.settings.empty_snapshot_timeout
+                };
+                last_message + gap
+            });
+            tokio::select! {
+                _ = shutdown.clone() => return Ok(Exit::Shutdown),
+                _ = &mut reconcile => return Ok(Exit::Reconcile),
+                _ = persist.tick(), if dirty => {
+                    self.persist().await?;
+                    dirty = false;
+                }
+                event = next_event(&mut stream, settle) => match event? {
+                    StreamEvent::Update(update) => {
+                        update.verify(&self.config_pubkey)?;
+                        last_message = time::Instant::now();
+                        // session key filters are synced from the skf stream
+                        if matches!(update.data, Some(Data::Skf(_))) {
+                            continue;
+                        }
+                        match snapshot.as_mut() {
+                            Some(snapshot) => snapshot.apply(&update),
+                            None => {
+                                self.store.write().await.apply(&update);
+                                self.publish().await;
+                                dirty = true;
+                            }
+                        }
+                    }
+                    StreamEvent::Settled => {
+                        let mut snapshot = snapshot.take().unwrap_or_default();
+                        tracing::info!(
+                            routes = snapshot.route_count(),
+                            "route snapshot received"
+                        );
+                        let mut store = self.store.write().await;
+                        snapshot.take_skfs(&mut store);
+                        *store = snapshot;
+                        drop(store);
+                        self.publish().await;
+                        dirty = true;
+                    }
+                    StreamEvent::Closed => return Err(Error::StreamClosed),
+                },
+                event = next_skf_event(&mut skf_stream) => match event? {
+                    SkfEvent::Update(update) => {
+                        update.verify(&self.config_pubkey)?;
+                        if self.apply_skfs(update, &mut skf_snapshot).await {
+                            self.publish().await;
+                            dirty = true;
+                        }
+                    }
+                    SkfEvent::Closed => return Err(Error::StreamClosed),
+                },
+            }
+        }
+    }
+
+    /// Apply a message of the skf stream, collecting a snapshot until its
+    /// end. Whether the local copy changed
+    async fn apply_skfs(
+        &self,
+        update: SkfStreamResV1,
+        skf_snapshot: &mut Option<RouteStore>,
+    ) -> bool {
+        let kind = SkfStreamKindV1::from_i32(update.kind);
+        let skfs = update.changes.iter().filter_map(|change| {
+            let add = change.action() == ActionV1::Add;
+            change.skf.as_ref().map(|skf| (add, skf))
+        });
+        match kind {
+            Some(SkfStreamKindV1::Snapshot) => {
+                let snapshot = skf_snapshot.get_or_insert_with(RouteStore::default);
+                skfs.for_each(|(add, skf)| snapshot.apply_skf(add, skf));
+                false
+            }
+            Some(SkfStreamKindV1::SnapshotEnd) => {
+                let mut snapshot = skf_snapshot.take().unwrap_or_default();
+                snapshot.set_skf_version(update.version);
+                tracing::info!(
+                    skfs = snapshot.skf_count(),
+                    version = update.version,
+                    "skf snapshot received"
+                );
+                self.store.write().await.take_skfs(&mut snapshot);
+                true
+            }
+            Some(SkfStreamKindV1::Delta) => {
+                let mut store = self.store.write().await;
+                skfs.for_each(|(add, skf)| store.apply_skf(add, skf));
+                store.set_skf_version(update.version);
+                true
+            }
+            None => {
+                tracing::warn!(
+                    kind = update.kind,
+                    "ignoring skf stream message of unknown kind"
+                );
+                false
+            }
+        }
+    }
+
+    async fn subscribe(&mut self) -> Result<Streaming<RouteStreamResV1>> {
+        let mut request = RouteStreamReqV1 {
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        Ok(self.client.stream(request).await?.into_inner())
+    }
+
+    async fn subscribe_skfs(&mut self) -> Result<Streaming<SkfStreamResV1>> {
+        let mut request = SkfStreamReqV1 {
+            since_version: self.store.read().await.skf_version(),
+            timestamp: Utc::now().encode_timestamp(),
+            signer: self.signing_key.public_key().into(),
+            signature: vec![],
+        };
+        request.signature = self.signing_key.sign(&request.encode_to_vec())?;
+        Ok(self.skf_client.stream(request).await?.into_inner())
+    }
+
+    async fn publish(&self) {
+        let store = self.store.read().await;
+        metrics::gauge!(ROUTES_GAUGE, store.route_count() as f64);
+        metrics::gauge!(SKFS_GAUGE, store.skf_count() as f64);
+        metrics::gauge!(CURSOR_GAUGE, store.cursor() as f64);
+        metrics::gauge!(SKF_VERSION_GAUGE, store.skf_version() as f64);
+        self.cursor.send_replace(store.cursor());
+    }
+
+    async fn persist(&self) -> Result {
+        let bytes = self.store.read().await.encode();
+        let tmp_path = self.settings.cache.with_extension("tmp");
+        tokio::fs::write(&tmp_path, bytes).await?;
+        tokio::fs::rename(&tmp_path, &self.settings.cache).await?;
+        Ok(())
+    }
+}
+
+async fn load(settings: &Settings) -> RouteStore {
+    let bytes = match tokio::fs::read(&settings.cache).await {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return RouteStore::default(),
+        Err(err) => {
+            tracing::warn!(?err, "failed to read persisted routes, starting empty");
+            return RouteStore::default();
+        }
+    };
+    match RouteStore::decode(&bytes) {
+        Ok(store) => {
+            tracing::info!(
+                routes = store.route_count(),
+                cursor = store.cursor(),
+                "restored persisted routes"
+            );
+            store
+        }
+        Err(err) => {
+            tracing::warn!(?err, "failed to decode persisted routes, starting empty");
+            RouteStore::default()
+        }
+    }
+}
+
+/// Next message on the stream. While a snapshot is being received, reaching
+/// the `settle` deadline without a message marks the end of the snapshot.
+async fn next_event(
+    stream: &mut Streaming<RouteStreamResV1>,
+    settle: Option<time::Instant>,
+) -> Result<StreamEvent> {
+    let message = match settle {
+        Some(settle) => match time::timeout_at(settle, stream.message()).await {
+            Ok(message) => message?,
+            Err(_) => return Ok(StreamEvent::Settled),
+        },
+        None => stream.message().await?,
+    };
+    Ok(message.map_or(StreamEvent::Closed, StreamEvent::Update))
+}
+
+async fn next_skf_event(stream: &mut Streaming<SkfStreamResV1>) -> Result<SkfEvent> {
+    let message = stream.message().await?;
+    Ok(message.map_or(SkfEvent::Closed, SkfEvent::Update))
+}
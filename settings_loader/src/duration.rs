@@ -0,0 +1,100 @@
+//! Serde helpers for duration settings, given as a plain number of seconds or
+//! a humantime string such as `"30s"` or `"1h 30m"`.
+
+use serde::{de, Deserializer, Serializer};
+use std::{fmt, time::Duration};
+
+/// For use with `#[serde(with = "settings_loader::duration")]`. Intervals of
+/// timers, which panic or spin on zero, use [`positive`]
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&humantime::format_duration(*duration).to_string())
+}
+
+/// Like the parent module, refusing zero durations
+pub mod positive {
+    use serde::{de, Deserializer};
+    use std::time::Duration;
+
+    pub use super::serialize;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let duration = super::deserialize(deserializer)?;
+        if duration.is_zero() {
+            return Err(de::Error::custom("duration must be positive"));
+        }
+        Ok(duration)
+    }
+}
+
+struct DurationVisitor;
+
+impl<'de> de::Visitor<'de> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of seconds or a duration string like \"5m\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+        u64::try_from(v)
+            .map(Duration::from_secs)
+            .map_err(|_| E::custom(format!("negative duration: {v}")))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        // Environment overrides always arrive as strings, so accept bare
+        // integers here as seconds too.
+        if let Ok(secs) = v.trim().parse::<u64>() {
+            return Ok(Duration::from_secs(secs));
+        }
+        humantime::parse_duration(v).map_err(|err| E::custom(format!("{v:?}: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::{value, IntoDeserializer};
+
+    fn parse_str(v: &str) -> Result<Duration, value::Error> {
+        deserialize(IntoDeserializer::<value::Error>::into_deserializer(v))
+    }
+
+    #[test]
+    fn parses_seconds_and_humantime() {
+        assert_eq!(
+            Duration::from_secs(30),
+            deserialize(IntoDeserializer::<value::Error>::into_deserializer(30u64)).unwrap()
+        );
+        assert_eq!(Duration::from_secs(45), parse_str("45").unwrap());
+        assert_eq!(Duration::from_secs(300), parse_str("5m").unwrap());
+        assert_eq!(Duration::from_secs(5400), parse_str("1h 30m").unwrap());
+        assert!(parse_str("soon").is_err());
+    }
+
+    #[test]
+    fn positive_refuses_zero() {
+        let parse =
+            |v: &str| positive::deserialize(IntoDeserializer::<value::Error>::into_deserializer(v));
+        assert!(parse("0").is_err());
+        assert!(parse("0s").is_err());
+        assert_eq!(Duration::from_secs(60), parse("1m").unwrap());
+    }
+}
@@ -0,0 +1,63 @@
+use config::ConfigError;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T = ()> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("settings file not found: {0}")]
+    FileNotFound(PathBuf),
+    #[error("missing required setting `{0}`")]
+    Missing(String),
+    #[error("invalid value for setting `{key}`: {reason}")]
+    Invalid { key: String, reason: String },
+    #[error("settings error: {0}")]
+    Config(ConfigError),
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::NotFound(key) => Self::Missing(key),
+            ConfigError::Type {
+                key: Some(key),
+                unexpected,
+                expected,
+                ..
+            } => Self::Invalid {
+                key,
+                reason: format!("expected {expected}, found {unexpected}"),
+            },
+            ConfigError::Message(msg) => match missing_field(&msg) {
+                Some(key) => Self::Missing(key),
+                None => Self::Config(ConfigError::Message(msg)),
+            },
+            other => Self::Config(other),
+        }
+    }
+}
+
+/// Extract the field name from a serde "missing field `name`" message so
+/// that the error names the offending key rather than only the struct.
+fn missing_field(msg: &str) -> Option<String> {
+    let rest = msg.strip_prefix("missing field `")?;
+    rest.split_once('`').map(|(field, _)| field.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_field_names_key() {
+        let err = Error::from(ConfigError::Message("missing field `database`".to_string()));
+        assert!(matches!(err, Error::Missing(key) if key == "database"));
+    }
+
+    #[test]
+    fn other_messages_pass_through() {
+        let err = Error::from(ConfigError::Message("boom".to_string()));
+        assert!(matches!(err, Error::Config(_)));
+    }
+}
@@ -0,0 +1,172 @@
+//! Layered settings loading shared by all oracle binaries.
+
+pub mod duration;
+mod error;
+
+pub use error::{Error, Result};
+
+use config::{Config, Environment, File};
+use helium_crypto::Keypair;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+/// Profiles settings are considered production settings with
+const PRODUCTION_PROFILES: [&str; 3] = ["prod", "production", "mainnet"];
+
+/// Load settings of type `T` from an optional file and the environment
+/// using the given environment prefix and `_` as the key separator.
+pub fn load<T, P>(path: Option<P>, env_prefix: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    Loader::new(env_prefix).file(path).load()
+}
+
+/// Read the keypair file a setting points at, e.g. the keypair a service
+/// signs its responses with
+pub fn keypair<P: AsRef<Path>>(path: P) -> std::result::Result<Keypair, helium_crypto::Error> {
+    let data = std::fs::read(path)?;
+    Keypair::try_from(&data[..])
+}
+
+/// Resolves settings from the following layers, each one overriding the ones
+/// before it:
+///
+/// 1. defaults declared on the settings struct with `#[serde(default)]`
+/// 2. the optional TOML settings file given on the command line
+/// 3. an optional profile file next to it, named `<file>.<profile>.toml`,
+///    where the profile is read from the `<PREFIX>_PROFILE` environment
+///    variable (e.g. `VERIFY_PROFILE=staging` loads `settings.staging.toml`)
+/// 4. environment variables with the given prefix, e.g. `VERIFY_LOG`
+///
+/// Errors name the offending key where possible.
+#[derive(Debug, Clone)]
+pub struct Loader {
+    env_prefix: String,
+    env_separator: String,
+    file: Option<PathBuf>,
+    file_required: bool,
+}
+
+impl Loader {
+    pub fn new(env_prefix: &str) -> Self {
+        Self {
+            env_prefix: env_prefix.to_string(),
+            env_separator: "_".to_string(),
+            file: None,
+            file_required: false,
+        }
+    }
+
+    /// Separator used to split environment variable names into nested keys.
+    /// Defaults to `_`.
+    pub fn env_separator(mut self, separator: &str) -> Self {
+        self.env_separator = separator.to_string();
+        self
+    }
+
+    /// Settings file to load. Missing files are ignored unless `required`
+    /// is set.
+    pub fn file<P: AsRef<Path>>(mut self, path: Option<P>) -> Self {
+        self.file = path.map(|p| p.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.file_required = required;
+        self
+    }
+
+    /// Name of the active profile, if any, as read from the environment.
+    pub fn profile(&self) -> Option<String> {
+        std::env::var(format!("{}_PROFILE", self.env_prefix))
+            .ok()
+            .filter(|profile| !profile.is_empty())
+    }
+
+    /// Whether the settings are production settings, either because no
+    /// profile is active or because the active one is `prod`, `production`
+    /// or `mainnet`. Settings only meant for test deployments should be
+    /// refused in production.
+    pub fn is_production(&self) -> bool {
+        self.profile().map_or(true, |profile| {
+            PRODUCTION_PROFILES.contains(&profile.to_lowercase().as_str())
+        })
+    }
+
+    pub fn load<T: DeserializeOwned>(self) -> Result<T> {
+        let mut builder = Config::builder();
+
+        if let Some(file) = &self.file {
+            if self.file_required && !file.exists() {
+                return Err(Error::FileNotFound(file.clone()));
+            }
+            builder = builder.add_source(File::with_name(&file.to_string_lossy()).required(false));
+
+            if let Some(profile) = self.profile() {
+                let profile_file = profile_path(file, &profile);
+                if !profile_file.exists() {
+                    return Err(Error::FileNotFound(profile_file));
+                }
+                builder = builder.add_source(File::with_name(&profile_file.to_string_lossy()));
+            }
+        }
+
+        builder
+            .add_source(Environment::with_prefix(&self.env_prefix).separator(&self.env_separator))
+            .build()?
+            .try_deserialize()
+            .map_err(Error::from)
+    }
+}
+
+/// Path of the profile overlay for a settings file, e.g. `settings.toml`
+/// with profile `staging` becomes `settings.staging.toml`.
+fn profile_path(file: &Path, profile: &str) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match file.extension() {
+        Some(ext) => format!("{stem}.{profile}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{profile}.toml"),
+    };
+    file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn production_without_a_test_profile() {
+        // each case uses its own prefix, tests share the environment
+        let loader = |prefix: &str, profile: Option<&str>| {
+            if let Some(profile) = profile {
+                std::env::set_var(format!("{prefix}_PROFILE"), profile);
+            }
+            Loader::new(prefix)
+        };
+        assert!(loader("SETTINGS_TEST_NONE", None).is_production());
+        assert!(loader("SETTINGS_TEST_EMPTY", Some("")).is_production());
+        assert!(loader("SETTINGS_TEST_MAINNET", Some("MainNet")).is_production());
+        assert!(!loader("SETTINGS_TEST_STAGING", Some("staging")).is_production());
+        assert_eq!(
+            Some("staging".to_string()),
+            loader("SETTINGS_TEST_STAGING", None).profile()
+        );
+    }
+
+    #[test]
+    fn profile_path_keeps_extension() {
+        assert_eq!(
+            PathBuf::from("/etc/verifier/settings.staging.toml"),
+            profile_path(Path::new("/etc/verifier/settings.toml"), "staging")
+        );
+        assert_eq!(
+            PathBuf::from("settings.dev.toml"),
+            profile_path(Path::new("settings"), "dev")
+        );
+    }
+}
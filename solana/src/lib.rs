@@ -1,4 +1,5 @@
 pub mod balance_monitor;
+pub mod payer_subscriptions;
 
 use anchor_client::{RequestBuilder, RequestNamespace};
 use anchor_lang::AccountDeserialize;
@@ -8,12 +9,16 @@ use helium_crypto::PublicKeyBinary;
 use helium_sub_daos::{DaoV0, SubDaoV0};
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_client::{
+    client_error::ClientError,
+    nonblocking::{pubsub_client::PubsubClientError, rpc_client::RpcClient},
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::Hash,
     program_pack::Pack,
     pubkey::{ParsePubkeyError, Pubkey},
-    signature::{read_keypair_file, Keypair},
+    signature::{read_keypair_file, Keypair, ParseSignatureError, Signature},
     signer::Signer,
     transaction::Transaction,
 };
@@ -36,37 +41,96 @@ pub trait SolanaNetwork: Send + Sync + 'static {
         payer: &PublicKeyBinary,
         amount: u64,
     ) -> Result<(), Self::Error>;
+
+    /// Sign a transaction burning `amount` DC of `payer`, to be recorded
+    /// before it is sent
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error>;
+
+    /// Send a signed burn transaction without waiting for its confirmation
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error>;
+
+    async fn burn_status(&self, burn: &BurnTransaction) -> Result<BurnStatus, Self::Error>;
+}
+
+/// Burn transaction tracked from being signed to its finalization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BurnTransaction {
+    pub signature: String,
+    /// DC burned by the transaction
+    pub amount: u64,
+    /// Block height after which the transaction can no longer land
+    pub last_valid_block_height: u64,
+}
+
+/// Burn transaction signed but not sent yet
+pub struct SignedBurn {
+    pub burn: BurnTransaction,
+    payer: PublicKeyBinary,
+    transaction: Transaction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BurnStatus {
+    /// Not finalized yet, but may still be
+    Pending,
+    Finalized,
+    /// Landed with an error, burning nothing
+    Failed,
+    /// Did not land before its blockhash expired and never will
+    Expired,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum SolanaRpcError {
     #[error("Solana rpc error: {0}")]
     RpcClientError(#[from] ClientError),
+    #[error("Solana pubsub error: {0}")]
+    PubsubClientError(#[from] PubsubClientError),
     #[error("Anchor error: {0}")]
     AnchorError(#[from] anchor_lang::error::Error),
     #[error("Solana program error: {0}")]
     ProgramError(#[from] solana_sdk::program_error::ProgramError),
     #[error("Parse pubkey error: {0}")]
     ParsePubkeyError(#[from] ParsePubkeyError),
+    #[error("Parse signature error: {0}")]
+    ParseSignatureError(#[from] ParseSignatureError),
     #[error("DC burn authority does not match keypair")]
     InvalidKeypair,
     #[error("System time error: {0}")]
     SystemTimeError(#[from] SystemTimeError),
     #[error("Failed to read keypair file")]
     FailedToReadKeypairError,
+    #[error("Missing solana settings")]
+    MissingSettings,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     rpc_url: String,
+    /// Websocket of the rpc node for account subscriptions, derived from
+    /// `rpc_url` when not set
+    ws_url: Option<String>,
     cluster: String,
     burn_keypair: String,
     dc_mint: String,
     dnt_mint: String,
 }
 
+impl Settings {
+    fn ws_url(&self) -> String {
+        self.ws_url
+            .clone()
+            .unwrap_or_else(|| self.rpc_url.replacen("http", "ws", 1))
+    }
+}
+
 pub struct SolanaRpc {
     provider: RpcClient,
+    ws_url: String,
     program_cache: BurnProgramCache,
     cluster: String,
     keypair: [u8; 64],
@@ -88,36 +152,39 @@ impl SolanaRpc {
         Ok(Arc::new(Self {
             cluster: settings.cluster.clone(),
             provider,
+            ws_url: settings.ws_url(),
             program_cache,
             keypair: keypair.to_bytes(),
         }))
     }
-}
 
-#[async_trait]
-impl SolanaNetwork for SolanaRpc {
-    type Error = SolanaRpcError;
+    /// Connect to solana if the integration is enabled, otherwise run in
+    /// shadow mode where every payer has a fixed balance and burns are only
+    /// logged. Shared by the packet verifiers so they debit and burn alike.
+    pub async fn shadowable(
+        enabled: bool,
+        settings: Option<&Settings>,
+    ) -> Result<Option<Arc<Self>>, SolanaRpcError> {
+        if !enabled {
+            tracing::info!("solana integration disabled, running in shadow mode");
+            return Ok(None);
+        }
+        let settings = settings.ok_or(SolanaRpcError::MissingSettings)?;
+        Ok(Some(Self::new(settings).await?))
+    }
 
-    async fn payer_balance(&self, payer: &PublicKeyBinary) -> Result<u64, Self::Error> {
-        let ddc_key = delegated_data_credits(&self.program_cache.sub_dao, payer);
-        let (escrow_account, _) = Pubkey::find_program_address(
-            &["escrow_dc_account".as_bytes(), &ddc_key.to_bytes()],
-            &data_credits::ID,
-        );
-        let Ok(account_data) = self.provider.get_account_data(&escrow_account).await else {
-            // If the account is empty, it has no DC
-            tracing::info!(%payer, "Account not found, therefore no balance");
-            return Ok(0);
-        };
-        let account_layout = spl_token::state::Account::unpack(account_data.as_slice())?;
-        Ok(account_layout.amount)
+    /// Check the rpc node reports itself healthy, for preflight checks
+    pub async fn check_health(&self) -> Result<(), SolanaRpcError> {
+        Ok(self.provider.get_health().await?)
     }
 
-    async fn burn_data_credits(
+    /// Transaction burning `amount` DC of `payer`, signed with the burn keypair
+    fn burn_transaction(
         &self,
         payer: &PublicKeyBinary,
         amount: u64,
-    ) -> Result<(), Self::Error> {
+        blockhash: Hash,
+    ) -> Result<Transaction, SolanaRpcError> {
         // Fetch the sub dao epoch info:
         const EPOCH_LENGTH: u64 = 60 * 60 * 24;
         let epoch = SystemTime::now()
@@ -134,11 +201,7 @@ impl SolanaNetwork for SolanaRpc {
         );
 
         // Fetch escrow account
-        let ddc_key = delegated_data_credits(&self.program_cache.sub_dao, payer);
-        let (escrow_account, _) = Pubkey::find_program_address(
-            &["escrow_dc_account".as_bytes(), &ddc_key.to_bytes()],
-            &data_credits::ID,
-        );
+        let escrow_account = escrow_dc_account(&self.program_cache.sub_dao, payer);
 
         let instructions = {
             let request = RequestBuilder::from(
@@ -177,15 +240,38 @@ impl SolanaNetwork for SolanaRpc {
                 .unwrap()
         };
 
-        let blockhash = self.provider.get_latest_blockhash().await?;
         let signer = Keypair::from_bytes(&self.keypair).unwrap();
-
-        let tx = Transaction::new_signed_with_payer(
+        Ok(Transaction::new_signed_with_payer(
             &instructions,
             Some(&signer.pubkey()),
             &[&signer],
             blockhash,
-        );
+        ))
+    }
+}
+
+#[async_trait]
+impl SolanaNetwork for SolanaRpc {
+    type Error = SolanaRpcError;
+
+    async fn payer_balance(&self, payer: &PublicKeyBinary) -> Result<u64, Self::Error> {
+        let escrow_account = escrow_dc_account(&self.program_cache.sub_dao, payer);
+        let Ok(account_data) = self.provider.get_account_data(&escrow_account).await else {
+            // If the account is empty, it has no DC
+            tracing::info!(%payer, "Account not found, therefore no balance");
+            return Ok(0);
+        };
+        let account_layout = spl_token::state::Account::unpack(account_data.as_slice())?;
+        Ok(account_layout.amount)
+    }
+
+    async fn burn_data_credits(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<(), Self::Error> {
+        let blockhash = self.provider.get_latest_blockhash().await?;
+        let tx = self.burn_transaction(payer, amount, blockhash)?;
 
         let signature = self.provider.send_and_confirm_transaction(&tx).await?;
 
@@ -196,6 +282,99 @@ impl SolanaNetwork for SolanaRpc {
 
         Ok(())
     }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error> {
+        let (blockhash, last_valid_block_height) = self
+            .provider
+            .get_latest_blockhash_with_commitment(self.provider.commitment())
+            .await?;
+        let transaction = self.burn_transaction(payer, amount, blockhash)?;
+        Ok(SignedBurn {
+            burn: BurnTransaction {
+                signature: transaction.signatures[0].to_string(),
+                amount,
+                last_valid_block_height,
+            },
+            payer: payer.clone(),
+            transaction,
+        })
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error> {
+        self.provider.send_transaction(&burn.transaction).await?;
+        tracing::info!(
+            payer = %burn.payer,
+            transaction = %burn.burn.signature,
+            "Sent data credit burn",
+        );
+        Ok(())
+    }
+
+    async fn burn_status(&self, burn: &BurnTransaction) -> Result<BurnStatus, Self::Error> {
+        burn_status(&self.provider, burn).await
+    }
+}
+
+/// The reads the status of a burn is decided from
+#[async_trait]
+trait BurnLedger: Sync {
+    async fn block_height(&self) -> Result<u64, SolanaRpcError>;
+
+    /// The status of the transaction with `signature`, if it landed
+    async fn landed_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<BurnStatus>, SolanaRpcError>;
+}
+
+#[async_trait]
+impl BurnLedger for RpcClient {
+    async fn block_height(&self) -> Result<u64, SolanaRpcError> {
+        Ok(self.get_block_height().await?)
+    }
+
+    async fn landed_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<BurnStatus>, SolanaRpcError> {
+        // Searching the history as well, a burn tracked across a restart may
+        // have landed long ago
+        let status = self
+            .get_signature_statuses_with_history(&[*signature])
+            .await?
+            .value
+            .pop()
+            .flatten();
+        Ok(status.map(|status| {
+            if status.err.is_some() {
+                BurnStatus::Failed
+            } else if status.satisfies_commitment(CommitmentConfig::finalized()) {
+                BurnStatus::Finalized
+            } else {
+                BurnStatus::Pending
+            }
+        }))
+    }
+}
+
+/// The status of `burn`. The block height is read before the signature
+/// status, a burn landing in between is otherwise missed at a height already
+/// past its last valid block and taken as expired, to be burned again
+async fn burn_status(
+    ledger: &impl BurnLedger,
+    burn: &BurnTransaction,
+) -> Result<BurnStatus, SolanaRpcError> {
+    let signature: Signature = burn.signature.parse()?;
+    let block_height = ledger.block_height().await?;
+    Ok(match ledger.landed_status(&signature).await? {
+        Some(status) => status,
+        None if block_height > burn.last_valid_block_height => BurnStatus::Expired,
+        None => BurnStatus::Pending,
+    })
 }
 
 /// Cached pubkeys for the burn program
@@ -268,9 +447,43 @@ impl SolanaNetwork for Option<Arc<SolanaRpc>> {
         if let Some(ref rpc) = self {
             rpc.burn_data_credits(payer, amount).await
         } else {
+            tracing::info!(%payer, %amount, "shadow mode, not burning DC");
+            Ok(())
+        }
+    }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error> {
+        if let Some(ref rpc) = self {
+            rpc.sign_burn(payer, amount).await
+        } else {
+            Ok(SignedBurn::unsigned(payer, amount))
+        }
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error> {
+        if let Some(ref rpc) = self {
+            rpc.send_burn(burn).await
+        } else {
+            tracing::info!(
+                payer = %burn.payer,
+                amount = %burn.burn.amount,
+                "shadow mode, not burning DC"
+            );
             Ok(())
         }
     }
+
+    async fn burn_status(&self, burn: &BurnTransaction) -> Result<BurnStatus, Self::Error> {
+        if let Some(ref rpc) = self {
+            rpc.burn_status(burn).await
+        } else {
+            Ok(BurnStatus::Finalized)
+        }
+    }
 }
 
 #[async_trait]
@@ -289,6 +502,53 @@ impl SolanaNetwork for Arc<Mutex<HashMap<PublicKeyBinary, u64>>> {
         *self.lock().await.get_mut(payer).unwrap() -= amount;
         Ok(())
     }
+
+    async fn sign_burn(
+        &self,
+        payer: &PublicKeyBinary,
+        amount: u64,
+    ) -> Result<SignedBurn, Self::Error> {
+        Ok(SignedBurn::unsigned(payer, amount))
+    }
+
+    async fn send_burn(&self, burn: &SignedBurn) -> Result<(), Self::Error> {
+        *self.lock().await.get_mut(&burn.payer).unwrap() -= burn.burn.amount;
+        Ok(())
+    }
+
+    async fn burn_status(&self, _burn: &BurnTransaction) -> Result<BurnStatus, Self::Error> {
+        Ok(BurnStatus::Finalized)
+    }
+}
+
+impl SignedBurn {
+    /// Burn of a network not sending transactions, like a mock, tracked by
+    /// the signature of `burn` if it has one
+    pub fn unsent(payer: &PublicKeyBinary, burn: BurnTransaction) -> Self {
+        Self {
+            burn,
+            payer: payer.clone(),
+            transaction: Transaction::default(),
+        }
+    }
+
+    /// Burn without a transaction, for when not burning on chain
+    fn unsigned(payer: &PublicKeyBinary, amount: u64) -> Self {
+        Self::unsent(
+            payer,
+            BurnTransaction {
+                signature: String::new(),
+                amount,
+                last_valid_block_height: 0,
+            },
+        )
+    }
+
+    /// Whether the burn has a signature to track it by. Unsigned burns are
+    /// never recorded, there is no transaction to land.
+    pub fn is_signed(&self) -> bool {
+        !self.burn.signature.is_empty()
+    }
 }
 
 /// Returns the PDA for the Delegated Data Credits of the given `payer`.
@@ -306,3 +566,85 @@ pub fn delegated_data_credits(sub_dao: &Pubkey, payer: &PublicKeyBinary) -> Pubk
     );
     ddc_key
 }
+
+/// Returns the PDA for the DC escrow account of the given `payer`.
+pub fn escrow_dc_account(sub_dao: &Pubkey, payer: &PublicKeyBinary) -> Pubkey {
+    let ddc_key = delegated_data_credits(sub_dao, payer);
+    let (escrow_account, _) = Pubkey::find_program_address(
+        &["escrow_dc_account".as_bytes(), &ddc_key.to_bytes()],
+        &data_credits::ID,
+    );
+    escrow_account
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Ledger on which the burn lands as soon as the block height is read,
+    /// at a height past the last valid block of the burn
+    #[derive(Default)]
+    struct LandingLedger {
+        landed: AtomicBool,
+    }
+
+    #[async_trait]
+    impl BurnLedger for LandingLedger {
+        async fn block_height(&self) -> Result<u64, SolanaRpcError> {
+            self.landed.store(true, Ordering::SeqCst);
+            Ok(200)
+        }
+
+        async fn landed_status(
+            &self,
+            _signature: &Signature,
+        ) -> Result<Option<BurnStatus>, SolanaRpcError> {
+            Ok(self
+                .landed
+                .load(Ordering::SeqCst)
+                .then_some(BurnStatus::Finalized))
+        }
+    }
+
+    /// Ledger on which the burn never lands
+    struct EmptyLedger(u64);
+
+    #[async_trait]
+    impl BurnLedger for EmptyLedger {
+        async fn block_height(&self) -> Result<u64, SolanaRpcError> {
+            Ok(self.0)
+        }
+
+        async fn landed_status(
+            &self,
+            _signature: &Signature,
+        ) -> Result<Option<BurnStatus>, SolanaRpcError> {
+            Ok(None)
+        }
+    }
+
+    fn burn() -> BurnTransaction {
+        BurnTransaction {
+            signature: Signature::default().to_string(),
+            amount: 1_000,
+            last_valid_block_height: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn burns_landing_between_the_reads_are_not_expired() {
+        let status = burn_status(&LandingLedger::default(), &burn())
+            .await
+            .unwrap();
+        assert_eq!(BurnStatus::Finalized, status);
+    }
+
+    #[tokio::test]
+    async fn missing_burns_expire_past_their_last_valid_block() {
+        let pending = burn_status(&EmptyLedger(100), &burn()).await.unwrap();
+        assert_eq!(BurnStatus::Pending, pending);
+        let expired = burn_status(&EmptyLedger(101), &burn()).await.unwrap();
+        assert_eq!(BurnStatus::Expired, expired);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{escrow_dc_account, SolanaNetwork, SolanaRpc, SolanaRpcError};
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use helium_crypto::PublicKeyBinary;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey,
+};
+
+/// Networks pushing the balances of payers as they change
+#[async_trait]
+pub trait SubscriptionNetwork: SolanaNetwork {
+    type Subscriptions: Subscribe<Error = Self::Error>;
+
+    /// Open a connection payers are subscribed to over
+    async fn payer_subscriptions(&self) -> Result<Self::Subscriptions, Self::Error>;
+}
+
+/// A connection payers are subscribed to over
+#[async_trait]
+pub trait Subscribe: Send + Sync {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Subscribe to the balance of `payer`, yielding it every time it
+    /// changes. The stream ends when the connection is lost.
+    async fn subscribe(&self, payer: &PublicKeyBinary) -> Result<BoxStream<'_, u64>, Self::Error>;
+}
+
+/// Websocket connection to the rpc node pushing the balances of payers
+pub struct PayerSubscriptions {
+    client: PubsubClient,
+    sub_dao: Pubkey,
+}
+
+#[async_trait]
+impl SubscriptionNetwork for SolanaRpc {
+    type Subscriptions = PayerSubscriptions;
+
+    async fn payer_subscriptions(&self) -> Result<PayerSubscriptions, SolanaRpcError> {
+        Ok(PayerSubscriptions {
+            client: PubsubClient::new(&self.ws_url).await?,
+            sub_dao: self.program_cache.sub_dao,
+        })
+    }
+}
+
+#[async_trait]
+impl Subscribe for PayerSubscriptions {
+    type Error = SolanaRpcError;
+
+    /// Subscribe to the DC escrow account of `payer`, yielding its balance
+    /// every time the account changes. The stream ends when the connection to
+    /// the rpc node is lost.
+    async fn subscribe(
+        &self,
+        payer: &PublicKeyBinary,
+    ) -> Result<BoxStream<'_, u64>, SolanaRpcError> {
+        let escrow_account = escrow_dc_account(&self.sub_dao, payer);
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::finalized()),
+            ..Default::default()
+        };
+        let (updates, _unsubscribe) = self
+            .client
+            .account_subscribe(&escrow_account, Some(config))
+            .await?;
+        Ok(updates.map(|update| escrow_balance(update.value)).boxed())
+    }
+}
+
+fn escrow_balance(account: UiAccount) -> u64 {
+    // A closed escrow account has no DC
+    account
+        .decode::<Account>()
+        .and_then(|account| spl_token::state::Account::unpack(&account.data).ok())
+        .map_or(0, |escrow| escrow.amount)
+}
@@ -0,0 +1,211 @@
+//! Cron expressions of five fields, `minute hour day-of-month month
+//! day-of-week`, or six with a leading `second` field, all in UTC.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use std::{fmt, str::FromStr};
+
+/// How far ahead the next run is looked for before giving up on expressions
+/// that never run, like `0 0 31 2 *`
+const MAX_LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CronError {
+    #[error("expected 5 or 6 fields, found {0}")]
+    FieldCount(usize),
+    #[error("invalid {field} field {value:?}")]
+    Field { field: &'static str, value: String },
+}
+
+/// Each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a
+/// comma separated list of those. Months are numbered 1 to 12 and days of the
+/// week 0 to 7, both 0 and 7 being Sunday; names aren't supported. As in cron,
+/// when both the day of the month and the day of the week are restricted a
+/// day matching either runs
+#[derive(Clone, PartialEq, Eq)]
+pub struct Cron {
+    expression: String,
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Cron {
+    /// The first time strictly after `after` the expression runs at, if any
+    /// within the next five years
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let limit = after + Duration::days(MAX_LOOKAHEAD_DAYS);
+        let mut next = after.with_nanosecond(0)? + Duration::seconds(1);
+        while next < limit {
+            if !contains(self.months, next.month()) {
+                let (year, month) = match next.month() {
+                    12 => (next.year() + 1, 1),
+                    month => (next.year(), month + 1),
+                };
+                next = start_of_day(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.runs_on(next.date_naive()) {
+                next = start_of_day(next.date_naive().succ_opt()?)?;
+            } else if !contains(self.hours, next.hour()) {
+                next = next.with_minute(0)?.with_second(0)? + Duration::hours(1);
+            } else if !contains(self.minutes, next.minute()) {
+                next = next.with_second(0)? + Duration::minutes(1);
+            } else if !contains(self.seconds, next.second()) {
+                next += Duration::seconds(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        let day_of_month = contains(self.days_of_month, date.day());
+        let day_of_week = contains(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let (seconds, rest) = match fields.len() {
+            5 => ("0", fields.as_slice()),
+            6 => (fields[0], &fields[1..]),
+            count => return Err(CronError::FieldCount(count)),
+        };
+        let mut days_of_week = parse_field("day of week", rest[4], 0, 7)?;
+        // 7 is sunday too
+        if contains(days_of_week, 7) {
+            days_of_week |= 1;
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            seconds: parse_field("second", seconds, 0, 59)?,
+            minutes: parse_field("minute", rest[0], 0, 59)?,
+            hours: parse_field("hour", rest[1], 0, 23)?,
+            days_of_month: parse_field("day of month", rest[2], 1, 31)?,
+            months: parse_field("month", rest[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: rest[2] == "*",
+            any_day_of_week: rest[4] == "*",
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl fmt::Debug for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Cron").field(&self.expression).finish()
+    }
+}
+
+fn contains(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Some(DateTime::from_utc(date.and_hms_opt(0, 0, 0)?, Utc))
+}
+
+/// The values of a field as a bit set, bit `n` being set when `n` matches
+fn parse_field(field: &'static str, value: &str, min: u32, max: u32) -> Result<u64, CronError> {
+    let invalid = || CronError::Field {
+        field,
+        value: value.to_string(),
+    };
+    let parse = |n: &str| {
+        n.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(invalid)
+    };
+    let mut set = 0;
+    for part in value.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None if step > 1 => (parse(range)?, max),
+            None => (parse(range)?, parse(range)?),
+        };
+        if step == 0 || start > end {
+            return Err(invalid());
+        }
+        for n in (start..=end).step_by(step as usize) {
+            set |= 1 << n;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn next(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        expression.parse::<Cron>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn finds_the_next_run() {
+        let at = |d, h, m, s| Utc.with_ymd_and_hms(2023, 9, d, h, m, s).unwrap();
+        // friday the 1st, 10:20:30
+        let now = at(1, 10, 20, 30);
+
+        assert_eq!(Some(at(1, 10, 21, 0)), next("* * * * *", now));
+        assert_eq!(Some(at(1, 10, 20, 45)), next("*/15 * * * * *", now));
+        assert_eq!(Some(at(1, 10, 35, 0)), next("5,35 * * * *", now));
+        assert_eq!(Some(at(2, 3, 0, 0)), next("0 3 * * *", now));
+        assert_eq!(Some(at(1, 12, 0, 0)), next("0 */6 * * *", now));
+        assert_eq!(Some(at(3, 0, 0, 0)), next("0 0 * * 0", now));
+        assert_eq!(Some(at(3, 0, 0, 0)), next("0 0 * * 7", now));
+        assert_eq!(Some(at(4, 0, 0, 0)), next("0 0 * * 1-5", now));
+        // either day restriction matching runs
+        assert_eq!(Some(at(3, 0, 0, 0)), next("0 0 15 * 0", now));
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            next("0 0 1 1 *", now)
+        );
+        // never strictly at the given time
+        assert_eq!(
+            Some(at(1, 10, 21, 0)),
+            next("21 10 * * *", at(1, 10, 20, 0))
+        );
+        assert_eq!(None, next("0 0 31 2 *", now));
+    }
+
+    #[test]
+    fn refuses_invalid_expressions() {
+        assert_eq!(Err(CronError::FieldCount(3)), "* * *".parse::<Cron>());
+        for expression in [
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+        ] {
+            assert!(expression.parse::<Cron>().is_err(), "{expression}");
+        }
+        for expression in ["*/0 * * * *", "5-1 * * * *", "a * * * *", "1,,2 * * * *"] {
+            assert!(expression.parse::<Cron>().is_err(), "{expression}");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Scheduling of the periodic tasks of the oracle servers, like purging,
+//! pruning and reconciling, from their settings.
+
+pub mod cron;
+
+pub use crate::cron::{Cron, CronError};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer};
+use std::{fmt, str::FromStr, time::Duration};
+
+const NEXT_RUN_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "-", "next-run");
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("zero duration, which would run continuously")]
+    ZeroDuration,
+    #[error(transparent)]
+    Cron(#[from] CronError),
+}
+
+/// When a task runs. As a setting either a duration between runs, given as
+/// a number of seconds or a humantime string like `"35m"`, or a cron
+/// expression like `"0 3 * * *"`, see [`cron`]. Zero durations, which would
+/// run continuously, are rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// Runs the duration apart, counted from the end of the previous run
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// A schedule of runs `duration` apart, which must not be zero
+    pub fn every(duration: Duration) -> Result<Self, ScheduleError> {
+        if duration.is_zero() {
+            return Err(ScheduleError::ZeroDuration);
+        }
+        Ok(Self::Every(duration))
+    }
+
+    /// The next run after a run ending at `after`. A zero duration built
+    /// directly has no next run rather than running continuously.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Every(duration) if duration.is_zero() => None,
+            Self::Every(duration) => Some(after + chrono::Duration::from_std(*duration).ok()?),
+            Self::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Every(duration) => write!(f, "every {}", humantime::format_duration(*duration)),
+            Self::Cron(cron) => write!(f, "cron {cron}"),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(secs) = s.trim().parse::<u64>() {
+            return Self::every(Duration::from_secs(secs));
+        }
+        if let Ok(duration) = humantime::parse_duration(s) {
+            return Self::every(duration);
+        }
+        Ok(Self::Cron(s.parse()?))
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ScheduleVisitor)
+    }
+}
+
+struct ScheduleVisitor;
+
+impl<'de> de::Visitor<'de> for ScheduleVisitor {
+    type Value = Schedule;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number of seconds, a duration string like \"5m\" or a cron expression")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Schedule, E> {
+        Schedule::every(Duration::from_secs(v)).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Schedule, E> {
+        let secs = u64::try_from(v).map_err(|_| E::custom(format!("negative duration: {v}")))?;
+        self.visit_u64(secs)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Schedule, E> {
+        v.parse()
+            .map_err(|err| E::custom(format!("invalid schedule {v:?}: {err}")))
+    }
+}
+
+/// Ticks on a schedule, like a [`tokio::time::Interval`] whose missed ticks
+/// are delayed. Each run is delayed by up to the jitter so replicas don't run
+/// in lockstep, and its time is exported as a unix timestamp in the
+/// `task_scheduler_next_run` gauge, labelled by task
+pub struct Scheduler {
+    task: &'static str,
+    schedule: Schedule,
+    jitter: Duration,
+    next: Option<DateTime<Utc>>,
+}
+
+impl Scheduler {
+    /// A scheduler of `task` first ticking at the first run of `schedule`
+    pub fn new(task: &'static str, schedule: Schedule, jitter: Duration) -> Self {
+        tracing::info!(task, %schedule, ?jitter, "scheduling task");
+        Self {
+            task,
+            schedule,
+            jitter,
+            next: None,
+        }
+    }
+
+    /// Tick right away the first time, as an interval does
+    pub fn immediately(self) -> Self {
+        Self {
+            next: Some(Utc::now()),
+            ..self
+        }
+    }
+
+    /// Wait for the next run, returning its scheduled time. Dropping the
+    /// returned future keeps the next run, so ticks can be awaited in a
+    /// `select!`. Schedules that never run again never tick.
+    pub async fn tick(&mut self) -> DateTime<Utc> {
+        let next = match self.next {
+            Some(next) => next,
+            None => {
+                let Some(next) = self.schedule.next_after(Utc::now()) else {
+                    tracing::warn!(task = self.task, schedule = %self.schedule, "no next run");
+                    return std::future::pending().await;
+                };
+                let next = next + self.jitter();
+                metrics::gauge!(NEXT_RUN_METRIC, next.timestamp() as f64, "task" => self.task);
+                tracing::debug!(task = self.task, %next, "next run scheduled");
+                self.next = Some(next);
+                next
+            }
+        };
+        if let Ok(wait) = (next - Utc::now()).to_std() {
+            tokio::time::sleep(wait).await;
+        }
+        self.next = None;
+        next
+    }
+
+    fn jitter(&self) -> chrono::Duration {
+        let millis = rand::thread_rng().gen_range(0..=self.jitter.as_millis() as i64);
+        chrono::Duration::milliseconds(millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::{value, IntoDeserializer};
+
+    fn parse_str(v: &str) -> Result<Schedule, value::Error> {
+        Schedule::deserialize(IntoDeserializer::<value::Error>::into_deserializer(v))
+    }
+
+    #[test]
+    fn parses_durations_and_cron_expressions() {
+        let every = |secs| Schedule::Every(Duration::from_secs(secs));
+        assert_eq!(
+            every(30),
+            Schedule::deserialize(IntoDeserializer::<value::Error>::into_deserializer(30u64))
+                .unwrap()
+        );
+        assert_eq!(every(45), parse_str("45").unwrap());
+        assert_eq!(every(2100), parse_str("35m").unwrap());
+        assert_eq!(
+            Schedule::Cron("0 3 * * *".parse().unwrap()),
+            parse_str("0 3 * * *").unwrap()
+        );
+        assert!(parse_str("soon").is_err());
+    }
+
+    #[test]
+    fn rejects_zero_durations() {
+        let zero = Schedule::deserialize(IntoDeserializer::<value::Error>::into_deserializer(0u64));
+        assert!(zero.is_err());
+        assert!(parse_str("0").is_err());
+        assert!(parse_str("0s").is_err());
+        assert_eq!(Err(ScheduleError::ZeroDuration), "0".parse::<Schedule>());
+        assert_eq!(None, Schedule::Every(Duration::ZERO).next_after(Utc::now()));
+    }
+}